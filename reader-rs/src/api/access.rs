@@ -0,0 +1,274 @@
+//! 公网访问保护：访问令牌与按 IP 限流的中间件
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::error::ApiError;
+use crate::services::constant_time_eq;
+
+/// 不需要访问令牌的接口 (封面图片由 `<img>` 直接加载，无法携带令牌；接口文档不含用户数据)
+const PUBLIC_PATHS: [&str; 3] = ["/cover", "/openapi.json", "/swagger"];
+/// 会访问书源、开销较大的接口，按 IP 限流
+const RATE_LIMITED_PATHS: [&str; 4] = ["/search", "/searchBookMultiSSE", "/exploreBooks", "/testBookSource"];
+/// 记录的 IP 数超过该值时清理已回满的令牌桶
+const MAX_TRACKED_IPS: usize = 4096;
+
+/// 访问控制配置
+#[derive(Debug, Clone, Default)]
+pub struct AccessConfig {
+    /// 设置后除封面与接口文档外的接口都需要该令牌
+    pub token: Option<String>,
+    /// 限流接口每个 IP 每秒补充的请求数，0 表示不限流
+    pub rate_limit_rps: f64,
+    /// 令牌桶容量 (允许的突发请求数)
+    pub rate_limit_burst: u32,
+}
+
+impl AccessConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let rate_limit_rps = var("READER_RATE_LIMIT_RPS")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|rps| rps.is_finite() && *rps > 0.0)
+            .unwrap_or(0.0);
+        let rate_limit_burst = var("READER_RATE_LIMIT_BURST")
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|burst| *burst > 0)
+            .unwrap_or_else(|| (rate_limit_rps.ceil() as u32).max(5));
+        Self {
+            token: var("READER_ACCESS_TOKEN")
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            rate_limit_rps,
+            rate_limit_burst,
+        }
+    }
+
+    /// 限流器，未配置 RPS 时为 None
+    pub fn rate_limiter(&self) -> Option<Arc<RateLimiter>> {
+        (self.rate_limit_rps > 0.0).then(|| Arc::new(RateLimiter::new(self.rate_limit_rps, self.rate_limit_burst)))
+    }
+}
+
+/// 校验 `Authorization: Bearer` 头或 `accessToken` 参数中的访问令牌，不匹配时返回 401
+///
+/// 多用户模式下 Bearer 头用于登录令牌，此时通过 `accessToken` 参数传递访问令牌。
+pub async fn require_access_token(
+    State(token): State<Arc<str>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if PUBLIC_PATHS.contains(&req.uri().path()) {
+        return Ok(next.run(req).await);
+    }
+    let provided: Vec<String> = [bearer_token(req.headers()), query_token(req.uri().query())]
+        .into_iter()
+        .flatten()
+        .collect();
+    if provided.is_empty() {
+        return Err(ApiError::Unauthorized("Access token required".to_string()));
+    }
+    if !provided
+        .iter()
+        .any(|p| constant_time_eq(p.as_bytes(), token.as_bytes()))
+    {
+        return Err(ApiError::Unauthorized("Invalid access token".to_string()));
+    }
+    Ok(next.run(req).await)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+fn query_token(query: Option<&str>) -> Option<String> {
+    query?.split('&').find_map(|pair| {
+        let value = pair.strip_prefix("accessToken=")?;
+        urlencoding::decode(&value.replace('+', " "))
+            .ok()
+            .map(|v| v.into_owned())
+    })
+}
+
+/// 限流接口超出令牌桶时返回 429 与 Retry-After
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if RATE_LIMITED_PATHS.contains(&req.uri().path()) {
+        // 未使用 `into_make_service_with_connect_info` 时所有请求共用一个令牌桶
+        let ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |info| info.0.ip());
+        if let Err(wait) = limiter.acquire(ip, Instant::now()) {
+            return Err(ApiError::RateLimited {
+                retry_after: wait.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+/// 按 IP 的内存令牌桶限流器
+#[derive(Debug)]
+pub struct RateLimiter {
+    rps: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rps: f64, burst: u32) -> Self {
+        Self {
+            rps,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 取一个令牌；桶已空时返回需要等待的时间
+    pub fn acquire(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_IPS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, bucket| self.refill(*bucket, now).tokens < self.burst);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        *bucket = self.refill(*bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rps))
+        }
+    }
+
+    fn refill(&self, bucket: Bucket, now: Instant) -> Bucket {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        Bucket {
+            tokens: (bucket.tokens + elapsed * self.rps).min(self.burst),
+            updated: now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/cover", get(|| async { "cover" }))
+            .route("/getBookshelf", get(|| async { "shelf" }))
+            .route("/search", get(|| async { "search" }))
+    }
+
+    async fn call(app: &Router, uri: &str, auth: Option<&str>, ip: [u8; 4]) -> Response {
+        let mut req = Request::builder().uri(uri);
+        if let Some(auth) = auth {
+            req = req.header(header::AUTHORIZATION, auth);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::from((ip, 1234))));
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_access_token_required_except_cover() {
+        let token: Arc<str> = Arc::from("s3cret");
+        let app = app().layer(middleware::from_fn_with_state(token, require_access_token));
+        let ip = [127, 0, 0, 1];
+
+        assert_eq!(call(&app, "/cover?path=a.jpg", None, ip).await.status(), StatusCode::OK);
+        assert_eq!(
+            call(&app, "/getBookshelf", None, ip).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let resp = call(&app, "/getBookshelf", Some("Bearer wrong"), ip).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            call(&app, "/getBookshelf", Some("Bearer s3cret"), ip).await.status(),
+            StatusCode::OK
+        );
+        let resp = call(&app, "/getBookshelf?refresh=0&accessToken=s3cret", None, ip).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_after_burst() {
+        let limiter = Arc::new(RateLimiter::new(0.5, 2));
+        let app = app().layer(middleware::from_fn_with_state(limiter, rate_limit));
+        let ip = [10, 0, 0, 1];
+
+        for _ in 0..2 {
+            assert_eq!(call(&app, "/search?key=a", None, ip).await.status(), StatusCode::OK);
+        }
+        let resp = call(&app, "/search?key=a", None, ip).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "2");
+
+        // 其他 IP 与不限流的接口不受影响
+        assert_eq!(
+            call(&app, "/search?key=a", None, [10, 0, 0, 2]).await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(call(&app, "/getBookshelf", None, ip).await.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_bucket_refills() {
+        let limiter = RateLimiter::new(2.0, 1);
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+        assert!(limiter.acquire(ip, now).is_ok());
+        assert_eq!(limiter.acquire(ip, now), Err(Duration::from_millis(500)));
+        assert!(limiter.acquire(ip, now + Duration::from_millis(500)).is_ok());
+    }
+
+    #[test]
+    fn test_access_config_from_vars() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        let config = AccessConfig::from_vars(vars(&[]));
+        assert!(config.token.is_none());
+        assert!(config.rate_limiter().is_none());
+
+        let config = AccessConfig::from_vars(vars(&[
+            ("READER_ACCESS_TOKEN", " abc "),
+            ("READER_RATE_LIMIT_RPS", "0.5"),
+        ]));
+        assert_eq!(config.token.as_deref(), Some("abc"));
+        assert_eq!(config.rate_limit_burst, 5);
+        assert!(config.rate_limiter().is_some());
+    }
+}
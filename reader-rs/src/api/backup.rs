@@ -0,0 +1,55 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::header,
+    response::{Json, Response},
+};
+use chrono::Local;
+use std::sync::Arc;
+
+use crate::models::ApiResponse;
+use crate::services::{AppState, DataImportSummary, WebdavConfig};
+use super::error::{ApiError, ApiResult};
+
+/// POST /backupToWebdav - 备份数据到 WebDAV，返回备份文件名
+pub async fn backup_to_webdav(
+    State(state): State<Arc<AppState>>,
+    Json(config): Json<WebdavConfig>,
+) -> ApiResult<String> {
+    let file_name = state.backup_service.backup_to_webdav(&config).await?;
+    Ok(Json(ApiResponse::success(file_name)))
+}
+
+/// POST /restoreFromWebdav - 从 WebDAV 恢复数据，返回恢复的备份文件名
+pub async fn restore_from_webdav(
+    State(state): State<Arc<AppState>>,
+    Json(config): Json<WebdavConfig>,
+) -> ApiResult<String> {
+    let file_name = state.backup_service.restore_from_webdav(&config).await?;
+    state.reload_data().await?;
+    Ok(Json(ApiResponse::success(file_name)))
+}
+
+/// 数据导入上传大小上限
+pub const IMPORT_DATA_MAX_BYTES: usize = 256 * 1024 * 1024;
+
+/// GET /exportData - 导出全部数据为 zip (含版本与时间清单)
+pub async fn export_data(State(state): State<Arc<AppState>>) -> Result<Response, ApiError> {
+    let archive = state.backup_service.create_archive().await?;
+    let file_name = Local::now().format("reader-%Y%m%d-%H%M%S.zip").to_string();
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name))
+        .body(Body::from(archive))
+        .unwrap())
+}
+
+/// POST /importData - 导入 exportData 导出的 zip，包内没有的数据保持不变
+pub async fn import_data(
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> ApiResult<DataImportSummary> {
+    let summary = state.backup_service.import_archive(&body).await?;
+    state.reload_data().await?;
+    Ok(Json(ApiResponse::success(summary)))
+}
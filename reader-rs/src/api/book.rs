@@ -1,93 +1,574 @@
 use axum::{
-    extract::{Query, State},
-    response::{Json, sse::{Event, Sse}},
+    body::Body,
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Json, Response, sse::{Event, Sse}},
 };
 use futures::stream::Stream;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::convert::Infallible;
 
-use crate::models::{Book, Chapter, SearchResult, ApiResponse};
-use crate::services::AppState;
+use crate::models::{Book, BookMetadata, BookProgress, SearchResult, ApiResponse, BOOK_CUSTOM_VARIABLE_KEY};
+use crate::services::{
+    AppState, CacheBookProgress, ChapterDiff, MergedSearch, PrefetchStatus, RefreshSummary, SearchFilter, SearchOrigin, ServiceError, ShelfQuery, ShelfSort,
+};
+use crate::engine::book_source::{AudioContent, ImageContent};
+use super::error::{ApiError, ApiResult};
 use crate::engine::search_engine::SearchResult as LocalSearchResult;
+use crate::storage::content_cache::ChapterVersion;
+use crate::storage::cover_cache::is_local_cover;
+use crate::storage::ReclaimedCache;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct BookshelfQuery {
     pub refresh: Option<i32>,
+    /// 分组位掩码或虚拟分组 (-1 全部, -2 本地, -3 未分组, -4 更新失败)
+    pub group: Option<i64>,
+    pub sort: Option<ShelfSort>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RefreshBookshelfQuery {
+    /// 只检查该书，缺省检查整个书架
+    pub url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ChapterListQuery {
     pub url: String,
     pub origin: Option<String>,
     pub refresh: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct BookContentQuery {
     pub url: String,
     pub index: i32,
+    pub refresh: Option<i32>,
+    /// 本次抓取最多跟随的正文分页数，缺省 20
+    #[serde(rename = "maxPages")]
+    pub max_pages: Option<usize>,
+    /// 1 按书籍设置 (或默认选项) 排版正文，0 不排版；缺省时使用书籍保存的排版选项
+    pub format: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChapterVersionsQuery {
+    pub url: String,
+    pub index: i32,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChapterDiffQuery {
+    pub url: String,
+    pub index: i32,
+    /// 旧版本号，缺省为 to 的上一个版本
+    pub from: Option<u32>,
+    /// 新版本号，缺省为当前版本
+    pub to: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PrefetchStatusQuery {
+    pub url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CacheBookRequest {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+    /// 起始章节序号，缺省为第一章
+    pub start: Option<i32>,
+    /// 结束章节序号 (含)，缺省为最后一章
+    pub end: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct CacheJobQuery {
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChapterAudioQuery {
+    pub url: String,
+    pub index: i32,
+    /// 朗读者，缺省使用 TTS_VOICE
+    pub voice: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AudioUrlQuery {
+    pub url: String,
+    pub index: i32,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AudioProxyQuery {
+    /// 音频地址 (getAudioUrl 返回的 url)
+    pub url: String,
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
 pub struct SearchQuery {
     pub key: String,
+    /// 1 表示书名须与关键字一致
+    pub exact: Option<i32>,
+    pub author: Option<String>,
+    pub min_words: Option<u64>,
+    pub max_words: Option<u64>,
+    pub kind: Option<String>,
+    /// 跳过已标记为失效的书源
+    pub skip_broken: Option<bool>,
+}
+
+impl SearchQuery {
+    fn filter(&self) -> SearchFilter {
+        SearchFilter {
+            exact: self.exact.unwrap_or(0) == 1,
+            author: self.author.clone(),
+            min_words: self.min_words,
+            max_words: self.max_words,
+            kind: self.kind.clone(),
+            skip_broken: self.skip_broken.unwrap_or(false),
+        }
+    }
+}
+
+/// 合并搜索默认并发数
+const DEFAULT_MERGED_CONCURRENT: usize = 24;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMergedQuery {
+    pub key: String,
+    pub concurrent_count: Option<usize>,
+    pub exact: Option<i32>,
+    pub author: Option<String>,
+    pub min_words: Option<u64>,
+    pub max_words: Option<u64>,
+    pub kind: Option<String>,
+    /// 跳过已标记为失效的书源
+    pub skip_broken: Option<bool>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOriginsQuery {
+    pub search_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct BookInfoQuery {
     pub url: String,
     pub origin: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImageProxyQuery {
+    /// 图片地址 (getBookContent 返回的漫画图片已改写为经由代理)
+    pub src: String,
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CoverQuery {
     pub path: String,
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ProgressRequest {
+    #[serde(alias = "bookUrl")]
     pub url: String,
+    #[serde(alias = "durChapterIndex")]
     pub index: i32,
+    #[serde(rename = "durChapterPos")]
+    pub pos: Option<i32>,
+    #[serde(rename = "durChapterTitle")]
+    pub title: Option<String>,
+    #[serde(rename = "durChapterTime")]
+    pub time: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProgressQuery {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BookVariableQuery {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SaveBookVariableRequest {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+    /// 缺省为 custom (规则中 `book.getVariable()` / `book.getCustomVariable()`)
+    pub key: Option<String>,
+    /// 为空时删除该变量
+    #[serde(default)]
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SaveBookMetadataRequest {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+    /// 未指定的项保持不变，空字符串清除该项
+    #[serde(flatten)]
+    pub metadata: BookMetadata,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ClearBookMetadataRequest {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetBookCanUpdateRequest {
+    #[serde(alias = "bookUrl")]
+    pub url: String,
+    #[serde(rename = "canUpdate")]
+    pub can_update: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DeleteBookRequest {
     pub url: String,
+    /// 同时删除正文、目录、封面等缓存，默认 true
+    #[serde(rename = "deleteCache")]
+    pub delete_cache: Option<bool>,
 }
 
 /// GET /getBookshelf - 获取书架列表
 pub async fn get_bookshelf(
     State(state): State<Arc<AppState>>,
     Query(query): Query<BookshelfQuery>,
-) -> Json<ApiResponse<Vec<Book>>> {
+) -> ApiResult<Vec<Book>> {
     let refresh = query.refresh.unwrap_or(0) == 1;
-    match state.book_service.get_bookshelf(refresh).await {
-        Ok(books) => Json(ApiResponse::success(books)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+    let hidden_groups = match query.group {
+        Some(_) => 0,
+        None => state.group_service.hidden_groups().await,
+    };
+    let shelf_query = ShelfQuery {
+        group: query.group,
+        sort: query.sort,
+        offset: query.offset.unwrap_or(0),
+        limit: query.limit,
+        hidden_groups,
+    };
+    let page = state.book_service.query_bookshelf(refresh, &shelf_query).await?;
+    let books = page.books.into_iter().map(Book::with_metadata).collect();
+    Ok(Json(ApiResponse::success(books).with_total(page.total)))
+}
+
+/// POST /refreshBookshelf - 立即检查书架更新
+pub async fn refresh_bookshelf(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RefreshBookshelfQuery>,
+) -> ApiResult<RefreshSummary> {
+    let summary = state.book_service.refresh_bookshelf(query.url.as_deref(), None).await?;
+    Ok(Json(ApiResponse::success(summary)))
+}
+
+/// POST /setBookCanUpdate - 设置书籍是否参与书架更新检查
+pub async fn set_book_can_update(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetBookCanUpdateRequest>,
+) -> ApiResult<Book> {
+    let book = state.book_service.set_can_update(&req.url, req.can_update).await?;
+    Ok(Json(ApiResponse::success(book)))
+}
+
+/// GET /updateFailures - 最近一次更新失败的书籍，按失败时间倒序
+pub async fn update_failures(State(state): State<Arc<AppState>>) -> ApiResult<Vec<Book>> {
+    let books = state.book_service.update_failures().await?;
+    Ok(Json(ApiResponse::success(books)))
 }
 
 /// GET /getChapterList - 获取章节列表
+///
+/// 响应带有按目录内容计算的 ETag，请求的 If-None-Match 与之相同时返回 304。
 pub async fn get_chapter_list(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ChapterListQuery>,
-) -> Json<ApiResponse<Vec<Chapter>>> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let refresh = query.refresh.unwrap_or(0) == 1;
-    match state.book_service.get_chapter_list(&query.url, query.origin.as_deref(), refresh).await {
-        Ok(chapters) => Json(ApiResponse::success(chapters)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    let chapters = state.book_service.get_chapter_list(&query.url, query.origin.as_deref(), refresh).await?;
+    let body = serde_json::to_vec(&ApiResponse::success(chapters)).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let etag = format!("\"{:x}\"", md5::compute(&body));
+
+    // 目录可能随时更新，要求客户端每次都用 ETag 校验
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "no-cache");
+    if if_none_match(&headers, &etag) {
+        return Ok(builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
     }
+    Ok(builder
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap())
+}
+
+/// If-None-Match 是否包含该 ETag (弱比较)
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// getBookContent 的返回数据：正文文本，或漫画书源的 `{type: "image", images}`
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum BookContent {
+    Text(String),
+    Images(ImageContent),
 }
 
 /// GET /getBookContent - 获取章节内容
+///
+/// 漫画书源返回 `{type: "image", images}`，其余返回正文文本。
 pub async fn get_book_content(
     State(state): State<Arc<AppState>>,
     Query(query): Query<BookContentQuery>,
-) -> Json<ApiResponse<String>> {
-    match state.book_service.get_book_content(&query.url, query.index).await {
-        Ok(content) => Json(ApiResponse::success(content)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<BookContent> {
+    let refresh = query.refresh.unwrap_or(0) == 1;
+    let format = query.format.map(|f| f == 1);
+    let content = state
+        .book_service
+        .get_book_content(
+            &query.url,
+            query.index,
+            refresh,
+            query.max_pages,
+            format,
+            state.budgets.content_deadline(),
+        )
+        .await?;
+    state.prefetcher.schedule(&query.url, query.index);
+    let content = match ImageContent::from_content(&content) {
+        Some(images) => BookContent::Images(images),
+        None => BookContent::Text(content),
+    };
+    Ok(Json(ApiResponse::success(content)))
+}
+
+/// GET /getBookContentSSE - 获取章节内容 (SSE)，分页抓取时逐页推送
+pub async fn get_book_content_sse(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BookContentQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let refresh = query.refresh.unwrap_or(0) == 1;
+    let format = query.format.map(|f| f == 1);
+    state.prefetcher.schedule(&query.url, query.index);
+    super::sse(
+        state
+            .book_service
+            .get_book_content_sse(query.url, query.index, refresh, query.max_pages, format),
+    )
+}
+
+/// GET /getChapterVersions - 章节缓存的各个版本 (刷新后内容变化时保留的旧版本及当前版本)
+pub async fn get_chapter_versions(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChapterVersionsQuery>,
+) -> ApiResult<Vec<ChapterVersion>> {
+    let versions = state.book_service.get_chapter_versions(&query.url, query.index).await?;
+    Ok(Json(ApiResponse::success(versions)))
+}
+
+/// GET /getChapterDiff - 章节两个版本间按行比较的 unified diff
+pub async fn get_chapter_diff(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChapterDiffQuery>,
+) -> ApiResult<ChapterDiff> {
+    let diff = state
+        .book_service
+        .get_chapter_diff(&query.url, query.index, query.from, query.to)
+        .await?;
+    Ok(Json(ApiResponse::success(diff)))
+}
+
+/// GET /prefetchStatus - 最近阅读章节之后 N 章的预取情况
+pub async fn prefetch_status(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PrefetchStatusQuery>,
+) -> ApiResult<PrefetchStatus> {
+    Ok(Json(ApiResponse::success(state.prefetcher.status(&query.url).await)))
+}
+
+/// POST /cacheBook - 在后台缓存整本书或指定范围的章节，已缓存的章节跳过，返回任务进度 (含 jobId)
+pub async fn cache_book(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CacheBookRequest>,
+) -> ApiResult<CacheBookProgress> {
+    let progress = state.book_cacher.start(&req.url, req.start, req.end).await?;
+    Ok(Json(ApiResponse::success(progress)))
+}
+
+/// GET /cacheBookProgress - 缓存任务的进度与最近的错误
+pub async fn cache_book_progress(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CacheJobQuery>,
+) -> ApiResult<CacheBookProgress> {
+    Ok(Json(ApiResponse::success(state.book_cacher.progress(&query.job_id)?)))
+}
+
+/// POST /cancelCacheBook - 取消缓存任务
+pub async fn cancel_cache_book(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CacheJobQuery>,
+) -> ApiResult<CacheBookProgress> {
+    Ok(Json(ApiResponse::success(state.book_cacher.cancel(&req.job_id)?)))
+}
+
+/// GET /getChapterAudio - 获取章节朗读音频，支持 Range 请求以便播放器拖动进度
+pub async fn get_chapter_audio(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChapterAudioQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let audio = state
+        .tts_service
+        .chapter_audio(&query.url, query.index, query.voice.as_deref(), || {
+            let deadline = state.budgets.content_deadline();
+            state.book_service.get_book_content(&query.url, query.index, false, None, None, deadline)
+        })
+        .await?;
+    Ok(ranged_response(&audio.content_type, audio.data, headers.get(header::RANGE)))
+}
+
+/// GET /getAudioUrl - 获取音频书源章节的播放地址 ({type: "audio", url, headers})
+pub async fn get_audio_url(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AudioUrlQuery>,
+) -> ApiResult<AudioContent> {
+    let audio = state.book_service.get_audio_url(&query.url, query.index).await?;
+    Ok(Json(ApiResponse::success(audio)))
+}
+
+/// GET /audioProxy - 音频代理，带上书源的请求头与 Cookie 并转发 Range 请求，供浏览器播放
+pub async fn audio_proxy(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AudioProxyQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    let mut audio = state
+        .book_service
+        .open_audio_stream(&query.url, &query.book_source_url, range)
+        .await?;
+    let mut builder = Response::builder().status(audio.status);
+    for (name, value) in &audio.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let body = futures::stream::poll_fn(move |cx| audio.body.poll_recv(cx));
+    Ok(builder.body(Body::from_stream(body)).unwrap())
+}
+
+/// GET /imageProxy - 漫画图片代理，带上书源的请求头、Cookie 与 Referer，图片缓存在磁盘上
+pub async fn image_proxy(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImageProxyQuery>,
+) -> Result<Response, ApiError> {
+    if !query.src.starts_with("http://") && !query.src.starts_with("https://") {
+        return Err(ApiError::BadRequest(format!("Unsupported image url: {}", query.src)));
+    }
+    let image = state
+        .book_service
+        .get_image(&query.src, query.book_source_url.as_deref())
+        .await?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, image.content_type)
+        .header(header::CACHE_CONTROL, "private, max-age=2592000, immutable")
+        .body(Body::from(image.data))
+        .unwrap())
+}
+
+/// 解析单个 `bytes=` 区间，返回闭区间 [start, end]
+///
+/// 无法解析或多区间时返回 None (按完整内容响应)，区间超出内容时返回 Some(None)。
+fn parse_range(range: &str, len: usize) -> Option<Option<(usize, usize)>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            if suffix == 0 {
+                return Some(None);
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        (start, "") => (start.parse().ok()?, len.saturating_sub(1)),
+        (start, end) => (start.parse().ok()?, end.parse::<usize>().ok()?.min(len.saturating_sub(1))),
+    };
+    Some((start < len && start <= end).then_some((start, end)))
+}
+
+/// 按 Range 请求头返回完整内容 (200) 或其中一段 (206)
+fn ranged_response(content_type: &str, data: Vec<u8>, range: Option<&HeaderValue>) -> Response {
+    let len = data.len();
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes");
+    let range = range.and_then(|r| r.to_str().ok()).and_then(|r| parse_range(r, len));
+    match range {
+        None => builder.body(Body::from(data)).unwrap(),
+        Some(Some((start, end))) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+            .body(Body::from(data[start..=end].to_vec()))
+            .unwrap(),
+        Some(None) => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+            .body(Body::empty())
+            .unwrap(),
     }
 }
 
@@ -95,22 +576,19 @@ pub async fn get_book_content(
 pub async fn get_book_info(
     State(state): State<Arc<AppState>>,
     Query(query): Query<BookInfoQuery>,
-) -> Json<ApiResponse<Book>> {
-    match state.book_service.get_book_info(&query.url, query.origin.as_deref()).await {
-        Ok(book) => Json(ApiResponse::success(book)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<Book> {
+    let book = state.book_service.get_book_info(&query.url, query.origin.as_deref()).await?;
+    Ok(Json(ApiResponse::success(book.with_metadata())))
 }
 
 /// GET /search - 搜索书籍
 pub async fn search(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchQuery>,
-) -> Json<ApiResponse<Vec<SearchResult>>> {
-    match state.book_service.search(&query.key).await {
-        Ok(results) => Json(ApiResponse::success(results)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<Vec<SearchResult>> {
+    let deadline = state.budgets.search_deadline();
+    let results = state.book_service.search(&query.key, &query.filter(), deadline).await?;
+    Ok(Json(ApiResponse::success(results)))
 }
 
 
@@ -118,11 +596,47 @@ pub async fn search(
 pub async fn local_search(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchQuery>,
-) -> Json<ApiResponse<Vec<LocalSearchResult>>> {
-    match state.search_engine.search(&query.key, 50) {
-        Ok(results) => Json(ApiResponse::success(results)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<Vec<LocalSearchResult>> {
+    let results = state.search_engine.search(&query.key, 50)?;
+    Ok(Json(ApiResponse::success(results)))
+}
+
+/// POST /rebuildSearchIndex - 清空并按书架重建本地全文索引，返回索引的书籍数
+pub async fn rebuild_search_index(State(state): State<Arc<AppState>>) -> ApiResult<usize> {
+    let indexed = state.book_service.rebuild_search_index().await?;
+    Ok(Json(ApiResponse::success(indexed)))
+}
+
+/// GET /searchMerged - 多书源搜索，按书名与作者合并排序
+pub async fn search_merged(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchMergedQuery>,
+) -> ApiResult<MergedSearch> {
+    if query.key.trim().is_empty() {
+        return Err(ApiError::BadRequest("key is required".to_string()));
     }
+    let concurrent = query.concurrent_count.unwrap_or(DEFAULT_MERGED_CONCURRENT);
+    let filter = SearchFilter {
+        exact: query.exact.unwrap_or(0) == 1,
+        author: query.author,
+        min_words: query.min_words,
+        max_words: query.max_words,
+        kind: query.kind,
+        skip_broken: query.skip_broken.unwrap_or(false),
+    };
+    let merged = state.book_service.search_merged(&query.key, concurrent, &filter).await?;
+    Ok(Json(ApiResponse::success(merged)))
+}
+
+/// GET /searchMergedOrigins - 获取合并搜索结果中某本书的全部来源
+pub async fn search_merged_origins(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchOriginsQuery>,
+) -> ApiResult<Vec<SearchOrigin>> {
+    let origins = state
+        .book_service
+        .search_merged_origins(&query.search_id, &query.name, &query.author)?;
+    Ok(Json(ApiResponse::success(origins)))
 }
 
 /// GET /searchBookMultiSSE - 多书源搜索 (SSE)
@@ -130,85 +644,2217 @@ pub async fn search_book_multi_sse(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchQuery>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let stream = state.book_service.search_multi_sse(query.key, false, None, 50);
-    Sse::new(stream)
+    let filter = query.filter();
+    let stream = state.book_service.search_multi_sse(query.key, 50, filter);
+    super::sse(stream)
 }
 
 /// POST /saveBook - 保存书籍到书架
 pub async fn save_book(
     State(state): State<Arc<AppState>>,
     Json(book): Json<Book>,
-) -> Json<ApiResponse<Book>> {
-    match state.book_service.save_book(book).await {
-        Ok(saved) => Json(ApiResponse::success(saved)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<Book> {
+    let saved = state.book_service.save_book(book).await?;
+    Ok(Json(ApiResponse::success(saved)))
 }
 
-/// POST /deleteBook - 删除书籍
+/// POST /deleteBook - 删除书籍，返回回收的缓存文件数与字节数
 pub async fn delete_book(
     State(state): State<Arc<AppState>>,
     Json(req): Json<DeleteBookRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.book_service.delete_book(&req.url).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<ReclaimedCache> {
+    state.prefetcher.cancel(&req.url);
+    state.book_cacher.cancel_book(&req.url);
+    let reclaimed = state
+        .book_service
+        .delete_book(&req.url, req.delete_cache.unwrap_or(true))
+        .await?;
+    Ok(Json(ApiResponse::success(reclaimed)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ClearBookCacheRequest {
+    #[serde(rename = "bookUrl")]
+    pub book_url: String,
+}
+
+/// POST /clearBookCache - 清除单本书的正文缓存
+pub async fn clear_book_cache(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClearBookCacheRequest>,
+) -> ApiResult<()> {
+    state.book_service.clear_book_cache(&req.book_url).await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// GET /getBookProgress - 获取阅读进度
+pub async fn get_book_progress(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ProgressQuery>,
+) -> ApiResult<BookProgress> {
+    let progress = state
+        .book_service
+        .get_progress(&query.url)
+        .await?
+        .ok_or_else(|| ServiceError::not_found("Book", &query.url))?;
+    Ok(Json(ApiResponse::success(progress)))
 }
 
-/// POST /saveBookProgress - 保存阅读进度
+/// POST /saveBookProgress - 保存阅读进度 (返回最终生效的进度)
 pub async fn save_book_progress(
     State(state): State<Arc<AppState>>,
     Json(req): Json<ProgressRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.book_service.save_progress(&req.url, req.index).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<BookProgress> {
+    let progress = BookProgress {
+        dur_chapter_index: req.index,
+        dur_chapter_pos: req.pos.unwrap_or(0),
+        dur_chapter_title: req.title,
+        dur_chapter_time: req.time.unwrap_or(0),
+    };
+    let progress = state
+        .book_service
+        .save_progress(&req.url, progress)
+        .await?
+        .ok_or_else(|| ServiceError::not_found("Book", &req.url))?;
+    Ok(Json(ApiResponse::success(progress)))
+}
+
+/// GET /getBookVariable - 获取书籍变量
+pub async fn get_book_variable(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BookVariableQuery>,
+) -> ApiResult<HashMap<String, String>> {
+    let variable = state.book_service.get_book_variable(&query.url).await?;
+    Ok(Json(ApiResponse::success(variable)))
+}
+
+/// POST /saveBookVariable - 保存书籍变量，规则中通过 `book.getVariable(key)` 读取，换源后保留
+pub async fn save_book_variable(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SaveBookVariableRequest>,
+) -> ApiResult<HashMap<String, String>> {
+    let key = req.key.as_deref().unwrap_or(BOOK_CUSTOM_VARIABLE_KEY);
+    let variable = state.book_service.save_book_variable(&req.url, key, &req.value).await?;
+    Ok(Json(ApiResponse::success(variable)))
+}
+
+/// POST /saveBookMetadata - 手动设置书名、作者、简介与封面，换源与书籍更新后保留
+pub async fn save_book_metadata(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SaveBookMetadataRequest>,
+) -> ApiResult<Book> {
+    let book = state.book_service.save_book_metadata(&req.url, req.metadata).await?;
+    Ok(Json(ApiResponse::success(book)))
+}
+
+/// POST /clearBookMetadata - 清除手动设置的书籍信息，恢复书源提供的值
+pub async fn clear_book_metadata(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClearBookMetadataRequest>,
+) -> ApiResult<Book> {
+    let book = state.book_service.clear_book_metadata(&req.url).await?;
+    Ok(Json(ApiResponse::success(book)))
+}
+
+/// 上传封面请求体的大小上限 (图片本身不超过 5MB)
+pub const COVER_UPLOAD_MAX_BYTES: usize = 6 * 1024 * 1024;
+
+/// POST /uploadBookCover - 上传封面图片 (multipart: url, file)，以本地封面地址设为书籍封面
+pub async fn upload_book_cover(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> ApiResult<Book> {
+    let mut book_url = None;
+    let mut image = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(e.body_text()))?
+    {
+        match field.name() {
+            Some("url") | Some("bookUrl") => {
+                let text = field.text().await.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+                book_url = Some(text);
+            }
+            Some("file") => {
+                let content_type = field.content_type().map(str::to_string);
+                let data = field.bytes().await.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+                image = Some((content_type, data.to_vec()));
+            }
+            _ => {}
+        }
     }
+
+    let book_url = book_url.ok_or_else(|| ApiError::BadRequest("Missing url field".to_string()))?;
+    let (content_type, data) = image.ok_or_else(|| ApiError::BadRequest("Missing file field".to_string()))?;
+    let book = state
+        .book_service
+        .upload_book_cover(&book_url, content_type.as_deref(), data)
+        .await?;
+    Ok(Json(ApiResponse::success(book)))
 }
 
+/// 封面获取失败时返回的占位图
+const COVER_PLACEHOLDER_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="120" height="160" viewBox="0 0 120 160"><rect width="120" height="160" fill="#e0e0e0"/></svg>"##;
+
 /// GET /cover - 封面图片代理
 pub async fn get_cover(
+    State(state): State<Arc<AppState>>,
     Query(query): Query<CoverQuery>,
-) -> impl axum::response::IntoResponse {
-    use axum::http::{header, StatusCode};
-    use axum::response::Response;
-    use axum::body::Body;
-    
-    // 如果是远程 URL，代理获取
-    if query.path.starts_with("http://") || query.path.starts_with("https://") {
-        match reqwest::get(&query.path).await {
-            Ok(resp) => {
-                let content_type = resp.headers()
-                    .get(header::CONTENT_TYPE)
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("image/jpeg")
-                    .to_string();
-                
-                match resp.bytes().await {
-                    Ok(bytes) => {
-                        Response::builder()
-                            .header(header::CONTENT_TYPE, content_type)
-                            .header(header::CACHE_CONTROL, "public, max-age=86400")
-                            .body(Body::from(bytes.to_vec()))
-                            .unwrap()
-                    }
-                    Err(_) => Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .body(Body::empty())
-                        .unwrap()
+) -> Result<Response, ApiError> {
+    let remote = query.path.starts_with("http://") || query.path.starts_with("https://");
+    if !remote && !is_local_cover(&query.path) {
+        // 本地文件
+        return Err(ApiError::NotFound(format!("Cover not found: {}", query.path)));
+    }
+
+    match state
+        .book_service
+        .get_cover(&query.path, query.book_source_url.as_deref())
+        .await
+    {
+        Ok(cover) => Ok(Response::builder()
+            .header(header::CONTENT_TYPE, cover.content_type)
+            .header(header::CACHE_CONTROL, "public, max-age=2592000, immutable")
+            .body(Body::from(cover.data))
+            .unwrap()),
+        Err(e) if e.is::<ServiceError>() => Err(e.into()),
+        Err(e) => {
+            tracing::warn!("Cover proxy failed for {}: {:#}", query.path, e);
+            Ok(Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .header(header::CONTENT_TYPE, "image/svg+xml")
+                .header(header::CACHE_CONTROL, "no-store")
+                .body(Body::from(COVER_PLACEHOLDER_SVG))
+                .unwrap())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportBookQuery {
+    pub url: String,
+    pub format: Option<String>,
+}
+
+/// GET /exportBook - 导出书籍 (EPUB)
+pub async fn export_book(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExportBookQuery>,
+) -> Result<Response, ApiError> {
+    let format = query.format.as_deref().unwrap_or("epub");
+    if !format.eq_ignore_ascii_case("epub") {
+        return Err(ApiError::BadRequest(format!("Unsupported export format: {}", format)));
+    }
+
+    let (filename, data) = state.book_service.export_epub(&query.url).await?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/epub+zip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"book.epub\"; filename*=UTF-8''{}",
+                urlencoding::encode(&filename)
+            ),
+        )
+        .body(Body::from(data))
+        .unwrap())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportBookshelfQuery {
+    /// json (默认) 或 csv
+    pub format: Option<String>,
+}
+
+/// GET /exportBookshelf - 导出书架与阅读进度报表 (JSON / CSV)
+pub async fn export_bookshelf(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExportBookshelfQuery>,
+) -> Result<Response, ApiError> {
+    let format = query.format.as_deref().unwrap_or("json").to_ascii_lowercase();
+    let report = state.group_service.shelf_report().await?;
+    let (content_type, data) = match format.as_str() {
+        "json" => ("application/json; charset=utf-8", report.to_json()?),
+        "csv" => ("text/csv; charset=utf-8", report.to_csv()),
+        _ => return Err(ApiError::BadRequest(format!("Unsupported export format: {}", format))),
+    };
+
+    let date = chrono::Local::now().format("%Y%m%d");
+    let disposition = format!("attachment; filename=\"bookshelf-{}.{}\"", date, format);
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .body(Body::from(data))
+        .unwrap())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ImportLocalBookRequest {
+    pub path: String,
+    #[serde(rename = "tocRules", default)]
+    pub toc_rules: Vec<String>,
+}
+
+/// 本地书籍上传大小上限
+pub const LOCAL_BOOK_MAX_BYTES: usize = 512 * 1024 * 1024;
+
+/// POST /importLocalBook - 导入本地书籍 (TXT / EPUB)
+///
+/// 支持 JSON `{path, tocRules}` (path 为数据目录下的相对路径，可先通过 /file/save 上传)，
+/// 或 multipart 上传 (字段 file，可选字段 tocRules 为 JSON 数组)。
+pub async fn import_local_book(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> ApiResult<Book> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("multipart/form-data"));
+
+    let (path, toc_rules) = if is_multipart {
+        let multipart = Multipart::from_request(request, &state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        save_uploaded_book(&state, multipart).await?
+    } else {
+        let Json(req) = Json::<ImportLocalBookRequest>::from_request(request, &state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        (state.book_service.local_file_path(&req.path)?, req.toc_rules)
+    };
+
+    let supported = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("epub"));
+    if !supported {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported book file: {}",
+            path.display()
+        )));
+    }
+
+    let book = state.book_service.import_local_book(&path, &toc_rules).await?;
+    Ok(Json(ApiResponse::success(book)))
+}
+
+/// GET /assets/{bookId}/{path} - 本地书籍资源 (EPUB 图片)
+pub async fn get_asset(
+    State(state): State<Arc<AppState>>,
+    Path((book_id, path)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let (content_type, data) = state.book_service.get_local_asset(&book_id, &path).await?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "public, max-age=2592000")
+        .body(Body::from(data))
+        .unwrap())
+}
+
+/// 将上传的书籍文件分块写入数据目录 `local/` 下
+async fn save_uploaded_book(
+    state: &AppState,
+    mut multipart: Multipart,
+) -> Result<(std::path::PathBuf, Vec<String>), ApiError> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut path = None;
+    let mut toc_rules = Vec::new();
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(e.body_text()))?
+    {
+        match field.name() {
+            Some("file") => {
+                let file_name = field
+                    .file_name()
+                    .and_then(|n| std::path::Path::new(n).file_name())
+                    .map(|n| n.to_string_lossy().to_string())
+                    .ok_or_else(|| ApiError::BadRequest("Missing file name".to_string()))?;
+                let dest = state
+                    .book_service
+                    .local_file_path(&format!("local/{}", file_name))?;
+                if let Some(parent) = dest.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err(anyhow::Error::from)?;
+                }
+
+                let mut file = tokio::fs::File::create(&dest).await.map_err(anyhow::Error::from)?;
+                while let Some(chunk) = field
+                    .chunk()
+                    .await
+                    .map_err(|e| ApiError::BadRequest(e.body_text()))?
+                {
+                    file.write_all(&chunk).await.map_err(anyhow::Error::from)?;
                 }
+                file.flush().await.map_err(anyhow::Error::from)?;
+                path = Some(dest);
             }
-            Err(_) => Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(Body::empty())
+            Some("tocRules") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+                if !text.trim().is_empty() {
+                    toc_rules = serde_json::from_str(&text).map_err(anyhow::Error::from)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let path = path.ok_or_else(|| ApiError::BadRequest("Missing file field".to_string()))?;
+    Ok((path, toc_rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_server::{header, headers, request_path, spawn_concurrent_server, spawn_server, Reply};
+    use axum::response::IntoResponse;
+    use std::sync::mpsc;
+
+    fn create_test_state(name: &str) -> Arc<AppState> {
+        let dir = format!("/tmp/reader_tests_api_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        Arc::new(AppState::with_storage_dir(&dir))
+    }
+
+    async fn into_json(resp: impl IntoResponse) -> (StatusCode, serde_json::Value) {
+        let resp = resp.into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_progress_last_writer_wins() {
+        let state = create_test_state("progress");
+        let url = "https://example.com/book/1".to_string();
+        state
+            .book_service
+            .save_book(Book {
+                book_url: url.clone(),
+                name: "书".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let save = |index: i32, time: i64| ProgressRequest {
+            url: url.clone(),
+            index,
+            pos: Some(0),
+            title: None,
+            time: Some(time),
+        };
+
+        let (status, body) =
+            into_json(save_book_progress(State(state.clone()), Json(save(5, 2000))).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["durChapterIndex"], 5);
+
+        // 较旧的写入返回已存储的进度
+        let (_, body) =
+            into_json(save_book_progress(State(state.clone()), Json(save(2, 1000))).await).await;
+        assert_eq!(body["data"]["durChapterIndex"], 5);
+
+        let (status, body) = into_json(
+            get_book_progress(State(state.clone()), Query(ProgressQuery { url: url.clone() })).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["durChapterIndex"], 5);
+        assert_eq!(body["data"]["durChapterTime"], 2000);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_progress_saves_persist() {
+        use crate::storage::bookshelf::BookshelfStore;
+
+        let state = create_test_state("progress_concurrent");
+        let urls = ["https://example.com/book/a", "https://example.com/book/b"];
+        for url in urls {
+            let book = Book {
+                book_url: url.to_string(),
+                name: url.to_string(),
+                ..Default::default()
+            };
+            state.book_service.save_book(book).await.unwrap();
+        }
+
+        let saves = (0..20).map(|i| {
+            let state = state.clone();
+            let url = urls[i % 2].to_string();
+            tokio::spawn(async move {
+                let req = ProgressRequest {
+                    url,
+                    index: i as i32,
+                    pos: Some(0),
+                    title: None,
+                    time: Some(1000 + i as i64),
+                };
+                let _ = save_book_progress(State(state), Json(req)).await.unwrap();
+            })
+        });
+        for save in futures::future::join_all(saves).await {
+            save.unwrap();
+        }
+        state.storage.flush().await.unwrap();
+
+        // 磁盘上两本书的最新进度都在
+        let books = BookshelfStore::new(state.storage.clone()).load().await.unwrap();
+        let progress: Vec<_> = books.iter().map(|b| (b.book_url.as_str(), b.dur_chapter_index)).collect();
+        assert_eq!(progress, vec![(urls[0], Some(18)), (urls[1], Some(19))]);
+    }
+
+    #[tokio::test]
+    async fn test_progress_book_not_on_shelf() {
+        let state = create_test_state("progress_missing");
+
+        let (status, body) = into_json(
+            get_book_progress(
+                State(state),
+                Query(ProgressQuery {
+                    url: "https://example.com/missing".to_string(),
+                }),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["isSuccess"], false);
+        assert_eq!(body["errorCode"], "NOT_FOUND");
+        assert!(body["errorMsg"].as_str().unwrap().contains("Book not found"));
+    }
+
+    /// 记录到的请求：(路径, Referer, X-Token)
+    type SeenRequest = (String, Option<String>, Option<String>);
+
+    /// 防盗链图床：没有 Referer 时返回 403
+    fn spawn_cover_server() -> (String, mpsc::Receiver<SeenRequest>) {
+        let (tx, rx) = mpsc::channel();
+        let base = spawn_server(move |head, _| {
+            let path = request_path(head).to_string();
+            let referer = header(head, "referer").map(str::to_string);
+            let token = header(head, "x-token").map(str::to_string);
+            let (status, content_type, body): (u16, &str, &[u8]) = if referer.is_none() {
+                (403, "text/plain", b"forbidden")
+            } else if path.ends_with(".png") {
+                (200, "image/png", b"\x89PNG fake image")
+            } else {
+                (200, "text/html", b"<html>not an image</html>")
+            };
+            let _ = tx.send((path, referer, token));
+            (status, vec![("Content-Type", content_type.to_string())], body)
+        });
+        (base, rx)
+    }
+
+    async fn fetch_cover(state: &Arc<AppState>, path: String, source: Option<String>) -> Response {
+        get_cover(
+            State(state.clone()),
+            Query(CoverQuery {
+                path,
+                book_source_url: source,
+            }),
+        )
+        .await
+        .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_cover_proxy_cache_hit() {
+        let state = create_test_state("cover_cache");
+        let (base, rx) = spawn_cover_server();
+        let url = format!("{}/img/cover.png", base);
+
+        for _ in 0..2 {
+            let resp = fetch_cover(&state, url.clone(), None).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/png");
+            assert!(resp.headers()[header::CACHE_CONTROL]
+                .to_str()
                 .unwrap()
+                .contains("max-age"));
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(&bytes[..], b"\x89PNG fake image");
         }
-    } else {
-        // 本地文件
-        Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::empty())
+
+        // 第二次由缓存返回，图床只收到一次请求
+        let seen: Vec<_> = rx.try_iter().collect();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].1.as_deref(), Some(format!("{}/", base).as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_cover_proxy_uses_source_headers() {
+        let state = create_test_state("cover_source");
+        let (base, rx) = spawn_cover_server();
+        let source_url = format!("{}/source", base);
+        let source = serde_json::json!({
+            "bookSourceUrl": source_url,
+            "bookSourceName": "图床书源",
+            "header": r#"{"X-Token":"secret"}"#,
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+
+        let resp = fetch_cover(&state, format!("{}/a.png", base), Some(source_url.clone())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (path, referer, token) = rx.try_recv().unwrap();
+        assert_eq!(path, "/a.png");
+        assert_eq!(referer, Some(format!("{}/", source_url)));
+        assert_eq!(token.as_deref(), Some("secret"));
+
+        let resp = fetch_cover(&state, format!("{}/b.png", base), Some("https://missing".to_string())).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cover_proxy_non_image_placeholder() {
+        let state = create_test_state("cover_placeholder");
+        let (base, _rx) = spawn_cover_server();
+
+        let resp = fetch_cover(&state, format!("{}/page.html", base), None).await;
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/svg+xml");
+    }
+
+    #[tokio::test]
+    async fn test_import_local_book_multipart() {
+        let state = create_test_state("import_local");
+        let (text, _, _) = encoding_rs::GBK.encode("第一章 开端\r\n你好，世界\r\n第二章 结局\r\n再见\r\n");
+
+        let boundary = "reader-test-boundary";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"《测试书》作者：某人.txt\"\r\nContent-Type: text/plain\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        body.extend_from_slice(&text);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        let request = Request::builder()
+            .method("POST")
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap();
+
+        let (status, book) = into_json(import_local_book(State(state.clone()), request).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(book["data"]["name"], "测试书");
+        assert_eq!(book["data"]["author"], "某人");
+        assert_eq!(book["data"]["origin"], "local");
+        let url = book["data"]["bookUrl"].as_str().unwrap().to_string();
+
+        // 本地书籍即使 refresh 也不会走书源
+        let (status, chapters) = into_json(
+            get_chapter_list(
+                State(state.clone()),
+                Query(ChapterListQuery {
+                    url: url.clone(),
+                    origin: None,
+                    refresh: Some(1),
+                }),
+                HeaderMap::new(),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(chapters["data"][1]["title"], "第二章 结局");
+
+        let (status, content) = into_json(
+            get_book_content(
+                State(state.clone()),
+                Query(BookContentQuery {
+                    url,
+                    index: 0,
+                    refresh: Some(1),
+                    max_pages: None,
+                    format: None,
+                }),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content["data"], "你好，世界");
+    }
+
+    #[tokio::test]
+    async fn test_import_local_book_rejects_escaping_path() {
+        let state = create_test_state("import_local_path");
+        let request = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"path":"../secret.txt"}"#))
+            .unwrap();
+
+        let (status, body) = into_json(import_local_book(State(state), request).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errorCode"], "BAD_REQUEST");
+    }
+
+    #[tokio::test]
+    async fn test_get_local_asset() {
+        let state = create_test_state("assets");
+        let dir = "/tmp/reader_tests_api_assets/cache/assets/abc123/OEBPS/Images";
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(format!("{}/插图.png", dir), b"\x89PNG").unwrap();
+
+        let resp = get_asset(
+            State(state.clone()),
+            Path(("abc123".to_string(), "OEBPS/Images/插图.png".to_string())),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/png");
+
+        let resp = get_asset(
+            State(state),
+            Path(("abc123".to_string(), "../../kv_store.json".to_string())),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_delete_book_purges_caches() {
+        use crate::storage::content_cache::ContentCache;
+        use crate::storage::cover_cache::{CachedCover, CoverCache};
+
+        let state = create_test_state("delete_book");
+        let content_cache = ContentCache::new(state.storage.clone());
+        let cover_cache = CoverCache::new(state.storage.clone());
+        let cover_url = "https://img.example.com/dune.jpg";
+        let books = [("https://example.com/book/dune", "Dune"), ("https://example.com/book/found", "Foundation")];
+        for (url, name) in books {
+            let book = Book {
+                book_url: url.to_string(),
+                name: name.to_string(),
+                cover_url: Some(cover_url.to_string()).filter(|_| name == "Dune"),
+                ..Default::default()
+            };
+            state.book_service.save_book(book).await.unwrap();
+            content_cache.put(url, 0, "第一章").await.unwrap();
+            content_cache.put(url, 1, "第二章!").await.unwrap();
+        }
+        let cover = CachedCover {
+            content_type: "image/jpeg".to_string(),
+            data: vec![0xFF; 100],
+        };
+        cover_cache.put(cover_url, &cover).await.unwrap();
+        // 保存后的索引在后台写入
+        let mut indexed = false;
+        for _ in 0..100 {
+            indexed = !state.search_engine.search("Dune", 10).unwrap().is_empty();
+            if indexed {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(indexed);
+
+        let req = DeleteBookRequest {
+            url: books[0].0.to_string(),
+            delete_cache: None,
+        };
+        let (status, body) = into_json(delete_book(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        // 两章正文 (9 + 10 字节)、封面及其类型文件
+        assert_eq!(body["data"]["files"], 4);
+        assert_eq!(body["data"]["bytes"], 9 + 10 + 100 + "image/jpeg".len());
+        assert!(content_cache.get(books[0].0, 0).await.is_none());
+        assert!(cover_cache.get(cover_url).await.is_none());
+        assert!(state.search_engine.search("Dune", 10).unwrap().is_empty());
+
+        // 不删除缓存时正文保留，索引仍会移除
+        let reclaimed = state
+            .book_service
+            .delete_books(&[books[1].0.to_string()], false)
+            .await
+            .unwrap();
+        assert_eq!(reclaimed, ReclaimedCache::default());
+        assert_eq!(content_cache.get(books[1].0, 1).await.as_deref(), Some("第二章!"));
+        assert!(state.search_engine.search("Foundation", 10).unwrap().is_empty());
+        assert!(state.book_service.get_bookshelf(false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_search_pinyin_and_rebuild() {
+        let state = create_test_state("local_search_pinyin");
+        let book = Book {
+            book_url: "https://example.com/book/doupo".to_string(),
+            name: "斗破苍穹".to_string(),
+            author: "天蚕土豆".to_string(),
+            ..Default::default()
+        };
+        state.book_service.save_book(book).await.unwrap();
+        let search = |key: &str| SearchQuery {
+            key: key.to_string(),
+            exact: None,
+            author: None,
+            min_words: None,
+            max_words: None,
+            kind: None,
+            skip_broken: None,
+        };
+        // 保存后的索引在后台写入
+        let mut body = serde_json::Value::Null;
+        for _ in 0..100 {
+            (_, body) = into_json(local_search(State(state.clone()), Query(search("dpcq"))).await).await;
+            if body["data"].as_array().is_some_and(|hits| !hits.is_empty()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(body["data"][0]["book_id"], "https://example.com/book/doupo");
+        assert_eq!(body["data"][0]["match_kind"], "exact");
+
+        // 索引丢失后可按书架重建
+        state.search_engine.clear_index().unwrap();
+        assert!(state.search_engine.search("斗破", 10).unwrap().is_empty());
+        let (status, body) = into_json(rebuild_search_index(State(state.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"], 1);
+        let (_, body) = into_json(local_search(State(state.clone()), Query(search("doupo"))).await).await;
+        assert_eq!(body["data"][0]["match_kind"], "prefix");
+    }
+
+    /// 目录页按当前章节数生成的书源站点，章节数为 0 时直接断开连接模拟站点故障
+    fn spawn_toc_server(chapters: Arc<std::sync::atomic::AtomicUsize>) -> String {
+        use std::sync::atomic::Ordering;
+
+        spawn_server(move |_, _| {
+            let count = chapters.load(Ordering::SeqCst);
+            if count == 0 {
+                return (0, vec![], String::new());
+            }
+            let items: String = (1..=count)
+                .map(|i| format!(r#"<li><a href="/c/{i}">第{i}章</a></li>"#))
+                .collect();
+            let body = format!("<ul>{}</ul>", items);
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_refresh_bookshelf_detects_new_chapter_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = create_test_state("refresh_shelf");
+        let chapters = Arc::new(AtomicUsize::new(2));
+        let base = spawn_toc_server(chapters.clone());
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "更新书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let books = [
+            (book_url.clone(), base.clone()),
+            (format!("{}/book/2", base), "https://missing".to_string()),
+        ];
+        for (url, origin) in books {
+            state
+                .book_service
+                .save_book(Book {
+                    book_url: url,
+                    name: "书".to_string(),
+                    origin: Some(origin),
+                    latest_chapter_title: Some("第2章".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut flips = 0;
+        for grow in [false, true, false] {
+            if grow {
+                chapters.fetch_add(1, Ordering::SeqCst);
+            }
+            let (status, body) = into_json(
+                refresh_bookshelf(State(state.clone()), Query(RefreshBookshelfQuery { url: None })).await,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            // 书源缺失的书只记录错误，不影响其他书
+            assert_eq!(body["data"]["checked"], 2);
+            assert_eq!(body["data"]["failed"], 1);
+            flips += body["data"]["updated"].as_u64().unwrap();
+        }
+        assert_eq!(flips, 1);
+
+        let query = BookshelfQuery {
+            refresh: None,
+            group: None,
+            sort: None,
+            offset: None,
+            limit: None,
+        };
+        let (_, shelf) = into_json(get_bookshelf(State(state.clone()), Query(query)).await).await;
+        assert_eq!(shelf["total"], 2);
+        let book = &shelf["data"][0];
+        assert_eq!(book["hasNewChapter"], true);
+        assert_eq!(book["latestChapterTitle"], "第3章");
+        assert_eq!(book["totalChapterNum"], 3);
+        assert!(book["lastCheckTime"].is_i64());
+        assert!(book.get("lastUpdateError").is_none());
+        assert_eq!(book["canUpdate"], true);
+        let error = &shelf["data"][1]["lastUpdateError"];
+        assert_eq!(error["stage"], "source");
+        assert_eq!(error["kind"], "NOT_FOUND");
+
+        // 目录缓存同步更新
+        let cached = state.book_service.get_chapter_list(&book_url, None, false).await.unwrap();
+        assert_eq!(cached.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_update_error_recorded_and_cleared() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = create_test_state("update_error");
+        let chapters = Arc::new(AtomicUsize::new(0));
+        let base = spawn_toc_server(chapters.clone());
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "故障书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "书".to_string(),
+                origin: Some(base.clone()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // 目录阶段失败时记录
+        let fetched = state.book_service.get_chapter_list(&book_url, None, true).await;
+        assert!(fetched.is_err());
+        let (_, failures) = into_json(update_failures(State(state.clone())).await).await;
+        let error = &failures["data"][0]["lastUpdateError"];
+        assert_eq!(error["stage"], "toc");
+        assert_eq!(error["kind"], "NETWORK");
+        assert!(error["time"].is_i64());
+
+        // 之后成功时清除
+        chapters.store(2, Ordering::SeqCst);
+        let query = RefreshBookshelfQuery { url: None };
+        let (_, body) = into_json(refresh_bookshelf(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"]["failed"], 0);
+        let (_, failures) = into_json(update_failures(State(state.clone())).await).await;
+        assert_eq!(failures["data"], serde_json::json!([]));
+
+        // 关闭更新后不再检查
+        let req = SetBookCanUpdateRequest {
+            url: book_url.clone(),
+            can_update: false,
+        };
+        let (status, body) = into_json(set_book_can_update(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["canUpdate"], false);
+        let query = RefreshBookshelfQuery { url: None };
+        let (_, body) = into_json(refresh_bookshelf(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"]["checked"], 0);
+    }
+
+    /// 按路径返回固定页面，并统计请求次数
+    fn spawn_pages_server(pages: Vec<(&'static str, &'static str)>, hits: Arc<std::sync::atomic::AtomicUsize>) -> String {
+        use std::sync::atomic::Ordering;
+
+        spawn_server(move |head, _| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            let path = request_path(head);
+            let body = pages.iter().find(|(p, _)| *p == path).map(|(_, b)| *b).unwrap_or("");
+            html_response(body)
+        })
+    }
+
+    /// 读取 SSE 响应中的 (事件名, 数据)
+    async fn read_sse(resp: impl IntoResponse) -> Vec<(String, serde_json::Value)> {
+        let bytes = axum::body::to_bytes(resp.into_response().into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec())
             .unwrap()
+            .split("\n\n")
+            .filter(|block| !block.trim().is_empty())
+            .map(|block| {
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|l| l.strip_prefix(name))
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string()
+                };
+                (field("event:"), serde_json::from_str(&field("data:")).unwrap())
+            })
+            .collect()
+    }
+
+    /// 音频书源的源站：章节页给出音频地址，音频按 Range 返回，收到的音频请求头发送到 `requests`
+    fn spawn_audio_origin(requests: mpsc::Sender<std::collections::HashMap<String, String>>) -> String {
+        spawn_server(move |head, _| match request_path(head) {
+            "/toc" => html_response(r#"<ul><li><a href="/c/1">第一集</a></li></ul>"#),
+            "/c/1" => html_response(r#"<audio src="/media/1.mp3"></audio>"#),
+            _ => {
+                let audio = b"0123456789";
+                let headers = headers(head);
+                let response = match headers.get("range").map(String::as_str) {
+                    Some("bytes=2-5") => {
+                        let range = vec![
+                            ("Content-Type", "audio/mpeg".to_string()),
+                            ("Content-Range", "bytes 2-5/10".to_string()),
+                            ("Accept-Ranges", "bytes".to_string()),
+                        ];
+                        (206, range, audio[2..6].to_vec())
+                    }
+                    _ => (200, vec![("Content-Type", "audio/mpeg".to_string())], audio.to_vec()),
+                };
+                requests.send(headers).unwrap();
+                response
+            }
+        })
+    }
+
+    fn html_response(body: &str) -> Reply<Vec<u8>> {
+        (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_audio_source_url_and_proxy() {
+        let state = create_test_state("audio_source");
+        let (tx, requests) = mpsc::channel();
+        let base = spawn_audio_origin(tx);
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "有声书源",
+            "bookSourceType": 1,
+            "header": r#"{"X-Token":"abc"}"#,
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:audio@src" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let book = state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "有声书".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(book.book_type, Some(0b10_0000));
+
+        let query = Query(AudioUrlQuery {
+            url: book_url.clone(),
+            index: 0,
+        });
+        let (status, body) = into_json(get_audio_url(State(state.clone()), query).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let audio_url = format!("{}/media/1.mp3", base);
+        assert_eq!(body["data"]["type"], "audio");
+        assert_eq!(body["data"]["url"], audio_url.as_str());
+        assert_eq!(body["data"]["headers"]["X-Token"], "abc");
+        assert_eq!(body["data"]["headers"]["Referer"], format!("{}/", base).as_str());
+
+        // 代理转发 Range，并带上书源请求头与 Referer
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=2-5"));
+        let query = Query(AudioProxyQuery {
+            url: audio_url.clone(),
+            book_source_url: base.clone(),
+        });
+        let resp = audio_proxy(State(state.clone()), query, headers).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "audio/mpeg");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"2345");
+        let sent = requests.recv().unwrap();
+        assert_eq!(sent["range"], "bytes=2-5");
+        assert_eq!(sent["x-token"], "abc");
+        assert_eq!(sent["referer"], format!("{}/", base));
+
+        // 不带 Range 时返回完整音频
+        let query = Query(AudioProxyQuery {
+            url: audio_url,
+            book_source_url: base.clone(),
+        });
+        let resp = audio_proxy(State(state.clone()), query, HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"0123456789");
+        assert!(!requests.recv().unwrap().contains_key("range"));
+    }
+
+    /// 漫画书源的源站：章节页的图片懒加载，图片没有 Referer 时返回 403，收到的图片请求发送到 `requests`
+    fn spawn_comic_origin(requests: mpsc::Sender<(String, Option<String>)>) -> String {
+        spawn_server(move |head, _| match request_path(head) {
+            "/toc" => html_response(r#"<ul><li><a href="/c/1">第一话</a></li></ul>"#),
+            "/c/1" => html_response(
+                r#"<div class="comic"><img src="/static/loading.gif" data-original="/img/1.png"><img src="/static/loading.gif" data-original="/img/2.png"></div>"#,
+            ),
+            path => {
+                let referer = header(head, "referer").map(str::to_string);
+                let response = match referer {
+                    Some(_) => {
+                        let image = [b"\x89PNG".as_slice(), path.as_bytes()].concat();
+                        (200, vec![("Content-Type", "image/png".to_string())], image)
+                    }
+                    None => (403, vec![], Vec::new()),
+                };
+                requests.send((path.to_string(), referer)).unwrap();
+                response
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_image_source_content_and_proxy() {
+        let state = create_test_state("image_source");
+        let (tx, requests) = mpsc::channel();
+        let base = spawn_comic_origin(tx);
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "漫画书源",
+            "bookSourceType": 2,
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:.comic img@src" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let book = state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "漫画".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(book.book_type, Some(0b100_0000));
+
+        let query = Query(BookContentQuery {
+            url: book_url.clone(),
+            index: 0,
+            refresh: None,
+            max_pages: None,
+            format: None,
+        });
+        let (status, body) = into_json(get_book_content(State(state.clone()), query).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["type"], "image");
+        let images: Vec<String> = serde_json::from_value(body["data"]["images"].clone()).unwrap();
+        let expected: Vec<String> = ["/img/1.png", "/img/2.png"]
+            .iter()
+            .map(|path| {
+                format!(
+                    "/reader3/imageProxy?src={}&bookSourceUrl={}",
+                    urlencoding::encode(&format!("{}{}", base, path)),
+                    urlencoding::encode(&base)
+                )
+            })
+            .collect();
+        assert_eq!(images, expected);
+
+        // 代理带上 Referer 请求图片，第二次由缓存返回
+        for _ in 0..2 {
+            let query = Query(ImageProxyQuery {
+                src: format!("{}/img/1.png", base),
+                book_source_url: Some(base.clone()),
+            });
+            let resp = image_proxy(State(state.clone()), query).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/png");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(&body[..], b"\x89PNG/img/1.png");
+        }
+        let seen: Vec<_> = requests.try_iter().collect();
+        assert_eq!(seen, vec![("/img/1.png".to_string(), Some(format!("{}/", base)))]);
+    }
+
+    #[tokio::test]
+    async fn test_chapter_list_cached_with_etag() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = create_test_state("chapter_list_etag");
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base = spawn_pages_server(
+            vec![(
+                "/toc",
+                r#"<ul><li><a href="/c/1">第一章</a></li><li><a href="/c/2">第二章</a></li></ul>"#,
+            )],
+            hits.clone(),
+        );
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "目录书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "目录缓存".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let call = |refresh: Option<i32>, etag: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(etag) = etag {
+                headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(etag).unwrap());
+            }
+            let query = Query(ChapterListQuery {
+                url: book_url.clone(),
+                origin: None,
+                refresh,
+            });
+            get_chapter_list(State(state.clone()), query, headers)
+        };
+
+        let resp = call(None, None).await.unwrap();
+        let etag = resp.headers()[header::ETAG].to_str().unwrap().to_string();
+        let (status, chapters) = into_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(chapters["data"][1]["title"], "第二章");
+        let fetched = hits.load(Ordering::SeqCst);
+        assert!(fetched > 0);
+
+        // 第二次从缓存读取，不请求书源
+        let resp = call(None, None).await.unwrap();
+        assert_eq!(resp.headers()[header::ETAG], etag.as_str());
+        assert_eq!(hits.load(Ordering::SeqCst), fetched);
+
+        let resp = call(None, Some(&format!("\"other\", W/{}", etag))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+        assert_eq!(call(None, Some("\"other\"")).await.unwrap().status(), StatusCode::OK);
+
+        // refresh=1 重新获取，内容不变时 ETag 不变
+        let resp = call(Some(1), Some(&etag)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert!(hits.load(Ordering::SeqCst) > fetched);
+
+        // 缓存过期后重新获取
+        let fetched = hits.load(Ordering::SeqCst);
+        let expired = state
+            .book_service
+            .clone()
+            .with_chapter_list_ttl(std::time::Duration::ZERO);
+        expired.get_chapter_list(&book_url, None, false).await.unwrap();
+        assert!(hits.load(Ordering::SeqCst) > fetched);
+
+        // 切换书源后目录缓存失效
+        let fetched = hits.load(Ordering::SeqCst);
+        state
+            .book_service
+            .set_book_source(&book_url, &book_url, &base)
+            .await
+            .unwrap();
+        assert_eq!(call(None, None).await.unwrap().status(), StatusCode::OK);
+        assert!(hits.load(Ordering::SeqCst) > fetched);
+    }
+
+    #[tokio::test]
+    async fn test_book_content_formatting() {
+        use std::sync::atomic::AtomicUsize;
+
+        let state = create_test_state("content_formatting");
+        let base = spawn_pages_server(
+            vec![
+                ("/toc", r#"<ul><li><a href="/c/1">第一章</a></li></ul>"#),
+                (
+                    "/c/1",
+                    "<div id=\"content\">&nbsp;&nbsp;&nbsp;&nbsp;他推开门，院子里的雪\n已经积了半尺深。\n\n\n\n&nbsp;&nbsp;&nbsp;&nbsp;远处传来犬吠。</div>",
+                ),
+            ],
+            Arc::new(AtomicUsize::new(0)),
+        );
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "排版书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:#content@text" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let book: Book = serde_json::from_value(serde_json::json!({
+            "bookUrl": book_url,
+            "name": "排版",
+            "author": "",
+            "origin": base,
+            "tocUrl": format!("{}/toc", base),
+            "formatting": { "indent": "  " }
+        }))
+        .unwrap();
+        let (status, _) = into_json(save_book(State(state.clone()), Json(book.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let content = |format: Option<i32>| {
+            let state = state.clone();
+            let url = book_url.clone();
+            async move {
+                let query = Query(BookContentQuery {
+                    url,
+                    index: 0,
+                    refresh: None,
+                    max_pages: None,
+                    format,
+                });
+                let (status, body) = into_json(get_book_content(State(state), query).await).await;
+                assert_eq!(status, StatusCode::OK);
+                body["data"].as_str().unwrap().to_string()
+            }
+        };
+        let formatted = content(None).await;
+        assert_eq!(formatted, "  他推开门，院子里的雪已经积了半尺深。\n\n  远处传来犬吠。");
+        let raw = content(Some(0)).await;
+        assert!(raw.contains("院子里的雪\n已经"), "{:?}", raw);
+
+        // 重新保存书籍 (不带排版选项) 时保留，关闭后仍可按请求使用默认排版
+        let mut book = book;
+        book.formatting = None;
+        state.book_service.save_book(book.clone()).await.unwrap();
+        assert!(content(None).await.starts_with("  他推开门"));
+        book.formatting = Some(crate::engine::utils::ContentFormatOptions {
+            enabled: false,
+            ..Default::default()
+        });
+        state.book_service.save_book(book).await.unwrap();
+        assert_eq!(content(None).await, raw);
+        let formatted = content(Some(1)).await;
+        assert!(formatted.starts_with("\u{3000}\u{3000}他推开门，院子里的雪已经积了半尺深。"));
+    }
+
+    /// 目录只有一章、正文取自 `content` 的站点
+    fn spawn_editable_chapter_site(content: Arc<std::sync::Mutex<String>>) -> String {
+        spawn_server(move |head, _| match request_path(head) {
+            "/toc" => html_response(r#"<ul><li><a href="/c/1">第一章</a></li></ul>"#),
+            _ => html_response(&format!(r#"<div id="content">{}</div>"#, content.lock().unwrap())),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_chapter_versions() {
+        let state = create_test_state("chapter_versions");
+        let chapter = Arc::new(std::sync::Mutex::new("第一行\n第二行\n第三行".to_string()));
+        let base = spawn_editable_chapter_site(chapter.clone());
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "修订书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:#content@text" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let book: Book = serde_json::from_value(serde_json::json!({
+            "bookUrl": book_url,
+            "name": "修订",
+            "author": "",
+            "origin": base,
+            "tocUrl": format!("{}/toc", base)
+        }))
+        .unwrap();
+        state.book_service.save_book(book).await.unwrap();
+
+        let fetch = |refresh: bool| {
+            let query = Query(BookContentQuery {
+                url: book_url.clone(),
+                index: 0,
+                refresh: Some(refresh as i32),
+                max_pages: None,
+                format: Some(0),
+            });
+            let state = state.clone();
+            async move {
+                let (status, _) = into_json(get_book_content(State(state), query).await).await;
+                assert_eq!(status, StatusCode::OK);
+            }
+        };
+        let versions = || {
+            let query = Query(ChapterVersionsQuery { url: book_url.clone(), index: 0 });
+            let state = state.clone();
+            async move { into_json(get_chapter_versions(State(state), query).await).await }
+        };
+        let diff = |from: Option<u32>, to: Option<u32>| {
+            let query = Query(ChapterDiffQuery { url: book_url.clone(), index: 0, from, to });
+            let state = state.clone();
+            async move { into_json(get_chapter_diff(State(state), query).await).await }
+        };
+
+        let (status, _) = versions().await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        fetch(false).await;
+        // 内容未变的刷新不产生新版本
+        fetch(true).await;
+        let (_, body) = versions().await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+        // 删除一行
+        *chapter.lock().unwrap() = "第一行\n第三行".to_string();
+        fetch(true).await;
+        let (_, body) = versions().await;
+        let list = body["data"].as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!((list[0]["version"].as_u64(), list[0]["current"].as_bool()), (Some(1), Some(false)));
+        assert_eq!((list[1]["version"].as_u64(), list[1]["current"].as_bool()), (Some(2), Some(true)));
+        assert_eq!(list[0]["length"], 11);
+        assert_eq!(list[1]["length"], 7);
+        assert!(list[0]["cachedAt"].as_i64().unwrap() > 0);
+
+        let (status, body) = diff(None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["data"]["from"].as_u64(), body["data"]["to"].as_u64()), (Some(1), Some(2)));
+        assert_eq!((body["data"]["added"].as_u64(), body["data"]["removed"].as_u64()), (Some(0), Some(1)));
+        assert_eq!(body["data"]["diff"], "--- v1\n+++ v2\n@@ -1,3 +1,2 @@\n 第一行\n-第二行\n 第三行\n");
+
+        // 插入一行
+        *chapter.lock().unwrap() = "第一行\n第三行\n第四行".to_string();
+        fetch(true).await;
+        let (_, body) = diff(Some(2), Some(3)).await;
+        assert_eq!((body["data"]["added"].as_u64(), body["data"]["removed"].as_u64()), (Some(1), Some(0)));
+        assert_eq!(body["data"]["diff"], "--- v2\n+++ v3\n@@ -1,2 +1,3 @@\n 第一行\n 第三行\n+第四行\n");
+        let (_, body) = diff(Some(1), None).await;
+        assert_eq!(body["data"]["diff"], "--- v1\n+++ v3\n@@ -1,3 +1,3 @@\n 第一行\n-第二行\n 第三行\n+第四行\n");
+
+        let (status, _) = diff(Some(9), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_book_variable_in_content_url() {
+        use std::sync::atomic::AtomicUsize;
+
+        let state = create_test_state("book_variable");
+        let base = spawn_pages_server(
+            vec![
+                (
+                    "/toc",
+                    r#"<ul><li><a href="/c/1?token={{book.getVariable('token')}}">第一章</a></li></ul>"#,
+                ),
+                ("/c/1?token=t-1", r#"<div id="content">带令牌的正文</div>"#),
+            ],
+            Arc::new(AtomicUsize::new(0)),
+        );
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "变量书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:#content@text" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        let book = Book {
+            book_url: book_url.clone(),
+            name: "书籍变量".to_string(),
+            origin: Some(base.clone()),
+            toc_url: Some(format!("{}/toc", base)),
+            ..Default::default()
+        };
+        state.book_service.save_book(book.clone()).await.unwrap();
+
+        let req = SaveBookVariableRequest {
+            url: book_url.clone(),
+            key: Some("token".to_string()),
+            value: "t-1".to_string(),
+        };
+        let (status, body) = into_json(save_book_variable(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["token"], "t-1");
+
+        let query = Query(BookContentQuery {
+            url: book_url.clone(),
+            index: 0,
+            refresh: None,
+            max_pages: None,
+            format: None,
+        });
+        let (status, body) = into_json(get_book_content(State(state.clone()), query).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"].as_str().unwrap().contains("带令牌的正文"));
+
+        // 重新保存书籍 (不带变量) 与换源都保留书籍变量
+        state.book_service.save_book(book).await.unwrap();
+        state
+            .book_service
+            .set_book_source(&book_url, &book_url, &base)
+            .await
+            .unwrap();
+        let query = Query(BookVariableQuery { url: book_url.clone() });
+        let (status, body) = into_json(get_book_variable(State(state.clone()), query).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"], serde_json::json!({ "token": "t-1" }));
+
+        let query = Query(BookVariableQuery {
+            url: format!("{}/book/missing", base),
+        });
+        let (status, _) = into_json(get_book_variable(State(state), query).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_book_content_sse_streams_pages() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = create_test_state("content_sse");
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base = spawn_pages_server(
+            vec![
+                ("/toc", r#"<ul><li><a href="/c/1">第一章</a></li></ul>"#),
+                ("/c/1", r#"<div id="content">第一页广告</div><a id="next" href="/c/1_2">下一页</a>"#),
+                ("/c/1_2", r#"<div id="content">第二页</div><a id="next" href="/c/1_3">下一页</a>"#),
+                ("/c/1_3", r#"<div id="content">第三页</div>"#),
+            ],
+            hits.clone(),
+        );
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "分页书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": {
+                "content": "@css:#content@text",
+                "nextContentUrl": "@css:#next@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "长章节".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        state
+            .replace_service
+            .save_rule(crate::models::ReplaceRule {
+                id: None,
+                name: "去广告".to_string(),
+                pattern: "广告".to_string(),
+                replacement: String::new(),
+                scope: String::new(),
+                is_enabled: true,
+                is_regex: false,
+                group: None,
+                scope_title: false,
+                order: 0,
+            })
+            .await
+            .unwrap();
+
+        let query = || {
+            Query(BookContentQuery {
+                url: book_url.clone(),
+                index: 0,
+                refresh: None,
+                max_pages: None,
+                format: None,
+            })
+        };
+        let events = read_sse(get_book_content_sse(State(state.clone()), query()).await).await;
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["chunk", "chunk", "chunk", "done"]);
+        let texts: Vec<&str> = events[..3].iter().map(|(_, data)| data["text"].as_str().unwrap()).collect();
+        assert_eq!(texts, vec!["第一页", "第二页", "第三页"]);
+        let pages: Vec<u64> = events[..3].iter().map(|(_, data)| data["pageIndex"].as_u64().unwrap()).collect();
+        assert_eq!(pages, vec![0, 1, 2]);
+
+        // 组装结果与非流式接口一致，且已写入缓存
+        let fetched = hits.load(Ordering::SeqCst);
+        let (_, content) = into_json(get_book_content(State(state.clone()), query()).await).await;
+        let content = content["data"].as_str().unwrap().to_string();
+        assert_eq!(content, "第一页\n\n第二页\n\n第三页");
+        assert_eq!(events[3].1["length"], content.chars().count());
+
+        let events = read_sse(get_book_content_sse(State(state.clone()), query()).await).await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].1["text"], content.as_str());
+        assert_eq!(hits.load(Ordering::SeqCst), fetched);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_next_chapters() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = "/tmp/reader_tests_api_prefetch";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut state = AppState::with_storage_dir(dir);
+        state.prefetcher = crate::services::Prefetcher::with_count(state.book_service.clone(), state.jobs.clone(), 3);
+        let state = Arc::new(state);
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base = spawn_pages_server(
+            vec![
+                (
+                    "/toc",
+                    r#"<ul><li><a href="/c/0">一</a></li><li><a href="/c/1">二</a></li><li><a href="/c/2">三</a></li>
+                    <li><a href="/c/3">四</a></li><li><a href="/c/4">五</a></li><li><a href="/c/5">六</a></li></ul>"#,
+                ),
+                ("/c/0", r#"<div id="content">第一章</div>"#),
+                ("/c/1", r#"<div id="content">第二章</div>"#),
+                ("/c/2", r#"<div id="content">第三章</div>"#),
+                ("/c/3", r#"<div id="content">第四章</div>"#),
+                ("/c/4", r#"<div id="content">第五章</div>"#),
+                ("/c/5", r#"<div id="content">第六章</div>"#),
+            ],
+            hits.clone(),
+        );
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "预取书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:#content@text" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: url.clone(),
+                name: "预取".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let read = |index: i32| {
+            let state = state.clone();
+            let url = url.clone();
+            async move {
+                let query = BookContentQuery {
+                    url,
+                    index,
+                    refresh: None,
+                    max_pages: None,
+                    format: None,
+                };
+                let (_, content) = into_json(get_book_content(State(state), Query(query)).await).await;
+                content["data"].as_str().unwrap().to_string()
+            }
+        };
+        let status = || {
+            let state = state.clone();
+            let url = url.clone();
+            async move {
+                // 等待后台预取结束
+                for _ in 0..100 {
+                    let query = PrefetchStatusQuery { url: url.clone() };
+                    let (_, status) = into_json(prefetch_status(State(state.clone()), Query(query)).await).await;
+                    if status["data"]["running"] == false {
+                        return status["data"]["chapters"].clone();
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                panic!("prefetch did not finish");
+            }
+        };
+
+        // 目录 + 第一章，随后预取第二到第四章
+        assert_eq!(read(0).await, "第一章");
+        let chapters = status().await;
+        assert_eq!(chapters.as_array().unwrap().len(), 3);
+        assert!(chapters.as_array().unwrap().iter().all(|c| c["cached"] == true));
+        assert_eq!(hits.load(Ordering::SeqCst), 1 + 1 + 3);
+
+        // 第二章命中缓存；第三、四章已缓存，只抓取第五章
+        assert_eq!(read(1).await, "第二章");
+        status().await;
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+
+        // 重复阅读不会再次排队
+        read(1).await;
+        status().await;
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+        assert_eq!(state.prefetcher.status(&url).await.chapters.last().unwrap().index, 4);
+    }
+
+    /// 每个连接一个线程的书站：章节页延迟 100ms 响应，记录各路径的请求次数与同时处理的最大请求数
+    fn spawn_concurrent_book_site(
+        chapters: usize,
+        hits: Arc<parking_lot::Mutex<HashMap<String, usize>>>,
+        max_in_flight: Arc<std::sync::atomic::AtomicUsize>,
+    ) -> String {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = AtomicUsize::new(0);
+        spawn_concurrent_server(move |head, _| {
+            let path = request_path(head);
+            *hits.lock().entry(path.to_string()).or_default() += 1;
+            let body = match path.strip_prefix("/c/") {
+                // 最后一章没有正文
+                Some(index) if index.parse::<usize>().unwrap() + 1 == chapters => String::new(),
+                Some(index) => {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    format!(r#"<div id="content">第{}章正文</div>"#, index)
+                }
+                None => (0..chapters)
+                    .map(|i| format!(r#"<li><a href="/c/{}">第{}章</a></li>"#, i, i))
+                    .collect(),
+            };
+            html_response(&body)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cache_book_parallel_and_resumable() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = "/tmp/reader_tests_api_cache_book";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut state = AppState::with_storage_dir(dir);
+        state.book_cacher =
+            crate::services::BookCacher::with_concurrency(state.book_service.clone(), state.jobs.clone(), 3);
+        let state = Arc::new(state);
+
+        let hits = Arc::new(parking_lot::Mutex::new(HashMap::new()));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let base = spawn_concurrent_book_site(10, hits.clone(), max_in_flight.clone());
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "缓存书源",
+            "ruleToc": {
+                "chapterList": "@css:li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": { "content": "@css:#content@text" }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: url.clone(),
+                name: "缓存".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        // 先读过的两章不再抓取
+        for index in [1, 2] {
+            state
+                .book_service
+                .get_book_content(&url, index, false, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let start = |start: Option<i32>| {
+            let state = state.clone();
+            let url = url.clone();
+            async move {
+                let req = CacheBookRequest { url, start, end: None };
+                let (_, progress) = into_json(cache_book(State(state), Json(req)).await).await;
+                progress["data"]["jobId"].as_str().unwrap().to_string()
+            }
+        };
+        let finished = |job_id: String| {
+            let state = state.clone();
+            async move {
+                for _ in 0..100 {
+                    let query = CacheJobQuery { job_id: job_id.clone() };
+                    let (_, progress) = into_json(cache_book_progress(State(state.clone()), Query(query)).await).await;
+                    if progress["data"]["running"] == false {
+                        return progress["data"].clone();
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                panic!("cache job did not finish");
+            }
+        };
+
+        let progress = finished(start(None).await).await;
+        assert_eq!(progress["total"], 10);
+        assert_eq!(progress["done"], 9);
+        assert_eq!(progress["skipped"], 2);
+        assert_eq!(progress["failed"], 1);
+        assert_eq!(progress["errors"][0]["index"], 9);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert!((0..10).all(|i| hits.lock()[&format!("/c/{}", i)] == 1));
+
+        // 继续缓存时只重试失败的章节
+        let progress = finished(start(Some(5)).await).await;
+        assert_eq!(progress["total"], 5);
+        assert_eq!(progress["skipped"], 4);
+        assert_eq!(progress["failed"], 1);
+        assert!((0..9).all(|i| hits.lock()[&format!("/c/{}", i)] == 1));
+        assert_eq!(hits.lock()["/c/9"], 2);
+
+        // 导出只读取缓存
+        let before = hits.lock().values().sum::<usize>();
+        state.book_service.export_epub(&url).await.unwrap();
+        assert_eq!(hits.lock().values().sum::<usize>(), before);
+
+        // 取消后不再开始新的章节
+        state.book_service.clear_book_cache(&url).await.unwrap();
+        let job_id = start(None).await;
+        let req = CacheJobQuery { job_id: job_id.clone() };
+        into_json(cancel_cache_book(State(state.clone()), Json(req)).await).await;
+        let progress = finished(job_id).await;
+        assert_eq!(progress["cancelled"], true);
+        assert!(progress["done"].as_u64().unwrap() < 9);
+
+        let query = CacheJobQuery {
+            job_id: "missing".to_string(),
+        };
+        let (status, _) = into_json(cache_book_progress(State(state.clone()), Query(query)).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_book_metadata_override_and_revert() {
+        let state = create_test_state("book_metadata");
+        let book_url = "https://example.com/book/1".to_string();
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "错误书名".to_string(),
+                author: "未知".to_string(),
+                cover_url: Some("https://example.com/cover.jpg".to_string()),
+                intro: Some("书源简介".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let req = serde_json::from_value(serde_json::json!({
+            "url": book_url,
+            "customName": "正确书名",
+            "customAuthor": "作者甲",
+        }))
+        .unwrap();
+        let (status, body) = into_json(save_book_metadata(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["name"], "正确书名");
+
+        let shelf_query = || {
+            Query(BookshelfQuery {
+                refresh: None,
+                group: None,
+                sort: None,
+                offset: None,
+                limit: None,
+            })
+        };
+        let (_, shelf) = into_json(get_bookshelf(State(state.clone()), shelf_query()).await).await;
+        let shown = &shelf["data"][0];
+        assert_eq!(shown["name"], "正确书名");
+        assert_eq!(shown["author"], "作者甲");
+        assert_eq!(shown["customName"], "正确书名");
+        assert_eq!(shown["intro"], "书源简介");
+
+        // 前端回存展示的书籍不会覆盖书源提供的值
+        let resaved: Book = serde_json::from_value(shown.clone()).unwrap();
+        state.book_service.save_book(resaved).await.unwrap();
+
+        // 换源后保留手动设置
+        let source_url = "https://other.example.com".to_string();
+        let source = serde_json::json!({ "bookSourceUrl": source_url, "bookSourceName": "新书源" });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let new_url = format!("{}/book/9", source_url);
+        let book = state
+            .book_service
+            .set_book_source(&book_url, &new_url, &source_url)
+            .await
+            .unwrap();
+        assert_eq!(book.custom_name.as_deref(), Some("正确书名"));
+
+        let req = ClearBookMetadataRequest { url: new_url.clone() };
+        let (status, body) = into_json(clear_book_metadata(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["name"], "错误书名");
+        assert_eq!(body["data"]["author"], "未知");
+        assert!(body["data"].get("customName").is_none());
+
+        let query = Query(BookInfoQuery {
+            url: new_url,
+            origin: None,
+        });
+        let (_, info) = into_json(get_book_info(State(state.clone()), query).await).await;
+        assert_eq!(info["data"]["name"], "错误书名");
+    }
+
+    #[tokio::test]
+    async fn test_upload_book_cover_served_locally() {
+        let state = create_test_state("cover_upload");
+        let book_url = "https://example.com/book/2".to_string();
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "无封面".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let boundary = "reader-cover-boundary";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"url\"\r\n\r\n{url}\r\n--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"cover.png\"\r\n\r\n",
+            b = boundary,
+            url = book_url
+        )
+        .into_bytes();
+        body.extend_from_slice(b"\x89PNG uploaded cover");
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        let request = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+            .body(Body::from(body))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &state).await.unwrap();
+
+        let (status, book) = into_json(upload_book_cover(State(state.clone()), multipart).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let cover = book["data"]["coverUrl"].as_str().unwrap().to_string();
+        assert!(cover.starts_with("reader3://cover/"));
+        assert_eq!(book["data"]["customCoverUrl"], cover.as_str());
+
+        // 本地封面由缓存直接返回，不访问网络
+        let resp = fetch_cover(&state, cover.clone(), None).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/png");
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&bytes[..], b"\x89PNG uploaded cover");
+
+        let resp = fetch_cover(&state, "reader3://cover/0123abcd".to_string(), None).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = fetch_cover(&state, "reader3://cover/../../etc".to_string(), None).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// 每个请求延迟 200ms 才响应的搜索站点，统计请求次数
+    fn spawn_slow_search_site(hits: Arc<std::sync::atomic::AtomicUsize>) -> String {
+        use std::sync::atomic::Ordering;
+
+        spawn_server(move |_, _| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            html_response(r#"<div class="book"><a href="/book/1">书名</a></div>"#)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_search_sse_cancelled_on_disconnect() {
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = create_test_state("search_cancel");
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base = spawn_slow_search_site(hits.clone());
+        let sources: Vec<_> = (0..8)
+            .map(|i| {
+                serde_json::json!({
+                    "bookSourceUrl": format!("{}/s{}", base, i),
+                    "bookSourceName": format!("慢书源{}", i),
+                    "searchUrl": "/search?q={{key}}",
+                    "ruleSearch": {
+                        "bookList": "@css:div.book",
+                        "name": "@css:a@text",
+                        "bookUrl": "@css:a@href"
+                    }
+                })
+            })
+            .collect();
+        state
+            .source_service
+            .import_sources(&serde_json::to_string(&sources).unwrap(), false)
+            .await
+            .unwrap();
+        let cancelled = crate::engine::stats::STATS.searches_cancelled.load(Ordering::Relaxed);
+
+        let mut stream = Box::pin(state.book_service.search_multi_sse("书名".to_string(), 1, SearchFilter::default()));
+        assert!(stream.next().await.is_some());
+        // 客户端断开：丢弃流
+        drop(stream);
+        let after_disconnect = hits.load(Ordering::SeqCst);
+        assert!(after_disconnect <= 2);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+        // 最多只有断开时已在进行的请求完成
+        assert!(hits.load(Ordering::SeqCst) <= after_disconnect + 1);
+        assert!(crate::engine::stats::STATS.searches_cancelled.load(Ordering::Relaxed) > cancelled);
+    }
+
+    /// 目录立即返回、正文每页延迟 250ms 且无限翻页的站点
+    fn spawn_slow_chapter_site() -> String {
+        spawn_server(|head, _| match request_path(head).strip_prefix("/c/1_") {
+            Some(page) => {
+                std::thread::sleep(std::time::Duration::from_millis(250));
+                let page: usize = page.parse().unwrap();
+                html_response(&format!(
+                    r#"<div id="content">第{}页</div><a id="next" href="/c/1_{}">下一页</a>"#,
+                    page,
+                    page + 1
+                ))
+            }
+            None => html_response(r#"<ul><li><a href="/c/1_1">第一章</a></li></ul>"#),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_book_content_deadline_reports_pages() {
+        let dir = "/tmp/reader_tests_api_content_deadline";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut state = AppState::with_storage_dir(dir);
+        state.budgets.content = Some(std::time::Duration::from_millis(1000));
+        let state = Arc::new(state);
+
+        let base = spawn_slow_chapter_site();
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "慢分页书源",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": {
+                "content": "@css:#content@text",
+                "nextContentUrl": "@css:#next@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+        let book_url = format!("{}/book/1", base);
+        state
+            .book_service
+            .save_book(Book {
+                book_url: book_url.clone(),
+                name: "慢章节".to_string(),
+                origin: Some(base.clone()),
+                toc_url: Some(format!("{}/toc", base)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let query = Query(BookContentQuery {
+            url: book_url,
+            index: 0,
+            refresh: None,
+            max_pages: None,
+            format: None,
+        });
+        let started = std::time::Instant::now();
+        let (status, body) = into_json(get_book_content(State(state), query).await).await;
+        // 20 页需 5 秒，预算到期即返回，不等正在进行的请求完成
+        assert!(started.elapsed() < std::time::Duration::from_millis(1500));
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["errorCode"], "DEADLINE_EXCEEDED");
+        assert_eq!(body["detail"]["budgetMs"], 1000);
+        let pages = body["detail"]["pagesFetched"].as_u64().unwrap();
+        assert!((1..=4).contains(&pages), "{}", body);
+        let partial = body["detail"]["partialContent"].as_str().unwrap();
+        assert!(partial.contains("第1页"));
+        assert!(partial.contains(&format!("第{}页", pages)));
+        assert!(!partial.contains(&format!("第{}页", pages + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_search_deadline_stops_trying_sources() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = "/tmp/reader_tests_api_search_deadline";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut state = AppState::with_storage_dir(dir);
+        state.budgets.search = Some(std::time::Duration::from_millis(300));
+        let state = Arc::new(state);
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base = spawn_slow_search_site(hits.clone());
+        let sources: Vec<_> = (0..8)
+            .map(|i| {
+                serde_json::json!({
+                    "bookSourceUrl": format!("{}/s{}", base, i),
+                    "bookSourceName": format!("慢书源{}", i),
+                    "searchUrl": "/search?q={{key}}",
+                    "ruleSearch": {
+                        "bookList": "@css:div.book",
+                        "name": "@css:a@text",
+                        "bookUrl": "@css:a@href"
+                    }
+                })
+            })
+            .collect();
+        state
+            .source_service
+            .import_sources(&serde_json::to_string(&sources).unwrap(), false)
+            .await
+            .unwrap();
+
+        let query = Query(SearchQuery {
+            key: "不存在".to_string(),
+            exact: Some(1),
+            author: None,
+            min_words: None,
+            max_words: None,
+            kind: None,
+            skip_broken: None,
+        });
+        let started = std::time::Instant::now();
+        let (status, body) = into_json(search(State(state), query).await).await;
+        assert!(started.elapsed() < std::time::Duration::from_millis(800));
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["errorCode"], "DEADLINE_EXCEEDED");
+        assert!(hits.load(Ordering::SeqCst) <= 2);
+    }
+
+    /// 每段输出固定字节的假 TTS 后端，text 含 "失败" 时报错
+    struct FakeTts;
+
+    impl crate::services::tts::TtsBackend for FakeTts {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn format(&self) -> crate::services::tts::AudioFormat {
+            crate::services::tts::AudioFormat::Mp3
+        }
+
+        fn synthesize(&self, text: &str, _voice: Option<&str>) -> anyhow::Result<Vec<u8>> {
+            anyhow::ensure!(!text.contains("失败"), "voice model missing");
+            Ok(b"\0\0\0\0\0".to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chapter_audio_range_and_errors() {
+        let dir = "/tmp/reader_tests_api_chapter_audio";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut state = AppState::with_storage_dir(dir);
+        state.tts_service =
+            crate::services::TtsService::with_backend(state.storage.clone(), Some(Arc::new(FakeTts)));
+        let state = Arc::new(state);
+
+        let url = "https://example.com/book/tts";
+        let chapter_dir = state.storage.cache_path(&format!("books/{:x}", md5::compute(url)));
+        std::fs::create_dir_all(&chapter_dir).unwrap();
+        std::fs::write(chapter_dir.join("0.txt"), "第一句。第二句。").unwrap();
+        std::fs::write(chapter_dir.join("1.txt"), "朗读失败。").unwrap();
+
+        let request = |index: i32, range: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(header::RANGE, HeaderValue::from_static(range));
+            }
+            get_chapter_audio(
+                State(state.clone()),
+                Query(ChapterAudioQuery {
+                    url: url.to_string(),
+                    index,
+                    voice: None,
+                }),
+                headers,
+            )
+        };
+
+        let resp = request(0, None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "audio/mpeg");
+        assert_eq!(resp.headers()[header::ACCEPT_RANGES], "bytes");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), 5);
+
+        let resp = request(0, Some("bytes=1-2")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 1-2/5");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), 2);
+
+        let resp = request(0, Some("bytes=-2")).await.unwrap();
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 3-4/5");
+        let resp = request(0, Some("bytes=9-")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes */5");
+
+        let (status, body) = into_json(request(1, None).await).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["errorCode"], "TTS_FAILED");
+        assert_eq!(body["detail"]["backend"], "fake");
+        assert!(body["errorMsg"].as_str().unwrap().contains("voice model missing"));
     }
 }
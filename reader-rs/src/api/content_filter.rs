@@ -0,0 +1,35 @@
+use axum::{
+    extract::State,
+    response::Json,
+};
+use std::sync::Arc;
+
+use crate::models::{ApiResponse, ContentFilter};
+use crate::services::AppState;
+use super::error::ApiResult;
+
+/// GET /contentFilters - 获取所有正文净化规则 (含内置规则)
+pub async fn get_content_filters(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Vec<ContentFilter>> {
+    let filters = state.content_filter_service.get_all_filters().await;
+    Ok(Json(ApiResponse::success(filters)))
+}
+
+/// POST /contentFilters - 批量新增或更新规则，正则非法时整批拒绝
+pub async fn save_content_filters(
+    State(state): State<Arc<AppState>>,
+    Json(filters): Json<Vec<ContentFilter>>,
+) -> ApiResult<Vec<ContentFilter>> {
+    let saved = state.content_filter_service.save_filters(filters).await?;
+    Ok(Json(ApiResponse::success(saved)))
+}
+
+/// POST /deleteContentFilters - 删除规则，内置规则只能停用
+pub async fn delete_content_filters(
+    State(state): State<Arc<AppState>>,
+    Json(filters): Json<Vec<ContentFilter>>,
+) -> ApiResult<()> {
+    state.content_filter_service.delete_filters(filters).await?;
+    Ok(Json(ApiResponse::success(())))
+}
@@ -0,0 +1,327 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+
+use crate::engine::error::{EngineError, PartialContent};
+use crate::models::ApiResponse;
+use crate::services::tts::TtsError;
+use crate::storage::sandbox::SandboxError;
+use crate::services::ServiceError;
+
+/// 接口统一返回类型
+pub type ApiResult<T> = Result<Json<ApiResponse<T>>, ApiError>;
+
+/// 结构化接口错误
+///
+/// 每个变体对应一个 HTTP 状态码，响应体沿用 `ApiResponse` 信封：
+/// `{isSuccess:false, errorCode, errorMsg, detail}`
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Unauthorized(String),
+    /// 访问存储目录之外的路径或不允许的文件类型
+    Forbidden(String),
+    /// 上传内容超过 max_bytes
+    PayloadTooLarge { max_bytes: u64, message: String },
+    /// 请求过于频繁，retry_after 秒后重试
+    RateLimited { retry_after: u64 },
+    SourceRuleMissing { field: String },
+    SourceDisabled { url: String },
+    Network { url: String, kind: String, message: String },
+    ParseFailed { rule: String, stage: String, message: String },
+    JsError { message: String },
+    /// 书源脚本执行超时被中断
+    JsTimeout { source_url: String, timeout_ms: u64, message: String },
+    /// 请求超出整体时间预算，partial 为超时前已抓取的正文
+    DeadlineExceeded { budget_ms: u64, partial: Option<PartialContent>, message: String },
+    Tts { backend: String, message: String },
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::SourceDisabled { .. } => StatusCode::CONFLICT,
+            Self::SourceRuleMissing { .. }
+            | Self::ParseFailed { .. }
+            | Self::JsError { .. }
+            | Self::JsTimeout { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Network { .. } | Self::Tts { .. } => StatusCode::BAD_GATEWAY,
+            Self::DeadlineExceeded { .. } => StatusCode::GATEWAY_TIMEOUT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "NOT_FOUND",
+            Self::BadRequest(_) => "BAD_REQUEST",
+            Self::Unauthorized(_) => "UNAUTHORIZED",
+            Self::Forbidden(_) => "FORBIDDEN",
+            Self::PayloadTooLarge { .. } => "PAYLOAD_TOO_LARGE",
+            Self::RateLimited { .. } => "RATE_LIMITED",
+            Self::SourceRuleMissing { .. } => "SOURCE_RULE_MISSING",
+            Self::SourceDisabled { .. } => "SOURCE_DISABLED",
+            Self::Network { .. } => "NETWORK",
+            Self::ParseFailed { .. } => "PARSE_FAILED",
+            Self::JsError { .. } => "JS_ERROR",
+            Self::JsTimeout { .. } => "JS_TIMEOUT",
+            Self::DeadlineExceeded { .. } => "DEADLINE_EXCEEDED",
+            Self::Tts { .. } => "TTS_FAILED",
+            Self::Internal(_) => "INTERNAL",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::NotFound(msg)
+            | Self::BadRequest(msg)
+            | Self::Unauthorized(msg)
+            | Self::Forbidden(msg)
+            | Self::Internal(msg) => msg.clone(),
+            Self::RateLimited { retry_after } => format!("Too many requests, retry after {}s", retry_after),
+            Self::SourceRuleMissing { field } => format!("Source rule missing: {}", field),
+            Self::SourceDisabled { url } => format!("Source disabled: {}", url),
+            Self::Network { message, .. }
+            | Self::ParseFailed { message, .. }
+            | Self::JsError { message }
+            | Self::JsTimeout { message, .. }
+            | Self::DeadlineExceeded { message, .. }
+            | Self::Tts { message, .. }
+            | Self::PayloadTooLarge { message, .. } => message.clone(),
+        }
+    }
+
+    fn detail(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::RateLimited { retry_after } => Some(json!({ "retryAfter": retry_after })),
+            Self::PayloadTooLarge { max_bytes, .. } => Some(json!({ "maxBytes": max_bytes })),
+            Self::SourceRuleMissing { field } => Some(json!({ "field": field })),
+            Self::SourceDisabled { url } => Some(json!({ "bookSourceUrl": url })),
+            Self::Network { url, kind, .. } => Some(json!({ "url": url, "kind": kind })),
+            Self::ParseFailed { rule, stage, .. } => Some(json!({ "rule": rule, "stage": stage })),
+            Self::JsTimeout {
+                source_url, timeout_ms, ..
+            } => Some(json!({ "bookSourceUrl": source_url, "timeoutMs": timeout_ms })),
+            Self::DeadlineExceeded { budget_ms, partial, .. } => Some(match partial {
+                Some(partial) => json!({
+                    "budgetMs": budget_ms,
+                    "pagesFetched": partial.pages,
+                    "partialContent": partial.content,
+                }),
+                None => json!({ "budgetMs": budget_ms, "pagesFetched": 0 }),
+            }),
+            Self::Tts { backend, .. } => Some(json!({ "backend": backend })),
+            _ => None,
+        }
+    }
+
+    fn from_engine(err: &EngineError, message: String) -> Self {
+        match err {
+            EngineError::RuleMissing(field) => Self::SourceRuleMissing {
+                field: field.clone(),
+            },
+            EngineError::ParseFailed { rule, stage } => Self::ParseFailed {
+                rule: rule.clone(),
+                stage: stage.clone(),
+                message,
+            },
+            EngineError::JavaScript(_) | EngineError::JsAnalysis(_) => Self::JsError { message },
+            EngineError::JsTimeout { source_url, timeout_ms } => Self::JsTimeout {
+                source_url: source_url.clone(),
+                timeout_ms: *timeout_ms,
+                message,
+            },
+            EngineError::DeadlineExceeded { budget_ms, partial } => Self::DeadlineExceeded {
+                budget_ms: *budget_ms,
+                partial: partial.clone(),
+                message,
+            },
+            EngineError::SourceCoolingDown { retry_after_secs, .. } => Self::RateLimited {
+                retry_after: *retry_after_secs,
+            },
+            EngineError::Http(_) | EngineError::UrlParse(_) => Self::Network {
+                url: String::new(),
+                kind: "request".to_string(),
+                message,
+            },
+            _ => Self::Internal(message),
+        }
+    }
+
+    fn from_reqwest(err: &reqwest::Error, message: String) -> Self {
+        let kind = if err.is_timeout() {
+            "timeout"
+        } else if err.is_connect() {
+            "connect"
+        } else if err.is_status() {
+            "status"
+        } else if err.is_decode() || err.is_body() {
+            "body"
+        } else {
+            "request"
+        };
+        Self::Network {
+            url: err.url().map(|u| u.to_string()).unwrap_or_default(),
+            kind: kind.to_string(),
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::from(&err)
+    }
+}
+
+impl From<&anyhow::Error> for ApiError {
+    fn from(err: &anyhow::Error) -> Self {
+        let message = format!("{:#}", err);
+
+        // 先检查最外层 (含 context)，再沿错误链查找
+        if let Some(e) = err.downcast_ref::<ServiceError>() {
+            return match e {
+                ServiceError::NotFound { .. } => Self::NotFound(e.to_string()),
+                ServiceError::InvalidInput(_) => Self::BadRequest(e.to_string()),
+                ServiceError::SourceDisabled(url) => Self::SourceDisabled { url: url.clone() },
+                ServiceError::Unauthorized(_) => Self::Unauthorized(e.to_string()),
+            };
+        }
+        if let Some(e) = err.downcast_ref::<EngineError>() {
+            return Self::from_engine(e, message);
+        }
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<EngineError>() {
+                return Self::from_engine(e, message);
+            }
+            if let Some(e) = cause.downcast_ref::<TtsError>() {
+                return Self::Tts {
+                    backend: e.backend.clone(),
+                    message,
+                };
+            }
+            if let Some(e) = cause.downcast_ref::<SandboxError>() {
+                return match e {
+                    SandboxError::TooLarge { max_bytes, .. } => Self::PayloadTooLarge {
+                        max_bytes: *max_bytes,
+                        message,
+                    },
+                    _ => Self::Forbidden(message),
+                };
+            }
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                return Self::from_reqwest(e, message);
+            }
+            if cause.is::<serde_json::Error>() {
+                return Self::BadRequest(message);
+            }
+        }
+        Self::Internal(message)
+    }
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(err: ServiceError) -> Self {
+        anyhow::Error::from(err).into()
+    }
+}
+
+impl From<SandboxError> for ApiError {
+    fn from(err: SandboxError) -> Self {
+        anyhow::Error::from(err).into()
+    }
+}
+
+impl From<tokio::task::JoinError> for ApiError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiResponse::<()>::error_with(self.code(), &self.message(), self.detail());
+        let mut resp = (self.status(), Json(body)).into_response();
+        if let Self::RateLimited { retry_after } = self {
+            resp.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_engine_error_mapping() {
+        let err: ApiError = anyhow::Error::from(EngineError::rule_missing("searchUrl")).into();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.detail().unwrap()["field"], "searchUrl");
+
+        let err: ApiError = Err::<(), _>(anyhow::anyhow!("selector error"))
+            .context(EngineError::parse_failed("@css:.list", "toc"))
+            .unwrap_err()
+            .into();
+        assert_eq!(err.code(), "PARSE_FAILED");
+        assert!(err.to_string().contains("selector error"));
+
+        let err: ApiError = anyhow::Error::from(EngineError::javascript("boom")).into();
+        assert_eq!(err.code(), "JS_ERROR");
+
+        let timeout = EngineError::js_timeout("https://slow.example.com", std::time::Duration::from_secs(5));
+        let err: ApiError = anyhow::Error::from(timeout).into();
+        assert_eq!(err.code(), "JS_TIMEOUT");
+        assert!(err.to_string().contains("https://slow.example.com"));
+        assert_eq!(err.detail().unwrap()["timeoutMs"], 5000);
+
+        let deadline = EngineError::DeadlineExceeded {
+            budget_ms: 300,
+            partial: Some(PartialContent {
+                pages: 2,
+                content: "第一页\n第二页".to_string(),
+            }),
+        };
+        let err: ApiError = anyhow::anyhow!("operation timed out").context(deadline).into();
+        assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(err.code(), "DEADLINE_EXCEEDED");
+        let detail = err.detail().unwrap();
+        assert_eq!(detail["budgetMs"], 300);
+        assert_eq!(detail["pagesFetched"], 2);
+        assert_eq!(detail["partialContent"], "第一页\n第二页");
+
+        let cooling = EngineError::source_cooling_down("https://busy.example.com", std::time::Duration::from_millis(1500));
+        let err: ApiError = anyhow::Error::from(cooling).into();
+        assert_eq!(err.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.detail().unwrap()["retryAfter"], 2);
+
+        let err: ApiError = anyhow::anyhow!("something").into();
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_network_error_mapping() {
+        let reqwest_err = reqwest::get("http://127.0.0.1:1/").await.unwrap_err();
+        let err: ApiError = anyhow::Error::from(reqwest_err).into();
+        assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.detail().unwrap()["kind"], "connect");
+    }
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::sync::Arc;
+
+use crate::engine::book_source::{BookItem, ExploreKind};
+use crate::models::{ApiResponse, BookSourceFull};
+use crate::services::AppState;
+use super::error::ApiResult;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExploreKindsQuery {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExploreBooksQuery {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+    #[serde(rename = "ruleFindUrl")]
+    pub rule_find_url: String,
+    pub page: Option<i32>,
+}
+
+/// GET /getExploreSources - 获取已启用且开启发现的书源
+pub async fn get_explore_sources(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Vec<BookSourceFull>> {
+    let sources = state.source_service.get_explore_sources().await?;
+    Ok(Json(ApiResponse::success(sources)))
+}
+
+/// GET /getExploreKinds - 获取书源发现分类
+pub async fn get_explore_kinds(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExploreKindsQuery>,
+) -> ApiResult<Vec<ExploreKind>> {
+    let kinds = state
+        .source_service
+        .get_explore_kinds(&query.book_source_url)
+        .await?;
+    Ok(Json(ApiResponse::success(kinds)))
+}
+
+/// GET /exploreBooks - 发现书籍
+pub async fn explore_books(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExploreBooksQuery>,
+) -> ApiResult<Vec<BookItem>> {
+    let page = query.page.unwrap_or(1).max(1);
+    let books = state
+        .source_service
+        .explore_books(&query.book_source_url, &query.rule_find_url, page)
+        .await?;
+    Ok(Json(ApiResponse::success(books)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_server::{request_path, spawn_server};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    fn create_test_state(name: &str) -> Arc<AppState> {
+        let dir = format!("/tmp/reader_tests_api_explore_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        Arc::new(AppState::with_storage_dir(&dir))
+    }
+
+    #[tokio::test]
+    async fn test_explore_books_parses_items() {
+        let state = create_test_state("books");
+        let base = spawn_server(|head, _| {
+            let body = match request_path(head) {
+                "/rank/2" => concat!(
+                    r#"<div class="book"><a href="/book/1">第一本</a><span>作者甲</span></div>"#,
+                    r#"<div class="book"><a href="/book/2">第二本</a><span>作者乙</span></div>"#,
+                ),
+                _ => "",
+            };
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        });
+        let source = serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "发现书源",
+            "exploreUrl": "排行::/rank/{{page}}",
+            "ruleExplore": {
+                "bookList": "@css:div.book",
+                "name": "@css:a@text",
+                "author": "@css:span@text",
+                "bookUrl": "@css:a@href"
+            }
+        });
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+
+        let query = ExploreBooksQuery {
+            book_source_url: base.clone(),
+            rule_find_url: "/rank/{{page}}".to_string(),
+            page: Some(2),
+        };
+        let resp = explore_books(State(state), Query(query)).await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let books = body["data"].as_array().unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0]["name"], "第一本");
+        assert_eq!(books[0]["author"], "作者甲");
+        assert_eq!(books[0]["bookUrl"], format!("{}/book/1", base));
+        assert_eq!(books[1]["name"], "第二本");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_source_returns_not_found() {
+        let state = create_test_state("missing");
+
+        let query = ExploreKindsQuery {
+            book_source_url: "https://missing.example.com".to_string(),
+        };
+        let resp = get_explore_kinds(State(state), Query(query))
+            .await
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["isSuccess"], false);
+        assert_eq!(body["errorCode"], "NOT_FOUND");
+    }
+}
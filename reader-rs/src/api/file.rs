@@ -1,20 +1,26 @@
 use axum::{
-    extract::Query,
+    extract::{Query, State},
     response::Json,
 };
+use once_cell::sync::Lazy;
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use std::sync::Arc;
 
 use crate::models::ApiResponse;
-use crate::storage::FileStorage;
+use crate::services::AppState;
+use super::error::ApiResult;
+use crate::storage::sandbox::{FileSaveLimits, SandboxError};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct FileGetQuery {
     pub path: String,
     #[serde(default)]
     pub home: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct FileSaveRequest {
     pub path: String,
     pub content: String,
@@ -22,26 +28,112 @@ pub struct FileSaveRequest {
     pub home: String,
 }
 
-/// GET /file/get - 获取文件内容
+/// /file/save 的大小与类型限制，来自环境变量
+static SAVE_LIMITS: Lazy<FileSaveLimits> = Lazy::new(FileSaveLimits::from_env);
+
+/// GET /file/get - 获取数据目录下的文件内容，路径越出数据目录时返回 403
 pub async fn file_get(
+    State(state): State<Arc<AppState>>,
     Query(query): Query<FileGetQuery>,
-) -> Json<ApiResponse<String>> {
-    let storage = FileStorage::default();
-    
-    match storage.read_file(&query.path).await {
-        Ok(content) => Json(ApiResponse::success(content)),
-        Err(_) => Json(ApiResponse::success(String::new())), // 文件不存在返回空
+) -> ApiResult<String> {
+    match state.storage.read_file(&query.path).await {
+        Ok(content) => Ok(Json(ApiResponse::success(content))),
+        Err(e) if e.is::<SandboxError>() => Err(e.into()),
+        Err(_) => Ok(Json(ApiResponse::success(String::new()))), // 文件不存在返回空
     }
 }
 
 /// POST /file/save - 保存文件内容
+///
+/// 只允许写入数据目录内 FILE_SAVE_EXTENSIONS 类型的文件，越界或类型不符返回 403，
+/// 超过 FILE_SAVE_MAX_BYTES 返回 413。
 pub async fn file_save(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<FileSaveRequest>,
-) -> Json<ApiResponse<bool>> {
-    let storage = FileStorage::default();
-    
-    match storage.write_file(&req.path, &req.content).await {
-        Ok(_) => Json(ApiResponse::success(true)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<bool> {
+    SAVE_LIMITS.check(&req.path, req.content.len() as u64)?;
+    state.storage.write_file(&req.path, &req.content).await?;
+    Ok(Json(ApiResponse::success(true)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    fn create_test_state(name: &str) -> Arc<AppState> {
+        let dir = format!("/tmp/reader_tests_api_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        Arc::new(AppState::with_storage_dir(&dir))
+    }
+
+    async fn into_json(resp: impl IntoResponse) -> (StatusCode, serde_json::Value) {
+        let resp = resp.into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn save(path: &str, content: String) -> Json<FileSaveRequest> {
+        Json(FileSaveRequest {
+            path: path.to_string(),
+            content,
+            home: String::new(),
+        })
+    }
+
+    fn get(path: &str) -> Query<FileGetQuery> {
+        Query(FileGetQuery {
+            path: path.to_string(),
+            home: String::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_file_api_sandbox() {
+        let state = create_test_state("file_sandbox");
+        let (status, _) = into_json(file_save(State(state.clone()), save("sub/subs.json", "[]".into())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = into_json(file_get(State(state.clone()), get("sub/subs.json")).await).await;
+        assert_eq!(body["data"], "[]");
+        let (_, body) = into_json(file_get(State(state.clone()), get("missing.json")).await).await;
+        assert_eq!(body["data"], "");
+
+        for path in ["../escape.json", "sub/../../escape.json", "/tmp/escape.json"] {
+            let (status, body) = into_json(file_save(State(state.clone()), save(path, "{}".into())).await).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+            assert_eq!(body["errorCode"], "FORBIDDEN");
+            let (status, _) = into_json(file_get(State(state.clone()), get(path)).await).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+        }
+        assert!(!std::path::Path::new("/tmp/reader_tests_api_file_sandbox/escape.json").exists());
+
+        let (status, _) = into_json(file_save(State(state.clone()), save("run.sh", "ls".into())).await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let oversized = "a".repeat(SAVE_LIMITS.max_bytes as usize + 1);
+        let (status, body) = into_json(file_save(State(state.clone()), save("big.txt", oversized)).await).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["detail"]["maxBytes"], SAVE_LIMITS.max_bytes);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_api_rejects_escaping_symlink() {
+        let state = create_test_state("file_symlink");
+        let outside = std::env::temp_dir().join(format!("reader_tests_file_outside_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        let data_dir = state.storage.file_path("");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::os::unix::fs::symlink(&outside, data_dir.join("link")).unwrap();
+
+        let (status, _) = into_json(file_get(State(state.clone()), get("link/secret.txt")).await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = into_json(file_save(State(state.clone()), save("link/new.txt", "x".into())).await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(!outside.join("new.txt").exists());
+        let _ = std::fs::remove_dir_all(&outside);
     }
 }
@@ -3,25 +3,20 @@ use axum::{
     response::Json,
 };
 use serde::Deserialize;
+use utoipa::ToSchema;
 use std::sync::Arc;
 
 use crate::models::{BookGroup, ApiResponse};
-use crate::services::AppState;
+use crate::services::{AppState, GroupOrderItem};
+use super::error::ApiResult;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DeleteGroupRequest {
     #[serde(rename = "groupId")]
     pub group_id: i64,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GroupOrderItem {
-    #[serde(rename = "groupId")]
-    pub group_id: i64,
-    pub order: i32,
-}
-
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SaveGroupOrderRequest {
     pub order: Vec<GroupOrderItem>,
 }
@@ -29,42 +24,34 @@ pub struct SaveGroupOrderRequest {
 /// GET /getBookGroups - 获取分组列表
 pub async fn get_book_groups(
     State(state): State<Arc<AppState>>,
-) -> Json<ApiResponse<Vec<BookGroup>>> {
-    match state.group_service.get_all_groups().await {
-        Ok(groups) => Json(ApiResponse::success(groups)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<Vec<BookGroup>> {
+    let groups = state.group_service.get_all_groups().await?;
+    Ok(Json(ApiResponse::success(groups)))
 }
 
 /// POST /saveBookGroup - 保存分组
 pub async fn save_book_group(
     State(state): State<Arc<AppState>>,
     Json(group): Json<BookGroup>,
-) -> Json<ApiResponse<BookGroup>> {
-    match state.group_service.save_group(group).await {
-        Ok(saved) => Json(ApiResponse::success(saved)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<BookGroup> {
+    let saved = state.group_service.save_group(group).await?;
+    Ok(Json(ApiResponse::success(saved)))
 }
 
-/// POST /deleteBookGroup - 删除分组
+/// POST /deleteBookGroup, /removeBookGroup - 删除分组，并从书籍清除该分组位
 pub async fn delete_book_group(
     State(state): State<Arc<AppState>>,
     Json(req): Json<DeleteGroupRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.group_service.delete_group(req.group_id).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.group_service.delete_group(req.group_id).await?;
+    Ok(Json(ApiResponse::success(())))
 }
 
-/// POST /saveBookGroupOrder - 保存分组顺序
+/// POST /saveBookGroupOrder - 保存分组顺序 (需提交完整的分组列表)
 pub async fn save_book_group_order(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SaveGroupOrderRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.group_service.save_group_order(req.order).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.group_service.save_group_order(req.order).await?;
+    Ok(Json(ApiResponse::success(())))
 }
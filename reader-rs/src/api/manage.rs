@@ -1,14 +1,25 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
     response::Json,
 };
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 use std::sync::Arc;
 
 use crate::models::{Book, ApiResponse};
 use crate::services::AppState;
+use crate::storage::ReclaimedCache;
+use super::error::ApiResult;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteBooksQuery {
+    /// 同时删除正文、目录、封面等缓存，默认 true
+    #[serde(rename = "deleteCache")]
+    pub delete_cache: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct GroupMultiRequest {
     #[serde(rename = "groupId")]
     pub group_id: i64,
@@ -16,35 +27,38 @@ pub struct GroupMultiRequest {
     pub book_list: Vec<Book>,
 }
 
-/// POST /deleteBooks - 批量删除书籍
+/// POST /deleteBooks?deleteCache=false - 批量删除书籍，返回回收的缓存文件数与字节数
 pub async fn delete_books(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<DeleteBooksQuery>,
     Json(books): Json<Vec<Book>>,
-) -> Json<ApiResponse<()>> {
-    match state.book_service.delete_books(books).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<ReclaimedCache> {
+    let urls: Vec<String> = books.into_iter().map(|b| b.book_url).collect();
+    for url in &urls {
+        state.prefetcher.cancel(url);
+        state.book_cacher.cancel_book(url);
     }
+    let reclaimed = state
+        .book_service
+        .delete_books(&urls, query.delete_cache.unwrap_or(true))
+        .await?;
+    Ok(Json(ApiResponse::success(reclaimed)))
 }
 
 /// POST /addBookGroupMulti - 批量加入分组
 pub async fn add_book_group_multi(
     State(state): State<Arc<AppState>>,
     Json(req): Json<GroupMultiRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.book_service.add_books_to_group(req.group_id, req.book_list).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.book_service.add_books_to_group(req.group_id, req.book_list).await?;
+    Ok(Json(ApiResponse::success(())))
 }
 
 /// POST /removeBookGroupMulti - 批量移出分组
 pub async fn remove_book_group_multi(
     State(state): State<Arc<AppState>>,
     Json(req): Json<GroupMultiRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.book_service.remove_books_from_group(req.group_id, req.book_list).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.book_service.remove_books_from_group(req.group_id, req.book_list).await?;
+    Ok(Json(ApiResponse::success(())))
 }
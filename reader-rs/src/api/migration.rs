@@ -3,46 +3,26 @@ use axum::{
     response::Json,
 };
 use serde::Deserialize;
+use utoipa::ToSchema;
 use std::sync::Arc;
 
 use crate::models::ApiResponse;
-use crate::services::AppState;
+use crate::services::{AppState, LegacyImportReport, Migration};
+use super::error::ApiResult;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct MigrationRequest {
-    /// 旧版存储目录路径
+    /// 旧版 storage 目录或其中的用户目录路径
     pub path: String,
+    /// 旧版用户名，缺省为 default
+    pub user: Option<String>,
 }
 
-/// POST /migrate - 从旧版 Kotlin 后端迁移数据
+/// POST /migrate - 从旧版 Kotlin 后端迁移数据，返回各类数据的导入、跳过与失败明细
 pub async fn migrate(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Json(req): Json<MigrationRequest>,
-) -> Json<ApiResponse<MigrationSummary>> {
-    use crate::services::Migration;
-    
-    let migration = Migration::new();
-    
-    match migration.migrate_from_legacy(&req.path).await {
-        Ok(result) => {
-            let summary = MigrationSummary {
-                sources: result.sources_migrated,
-                books: result.books_migrated,
-                rules: result.rules_migrated,
-                groups: result.groups_migrated,
-                total: result.total(),
-            };
-            Json(ApiResponse::success(summary))
-        }
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
-}
-
-#[derive(Debug, serde::Serialize)]
-pub struct MigrationSummary {
-    pub sources: usize,
-    pub books: usize,
-    pub rules: usize,
-    pub groups: usize,
-    pub total: usize,
+) -> ApiResult<LegacyImportReport> {
+    let report = Migration::import_legacy(&state, &req.path, req.user.as_deref()).await?;
+    Ok(Json(ApiResponse::success(report)))
 }
@@ -1,36 +1,123 @@
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
     routing::{get, post},
     Router,
 };
+use futures::Stream;
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
 
+mod access;
+mod backup;
 mod book;
+mod content_filter;
+mod error;
+mod explore;
 mod file;
 pub mod group;
 mod manage;
 mod migration;
+mod openapi;
+mod opds;
+mod reading_stats;
 mod replace;
 mod source;
+mod user;
 
 use crate::services::AppState;
 
-pub fn routes() -> Router {
-    let state = Arc::new(AppState::new());
+pub use access::AccessConfig;
+pub(crate) use error::ApiError;
 
+/// SSE 保活注释的发送间隔
+const SSE_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// SSE 响应：空闲时定期发送 `: ping` 注释，避免代理断开长时间无数据的连接
+fn sse<S>(stream: S) -> Sse<S>
+where
+    S: Stream<Item = Result<Event, Infallible>> + Send + 'static,
+{
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(SSE_KEEP_ALIVE_INTERVAL).text("ping"))
+}
+
+/// 全部接口；多用户模式下需先登录，请求转发给当前用户的服务
+///
+/// 配置了访问令牌或限流时，在最外层校验令牌，通过后再按 IP 限流。
+/// 接口文档 (/openapi.json、/swagger) 不需要登录。
+pub fn routes(state: Arc<AppState>, access: &AccessConfig) -> Router {
+    let mut router = match state.users.clone() {
+        Some(users) => user::routes(users),
+        None => reader_routes(state),
+    };
+    router = router
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/swagger", get(openapi::swagger_ui));
+    if let Some(limiter) = access.rate_limiter() {
+        router = router.layer(middleware::from_fn_with_state(limiter, access::rate_limit));
+    }
+    if let Some(token) = &access.token {
+        router = router.layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token.as_str()),
+            access::require_access_token,
+        ));
+    }
+    router
+}
+
+/// 一组服务 (单用户模式或多用户模式下的一个用户) 的接口
+fn reader_routes(state: Arc<AppState>) -> Router {
     Router::new()
         // 书籍 API
         .route("/getBookshelf", get(book::get_bookshelf))
+        .route("/refreshBookshelf", post(book::refresh_bookshelf))
+        .route("/setBookCanUpdate", post(book::set_book_can_update))
+        .route("/updateFailures", get(book::update_failures))
         .route("/getChapterList", get(book::get_chapter_list))
         .route("/getBookContent", get(book::get_book_content))
+        .route("/getBookContentSSE", get(book::get_book_content_sse))
+        .route("/getChapterVersions", get(book::get_chapter_versions))
+        .route("/getChapterDiff", get(book::get_chapter_diff))
+        .route("/prefetchStatus", get(book::prefetch_status))
+        .route("/cacheBook", post(book::cache_book))
+        .route("/cacheBookProgress", get(book::cache_book_progress))
+        .route("/cancelCacheBook", post(book::cancel_cache_book))
+        .route("/getChapterAudio", get(book::get_chapter_audio))
+        .route("/getAudioUrl", get(book::get_audio_url))
+        .route("/audioProxy", get(book::audio_proxy))
+        .route("/imageProxy", get(book::image_proxy))
         .route("/getBookInfo", get(book::get_book_info))
         .route("/search", get(book::search))
         .route("/local_search", get(book::local_search))
+        .route("/rebuildSearchIndex", post(book::rebuild_search_index))
         .route("/searchBookMultiSSE", get(book::search_book_multi_sse))
+        .route("/searchMerged", get(book::search_merged))
+        .route("/searchMergedOrigins", get(book::search_merged_origins))
         .route("/saveBook", post(book::save_book))
         .route("/deleteBook", post(book::delete_book))
         .route("/saveBookProgress", post(book::save_book_progress))
+        .route("/getBookProgress", get(book::get_book_progress))
+        .route("/saveBookMetadata", post(book::save_book_metadata))
+        .route("/clearBookMetadata", post(book::clear_book_metadata))
+        .route(
+            "/uploadBookCover",
+            post(book::upload_book_cover).layer(DefaultBodyLimit::max(book::COVER_UPLOAD_MAX_BYTES)),
+        )
+        .route("/getBookVariable", get(book::get_book_variable))
+        .route("/saveBookVariable", post(book::save_book_variable))
+        .route("/clearBookCache", post(book::clear_book_cache))
+        .route("/exportBook", get(book::export_book))
+        .route("/exportBookshelf", get(book::export_bookshelf))
+        .route(
+            "/importLocalBook",
+            post(book::import_local_book).layer(DefaultBodyLimit::max(book::LOCAL_BOOK_MAX_BYTES)),
+        )
         // 书源 API
         .route("/getBookSources", get(source::get_book_sources))
+        .route("/getSourceStats", get(source::get_source_stats))
+        .route("/sourceHealthReport", get(source::source_health_report))
         .route(
             "/getAvailableBookSource",
             post(source::get_available_book_source),
@@ -46,21 +133,48 @@ pub fn routes() -> Router {
         )
         .route("/saveBookSources", post(source::save_book_sources))
         .route("/injectCookies", post(source::inject_cookies))
+        .route("/getLoginInfo", get(source::get_login_info))
+        .route("/loginBookSource", post(source::login_book_source))
+        .route("/logoutBookSource", post(source::logout_book_source))
+        .route("/getSourceVariable", get(source::get_source_variable))
+        .route("/saveSourceVariable", post(source::save_source_variable))
+        .route("/clearRuleCache", post(source::clear_rule_cache))
         .route("/testBookSource", post(source::test_book_source))
+        .route("/testBookSources", post(source::test_book_sources))
+        .route("/debugBookSource", post(source::debug_book_source))
+        .route("/validateSourceRule", post(source::validate_source_rule))
+        .route("/inspectSource", get(source::inspect_source))
         .route("/deleteBookSources", post(source::delete_book_sources))
+        .route("/enableBookSources", post(source::enable_book_sources))
+        .route("/disableBookSources", post(source::disable_book_sources))
         .route(
             "/saveFromRemoteSource",
             post(source::save_from_remote_source),
         )
+        .route("/addSourceSubscription", post(source::add_source_subscription))
+        .route("/getSourceSubscriptions", get(source::get_source_subscriptions))
+        .route("/refreshSourceSubscription", post(source::refresh_source_subscription))
+        .route("/deleteSourceSubscription", post(source::delete_source_subscription))
+        // 发现 API
+        .route("/getExploreSources", get(explore::get_explore_sources))
+        .route("/getExploreKinds", get(explore::get_explore_kinds))
+        .route("/exploreBooks", get(explore::explore_books))
         // 替换规则 API
         .route("/getReplaceRules", get(replace::get_replace_rules))
         .route("/saveReplaceRule", post(replace::save_replace_rule))
         .route("/saveReplaceRules", post(replace::save_replace_rules))
         .route("/deleteReplaceRules", post(replace::delete_replace_rules))
+        // 正文净化规则 API
+        .route(
+            "/contentFilters",
+            get(content_filter::get_content_filters).post(content_filter::save_content_filters),
+        )
+        .route("/deleteContentFilters", post(content_filter::delete_content_filters))
         // 分组 API
         .route("/getBookGroups", get(group::get_book_groups))
         .route("/saveBookGroup", post(group::save_book_group))
         .route("/deleteBookGroup", post(group::delete_book_group))
+        .route("/removeBookGroup", post(group::delete_book_group))
         .route("/saveBookGroupOrder", post(group::save_book_group_order))
         // 批量管理 API
         .route("/deleteBooks", post(manage::delete_books))
@@ -74,17 +188,87 @@ pub fn routes() -> Router {
         // 文件 API
         .route("/file/get", get(file::file_get))
         .route("/file/save", post(file::file_save))
+        // 备份 API
+        .route("/backupToWebdav", post(backup::backup_to_webdav))
+        .route("/restoreFromWebdav", post(backup::restore_from_webdav))
+        .route("/exportData", get(backup::export_data))
+        .route(
+            "/importData",
+            post(backup::import_data).layer(DefaultBodyLimit::max(backup::IMPORT_DATA_MAX_BYTES)),
+        )
+        // 阅读统计 API
+        .route("/heartbeat", post(reading_stats::heartbeat))
+        .route("/readingStats", get(reading_stats::get_reading_stats))
+        .route("/readingHistory", get(reading_stats::get_reading_history))
+        // OPDS 目录
+        .route("/opds", get(opds::root))
+        .route("/opds/books", get(opds::books))
+        .route("/opds/book", get(opds::book))
+        .route("/opds/search", get(opds::search))
+        .route("/opds/opensearch.xml", get(opds::opensearch))
         // 静态资源
         .route("/cover", get(book::get_cover))
+        .route("/assets/:book_id/*path", get(book::get_asset))
         // 统计 API
         .route("/stats", get(get_stats))
         .route("/stats/reset", post(reset_stats))
+        // 后台任务
+        .route("/jobs", get(get_jobs))
         .with_state(state)
 }
 
+/// GET /jobs - 后台任务的运行状态 (最近执行时间、错误、是否正在执行)
+async fn get_jobs(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+) -> error::ApiResult<Vec<crate::services::JobStatus>> {
+    Ok(axum::Json(crate::models::ApiResponse::success(state.jobs.statuses())))
+}
+
+/// Query parameters of `/stats`
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StatsParams {
+    /// Restrict the detail breakdown to one book source URL
+    source: Option<String>,
+    /// Trailing window such as `1h`, `15m` or `30`
+    window: Option<String>,
+}
+
 /// Get execution statistics
-async fn get_stats() -> axum::Json<crate::engine::stats::StatsSnapshot> {
-    axum::Json(crate::engine::stats::STATS.snapshot())
+async fn get_stats(
+    axum::extract::Query(params): axum::extract::Query<StatsParams>,
+) -> Result<axum::Json<crate::engine::stats::StatsSnapshot>, error::ApiError> {
+    let window_minutes = params
+        .window
+        .as_deref()
+        .map(|window| {
+            parse_window_minutes(window).ok_or_else(|| {
+                error::ApiError::BadRequest(format!(
+                    "window must be between 1m and {}m: {}",
+                    crate::engine::stats::WINDOW_MINUTES,
+                    window
+                ))
+            })
+        })
+        .transpose()?;
+    let query = crate::engine::stats::StatsQuery {
+        source: params.source.filter(|s| !s.is_empty()),
+        window_minutes,
+    };
+    Ok(axum::Json(crate::engine::stats::STATS.snapshot_with(&query)))
+}
+
+/// Parse `1h`, `15m` or a plain number of minutes, within the tracked window
+fn parse_window_minutes(window: &str) -> Option<u64> {
+    let window = window.trim();
+    let minutes = if let Some(hours) = window.strip_suffix('h') {
+        hours.parse::<u64>().ok()?.checked_mul(60)?
+    } else {
+        window.strip_suffix('m').unwrap_or(window).parse::<u64>().ok()?
+    };
+    (1..=crate::engine::stats::WINDOW_MINUTES)
+        .contains(&minutes)
+        .then_some(minutes)
 }
 
 /// Reset execution statistics
@@ -0,0 +1,218 @@
+//! OPDS 目录：供 KOReader 等阅读器浏览书架并下载 EPUB
+
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{header, Uri},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::sync::Arc;
+
+use super::error::ApiError;
+use crate::services::{
+    find_group, AppState, BookPage, OpdsCatalog, ShelfQuery, ACQUISITION_FEED_TYPE, ENTRY_TYPE, GROUP_ALL,
+    NAVIGATION_FEED_TYPE, OPENSEARCH_TYPE,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OpdsBooksQuery {
+    /// 分组位掩码或虚拟分组，默认全部书籍
+    pub group: Option<i64>,
+    pub page: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OpdsBookQuery {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OpdsSearchQuery {
+    pub q: String,
+    pub page: Option<usize>,
+}
+
+/// 由完整请求路径与路由内路径得到接口前缀 (如 `/reader3`)
+fn catalog(original: &Uri, uri: &Uri) -> OpdsCatalog {
+    let path = original.path();
+    let base = path.strip_suffix(uri.path()).unwrap_or("");
+    OpdsCatalog::new(base)
+}
+
+fn xml(content_type: &'static str, body: String) -> Response {
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+/// GET /opds - 根目录，列出书架分组
+pub async fn root(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(original): OriginalUri,
+    uri: Uri,
+) -> Result<Response, ApiError> {
+    let groups = state.group_service.get_all_groups().await?;
+    let books = state.book_service.get_bookshelf(false).await?;
+    Ok(xml(
+        NAVIGATION_FEED_TYPE,
+        catalog(&original, &uri).root(&groups, &books),
+    ))
+}
+
+/// GET /opds/books - 分组内的书籍，按页返回
+pub async fn books(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(original): OriginalUri,
+    uri: Uri,
+    Query(query): Query<OpdsBooksQuery>,
+) -> Result<Response, ApiError> {
+    let group_id = query.group.unwrap_or(GROUP_ALL);
+    let groups = state.group_service.get_all_groups().await?;
+    let group =
+        find_group(groups, group_id).ok_or_else(|| ApiError::NotFound(format!("Group not found: {}", group_id)))?;
+    let shelf_query = ShelfQuery {
+        group: Some(group_id),
+        ..Default::default()
+    };
+    let shelf = state.book_service.query_bookshelf(false, &shelf_query).await?;
+    let page = BookPage::paginate(shelf.books, query.page.unwrap_or(1));
+    Ok(xml(
+        ACQUISITION_FEED_TYPE,
+        catalog(&original, &uri).group(&group, &page),
+    ))
+}
+
+/// GET /opds/book - 单本书籍的条目
+pub async fn book(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(original): OriginalUri,
+    uri: Uri,
+    Query(query): Query<OpdsBookQuery>,
+) -> Result<Response, ApiError> {
+    let book = state
+        .book_service
+        .get_bookshelf(false)
+        .await?
+        .into_iter()
+        .find(|b| b.book_url == query.url)
+        .ok_or_else(|| ApiError::NotFound(format!("Book not found: {}", query.url)))?;
+    Ok(xml(ENTRY_TYPE, catalog(&original, &uri).entry(&book)))
+}
+
+/// GET /opds/search - 在书架中搜索 (不搜索书源)
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(original): OriginalUri,
+    uri: Uri,
+    Query(query): Query<OpdsSearchQuery>,
+) -> Result<Response, ApiError> {
+    let books = state.book_service.search_bookshelf(&query.q).await?;
+    let page = BookPage::paginate(books, query.page.unwrap_or(1));
+    Ok(xml(
+        ACQUISITION_FEED_TYPE,
+        catalog(&original, &uri).search(&query.q, &page),
+    ))
+}
+
+/// GET /opds/opensearch.xml - OpenSearch 描述文件
+pub async fn opensearch(OriginalUri(original): OriginalUri, uri: Uri) -> Response {
+    xml(OPENSEARCH_TYPE, catalog(&original, &uri).opensearch())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::AccessConfig;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::{Method, StatusCode};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, method: Method, uri: &str, body: serde_json::Value) -> (StatusCode, String, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let content_type = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_opds_routes() {
+        let dir = "/tmp/reader_tests_api_opds";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = Arc::new(AppState::with_storage_dir(dir));
+        let app = Router::new().nest("/reader3", super::super::routes(state, &AccessConfig::default()));
+
+        let book = serde_json::json!({ "bookUrl": "https://example.com/book/1", "name": "雪中悍刀行", "author": "烽火戏诸侯" });
+        let (status, _, _) = call(&app, Method::POST, "/reader3/saveBook", book).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, content_type, body) = call(&app, Method::GET, "/reader3/opds", serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, NAVIGATION_FEED_TYPE);
+        assert!(body.contains("/reader3/opds/books?group=-1"));
+
+        let (status, content_type, body) = call(
+            &app,
+            Method::GET,
+            "/reader3/opds/books?group=-1",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, ACQUISITION_FEED_TYPE);
+        assert!(body.contains("雪中悍刀行"));
+        assert!(body.contains("/reader3/exportBook?url=https%3A%2F%2Fexample.com%2Fbook%2F1"));
+
+        let (status, content_type, _) = call(
+            &app,
+            Method::GET,
+            "/reader3/opds/book?url=https%3A%2F%2Fexample.com%2Fbook%2F1",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, ENTRY_TYPE);
+        let (status, _, _) = call(
+            &app,
+            Method::GET,
+            "/reader3/opds/book?url=missing",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = call(
+            &app,
+            Method::GET,
+            "/reader3/opds/books?group=64",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, content_type, body) = call(
+            &app,
+            Method::GET,
+            "/reader3/opds/opensearch.xml",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, OPENSEARCH_TYPE);
+        assert!(body.contains("/reader3/opds/search?q={searchTerms}"));
+    }
+}
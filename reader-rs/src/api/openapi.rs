@@ -0,0 +1,710 @@
+//! OpenAPI 3 描述：`GET /openapi.json` 返回由路由表生成的接口文档，`GET /swagger` 为浏览页面
+//!
+//! 请求参数与请求体的结构来自处理函数使用的类型 (`IntoParams` / `ToSchema`)，
+//! JSON 接口的响应统一为 `ApiResponse` 信封，`data` 的结构按接口给出。
+
+use axum::{
+    http::header,
+    response::{Html, IntoResponse, Json, Response},
+};
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::engine::book_source::ImageContent;
+use crate::models::{
+    Book, BookGroup, BookInfoRule, BookMetadata, BookProgress, BookSourceFull, BookUpdateError,
+    Chapter, ContentFilter, ContentRule, ExploreRule, ReplaceRule, SearchResult, SearchRule,
+    SourceHealth, SourceScorecard, SourceSubscription, SourceTestStage, SourceTestSummary, StageResult,
+    StageStatus, TocRule, UpdateStage,
+};
+use crate::services::{
+    ChapterDiff, DebugSourceRequest, GroupOrderItem, Heartbeat, ShelfSort, SourceFailure, SourceHealthReport,
+    SourceTestOptions, ValidateRuleRequest, WebdavConfig,
+};
+use crate::storage::content_cache::ChapterVersion;
+use super::book::{
+    AudioProxyQuery, AudioUrlQuery, BookContent, BookContentQuery, BookInfoQuery, BookVariableQuery,
+    BookshelfQuery, CacheBookRequest, CacheJobQuery, ChapterAudioQuery, ChapterDiffQuery, ChapterListQuery,
+    ChapterVersionsQuery, ClearBookCacheRequest, ClearBookMetadataRequest, CoverQuery, DeleteBookRequest, ExportBookQuery,
+    ExportBookshelfQuery, ImageProxyQuery, ImportLocalBookRequest, PrefetchStatusQuery, ProgressQuery,
+    ProgressRequest, RefreshBookshelfQuery, SaveBookMetadataRequest, SaveBookVariableRequest,
+    SearchMergedQuery, SearchOriginsQuery, SearchQuery, SetBookCanUpdateRequest,
+};
+use super::explore::{ExploreBooksQuery, ExploreKindsQuery};
+use super::file::{FileGetQuery, FileSaveRequest};
+use super::group::{DeleteGroupRequest, SaveGroupOrderRequest};
+use super::manage::{DeleteBooksQuery, GroupMultiRequest};
+use super::migration::MigrationRequest;
+use super::opds::{OpdsBookQuery, OpdsBooksQuery, OpdsSearchQuery};
+use super::reading_stats::{ReadingHistoryQuery, ReadingStatsQuery};
+use super::source::{
+    AddSubscriptionRequest, AvailableSourceRequest, DeleteSourceRequest, DeleteSubscriptionRequest,
+    GetBookSourcesQuery, ImportSourceQuery, ImportSourceRequest, InjectCookieRequest, LoginInfoQuery,
+    LoginSourceRequest, LogoutSourceRequest, ReadRemoteRequest, SaveSourceRequest, SaveSourceVariableRequest,
+    SearchSourceSSEQuery, SetSourceRequest, SourceRef, SubscriptionRef, SyncRemoteRequest, SyncResult,
+    TestSourceRequest, TestSourcesRequest, ToggleSourcesRequest,
+};
+use super::user::{LoginRequest, LoginResult};
+use super::StatsParams;
+
+/// 文档中的服务地址 (接口挂载在 /reader3 下)
+const SERVER_URL: &str = "/reader3";
+
+/// 生成一次即可：文档只取决于路由表
+static DOCUMENT: Lazy<Value> = Lazy::new(document);
+
+/// GET /openapi.json - 接口的 OpenAPI 3 描述
+pub async fn openapi_json() -> Json<Value> {
+    Json(DOCUMENT.clone())
+}
+
+/// GET /swagger - 浏览 /openapi.json 的 swagger-ui 页面
+pub async fn swagger_ui() -> Response {
+    ([(header::CACHE_CONTROL, "no-cache")], Html(SWAGGER_HTML)).into_response()
+}
+
+const SWAGGER_HTML: &str = r##"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+<meta charset="utf-8">
+<title>Reader API</title>
+<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+<script>
+window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+</script>
+</body>
+</html>
+"##;
+
+/// 收集文档用到的组件结构
+#[derive(Default)]
+struct Schemas {
+    components: Map<String, Value>,
+}
+
+impl Schemas {
+    /// 登记类型的结构，返回对它的引用
+    fn of<'s, T: ToSchema<'s>>(&mut self) -> Value {
+        let (name, schema) = T::schema();
+        self.components
+            .entry(name.to_string())
+            .or_insert_with(|| serde_json::to_value(schema).unwrap_or_default());
+        json!({ "$ref": format!("#/components/schemas/{}", name) })
+    }
+
+    /// 只登记类型的结构 (被其他结构引用的类型)
+    fn register<'s, T: ToSchema<'s>>(&mut self) {
+        self.of::<T>();
+    }
+}
+
+/// 成功响应
+enum Reply {
+    /// `ApiResponse` 信封，`data` 为给出的结构
+    Data(Value),
+    /// 不带信封的 JSON
+    Json(Value),
+    /// SSE 事件流
+    EventStream(&'static str),
+    /// 文件或其他非 JSON 内容
+    Raw(&'static str),
+}
+
+/// 路由表中的一个接口
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    params: Vec<Value>,
+    /// 请求体的媒体类型 → 结构
+    body: Map<String, Value>,
+    reply: Reply,
+}
+
+fn get(path: &'static str, tag: &'static str, summary: &'static str) -> Route {
+    Route::new("get", path, tag, summary)
+}
+
+fn post(path: &'static str, tag: &'static str, summary: &'static str) -> Route {
+    Route::new("post", path, tag, summary)
+}
+
+impl Route {
+    fn new(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            tag,
+            summary,
+            params: Vec::new(),
+            body: Map::new(),
+            reply: Reply::Data(json!({})),
+        }
+    }
+
+    fn query<T: IntoParams>(mut self) -> Self {
+        self.params.extend(
+            T::into_params(|| None)
+                .into_iter()
+                .map(|param| serde_json::to_value(param).unwrap_or_default()),
+        );
+        self
+    }
+
+    fn path_param(mut self, name: &str, description: &str) -> Self {
+        self.params.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": string(),
+        }));
+        self
+    }
+
+    fn json(self, schema: Value) -> Self {
+        self.body_as("application/json", schema)
+    }
+
+    fn multipart(self, schema: Value) -> Self {
+        self.body_as("multipart/form-data", schema)
+    }
+
+    fn binary(self, content_type: &str) -> Self {
+        self.body_as(content_type, binary())
+    }
+
+    fn body_as(mut self, content_type: &str, schema: Value) -> Self {
+        self.body.insert(content_type.to_string(), json!({ "schema": schema }));
+        self
+    }
+
+    fn data(mut self, schema: Value) -> Self {
+        self.reply = Reply::Data(schema);
+        self
+    }
+
+    fn plain_json(mut self, schema: Value) -> Self {
+        self.reply = Reply::Json(schema);
+        self
+    }
+
+    fn events(mut self, description: &'static str) -> Self {
+        self.reply = Reply::EventStream(description);
+        self
+    }
+
+    fn raw(mut self, content_type: &'static str) -> Self {
+        self.reply = Reply::Raw(content_type);
+        self
+    }
+
+    /// OpenAPI 的 operation 对象
+    fn operation(&self) -> Value {
+        let mut op = json!({
+            "tags": [self.tag],
+            "summary": self.summary,
+            "operationId": self.path.trim_start_matches('/').replace(['/', ':', '*'], "_"),
+        });
+        if !self.params.is_empty() {
+            op["parameters"] = Value::Array(self.params.clone());
+        }
+        if !self.body.is_empty() {
+            op["requestBody"] = json!({ "required": true, "content": self.body });
+        }
+        let ok = match &self.reply {
+            Reply::Data(data) => json!({
+                "description": "成功",
+                "content": { "application/json": { "schema": envelope(data.clone()) } },
+            }),
+            Reply::Json(schema) => json!({
+                "description": "成功",
+                "content": { "application/json": { "schema": schema } },
+            }),
+            Reply::EventStream(description) => json!({
+                "description": description,
+                "content": { "text/event-stream": { "schema": string() } },
+            }),
+            Reply::Raw(content_type) => {
+                let mut content = Map::new();
+                content.insert(content_type.to_string(), json!({ "schema": binary() }));
+                json!({ "description": "成功", "content": content })
+            }
+        };
+        op["responses"] = json!({
+            "200": ok,
+            "default": {
+                "description": "失败：isSuccess 为 false，errorCode 给出错误类型",
+                "content": { "application/json": { "schema": { "$ref": "#/components/schemas/ApiResponse" } } },
+            },
+        });
+        op
+    }
+}
+
+/// `ApiResponse` 信封，`data` 为给出的结构
+fn envelope(data: Value) -> Value {
+    json!({
+        "allOf": [
+            { "$ref": "#/components/schemas/ApiResponse" },
+            { "type": "object", "properties": { "data": data } },
+        ]
+    })
+}
+
+fn api_response_schema() -> Value {
+    json!({
+        "type": "object",
+        "description": "统一响应格式",
+        "required": ["isSuccess"],
+        "properties": {
+            "isSuccess": { "type": "boolean" },
+            "errorCode": { "type": "string", "description": "失败时的错误类型，如 NOT_FOUND、DEADLINE_EXCEEDED" },
+            "errorMsg": { "type": "string" },
+            "detail": { "type": "object", "description": "错误的结构化信息" },
+            "data": {},
+            "total": { "type": "integer", "description": "分页查询时过滤后的总数" },
+        },
+    })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn binary() -> Value {
+    json!({ "type": "string", "format": "binary" })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// 没有描述字段的对象 (结构由服务内部类型决定)
+fn object(title: &str) -> Value {
+    json!({ "type": "object", "title": title })
+}
+
+fn string_map() -> Value {
+    json!({ "type": "object", "additionalProperties": { "type": "string" } })
+}
+
+/// 只有 `data: null` 的响应
+fn unit() -> Value {
+    json!({ "nullable": true })
+}
+
+fn file_upload(extra: &[(&str, &str)]) -> Value {
+    let mut properties = json!({ "file": { "type": "string", "format": "binary" } });
+    for (name, description) in extra {
+        properties[*name] = json!({ "type": "string", "description": description });
+    }
+    json!({ "type": "object", "required": ["file"], "properties": properties })
+}
+
+/// 全部接口，与 `reader_routes` 及多用户模式的 `/login` 对应
+fn route_table(s: &mut Schemas) -> Vec<Route> {
+    vec![
+        // 书籍
+        get("/getBookshelf", "book", "获取书架列表").query::<BookshelfQuery>().data(array(s.of::<Book>())),
+        post("/refreshBookshelf", "book", "检查书架更新").query::<RefreshBookshelfQuery>().data(object("RefreshSummary")),
+        post("/setBookCanUpdate", "book", "设置书籍是否检查更新").json(s.of::<SetBookCanUpdateRequest>()).data(s.of::<Book>()),
+        get("/updateFailures", "book", "上次检查更新失败的书籍").data(array(s.of::<Book>())),
+        get("/getChapterList", "book", "获取章节列表 (带 ETag)").query::<ChapterListQuery>().data(array(s.of::<Chapter>())),
+        get("/getBookContent", "book", "获取章节内容").query::<BookContentQuery>().data(s.of::<BookContent>()),
+        get("/getBookContentSSE", "book", "获取章节内容，分页抓取时逐页推送").query::<BookContentQuery>().events("page / done / error 事件"),
+        get("/getChapterVersions", "book", "章节缓存的各个版本").query::<ChapterVersionsQuery>().data(array(s.of::<ChapterVersion>())),
+        get("/getChapterDiff", "book", "章节两个版本的 unified diff").query::<ChapterDiffQuery>().data(s.of::<ChapterDiff>()),
+        get("/prefetchStatus", "book", "后续章节的预取状态").query::<PrefetchStatusQuery>().data(object("PrefetchStatus")),
+        post("/cacheBook", "book", "后台缓存书籍章节").json(s.of::<CacheBookRequest>()).data(object("CacheBookProgress")),
+        get("/cacheBookProgress", "book", "缓存任务进度").query::<CacheJobQuery>().data(object("CacheBookProgress")),
+        post("/cancelCacheBook", "book", "取消缓存任务").json(s.of::<CacheJobQuery>()).data(object("CacheBookProgress")),
+        get("/getChapterAudio", "book", "章节朗读音频").query::<ChapterAudioQuery>().raw("audio/mpeg"),
+        get("/getAudioUrl", "book", "有声书源的章节音频地址").query::<AudioUrlQuery>().data(object("AudioContent")),
+        get("/audioProxy", "book", "代理音频请求 (支持 Range)").query::<AudioProxyQuery>().raw("audio/*"),
+        get("/imageProxy", "book", "代理图片请求").query::<ImageProxyQuery>().raw("image/*"),
+        get("/getBookInfo", "book", "获取书籍详情").query::<BookInfoQuery>().data(s.of::<Book>()),
+        get("/search", "book", "搜索书籍").query::<SearchQuery>().data(array(s.of::<SearchResult>())),
+        get("/local_search", "book", "在已缓存的书籍中全文搜索").query::<SearchQuery>().data(array(object("LocalSearchResult"))),
+        post("/rebuildSearchIndex", "book", "重建全文索引").data(integer()),
+        get("/searchBookMultiSSE", "book", "逐个书源搜索并推送结果").query::<SearchQuery>().events("每个书源的搜索结果与 end 事件"),
+        get("/searchMerged", "book", "合并多个书源的搜索结果").query::<SearchMergedQuery>().data(object("MergedSearch")),
+        get("/searchMergedOrigins", "book", "合并结果的全部来源").query::<SearchOriginsQuery>().data(array(object("SearchOrigin"))),
+        post("/saveBook", "book", "加入书架或更新书籍").json(s.of::<Book>()).data(s.of::<Book>()),
+        post("/deleteBook", "book", "从书架删除书籍").json(s.of::<DeleteBookRequest>()).data(object("ReclaimedCache")),
+        post("/saveBookProgress", "book", "保存阅读进度").json(s.of::<ProgressRequest>()).data(s.of::<BookProgress>()),
+        get("/getBookProgress", "book", "获取阅读进度").query::<ProgressQuery>().data(s.of::<BookProgress>()),
+        post("/saveBookMetadata", "book", "覆盖书名、作者、简介或封面").json(s.of::<SaveBookMetadataRequest>()).data(s.of::<Book>()),
+        post("/clearBookMetadata", "book", "恢复书源提供的书籍信息").json(s.of::<ClearBookMetadataRequest>()).data(s.of::<Book>()),
+        post("/uploadBookCover", "book", "上传本地封面")
+            .multipart(file_upload(&[("url", "书籍地址")]))
+            .data(s.of::<Book>()),
+        get("/getBookVariable", "book", "获取书籍变量").query::<BookVariableQuery>().data(string_map()),
+        post("/saveBookVariable", "book", "保存书籍变量").json(s.of::<SaveBookVariableRequest>()).data(string_map()),
+        post("/clearBookCache", "book", "清除书籍正文缓存").json(s.of::<ClearBookCacheRequest>()).data(unit()),
+        get("/exportBook", "book", "导出书籍 (EPUB)").query::<ExportBookQuery>().raw("application/epub+zip"),
+        get("/exportBookshelf", "book", "导出书架报表 (JSON / CSV)").query::<ExportBookshelfQuery>().raw("text/csv"),
+        post("/importLocalBook", "book", "导入本地书籍 (TXT / EPUB)")
+            .json(s.of::<ImportLocalBookRequest>())
+            .multipart(file_upload(&[("tocRules", "目录规则 JSON 数组")]))
+            .data(s.of::<Book>()),
+        // 书源
+        get("/getBookSources", "source", "获取书源").query::<GetBookSourcesQuery>().data(array(s.of::<BookSourceFull>())),
+        get("/getSourceStats", "source", "书源响应统计").data(array(object("SourceStatInfo"))),
+        get("/sourceHealthReport", "source", "书源健康状态统计").data(s.of::<SourceHealthReport>()),
+        post("/getAvailableBookSource", "source", "换源：搜索同一本书的其他来源").json(s.of::<AvailableSourceRequest>()).data(array(object("SourceCandidate"))),
+        post("/setBookSource", "source", "切换书籍的书源").json(s.of::<SetSourceRequest>()).data(s.of::<Book>()),
+        get("/searchBookSourceSSE", "source", "换源搜索，逐个推送候选来源").query::<SearchSourceSSEQuery>().events("候选来源与 end 事件"),
+        post("/saveBookSource", "source", "保存书源").json(s.of::<SaveSourceRequest>()).data(unit()),
+        post("/deleteBookSource", "source", "删除书源").json(s.of::<DeleteSourceRequest>()).data(unit()),
+        post("/importBookSource", "source", "批量导入书源 (可 gzip 压缩)")
+            .query::<ImportSourceQuery>()
+            .json(json!({ "oneOf": [s.of::<ImportSourceRequest>(), array(s.of::<BookSourceFull>()), s.of::<BookSourceFull>()] }))
+            .data(object("ImportReport")),
+        post("/readRemoteSourceFile", "source", "读取远程书源文件").json(s.of::<ReadRemoteRequest>()).data(array(string())),
+        post("/saveBookSources", "source", "批量保存书源").json(array(s.of::<BookSourceFull>())).data(integer()),
+        post("/injectCookies", "source", "注入登录 Cookie").json(s.of::<InjectCookieRequest>()).data(unit()),
+        get("/getLoginInfo", "source", "书源登录界面").query::<LoginInfoQuery>().data(object("SourceLoginInfo")),
+        post("/loginBookSource", "source", "登录书源").json(s.of::<LoginSourceRequest>()).data(object("LoginResult")),
+        post("/logoutBookSource", "source", "退出书源登录").json(s.of::<LogoutSourceRequest>()).data(unit()),
+        get("/getSourceVariable", "source", "获取书源变量").query::<LoginInfoQuery>().data(object("SourceVariable")),
+        post("/saveSourceVariable", "source", "保存书源变量").json(s.of::<SaveSourceVariableRequest>()).data(unit()),
+        post("/clearRuleCache", "source", "清空规则编译缓存").data(integer()),
+        post("/testBookSource", "source", "测试书源各阶段").json(s.of::<TestSourceRequest>()).data(s.of::<SourceScorecard>()),
+        post("/testBookSources", "source", "批量测试书源").json(s.of::<TestSourcesRequest>()).events("每个书源的评分与 summary 事件"),
+        post("/debugBookSource", "source", "调试书源，返回执行轨迹").json(s.of::<DebugSourceRequest>()).data(array(object("TraceEntry"))),
+        post("/validateSourceRule", "source", "校验规则并预览提取结果").json(s.of::<ValidateRuleRequest>()).data(object("RuleValidation")),
+        get("/inspectSource", "source", "书源规则的编译结果").query::<LoginInfoQuery>().data(object("SourceInspection")),
+        post("/deleteBookSources", "source", "批量删除书源").json(array(s.of::<SourceRef>())).data(integer()),
+        post("/enableBookSources", "source", "批量启用书源").json(s.of::<ToggleSourcesRequest>()).data(integer()),
+        post("/disableBookSources", "source", "批量禁用书源").json(s.of::<ToggleSourcesRequest>()).data(integer()),
+        post("/saveFromRemoteSource", "source", "从远程地址同步书源").json(s.of::<SyncRemoteRequest>()).data(s.of::<SyncResult>()),
+        post("/addSourceSubscription", "source", "添加书源订阅").json(s.of::<AddSubscriptionRequest>()).data(s.of::<SourceSubscription>()),
+        get("/getSourceSubscriptions", "source", "书源订阅列表").data(array(s.of::<SourceSubscription>())),
+        post("/refreshSourceSubscription", "source", "更新书源订阅").json(s.of::<SubscriptionRef>()).data(s.of::<SourceSubscription>()),
+        post("/deleteSourceSubscription", "source", "删除书源订阅，可一并删除其书源").json(s.of::<DeleteSubscriptionRequest>()).data(integer()),
+        // 发现
+        get("/getExploreSources", "explore", "支持发现的书源").data(array(s.of::<BookSourceFull>())),
+        get("/getExploreKinds", "explore", "书源的发现分类").query::<ExploreKindsQuery>().data(array(object("ExploreKind"))),
+        get("/exploreBooks", "explore", "发现页书籍").query::<ExploreBooksQuery>().data(array(object("BookItem"))),
+        // 替换规则
+        get("/getReplaceRules", "replace", "获取替换规则").data(array(s.of::<ReplaceRule>())),
+        post("/saveReplaceRule", "replace", "保存替换规则").json(s.of::<ReplaceRule>()).data(s.of::<ReplaceRule>()),
+        post("/saveReplaceRules", "replace", "批量保存替换规则").json(array(s.of::<ReplaceRule>())).data(unit()),
+        post("/deleteReplaceRules", "replace", "批量删除替换规则").json(array(s.of::<ReplaceRule>())).data(unit()),
+        // 正文净化规则
+        get("/contentFilters", "contentFilter", "获取正文净化规则").data(array(s.of::<ContentFilter>())),
+        post("/contentFilters", "contentFilter", "保存正文净化规则").json(array(s.of::<ContentFilter>())).data(array(s.of::<ContentFilter>())),
+        post("/deleteContentFilters", "contentFilter", "删除正文净化规则").json(array(s.of::<ContentFilter>())).data(unit()),
+        // 分组
+        get("/getBookGroups", "group", "获取分组").data(array(s.of::<BookGroup>())),
+        post("/saveBookGroup", "group", "保存分组").json(s.of::<BookGroup>()).data(s.of::<BookGroup>()),
+        post("/deleteBookGroup", "group", "删除分组").json(s.of::<DeleteGroupRequest>()).data(unit()),
+        post("/removeBookGroup", "group", "删除分组 (deleteBookGroup 的别名)").json(s.of::<DeleteGroupRequest>()).data(unit()),
+        post("/saveBookGroupOrder", "group", "保存分组顺序").json(s.of::<SaveGroupOrderRequest>()).data(unit()),
+        // 批量管理
+        post("/deleteBooks", "manage", "批量删除书籍").query::<DeleteBooksQuery>().json(array(s.of::<Book>())).data(object("ReclaimedCache")),
+        post("/addBookGroupMulti", "manage", "批量加入分组").json(s.of::<GroupMultiRequest>()).data(unit()),
+        post("/removeBookGroupMulti", "manage", "批量移出分组").json(s.of::<GroupMultiRequest>()).data(unit()),
+        // 迁移
+        post("/migrate", "migration", "从旧版后端迁移数据").json(s.of::<MigrationRequest>()).data(object("LegacyImportReport")),
+        // 文件
+        get("/file/get", "file", "读取数据目录下的文件").query::<FileGetQuery>().data(string()),
+        post("/file/save", "file", "保存数据目录下的文件").json(s.of::<FileSaveRequest>()).data(boolean()),
+        // 备份
+        post("/backupToWebdav", "backup", "备份到 WebDAV").json(s.of::<WebdavConfig>()).data(string()),
+        post("/restoreFromWebdav", "backup", "从 WebDAV 恢复").json(s.of::<WebdavConfig>()).data(string()),
+        get("/exportData", "backup", "导出全部数据 (zip)").raw("application/zip"),
+        post("/importData", "backup", "导入 exportData 导出的 zip").binary("application/zip").data(object("DataImportSummary")),
+        // 阅读统计
+        post("/heartbeat", "readingStats", "阅读心跳").json(s.of::<Heartbeat>()).data(integer()),
+        get("/readingStats", "readingStats", "阅读统计").query::<ReadingStatsQuery>().data(object("ReadingStats")),
+        get("/readingHistory", "readingStats", "阅读记录").query::<ReadingHistoryQuery>().data(array(object("HistoryEntry"))),
+        // OPDS
+        get("/opds", "opds", "OPDS 根目录").raw("application/atom+xml"),
+        get("/opds/books", "opds", "OPDS 书架").query::<OpdsBooksQuery>().raw("application/atom+xml"),
+        get("/opds/book", "opds", "OPDS 书籍条目").query::<OpdsBookQuery>().raw("application/atom+xml"),
+        get("/opds/search", "opds", "OPDS 搜索").query::<OpdsSearchQuery>().raw("application/atom+xml"),
+        get("/opds/opensearch.xml", "opds", "OpenSearch 描述").raw("application/opensearchdescription+xml"),
+        // 静态资源
+        get("/cover", "assets", "书籍封面 (无需令牌)").query::<CoverQuery>().raw("image/*"),
+        get("/assets/:book_id/*path", "assets", "本地书籍中的资源")
+            .path_param("book_id", "书籍 ID")
+            .path_param("path", "资源在书籍中的路径")
+            .raw("application/octet-stream"),
+        // 统计与后台任务
+        get("/stats", "stats", "规则执行统计").query::<StatsParams>().plain_json(object("StatsSnapshot")),
+        post("/stats/reset", "stats", "重置规则执行统计").raw("text/plain"),
+        get("/jobs", "stats", "后台任务状态").data(array(object("JobStatus"))),
+        // 多用户模式
+        post("/login", "user", "登录 (仅多用户模式)").json(s.of::<LoginRequest>()).data(s.of::<LoginResult>()),
+        // 本文档
+        get("/openapi.json", "meta", "OpenAPI 描述").plain_json(object("OpenAPI")),
+        get("/swagger", "meta", "swagger-ui 页面").raw("text/html"),
+    ]
+}
+
+/// axum 的 `:param` / `*param` 路径写成 OpenAPI 的 `{param}`
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix([':', '*']) {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 生成 OpenAPI 文档
+fn document() -> Value {
+    let mut s = Schemas::default();
+    let routes = route_table(&mut s);
+    // 只在其他结构中出现的类型
+    s.register::<UpdateStage>();
+    s.register::<BookUpdateError>();
+    s.register::<BookMetadata>();
+    s.register::<ShelfSort>();
+    s.register::<SearchRule>();
+    s.register::<BookInfoRule>();
+    s.register::<TocRule>();
+    s.register::<ContentRule>();
+    s.register::<ExploreRule>();
+    s.register::<SourceTestStage>();
+    s.register::<StageStatus>();
+    s.register::<StageResult>();
+    s.register::<SourceTestSummary>();
+    s.register::<SourceHealth>();
+    s.register::<SourceFailure>();
+    s.register::<SourceTestOptions>();
+    s.register::<GroupOrderItem>();
+    s.register::<ImageContent>();
+    s.components.insert("ApiResponse".to_string(), api_response_schema());
+
+    let mut paths = Map::new();
+    for route in &routes {
+        let item = paths
+            .entry(openapi_path(route.path))
+            .or_insert_with(|| json!({}));
+        let mut operation = route.operation();
+        // 同一路径的多个方法 (如 /contentFilters) 需要不同的 operationId
+        if item.as_object().is_some_and(|methods| !methods.is_empty()) {
+            operation["operationId"] = json!(format!("{}_{}", operation["operationId"].as_str().unwrap_or_default(), route.method));
+        }
+        item[route.method] = operation;
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Reader API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "阅读 3 服务端接口。配置了访问令牌时通过 `Authorization: Bearer` 或 `accessToken` 参数传递；多用户模式下需先调用 /login。",
+        },
+        "servers": [{ "url": SERVER_URL }],
+        "paths": paths,
+        "components": { "schemas": s.components },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use std::collections::BTreeSet;
+
+    use super::super::source::ImportSourcePayload;
+
+    /// 路由表中的全部路径
+    fn documented() -> BTreeSet<String> {
+        route_table(&mut Schemas::default())
+            .iter()
+            .map(|route| route.path.to_string())
+            .collect()
+    }
+
+    /// 从源码中取出 `.route("...")` 注册的路径
+    fn registered(source: &str) -> BTreeSet<String> {
+        source
+            .split(".route(")
+            .skip(1)
+            .filter_map(|rest| rest.trim_start().strip_prefix('"'))
+            .filter_map(|rest| rest.split('"').next())
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_route_table_matches_router() {
+        let mut expected = registered(include_str!("mod.rs"));
+        expected.extend(registered(include_str!("user.rs")));
+        assert!(expected.len() > 100, "route parsing broke: {:?}", expected);
+        assert_eq!(documented(), expected);
+    }
+
+    #[test]
+    fn test_document_refs_resolve() {
+        fn collect_refs(value: &Value, refs: &mut BTreeSet<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        refs.insert(r.clone());
+                    }
+                    map.values().for_each(|v| collect_refs(v, refs));
+                }
+                Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+                _ => {}
+            }
+        }
+
+        let doc = document();
+        let mut refs = BTreeSet::new();
+        collect_refs(&doc, &mut refs);
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        for r in refs {
+            let name = r.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "unresolved {}", r);
+        }
+    }
+
+    #[test]
+    fn test_document_describes_params_and_bodies() {
+        let doc = document();
+        let content = &doc["paths"]["/getBookContent"]["get"];
+        let names: Vec<&str> = content["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["url", "index", "refresh", "maxPages", "format"]);
+        assert_eq!(content["parameters"][0]["in"], "query");
+        assert_eq!(content["parameters"][0]["required"], true);
+
+        let body = &doc["paths"]["/setBookSource"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(body["$ref"], "#/components/schemas/SetSourceRequest");
+        let request = &doc["components"]["schemas"]["SetSourceRequest"];
+        assert!(request["properties"]["bookSourceUrl"].is_object());
+
+        assert!(doc["paths"]["/assets/{book_id}/{path}"]["get"].is_object());
+        assert_eq!(doc["servers"][0]["url"], "/reader3");
+    }
+
+    fn parse<T: DeserializeOwned>(body: &Value) -> Result<T, serde_json::Error> {
+        T::deserialize(body)
+    }
+
+    /// 前端与阅读 App 实际发送的请求体都能解析为接口的请求类型
+    #[test]
+    fn test_recorded_request_bodies_deserialize() {
+        let fixture = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/api/requests.json"));
+        let recorded: Map<String, Value> = serde_json::from_str(fixture).unwrap();
+        let documented = documented();
+        for (path, bodies) in &recorded {
+            assert!(documented.contains(path), "{} is not a route", path);
+            for body in bodies.as_array().unwrap() {
+                let result = match path.as_str() {
+                    "/saveBook" => parse::<Book>(body).map(drop),
+                    "/deleteBook" => parse::<DeleteBookRequest>(body).map(drop),
+                    "/saveBookProgress" => parse::<ProgressRequest>(body).map(drop),
+                    "/getAvailableBookSource" => parse::<AvailableSourceRequest>(body).map(drop),
+                    "/setBookSource" => parse::<SetSourceRequest>(body).map(drop),
+                    "/saveBookSource" => parse::<SaveSourceRequest>(body).map(drop),
+                    "/deleteBookSource" => parse::<DeleteSourceRequest>(body).map(drop),
+                    "/importBookSource" => parse::<ImportSourcePayload>(body).map(drop),
+                    "/testBookSource" => parse::<TestSourceRequest>(body).map(drop),
+                    "/readRemoteSourceFile" => parse::<ReadRemoteRequest>(body).map(drop),
+                    "/saveFromRemoteSource" => parse::<SyncRemoteRequest>(body).map(drop),
+                    "/saveBookSources" => parse::<Vec<Value>>(body).map(drop),
+                    "/deleteBookSources" => parse::<Vec<SourceRef>>(body).map(drop),
+                    "/file/save" => parse::<FileSaveRequest>(body).map(drop),
+                    "/saveBookGroup" => parse::<BookGroup>(body).map(drop),
+                    "/deleteBookGroup" => parse::<DeleteGroupRequest>(body).map(drop),
+                    "/saveBookGroupOrder" => parse::<SaveGroupOrderRequest>(body).map(drop),
+                    "/deleteBooks" => parse::<Vec<Book>>(body).map(drop),
+                    "/addBookGroupMulti" => parse::<GroupMultiRequest>(body).map(drop),
+                    "/saveReplaceRule" => parse::<ReplaceRule>(body).map(drop),
+                    "/migrate" => parse::<MigrationRequest>(body).map(drop),
+                    "/heartbeat" => parse::<Heartbeat>(body).map(drop),
+                    "/cacheBook" => parse::<CacheBookRequest>(body).map(drop),
+                    "/backupToWebdav" => parse::<WebdavConfig>(body).map(drop),
+                    "/login" => parse::<LoginRequest>(body).map(drop),
+                    other => panic!("no request type for {}", other),
+                };
+                if let Err(e) = result {
+                    panic!("{} rejected {}: {}", path, body, e);
+                }
+            }
+        }
+
+        let bodies = |path: &str| recorded[path].as_array().unwrap().clone();
+        // App 的进度格式与前端的简写格式一致
+        let progress: Vec<ProgressRequest> = bodies("/saveBookProgress").iter().map(|b| parse(b).unwrap()).collect();
+        assert_eq!(progress[1].url, progress[0].url);
+        assert_eq!(progress[1].index, 12);
+        assert_eq!(progress[1].pos, Some(340));
+        // 不含 bookSourceUrl 的对象是导入参数，其余按书源导入
+        let imports: Vec<ImportSourcePayload> = bodies("/importBookSource").iter().map(|b| parse(b).unwrap()).collect();
+        assert!(matches!(&imports[0], ImportSourcePayload::Request(req) if req.source.is_some()));
+        assert!(matches!(&imports[1], ImportSourcePayload::Request(req) if req.dry_run == Some(true)));
+        assert!(matches!(imports[2], ImportSourcePayload::Sources(Value::Array(_))));
+        assert!(matches!(imports[3], ImportSourcePayload::Sources(Value::Object(_))));
+        // 缺少地址的书源被跳过而不是拒绝整个请求
+        let refs: Vec<SourceRef> = parse(&bodies("/deleteBookSources")[0]).unwrap();
+        assert_eq!(refs[1].book_source_url, None);
+        // 新建分组只提交 groupName
+        let group: BookGroup = parse(&bodies("/saveBookGroup")[0]).unwrap();
+        assert_eq!((group.group_id, group.order, group.show), (0, 0, true));
+        let file: FileSaveRequest = parse(&bodies("/file/save")[0]).unwrap();
+        assert_eq!(file.home, "__HOME__");
+        let migration: MigrationRequest = parse(&bodies("/migrate")[1]).unwrap();
+        assert_eq!(migration.user, None);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_served_without_token() {
+        use crate::api::AccessConfig;
+        use crate::services::AppState;
+        use axum::body::Body;
+        use axum::extract::Request;
+        use axum::http::StatusCode;
+        use axum::Router;
+        use std::sync::Arc;
+        use tower::ServiceExt;
+
+        let dir = "/tmp/reader_tests_api_openapi";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = Arc::new(AppState::with_storage_dir(dir));
+        let access = AccessConfig {
+            token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let app = Router::new().nest("/reader3", super::super::routes(state, &access));
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let resp = app.clone().oneshot(get("/reader3/openapi.json")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let doc: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert!(doc["paths"]["/getBookshelf"]["get"].is_object());
+
+        let resp = app.clone().oneshot(get("/reader3/swagger")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.clone().oneshot(get("/reader3/getBookshelf")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
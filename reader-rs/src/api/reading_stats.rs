@@ -0,0 +1,56 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::sync::Arc;
+
+use super::error::ApiResult;
+use crate::models::ApiResponse;
+use crate::services::{AppState, Heartbeat, HistoryEntry, ReadingStats};
+
+/// 阅读记录默认返回条数
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReadingStatsQuery {
+    /// 起始日期 YYYY-MM-DD
+    pub from: Option<String>,
+    /// 结束日期 YYYY-MM-DD (含)
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReadingHistoryQuery {
+    pub limit: Option<usize>,
+}
+
+/// POST /heartbeat - 阅读时定期上报，返回计入的阅读时长 (秒)，重复上报为 0
+pub async fn heartbeat(State(state): State<Arc<AppState>>, Json(beat): Json<Heartbeat>) -> ApiResult<u64> {
+    let counted = state.reading_stats.heartbeat(beat).await?;
+    Ok(Json(ApiResponse::success(counted)))
+}
+
+/// GET /readingStats - 每日与每本书的阅读时长、读完章节数及连续阅读天数
+pub async fn get_reading_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReadingStatsQuery>,
+) -> ApiResult<ReadingStats> {
+    let stats = state
+        .reading_stats
+        .stats(query.from.as_deref(), query.to.as_deref())
+        .await?;
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+/// GET /readingHistory - 最近读过的章节，新的在前
+pub async fn get_reading_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReadingHistoryQuery>,
+) -> ApiResult<Vec<HistoryEntry>> {
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    Ok(Json(ApiResponse::success(state.reading_stats.history(limit).await)))
+}
@@ -1,23 +1,53 @@
 use axum::{
+    body::Bytes,
     extract::{Query, State},
     response::{Json, sse::{Event, Sse}},
 };
 use futures::stream::Stream;
 use futures::StreamExt;
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::convert::Infallible;
 
-use crate::models::{BookSource, BookSourceFull, ApiResponse};
-use crate::services::AppState;
+use crate::models::{Book, BookSourceFull, ApiResponse, SourceHealth, SourceScorecard, SourceSubscription};
+use crate::engine::login::LoginResult;
+use crate::engine::rule_validator::RuleValidation;
+use crate::engine::source_inspector::SourceInspection;
+use crate::engine::trace::TraceEntry;
+use crate::services::{
+    decode_payload, fetch_remote_sources, AppState, ChangeSourceEvent, ChangeSourceQuery,
+    DebugSourceRequest, ImportReport, ServiceError, SourceCandidate, SourceLoginInfo,
+    SourceHealthReport, SourceStatInfo, SourceTestOptions, SourceVariable, ValidateRuleRequest,
+};
+use super::error::ApiResult;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetBookSourcesQuery {
+    /// 排序方式，目前支持 weight
+    pub sort: Option<String>,
+    /// 只返回已启用 (true) 或已禁用 (false) 的书源
+    pub enabled: Option<bool>,
+    /// 只返回该健康状态 (healthy / degraded / broken) 的书源
+    pub health: Option<SourceHealth>,
+}
 
-#[derive(Debug, Deserialize)]
+/// 换源请求；未给出书名时从书架或书源获取书籍信息
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct AvailableSourceRequest {
-    pub url: String,
-    pub refresh: Option<i32>,
+    #[serde(alias = "url")]
+    pub book_url: String,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub with_chapter_count: Option<bool>,
+    pub book_source_group: Option<String>,
+    pub concurrent_count: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SetSourceRequest {
     #[serde(rename = "bookUrl")]
     pub book_url: String,
@@ -27,50 +57,122 @@ pub struct SetSourceRequest {
     pub book_source_url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
 pub struct SearchSourceSSEQuery {
     pub url: String,
-    #[serde(rename = "bookSourceGroup")]
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub with_chapter_count: Option<bool>,
     pub book_source_group: Option<String>,
-    #[serde(rename = "concurrentCount")]
-    pub concurrent_count: Option<i32>,
+    pub concurrent_count: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SaveSourceRequest {
     pub source: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DeleteSourceRequest {
     #[serde(rename = "bookSourceUrl")]
     pub book_source_url: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// importBookSource 的对象形式请求体：`source` 为书源 JSON (字符串或数组)，`url` 为远程书源文件
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct ImportSourceRequest {
-    pub source: String,
+    pub source: Option<serde_json::Value>,
+    pub url: Option<String>,
+    pub dry_run: Option<bool>,
+}
+
+/// importBookSource 的请求体：不含 bookSourceUrl 的对象为 [`ImportSourceRequest`]，
+/// 其余 (书源数组或单个书源) 直接作为书源导入
+#[derive(Debug)]
+pub enum ImportSourcePayload {
+    Request(ImportSourceRequest),
+    Sources(serde_json::Value),
+}
+
+impl<'de> Deserialize<'de> for ImportSourcePayload {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::Object(obj) if !obj.contains_key("bookSourceUrl") => {
+                serde_json::from_value(serde_json::Value::Object(obj))
+                    .map(Self::Request)
+                    .map_err(serde::de::Error::custom)
+            }
+            sources => Ok(Self::Sources(sources)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSourceQuery {
+    pub dry_run: Option<bool>,
 }
 
 /// GET /getBookSources - 获取所有书源 (完整版)
 pub async fn get_book_sources(
     State(state): State<Arc<AppState>>,
-) -> Json<ApiResponse<Vec<BookSourceFull>>> {
-    match state.source_service.get_all_sources().await {
-        Ok(sources) => Json(ApiResponse::success(sources)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+    Query(query): Query<GetBookSourcesQuery>,
+) -> ApiResult<Vec<BookSourceFull>> {
+    let sources = state
+        .source_service
+        .get_sources_sorted(query.sort.as_deref(), query.enabled, query.health)
+        .await?;
+    Ok(Json(ApiResponse::success(sources)))
+}
+
+/// GET /getSourceStats - 获取书源响应统计
+pub async fn get_source_stats(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Vec<SourceStatInfo>> {
+    let stats = state.source_service.get_source_stats().await?;
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+/// GET /sourceHealthReport - 书源健康状态统计与最近失败的书源
+pub async fn source_health_report(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<SourceHealthReport> {
+    let report = state.source_service.get_source_health_report().await?;
+    Ok(Json(ApiResponse::success(report)))
 }
 
-/// POST /getAvailableBookSource - 获取可用书源
+/// POST /getAvailableBookSource - 换源：搜索所有已启用的书源，返回同一本书的候选来源
 pub async fn get_available_book_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<AvailableSourceRequest>,
-) -> Json<ApiResponse<Vec<BookSource>>> {
-    let refresh = req.refresh.unwrap_or(0) == 1;
-    match state.source_service.get_available_sources(&req.url, refresh).await {
-        Ok(sources) => Json(ApiResponse::success(sources)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<Vec<SourceCandidate>> {
+    let mut query = change_source_query(&state, &req.book_url, req.name, req.author).await?;
+    query.with_chapter_count = req.with_chapter_count.unwrap_or(false);
+    query.group = req.book_source_group;
+    if let Some(concurrent) = req.concurrent_count {
+        query.concurrent = concurrent;
+    }
+    let candidates = state.book_service.available_sources(query).await?;
+    Ok(Json(ApiResponse::success(candidates)))
+}
+
+/// 换源搜索条件，缺少书名时按书籍地址获取书名与作者
+async fn change_source_query(
+    state: &AppState,
+    book_url: &str,
+    name: Option<String>,
+    author: Option<String>,
+) -> anyhow::Result<ChangeSourceQuery> {
+    match name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => Ok(ChangeSourceQuery::new(name, author.unwrap_or_default())),
+        None => {
+            let book = state.book_service.get_book_info(book_url, None).await?;
+            Ok(ChangeSourceQuery::new(book.name, author.unwrap_or(book.author)))
+        }
     }
 }
 
@@ -78,85 +180,99 @@ pub async fn get_available_book_source(
 pub async fn set_book_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SetSourceRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.source_service.set_book_source(&req.book_url, &req.new_url, &req.book_source_url).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<Book> {
+    let book = state.book_service.set_book_source(&req.book_url, &req.new_url, &req.book_source_url).await?;
+    state.prefetcher.cancel(&req.book_url);
+    state.book_cacher.cancel_book(&req.book_url);
+    Ok(Json(ApiResponse::success(book.with_metadata())))
 }
 
-/// GET /searchBookSourceSSE - 搜索书源 (SSE)
+/// GET /searchBookSourceSSE - 换源 (SSE)，逐个推送候选来源，结束事件携带排序后的全部候选
 pub async fn search_book_source_sse(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchSourceSSEQuery>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    // 1. 获取书籍信息以得到书名
-    // 注意：这里需要 async 块来处理 await，由于 Sse::new 需要流，我们使用 async_stream
-    let book_service = state.book_service.clone();
-    let url = query.url.clone();
-    let concurrent = query.concurrent_count.unwrap_or(20) as usize;
-
     let stream = async_stream::stream! {
-        // 尝试获取书籍信息
-        let (book_name, book_author) = match book_service.get_book_info(&url, None).await {
-            Ok(book) => (book.name, Some(book.author)),
+        let mut change_query = match change_source_query(&state, &query.url, query.name, query.author).await {
+            Ok(change_query) => change_query,
             Err(_) => {
                 // 书籍未找到，可能是 ID 错误
-                tracing::warn!("Book not found for source search: {}", url);
+                tracing::warn!("Book not found for source search: {}", query.url);
                 yield Ok(Event::default().data(r#"{"type":"end"}"#));
                 return;
             }
         };
+        change_query.with_chapter_count = query.with_chapter_count.unwrap_or(false);
+        change_query.group = query.book_source_group;
+        if let Some(concurrent) = query.concurrent_count {
+            change_query.concurrent = concurrent;
+        }
 
-        tracing::info!("Starting strict source search for book: {}, author: {:?}", book_name, book_author);
-
-        let mut search_stream = Box::pin(book_service.search_multi_sse(book_name, true, book_author, concurrent));
+        tracing::info!("Starting source search for book: {}, author: {}", change_query.name, change_query.author);
 
-        while let Some(event) = search_stream.next().await {
-            yield event;
+        let name = change_query.name.clone();
+        let mut events = Box::pin(state.book_service.change_source_events(change_query));
+        while let Some(event) = events.next().await {
+            if let ChangeSourceEvent::Done(candidates) = &event {
+                tracing::info!("Found {} alternative sources for: {}", candidates.len(), name);
+            }
+            yield Ok(event.to_sse());
         }
-        
-        // 发送结束事件 (虽然 search_multi_sse 可能已经发了，但多发无害，或者依靠 frontend 的处理)
-        // yield Ok(Event::default().data(r#"{"type":"end"}"#));
     };
 
-    Sse::new(stream)
+    super::sse(stream)
 }
 
 /// POST /saveBookSource - 保存书源
 pub async fn save_book_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SaveSourceRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.source_service.save_source(&req.source).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.source_service.save_source(&req.source).await?;
+    Ok(Json(ApiResponse::success(())))
 }
 
 /// POST /deleteBookSource - 删除书源
 pub async fn delete_book_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<DeleteSourceRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.source_service.delete_source(&req.book_source_url).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.source_service.delete_source(&req.book_source_url).await?;
+    Ok(Json(ApiResponse::success(())))
 }
 
-/// POST /importBookSource - 批量导入书源
+/// POST /importBookSource - 批量导入书源，返回导入报告
+///
+/// 请求体可以是书源数组、`{source}`、`{url}` (远程书源文件)，支持 gzip 压缩；
+/// `dryRun` 为 true 时只返回报告，不保存。
 pub async fn import_book_source(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<ImportSourceRequest>,
-) -> Json<ApiResponse<i32>> {
-    match state.source_service.import_sources(&req.source).await {
-        Ok(count) => Json(ApiResponse::success(count)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+    Query(query): Query<ImportSourceQuery>,
+    body: Bytes,
+) -> ApiResult<ImportReport> {
+    let text = decode_payload(&body)?;
+    let payload: ImportSourcePayload = serde_json::from_str(&text)
+        .map_err(|e| ServiceError::invalid_input(format!("invalid book source JSON: {}", e)))?;
+
+    let (sources_json, body_dry_run) = match payload {
+        ImportSourcePayload::Request(req) => {
+            let sources_json = match (req.source, req.url) {
+                (Some(serde_json::Value::String(source)), _) => source,
+                (Some(source), _) => source.to_string(),
+                (None, Some(url)) => fetch_remote_sources(&url).await?,
+                (None, None) => return Err(ServiceError::invalid_input("missing source or url").into()),
+            };
+            (sources_json, req.dry_run)
+        }
+        ImportSourcePayload::Sources(sources) => (sources.to_string(), None),
+    };
+
+    let dry_run = query.dry_run.or(body_dry_run).unwrap_or(false);
+    let report = state.source_service.import_sources(&sources_json, dry_run).await?;
+    Ok(Json(ApiResponse::success(report)))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ReadRemoteRequest {
     pub url: String,
 }
@@ -164,28 +280,20 @@ pub struct ReadRemoteRequest {
 /// POST /readRemoteSourceFile - 读取远程书源文件
 pub async fn read_remote_source_file(
     Json(req): Json<ReadRemoteRequest>,
-) -> Json<ApiResponse<Vec<String>>> {
+) -> ApiResult<Vec<String>> {
     // 从远程 URL 获取书源内容
-    match reqwest::get(&req.url).await {
-        Ok(resp) => {
-            match resp.text().await {
-                Ok(text) => {
-                    // 尝试解析为 JSON 数组
-                    if let Ok(sources) = serde_json::from_str::<Vec<serde_json::Value>>(&text) {
-                        // 返回每个书源的 JSON 字符串
-                        let result: Vec<String> = sources.iter()
-                            .filter_map(|s| serde_json::to_string(s).ok())
-                            .collect();
-                        Json(ApiResponse::success(result))
-                    } else {
-                        // 可能是单个书源或纯文本
-                        Json(ApiResponse::success(vec![text]))
-                    }
-                }
-                Err(e) => Json(ApiResponse::error(&format!("Failed to read response: {}", e))),
-            }
-        }
-        Err(e) => Json(ApiResponse::error(&format!("Failed to fetch: {}", e))),
+    let text = fetch_remote_sources(&req.url).await?;
+
+    // 尝试解析为 JSON 数组
+    if let Ok(sources) = serde_json::from_str::<Vec<serde_json::Value>>(&text) {
+        // 返回每个书源的 JSON 字符串
+        let result: Vec<String> = sources.iter()
+            .filter_map(|s| serde_json::to_string(s).ok())
+            .collect();
+        Ok(Json(ApiResponse::success(result)))
+    } else {
+        // 可能是单个书源或纯文本
+        Ok(Json(ApiResponse::success(vec![text])))
     }
 }
 
@@ -193,47 +301,129 @@ pub async fn read_remote_source_file(
 pub async fn save_book_sources(
     State(state): State<Arc<AppState>>,
     Json(sources): Json<Vec<serde_json::Value>>,
-) -> Json<ApiResponse<i32>> {
+) -> ApiResult<i32> {
     let json_str = serde_json::to_string(&sources).unwrap_or_default();
-    match state.source_service.import_sources(&json_str).await {
-        Ok(count) => Json(ApiResponse::success(count)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+    let report = state.source_service.import_sources(&json_str, false).await?;
+    Ok(Json(ApiResponse::success(report.accepted() as i32)))
 }
 
-#[derive(Debug, Deserialize)]
+/// 批量测试书源的默认并发数
+const DEFAULT_TEST_CONCURRENT: usize = 8;
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TestSourceRequest {
     #[serde(rename = "bookSourceUrl")]
     pub book_source_url: String,
+    #[serde(flatten)]
+    pub options: SourceTestOptions,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestSourcesRequest {
+    pub book_source_urls: Vec<String>,
+    /// 同时测试的书源数
+    pub concurrent_count: Option<usize>,
+    #[serde(flatten)]
+    pub options: SourceTestOptions,
 }
 
-/// POST /testBookSource - 测试书源
+/// POST /testBookSource - 测试书源：搜索 → 详情 → 目录 → 首章正文，返回各阶段结果与耗时
 pub async fn test_book_source(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Json(req): Json<TestSourceRequest>,
-) -> Json<ApiResponse<String>> {
-    // TODO: 实现书源测试逻辑
-    // 需要：1. 获取书源配置 2. 执行搜索规则 3. 返回测试结果
-    Json(ApiResponse::success(format!("Testing source: {}", req.book_source_url)))
+) -> ApiResult<SourceScorecard> {
+    let card = state.source_service.test_source(&req.book_source_url, &req.options).await?;
+    Ok(Json(ApiResponse::success(card)))
+}
+
+/// POST /testBookSources - 批量测试书源 (SSE)，逐个推送成绩单
+pub async fn test_book_sources(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TestSourcesRequest>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let concurrent = req.concurrent_count.unwrap_or(DEFAULT_TEST_CONCURRENT);
+    let events = state
+        .source_service
+        .test_source_events(req.book_source_urls, concurrent, req.options);
+    super::sse(events.map(|event| Ok(event.to_sse())))
+}
+
+/// POST /debugBookSource - 调试书源，返回搜索到正文各阶段的跟踪记录
+pub async fn debug_book_source(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DebugSourceRequest>,
+) -> ApiResult<Vec<TraceEntry>> {
+    let trace = state.source_service.debug_source(req).await?;
+    Ok(Json(ApiResponse::success(trace)))
+}
+
+/// POST /validateSourceRule - 校验规则语法，返回规则类型、错误位置、能否原生执行与样例提取预览
+pub async fn validate_source_rule(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ValidateRuleRequest>,
+) -> ApiResult<RuleValidation> {
+    let validation = state.source_service.validate_rule(req).await?;
+    Ok(Json(ApiResponse::success(validation)))
+}
+
+/// GET /inspectSource - 查看书源各规则的原文、编译结果 (选择器 / 原生调用 / JS 及原因) 与统计
+pub async fn inspect_source(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginInfoQuery>,
+) -> ApiResult<SourceInspection> {
+    let inspection = state.source_service.inspect_source(&query.book_source_url).await?;
+    Ok(Json(ApiResponse::success(inspection)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ToggleSourcesRequest {
+    #[serde(rename = "bookSourceUrls")]
+    pub book_source_urls: Vec<String>,
+}
+
+/// POST /enableBookSources - 批量启用书源，返回状态发生变化的书源数
+pub async fn enable_book_sources(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ToggleSourcesRequest>,
+) -> ApiResult<usize> {
+    let changed = state.source_service.set_sources_enabled(&req.book_source_urls, true).await?;
+    Ok(Json(ApiResponse::success(changed)))
+}
+
+/// POST /disableBookSources - 批量禁用书源，返回状态发生变化的书源数
+pub async fn disable_book_sources(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ToggleSourcesRequest>,
+) -> ApiResult<usize> {
+    let changed = state.source_service.set_sources_enabled(&req.book_source_urls, false).await?;
+    Ok(Json(ApiResponse::success(changed)))
+}
+
+/// deleteBookSources 的数组元素：前端提交完整书源，只用到其中的 bookSourceUrl
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SourceRef {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: Option<String>,
 }
 
 /// POST /deleteBookSources - 批量删除书源
 pub async fn delete_book_sources(
     State(state): State<Arc<AppState>>,
-    Json(sources): Json<Vec<serde_json::Value>>,
-) -> Json<ApiResponse<i32>> {
+    Json(sources): Json<Vec<SourceRef>>,
+) -> ApiResult<i32> {
     let mut deleted_count = 0;
     for source in sources {
-        if let Some(url) = source.get("bookSourceUrl").and_then(|v| v.as_str()) {
+        if let Some(url) = source.book_source_url.as_deref() {
             if state.source_service.delete_source(url).await.is_ok() {
                 deleted_count += 1;
             }
         }
     }
-    Json(ApiResponse::success(deleted_count))
+    Ok(Json(ApiResponse::success(deleted_count)))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SyncRemoteRequest {
     pub url: String,
 }
@@ -242,19 +432,84 @@ pub struct SyncRemoteRequest {
 pub async fn save_from_remote_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SyncRemoteRequest>,
-) -> Json<ApiResponse<SyncResult>> {
-    match state.source_service.save_from_remote_source(&req.url).await {
-        Ok(count) => Json(ApiResponse::success(SyncResult { count })),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<SyncResult> {
+    let report = state.source_service.save_from_remote_source(&req.url).await?;
+    Ok(Json(ApiResponse::success(SyncResult {
+        count: report.accepted() as i32,
+    })))
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, ToSchema)]
 pub struct SyncResult {
     pub count: i32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddSubscriptionRequest {
+    pub url: String,
+    #[serde(default)]
+    pub name: String,
+    /// 每天自动更新
+    #[serde(default)]
+    pub auto_update: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubscriptionRef {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteSubscriptionRequest {
+    pub url: String,
+    /// 一并删除从该订阅导入的书源
+    #[serde(default)]
+    pub delete_sources: bool,
+}
+
+/// POST /addSourceSubscription - 添加书源订阅
+pub async fn add_source_subscription(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AddSubscriptionRequest>,
+) -> ApiResult<SourceSubscription> {
+    let subscription = state
+        .source_service
+        .add_subscription(&req.url, &req.name, req.auto_update)
+        .await?;
+    Ok(Json(ApiResponse::success(subscription)))
+}
+
+/// GET /getSourceSubscriptions - 获取书源订阅
+pub async fn get_source_subscriptions(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Vec<SourceSubscription>> {
+    Ok(Json(ApiResponse::success(state.source_service.get_subscriptions().await)))
+}
+
+/// POST /refreshSourceSubscription - 下载订阅的书源文件并合并导入
+pub async fn refresh_source_subscription(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SubscriptionRef>,
+) -> ApiResult<SourceSubscription> {
+    let subscription = state.source_service.refresh_subscription(&req.url).await?;
+    Ok(Json(ApiResponse::success(subscription)))
+}
+
+/// POST /deleteSourceSubscription - 删除书源订阅，返回一并删除的书源数
+pub async fn delete_source_subscription(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DeleteSubscriptionRequest>,
+) -> ApiResult<usize> {
+    let removed = state
+        .source_service
+        .delete_subscription(&req.url, req.delete_sources)
+        .await?;
+    Ok(Json(ApiResponse::success(removed)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct InjectCookieRequest {
     #[serde(rename = "bookSourceUrl")]
     pub book_source_url: String,
@@ -265,14 +520,99 @@ pub struct InjectCookieRequest {
 pub async fn inject_cookies(
     State(state): State<Arc<AppState>>,
     Json(req): Json<InjectCookieRequest>,
-) -> Json<ApiResponse<()>> {
-    match state.source_service.inject_cookies(&req.book_source_url, &req.cookies).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
-    }
+) -> ApiResult<()> {
+    state.source_service.inject_cookies(&req.book_source_url, &req.cookies).await?;
+    Ok(Json(ApiResponse::success(())))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LoginInfoQuery {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+}
+
+/// GET /getLoginInfo - 获取书源登录地址与 loginUi 表单定义
+pub async fn get_login_info(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginInfoQuery>,
+) -> ApiResult<SourceLoginInfo> {
+    let info = state.source_service.get_login_info(&query.book_source_url).await?;
+    Ok(Json(ApiResponse::success(info)))
+}
+
+/// GET /getSourceVariable - 获取书源变量及 variableComment 说明
+pub async fn get_source_variable(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginInfoQuery>,
+) -> ApiResult<SourceVariable> {
+    let variable = state.source_service.get_source_variable(&query.book_source_url).await?;
+    Ok(Json(ApiResponse::success(variable)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSourceVariableRequest {
+    pub book_source_url: String,
+    #[serde(default)]
+    pub variable: String,
+}
+
+/// POST /saveSourceVariable - 保存书源变量，规则中通过 `source.getVariable()` 读取
+pub async fn save_source_variable(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SaveSourceVariableRequest>,
+) -> ApiResult<()> {
+    state
+        .source_service
+        .save_source_variable(&req.book_source_url, &req.variable)
+        .await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginSourceRequest {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+    /// 登录表单填写的字段，如 username、password
+    #[serde(default)]
+    pub data: HashMap<String, String>,
+}
+
+/// POST /loginBookSource - 执行书源登录，返回 loginCheckJs 的检测结果
+pub async fn login_book_source(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LoginSourceRequest>,
+) -> ApiResult<LoginResult> {
+    let result = state
+        .source_service
+        .login_source(&req.book_source_url, req.data)
+        .await?;
+    Ok(Json(ApiResponse::success(result)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LogoutSourceRequest {
+    #[serde(rename = "bookSourceUrl")]
+    pub book_source_url: String,
+}
+
+/// POST /logoutBookSource - 退出书源登录，清除其域名下的 Cookie
+pub async fn logout_book_source(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LogoutSourceRequest>,
+) -> ApiResult<()> {
+    state.source_service.logout_source(&req.book_source_url).await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// POST /clearRuleCache - 清空书源规则编译缓存，返回删除的条目数
+pub async fn clear_rule_cache(State(state): State<Arc<AppState>>) -> ApiResult<usize> {
+    let removed = state.source_service.clear_rule_cache().await?;
+    Ok(Json(ApiResponse::success(removed)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CheckSourceRequest {
     #[serde(rename = "bookSourceUrl")]
     pub book_source_url: String,
@@ -282,9 +622,568 @@ pub struct CheckSourceRequest {
 pub async fn check_book_source(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CheckSourceRequest>,
-) -> Json<ApiResponse<bool>> {
-    match state.source_service.check_source(&req.book_source_url).await {
-        Ok(valid) => Json(ApiResponse::success(valid)),
-        Err(e) => Json(ApiResponse::error(&e.to_string())),
+) -> ApiResult<bool> {
+    let valid = state.source_service.check_source(&req.book_source_url).await?;
+    Ok(Json(ApiResponse::success(valid)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use crate::engine::test_server::{header, request_path, spawn_server};
+    use crate::services::SearchFilter;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn create_test_state(name: &str) -> Arc<AppState> {
+        let dir = format!("/tmp/reader_tests_api_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        Arc::new(AppState::with_storage_dir(&dir))
+    }
+
+    async fn into_json(resp: impl IntoResponse) -> (StatusCode, serde_json::Value) {
+        let resp = resp.into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    /// 统计请求次数的搜索站点
+    fn spawn_counting_site(hits: Arc<AtomicUsize>) -> String {
+        spawn_server(move |_, _| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            let body = r#"<div class="book"><a href="/book/1">书名</a></div>"#;
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        })
+    }
+
+    fn source_json(base: &str) -> serde_json::Value {
+        serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": base,
+            "searchUrl": "/search?q={{key}}",
+            "ruleSearch": {
+                "bookList": "@css:div.book",
+                "name": "@css:a@text",
+                "bookUrl": "@css:a@href"
+            }
+        })
+    }
+
+    fn toggle(urls: &[&str]) -> Json<ToggleSourcesRequest> {
+        Json(ToggleSourcesRequest {
+            book_source_urls: urls.iter().map(|u| u.to_string()).collect(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_disabled_source_never_searched() {
+        let state = create_test_state("disabled_source");
+        let enabled_hits = Arc::new(AtomicUsize::new(0));
+        let disabled_hits = Arc::new(AtomicUsize::new(0));
+        let enabled = spawn_counting_site(enabled_hits.clone());
+        let disabled = spawn_counting_site(disabled_hits.clone());
+        let sources = serde_json::json!([source_json(&enabled), source_json(&disabled)]);
+        state.source_service.import_sources(&sources.to_string(), false).await.unwrap();
+
+        let (_, body) = into_json(disable_book_sources(State(state.clone()), toggle(&[&disabled])).await).await;
+        assert_eq!(body["data"], 1);
+
+        let merged = state.book_service.search_merged("书名", 4, &SearchFilter::default()).await.unwrap();
+        assert!(!merged.books.is_empty());
+        assert!(enabled_hits.load(Ordering::SeqCst) > 0);
+        assert_eq!(disabled_hits.load(Ordering::SeqCst), 0);
+
+        let query = GetBookSourcesQuery {
+            sort: None,
+            enabled: Some(true),
+            health: None,
+        };
+        let (_, body) = into_json(get_book_sources(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        assert_eq!(body["data"][0]["bookSourceUrl"], enabled.as_str());
+
+        // 切换到已禁用的书源返回结构化错误
+        let req = SetSourceRequest {
+            book_url: format!("{}/book/1", enabled),
+            new_url: format!("{}/book/1", disabled),
+            book_source_url: disabled.clone(),
+        };
+        let (status, body) = into_json(set_book_source(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["errorCode"], "SOURCE_DISABLED");
+        assert_eq!(body["detail"]["bookSourceUrl"], disabled.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_broken_source_flagged_skipped_and_recovered() {
+        let state = create_test_state("source_health");
+        let healthy_hits = Arc::new(AtomicUsize::new(0));
+        let broken_hits = Arc::new(AtomicUsize::new(0));
+        let healthy = spawn_counting_site(healthy_hits.clone());
+        let broken = spawn_counting_site(broken_hits.clone());
+        let now = chrono::Utc::now().timestamp_millis();
+        let four_days_ago = now - 4 * 24 * 60 * 60 * 1000;
+
+        let mut healthy_source = source_json(&healthy);
+        healthy_source["lastTest"] = serde_json::json!({"passed": true, "testedAt": now});
+        // 已连续失败两次、持续四天，搜索规则找不到结果
+        let mut broken_source = source_json(&broken);
+        broken_source["ruleSearch"]["bookList"] = serde_json::json!("@css:div.missing");
+        broken_source["health"] = serde_json::json!("degraded");
+        broken_source["lastTest"] = serde_json::json!({
+            "passed": false,
+            "testedAt": four_days_ago,
+            "consecutiveFailures": 2,
+            "failingSince": four_days_ago,
+        });
+        let sources = serde_json::json!([healthy_source, broken_source.clone()]);
+        state.source_service.import_sources(&sources.to_string(), false).await.unwrap();
+
+        // 最久未测试的书源先检查，第三次失败后标记为 broken，但仍保持启用
+        let card = state.source_service.check_next_source_health().await.unwrap().unwrap();
+        assert_eq!(card.book_source_url, broken);
+        assert!(!card.passed);
+        let query = GetBookSourcesQuery { sort: None, enabled: Some(true), health: Some(SourceHealth::Broken) };
+        let (_, body) = into_json(get_book_sources(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        assert_eq!(body["data"][0]["bookSourceUrl"], broken.as_str());
+        assert_eq!(body["data"][0]["lastTest"]["consecutiveFailures"], 3);
+
+        let (_, body) = into_json(source_health_report(State(state.clone())).await).await;
+        assert_eq!((body["data"]["healthy"].as_u64(), body["data"]["broken"].as_u64()), (Some(1), Some(1)));
+        assert_eq!(body["data"]["recentFailures"][0]["bookSourceUrl"], broken.as_str());
+        assert_eq!(body["data"]["recentFailures"][0]["failedStage"], "search");
+
+        // 多源搜索可跳过失效的书源
+        broken_hits.store(0, Ordering::SeqCst);
+        let skip_broken = SearchFilter { skip_broken: true, ..Default::default() };
+        state.book_service.search_merged("书名", 4, &skip_broken).await.unwrap();
+        assert!(healthy_hits.load(Ordering::SeqCst) > 0);
+        assert_eq!(broken_hits.load(Ordering::SeqCst), 0);
+        state.book_service.search_merged("书名", 4, &SearchFilter::default()).await.unwrap();
+        assert!(broken_hits.load(Ordering::SeqCst) > 0);
+
+        // 修复规则后测试通过 (手动测试与定期检查共用状态)，清除失效标记
+        broken_source["ruleSearch"]["bookList"] = serde_json::json!("@css:div.book");
+        broken_source["ruleToc"] = serde_json::json!({
+            "chapterList": "@css:div.book",
+            "chapterName": "@css:a@text",
+            "chapterUrl": "@css:a@href",
+        });
+        broken_source["ruleContent"] = serde_json::json!({"content": "@css:div.book@text"});
+        let sources = serde_json::json!([broken_source]);
+        state.source_service.import_sources(&sources.to_string(), false).await.unwrap();
+        let card = state.source_service.test_source(&broken, &SourceTestOptions::default()).await.unwrap();
+        assert!(card.passed, "{:?}", card.failed_stage());
+        let (_, body) = into_json(source_health_report(State(state.clone())).await).await;
+        assert_eq!((body["data"]["healthy"].as_u64(), body["data"]["broken"].as_u64()), (Some(2), Some(0)));
+        assert!(body["data"]["recentFailures"].as_array().unwrap().is_empty());
+    }
+
+    /// 统计请求次数、总是返回 429 的站点
+    fn spawn_throttled_site(hits: Arc<AtomicUsize>) -> String {
+        spawn_server(move |_, _| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            (429, vec![("Retry-After", "120".to_string())], "")
+        })
+    }
+
+    #[tokio::test]
+    async fn test_throttled_source_cools_down() {
+        let state = create_test_state("throttled_source");
+        let ok_hits = Arc::new(AtomicUsize::new(0));
+        let throttled_hits = Arc::new(AtomicUsize::new(0));
+        let ok = spawn_counting_site(ok_hits.clone());
+        let throttled = spawn_throttled_site(throttled_hits.clone());
+        let sources = serde_json::json!([source_json(&ok), source_json(&throttled)]);
+        state.source_service.import_sources(&sources.to_string(), false).await.unwrap();
+
+        let merged = state.book_service.search_merged("书名", 4, &SearchFilter::default()).await.unwrap();
+        assert!(!merged.books.is_empty());
+        // 429 不重试，书源进入冷却
+        assert_eq!(throttled_hits.load(Ordering::SeqCst), 1);
+        let remaining = state.cooldowns.remaining(&throttled).unwrap();
+        assert!(remaining > std::time::Duration::from_secs(100));
+
+        let (_, body) = into_json(get_source_stats(State(state.clone())).await).await;
+        let stats = body["data"].as_array().unwrap();
+        let stat = |url: &str| stats.iter().find(|s| s["bookSourceUrl"] == url).unwrap().clone();
+        assert!(stat(&throttled)["cooldownUntil"].as_i64().unwrap() > chrono::Utc::now().timestamp_millis());
+        assert!(stat(&ok).get("cooldownUntil").is_none());
+
+        // 冷却期间多源搜索跳过该书源
+        let ok_before = ok_hits.load(Ordering::SeqCst);
+        let merged = state.book_service.search_merged("书名", 4, &SearchFilter::default()).await.unwrap();
+        assert!(!merged.books.is_empty());
+        assert!(ok_hits.load(Ordering::SeqCst) > ok_before);
+        assert_eq!(throttled_hits.load(Ordering::SeqCst), 1);
+
+        state.cooldowns.clear(&throttled);
+    }
+
+    #[tokio::test]
+    async fn test_toggle_sources_persists() {
+        let state = create_test_state("toggle_sources");
+        let sources: Vec<serde_json::Value> = ["https://a.com", "https://b.com"]
+            .into_iter()
+            .map(|url| {
+                let mut source = source_json(url);
+                source["exploreUrl"] = serde_json::json!("玄幻::/explore/xuanhuan");
+                source
+            })
+            .collect();
+        let sources = serde_json::to_string(&sources).unwrap();
+        state.source_service.import_sources(&sources, false).await.unwrap();
+
+        let urls = ["https://a.com", "https://b.com", "https://missing.com"];
+        let (_, body) = into_json(disable_book_sources(State(state.clone()), toggle(&urls)).await).await;
+        assert_eq!(body["data"], 2);
+        state.storage.flush().await.unwrap();
+
+        // 从文件重新加载
+        let reloaded = crate::services::SourceService::with_storage(state.storage.clone(), state.kv_store.clone());
+        let disabled = reloaded.get_sources_sorted(None, Some(false), None).await.unwrap();
+        assert_eq!(disabled.len(), 2);
+
+        let (_, body) = into_json(enable_book_sources(State(state.clone()), toggle(&["https://b.com"])).await).await;
+        assert_eq!(body["data"], 1);
+        let (_, body) = into_json(enable_book_sources(State(state.clone()), toggle(&["https://b.com"])).await).await;
+        assert_eq!(body["data"], 0);
+
+        // 已禁用的书源不出现在发现列表中
+        let explore = state.source_service.get_explore_sources().await.unwrap();
+        let explore: Vec<&str> = explore.iter().map(|s| s.book_source_url.as_str()).collect();
+        assert_eq!(explore, vec!["https://b.com"]);
+        let err = state.source_service.get_explore_kinds("https://a.com").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::services::ServiceError>(),
+            Some(crate::services::ServiceError::SourceDisabled(_))
+        ));
+    }
+
+    /// 换源站点：搜索结果带作者，书籍页即目录页，有两章
+    fn spawn_change_source_site(author: &'static str) -> String {
+        spawn_server(move |head, _| {
+            let body = if request_path(head).starts_with("/search") {
+                format!(
+                    r#"<div class="book"><a href="/book/1">书名</a><span>{}</span><em>第2章</em></div>"#,
+                    author
+                )
+            } else {
+                r#"<ul><li><a href="/c/1">第1章</a></li><li><a href="/c/2">第2章</a></li></ul>"#.to_string()
+            };
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_available_sources_filters_by_author() {
+        let state = create_test_state("available_sources");
+        let matching = spawn_change_source_site("作者：张三");
+        let wrong_author = spawn_change_source_site("李四");
+        // 端口已关闭的书源，搜索时连接失败
+        let erroring = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let sources: Vec<serde_json::Value> = [&matching, &wrong_author, &erroring]
+            .into_iter()
+            .map(|base| {
+                let mut source = source_json(base);
+                source["ruleSearch"]["author"] = serde_json::json!("@css:span@text");
+                source["ruleSearch"]["lastChapter"] = serde_json::json!("@css:em@text");
+                source["ruleToc"] = serde_json::json!({
+                    "chapterList": "@css:ul li a",
+                    "chapterName": "@css:a@text",
+                    "chapterUrl": "@css:a@href"
+                });
+                source
+            })
+            .collect();
+        let sources = serde_json::to_string(&sources).unwrap();
+        state.source_service.import_sources(&sources, false).await.unwrap();
+
+        let req = AvailableSourceRequest {
+            book_url: format!("{}/book/1", erroring),
+            name: Some("书名".to_string()),
+            author: Some("张三".to_string()),
+            with_chapter_count: Some(true),
+            book_source_group: None,
+            concurrent_count: Some(3),
+        };
+        let (status, body) = into_json(get_available_book_source(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let candidates = body["data"].as_array().unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0]["sourceUrl"], matching.as_str());
+        assert_eq!(candidates[0]["bookUrl"], format!("{}/book/1", matching));
+        assert_eq!(candidates[0]["lastChapter"], "第2章");
+        assert_eq!(candidates[0]["chapterCount"], 2);
+        assert!(candidates[0]["respondTime"].is_u64());
+
+        // SSE 与 JSON 接口共用同一实现
+        let query = ChangeSourceQuery::new("书名", "张三");
+        let events: Vec<_> = state.book_service.change_source_events(query).collect().await;
+        let found = events
+            .iter()
+            .filter(|e| matches!(e, ChangeSourceEvent::Found(_)))
+            .count();
+        assert_eq!(found, 1);
+        match events.last() {
+            Some(ChangeSourceEvent::Done(candidates)) => {
+                assert_eq!(candidates.len(), 1);
+                assert_eq!(candidates[0].chapter_count, None);
+            }
+            other => panic!("unexpected last event: {:?}", other),
+        }
+    }
+
+    /// 登录站点：正确的账号密码返回 welcome 并设置 token Cookie，记录搜索请求携带的 Cookie
+    fn spawn_login_site(search_cookies: Arc<std::sync::Mutex<Vec<String>>>) -> String {
+        spawn_server(move |head, _| {
+            let path = request_path(head);
+            let mut headers = vec![("Content-Type", "text/html; charset=utf-8".to_string())];
+            let body = if path == "/login?user=alice&pass=secret" {
+                headers.push(("Set-Cookie", "token=abc123; Path=/".to_string()));
+                "welcome alice"
+            } else if path.starts_with("/login") {
+                "denied"
+            } else {
+                let cookie = header(head, "cookie").unwrap_or("").to_string();
+                search_cookies.lock().unwrap().push(cookie);
+                r#"<div class="book"><a href="/book/1">书名</a></div>"#
+            };
+            (200, headers, body)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_source_variable_shared_by_native_and_js() {
+        use crate::engine::rule_analyzer::RuleAnalyzer;
+
+        let state = create_test_state("source_variable");
+        let base = "https://var.example".to_string();
+        let mut source = source_json(&base);
+        source["variableComment"] = serde_json::json!("填写 API token");
+        let sources = serde_json::json!([source]).to_string();
+        state.source_service.import_sources(&sources, false).await.unwrap();
+
+        let query = GetBookSourcesQuery { sort: None, enabled: None, health: None };
+        let (_, body) = into_json(get_book_sources(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"][0]["variableComment"], "填写 API token");
+
+        let req = SaveSourceVariableRequest { book_source_url: base.clone(), variable: "tok-1".to_string() };
+        into_json(save_source_variable(State(state.clone()), Json(req)).await).await;
+        let query = LoginInfoQuery { book_source_url: base.clone() };
+        let (_, body) = into_json(get_source_variable(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"]["variable"], "tok-1");
+        assert_eq!(body["data"]["variableComment"], "填写 API token");
+
+        let kv_store = state.kv_store.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut analyzer = RuleAnalyzer::new(kv_store).unwrap();
+            // 请求的 baseUrl 与书源 URL 不同，变量仍按 bookSourceUrl 读取
+            analyzer.set_base_url("https://cdn.var.example/api");
+            analyzer.set_source_url(&base);
+            let vars = HashMap::new();
+            assert_eq!(analyzer.evaluate_url("/s?t={{source.getVariable()}}", &vars).unwrap(), "/s?t=tok-1");
+            let js = "@js:(function () { return source.getVariable(); })()";
+            assert_eq!(analyzer.get_string("", js).unwrap(), "tok-1");
+
+            analyzer.get_string("", "@js:(function () { source.setVariable('tok-2'); return ''; })()").unwrap();
+            assert_eq!(analyzer.evaluate_url("{{source.getVariable()}}", &vars).unwrap(), "tok-2");
+        })
+        .await
+        .unwrap();
+
+        let query = LoginInfoQuery { book_source_url: "https://var.example".to_string() };
+        let (_, body) = into_json(get_source_variable(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"]["variable"], "tok-2");
+    }
+
+    #[tokio::test]
+    async fn test_login_captures_cookies() {
+        use crate::engine::cookie::CookieManager;
+
+        let state = create_test_state("login_source");
+        let search_cookies = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let base = spawn_login_site(search_cookies.clone());
+        let mut source = source_json(&base);
+        source["loginUrl"] = serde_json::json!(format!("{}/login?user={{{{username}}}}&pass={{{{password}}}}", base));
+        source["loginCheckJs"] = serde_json::json!("result.indexOf('welcome') > -1");
+        source["loginUi"] = serde_json::json!(r#"[{"name":"username","type":"text"},{"name":"password","type":"password"}]"#);
+        let js_url = format!("{}/js", base);
+        let js_source = serde_json::json!({
+            "bookSourceUrl": js_url,
+            "bookSourceName": "js",
+            "loginUrl": format!(
+                "function login() {{ return java.ajax('{}/login?user=' + result.username + '&pass=' + result.password) }}",
+                base
+            ),
+            "loginCheckJs": "result.indexOf('welcome') > -1"
+        });
+        let sources = serde_json::json!([source, js_source]).to_string();
+        state.source_service.import_sources(&sources, false).await.unwrap();
+
+        let query = LoginInfoQuery { book_source_url: base.clone() };
+        let (_, body) = into_json(get_login_info(State(state.clone()), Query(query)).await).await;
+        assert_eq!(body["data"]["loginUi"][1]["type"], "password");
+
+        let login = |url: &str, password: &str| {
+            let data = [("username", "alice"), ("password", password)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            Json(LoginSourceRequest { book_source_url: url.to_string(), data })
+        };
+        let token = || CookieManager::shared().get_cookie("127.0.0.1", Some("token"));
+
+        // loginCheckJs 未通过
+        let (_, body) = into_json(login_book_source(State(state.clone()), login(&base, "wrong")).await).await;
+        assert_eq!(body["data"]["success"], false);
+        assert_eq!(token(), "");
+
+        let (_, body) = into_json(login_book_source(State(state.clone()), login(&base, "secret")).await).await;
+        assert_eq!(body["data"]["success"], true);
+        assert_eq!(token(), "abc123");
+        let saved: String = state.storage.read_file_or_default("cookies.json").await;
+        assert!(saved.contains("abc123"));
+
+        // 之后的搜索携带登录 Cookie
+        state.book_service.search_merged("书名", 4, &SearchFilter::default()).await.unwrap();
+        assert!(search_cookies.lock().unwrap().iter().any(|c| c.contains("token=abc123")));
+
+        let req = Json(LogoutSourceRequest { book_source_url: base.clone() });
+        into_json(logout_book_source(State(state.clone()), req).await).await;
+        assert_eq!(token(), "");
+
+        // 脚本形式的 loginUrl，表单字段通过 result 传入
+        let (_, body) = into_json(login_book_source(State(state.clone()), login(&js_url, "secret")).await).await;
+        assert_eq!(body["data"]["success"], true);
+        assert_eq!(token(), "abc123");
+
+        let req = Json(LogoutSourceRequest { book_source_url: js_url.clone() });
+        into_json(logout_book_source(State(state.clone()), req).await).await;
+        assert_eq!(token(), "");
+    }
+
+    /// 依次返回各版本书源文件的远程地址，最后一个版本之后保持不变
+    fn spawn_versioned_collection(versions: Vec<serde_json::Value>) -> String {
+        let served = AtomicUsize::new(0);
+        let base = spawn_server(move |_, _| {
+            let served = served.fetch_add(1, Ordering::SeqCst);
+            let body = versions[served.min(versions.len() - 1)].to_string();
+            (200, vec![("Content-Type", "application/json".to_string())], body)
+        });
+        format!("{}/sources.json", base)
+    }
+
+    #[tokio::test]
+    async fn test_source_subscription_refresh_and_delete() {
+        let state = create_test_state("source_subscription");
+        let source = |url: &str, name: &str| {
+            serde_json::json!({"bookSourceUrl": url, "bookSourceName": name, "searchUrl": "/s?q={{key}}"})
+        };
+        let v1 = serde_json::json!([source("https://a.example", "A"), source("https://b.example", "B")]);
+        let v2 = serde_json::json!([
+            source("https://a.example", "A"),
+            source("https://b.example", "B 新版"),
+            source("https://c.example", "C"),
+        ]);
+        let url = spawn_versioned_collection(vec![v1, v2]);
+        let manual = serde_json::json!([source("https://manual.example", "手动")]);
+        state.source_service.import_sources(&manual.to_string(), false).await.unwrap();
+
+        let req = AddSubscriptionRequest { url: "ftp://x".to_string(), name: String::new(), auto_update: false };
+        let (status, _) = into_json(add_source_subscription(State(state.clone()), Json(req)).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let req = AddSubscriptionRequest { url: url.clone(), name: "我的书源".to_string(), auto_update: true };
+        let (_, body) = into_json(add_source_subscription(State(state.clone()), Json(req)).await).await;
+        assert_eq!(body["data"]["autoUpdate"], true);
+
+        // 第一个版本：全部新增，书源带有订阅地址
+        let (_, body) = into_json(refresh_source_subscription(State(state.clone()), Json(SubscriptionRef { url: url.clone() })).await).await;
+        assert_eq!((body["data"]["added"].as_u64(), body["data"]["updated"].as_u64()), (Some(2), Some(0)));
+        let updated_at = body["data"]["lastUpdateTime"].as_i64().unwrap();
+        let b = state.source_service.get_source_by_url("https://b.example").await.unwrap();
+        assert_eq!(b.subscription_url.as_deref(), Some(url.as_str()));
+
+        // 不到一天不自动更新；到期后自动更新到第二个版本：一个未变、一个更新、一个新增
+        let day = 24 * 60 * 60 * 1000;
+        assert_eq!(state.source_service.refresh_auto_subscriptions(updated_at + day - 1).await, 0);
+        assert_eq!(state.source_service.refresh_auto_subscriptions(updated_at + day).await, 1);
+        let (_, body) = into_json(get_source_subscriptions(State(state.clone())).await).await;
+        let subscription = &body["data"][0];
+        assert_eq!(subscription["name"], "我的书源");
+        let counts = ["added", "updated", "unchanged", "invalid"].map(|k| subscription[k].as_u64().unwrap());
+        assert_eq!(counts, [1, 1, 1, 0]);
+        let b = state.source_service.get_source_by_url("https://b.example").await.unwrap();
+        assert_eq!(b.book_source_name, "B 新版");
+
+        // 删除订阅及其书源，手动导入的书源保留
+        let req = DeleteSubscriptionRequest { url: url.clone(), delete_sources: true };
+        let (_, body) = into_json(delete_source_subscription(State(state.clone()), Json(req)).await).await;
+        assert_eq!(body["data"], 3);
+        let remaining = state.source_service.get_all_sources().await.unwrap();
+        let urls: Vec<_> = remaining.iter().map(|s| s.book_source_url.as_str()).collect();
+        assert_eq!(urls, vec!["https://manual.example"]);
+        let (status, _) = into_json(refresh_source_subscription(State(state.clone()), Json(SubscriptionRef { url })).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_keeps_manual_source_with_same_url() {
+        let state = create_test_state("source_subscription_manual");
+        let source = |url: &str, name: &str| {
+            serde_json::json!({"bookSourceUrl": url, "bookSourceName": name, "searchUrl": "/s?q={{key}}"})
+        };
+        let remote = serde_json::json!([source("https://shared.example", "订阅版"), source("https://sub.example", "订阅")]);
+        let url = spawn_versioned_collection(vec![remote]);
+        let manual = serde_json::json!([source("https://shared.example", "手动版")]);
+        state.source_service.import_sources(&manual.to_string(), false).await.unwrap();
+        state.source_service.add_subscription(&url, "", false).await.unwrap();
+
+        // 同 URL 的手动书源不被订阅覆盖或认领
+        let (_, body) = into_json(refresh_source_subscription(State(state.clone()), Json(SubscriptionRef { url: url.clone() })).await).await;
+        let counts = ["added", "updated", "unchanged", "skipped"].map(|k| body["data"][k].as_u64().unwrap());
+        assert_eq!(counts, [1, 0, 0, 1]);
+        let shared = state.source_service.get_source_by_url("https://shared.example").await.unwrap();
+        assert_eq!((shared.book_source_name.as_str(), shared.subscription_url), ("手动版", None));
+
+        let req = DeleteSubscriptionRequest { url, delete_sources: true };
+        let (_, body) = into_json(delete_source_subscription(State(state.clone()), Json(req)).await).await;
+        assert_eq!(body["data"], 1);
+        let remaining = state.source_service.get_all_sources().await.unwrap();
+        let names: Vec<_> = remaining.iter().map(|s| s.book_source_name.as_str()).collect();
+        assert_eq!(names, vec!["手动版"]);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_source() {
+        let state = create_test_state("inspect_source");
+        let mut source = source_json("https://inspect.example.com");
+        source["ruleSearch"]["author"] = "@js:java.base64Decode(result)".into();
+        state.source_service.save_source(&source.to_string()).await.unwrap();
+
+        let query = |url: &str| {
+            Query(LoginInfoQuery {
+                book_source_url: url.to_string(),
+            })
+        };
+        let url = "https://inspect.example.com";
+        let (status, body) = into_json(inspect_source(State(state.clone()), query(url)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let data = &body["data"];
+        assert_eq!(data["searchUrl"]["original"], "/search?q={{key}}");
+        assert_eq!(data["rules"][0]["field"], "ruleSearch.bookList");
+        assert_eq!(data["rules"][0]["rule"], "@css:div.book");
+        assert_eq!(data["rules"][0]["compiled"]["kind"], "selector");
+        assert_eq!(data["counts"]["rules"], 4);
+        assert_eq!(data["counts"]["native"], 1);
+
+        let (status, _) = into_json(inspect_source(State(state), query("https://missing.example.com")).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
     }
 }
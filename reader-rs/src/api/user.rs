@@ -0,0 +1,267 @@
+//! 多用户模式：登录接口与鉴权中间件
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tower::ServiceExt;
+
+use super::error::ApiError;
+use crate::models::ApiResponse;
+use crate::services::{UserServices, TOKEN_TTL};
+
+/// 保存登录令牌的 Cookie
+const TOKEN_COOKIE: &str = "reader_token";
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResult {
+    pub username: String,
+    pub token: String,
+}
+
+/// 当前登录的用户名，由鉴权中间件放入请求扩展
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub String);
+
+/// 各用户的接口路由，首次访问时创建
+struct UserRouters {
+    users: Arc<UserServices>,
+    routers: RwLock<HashMap<String, Router>>,
+}
+
+impl UserRouters {
+    async fn router(&self, username: &str) -> Router {
+        if let Some(router) = self.routers.read().await.get(username) {
+            return router.clone();
+        }
+        let state = self.users.state(username).await;
+        self.routers
+            .write()
+            .await
+            .entry(username.to_string())
+            .or_insert_with(|| super::reader_routes(state))
+            .clone()
+    }
+}
+
+/// 多用户模式的接口：`/login` 之外的请求都需要令牌
+pub fn routes(users: Arc<UserServices>) -> Router {
+    let state = Arc::new(UserRouters {
+        users,
+        routers: RwLock::new(HashMap::new()),
+    });
+    Router::new()
+        .fallback(dispatch)
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route("/login", post(login))
+        .with_state(state)
+}
+
+/// POST /login - 登录，令牌同时通过 Cookie 与响应体返回
+async fn login(State(state): State<Arc<UserRouters>>, Json(req): Json<LoginRequest>) -> Result<Response, ApiError> {
+    let token = state.users.accounts.login(&req.username, &req.password).await?;
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        TOKEN_COOKIE,
+        token,
+        TOKEN_TTL.as_secs()
+    );
+    let result = LoginResult {
+        username: req.username,
+        token,
+    };
+    Ok(([(header::SET_COOKIE, cookie)], Json(ApiResponse::success(result))).into_response())
+}
+
+/// 校验 `Authorization: Bearer` 头或 Cookie 中的令牌，未登录时返回 401
+async fn authenticate(
+    State(state): State<Arc<UserRouters>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = request_token(req.headers()).ok_or_else(|| ApiError::Unauthorized("Login required".to_string()))?;
+    let username = state
+        .users
+        .accounts
+        .authenticate(&token)
+        .await
+        .ok_or_else(|| ApiError::Unauthorized("Invalid or expired token".to_string()))?;
+    req.extensions_mut().insert(CurrentUser(username));
+    Ok(next.run(req).await)
+}
+
+/// 把请求交给当前用户的接口
+async fn dispatch(
+    State(state): State<Arc<UserRouters>>,
+    Extension(CurrentUser(username)): Extension<CurrentUser>,
+    req: Request,
+) -> Response {
+    match state.router(&username).await.oneshot(req).await {
+        Ok(resp) => resp,
+        Err(never) => match never {},
+    }
+}
+
+fn request_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.trim().to_string());
+    }
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == TOKEN_COOKIE).then(|| value.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::AccessConfig;
+    use crate::services::{AppState, UserConfig};
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+
+    async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let resp = app
+            .clone()
+            .oneshot(req.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_users_have_separate_bookshelves() {
+        let dir = "/tmp/reader_tests_api_multi_user";
+        let _ = std::fs::remove_dir_all(dir);
+        let config = UserConfig {
+            multi_user: true,
+            secret: "test secret".to_string(),
+            shared_sources: true,
+        };
+        let state = Arc::new(AppState::with_config(dir, &config));
+        let users = state.users.clone().unwrap();
+        users.accounts.add_user("alice", "a-pass").await.unwrap();
+        users.accounts.add_user("bob", "b-pass").await.unwrap();
+        let app = Router::new().nest(
+            "/reader3",
+            super::super::routes(state.clone(), &AccessConfig::default()),
+        );
+
+        let (status, body) = call(
+            &app,
+            Method::GET,
+            "/reader3/getBookshelf",
+            None,
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["errorCode"], "UNAUTHORIZED");
+        let (status, _) = call(
+            &app,
+            Method::GET,
+            "/reader3/getBookshelf",
+            Some("alice.1.bad"),
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let login = |username: &'static str, password: &'static str| {
+            let app = app.clone();
+            async move {
+                let body = serde_json::json!({ "username": username, "password": password });
+                call(&app, Method::POST, "/reader3/login", None, body).await
+            }
+        };
+        assert_eq!(login("alice", "b-pass").await.0, StatusCode::UNAUTHORIZED);
+        let alice = login("alice", "a-pass").await.1["data"]["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let bob = login("bob", "b-pass").await.1["data"]["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for (token, name) in [(&alice, "Alice 的书"), (&bob, "Bob 的书")] {
+            let book =
+                serde_json::json!({ "bookUrl": format!("https://example.com/{}", name), "name": name, "author": "" });
+            let (status, _) = call(&app, Method::POST, "/reader3/saveBook", Some(token), book).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        for (token, name) in [(&alice, "Alice 的书"), (&bob, "Bob 的书")] {
+            let (status, shelf) = call(
+                &app,
+                Method::GET,
+                "/reader3/getBookshelf",
+                Some(token),
+                serde_json::Value::Null,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            let names: Vec<&str> = shelf["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| b["name"].as_str().unwrap())
+                .collect();
+            assert_eq!(names, vec![name]);
+        }
+
+        // 每个用户的数据位于自己的目录，全局书架为空
+        assert!(std::path::Path::new(dir).join("users/alice/data").exists());
+        assert!(std::path::Path::new(dir).join("users/bob/data").exists());
+        assert!(state.book_service.get_bookshelf(false).await.unwrap().is_empty());
+        assert!(state.shutdown().await.is_empty());
+    }
+
+    #[test]
+    fn test_request_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; reader_token=abc.1.ff".parse().unwrap());
+        assert_eq!(request_token(&headers).as_deref(), Some("abc.1.ff"));
+        headers.insert(header::AUTHORIZATION, "Bearer xyz".parse().unwrap());
+        assert_eq!(request_token(&headers).as_deref(), Some("xyz"));
+        assert_eq!(request_token(&HeaderMap::new()), None);
+    }
+}
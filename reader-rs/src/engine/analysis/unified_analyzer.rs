@@ -5,20 +5,43 @@
 //!
 //! ## Analysis Strategy
 //!
-//! 1. **Cache Lookup** (O(1)): Check if already analyzed
+//! 1. **Cache Lookup** (O(1)): Check if this rule source was already analyzed
 //! 2. **Regex Analysis** (~3 µs): For simple patterns
 //! 3. **AST Analysis** (~10 µs): For complex expressions
 //! 4. **QuickJS Fallback**: When native execution is not possible
 
-use std::cell::RefCell;
+use parking_lot::Mutex;
 use std::collections::HashMap;
 
-use crate::engine::ast::{ExecutionPlanCompiler, JsAstParser};
+use crate::engine::ast::{AstAnalysisResult, ExecutionPlanCompiler, JsAstParser, NativeExecutionPlan};
 use crate::engine::js_analyzer::{AnalysisResult, JsPatternAnalyzer, NativeExecution};
+use crate::engine::stats::STATS;
 
 /// Maximum cache size (number of entries)
 const CACHE_MAX_SIZE: usize = 256;
 
+/// Which step of [`UnifiedJsAnalyzer::analyze`] decided how code runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalysisStrategy {
+    /// Matched a regex pattern
+    Regex,
+    /// Compiled from the AST
+    Ast,
+    /// Falls back to QuickJS
+    Js,
+}
+
+/// Uncached analysis of one piece of code
+#[derive(Debug, Clone)]
+pub struct AnalysisExplanation {
+    pub strategy: AnalysisStrategy,
+    pub result: AnalysisResult,
+    /// Native plans found by the AST analysis, including those it could not compile
+    pub plans: Vec<NativeExecutionPlan>,
+    /// Reason label for the `Js` strategy, as recorded in statistics
+    pub js_reason: Option<String>,
+}
+
 /// Unified JavaScript Analyzer with caching
 ///
 /// Combines regex-based pattern matching with AST-based analysis
@@ -33,21 +56,20 @@ pub struct UnifiedJsAnalyzer {
     /// Compiler for converting AST results to legacy format
     ast_compiler: ExecutionPlanCompiler,
 
-    /// LRU-like cache for analysis results (code hash -> result type)
-    /// Uses RefCell for interior mutability in analyze_readonly
-    cache: RefCell<AnalysisCache>,
+    /// LRU memo of analysis results keyed by rule source, shared by engine forks
+    cache: Mutex<AnalysisCache>,
 
     /// Statistics for analysis decisions
-    stats: RefCell<AnalysisStats>,
+    stats: Mutex<AnalysisStats>,
 }
 
-/// Simple LRU-like cache for analysis results
+/// LRU cache for analysis results
 #[derive(Debug, Default)]
 struct AnalysisCache {
-    /// Map from code hash to cached result type
-    entries: HashMap<u64, CachedResult>,
-    /// Order of insertion for LRU eviction
-    insert_order: Vec<u64>,
+    /// Map from rule source to cached result and its last use
+    entries: HashMap<String, (CachedResult, u64)>,
+    /// Monotonic use counter
+    tick: u64,
 }
 
 /// Cached analysis result (lightweight representation)
@@ -57,26 +79,35 @@ enum CachedResult {
     Native(NativeExecution),
     /// Chain of native executions
     NativeChain(Vec<NativeExecution>),
-    /// Requires JS execution
-    RequiresJs,
+    /// Requires JS execution, with the reason label recorded in statistics
+    RequiresJs(String),
 }
 
 impl AnalysisCache {
-    fn get(&self, hash: u64) -> Option<&CachedResult> {
-        self.entries.get(&hash)
+    fn get(&mut self, code: &str) -> Option<&CachedResult> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(code).map(|(result, used)| {
+            *used = tick;
+            &*result
+        })
     }
 
-    fn insert(&mut self, hash: u64, result: CachedResult) {
-        // Evict oldest if at capacity
-        if self.entries.len() >= CACHE_MAX_SIZE {
-            if let Some(oldest) = self.insert_order.first().cloned() {
+    fn insert(&mut self, code: &str, result: CachedResult) {
+        // Evict the least recently used entry if at capacity
+        if self.entries.len() >= CACHE_MAX_SIZE && !self.entries.contains_key(code) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(code, _)| code.clone());
+            if let Some(oldest) = oldest {
                 self.entries.remove(&oldest);
-                self.insert_order.remove(0);
             }
         }
 
-        self.entries.insert(hash, result);
-        self.insert_order.push(hash);
+        self.tick += 1;
+        self.entries.insert(code.to_string(), (result, self.tick));
     }
 
     fn len(&self) -> usize {
@@ -85,7 +116,6 @@ impl AnalysisCache {
 
     fn clear(&mut self) {
         self.entries.clear();
-        self.insert_order.clear();
     }
 }
 
@@ -151,15 +181,6 @@ impl Default for UnifiedJsAnalyzer {
     }
 }
 
-/// Simple hash function for code strings
-fn hash_code(code: &str) -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    code.hash(&mut hasher);
-    hasher.finish()
-}
-
 impl UnifiedJsAnalyzer {
     /// Create a new unified analyzer
     pub fn new() -> Self {
@@ -167,8 +188,8 @@ impl UnifiedJsAnalyzer {
             regex_analyzer: JsPatternAnalyzer::new(),
             ast_parser: JsAstParser::new(),
             ast_compiler: ExecutionPlanCompiler::new(),
-            cache: RefCell::new(AnalysisCache::default()),
-            stats: RefCell::new(AnalysisStats::default()),
+            cache: Mutex::new(AnalysisCache::default()),
+            stats: Mutex::new(AnalysisStats::default()),
         }
     }
 
@@ -180,69 +201,86 @@ impl UnifiedJsAnalyzer {
     /// 3. AST-based analysis
     /// 4. Falls back to RequiresJs if no native strategy found
     pub fn analyze(&self, code: &str) -> AnalysisResult {
-        let hash = hash_code(code);
-
         // Step 0: Check cache
+        if let Some(cached) = self.cache.lock().get(code) {
+            self.stats.lock().cache_hits += 1;
+            STATS.record_analysis_cache_hit();
+            if let CachedResult::RequiresJs(reason) = cached {
+                STATS.record_js_fallback(reason);
+            }
+            return cached_to_result(cached, code);
+        }
+        STATS.record_analysis_cache_miss();
+
+        let explanation = self.explain(code);
+        let cached = match &explanation.result {
+            AnalysisResult::Native(exec) => CachedResult::Native(exec.clone()),
+            AnalysisResult::NativeChain(chain) => CachedResult::NativeChain(chain.clone()),
+            AnalysisResult::RequiresJs(_) => {
+                let reason = explanation.js_reason.clone().unwrap_or_default();
+                STATS.record_js_fallback(&reason);
+                CachedResult::RequiresJs(reason)
+            }
+        };
         {
-            let cache = self.cache.borrow();
-            if let Some(cached) = cache.get(hash) {
-                self.stats.borrow_mut().cache_hits += 1;
-                return cached_to_result(cached);
+            let mut stats = self.stats.lock();
+            match explanation.strategy {
+                AnalysisStrategy::Regex => stats.regex_matches += 1,
+                AnalysisStrategy::Ast => stats.ast_matches += 1,
+                AnalysisStrategy::Js => stats.js_fallbacks += 1,
             }
         }
+        self.cache.lock().insert(code, cached);
+        explanation.result
+    }
 
+    /// Analyze code the way [`Self::analyze`] does, without the cache or statistics
+    ///
+    /// Also reports which step decided, the AST plans and why QuickJS is needed.
+    pub fn explain(&self, code: &str) -> AnalysisExplanation {
         // Step 1: Try regex-based pattern analysis (fastest)
         let regex_result = self.regex_analyzer.analyze(code);
-
-        match &regex_result {
-            AnalysisResult::Native(exec) => {
-                self.stats.borrow_mut().regex_matches += 1;
-                self.cache
-                    .borrow_mut()
-                    .insert(hash, CachedResult::Native(exec.clone()));
-                return regex_result;
-            }
-            AnalysisResult::NativeChain(chain) => {
-                self.stats.borrow_mut().regex_matches += 1;
-                self.cache
-                    .borrow_mut()
-                    .insert(hash, CachedResult::NativeChain(chain.clone()));
-                return regex_result;
-            }
-            AnalysisResult::RequiresJs(_) => {
-                // Continue to AST analysis
-            }
+        if !matches!(regex_result, AnalysisResult::RequiresJs(_)) {
+            return AnalysisExplanation {
+                strategy: AnalysisStrategy::Regex,
+                result: regex_result,
+                plans: Vec::new(),
+                js_reason: None,
+            };
         }
 
         // Step 2: Try AST-based analysis (more accurate)
         let ast_result = self.ast_parser.parse_and_analyze(code);
-
+        let plans = match &ast_result {
+            AstAnalysisResult::Native(plan) => vec![plan.clone()],
+            AstAnalysisResult::NativeChain(plans) => plans.clone(),
+            AstAnalysisResult::Partial { native_parts, .. } => native_parts.clone(),
+            AstAnalysisResult::RequiresJs { .. } => Vec::new(),
+        };
         if let Some(legacy) = self.ast_compiler.to_legacy_format(&ast_result) {
-            match &legacy {
-                AnalysisResult::Native(exec) => {
-                    self.stats.borrow_mut().ast_matches += 1;
-                    self.cache
-                        .borrow_mut()
-                        .insert(hash, CachedResult::Native(exec.clone()));
-                    return legacy;
-                }
-                AnalysisResult::NativeChain(chain) => {
-                    self.stats.borrow_mut().ast_matches += 1;
-                    self.cache
-                        .borrow_mut()
-                        .insert(hash, CachedResult::NativeChain(chain.clone()));
-                    return legacy;
-                }
-                AnalysisResult::RequiresJs(_) => {
-                    // Fall through
-                }
+            if !matches!(legacy, AnalysisResult::RequiresJs(_)) {
+                return AnalysisExplanation {
+                    strategy: AnalysisStrategy::Ast,
+                    result: legacy,
+                    plans,
+                    js_reason: None,
+                };
             }
         }
 
         // Step 3: Must use JS execution
-        self.stats.borrow_mut().js_fallbacks += 1;
-        self.cache.borrow_mut().insert(hash, CachedResult::RequiresJs);
-        AnalysisResult::RequiresJs(code.to_string())
+        let reason = match &ast_result {
+            AstAnalysisResult::RequiresJs { reason, .. } => reason.label(),
+            AstAnalysisResult::Partial { .. } => "partial".to_string(),
+            // The AST matched but the plan has no legacy equivalent
+            _ => "uncompiledPlan".to_string(),
+        };
+        AnalysisExplanation {
+            strategy: AnalysisStrategy::Js,
+            result: AnalysisResult::RequiresJs(code.to_string()),
+            plans,
+            js_reason: Some(reason),
+        }
     }
 
     /// Analyze without mutating stats (for read-only contexts)
@@ -253,27 +291,27 @@ impl UnifiedJsAnalyzer {
 
     /// Get analysis statistics
     pub fn stats(&self) -> AnalysisStats {
-        self.stats.borrow().clone()
+        self.stats.lock().clone()
     }
 
     /// Reset statistics
     pub fn reset_stats(&self) {
-        *self.stats.borrow_mut() = AnalysisStats::default();
+        *self.stats.lock() = AnalysisStats::default();
     }
 
     /// Get cache size
     pub fn cache_size(&self) -> usize {
-        self.cache.borrow().len()
+        self.cache.lock().len()
     }
 
     /// Clear the analysis cache
     pub fn clear_cache(&self) {
-        self.cache.borrow_mut().clear();
+        self.cache.lock().clear();
     }
 
     /// Get the native execution rate
     pub fn native_rate(&self) -> f64 {
-        let stats = self.stats.borrow();
+        let stats = self.stats.lock();
         let total = stats.regex_matches + stats.ast_matches + stats.js_fallbacks;
         if total == 0 {
             return 0.0;
@@ -284,9 +322,8 @@ impl UnifiedJsAnalyzer {
 
     /// Get cache hit rate
     pub fn cache_hit_rate(&self) -> f64 {
-        let stats = self.stats.borrow();
-        let total =
-            stats.cache_hits + stats.regex_matches + stats.ast_matches + stats.js_fallbacks;
+        let stats = self.stats.lock();
+        let total = stats.cache_hits + stats.regex_matches + stats.ast_matches + stats.js_fallbacks;
         if total == 0 {
             return 0.0;
         }
@@ -318,11 +355,11 @@ impl UnifiedJsAnalyzer {
 }
 
 /// Convert cached result back to AnalysisResult
-fn cached_to_result(cached: &CachedResult) -> AnalysisResult {
+fn cached_to_result(cached: &CachedResult, code: &str) -> AnalysisResult {
     match cached {
         CachedResult::Native(exec) => AnalysisResult::Native(exec.clone()),
         CachedResult::NativeChain(chain) => AnalysisResult::NativeChain(chain.clone()),
-        CachedResult::RequiresJs => AnalysisResult::RequiresJs(String::new()),
+        CachedResult::RequiresJs(_) => AnalysisResult::RequiresJs(code.to_string()),
     }
 }
 
@@ -380,6 +417,26 @@ mod tests {
         assert!(analyzer.cache_size() <= CACHE_MAX_SIZE);
     }
 
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let analyzer = UnifiedJsAnalyzer::new();
+
+        analyzer.analyze("result.trim()");
+        for i in 0..CACHE_MAX_SIZE {
+            // Keep the first rule in use while the cache fills up
+            analyzer.analyze("result.trim()");
+            analyzer.analyze(&format!("result.trim{i}()"));
+        }
+        let hits = analyzer.stats().cache_hits;
+        analyzer.analyze("result.trim()");
+        assert_eq!(analyzer.stats().cache_hits, hits + 1);
+
+        // The first filler was evicted
+        analyzer.analyze("result.trim0()");
+        assert_eq!(analyzer.stats().cache_hits, hits + 1);
+        assert_eq!(analyzer.cache_size(), CACHE_MAX_SIZE);
+    }
+
     #[test]
     fn test_cache_hit_rate() {
         let analyzer = UnifiedJsAnalyzer::new();
@@ -0,0 +1,339 @@
+//! Anti-ban - User-Agent rotation, request jitter and source cooldowns
+//!
+//! Sources that ban a client after many similar requests (e.g. while caching a
+//! whole book) can opt into:
+//! - `userAgents` / `uaRotation`: a User-Agent per request from the source's
+//!   own list or the built-in [`UA_POOL`]
+//! - `jitterMinMs` / `jitterMaxMs`: a random delay added after the rate limiter wait
+//!
+//! Independently of those settings, a 429 (or a 403 carrying `Retry-After`)
+//! puts the source into a cooldown kept in [`Cooldowns`]. Requests to a cooling
+//! source fail fast with [`EngineError::SourceCoolingDown`] until it ends.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use super::error::EngineError;
+
+/// Built-in User-Agents used when a source sets `uaRotation` without its own list
+pub const UA_POOL: &[&str] = &[
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:127.0) Gecko/20100101 Firefox/127.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0",
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.71 Mobile Safari/537.36",
+];
+
+/// Cooldown used for a 429 without a usable `Retry-After`
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Longest cooldown honoured, whatever `Retry-After` asks for
+const MAX_COOLDOWN: Duration = Duration::from_secs(3600);
+
+/// Uniformly distributed value in `0..bound` (`bound` > 0)
+fn random_below(bound: u64) -> u64 {
+    (uuid::Uuid::new_v4().as_u128() % bound as u128) as u64
+}
+
+/// How the User-Agent is chosen for each request (`uaRotation`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UaRotation {
+    /// Cycle through the list in order (the default for a source's own list)
+    Sequential,
+    /// Pick a random entry for every request
+    Random,
+    /// Pick one entry when the client is created and keep it
+    PerSession,
+}
+
+impl UaRotation {
+    /// Parse a `uaRotation` value such as `random` or `per-session`
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_lowercase().replace('_', "-").as_str() {
+            "sequential" | "sequence" | "round-robin" => Some(Self::Sequential),
+            "random" => Some(Self::Random),
+            "per-session" | "session" => Some(Self::PerSession),
+            _ => None,
+        }
+    }
+}
+
+/// Supplies the User-Agent of each request
+#[derive(Debug)]
+pub struct UserAgentRotator {
+    agents: Vec<String>,
+    mode: UaRotation,
+    next: AtomicUsize,
+}
+
+impl UserAgentRotator {
+    /// Rotator over `agents`, or the built-in pool when the list is empty
+    ///
+    /// Returns `None` when there is nothing to rotate: no agents and no mode.
+    pub fn new(agents: &[String], mode: Option<UaRotation>) -> Option<Self> {
+        let agents: Vec<String> = agents
+            .iter()
+            .map(|ua| ua.trim())
+            .filter(|ua| !ua.is_empty())
+            .map(|ua| ua.to_string())
+            .collect();
+        let (agents, mode) = match (agents.is_empty(), mode) {
+            (true, None) => return None,
+            (true, Some(mode)) => (UA_POOL.iter().map(|ua| ua.to_string()).collect(), mode),
+            (false, mode) => (agents, mode.unwrap_or(UaRotation::Sequential)),
+        };
+        let start = match mode {
+            UaRotation::Sequential => 0,
+            UaRotation::Random | UaRotation::PerSession => random_below(agents.len() as u64) as usize,
+        };
+        Some(Self {
+            agents,
+            mode,
+            next: AtomicUsize::new(start),
+        })
+    }
+
+    pub fn mode(&self) -> UaRotation {
+        self.mode
+    }
+
+    /// User-Agent for the next request
+    pub fn pick(&self) -> &str {
+        let index = match self.mode {
+            UaRotation::Sequential => self.next.fetch_add(1, Ordering::Relaxed),
+            UaRotation::Random => random_below(self.agents.len() as u64) as usize,
+            UaRotation::PerSession => self.next.load(Ordering::Relaxed),
+        };
+        &self.agents[index % self.agents.len()]
+    }
+}
+
+/// Random delay added to every request (`jitterMinMs`..=`jitterMaxMs`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jitter {
+    min_ms: u64,
+    max_ms: u64,
+}
+
+impl Jitter {
+    /// Jitter between `min_ms` and `max_ms`; a missing bound takes the other's value
+    ///
+    /// Returns `None` when neither bound is set or both are zero.
+    pub fn new(min_ms: Option<u64>, max_ms: Option<u64>) -> Option<Self> {
+        let (min_ms, max_ms) = match (min_ms, max_ms) {
+            (None, None) => return None,
+            (Some(min), None) => (min, min),
+            (None, Some(max)) => (0, max),
+            (Some(min), Some(max)) => (min.min(max), min.max(max)),
+        };
+        (max_ms > 0).then_some(Self { min_ms, max_ms })
+    }
+
+    /// Delay before the next request
+    pub fn sample(&self) -> Duration {
+        let span = self.max_ms - self.min_ms;
+        Duration::from_millis(self.min_ms + random_below(span + 1))
+    }
+}
+
+/// Cooldown a response asks for, if any
+///
+/// A 429 always triggers one (`Retry-After`, or a minute without it); a 403
+/// only when it carries `Retry-After`, as a plain 403 usually means the page is
+/// forbidden rather than that the client is throttled. `Retry-After` may be
+/// seconds or an HTTP date.
+pub fn cooldown_for(status: u16, retry_after: Option<&str>) -> Option<Duration> {
+    let requested = retry_after.and_then(parse_retry_after);
+    let cooldown = match status {
+        429 => requested.unwrap_or(DEFAULT_COOLDOWN),
+        403 => requested?,
+        _ => return None,
+    };
+    Some(cooldown.clamp(Duration::from_secs(1), MAX_COOLDOWN))
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (at.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(secs as u64))
+}
+
+/// Cooldown of one source
+#[derive(Debug, Clone, Copy)]
+struct Cooldown {
+    until: Instant,
+    /// `until` as epoch milliseconds, for reporting
+    until_ms: i64,
+}
+
+/// Sources backing off after being throttled, keyed by `bookSourceUrl`
+///
+/// Engines share the process-wide registry from [`Cooldowns::shared`] so a
+/// cooldown started by one request holds for every other request to the source.
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    sources: RwLock<HashMap<String, Cooldown>>,
+}
+
+static SHARED_COOLDOWNS: Lazy<Arc<Cooldowns>> = Lazy::new(|| Arc::new(Cooldowns::default()));
+
+impl Cooldowns {
+    /// Process-wide registry used by all engines
+    pub fn shared() -> Arc<Self> {
+        SHARED_COOLDOWNS.clone()
+    }
+
+    /// Back `source_url` off for `duration`, extending any shorter cooldown
+    pub fn start(&self, source_url: &str, duration: Duration) {
+        let cooldown = Cooldown {
+            until: Instant::now() + duration,
+            until_ms: chrono::Utc::now().timestamp_millis() + duration.as_millis() as i64,
+        };
+        let mut sources = self.sources.write().unwrap_or_else(|e| e.into_inner());
+        let entry = sources.entry(source_url.to_string()).or_insert(cooldown);
+        if entry.until < cooldown.until {
+            *entry = cooldown;
+        }
+    }
+
+    /// Time left in the cooldown of `source_url`, `None` when it is not cooling down
+    pub fn remaining(&self, source_url: &str) -> Option<Duration> {
+        self.active(source_url)
+            .map(|c| c.until.saturating_duration_since(Instant::now()))
+    }
+
+    /// End of the cooldown of `source_url` in epoch milliseconds
+    pub fn until_ms(&self, source_url: &str) -> Option<i64> {
+        self.active(source_url).map(|c| c.until_ms)
+    }
+
+    pub fn is_cooling_down(&self, source_url: &str) -> bool {
+        self.active(source_url).is_some()
+    }
+
+    /// End the cooldown of `source_url` early
+    pub fn clear(&self, source_url: &str) {
+        self.sources
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(source_url);
+    }
+
+    /// Fail with [`EngineError::SourceCoolingDown`] while `source_url` is cooling down
+    pub fn check(&self, source_url: &str) -> Result<(), EngineError> {
+        match self.remaining(source_url) {
+            Some(left) => Err(EngineError::source_cooling_down(source_url, left)),
+            None => Ok(()),
+        }
+    }
+
+    fn active(&self, source_url: &str) -> Option<Cooldown> {
+        let cooldown = *self.sources.read().unwrap_or_else(|e| e.into_inner()).get(source_url)?;
+        if cooldown.until > Instant::now() {
+            return Some(cooldown);
+        }
+        self.sources
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, c| c.until > Instant::now());
+        None
+    }
+}
+
+/// Whether `err` is, or was caused by, [`EngineError::SourceCoolingDown`]
+pub fn is_cooling_down(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|e| matches!(e.downcast_ref::<EngineError>(), Some(EngineError::SourceCoolingDown { .. })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ua_rotation_parse() {
+        assert_eq!(UaRotation::parse("random"), Some(UaRotation::Random));
+        assert_eq!(UaRotation::parse(" Per-Session "), Some(UaRotation::PerSession));
+        assert_eq!(UaRotation::parse("per_session"), Some(UaRotation::PerSession));
+        assert_eq!(UaRotation::parse("sequential"), Some(UaRotation::Sequential));
+        assert_eq!(UaRotation::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_rotator_cycles_own_list() {
+        let agents = vec!["A".to_string(), " ".to_string(), "B".to_string()];
+        let rotator = UserAgentRotator::new(&agents, None).unwrap();
+        assert_eq!(rotator.mode(), UaRotation::Sequential);
+        let picked: Vec<&str> = (0..4).map(|_| rotator.pick()).collect();
+        assert_eq!(picked, ["A", "B", "A", "B"]);
+    }
+
+    #[test]
+    fn test_rotator_pool_modes() {
+        assert!(UserAgentRotator::new(&[], None).is_none());
+
+        let session = UserAgentRotator::new(&[], Some(UaRotation::PerSession)).unwrap();
+        let first = session.pick().to_string();
+        assert!(UA_POOL.contains(&first.as_str()));
+        assert!((0..10).all(|_| session.pick() == first));
+
+        let random = UserAgentRotator::new(&[], Some(UaRotation::Random)).unwrap();
+        assert!((0..10).all(|_| UA_POOL.contains(&random.pick())));
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        assert!(Jitter::new(None, None).is_none());
+        assert!(Jitter::new(Some(0), Some(0)).is_none());
+        let jitter = Jitter::new(Some(300), Some(100)).unwrap();
+        for _ in 0..50 {
+            let delay = jitter.sample();
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+        }
+        assert_eq!(Jitter::new(Some(50), None).unwrap().sample(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_cooldown_for_status() {
+        assert_eq!(cooldown_for(429, Some("120")), Some(Duration::from_secs(120)));
+        assert_eq!(cooldown_for(429, None), Some(DEFAULT_COOLDOWN));
+        assert_eq!(cooldown_for(429, Some("soon")), Some(DEFAULT_COOLDOWN));
+        assert_eq!(cooldown_for(429, Some("86400")), Some(MAX_COOLDOWN));
+        assert_eq!(cooldown_for(403, Some("30")), Some(Duration::from_secs(30)));
+        assert_eq!(cooldown_for(403, None), None);
+        assert_eq!(cooldown_for(500, Some("30")), None);
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let cooldown = cooldown_for(429, Some(&date)).unwrap();
+        assert!(cooldown > Duration::from_secs(80) && cooldown <= Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_cooldowns_expire() {
+        let cooldowns = Cooldowns::default();
+        cooldowns.start("https://a.example", Duration::from_millis(50));
+        cooldowns.start("https://a.example", Duration::from_millis(10));
+        assert!(cooldowns.is_cooling_down("https://a.example"));
+        assert!(cooldowns.remaining("https://a.example").unwrap() > Duration::from_millis(10));
+        assert!(cooldowns.until_ms("https://a.example").unwrap() > chrono::Utc::now().timestamp_millis());
+        assert!(!cooldowns.is_cooling_down("https://b.example"));
+
+        let err: anyhow::Error = cooldowns.check("https://a.example").unwrap_err().into();
+        assert!(is_cooling_down(&err));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!cooldowns.is_cooling_down("https://a.example"));
+        assert!(cooldowns.check("https://a.example").is_ok());
+    }
+}
@@ -7,7 +7,9 @@ use super::types::*;
 use crate::engine::js_analyzer::{
     AnalysisResult as LegacyAnalysisResult, ExprValue, NativeExecution,
 };
-use crate::engine::preprocessor::NativeApi;
+use crate::engine::native::js_globals::format_number;
+use crate::engine::native::string_ops::js_regex_to_rust;
+use crate::engine::preprocessor::{ArithmeticOp, JsType, NativeApi};
 
 /// Compiles analysis results into executable plans
 pub struct ExecutionPlanCompiler {
@@ -166,6 +168,19 @@ impl ExecutionPlanCompiler {
                 })
             }
 
+            // Global conversion: parseInt(x), String(x), ...
+            Operation::MethodCall {
+                object,
+                method,
+                args,
+            } if matches!(object.as_ref(), Operand::Null) => Some(NativeExecution {
+                api: self.global_to_native_api(method, args)?,
+                args: args
+                    .iter()
+                    .map(|op| self.operand_to_expr_value(op))
+                    .collect::<Option<Vec<_>>>()?,
+            }),
+
             Operation::MethodCall {
                 object,
                 method,
@@ -182,6 +197,35 @@ impl ExecutionPlanCompiler {
                 })
             }
 
+            // Index into an earlier result, e.g. `result.split(",")[0]`
+            Operation::PropertyAccess {
+                object,
+                property: PropKey::ComputedIndex(index),
+            } => Some(NativeExecution {
+                api: NativeApi::ArrayIndex { index: *index },
+                args: vec![self.operand_to_expr_value(object)?],
+            }),
+
+            Operation::BinaryOp { left, op, right } => Some(NativeExecution {
+                api: self.binary_to_native_api(left, op, right)?,
+                args: vec![
+                    self.operand_to_expr_value(left)?,
+                    self.operand_to_expr_value(right)?,
+                ],
+            }),
+
+            // `${a}-${b}` concatenates its parts
+            Operation::TemplateLiteral { parts } => Some(NativeExecution {
+                api: NativeApi::JsConcat,
+                args: parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::Static(s) => Some(ExprValue::Literal(s.clone())),
+                        TemplatePart::Expression(op) => self.operand_to_expr_value(op),
+                    })
+                    .collect::<Option<Vec<_>>>()?,
+            }),
+
             Operation::Literal(_op) => {
                 // Literal values don't map to NativeExecution
                 None
@@ -191,6 +235,61 @@ impl ExecutionPlanCompiler {
         }
     }
 
+    /// Map a global conversion function to NativeApi
+    fn global_to_native_api(&self, name: &str, args: &[Operand]) -> Option<NativeApi> {
+        let from = match args.first().map(Operand::value_type) {
+            Some(ValueType::Number) => JsType::Number,
+            Some(ValueType::Boolean) => JsType::Boolean,
+            None | Some(ValueType::String) => JsType::String,
+            // Truthiness depends on the runtime type, which strings can't carry
+            Some(_) if name == "Boolean" => return None,
+            Some(_) => JsType::String,
+        };
+        match name {
+            "parseInt" => Some(NativeApi::ParseInt),
+            "parseFloat" => Some(NativeApi::ParseFloat),
+            "Number" => Some(NativeApi::ToNumber { from }),
+            "String" => Some(NativeApi::ToJsString { from }),
+            "Boolean" => Some(NativeApi::ToBoolean { from }),
+            _ => None,
+        }
+    }
+
+    /// Map an arithmetic operator to NativeApi
+    ///
+    /// `+` concatenates when either side is a string and adds when both are
+    /// numbers; operands of unknown or non-primitive type are left to the JS engine.
+    fn binary_to_native_api(
+        &self,
+        left: &Operand,
+        op: &BinaryOperator,
+        right: &Operand,
+    ) -> Option<NativeApi> {
+        let (left, right) = (left.value_type(), right.value_type());
+        // Booleans, null and objects don't survive the trip through strings
+        let primitive = |t: &ValueType| {
+            matches!(t, ValueType::String | ValueType::Number | ValueType::Unknown)
+        };
+        if !primitive(&left) || !primitive(&right) {
+            return None;
+        }
+
+        let op = match op {
+            BinaryOperator::Add => match (left, right) {
+                (ValueType::String, _) | (_, ValueType::String) => return Some(NativeApi::JsConcat),
+                (ValueType::Number, ValueType::Number) => ArithmeticOp::Add,
+                _ => return None,
+            },
+            BinaryOperator::Concat => return Some(NativeApi::JsConcat),
+            BinaryOperator::Sub => ArithmeticOp::Sub,
+            BinaryOperator::Mul => ArithmeticOp::Mul,
+            BinaryOperator::Div => ArithmeticOp::Div,
+            BinaryOperator::Mod => ArithmeticOp::Mod,
+            _ => return None,
+        };
+        Some(NativeApi::JsArithmetic { op })
+    }
+
     /// Map method name to NativeApi
     fn method_to_native_api(&self, method: &str, args: &[Operand]) -> Option<NativeApi> {
         match method {
@@ -199,19 +298,31 @@ impl ExecutionPlanCompiler {
             }
 
             "replace" | "replaceAll" => {
-                // Extract pattern and replacement from args if available
-                let pattern = self.operand_to_string(args.get(0)).unwrap_or_default();
-                let replacement = self.operand_to_string(args.get(1)).unwrap_or_default();
+                let replacement = self.operand_to_string(args.get(1))?;
+                let (pattern, is_regex, global) = match args.first()? {
+                    Operand::RegexLiteral { pattern, flags } => {
+                        let global = flags.contains('g');
+                        // replaceAll throws on a non-global regex
+                        if method == "replaceAll" && !global {
+                            return None;
+                        }
+                        (js_regex_to_rust(pattern, flags)?, true, global)
+                    }
+                    // A string pattern only replaces the first occurrence
+                    Operand::StringLiteral(s) => (s.clone(), false, method == "replaceAll"),
+                    _ => return None,
+                };
                 Some(NativeApi::StringReplace {
                     pattern,
                     replacement,
-                    is_regex: method == "replaceAll",
-                    global: true,
+                    is_regex,
+                    global,
                 })
             }
 
+            // Splitting on a regex needs the JS engine
             "split" => {
-                let delimiter = self.operand_to_string(args.get(0)).unwrap_or_default();
+                let delimiter = self.operand_to_string(args.first())?;
                 Some(NativeApi::StringSplit { delimiter })
             }
 
@@ -276,6 +387,15 @@ impl ExecutionPlanCompiler {
                 Some(NativeApi::StringRepeat { count })
             }
 
+            // Array.join defaults to ","; a non-literal separator needs JS
+            "join" => {
+                let separator = match args.first() {
+                    Some(op) => self.operand_to_string(Some(op))?,
+                    None => ",".to_string(),
+                };
+                Some(NativeApi::ArrayJoin { separator })
+            }
+
             _ => None,
         }
     }
@@ -300,17 +420,14 @@ impl ExecutionPlanCompiler {
     fn operand_to_expr_value(&self, op: &Operand) -> Option<ExprValue> {
         match op {
             Operand::StringLiteral(s) => Some(ExprValue::Literal(s.clone())),
-            Operand::NumberLiteral(n) => Some(ExprValue::Literal(n.to_string())),
+            Operand::NumberLiteral(n) => Some(ExprValue::Literal(format_number(*n))),
             Operand::BooleanLiteral(b) => Some(ExprValue::Literal(b.to_string())),
             Operand::Variable(name) => Some(ExprValue::Variable(name.clone())),
             Operand::ContextValue(ctx) => match ctx {
                 ContextKey::Result | ContextKey::Content | ContextKey::Src => {
                     Some(ExprValue::CurrentContent)
                 }
-                ContextKey::Key => Some(ExprValue::Variable("key".to_string())),
-                ContextKey::Page => Some(ExprValue::Variable("page".to_string())),
-                ContextKey::BaseUrl => Some(ExprValue::Variable("baseUrl".to_string())),
-                _ => Some(ExprValue::Variable(format!("{:?}", ctx).to_lowercase())),
+                _ => Some(ExprValue::Variable(ctx.name().to_string())),
             },
             Operand::Nested(plan) => {
                 // Convert nested plan to NativeCall
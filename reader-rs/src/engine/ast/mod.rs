@@ -34,3 +34,4 @@ mod types;
 
 pub use compiler::ExecutionPlanCompiler;
 pub use parser::JsAstParser;
+pub(crate) use types::{AstAnalysisResult, ContextKey, NativeExecutionPlan};
@@ -8,6 +8,7 @@ use oxc_parser::Parser;
 use oxc_span::SourceType;
 
 use super::pattern_matcher::AstPatternMatcher;
+use crate::engine::parsers::RuleSyntaxError;
 use super::types::*;
 
 /// JavaScript AST Parser wrapper
@@ -78,6 +79,26 @@ impl JsAstParser {
         matcher.analyze_program(&parser_return.program)
     }
 
+    /// Parse code as a script and return its syntax errors, without analyzing
+    /// or running it
+    ///
+    /// Offsets point into `code`; `@js:` / `<js>` wrappers must already be removed.
+    pub fn syntax_errors(code: &str) -> Vec<RuleSyntaxError> {
+        let allocator = Allocator::default();
+        let parser_return = Parser::new(&allocator, code, SourceType::unambiguous()).parse();
+        parser_return
+            .errors
+            .iter()
+            .map(|e| {
+                let offset = e.labels.as_ref().and_then(|labels| labels.first()).map(|label| label.offset());
+                RuleSyntaxError {
+                    offset,
+                    message: e.message.to_string(),
+                }
+            })
+            .collect()
+    }
+
     /// Normalize code by removing common prefixes
     fn normalize_code(code: &str) -> String {
         let code = code.trim();
@@ -90,6 +111,9 @@ impl JsAstParser {
             })
             .unwrap_or(code)
             .trim()
+            // A trailing `;` would break the expression wrapping
+            .trim_end_matches(';')
+            .trim_end()
             .to_string()
     }
 
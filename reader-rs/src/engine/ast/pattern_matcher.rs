@@ -20,6 +20,7 @@ use super::types::{
     NativeExecutionPlan, Operand, Operation, PropKey, TemplatePart, ValueType,
 };
 use crate::engine::preprocessor::NativeApi;
+use crate::engine::rule_context::BOOK_VARIABLE;
 
 /// AST Pattern Matcher - identifies native-executable patterns in JavaScript AST
 pub struct AstPatternMatcher {
@@ -150,21 +151,23 @@ impl AstPatternMatcher {
             // Direct function call: func()
             Expression::Identifier(ident) => self.analyze_global_call(&ident.name, &call.arguments),
 
-            // Chained call: a().b()
-            Expression::CallExpression(inner_call) => {
-                // First analyze the inner call
-                let inner_result = self.analyze_call_expression(inner_call);
-
-                // This is complex chaining - for now, require JS
-                if matches!(inner_result, AstAnalysisResult::Native(_)) {
-                    // Could potentially chain, but complex - fallback for now
+            // Parenthesized callee: (obj.method)()
+            Expression::ParenthesizedExpression(paren) => match &paren.expression {
+                Expression::StaticMemberExpression(member) => {
+                    self.analyze_member_call(member, &call.arguments)
                 }
+                _ => AstAnalysisResult::RequiresJs {
+                    code: "<complex callee>".to_string(),
+                    reason: JsRequiredReason::DynamicPropertyAccess,
+                },
+            },
 
-                AstAnalysisResult::RequiresJs {
-                    code: "<chained call>".to_string(),
-                    reason: JsRequiredReason::UnsupportedExpression,
-                }
-            }
+            // Calling a returned value: a()() - the callee is a function, which needs JS.
+            // Method chains a().b() have a member callee and are handled above.
+            Expression::CallExpression(_) => AstAnalysisResult::RequiresJs {
+                code: "<call of call result>".to_string(),
+                reason: JsRequiredReason::UnsupportedExpression,
+            },
 
             _ => AstAnalysisResult::RequiresJs {
                 code: "<complex callee>".to_string(),
@@ -197,6 +200,10 @@ impl AstPatternMatcher {
                 return self.match_source_api(method_name, arguments);
             }
 
+            if obj_name == "book" && method_name == "getVariable" {
+                return self.match_book_variable(arguments);
+            }
+
             // Could be a variable - try string method matching
             if let Some(context_key) = ContextKey::from_str(obj_name) {
                 return self.match_string_method(
@@ -214,35 +221,15 @@ impl AstPatternMatcher {
             );
         }
 
-        // If the object is itself a call expression (method chaining)
-        if let Expression::CallExpression(inner_call) = &member.object {
-            // Analyze the inner call first
-            if let AstAnalysisResult::Native(inner_plan) = self.analyze_call_expression(inner_call)
-            {
-                // Chain this method call onto the result
-                return self.match_string_method(
-                    Operand::Nested(Box::new(inner_plan)),
-                    method_name,
-                    arguments,
-                );
-            }
-        }
-
-        // If the object is a member expression (e.g., JSON.parse(x).data)
-        if let Expression::StaticMemberExpression(_) = &member.object {
-            // Complex property access - analyze the object first
-            if let AstAnalysisResult::Native(obj_plan) = self.analyze_expression(&member.object) {
-                return self.match_string_method(
-                    Operand::Nested(Box::new(obj_plan)),
-                    method_name,
-                    arguments,
-                );
-            }
-        }
-
-        AstAnalysisResult::RequiresJs {
-            code: format!("<unknown>.{}()", method_name),
-            reason: JsRequiredReason::UnsupportedExpression,
+        // Method chaining: the object is a call, property access or index into an
+        // earlier result (e.g. `result.split(",")[0].trim()`), analyzed recursively
+        // and applied as a nested plan so chains of any depth stay native
+        match self.expression_to_operand(&member.object) {
+            Ok(object) => self.match_string_method(object, method_name, arguments),
+            Err(reason) => AstAnalysisResult::RequiresJs {
+                code: format!("<unknown>.{}()", method_name),
+                reason,
+            },
         }
     }
 
@@ -281,6 +268,8 @@ impl AstPatternMatcher {
             "post" | "httpPost" => NativeApi::HttpPost,
             "request" | "httpRequest" => NativeApi::HttpRequest,
             "getAll" | "httpGetAll" => NativeApi::HttpGetAll,
+            "head" => NativeApi::HttpHead,
+            "getSize" => NativeApi::HttpContentLength,
 
             // Storage
             "put" => NativeApi::CacheSet,
@@ -314,9 +303,14 @@ impl AstPatternMatcher {
             "readTxtFile" => NativeApi::ReadTxtFile,
             "readTxtFileWithCharset" => NativeApi::ReadTxtFileWithCharset,
             "getFile" => NativeApi::GetFile,
+            "getTxtInFolder" => NativeApi::GetTxtInFolder,
             "deleteFile" => NativeApi::DeleteFile,
             "importScript" => NativeApi::ImportScript,
 
+            // Font
+            "queryTTF" => NativeApi::QueryTtf,
+            "replaceFont" => NativeApi::ReplaceFont,
+
             // ZIP
             "zipReadString" => NativeApi::ZipReadString,
             "zipReadStringWithCharset" => NativeApi::ZipReadStringWithCharset,
@@ -328,10 +322,15 @@ impl AstPatternMatcher {
 
             // String operations
             "htmlToText" | "textTrim" => NativeApi::HtmlToText,
+            "t2s" => NativeApi::ChineseT2s,
+            "s2t" => NativeApi::ChineseS2t,
 
             // Logging
             "log" | "logType" => NativeApi::Log,
 
+            // UI-only calls, no-ops on the server
+            "toast" | "longToast" | "startBrowser" => NativeApi::UiNoop(method.to_string()),
+
             // Unknown
             _ => {
                 return AstAnalysisResult::RequiresJs {
@@ -352,6 +351,14 @@ impl AstPatternMatcher {
             }
         };
 
+        // java.encodeURI(str, "gbk") encodes in a fixed charset
+        if let (NativeApi::EncodeUri, [input, Operand::StringLiteral(enc)]) = (&api, operands.as_slice()) {
+            return AstAnalysisResult::Native(NativeExecutionPlan::api_call(
+                NativeApi::EncodeUriWithEnc(enc.clone()),
+                vec![input.clone()],
+            ));
+        }
+
         AstAnalysisResult::Native(NativeExecutionPlan::api_call(api, operands))
     }
 
@@ -392,7 +399,7 @@ impl AstPatternMatcher {
         args: &oxc_allocator::Vec<Argument>,
     ) -> AstAnalysisResult {
         let api = match method {
-            "putVariable" => NativeApi::SourceVarSet,
+            "putVariable" | "setVariable" => NativeApi::SourceVarSet,
             "getVariable" => NativeApi::SourceVarGet,
             _ => {
                 return AstAnalysisResult::RequiresJs {
@@ -415,6 +422,22 @@ impl AstPatternMatcher {
         AstAnalysisResult::Native(NativeExecutionPlan::api_call(api, operands))
     }
 
+    /// Match book.getVariable(key), which reads the book's variable JSON from the context
+    fn match_book_variable(&self, args: &oxc_allocator::Vec<Argument>) -> AstAnalysisResult {
+        let mut operands = vec![Operand::Variable(BOOK_VARIABLE.to_string())];
+        match self.parse_arguments(args) {
+            Ok(ops) => operands.extend(ops),
+            Err(reason) => {
+                return AstAnalysisResult::RequiresJs {
+                    code: "book.getVariable(...)".to_string(),
+                    reason,
+                };
+            }
+        }
+
+        AstAnalysisResult::Native(NativeExecutionPlan::api_call(NativeApi::BookVarGet, operands))
+    }
+
     /// Match string methods on an object
     fn match_string_method(
         &self,
@@ -433,7 +456,7 @@ impl AstPatternMatcher {
         };
 
         // Map string methods to operations
-        match method {
+        let result = match method {
             "trim" | "trimStart" | "trimEnd" | "trimLeft" | "trimRight" => AstAnalysisResult::Native(
                 NativeExecutionPlan::method_call(object, "trim".to_string(), operands),
             ),
@@ -560,6 +583,21 @@ impl AstPatternMatcher {
                 code: format!("<obj>.{}()", method),
                 reason: JsRequiredReason::UnsupportedApi(method.to_string()),
             },
+        };
+
+        // Record non-string results so `+` on them is typed correctly
+        match result {
+            AstAnalysisResult::Native(mut plan) => {
+                plan.output_type = match method {
+                    "indexOf" | "lastIndexOf" | "charCodeAt" | "codePointAt" | "search"
+                    | "length" => ValueType::Number,
+                    "includes" | "startsWith" | "endsWith" => ValueType::Boolean,
+                    "split" => ValueType::Array,
+                    _ => plan.output_type,
+                };
+                AstAnalysisResult::Native(plan)
+            }
+            other => other,
         }
     }
 
@@ -581,11 +619,17 @@ impl AstPatternMatcher {
                     }
                 };
 
-                AstAnalysisResult::Native(NativeExecutionPlan::method_call(
+                let mut plan = NativeExecutionPlan::method_call(
                     Operand::Null, // Global context
                     name.to_string(),
                     operands,
-                ))
+                );
+                plan.output_type = match name {
+                    "String" => ValueType::String,
+                    "Boolean" => ValueType::Boolean,
+                    _ => ValueType::Number,
+                };
+                AstAnalysisResult::Native(plan)
             }
 
             "encodeURI" | "encodeURIComponent" | "decodeURI" | "decodeURIComponent" => {
@@ -663,8 +707,11 @@ impl AstPatternMatcher {
                 _ => Err(JsRequiredReason::UnsupportedExpression),
             },
 
-            // Member expression - could be property access
+            // Member expression - book/chapter metadata or property access
             Expression::StaticMemberExpression(member) => {
+                if let Some(ctx) = context_member(member) {
+                    return Ok(Operand::ContextValue(ctx));
+                }
                 match self.analyze_static_member(member) {
                     AstAnalysisResult::Native(plan) => Ok(Operand::Nested(Box::new(plan))),
                     AstAnalysisResult::RequiresJs { reason, .. } => Err(reason),
@@ -672,11 +719,19 @@ impl AstPatternMatcher {
                 }
             }
 
-            // Regex literal - store as string
-            Expression::RegExpLiteral(regex) => Ok(Operand::StringLiteral(format!(
-                "/{}/{}",
-                regex.regex.pattern, regex.regex.flags
-            ))),
+            // Index access - e.g. the element of a split result
+            Expression::ComputedMemberExpression(member) => {
+                match self.analyze_computed_member(member) {
+                    AstAnalysisResult::Native(plan) => Ok(Operand::Nested(Box::new(plan))),
+                    AstAnalysisResult::RequiresJs { reason, .. } => Err(reason),
+                    _ => Err(JsRequiredReason::UnsupportedExpression),
+                }
+            }
+
+            Expression::RegExpLiteral(regex) => Ok(Operand::RegexLiteral {
+                pattern: regex.regex.pattern.to_string(),
+                flags: regex.regex.flags.to_string(),
+            }),
 
             // Template literal
             Expression::TemplateLiteral(tmpl) => {
@@ -690,7 +745,11 @@ impl AstPatternMatcher {
                         .collect();
                     Ok(Operand::StringLiteral(s))
                 } else {
-                    Err(JsRequiredReason::UnsupportedExpression)
+                    match self.analyze_template_literal(tmpl) {
+                        AstAnalysisResult::Native(plan) => Ok(Operand::Nested(Box::new(plan))),
+                        AstAnalysisResult::RequiresJs { reason, .. } => Err(reason),
+                        _ => Err(JsRequiredReason::UnsupportedExpression),
+                    }
                 }
             }
 
@@ -709,6 +768,11 @@ impl AstPatternMatcher {
     fn analyze_static_member(&self, member: &StaticMemberExpression) -> AstAnalysisResult {
         let property = member.property.name.as_str();
 
+        // Book/chapter metadata: book.name, chapter.index, ...
+        if let Some(ctx) = context_member(member) {
+            return AstAnalysisResult::Native(NativeExecutionPlan::literal(Operand::ContextValue(ctx)));
+        }
+
         // Special case: object.length
         if property == "length" {
             if let Ok(obj_operand) = self.expression_to_operand(&member.object) {
@@ -838,6 +902,7 @@ impl AstPatternMatcher {
             }
         };
 
+        let output_type = binary_output_type(&left, &op, &right);
         AstAnalysisResult::Native(NativeExecutionPlan {
             operations: vec![Operation::BinaryOp {
                 left: Box::new(left),
@@ -845,7 +910,7 @@ impl AstPatternMatcher {
                 right: Box::new(right),
             }],
             input_binding: InputBinding::None,
-            output_type: ValueType::Unknown,
+            output_type,
         })
     }
 
@@ -1081,6 +1146,41 @@ impl AstPatternMatcher {
     }
 }
 
+/// Static result type of a binary operation
+///
+/// `+` concatenates as soon as either side is a string and adds when both are
+/// numeric; with an operand of unknown type the result stays unknown.
+fn binary_output_type(left: &Operand, op: &BinaryOperator, right: &Operand) -> ValueType {
+    match op {
+        BinaryOperator::Add => {
+            let (l, r) = (left.value_type(), right.value_type());
+            let numeric = |t: &ValueType| {
+                matches!(t, ValueType::Number | ValueType::Boolean | ValueType::Null)
+            };
+            if l == ValueType::String || r == ValueType::String {
+                ValueType::String
+            } else if numeric(&l) && numeric(&r) {
+                ValueType::Number
+            } else {
+                ValueType::Unknown
+            }
+        }
+        BinaryOperator::Concat => ValueType::String,
+        BinaryOperator::Sub | BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Mod => {
+            ValueType::Number
+        }
+        _ => ValueType::Boolean,
+    }
+}
+
+/// Context key of a metadata access such as `book.name` or `chapter.index`
+fn context_member(member: &StaticMemberExpression) -> Option<ContextKey> {
+    let Expression::Identifier(object) = &member.object else {
+        return None;
+    };
+    ContextKey::from_str(&format!("{}.{}", object.name, member.property.name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1103,6 +1203,17 @@ mod tests {
         assert!(matches!(result, AstAnalysisResult::Native(_)));
     }
 
+    #[test]
+    fn test_encode_uri_with_charset() {
+        let AstAnalysisResult::Native(plan) = analyze_code(r#"java.encodeURI(key, "gbk")"#) else {
+            panic!("Expected Native result");
+        };
+        assert!(matches!(
+            &plan.operations[..],
+            [Operation::ApiCall { api: NativeApi::EncodeUriWithEnc(enc), args }] if enc == "gbk" && args.len() == 1
+        ));
+    }
+
     #[test]
     fn test_string_trim() {
         let result = analyze_code("result.trim()");
@@ -1145,4 +1256,48 @@ mod tests {
         let result = analyze_code("function() { return 1; }");
         assert!(matches!(result, AstAnalysisResult::RequiresJs { .. }));
     }
+
+    /// Depth of Operand::Nested plans under the top-level operation's object
+    fn chain_depth(result: &AstAnalysisResult) -> usize {
+        fn depth(plan: &NativeExecutionPlan) -> usize {
+            let object = match plan.operations.first() {
+                Some(Operation::MethodCall { object, .. }) => object,
+                Some(Operation::PropertyAccess { object, .. }) => object,
+                _ => return 1,
+            };
+            match object.as_ref() {
+                Operand::Nested(inner) => 1 + depth(inner),
+                _ => 1,
+            }
+        }
+        match result {
+            AstAnalysisResult::Native(plan) => depth(plan),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_three_level_chains() {
+        for code in [
+            "result.trim().split(',').join('|')",
+            "result.split(',')[0].trim()",
+            "java.base64Decode(result).trim().toUpperCase()",
+            "(result.trim()).split(',').join('|')",
+        ] {
+            let result = analyze_code(code);
+            assert_eq!(chain_depth(&result), 3, "{}", code);
+        }
+
+        let result = analyze_code("result.trim().split(',').reverse().join('|')");
+        assert_eq!(chain_depth(&result), 4);
+    }
+
+    #[test]
+    fn test_call_of_call_requires_js() {
+        let result = analyze_code("getFormatter()('x')");
+        assert!(matches!(result, AstAnalysisResult::RequiresJs { .. }));
+        // A JS-only link anywhere in the chain keeps the whole chain in JS
+        let result = analyze_code("result.split(',').map(x => x.trim()).join('|')");
+        assert!(matches!(result, AstAnalysisResult::RequiresJs { .. }));
+    }
 }
@@ -4,6 +4,7 @@
 
 use crate::engine::preprocessor::NativeApi;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Result of AST analysis
 #[derive(Debug, Clone)]
@@ -53,6 +54,21 @@ pub enum JsRequiredReason {
     ParseError(String),
 }
 
+impl JsRequiredReason {
+    /// Short stable label for statistics (parse error details are dropped)
+    pub fn label(&self) -> String {
+        match self {
+            Self::UnsupportedApi(api) => format!("unsupportedApi:{}", api),
+            Self::ControlFlow(kind) => format!("controlFlow:{:?}", kind),
+            Self::DynamicPropertyAccess => "dynamicPropertyAccess".to_string(),
+            Self::FunctionDefinition => "functionDefinition".to_string(),
+            Self::ComplexConditional => "complexConditional".to_string(),
+            Self::UnsupportedExpression => "unsupportedExpression".to_string(),
+            Self::ParseError(_) => "parseError".to_string(),
+        }
+    }
+}
+
 /// Control flow statement types
 #[derive(Debug, Clone, PartialEq)]
 pub enum ControlFlowKind {
@@ -161,6 +177,8 @@ pub enum Operand {
     Null,
     /// Undefined
     Undefined,
+    /// Regex literal with its JS source and flags
+    RegexLiteral { pattern: String, flags: String },
     /// Variable reference
     Variable(String),
     /// Context value (result, content, src, etc.)
@@ -175,6 +193,27 @@ pub enum Operand {
     ObjectLiteral(Vec<(String, Operand)>),
 }
 
+impl Operand {
+    /// Static type of the value, `Unknown` when only known at runtime
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Operand::StringLiteral(_) => ValueType::String,
+            Operand::NumberLiteral(_) => ValueType::Number,
+            Operand::BooleanLiteral(_) => ValueType::Boolean,
+            Operand::Null | Operand::Undefined => ValueType::Null,
+            Operand::ContextValue(ContextKey::Page | ContextKey::ChapterIndex) => ValueType::Number,
+            Operand::ContextValue(ContextKey::Book | ContextKey::Chapter | ContextKey::Source) => {
+                ValueType::Object
+            }
+            Operand::ContextValue(_) => ValueType::String,
+            Operand::Nested(plan) => plan.output_type.clone(),
+            Operand::ArrayLiteral(_) => ValueType::Array,
+            Operand::ObjectLiteral(_) | Operand::RegexLiteral { .. } => ValueType::Object,
+            Operand::Variable(_) | Operand::PreviousResult => ValueType::Unknown,
+        }
+    }
+}
+
 /// Context keys available in JavaScript rules
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ContextKey {
@@ -196,6 +235,14 @@ pub enum ContextKey {
     Chapter,
     /// Source object
     Source,
+    /// `book.name`
+    BookName,
+    /// `book.author`
+    BookAuthor,
+    /// `chapter.title`, also available as `title`
+    ChapterTitle,
+    /// `chapter.index`, zero-based
+    ChapterIndex,
 }
 
 impl ContextKey {
@@ -211,9 +258,32 @@ impl ContextKey {
             "book" => Some(Self::Book),
             "chapter" => Some(Self::Chapter),
             "source" => Some(Self::Source),
+            "book.name" => Some(Self::BookName),
+            "book.author" => Some(Self::BookAuthor),
+            "chapter.title" | "title" => Some(Self::ChapterTitle),
+            "chapter.index" => Some(Self::ChapterIndex),
             _ => None,
         }
     }
+
+    /// Variable name the value is looked up by at runtime
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Result => "result",
+            Self::Content => "content",
+            Self::Src => "src",
+            Self::BaseUrl => "baseUrl",
+            Self::Key => "key",
+            Self::Page => "page",
+            Self::Book => "book",
+            Self::Chapter => "chapter",
+            Self::Source => "source",
+            Self::BookName => "book.name",
+            Self::BookAuthor => "book.author",
+            Self::ChapterTitle => "chapter.title",
+            Self::ChapterIndex => "chapter.index",
+        }
+    }
 }
 
 /// Property key for property access (renamed to avoid oxc conflict)
@@ -292,3 +362,142 @@ pub enum ValueType {
     Null,
     Unknown,
 }
+
+// Display renders plans as JS-like source, one operation per `;`,
+// so inspection output stays readable and stable across serde changes.
+
+/// Write `items` separated by `, `
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Write `s` as a double-quoted string literal
+fn write_quoted(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "{}", serde_json::Value::String(s.to_string()))
+}
+
+impl fmt::Display for NativeExecutionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.operations.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Literal(value) => write!(f, "{}", value),
+            Operation::ApiCall { api, args } => {
+                write!(f, "{:?}(", api)?;
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Operation::PropertyAccess { object, property } => write!(f, "{}{}", object, property),
+            // A null receiver marks a global function such as parseInt
+            Operation::MethodCall { object, method, args } => {
+                match object.as_ref() {
+                    Operand::Null => write!(f, "{}(", method)?,
+                    object => write!(f, "{}.{}(", object, method)?,
+                }
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Operation::BinaryOp { left, op, right } => write!(f, "{} {} {}", left, op, right),
+            Operation::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => write!(f, "{} ? {} : {}", condition, then_branch, else_branch),
+            Operation::TemplateLiteral { parts } => {
+                f.write_str("`")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Static(text) => f.write_str(text)?,
+                        TemplatePart::Expression(value) => write!(f, "${{{}}}", value)?,
+                    }
+                }
+                f.write_str("`")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::StringLiteral(s) => write_quoted(f, s),
+            Operand::NumberLiteral(n) => write!(f, "{}", n),
+            Operand::BooleanLiteral(b) => write!(f, "{}", b),
+            Operand::Null => f.write_str("null"),
+            Operand::Undefined => f.write_str("undefined"),
+            Operand::RegexLiteral { pattern, flags } => write!(f, "/{}/{}", pattern, flags),
+            Operand::Variable(name) => f.write_str(name),
+            Operand::ContextValue(key) => f.write_str(key.name()),
+            Operand::PreviousResult => f.write_str("<prev>"),
+            Operand::Nested(plan) => write!(f, "({})", plan),
+            Operand::ArrayLiteral(items) => {
+                f.write_str("[")?;
+                write_list(f, items)?;
+                f.write_str("]")
+            }
+            Operand::ObjectLiteral(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for PropKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropKey::Static(name) => write!(f, ".{}", name),
+            PropKey::ComputedLiteral(name) => {
+                f.write_str("[")?;
+                write_quoted(f, name)?;
+                f.write_str("]")
+            }
+            PropKey::ComputedIndex(index) => write!(f, "[{}]", index),
+            PropKey::Dynamic(key) => write!(f, "[{}]", key),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOperator::Add | BinaryOperator::Concat => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+            BinaryOperator::Mod => "%",
+            BinaryOperator::Eq => "==",
+            BinaryOperator::Ne => "!=",
+            BinaryOperator::StrictEq => "===",
+            BinaryOperator::StrictNe => "!==",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::Le => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::Ge => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        })
+    }
+}
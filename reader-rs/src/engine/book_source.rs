@@ -5,20 +5,32 @@
 //! - HttpClient for network requests  
 //! - JsExecutor for JavaScript execution
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::engine::utils::get_cache_dir;
+use crate::engine::utils::{decode_numeric_entities, resolve_absolute_url};
 
-use super::http_client::HttpClient;
+use super::anti_ban::Cooldowns;
+use super::cookie::CookieManager;
+use super::deadline::{self, Deadline};
+use super::error::{EngineError, PartialContent};
+use super::http_cache::HttpCache;
+use super::http_client::{parse_header_map, split_url_options, BinaryResponse, HttpClient, RequestConfig, StrResponse};
+use super::login::{self, LoginResult};
 use super::native_api::NativeApiProvider;
 use super::native_executor::NativeExecutor;
 use super::parsers::RuleType;
 use super::rule_analyzer::RuleAnalyzer;
-use super::source_transformer::{CompiledRule, SourceTransformer, TransformedSource};
+use super::rule_cache::RuleCache;
+use super::rule_context::{BookContext, ChapterContext};
+use super::source_transformer::{CompiledRule, JoinType, SourceTransformer, TransformedSource};
+use super::stats;
+use super::trace::{self, TraceCollector};
 use crate::models::BookSourceFull;
 use crate::storage::kv::KvStore;
 
@@ -54,6 +66,21 @@ pub struct BookSource {
     /// TLS Fingerprint to mimic (e.g., "chrome", "safari")
     #[serde(default)]
     pub fingerprint: Option<String>,
+    /// Solve Cloudflare challenges through FlareSolverr (default true)
+    #[serde(default)]
+    pub enabled_cloudflare_bypass: Option<bool>,
+    /// User-Agents to rotate through, one per request
+    #[serde(default)]
+    pub user_agents: Vec<String>,
+    /// User-Agent rotation mode (`random`, `per-session`), over the built-in pool without `user_agents`
+    #[serde(default)]
+    pub ua_rotation: Option<String>,
+    /// Minimum random delay added to each request (milliseconds)
+    #[serde(default)]
+    pub jitter_min_ms: Option<u64>,
+    /// Maximum random delay added to each request (milliseconds)
+    #[serde(default)]
+    pub jitter_max_ms: Option<u64>,
 }
 
 /// Search rule configuration
@@ -143,6 +170,70 @@ pub struct BookItem {
     pub toc_url: Option<String>,
 }
 
+impl BookItem {
+    /// Decode the numeric entities some sources leave in text fields (e.g. `&#x4e2d;` titles)
+    fn decode_entities(mut self) -> Self {
+        decode_entities_in(&mut self.name);
+        decode_entities_in(&mut self.author);
+        for field in [
+            &mut self.intro,
+            &mut self.kind,
+            &mut self.word_count,
+            &mut self.last_chapter,
+            &mut self.update_time,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            decode_entities_in(field);
+        }
+        self
+    }
+}
+
+fn decode_entities_in(text: &mut String) {
+    if let Cow::Owned(decoded) = decode_numeric_entities(text) {
+        *text = decoded;
+    }
+}
+
+/// Explore category parsed from `exploreUrl`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExploreKind {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+}
+
+/// Parse explore categories from the static `exploreUrl` formats Legado uses:
+/// a JSON array of `{title, url}` objects, or `title::url` entries separated
+/// by newlines or `&&`. Entries without `::` are kept as title-only headers.
+pub fn parse_explore_kinds(text: &str) -> Vec<ExploreKind> {
+    let text = text.trim();
+    if text.starts_with('[') {
+        if let Ok(kinds) = serde_json::from_str::<Vec<ExploreKind>>(text) {
+            return kinds;
+        }
+    }
+
+    text.split("&&")
+        .flat_map(|part| part.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once("::") {
+            Some((title, url)) => ExploreKind {
+                title: title.trim().to_string(),
+                url: url.trim().to_string(),
+            },
+            None => ExploreKind {
+                title: line.to_string(),
+                url: String::new(),
+            },
+        })
+        .collect()
+}
+
 /// Chapter item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chapter {
@@ -151,6 +242,51 @@ pub struct Chapter {
     pub is_volume: bool,
 }
 
+/// `bookSourceType` of audio sources, whose content rule yields an audio URL
+pub const SOURCE_TYPE_AUDIO: i32 = 1;
+
+/// `bookSourceType` of image (comic) sources, whose content rule yields a list of image URLs
+pub const SOURCE_TYPE_IMAGE: i32 = 2;
+
+/// How long a proxied media stream may take before it is cut off
+const MEDIA_STREAM_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Playable audio of a chapter from an audio source, serialized as `{type: "audio", url, headers}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "audio")]
+pub struct AudioContent {
+    pub url: String,
+    /// Headers the origin expects: source headers, cookies and a Referer
+    pub headers: HashMap<String, String>,
+}
+
+/// Pages of a chapter from an image source, serialized as `{type: "image", images}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename = "image")]
+pub struct ImageContent {
+    pub images: Vec<String>,
+}
+
+impl ImageContent {
+    /// Read back content produced by an image source; `None` for text content
+    pub fn from_content(content: &str) -> Option<Self> {
+        if !content.starts_with('{') {
+            return None;
+        }
+        serde_json::from_str(content).ok()
+    }
+}
+
+/// Attributes lazy-loading scripts read the real image URL from
+const LAZY_SRC_ATTRS: [&str; 6] = [
+    "data-original",
+    "data-src",
+    "data-lazy-src",
+    "data-lazyload",
+    "data-echo",
+    "data-url",
+];
+
 // Helper implementations for rule conversions if fields match exactly,
 // otherwise use serde_json for robust conversion as they are practically identical structs
 // But since they are defined in different modules with same structure...
@@ -158,13 +294,220 @@ pub struct Chapter {
 // Let's implement From using serde_json trick in the impl above if explicit mapping is tedious.
 // Re-visiting implementation:
 
+/// Default cap on chapters collected across all TOC pages
+pub const DEFAULT_MAX_TOC_CHAPTERS: usize = 10_000;
+
+/// Default safety net on the number of TOC pages fetched for one book
+const MAX_TOC_PAGES: usize = 500;
+
+/// Default cap on nextContentUrl pages fetched for one chapter
+pub const DEFAULT_MAX_CONTENT_PAGES: usize = 20;
+
+/// Upper bound for a per-request content page cap
+const MAX_CONTENT_PAGES: usize = 100;
+
+/// A content page sharing more than this share of its text with the previous
+/// page is a duplicate and ends pagination
+const DUPLICATE_PAGE_OVERLAP: f64 = 0.8;
+
+/// Length in characters of the shingles compared by [`content_overlap`]
+const SHINGLE_LEN: usize = 8;
+
+/// Resolve a next-page URL (nextTocUrl / nextContentUrl) against the page it came from
+///
+/// A next URL without its own `,{options}` inherits the page's `headers`, so
+/// per-request headers keep applying to every page of a TOC or chapter.
+fn next_page_url(page_url: &str, next_url: &str) -> String {
+    let (page_base, headers) = match split_url_options(page_url) {
+        Some((base, options)) => (base, options.get("headers").cloned()),
+        None => (page_url, None),
+    };
+    let url = resolve_absolute_url(page_base, next_url);
+    match headers {
+        Some(headers) if split_url_options(&url).is_none() => {
+            format!("{},{}", url, serde_json::json!({ "headers": headers }))
+        }
+        _ => url,
+    }
+}
+
+/// Restores the analyzer's page URL when an entry point returns, so request URLs
+/// built later see the source's base URL as `baseUrl` again
+struct PageScope<'a> {
+    analyzer: &'a RuleAnalyzer,
+    previous: String,
+}
+
+impl Drop for PageScope<'_> {
+    fn drop(&mut self) {
+        self.analyzer.set_page_url(&self.previous);
+    }
+}
+
+/// The URL `page_url` ended up at after redirects, keeping its `,{options}`
+///
+/// Returns `page_url` itself when the request was not redirected.
+fn redirected_url(page_url: &str, final_url: &str) -> String {
+    let (base, options) = match split_url_options(page_url) {
+        Some((base, options)) => (base, Some(options)),
+        None => (page_url, None),
+    };
+    let unchanged = reqwest::Url::parse(base).is_ok_and(|url| url.as_str() == final_url);
+    match options {
+        _ if unchanged || final_url.is_empty() => page_url.to_string(),
+        Some(options) => format!("{},{}", final_url, serde_json::Value::Object(options)),
+        None => final_url.to_string(),
+    }
+}
+
+/// Whether a source `header` is `@js:`/`<js>` code computing the headers rather than JSON
+fn is_header_js(header: &str) -> bool {
+    let header = header.trim_start();
+    header.starts_with("@js:") || header.starts_with("<js>")
+}
+
+/// Evaluate a `header` JS rule into a header map; failures and non-object results give no headers
+fn eval_header_js(analyzer: &RuleAnalyzer, code: &str) -> HashMap<String, String> {
+    let code = code.trim();
+    let result = match code.strip_prefix("@js:") {
+        Some(js) => analyzer.eval_js(js, &HashMap::new()),
+        None => analyzer.process_js_tags(code, ""),
+    };
+    match result {
+        Ok(json) => parse_header_map(&json).unwrap_or_else(|| {
+            let preview: String = json.chars().take(100).collect();
+            tracing::warn!("Header JS did not return a JSON object: {}", preview);
+            HashMap::new()
+        }),
+        Err(e) => {
+            tracing::warn!("Failed to evaluate header JS: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Compile a source's rules, reusing the on-disk rule cache keyed by the md5 of the source JSON
+///
+/// Returns `None` when the source does not convert to a `BookSourceFull`.
+pub fn transform_source(source: &BookSource) -> Option<TransformedSource> {
+    let rule_cache = RuleCache::open_default();
+    let source_json_str = serde_json::to_string(source).unwrap_or_default();
+    let hash = format!("{:x}", md5::compute(&source_json_str));
+    if let Some(transformed) = rule_cache.load(&hash) {
+        return Some(transformed);
+    }
+
+    let json = serde_json::to_value(source).ok()?;
+    let full_source = serde_json::from_value::<BookSourceFull>(json).ok()?;
+    let transformed = SourceTransformer::new().transform(&full_source);
+    rule_cache.store(&hash, &transformed);
+    Some(transformed)
+}
+
+/// Identity of a TOC or content page for cycle detection: the URL without its
+/// `#fragment`, keeping any `,{options}` suffix since it changes the request
+fn page_key(url: &str) -> String {
+    let (base, options) = match split_url_options(url) {
+        Some((base, options)) => (base, Some(serde_json::Value::Object(options).to_string())),
+        None => (url, None),
+    };
+    let base = base.trim();
+    let base = base.split_once('#').map_or(base, |(b, _)| b);
+    match options {
+        Some(options) => format!("{},{}", base, options),
+        None => base.to_string(),
+    }
+}
+
+/// Identity of a chapter for matching nextContentUrl against the TOC: like
+/// [`page_key`], but ignoring the `headers` next pages inherit from the first
+fn chapter_key(url: &str) -> String {
+    match split_url_options(url) {
+        Some((base, mut options)) => {
+            options.remove("headers");
+            if options.is_empty() {
+                page_key(base)
+            } else {
+                page_key(&format!("{},{}", base, serde_json::Value::Object(options)))
+            }
+        }
+        None => page_key(url),
+    }
+}
+
+/// Share of `page`'s text already present in `previous`, compared as sets of
+/// hashed character shingles with whitespace removed
+fn content_overlap(previous: &str, page: &str) -> f64 {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    fn shingles(text: &str) -> HashSet<u64> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let window = SHINGLE_LEN.min(chars.len().max(1));
+        chars
+            .windows(window)
+            .map(|w| {
+                let mut hasher = DefaultHasher::new();
+                w.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    let page = shingles(page);
+    if page.is_empty() {
+        return 0.0;
+    }
+    let previous = shingles(previous);
+    page.iter().filter(|s| previous.contains(s)).count() as f64 / page.len() as f64
+}
+
+/// URL of the image an `<img>` fragment shows: its src, or for a lazy-loaded
+/// image whose src is a placeholder, the first `data-*` attribute holding the URL
+fn image_src(fragment: &str) -> Option<String> {
+    let html = scraper::Html::parse_fragment(fragment);
+    let selector = scraper::Selector::parse("img").ok()?;
+    let img = html.select(&selector).next()?.value();
+    img.attr("src")
+        .map(str::trim)
+        .filter(|src| !is_placeholder_src(src))
+        .or_else(|| {
+            LAZY_SRC_ATTRS
+                .iter()
+                .filter_map(|attr| img.attr(attr).map(str::trim))
+                .find(|url| !url.is_empty())
+        })
+        .map(str::to_string)
+}
+
+/// Whether an img src is a lazy-loading placeholder rather than the real image
+fn is_placeholder_src(src: &str) -> bool {
+    let lower = src.to_ascii_lowercase();
+    lower.is_empty()
+        || lower.starts_with("data:")
+        || ["loading", "placeholder", "blank.", "lazy", "grey.gif", "pixel.gif"]
+            .iter()
+            .any(|marker| lower.contains(marker))
+}
+
 /// Main Book Source Engine
 pub struct BookSourceEngine {
     pub(crate) source: BookSource,
     pub(crate) analyzer: RuleAnalyzer,
-    pub(crate) http: HttpClient,
-    pub(crate) transformed: Option<TransformedSource>,
-    pub(crate) native_executor: Option<NativeExecutor>,
+    pub(crate) http: Arc<HttpClient>,
+    pub(crate) transformed: Option<Arc<TransformedSource>>,
+    pub(crate) native_executor: Option<Arc<NativeExecutor>>,
+    pub(crate) max_toc_chapters: usize,
+    pub(crate) max_toc_pages: usize,
+    pub(crate) max_content_pages: usize,
+    /// TOC chapter URLs; nextContentUrl never follows into another chapter
+    pub(crate) chapter_urls: Vec<String>,
+    /// Cached TOC pages younger than this are reused without a request
+    pub(crate) toc_max_age: Option<Duration>,
+    /// The `header` JS reads `java.` values (cache, time), so it is re-evaluated per request
+    pub(crate) dynamic_headers: bool,
+    pub(crate) trace: Option<TraceCollector>,
+    /// Deadline of the request this engine serves
+    pub(crate) deadline: Option<Deadline>,
 }
 
 impl BookSourceEngine {
@@ -187,10 +530,25 @@ impl BookSourceEngine {
             }
         }
 
-        // Create HTTP client with source-level headers
-        let http = HttpClient::with_config(&base_url, source.header.as_deref(), source.fingerprint.as_deref())?;
-        let mut analyzer = RuleAnalyzer::new(kv_store.clone())?;
+        // Create HTTP client with source-level headers; JS headers are evaluated once the analyzer is ready
+        let header_js = source.header.as_deref().filter(|h| is_header_js(h));
+        let static_headers = source.header.as_deref().filter(|_| header_js.is_none());
+        let mut http = HttpClient::with_config(&base_url, static_headers, source.fingerprint.as_deref())?;
+        if let Some(rate) = source.concurrent_rate.as_deref().filter(|r| !r.trim().is_empty()) {
+            http.set_rate_limit(rate);
+        }
+        http.set_cloudflare_bypass(source.enabled_cloudflare_bypass.unwrap_or(true));
+        http.set_cache(HttpCache::open_default());
+        http.set_user_agents(&source.user_agents, source.ua_rotation.as_deref());
+        http.set_jitter(source.jitter_min_ms, source.jitter_max_ms);
+        http.set_cooldowns(Cooldowns::shared(), &source.book_source_url);
+        // All engines share one cookie jar so login sessions carry over
+        let cookie_manager = CookieManager::shared();
+        *http.cookie_manager_mut() = cookie_manager.clone();
+        let cookie_manager = Arc::new(cookie_manager);
+        let mut analyzer = RuleAnalyzer::with_cookie_manager(cookie_manager.clone(), kv_store.clone())?;
         analyzer.set_base_url(&base_url);
+        analyzer.set_source_url(&source.book_source_url);
 
         // Preload jsLib if present
         if let Some(ref js_lib) = source.js_lib {
@@ -198,58 +556,145 @@ impl BookSourceEngine {
                 tracing::warn!("Failed to preload jsLib: {}", e);
             }
         }
-
-        // Compile source rules
-        // Compile source rules with caching
-        let mut transformed = None;
-        // Initialize Native Executor early infrastructure
-        let cm = Arc::new(crate::engine::cookie::CookieManager::new());
-        let provider = Arc::new(NativeApiProvider::new(cm, kv_store));
-        let native_executor = Some(NativeExecutor::new(provider));
-
-        // Setup cache
-        let cache_dir = get_cache_dir().join("rules");
-        let _ = fs::create_dir_all(&cache_dir);
-
-        let source_json_str = serde_json::to_string(&source).unwrap_or_default();
-        let hash = format!("{:x}", md5::compute(&source_json_str));
-        let cache_path = cache_dir.join(format!("{}.json", hash));
-
-        // Try load from cache
-        if cache_path.exists() {
-            if let Ok(file) = fs::File::open(&cache_path) {
-                if let Ok(cached) = serde_json::from_reader(file) {
-                    transformed = Some(cached);
-                }
-            }
+        if let Some(code) = header_js {
+            http.set_default_headers(eval_header_js(&analyzer, code));
         }
+        let dynamic_headers = header_js.is_some_and(|code| code.contains("java."));
 
-        // Convert to BookSourceFull for transformer if not cached
-        if transformed.is_none() {
-            if let Ok(json) = serde_json::to_value(&source) {
-                if let Ok(full_source) = serde_json::from_value::<BookSourceFull>(json) {
-                    let transformer = SourceTransformer::new();
-                    let t = transformer.transform(&full_source);
-
-                    // Save to cache
-                    if let Ok(file) = fs::File::create(&cache_path) {
-                        let _ = serde_json::to_writer(file, &t);
-                    }
+        // Initialize Native Executor early infrastructure
+        let provider = Arc::new(NativeApiProvider::new(cookie_manager, kv_store));
+        let native_executor = Some(Arc::new(NativeExecutor::new(provider)));
 
-                    transformed = Some(t);
-                }
-            }
-        }
+        let transformed = transform_source(&source);
 
         Ok(Self {
             source,
             analyzer,
-            http,
-            transformed,
+            http: Arc::new(http),
+            transformed: transformed.map(Arc::new),
             native_executor,
+            max_toc_chapters: DEFAULT_MAX_TOC_CHAPTERS,
+            max_toc_pages: MAX_TOC_PAGES,
+            max_content_pages: DEFAULT_MAX_CONTENT_PAGES,
+            chapter_urls: Vec::new(),
+            toc_max_age: None,
+            dynamic_headers,
+            trace: None,
+            deadline: None,
         })
     }
 
+    /// A new engine for one request, sharing this engine's HTTP client, JS runtime and compiled rules
+    ///
+    /// Request state (book, chapter, `@put` variables, chapter URLs, TOC cache age,
+    /// JS limits, trace and deadline) starts empty, so forks of one engine can run concurrently.
+    pub fn fork(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            analyzer: self.analyzer.fork(),
+            http: self.http.clone(),
+            transformed: self.transformed.clone(),
+            native_executor: self.native_executor.clone(),
+            max_toc_chapters: self.max_toc_chapters,
+            max_toc_pages: self.max_toc_pages,
+            max_content_pages: self.max_content_pages,
+            chapter_urls: Vec::new(),
+            toc_max_age: None,
+            dynamic_headers: self.dynamic_headers,
+            trace: None,
+            deadline: None,
+        }
+    }
+
+    /// Log in with the fields submitted from the source's loginUi form
+    pub fn login(&self, fields: &HashMap<String, String>) -> Result<LoginResult> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        login::run_login(&self.source, fields, &self.http, &self.analyzer)
+    }
+
+    /// Set the book rules see as `book` (search URLs, book info, TOC and content rules)
+    pub fn set_book(&self, book: BookContext) {
+        self.analyzer.set_book(Some(book));
+    }
+
+    /// Set the chapter rules see as `chapter` / `title` while fetching its content
+    pub fn set_chapter(&self, chapter: ChapterContext) {
+        self.analyzer.set_chapter(Some(chapter));
+    }
+
+    /// Limit each JS evaluation of the source's rules to `timeout`
+    pub fn set_js_timeout(&self, timeout: Duration) {
+        self.analyzer.set_js_timeout(timeout);
+    }
+
+    /// Interrupt the source's running JS once `flag` is raised (e.g. a cancelled search)
+    pub fn set_js_cancel_flag(&self, flag: Arc<AtomicBool>) {
+        self.analyzer.set_js_cancel_flag(flag);
+    }
+
+    /// Bound the engine's calls by `deadline`: requests and JS evaluations are not
+    /// started after it and their own timeouts are trimmed to the time left
+    pub fn set_deadline(&mut self, deadline: Option<Deadline>) {
+        self.deadline = deadline;
+    }
+
+    /// Record requests and rule evaluations into `trace` (source debugging)
+    pub fn set_trace(&mut self, trace: TraceCollector) {
+        self.analyzer.set_trace(trace.clone());
+        trace.message(format!("Client profile: {}", self.http.profile().name));
+        self.trace = Some(trace);
+    }
+
+    /// Perform a request, recording it when tracing is enabled
+    fn fetch(&self, config: &RequestConfig) -> Result<String> {
+        Ok(self.fetch_page(config)?.body)
+    }
+
+    /// Perform a request and make its final URL (after redirects) the page rules resolve against
+    fn fetch_page(&self, config: &RequestConfig) -> Result<StrResponse> {
+        let config = self.with_dynamic_headers(config);
+        let result = self.http.request_with_meta(&config);
+        if let Some(trace) = &self.trace {
+            let body = result.as_ref().map(|r| r.body.clone()).map_err(|e| anyhow!("{:#}", e));
+            trace.request(&config, self.http.default_headers(), &body);
+        }
+        let response = result?;
+        self.analyzer.set_page_url(&response.url);
+        Ok(response)
+    }
+
+    /// Keep the page set by requests in the calling entry point, restoring the previous one after
+    fn page_scope(&self) -> PageScope<'_> {
+        PageScope {
+            analyzer: &self.analyzer,
+            previous: self.analyzer.page_url(),
+        }
+    }
+
+    /// Resolve a link found on the current page against the page's final URL
+    fn absolute_url(&self, url: &str) -> String {
+        let page_url = self.analyzer.page_url();
+        let page = page_url.split(['?', '#']).next().unwrap_or_default();
+        resolve_absolute_url(page, url)
+    }
+
+    /// Add the re-evaluated `header` JS to a request; headers from the URL options still win
+    fn with_dynamic_headers<'a>(&self, config: &'a RequestConfig) -> Cow<'a, RequestConfig> {
+        let code = match self.source.header.as_deref() {
+            Some(code) if self.dynamic_headers => code,
+            _ => return Cow::Borrowed(config),
+        };
+        let mut config = config.clone();
+        let headers = config.headers.get_or_insert_with(HashMap::new);
+        for (name, value) in eval_header_js(&self.analyzer, code) {
+            if !headers.keys().any(|h| h.eq_ignore_ascii_case(&name)) {
+                headers.insert(name, value);
+            }
+        }
+        Cow::Owned(config)
+    }
+
     /// Reconstruct rule string from CompiledRule
     fn reconstruct_rule(&self, rule_type: &RuleType, selector: &str) -> String {
         let prefix = match rule_type {
@@ -257,7 +702,8 @@ impl BookSourceEngine {
             RuleType::XPath => "@xpath:",
             RuleType::JsonPath => "@json:",
             RuleType::JsoupDefault => "",
-            RuleType::Regex => "@regex:",
+            // `##pattern` and `:pattern` carry their own marker
+            RuleType::Regex => "",
             _ => "",
         };
         format!("{}{}", prefix, selector)
@@ -278,9 +724,19 @@ impl BookSourceEngine {
                 if let Some(executor) = &self.native_executor {
                     let context = crate::engine::native_api::ExecutionContext {
                         base_url: self.source.book_source_url.clone(),
+                        source_url: self.source.book_source_url.clone(),
                     };
-                    let vars = HashMap::new();
-                    executor.execute(exec, &context, &vars, Some(content))
+                    let vars = self.analyzer.context_variables();
+                    let (result, execution) =
+                        trace::measure(|| executor.execute(exec, &context, &vars, Some(content)));
+                    if let Some(trace) = &self.trace {
+                        let value = match &result {
+                            Ok(v) => Ok(v.clone()),
+                            Err(e) => Err(format!("{:#}", e)),
+                        };
+                        trace.rule(&format!("{:?}", exec), None, execution, value);
+                    }
+                    result
                 } else {
                     Err(anyhow!("Native executor not initialized"))
                 }
@@ -289,6 +745,32 @@ impl BookSourceEngine {
                 let rule_str = format!("@js:{}", code);
                 self.analyzer.get_string(content, &rule_str)
             }
+            CompiledRule::Chain {
+                put,
+                base,
+                regex_suffix,
+                js_post,
+            } => {
+                self.analyzer.put_rules(content, put);
+                let result = self.execute_compiled(base, content)?;
+                self.analyzer
+                    .post_process(result, regex_suffix.as_deref(), js_post.as_deref())
+            }
+            CompiledRule::Composite {
+                parts,
+                join_type: JoinType::FirstMatch,
+            } => Ok(parts
+                .iter()
+                .filter_map(|part| self.execute_compiled(part, content).ok())
+                .find(|result| !result.is_empty() && result != "null")
+                .unwrap_or_default()),
+            CompiledRule::Composite {
+                parts,
+                join_type: JoinType::Concatenate,
+            } => Ok(parts
+                .iter()
+                .map(|part| self.execute_compiled(part, content).unwrap_or_default())
+                .collect()),
             _ => Err(anyhow!("Unsupported compiled rule type")),
         }
     }
@@ -307,8 +789,9 @@ impl BookSourceEngine {
                 if let Some(executor) = &self.native_executor {
                     let context = crate::engine::native_api::ExecutionContext {
                         base_url: self.source.book_source_url.clone(),
+                        source_url: self.source.book_source_url.clone(),
                     };
-                    let vars = HashMap::new();
+                    let vars = self.analyzer.context_variables();
                     let res = executor.execute(exec, &context, &vars, Some(content))?;
                     // Try parse as JSON list
                     if res.trim().starts_with('[') {
@@ -328,11 +811,14 @@ impl BookSourceEngine {
 
     /// Search for books
     pub fn search(&self, key: &str, page: i32) -> Result<Vec<BookItem>> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
         let search_url = self
             .source
             .search_url
             .as_ref()
-            .ok_or_else(|| anyhow!("No search URL defined"))?;
+            .ok_or_else(|| EngineError::rule_missing("searchUrl"))?;
 
         tracing::debug!(
             "Search URL template: {}",
@@ -367,7 +853,7 @@ impl BookSourceEngine {
             config.method
         );
 
-        let content = match self.http.request(&config) {
+        let content = match self.fetch(&config) {
             Ok(c) => {
                 tracing::debug!("Search response length: {} bytes", c.len());
                 c
@@ -405,17 +891,17 @@ impl BookSourceEngine {
                     cover_url: self
                         .execute_compiled(&rules.cover_url, &element)
                         .ok()
-                        .map(|u| self.http.absolute_url(&u)),
+                        .map(|u| self.absolute_url(&u)),
                     book_url: self
                         .execute_compiled(&rules.book_url, &element)
                         .ok()
-                        .map(|u| self.http.absolute_url(&u))
+                        .map(|u| self.absolute_url(&u))
                         .unwrap_or_default(),
                     word_count: self.execute_compiled(&rules.word_count, &element).ok(),
                     update_time: self.execute_compiled(&rules.update_time, &element).ok(),
                     toc_url: None, // Search usually doesn't provide TOC link directly or same as book_url
                 };
-                books.push(book);
+                books.push(book.decode_entities());
             }
             return Ok(books);
         }
@@ -424,27 +910,53 @@ impl BookSourceEngine {
             .source
             .rule_search
             .as_ref()
-            .ok_or_else(|| anyhow!("No search rule defined"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleSearch"))?;
 
         let book_list_rule = rule
             .book_list
             .as_ref()
-            .ok_or_else(|| anyhow!("No book_list rule"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleSearch.bookList"))?;
 
-        let elements = self.analyzer.get_elements(&content, book_list_rule)?;
+        let elements = self
+            .analyzer
+            .get_elements(&content, book_list_rule)
+            .with_context(|| EngineError::parse_failed(book_list_rule.as_str(), "search"))?;
 
         let mut books = Vec::new();
         for element in elements {
             if let Ok(book) = self.parse_book_item(&element, rule) {
-                books.push(book);
+                books.push(book.decode_entities());
             }
         }
 
         Ok(books)
     }
 
+    /// Get explore categories from `exploreUrl`, evaluating `<js>`/`@js:` generated lists first
+    pub fn explore_kinds(&self) -> Result<Vec<ExploreKind>> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let raw = match self.source.explore_url.as_deref().map(str::trim) {
+            Some(raw) if !raw.is_empty() => raw,
+            _ => return Ok(Vec::new()),
+        };
+
+        let text = if let Some(code) = raw.strip_prefix("@js:") {
+            self.analyzer.eval_js(code, &HashMap::new())?
+        } else if raw.starts_with("<js>") {
+            self.analyzer.process_js_tags(raw, "")?
+        } else {
+            raw.to_string()
+        };
+
+        Ok(parse_explore_kinds(&text))
+    }
+
     /// Explore/Discovery books by URL (e.g. from exploreUrl categories)
     pub fn explore(&self, url_template: &str, page: i32) -> Result<Vec<BookItem>> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
         let mut vars = HashMap::new();
         vars.insert("page".to_string(), page.to_string());
 
@@ -470,26 +982,29 @@ impl BookSourceEngine {
             config.method
         );
 
-        let content = self.http.request(&config)?;
+        let content = self.fetch(&config)?;
 
         // Parse results
         let rule = self
             .source
             .rule_explore
             .as_ref()
-            .ok_or_else(|| anyhow!("No explore rule defined"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleExplore"))?;
 
         let book_list_rule = rule
             .book_list
             .as_ref()
-            .ok_or_else(|| anyhow!("No book_list rule in rule_explore"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleExplore.bookList"))?;
 
-        let elements = self.analyzer.get_elements(&content, book_list_rule)?;
+        let elements = self
+            .analyzer
+            .get_elements(&content, book_list_rule)
+            .with_context(|| EngineError::parse_failed(book_list_rule.as_str(), "explore"))?;
 
         let mut books = Vec::new();
         for element in elements {
             if let Ok(book) = self.parse_explore_item(&element, rule) {
-                books.push(book);
+                books.push(book.decode_entities());
             }
         }
 
@@ -522,9 +1037,16 @@ impl BookSourceEngine {
         Ok(false)
     }
 
+    /// Fetch an image (e.g. a cover) with the source's headers, cookies and Referer
+    pub fn fetch_image(&self, url: &str, max_bytes: usize) -> Result<BinaryResponse> {
+        let config = self.http.media_request_config(url);
+        self.http.request_bytes(&self.with_dynamic_headers(&config), max_bytes)
+    }
+
     /// Refresh book URL by searching for the book again
     /// Useful for sources where bookUrl changes periodically
     pub fn refresh_book_url(&self, book: &mut BookItem) -> Result<()> {
+        self.set_book(BookContext::from(&*book));
         let results = self.search(&book.name, 1)?;
 
         for result in results {
@@ -564,9 +1086,31 @@ impl BookSourceEngine {
     }
 
     /// Get book info
+    ///
+    /// The parsed info becomes the current `book`, keeping the name, author and
+    /// origin already known when the page does not provide them.
     pub fn get_book_info(&self, book_url: &str) -> Result<BookItem> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
+        let info = self.parse_book_info(book_url)?.decode_entities();
+        let mut book = BookContext::from(&info);
+        if let Some(known) = self.analyzer.book() {
+            if book.name.is_empty() {
+                book.name = known.name;
+            }
+            if book.author.is_empty() {
+                book.author = known.author;
+            }
+            book.origin = known.origin;
+        }
+        self.set_book(book);
+        Ok(info)
+    }
+
+    fn parse_book_info(&self, book_url: &str) -> Result<BookItem> {
         let config = self.http.parse_request_config(book_url);
-        let content_raw = self.http.request(&config)?;
+        let content_raw = self.fetch(&config)?;
 
         // Use compiled rules if available
         if let Some(transformed) = &self.transformed {
@@ -589,13 +1133,17 @@ impl BookSourceEngine {
                 cover_url: self
                     .execute_compiled(&rules.cover_url, &content)
                     .ok()
-                    .map(|u| self.http.absolute_url(&u)),
+                    .map(|u| self.absolute_url(&u)),
                 book_url: book_url.to_string(),
                 kind: self.execute_compiled(&rules.kind, &content).ok(),
                 last_chapter: self.execute_compiled(&rules.last_chapter, &content).ok(),
                 word_count: self.execute_compiled(&rules.word_count, &content).ok(),
                 update_time: self.execute_compiled(&rules.update_time, &content).ok(),
-                toc_url: self.execute_compiled(&rules.toc_url, &content).ok(),
+                toc_url: self
+                    .execute_compiled(&rules.toc_url, &content)
+                    .ok()
+                    .filter(|u| !u.trim().is_empty())
+                    .map(|u| self.absolute_url(&u)),
             });
         }
 
@@ -604,7 +1152,7 @@ impl BookSourceEngine {
             .source
             .rule_book_info
             .as_ref()
-            .ok_or_else(|| anyhow!("No book info rule defined"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleBookInfo"))?;
 
         // Process init rule if present
         let content = if let Some(init_rule) = &rule.init {
@@ -630,34 +1178,31 @@ impl BookSourceEngine {
             cover_url: self
                 .get_rule_value(&content, &rule.cover_url)
                 .ok()
-                .map(|u| self.http.absolute_url(&u)),
+                .map(|u| self.absolute_url(&u)),
             book_url: book_url.to_string(),
             kind: self.get_rule_value(&content, &rule.kind).ok(),
             last_chapter: self.get_rule_value(&content, &rule.last_chapter).ok(),
             word_count: self.get_rule_value(&content, &rule.word_count).ok(),
             update_time: self.get_rule_value(&content, &rule.update_time).ok(),
-            toc_url: self.get_rule_value(&content, &rule.toc_url).ok(),
+            toc_url: self
+                .get_rule_value(&content, &rule.toc_url)
+                .ok()
+                .filter(|u| !u.trim().is_empty())
+                .map(|u| self.absolute_url(&u)),
         })
     }
 
     /// Get table of contents
     pub fn get_chapters(&self, toc_url: &str) -> Result<Vec<Chapter>> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
         // Compiled path
         if let Some(transformed) = &self.transformed {
             let rules = &transformed.toc_rules;
-            let mut all_chapters = Vec::new();
-            let mut current_url = toc_url.to_string();
-            let max_pages = 50;
-
-            for _ in 0..max_pages {
-                let config = self.http.parse_request_config(&current_url);
-                let content = self.http.request(&config)?;
-
-                let elements = self.execute_compiled_list(&rules.chapter_list, &content)?;
-                if elements.is_empty() {
-                    break;
-                }
-
+            return self.paginate_toc(toc_url, |content, _page_url| {
+                let elements = self.execute_compiled_list(&rules.chapter_list, content)?;
+                let mut chapters = Vec::new();
                 for element in elements {
                     let title = self
                         .execute_compiled(&rules.chapter_name, &element)
@@ -666,9 +1211,9 @@ impl BookSourceEngine {
                         .execute_compiled(&rules.chapter_url, &element)
                         .unwrap_or_default();
                     if !title.is_empty() {
-                        all_chapters.push(Chapter {
+                        chapters.push(Chapter {
                             title,
-                            url: self.http.absolute_url(&url),
+                            url: self.absolute_url(&url),
                             is_volume: self
                                 .execute_compiled(&rules.is_volume, &element)
                                 .ok()
@@ -677,231 +1222,505 @@ impl BookSourceEngine {
                         });
                     }
                 }
-
-                // Next page
                 let next_url = self
-                    .execute_compiled(&rules.next_toc_url, &content)
+                    .execute_compiled(&rules.next_toc_url, content)
                     .unwrap_or_default();
-                let next_url = next_url.trim();
-
-                if !next_url.is_empty() && next_url != current_url {
-                    let old_url = current_url.clone();
-                    current_url = self.http.absolute_url(next_url);
-                    if current_url != old_url {
-                        continue;
-                    }
-                }
-                break;
-            }
-            return Ok(all_chapters);
+                Ok((chapters, next_url))
+            });
         }
 
         let rule = self
             .source
             .rule_toc
             .as_ref()
-            .ok_or_else(|| anyhow!("No TOC rule defined"))?;
+            .ok_or_else(|| EngineError::rule_missing("ruleToc"))?;
 
         let chapter_list_rule = rule
             .chapter_list
             .as_ref()
-            .ok_or_else(|| anyhow!("No chapter_list rule"))?;
-
-        let mut all_chapters = Vec::new();
-        let mut current_url = toc_url.to_string();
-        let max_pages = 50; // TOC can be very long
-
-        for page_num in 0..max_pages {
-            let config = self.http.parse_request_config(&current_url);
-            let content = self.http.request(&config)?;
+            .ok_or_else(|| EngineError::rule_missing("ruleToc.chapterList"))?;
 
+        self.paginate_toc(toc_url, |content, page_url| {
             tracing::debug!(
                 "get_chapters: url={}, content_len={}, chapter_list_rule='{}', has_id_list={}, has_dd={}",
-                current_url, content.len(), chapter_list_rule,
+                page_url, content.len(), chapter_list_rule,
                 content.contains("id=\"list\"") || content.contains("id='list'"),
                 content.contains("<dd>") || content.contains("<dd ")
             );
 
-            let elements = self.analyzer.get_elements(&content, chapter_list_rule)?;
-            let page_chapters_count = elements.len();
-
-            tracing::debug!(
-                "get_chapters: found {} elements on page {}",
-                page_chapters_count,
-                page_num
-            );
+            let elements = self
+                .analyzer
+                .get_elements(content, chapter_list_rule)
+                .with_context(|| EngineError::parse_failed(chapter_list_rule.as_str(), "toc"))?;
 
+            let mut chapters = Vec::new();
             for element in elements {
-                match self.parse_chapter(&element, rule, &current_url) {
-                    Ok(chapter) => all_chapters.push(chapter),
+                match self.parse_chapter(&element, rule, page_url) {
+                    Ok(chapter) => chapters.push(chapter),
                     Err(e) => tracing::error!("Failed to parse chapter: {}", e),
                 }
             }
 
-            // Check for next page
-            if let Some(next_url_rule) = &rule.next_toc_url {
-                if !next_url_rule.is_empty() {
-                    if let Ok(next_url) = self.analyzer.get_string(&content, next_url_rule) {
-                        let next_url = next_url.trim();
-                        if !next_url.is_empty() && next_url != current_url {
-                            let old_url = current_url.clone();
-                            current_url = self.http.absolute_url(next_url);
-                            if current_url != old_url {
-                                tracing::debug!(
-                                    "Following nextTocUrl to page {}: {}",
-                                    page_num + 2,
-                                    current_url
-                                );
-                                continue;
-                            }
-                        }
-                    }
-                }
+            let next_url = match rule.next_toc_url.as_deref().filter(|r| !r.is_empty()) {
+                Some(next_url_rule) => self.analyzer.get_string(content, next_url_rule).unwrap_or_default(),
+                None => String::new(),
+            };
+            Ok((chapters, next_url))
+        })
+    }
+
+    /// Follow a paginated TOC starting at `toc_url`.
+    ///
+    /// `parse_page` receives each page's content and URL and returns the chapters
+    /// found on it plus the raw nextTocUrl value (which may list several URLs, one
+    /// per line). Relative next URLs resolve against the page they came from, and
+    /// pages are compared ignoring `#fragment`s so cycles terminate. Collection
+    /// stops once `max_toc_chapters` chapters have been gathered. Numeric entities
+    /// in chapter titles are decoded.
+    fn paginate_toc<F>(&self, toc_url: &str, mut parse_page: F) -> Result<Vec<Chapter>>
+    where
+        F: FnMut(&str, &str) -> Result<(Vec<Chapter>, String)>,
+    {
+        let mut chapters = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = VecDeque::from([toc_url.to_string()]);
+        visited.insert(page_key(toc_url));
+        let mut pages = 0;
+
+        let reason = loop {
+            let Some(page_url) = pending.pop_front() else {
+                break "no unvisited nextTocUrl";
+            };
+            if pages >= self.max_toc_pages {
+                break "page limit reached";
             }
+            pages += 1;
 
-            // If no next page or same URL, stop
-            if page_chapters_count == 0 {
-                break;
+            let mut config = self.http.parse_request_config(&page_url);
+            config.cache_max_age = self.toc_max_age;
+            let response = self.fetch_page(&config)?;
+            let page_url = redirected_url(&page_url, &response.url);
+            let (page_chapters, next) = parse_page(&response.body, &page_url)?;
+            tracing::debug!("get_chapters: found {} chapters on page {}", page_chapters.len(), pages);
+
+            if page_chapters.is_empty() && pages > 1 {
+                break "page has no chapters";
+            }
+            chapters.extend(page_chapters.into_iter().map(|mut chapter| {
+                decode_entities_in(&mut chapter.title);
+                chapter
+            }));
+            if chapters.len() >= self.max_toc_chapters {
+                chapters.truncate(self.max_toc_chapters);
+                break "chapter limit reached";
             }
-            break;
-        }
 
-        Ok(all_chapters)
+            for next_url in next.lines().map(str::trim).filter(|u| !u.is_empty()) {
+                let next_url = next_page_url(&page_url, next_url);
+                if visited.insert(page_key(&next_url)) {
+                    tracing::debug!("Following nextTocUrl to page {}: {}", pages + pending.len() + 1, next_url);
+                    pending.push_back(next_url);
+                }
+            }
+        };
+
+        tracing::debug!(
+            "get_chapters: stopped after page {} of {} ({}), {} chapters",
+            pages,
+            toc_url,
+            reason,
+            chapters.len()
+        );
+        Ok(chapters)
+    }
+
+    /// Cap on the total number of chapters collected across TOC pages
+    pub fn set_max_toc_chapters(&mut self, max: usize) {
+        self.max_toc_chapters = max.max(1);
+    }
+
+    /// Cap on the number of TOC pages fetched via nextTocUrl
+    pub fn set_max_toc_pages(&mut self, max: usize) {
+        self.max_toc_pages = max.clamp(1, MAX_TOC_PAGES);
+    }
+
+    /// Cap on the number of content pages fetched via nextContentUrl
+    pub fn set_max_content_pages(&mut self, max: usize) {
+        self.max_content_pages = max.clamp(1, MAX_CONTENT_PAGES);
+    }
+
+    /// URLs of the book's chapters, so content pagination stops at a link into another chapter
+    pub fn set_chapter_urls(&mut self, urls: Vec<String>) {
+        self.chapter_urls = urls;
+    }
+
+    /// Accept cached TOC pages up to `max_age` old instead of revalidating them
+    pub fn set_toc_max_age(&mut self, max_age: Option<Duration>) {
+        self.toc_max_age = max_age;
     }
 
     /// Get chapter content (with pagination support)
+    ///
+    /// Call [`Self::set_chapter`] first so rules can read `chapter` / `title`.
     pub fn get_content(&self, chapter_url: &str) -> Result<String> {
-        // Compiled path
-        if let Some(transformed) = &self.transformed {
-            let rules = &transformed.content_rules;
-            let mut full_content = String::new();
-            let mut current_url = chapter_url.to_string();
-            let max_pages = 20;
+        self.get_content_pages(chapter_url, |_, _| Ok(()))
+    }
 
-            for page_num in 0..max_pages {
-                let config = self.http.parse_request_config(&current_url);
-                let page_html = self.http.request(&config)?;
+    /// Get chapter content, handing each page's cleaned content to `on_page` as soon as it is fetched
+    ///
+    /// Returns the same assembled content as `get_content`; an error from `on_page` stops pagination.
+    /// Audio sources yield the chapter's [`AudioContent`] as JSON instead of text, image
+    /// sources the [`ImageContent`] collected across all pages (handed to `on_page` once).
+    pub fn get_content_pages<F>(&self, chapter_url: &str, mut on_page: F) -> Result<String>
+    where
+        F: FnMut(usize, String) -> Result<()>,
+    {
+        if self.is_audio_source() {
+            let audio = serde_json::to_string(&self.get_audio(chapter_url)?)?;
+            on_page(0, audio.clone())?;
+            return Ok(audio);
+        }
 
-                let page_content = self
-                    .execute_compiled(&rules.content, &page_html)
-                    .unwrap_or_default();
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
+        if self.transformed.is_none() {
+            self.source
+                .rule_content
+                .as_ref()
+                .ok_or_else(|| EngineError::rule_missing("ruleContent"))?
+                .content
+                .as_ref()
+                .ok_or_else(|| EngineError::rule_missing("ruleContent.content"))?;
+        }
 
-                if !page_content.is_empty() {
-                    if page_num > 0 {
-                        full_content.push_str("\n\n");
-                    }
-                    full_content.push_str(&page_content);
-                }
+        let mut full_content = String::new();
+        // Templates left for this stage, e.g. `{{chapter.index+1}}`
+        let mut current_url = if chapter_url.contains("{{") {
+            self.analyzer.process_url_templates(chapter_url, &HashMap::new())
+        } else {
+            chapter_url.to_string()
+        };
 
-                // Next page
-                let next_url = self
-                    .execute_compiled(&rules.next_content_url, &page_html)
-                    .unwrap_or_default();
-                let next_url = next_url.trim();
+        // Guards against a nextContentUrl that loops back, runs into the next
+        // chapter, or serves the same text under a new URL
+        let mut visited = HashSet::from([page_key(&current_url)]);
+        let first_chapter = chapter_key(&current_url);
+        let chapter_base = split_url_options(&current_url).map_or(current_url.as_str(), |(base, _)| base);
+        let other_chapters: HashSet<String> = self
+            .chapter_urls
+            .iter()
+            .map(|url| chapter_key(&resolve_absolute_url(chapter_base, url)))
+            .filter(|key| *key != first_chapter)
+            .collect();
+        let mut previous_page = String::new();
+        let image_source = self.is_image_source();
+
+        for page_num in 0..self.max_content_pages {
+            let mut config = self.http.parse_request_config(&current_url);
+            // The URL's own `js` option wins over the source's webJs
+            if config.web_view && config.web_js.is_none() {
+                config.web_js = self.content_web_js().map(str::to_string);
+            }
+            // Pages fetched before the request deadline are reported with its error
+            let partial = |e| {
+                if image_source {
+                    return e;
+                }
+                self.with_partial_content(e, page_num, &full_content)
+            };
+            let response = self.fetch_page(&config).map_err(partial)?;
+            current_url = redirected_url(&current_url, &response.url);
+            let page_html = response.body;
+            let (page_content, next_url) = if image_source {
+                self.extract_image_page(&page_html, &response.url)?
+            } else {
+                self.extract_content_page(&page_html).map_err(partial)?
+            };
 
-                if !next_url.is_empty() && next_url != current_url {
-                    current_url = self.http.absolute_url(next_url);
-                    continue;
+            if !page_content.is_empty() {
+                // Image URLs of consecutive pages differ only in a few characters
+                let repeated = !image_source && content_overlap(&previous_page, &page_content) > DUPLICATE_PAGE_OVERLAP;
+                if page_num > 0 && repeated {
+                    tracing::info!(
+                        "Stopped content pagination of {}: page {} repeats the previous page",
+                        chapter_url,
+                        page_num + 1
+                    );
+                    break;
                 }
+                if page_num > 0 {
+                    full_content.push_str("\n\n"); // Page separator
+                }
+                full_content.push_str(&page_content);
+                if !image_source {
+                    on_page(page_num, self.clean_content(&page_content))?;
+                }
+                previous_page = page_content;
+            }
+
+            let Some(next_url) = next_url else {
+                break; // No more pages
+            };
+            let next_url = next_page_url(&current_url, &next_url);
+            if !visited.insert(page_key(&next_url)) {
+                tracing::info!(
+                    "Stopped content pagination of {}: next page {} was already fetched",
+                    chapter_url,
+                    next_url
+                );
+                break;
+            }
+            if other_chapters.contains(&chapter_key(&next_url)) {
+                tracing::info!(
+                    "Stopped content pagination of {}: next page {} is another chapter",
+                    chapter_url,
+                    next_url
+                );
                 break;
             }
+            if page_num + 1 == self.max_content_pages {
+                tracing::info!(
+                    "Stopped content pagination of {}: reached the cap of {} pages",
+                    chapter_url,
+                    self.max_content_pages
+                );
+                break;
+            }
+            current_url = next_url;
+            tracing::debug!("Following nextContentUrl to page {}: {}", page_num + 2, current_url);
+        }
 
-            let smart_cleaned = self.smart_filter_content(&full_content);
-            let mut result = smart_cleaned;
+        if image_source {
+            let mut seen = HashSet::new();
+            let images = full_content
+                .lines()
+                .filter(|url| !url.is_empty() && seen.insert(*url))
+                .map(str::to_string)
+                .collect();
+            let content = serde_json::to_string(&ImageContent { images })?;
+            on_page(0, content.clone())?;
+            return Ok(content);
+        }
+        Ok(self.clean_content(&full_content))
+    }
 
-            // Apply compiled replace regex
-            for (pattern, replacement) in &rules.replace_regex {
-                if let Ok(re) = regex::Regex::new(pattern) {
-                    result = re.replace_all(&result, replacement.as_str()).to_string();
-                }
-            }
-            return Ok(result);
+    /// Attach the first `pages` content pages to a `DeadlineExceeded` error raised while paginating
+    fn with_partial_content(&self, err: anyhow::Error, pages: usize, content: &str) -> anyhow::Error {
+        if pages == 0 || !deadline::is_exceeded(&err) {
+            return err;
+        }
+        EngineError::DeadlineExceeded {
+            budget_ms: deadline::current().map_or(0, |d| d.budget().as_millis() as u64),
+            partial: Some(PartialContent {
+                pages,
+                content: self.clean_content(content),
+            }),
         }
+        .into()
+    }
 
-        let rule = self
+    /// Whether this is an audio source (`bookSourceType: 1`)
+    pub fn is_audio_source(&self) -> bool {
+        self.source.book_source_type == Some(SOURCE_TYPE_AUDIO)
+    }
+
+    /// Resolve the audio URL of a chapter from an audio source
+    ///
+    /// The content rule runs on the chapter page and its first non-empty line is
+    /// the audio URL; without a content rule the chapter URL itself is the audio.
+    pub fn get_audio(&self, chapter_url: &str) -> Result<AudioContent> {
+        let _stats = stats::enter_source(&self.source.book_source_url);
+        let _deadline = deadline::enter(self.deadline);
+        let _page = self.page_scope();
+        let chapter_url = if chapter_url.contains("{{") {
+            self.analyzer.process_url_templates(chapter_url, &HashMap::new())
+        } else {
+            chapter_url.to_string()
+        };
+
+        let has_content_rule = self
             .source
             .rule_content
             .as_ref()
-            .ok_or_else(|| anyhow!("No content rule defined"))?;
+            .and_then(|rule| rule.content.as_deref())
+            .is_some_and(|rule| !rule.trim().is_empty());
+        let audio_url = if has_content_rule {
+            let config = self.http.parse_request_config(&chapter_url);
+            let (content, _) = self.extract_content_page(&self.fetch(&config)?)?;
+            let url = content
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .ok_or_else(|| EngineError::parse_failed("ruleContent.content", "audio url"))?;
+            resolve_absolute_url(&config.url, url)
+        } else {
+            chapter_url
+        };
+
+        let config = self.http.media_request_config(&audio_url);
+        let config = self.with_dynamic_headers(&config);
+        Ok(AudioContent {
+            url: config.url.clone(),
+            headers: self.http.request_headers(&config),
+        })
+    }
 
+    /// Open an audio (or other media) URL for proxying, forwarding `range`
+    ///
+    /// The request carries the source's headers, cookies and a Referer, as [`Self::get_audio`] reports.
+    pub fn open_media(&self, url: &str, range: Option<&str>) -> Result<reqwest::blocking::Response> {
+        let mut config = self
+            .with_dynamic_headers(&self.http.media_request_config(url))
+            .into_owned();
+        if let Some(range) = range {
+            config
+                .headers
+                .get_or_insert_with(HashMap::new)
+                .insert("Range".to_string(), range.to_string());
+        }
+        config.timeout = MEDIA_STREAM_TIMEOUT;
+        self.http.open_stream(&config)
+    }
+
+    /// Whether this is an image (comic) source (`bookSourceType: 2`)
+    pub fn is_image_source(&self) -> bool {
+        self.source.book_source_type == Some(SOURCE_TYPE_IMAGE)
+    }
+
+    /// Extract the image URLs of one page of an image source, one absolute URL per line
+    ///
+    /// The content rule runs as a list rule. When it reads `@src` of the matched
+    /// elements, lazy-loaded images whose src is only a placeholder fall back to
+    /// `data-original`, `data-src` and similar attributes.
+    fn extract_image_page(&self, page_html: &str, page_url: &str) -> Result<(String, Option<String>)> {
+        let rule = self
+            .source
+            .rule_content
+            .as_ref()
+            .ok_or_else(|| EngineError::rule_missing("ruleContent"))?;
         let content_rule = rule
             .content
+            .as_deref()
+            .ok_or_else(|| EngineError::rule_missing("ruleContent.content"))?
+            .trim();
+
+        let lazy_images = content_rule
+            .strip_suffix("@src")
+            .filter(|elements| !elements.is_empty() && !elements.contains("##"))
+            .and_then(|elements| self.analyzer.get_elements(page_html, elements).ok())
+            .map(|elements| elements.iter().filter_map(|el| image_src(el)).collect::<Vec<_>>())
+            .filter(|images| !images.is_empty());
+        let images = match lazy_images {
+            Some(images) => images,
+            None => self.analyzer.get_list(page_html, content_rule)?,
+        };
+        let images: Vec<String> = images
+            .iter()
+            .map(|url| url.trim())
+            .filter(|url| !url.is_empty())
+            .map(|url| resolve_absolute_url(page_url, url))
+            .collect();
+
+        let next_url = rule
+            .next_content_url
             .as_ref()
-            .ok_or_else(|| anyhow!("No content rule"))?;
-
-        let mut full_content = String::new();
-        let mut current_url = chapter_url.to_string();
-        let max_pages = 20; // Prevent infinite loops
+            .filter(|r| !r.is_empty())
+            .and_then(|r| self.analyzer.get_string(page_html, r).ok())
+            .unwrap_or_default();
+        let next_url = next_url.trim();
+        Ok((images.join("\n"), (!next_url.is_empty()).then(|| next_url.to_string())))
+    }
 
-        for page_num in 0..max_pages {
-            let config = self.http.parse_request_config(&current_url);
-            let page_html = self.http.request(&config)?;
+    /// Extract one content page and its nextContentUrl (if any)
+    fn extract_content_page(&self, page_html: &str) -> Result<(String, Option<String>)> {
+        let (content, next_url) = if let Some(transformed) = &self.transformed {
+            let rules = &transformed.content_rules;
+            let content = self
+                .execute_compiled(&rules.content, page_html)
+                .unwrap_or_default();
+            let next_url = self
+                .execute_compiled(&rules.next_content_url, page_html)
+                .unwrap_or_default();
+            (content, next_url)
+        } else {
+            let rule = self
+                .source
+                .rule_content
+                .as_ref()
+                .ok_or_else(|| EngineError::rule_missing("ruleContent"))?;
+            let content_rule = rule
+                .content
+                .as_ref()
+                .ok_or_else(|| EngineError::rule_missing("ruleContent.content"))?;
+            let content = self.analyzer.get_string(page_html, content_rule)?;
+            let next_url = rule
+                .next_content_url
+                .as_ref()
+                .filter(|r| !r.is_empty())
+                .and_then(|r| self.analyzer.get_string(page_html, r).ok())
+                .unwrap_or_default();
+            (content, next_url)
+        };
 
-            // Extract content from this page
-            let page_content = self.analyzer.get_string(&page_html, content_rule)?;
+        let content = match self.content_source_regex() {
+            Some(pattern) => self.pick_content_block(page_html, pattern).unwrap_or(content),
+            None => content,
+        };
+        let next_url = next_url.trim();
+        Ok((content, (!next_url.is_empty()).then(|| next_url.to_string())))
+    }
 
-            if !page_content.is_empty() {
-                if page_num > 0 {
-                    full_content.push_str("\n\n"); // Page separator
-                }
-                full_content.push_str(&page_content);
-            }
+    /// The first of the content rule's matches that matches sourceRegex
+    fn pick_content_block(&self, page_html: &str, pattern: &str) -> Option<String> {
+        let re = regex::Regex::new(pattern)
+            .map_err(|e| tracing::warn!("Invalid sourceRegex {}: {}", pattern, e))
+            .ok()?;
+        let rule = self.source.rule_content.as_ref()?.content.as_deref()?;
+        let blocks = self.analyzer.get_list(page_html, rule).ok()?;
+        tracing::debug!("Choosing among {} content blocks with sourceRegex {}", blocks.len(), pattern);
+        blocks.into_iter().find(|block| re.is_match(block))
+    }
 
-            // Check for next page
-            if let Some(next_url_rule) = &rule.next_content_url {
-                if !next_url_rule.is_empty() {
-                    if let Ok(next_url) = self.analyzer.get_string(&page_html, next_url_rule) {
-                        let next_url = next_url.trim();
-                        if !next_url.is_empty() && next_url != current_url {
-                            current_url = self.http.absolute_url(next_url);
-                            tracing::debug!(
-                                "Following nextContentUrl to page {}: {}",
-                                page_num + 2,
-                                current_url
-                            );
-                            continue;
-                        }
-                    }
-                }
-            }
-            break; // No more pages
+    /// The content rule's webJs
+    fn content_web_js(&self) -> Option<&str> {
+        match &self.transformed {
+            Some(transformed) => transformed.content_rules.web_js.as_deref(),
+            None => self.source.rule_content.as_ref()?.web_js.as_deref(),
         }
+        .filter(|js| !js.trim().is_empty())
+    }
 
-        // Apply smart filtering for common artifacts (pagination, loading text)
-        let smart_cleaned = self.smart_filter_content(&full_content);
-
-        // Apply replaceRegex if configured
-        let final_content = if let Some(replace_regex) = &rule.replace_regex {
-            self.apply_replace_regex(&smart_cleaned, replace_regex)
-        } else {
-            smart_cleaned
-        };
-
-        Ok(final_content)
+    /// The content rule's sourceRegex
+    fn content_source_regex(&self) -> Option<&str> {
+        match &self.transformed {
+            Some(transformed) => transformed.content_rules.source_regex.as_deref(),
+            None => self.source.rule_content.as_ref()?.source_regex.as_deref(),
+        }
+        .filter(|pattern| !pattern.trim().is_empty())
     }
 
-    /// Smart filter to remove common pollution (pagination info, 'loading', 'next page' prompts)
-    fn smart_filter_content(&self, content: &str) -> String {
-        use regex::Regex;
+    /// Apply the source's replaceRegex
+    ///
+    /// Common pagination artifacts are stripped afterwards by the content
+    /// filters of the service layer, which users can configure per source.
+    fn clean_content(&self, content: &str) -> String {
         let mut result = content.to_string();
 
-        // Common patterns to strip
-        let patterns = [
-            r"（本章未完，请点击下一页继续阅读）",
-            r"\(第\d+/\d+页\)",
-            r"\(第\d+页\)",
-            r"请点击下一页继续阅读",
-            r"本章未完，点击下一页继续阅读",
-            r"加载中...",
-            r"-->>",
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                result = re.replace_all(&result, "").to_string();
+        if let Some(transformed) = &self.transformed {
+            for (pattern, replacement) in &transformed.content_rules.replace_regex {
+                if let Ok(re) = regex::Regex::new(pattern) {
+                    result = re.replace_all(&result, replacement.as_str()).to_string();
+                }
             }
+        } else if let Some(replace_regex) = self
+            .source
+            .rule_content
+            .as_ref()
+            .and_then(|r| r.replace_regex.as_ref())
+        {
+            result = self.apply_replace_regex(&result, replace_regex);
         }
-
         result
     }
 
@@ -945,10 +1764,8 @@ impl BookSourceEngine {
             cover_url: self
                 .get_rule_value(element, &rule.cover_url)
                 .ok()
-                .map(|u| self.http.absolute_url(&u)),
-            book_url: self
-                .http
-                .absolute_url(&self.get_rule_value(element, &rule.book_url)?),
+                .map(|u| self.absolute_url(&u)),
+            book_url: self.absolute_url(&self.get_rule_value(element, &rule.book_url)?),
             kind: self.get_rule_value(element, &rule.kind).ok(),
             last_chapter: None,
             word_count: self.get_rule_value(element, &rule.word_count).ok(),
@@ -956,7 +1773,7 @@ impl BookSourceEngine {
             toc_url: self
                 .get_rule_value(element, &rule.toc_url)
                 .ok()
-                .map(|u| self.http.absolute_url(&u)),
+                .map(|u| self.absolute_url(&u)),
         })
     }
 
@@ -970,10 +1787,8 @@ impl BookSourceEngine {
             cover_url: self
                 .get_rule_value(element, &rule.cover_url)
                 .ok()
-                .map(|u| self.http.absolute_url(&u)),
-            book_url: self
-                .http
-                .absolute_url(&self.get_rule_value(element, &rule.book_url)?),
+                .map(|u| self.absolute_url(&u)),
+            book_url: self.absolute_url(&self.get_rule_value(element, &rule.book_url)?),
             kind: self.get_rule_value(element, &rule.kind).ok(),
             last_chapter: self.get_rule_value(element, &rule.last_chapter).ok(),
             word_count: self.get_rule_value(element, &rule.word_count).ok(),
@@ -988,24 +1803,21 @@ impl BookSourceEngine {
         // For chapter URL, we need to process templates with baseUrl and element data
         let chapter_url_raw = self.get_rule_value(element, &rule.chapter_url)?;
 
-        // Replace baseUrl in the URL template
-        let chapter_url_processed = chapter_url_raw.replace("{{baseUrl}}", base_url);
-
-        // If URL contains more template expressions, process them with the element as context
-        let chapter_url = if chapter_url_processed.contains("{{") {
-            // Use process_templates with baseUrl as a variable
+        // Templates inside `,{options}` are evaluated per JSON value so the
+        // options (e.g. per-request headers) survive into the RequestConfig
+        let chapter_url = if chapter_url_raw.contains("{{") {
+            let base_url = split_url_options(base_url).map_or(base_url, |(url, _)| url);
             let mut vars = HashMap::new();
             vars.insert("baseUrl".to_string(), base_url.to_string());
             vars.insert("result".to_string(), element.to_string());
-            self.analyzer
-                .process_templates(&chapter_url_processed, &vars)
+            self.analyzer.process_url_templates(&chapter_url_raw, &vars)
         } else {
-            chapter_url_processed
+            chapter_url_raw
         };
 
         Ok(Chapter {
             title,
-            url: self.http.absolute_url(&chapter_url),
+            url: self.absolute_url(&chapter_url),
             is_volume: self
                 .get_rule_value(element, &rule.is_volume)
                 .map(|v| v == "true" || v == "1")
@@ -1022,6 +1834,7 @@ impl BookSourceEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::test_server::{headers, request_path, spawn_server};
 
     #[test]
     fn test_book_source_parse() {
@@ -1036,5 +1849,734 @@ mod tests {
         assert_eq!(source.search_url.unwrap(), "/search?q={{key}}&p={{page}}");
     }
 
+    fn create_test_kv() -> Arc<KvStore> {
+        let fs = crate::storage::FileStorage::new("/tmp/reader_tests_bs");
+        Arc::new(KvStore::new(fs, "test_kv_bs.json"))
+    }
+
+    #[test]
+    fn test_parse_explore_kinds_json() {
+        let text = r#"[{"title":"玄幻","url":"/cat/1/{{page}}","style":{"layout_flexBasisPercent":0.25}},{"title":"分类"}]"#;
+        let kinds = parse_explore_kinds(text);
+        assert_eq!(kinds.len(), 2);
+        assert_eq!(kinds[0].title, "玄幻");
+        assert_eq!(kinds[0].url, "/cat/1/{{page}}");
+        assert_eq!(kinds[1].url, "");
+    }
+
+    #[test]
+    fn test_parse_explore_kinds_lines() {
+        let text = "玄幻::/cat/1/{{page}}\n都市::/cat/2/{{page}}&&历史::/cat/3/{{page}}\n\n排行";
+        let kinds = parse_explore_kinds(text);
+        assert_eq!(kinds.len(), 4);
+        assert_eq!(kinds[1].title, "都市");
+        assert_eq!(kinds[2].url, "/cat/3/{{page}}");
+        assert_eq!(kinds[3].title, "排行");
+        assert_eq!(kinds[3].url, "");
+    }
+
+    #[test]
+    fn test_explore_kinds_js() {
+        let json = r#"{
+            "bookSourceUrl": "https://example.com",
+            "bookSourceName": "Stub Source",
+            "exploreUrl": "<js>JSON.stringify([{title:'男频',url:'/m/{{page}}'},{title:'女频',url:'/f/{{page}}'}])</js>"
+        }"#;
+        let source: BookSource = serde_json::from_str(json).unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+
+        let kinds = engine.explore_kinds().unwrap();
+        assert_eq!(kinds.len(), 2);
+        assert_eq!(kinds[1].title, "女频");
+        assert_eq!(kinds[1].url, "/f/{{page}}");
+    }
+
+    /// Serve `pages` TOC pages, recording when each request arrives
+    fn spawn_toc_server(pages: usize) -> (String, std::sync::mpsc::Receiver<std::time::Instant>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let base = spawn_server(move |head, _| {
+            let _ = tx.send(std::time::Instant::now());
+            let page: usize = request_path(head).rsplit('/').next().and_then(|n| n.parse().ok()).unwrap_or(1);
+            let next = if page < pages {
+                format!(r#"<a id="next" href="/toc/{}">下一页</a>"#, page + 1)
+            } else {
+                String::new()
+            };
+            let body = format!(
+                r#"<html><body><ul><li><a href="/c/{0}">第{0}章</a></li></ul>{1}</body></html>"#,
+                page, next
+            );
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        });
+        (base, rx)
+    }
+
+    /// Serve fixed pages by path, recording every requested path
+    fn spawn_fixture_server(
+        pages: Vec<(&'static str, String)>,
+    ) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requested.clone();
+        let base = serve_pages(pages, move |path, _| log.lock().unwrap().push(path));
+        (base, requested)
+    }
+
+    type RecordedRequest = (String, HashMap<String, String>);
+
+    /// Serve fixed pages by path, recording each path with its (lowercased) request headers
+    fn spawn_header_server(
+        pages: Vec<(&'static str, String)>,
+    ) -> (String, Arc<std::sync::Mutex<Vec<RecordedRequest>>>) {
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requested.clone();
+        let base = serve_pages(pages, move |path, headers| log.lock().unwrap().push((path, headers)));
+        (base, requested)
+    }
+
+    fn serve_pages<F>(pages: Vec<(&'static str, String)>, record: F) -> String
+    where
+        F: Fn(String, HashMap<String, String>) + Send + 'static,
+    {
+        spawn_server(move |head, _| {
+            let path = request_path(head).to_string();
+            let (status, body) = match pages.iter().find(|(p, _)| *p == path) {
+                Some((_, body)) => (200, body.clone()),
+                None => (404, String::new()),
+            };
+            record(path, headers(head));
+            (status, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        })
+    }
+
+    fn toc_page(chapters: &[u32], next: Option<&str>) -> String {
+        let items: String = chapters
+            .iter()
+            .map(|n| format!(r#"<li><a href="/c/{0}">第{0}章</a></li>"#, n))
+            .collect();
+        let next = next
+            .map(|href| format!(r#"<a id="next" href="{}">下一页</a>"#, href))
+            .unwrap_or_default();
+        format!("<html><body><ul>{}</ul>{}</body></html>", items, next)
+    }
+
+    fn toc_engine(base: &str) -> BookSourceEngine {
+        let json = format!(
+            r#"{{
+                "bookSourceUrl": "{0}",
+                "bookSourceName": "Toc Source",
+                "ruleToc": {{
+                    "chapterList": "@css:ul li a",
+                    "chapterName": "@css:a@text",
+                    "chapterUrl": "@css:a@href",
+                    "nextTocUrl": "@css:#next@href"
+                }}
+            }}"#,
+            base
+        );
+        let source: BookSource = serde_json::from_str(&json).unwrap();
+        BookSourceEngine::new(source, create_test_kv()).unwrap()
+    }
+
+    #[test]
+    fn test_toc_relative_next_url() {
+        let (base, requested) = spawn_fixture_server(vec![
+            ("/book/1/index.html", toc_page(&[1, 2], Some("index_2.html"))),
+            ("/book/1/index_2.html", toc_page(&[3], None)),
+        ]);
+        let engine = toc_engine(&base);
+
+        let chapters = engine.get_chapters(&format!("{}/book/1/index.html", base)).unwrap();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[2].title, "第3章");
+        assert_eq!(chapters[2].url, format!("{}/c/3", base));
+        assert_eq!(*requested.lock().unwrap(), vec!["/book/1/index.html", "/book/1/index_2.html"]);
+    }
+
+    #[test]
+    fn test_toc_cycle_and_chapter_cap() {
+        let (base, requested) = spawn_fixture_server(vec![
+            ("/toc/a", toc_page(&[1, 2], Some("/toc/b"))),
+            // Links back to the first page, differing only by fragment
+            ("/toc/b", toc_page(&[3, 4], Some("/toc/a#top"))),
+        ]);
+        let mut engine = toc_engine(&base);
+
+        let chapters = engine.get_chapters(&format!("{}/toc/a", base)).unwrap();
+        assert_eq!(chapters.len(), 4);
+        assert_eq!(requested.lock().unwrap().len(), 2);
+
+        requested.lock().unwrap().clear();
+        engine.set_max_toc_chapters(3);
+        let chapters = engine.get_chapters(&format!("{}/toc/a", base)).unwrap();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[2].title, "第3章");
+        assert_eq!(requested.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_chapter_url_headers_survive_pagination() {
+        let (base, requested) = spawn_header_server(vec![
+            ("/toc/1", toc_page(&[1], Some("/toc/2"))),
+            ("/toc/2", toc_page(&[2], None)),
+            ("/c/1", r#"<div id="content">第一页</div><a id="next" href="1_2">下一页</a>"#.to_string()),
+            ("/c/1_2", r#"<div id="content">第二页</div>"#.to_string()),
+        ]);
+        // The templates sit inside JSON string values of the appended options
+        let chapter_url = format!(
+            r#"@css:a@href
+@js:result + ',{{"headers":{{"Referer":"{{{{baseUrl}}}}","X-Token":"{{{{java.getCookie(\'{}\', \'token\')}}}}"}}}}'"#,
+            base
+        );
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Header Source",
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": chapter_url,
+                "nextTocUrl": "@css:#next@href"
+            },
+            "ruleContent": {
+                "content": "@css:#content@text",
+                "nextContentUrl": "@css:#next@href"
+            }
+        }))
+        .unwrap();
+        let mut engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+        engine.transformed = None;
+        // Quotes in the evaluated value must not break the options JSON
+        engine
+            .analyzer
+            .native_api()
+            .execute(
+                &crate::engine::preprocessor::NativeApi::SetCookie,
+                &[base.clone(), r#"token=a"b\c"#.to_string()],
+                &Default::default(),
+            )
+            .unwrap();
+
+        let chapters = engine.get_chapters(&format!("{}/toc/1", base)).unwrap();
+        assert_eq!(chapters.len(), 2);
+        let (url, options) = split_url_options(&chapters[0].url).unwrap();
+        assert_eq!(url, format!("{}/c/1", base));
+        assert_eq!(options["headers"]["X-Token"], r#"a"b\c"#);
+        assert_eq!(options["headers"]["Referer"], format!("{}/toc/1", base));
+
+        requested.lock().unwrap().clear();
+        let content = engine.get_content(&chapters[0].url).unwrap();
+        assert!(content.contains("第一页") && content.contains("第二页"));
+
+        let requests = requested.lock().unwrap();
+        let paths: Vec<_> = requests.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["/c/1", "/c/1_2"]);
+        for (_, headers) in requests.iter() {
+            assert_eq!(headers["x-token"], r#"a"b\c"#);
+            assert_eq!(headers["referer"], format!("{}/toc/1", base));
+        }
+        drop(requests);
+
+        // TOC pages reached via nextTocUrl keep the first page's headers too
+        requested.lock().unwrap().clear();
+        engine
+            .get_chapters(&format!(r#"{}/toc/1,{{"headers":{{"X-Token":"toc"}}}}"#, base))
+            .unwrap();
+        let requests = requested.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|(_, headers)| headers["x-token"] == "toc"));
+    }
+
+    #[test]
+    fn test_chapter_and_book_context() {
+        let (base, requested) = spawn_fixture_server(vec![
+            ("/c/5", r#"<div id="content">正文</div>"#.to_string()),
+            ("/s/Dune", r#"<a class="b" href="/b/1">Dune</a>"#.to_string()),
+            ("/js/Herbert", r#"<a class="b" href="/b/2">Dune</a>"#.to_string()),
+        ]);
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Context Source",
+            "searchUrl": "/s/{{book.name}}",
+            "ruleSearch": {
+                "bookList": "@css:a.b",
+                "name": "@css:a@text",
+                "bookUrl": "@css:a@href"
+            },
+            "ruleContent": {
+                "content": "@css:#content@text<js>result + '|' + book.author</js>"
+            }
+        }))
+        .unwrap();
+        let mut engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+        engine.transformed = None;
+        engine.set_book(BookContext {
+            name: "Dune".to_string(),
+            author: "Herbert".to_string(),
+            book_url: format!("{}/b/1", base),
+            ..Default::default()
+        });
+        engine.set_chapter(ChapterContext {
+            title: "第五章".to_string(),
+            url: format!("{}/c/{{{{chapter.index+1}}}}", base),
+            index: 4,
+        });
+
+        let content = engine.get_content(&format!("{}/c/{{{{chapter.index+1}}}}", base)).unwrap();
+        assert_eq!(content, "正文|Herbert");
+
+        // Native template
+        let books = engine.search("ignored", 1).unwrap();
+        assert_eq!(books[0].book_url, format!("{}/b/1", base));
+
+        // JS fallback
+        engine.source.search_url = Some("@js:baseUrl + '/js/' + book.author".to_string());
+        let books = engine.search("ignored", 1).unwrap();
+        assert_eq!(books[0].book_url, format!("{}/b/2", base));
+
+        assert_eq!(*requested.lock().unwrap(), vec!["/c/5", "/s/Dune", "/js/Herbert"]);
+    }
+
+    #[test]
+    fn test_pure_json_source_with_bare_field_rules() {
+        let (base, _) = spawn_fixture_server(vec![
+            (
+                "/search?q=dou",
+                r#"{"code":0,"data":{"list":[
+                    {"name":"斗破苍穹","author":"天蚕土豆","info":{"cover":"/c/1.jpg","intro":"简介"},"url":"/book/1"},
+                    {"name":"斗罗大陆","author":"唐家三少","info":{"cover":"/c/2.jpg","intro":""},"url":"/book/2"}
+                ]}}"#
+                    .to_string(),
+            ),
+            (
+                "/toc/1",
+                r#"{"data":{"chapters":[
+                    {"name":"第一章","path":"/chapter/1","meta":{"vip":false,"words":3000}},
+                    {"name":"第二章","path":"/chapter/2","meta":{"words":2800,"vip":true}}
+                ]}}"#
+                    .to_string(),
+            ),
+            ("/chapter/1", r#"{"data":{"content":"第一章正文"}}"#.to_string()),
+            ("/versions", r#"[{"v":"1.0"},{"v":"2.0"}]"#.to_string()),
+        ]);
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "JSON API",
+            "searchUrl": "/search?q={{key}}",
+            "ruleSearch": {
+                "bookList": "data.list",
+                "name": "name",
+                "author": "author",
+                "coverUrl": "info.cover",
+                "intro": "info.intro",
+                "bookUrl": "url"
+            },
+            "ruleToc": {
+                "chapterList": "$.data.chapters",
+                "chapterName": "name",
+                "chapterUrl": "path"
+            },
+            "ruleContent": { "content": "data.content" }
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+
+        let books = engine.search("dou", 1).unwrap();
+        let names: Vec<_> = books.iter().map(|b| (b.name.as_str(), b.author.as_str())).collect();
+        assert_eq!(names, vec![("斗破苍穹", "天蚕土豆"), ("斗罗大陆", "唐家三少")]);
+        assert_eq!(books[0].book_url, format!("{}/book/1", base));
+        let cover = format!("{}/c/1.jpg", base);
+        assert_eq!(books[0].cover_url.as_deref(), Some(cover.as_str()));
+
+        let chapters = engine.get_chapters(&format!("{}/toc/1", base)).unwrap();
+        let titles: Vec<_> = chapters.iter().map(|c| (c.title.as_str(), c.url.as_str())).collect();
+        assert_eq!(
+            titles,
+            vec![
+                ("第一章", format!("{}/chapter/1", base).as_str()),
+                ("第二章", format!("{}/chapter/2", base).as_str())
+            ]
+        );
 
+        let content = engine.get_content(&format!("{}/chapter/1", base)).unwrap();
+        assert_eq!(content, "第一章正文");
+
+        // Elements are compact JSON with a stable key order
+        let toc = r#"{"data":{"chapters":[{"name":"a","meta":{"words":1,"vip":true}}]}}"#;
+        let elements = engine.analyzer.get_elements(toc, "data.chapters").unwrap();
+        assert_eq!(elements, vec![r#"{"meta":{"vip":true,"words":1},"name":"a"}"#]);
+        let versions = r#"[{"v":"1.0"},{"v":"2.0"}]"#;
+        assert_eq!(engine.analyzer.get_string(versions, "[1].v").unwrap(), "2.0");
+    }
+
+    #[test]
+    fn test_page_key() {
+        assert_eq!(page_key("https://a.com/toc#list"), "https://a.com/toc");
+        assert_ne!(
+            page_key(r#"https://a.com/toc,{"method":"POST","body":"p=1"}"#),
+            page_key(r#"https://a.com/toc,{"method":"POST","body":"p=2"}"#)
+        );
+    }
+
+    #[test]
+    fn test_entity_encoded_titles() {
+        let (base, _) = spawn_fixture_server(vec![
+            (
+                "/list",
+                r#"{"books":[{"name":"&#x96EA;&#20013;悍刀行","author":"烽火戏诸侯","url":"/b/1","intro":"A&amp;B &#x;"}]}"#
+                    .to_string(),
+            ),
+            ("/toc", r#"<ul><li><a href="/c/1">&amp;#31532;&amp;#x4E00;章</a></li></ul>"#.to_string()),
+        ]);
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Entity Source",
+            "ruleExplore": {
+                "bookList": "$.books[*]",
+                "name": "$.name",
+                "author": "$.author",
+                "intro": "$.intro",
+                "bookUrl": "$.url"
+            }
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+        let books = engine.explore(&format!("{}/list", base), 1).unwrap();
+        assert_eq!(books[0].name, "雪中悍刀行");
+        // Named entities and malformed references are left alone
+        assert_eq!(books[0].intro.as_deref(), Some("A&amp;B &#x;"));
+
+        let chapters = toc_engine(&base).get_chapters(&format!("{}/toc", base)).unwrap();
+        assert_eq!(chapters[0].title, "第一章");
+    }
+
+    #[test]
+    fn test_header_js_reads_kv_token() {
+        let (base, requested) = spawn_header_server(vec![("/list", r#"{"books":[]}"#.to_string())]);
+        let kv = create_test_kv();
+        kv.set_cache("header_js_token", "t-1", 0);
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Header JS",
+            "header": r#"@js:JSON.stringify({"X-Token": java.get("header_js_token"), "X-Client": "reader"})"#,
+            "ruleExplore": { "bookList": "$.books[*]", "name": "$.name" }
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, kv.clone()).unwrap();
+        assert!(engine.dynamic_headers);
+        let token = engine.http.default_headers().get("X-Token");
+        assert_eq!(token.map(String::as_str), Some("t-1"));
+
+        // The token is read again for each request
+        kv.set_cache("header_js_token", "t-2", 0);
+        engine.explore(&format!("{}/list", base), 1).unwrap();
+        let (_, headers) = requested.lock().unwrap().pop().unwrap();
+        assert_eq!(headers["x-token"], "t-2");
+        assert_eq!(headers["x-client"], "reader");
+
+        // Output that is not a JSON object leaves the source without headers
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Broken Header JS",
+            "header": "<js>'not json'</js>"
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, kv).unwrap();
+        assert!(!engine.dynamic_headers);
+        assert!(engine.http.default_headers().is_empty());
+    }
+
+    fn content_engine(base: &str) -> BookSourceEngine {
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Paged Content",
+            "ruleContent": {
+                "content": "@css:#content@text",
+                "nextContentUrl": "@css:#next@href"
+            }
+        }))
+        .unwrap();
+        BookSourceEngine::new(source, create_test_kv()).unwrap()
+    }
+
+    #[test]
+    fn test_forks_keep_put_variables_apart() {
+        let engine = content_engine("https://fork.example.com");
+        let rule = "$.token@put:{token:$.token}\n<js>(function () { return book.name + '|' + result + '|@get:token'; })()</js>";
+        std::thread::scope(|s| {
+            for name in ["甲", "乙"] {
+                let engine = &engine;
+                s.spawn(move || {
+                    let fork = engine.fork();
+                    fork.set_book(BookContext {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                    let json = format!(r#"{{"token":"{}"}}"#, name);
+                    for _ in 0..50 {
+                        let value = fork.analyzer.get_string(&json, rule).unwrap();
+                        assert_eq!(value, format!("{0}|{0}|{0}", name));
+                    }
+                });
+            }
+        });
+        // Forks share the heavy parts and leave nothing behind in the engine
+        let fork = engine.fork();
+        assert!(Arc::ptr_eq(&engine.http, &fork.http));
+        assert!(engine.analyzer.get_variable("token").is_none());
+        assert!(engine.analyzer.book().is_none());
+    }
+
+    #[test]
+    fn test_content_pagination_stops_at_next_chapter() {
+        let (base, requested) = spawn_fixture_server(vec![
+            ("/c/1", r#"<div id="content">第一章上半</div><a id="next" href="1_2">下一页</a>"#.to_string()),
+            ("/c/1_2", r#"<div id="content">第一章下半</div><a id="next" href="2">下一页</a>"#.to_string()),
+            ("/c/2", r#"<div id="content">第二章</div><a id="next" href="2#top">下一页</a>"#.to_string()),
+        ]);
+        let mut engine = content_engine(&base);
+        engine.set_chapter_urls(vec![format!("{}/c/1", base), "/c/2".to_string()]);
+        let content = engine.get_content(&format!("{}/c/1", base)).unwrap();
+        assert_eq!(content, "第一章上半\n\n第一章下半");
+        assert_eq!(*requested.lock().unwrap(), vec!["/c/1", "/c/1_2"]);
+
+        // A relative link back to the page itself is not followed
+        requested.lock().unwrap().clear();
+        assert_eq!(engine.get_content(&format!("{}/c/2", base)).unwrap(), "第二章");
+        assert_eq!(*requested.lock().unwrap(), vec!["/c/2"]);
+
+        // Without a TOC only the page cap applies
+        requested.lock().unwrap().clear();
+        engine.set_chapter_urls(Vec::new());
+        engine.set_max_content_pages(2);
+        let content = engine.get_content(&format!("{}/c/1", base)).unwrap();
+        assert_eq!(content, "第一章上半\n\n第一章下半");
+        assert_eq!(*requested.lock().unwrap(), vec!["/c/1", "/c/1_2"]);
+    }
+
+    #[test]
+    fn test_content_pagination_stops_at_duplicate_page() {
+        let text = "他推开门，院子里的雪已经积了半尺深。远处传来几声犬吠，很快又归于寂静。";
+        let (base, requested) = spawn_fixture_server(vec![
+            ("/c/1", format!(r#"<div id="content">{}</div><a id="next" href="/c/1_2">下一页</a>"#, text)),
+            // Same text under a new URL, with only the page number changed
+            (
+                "/c/1_2",
+                format!(r#"<div id="content">{}(2)</div><a id="next" href="/c/1_3">下一页</a>"#, text),
+            ),
+            ("/c/1_3", r#"<div id="content">不应抓取</div>"#.to_string()),
+        ]);
+        let engine = content_engine(&base);
+        let content = engine.get_content(&format!("{}/c/1", base)).unwrap();
+        assert_eq!(content, text);
+        assert_eq!(*requested.lock().unwrap(), vec!["/c/1", "/c/1_2"]);
+    }
+
+    #[test]
+    fn test_image_source_lazy_src() {
+        let (base, requested) = spawn_fixture_server(vec![
+            (
+                "/comic/1",
+                r#"<div class="comic">
+                    <img src="/static/loading.gif" data-original="/img/1/001.jpg">
+                    <img src="data:image/gif;base64,R0lGOD" data-src="https://cdn.example.com/1/002.jpg">
+                    <img src="/img/1/003.jpg">
+                </div><a id="next" href="/comic/1_2">下一页</a>"#
+                    .to_string(),
+            ),
+            (
+                "/comic/1_2",
+                r#"<div class="comic"><img src="/img/1/004.jpg"><img src="/img/1/003.jpg"></div>"#.to_string(),
+            ),
+        ]);
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Comic",
+            "bookSourceType": SOURCE_TYPE_IMAGE,
+            "ruleContent": {
+                "content": "@css:.comic img@src",
+                "nextContentUrl": "@css:#next@href"
+            }
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+        assert!(engine.is_image_source());
+
+        let content = engine.get_content(&format!("{}/comic/1", base)).unwrap();
+        let images = ImageContent::from_content(&content).unwrap().images;
+        assert_eq!(
+            images,
+            vec![
+                format!("{}/img/1/001.jpg", base),
+                "https://cdn.example.com/1/002.jpg".to_string(),
+                format!("{}/img/1/003.jpg", base),
+                format!("{}/img/1/004.jpg", base),
+            ]
+        );
+        assert_eq!(*requested.lock().unwrap(), vec!["/comic/1", "/comic/1_2"]);
+        assert!(ImageContent::from_content("第一章").is_none());
+    }
+
+    #[test]
+    fn test_content_overlap() {
+        let page = "第一段正文内容很长很长。第二段正文内容也很长。";
+        assert_eq!(content_overlap(page, page), 1.0);
+        assert_eq!(content_overlap(page, &format!(" {} ", page.replace('。', "。\n"))), 1.0);
+        assert!(content_overlap(page, "完全不同的另一页内容，与上一页没有重复的部分。") < 0.1);
+        assert_eq!(content_overlap("第一页", "第二页"), 0.0);
+        assert_eq!(content_overlap(page, ""), 0.0);
+    }
+
+    #[test]
+    fn test_compiled_rules_match_legacy() {
+        let (base, _) = spawn_fixture_server(vec![
+            (
+                "/book",
+                r#"<div class="info" data-bid="42"><h1>旧书名</h1><p class="author">某人</p><a class="toc" href="/toc">目录</a></div>"#
+                    .to_string(),
+            ),
+            ("/toc?bid=42", toc_page(&[1, 2], Some("toc2"))),
+            ("/toc2.html", toc_page(&[3], None)),
+            ("/c/1", r#"<div class="txt">广告</div><div class="txt">第1章 正文</div>"#.to_string()),
+        ]);
+        // Every rule field is set so the source converts to BookSourceFull and compiles
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": "Compiled Source",
+            "bookSourceGroup": "",
+            "bookSourceType": 0,
+            "searchUrl": "",
+            "exploreUrl": "",
+            "enabledCloudflareBypass": false,
+            "ruleBookInfo": {
+                "init": r#"@css:div.info@html<js>result.replace("旧书名", "新书名")</js>@put:{"bid":"@css:div.info@data-bid"}"#,
+                "name": "@css:h1@text",
+                "author": "@css:.author@text",
+                "intro": "", "kind": "", "wordCount": "", "coverUrl": "", "lastChapter": "", "updateTime": "",
+                "tocUrl": "@css:a.toc@href<js>result + '?bid=@get:bid'</js>"
+            },
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href",
+                "nextTocUrl": r#"@css:#next@href<js>result && (result + ".html")</js>"#
+            },
+            "ruleContent": {
+                "content": "@css:div.txt@text",
+                "nextContentUrl": "",
+                "webJs": "",
+                "sourceRegex": "第.章",
+                "replaceRegex": ""
+            }
+        }))
+        .unwrap();
+        let compiled = BookSourceEngine::new(source.clone(), create_test_kv()).unwrap();
+        assert!(compiled.transformed.is_some());
+        let mut legacy = BookSourceEngine::new(source, create_test_kv()).unwrap();
+        legacy.transformed = None;
+
+        let results: Vec<_> = [&compiled, &legacy]
+            .into_iter()
+            .map(|engine| {
+                let info = engine.parse_book_info(&format!("{}/book", base)).unwrap();
+                let chapters = engine.get_chapters(info.toc_url.as_deref().unwrap()).unwrap();
+                let content = engine.get_content(&format!("{}/c/1", base)).unwrap();
+                serde_json::json!({ "info": info, "chapters": chapters, "content": content })
+            })
+            .collect();
+        assert_eq!(results[0], results[1]);
+
+        let result = &results[0];
+        assert_eq!(result["info"]["name"], "新书名");
+        assert_eq!(result["info"]["author"], "某人");
+        assert_eq!(result["info"]["tocUrl"], format!("{}/toc?bid=42", base));
+        let titles: Vec<&str> = result["chapters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["第1章", "第2章", "第3章"]);
+        assert_eq!(result["content"], "第1章 正文");
+    }
+
+    #[test]
+    fn test_concurrent_rate_toc_pagination() {
+        let (base, rx) = spawn_toc_server(3);
+        let json = format!(
+            r#"{{
+                "bookSourceUrl": "{0}",
+                "bookSourceName": "Rate Source",
+                "concurrentRate": "300",
+                "ruleToc": {{
+                    "chapterList": "@css:ul li a",
+                    "chapterName": "@css:a@text",
+                    "chapterUrl": "@css:a@href",
+                    "nextTocUrl": "@css:#next@href"
+                }}
+            }}"#,
+            base
+        );
+        let source: BookSource = serde_json::from_str(&json).unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+
+        let chapters = engine.get_chapters(&format!("{}/toc/1", base)).unwrap();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[2].title, "第3章");
+
+        let times: Vec<_> = rx.try_iter().collect();
+        assert_eq!(times.len(), 3);
+        for pair in times.windows(2) {
+            // 允许少量调度误差
+            assert!(pair[1] - pair[0] >= std::time::Duration::from_millis(280));
+        }
+    }
+
+    #[test]
+    fn test_links_resolve_against_redirected_page() {
+        let (target, _) = spawn_fixture_server(vec![
+            (
+                "/novel/1/",
+                r#"<h1>书名</h1><a class="toc" href="list.html">目录</a>"#.to_string(),
+            ),
+            (
+                "/novel/1/list.html",
+                r#"<ul><li><a href="1.html">第1章</a></li><li><a href="/novel/1/2.html">第2章</a></li></ul>"#
+                    .to_string(),
+            ),
+            ("/novel/1/1.html", r#"<div id="content">正文</div>"#.to_string()),
+        ]);
+        let target = target.replace("127.0.0.1", "localhost");
+
+        // The source's own host only redirects to the canonical page on the other host
+        let location = format!("{}/novel/1/", target);
+        let origin = spawn_server(move |_, _| (302, vec![("Location", location.clone())], ""));
+
+        let source: BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": origin,
+            "bookSourceName": "Redirect Source",
+            "ruleBookInfo": { "name": "@css:h1@text", "tocUrl": "@css:a.toc@href" },
+            "ruleToc": { "chapterList": "@css:li a", "chapterName": "@css:a@text", "chapterUrl": "@css:a@href" },
+            "ruleContent": { "content": "@css:#content@text<js>result + '|' + baseUrl</js>" }
+        }))
+        .unwrap();
+        let engine = BookSourceEngine::new(source, create_test_kv()).unwrap();
+
+        let info = engine.get_book_info(&format!("{}/book/1", origin)).unwrap();
+        assert_eq!(info.name, "书名");
+        assert_eq!(info.book_url, format!("{}/book/1", origin));
+        let toc_url = info.toc_url.unwrap();
+        assert_eq!(toc_url, format!("{}/novel/1/list.html", target));
+
+        let chapters = engine.get_chapters(&toc_url).unwrap();
+        let urls: Vec<_> = chapters.iter().map(|c| c.url.as_str()).collect();
+        let expected = [
+            format!("{}/novel/1/1.html", target),
+            format!("{}/novel/1/2.html", target),
+        ];
+        assert_eq!(urls, expected);
+
+        let content = engine.get_content(&chapters[0].url).unwrap();
+        assert_eq!(content, format!("正文|{}", expected[0]));
+        // Request URLs built afterwards see the source's base URL again
+        assert_eq!(engine.analyzer.page_url(), origin);
+    }
 }
@@ -6,9 +6,14 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
+use once_cell::sync::Lazy;
+
 /// Cookie storage: domain -> (cookie_name -> cookie_value)
 pub type CookieStore = Arc<RwLock<HashMap<String, HashMap<String, String>>>>;
 
+/// Process-wide store behind [`CookieManager::shared`]
+static SHARED_STORE: Lazy<CookieStore> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
 /// Cookie manager for handling cookies across requests
 #[derive(Clone)]
 pub struct CookieManager {
@@ -34,11 +39,33 @@ impl CookieManager {
         Self { store }
     }
 
+    /// Cookie manager backed by the process-wide store
+    ///
+    /// Book source engines use this jar, so cookies captured by a login
+    /// are sent with every later request to the same domain.
+    pub fn shared() -> Self {
+        Self::with_store(SHARED_STORE.clone())
+    }
+
     /// Get the underlying store for sharing
     pub fn get_store(&self) -> CookieStore {
         self.store.clone()
     }
 
+    /// Copy of all cookies, for persisting the jar
+    pub fn snapshot(&self) -> HashMap<String, HashMap<String, String>> {
+        self.store.read().map(|store| store.clone()).unwrap_or_default()
+    }
+
+    /// Merge previously persisted cookies into the jar
+    pub fn restore(&self, cookies: HashMap<String, HashMap<String, String>>) {
+        if let Ok(mut store) = self.store.write() {
+            for (domain, values) in cookies {
+                store.entry(domain).or_default().extend(values);
+            }
+        }
+    }
+
     /// Set a cookie value for a domain
     pub fn set_cookie(&self, domain: &str, name: &str, value: &str) {
         if let Ok(mut store) = self.store.write() {
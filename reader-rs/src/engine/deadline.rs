@@ -0,0 +1,155 @@
+//! Request Deadline - Overall time budget of one API request
+//!
+//! An API handler turns its budget into a [`Deadline`] and hands it to the
+//! engine with `BookSourceEngine::set_deadline`. Engine entry points [`enter`]
+//! it on the running thread, so the layers below them (HTTP clients, native
+//! APIs, the JS executor) check the remaining time before starting a request
+//! or evaluation and trim their own timeouts to it.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use super::error::EngineError;
+
+thread_local! {
+    /// Deadline of the request whose engine calls run on this thread
+    static CURRENT_DEADLINE: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+/// Point in time by which a request must be answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// A deadline `budget` from now
+    pub fn after(budget: Duration) -> Self {
+        Self {
+            at: Instant::now() + budget,
+            budget,
+        }
+    }
+
+    /// The whole budget the deadline was created with
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Time left, zero once expired
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// `timeout` shortened to the time left
+    pub fn trim(&self, timeout: Duration) -> Duration {
+        timeout.min(self.remaining())
+    }
+
+    /// Fail with [`EngineError::DeadlineExceeded`] once expired
+    pub fn check(&self) -> Result<(), EngineError> {
+        if self.is_expired() {
+            return Err(self.exceeded());
+        }
+        Ok(())
+    }
+
+    /// The error reported when the budget ran out
+    pub fn exceeded(&self) -> EngineError {
+        EngineError::deadline_exceeded(self.budget)
+    }
+}
+
+/// Deadline of the current thread's request, if any
+pub fn current() -> Option<Deadline> {
+    CURRENT_DEADLINE.with(|d| d.get())
+}
+
+/// Fail with [`EngineError::DeadlineExceeded`] if the current thread's deadline expired
+pub fn check() -> Result<(), EngineError> {
+    current().map_or(Ok(()), |deadline| deadline.check())
+}
+
+/// `timeout` shortened to the time left before the current thread's deadline
+pub fn trim(timeout: Duration) -> Duration {
+    current().map_or(timeout, |deadline| deadline.trim(timeout))
+}
+
+/// Report an error of a call cut short by the current thread's deadline as `DeadlineExceeded`
+///
+/// The original error is kept as the cause; errors raised before the deadline pass through.
+pub fn attribute(err: anyhow::Error) -> anyhow::Error {
+    match current().filter(Deadline::is_expired) {
+        Some(deadline) if !is_exceeded(&err) => err.context(deadline.exceeded()),
+        _ => err,
+    }
+}
+
+/// Whether `err` is, or was caused by, [`EngineError::DeadlineExceeded`]
+pub fn is_exceeded(err: &anyhow::Error) -> bool {
+    let exceeded = |e: Option<&EngineError>| matches!(e, Some(EngineError::DeadlineExceeded { .. }));
+    exceeded(err.downcast_ref()) || err.chain().any(|cause| exceeded(cause.downcast_ref()))
+}
+
+/// Make `deadline` the current thread's deadline until the guard is dropped
+///
+/// `None` keeps the enclosing deadline, so nested engine calls stay bounded by it.
+pub fn enter(deadline: Option<Deadline>) -> DeadlineScope {
+    let previous = current();
+    if deadline.is_some() {
+        CURRENT_DEADLINE.with(|d| d.set(deadline));
+    }
+    DeadlineScope { previous }
+}
+
+/// Guard returned by [`enter`]; restores the enclosing deadline on drop
+pub struct DeadlineScope {
+    previous: Option<Deadline>,
+}
+
+impl Drop for DeadlineScope {
+    fn drop(&mut self) {
+        CURRENT_DEADLINE.with(|d| d.set(self.previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_scope() {
+        assert!(current().is_none());
+        assert_eq!(trim(Duration::from_secs(10)), Duration::from_secs(10));
+
+        let outer = Deadline::after(Duration::from_secs(5));
+        {
+            let _outer = enter(Some(outer));
+            assert!(trim(Duration::from_secs(10)) <= Duration::from_secs(5));
+            {
+                // Engines without a deadline of their own stay bounded by the enclosing one
+                let _inner = enter(None);
+                assert_eq!(current(), Some(outer));
+            }
+            let expired = Deadline::after(Duration::ZERO);
+            {
+                let _inner = enter(Some(expired));
+                assert_eq!(trim(Duration::from_secs(10)), Duration::ZERO);
+                assert!(matches!(check(), Err(EngineError::DeadlineExceeded { budget_ms: 0, .. })));
+                // A request timed out by the trimmed timeout reports the deadline, keeping the cause
+                let err = attribute(anyhow::anyhow!("timed out"));
+                assert!(is_exceeded(&err));
+                assert!(format!("{:#}", err).ends_with("timed out"));
+            }
+            assert_eq!(current(), Some(outer));
+            assert!(check().is_ok());
+            assert!(!is_exceeded(&attribute(anyhow::anyhow!("timed out"))));
+        }
+        assert!(current().is_none());
+    }
+}
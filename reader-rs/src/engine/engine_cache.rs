@@ -0,0 +1,193 @@
+//! Engine Cache - Book source engines reused across requests
+//!
+//! Building a `BookSourceEngine` loads the compiled rules, starts a JS runtime
+//! (running the source's jsLib) and sets up the HTTP client. The cache keeps one
+//! engine per `bookSourceUrl` and hands out forks of it, so requests against the
+//! same source share that work while each keeps its own parse state.
+//!
+//! Entries are keyed by the md5 of the source JSON as well, so an edited source
+//! gets a fresh engine. The number of sources is capped (`ENGINE_CACHE_SIZE`,
+//! default 32; 0 disables the cache) by evicting the least recently used one.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use parking_lot::Mutex;
+
+use super::book_source::{BookSource, BookSourceEngine};
+use crate::storage::kv::KvStore;
+
+/// Default number of sources whose engines are kept
+pub const DEFAULT_ENGINE_CACHE_SIZE: usize = 32;
+
+/// Cache of engines by book source URL
+pub struct EngineCache {
+    kv_store: Arc<KvStore>,
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Map from source URL to the source hash, its engine and its last use
+    entries: HashMap<String, (String, Arc<BookSourceEngine>, u64)>,
+    /// Monotonic use counter
+    tick: u64,
+}
+
+impl EngineCache {
+    pub fn new(kv_store: Arc<KvStore>, capacity: usize) -> Self {
+        Self {
+            kv_store,
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Cache sized by the ENGINE_CACHE_SIZE env var
+    pub fn from_env(kv_store: Arc<KvStore>) -> Self {
+        let capacity = std::env::var("ENGINE_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_ENGINE_CACHE_SIZE);
+        Self::new(kv_store, capacity)
+    }
+
+    /// An engine for `source`, forked from the cached one
+    ///
+    /// The engine is built first when the source is not cached or has changed.
+    /// Building happens outside the lock, since running jsLib may take a while.
+    pub fn engine(&self, source: &BookSource) -> Result<BookSourceEngine> {
+        if self.capacity == 0 {
+            return BookSourceEngine::new(source.clone(), self.kv_store.clone());
+        }
+
+        let hash = format!("{:x}", md5::compute(serde_json::to_string(source)?));
+        if let Some(engine) = self.lookup(&source.book_source_url, &hash) {
+            return Ok(engine.fork());
+        }
+
+        let engine = Arc::new(BookSourceEngine::new(source.clone(), self.kv_store.clone())?);
+        let fork = engine.fork();
+        self.insert(&source.book_source_url, hash, engine);
+        Ok(fork)
+    }
+
+    /// Drop the engine of one source (e.g. after it was deleted or logged out)
+    pub fn invalidate(&self, source_url: &str) {
+        self.state.lock().entries.remove(source_url);
+    }
+
+    /// Drop all engines
+    pub fn clear(&self) {
+        self.state.lock().entries.clear();
+    }
+
+    /// Number of cached engines
+    pub fn len(&self) -> usize {
+        self.state.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lookup(&self, source_url: &str, hash: &str) -> Option<Arc<BookSourceEngine>> {
+        let mut state = self.state.lock();
+        state.tick += 1;
+        let tick = state.tick;
+        match state.entries.get_mut(source_url) {
+            Some((cached, engine, used)) if cached == hash => {
+                *used = tick;
+                Some(engine.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&self, source_url: &str, hash: String, engine: Arc<BookSourceEngine>) {
+        let mut state = self.state.lock();
+        // Evict the least recently used engine if at capacity
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(source_url) {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, _, used))| *used)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(source_url.to_string(), (hash, engine, tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+
+    fn kv_store() -> Arc<KvStore> {
+        let dir = std::env::temp_dir().join(format!("reader_tests_engine_cache_{}", uuid::Uuid::new_v4()));
+        Arc::new(KvStore::new(FileStorage::new(dir), "kv.json"))
+    }
+
+    fn source(url: &str) -> BookSource {
+        serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": url,
+            "bookSourceName": "test",
+            "jsLib": "var counter = 0;"
+        }))
+        .unwrap()
+    }
+
+    fn cached(cache: &EngineCache, url: &str) -> Arc<BookSourceEngine> {
+        cache.state.lock().entries[url].1.clone()
+    }
+
+    #[test]
+    fn test_reuses_engine_until_source_changes() {
+        let cache = EngineCache::new(kv_store(), 4);
+        let a = source("https://a.example");
+
+        cache.engine(&a).unwrap();
+        let first = cached(&cache, &a.book_source_url);
+        cache.engine(&a).unwrap();
+        assert!(Arc::ptr_eq(&first, &cached(&cache, &a.book_source_url)));
+        assert_eq!(cache.len(), 1);
+
+        let mut edited = a.clone();
+        edited.book_source_name = "edited".to_string();
+        cache.engine(&edited).unwrap();
+        assert!(!Arc::ptr_eq(&first, &cached(&cache, &a.book_source_url)));
+        assert_eq!(cache.len(), 1);
+
+        cache.invalidate(&a.book_source_url);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_source() {
+        let cache = EngineCache::new(kv_store(), 2);
+        let (a, b, c) = (
+            source("https://a.example"),
+            source("https://b.example"),
+            source("https://c.example"),
+        );
+
+        cache.engine(&a).unwrap();
+        let kept = cached(&cache, &a.book_source_url);
+        cache.engine(&b).unwrap();
+        cache.engine(&a).unwrap();
+        cache.engine(&c).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.state.lock().entries.contains_key(&b.book_source_url));
+        cache.engine(&a).unwrap();
+        assert!(Arc::ptr_eq(&kept, &cached(&cache, &a.book_source_url)));
+    }
+}
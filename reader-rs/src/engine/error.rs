@@ -34,6 +34,20 @@ pub enum EngineError {
     #[error("JS analysis failed: {0}")]
     JsAnalysis(String),
 
+    #[error("JavaScript of source {source_url} exceeded the {timeout_ms}ms time limit")]
+    JsTimeout { source_url: String, timeout_ms: u64 },
+
+    #[error("JavaScript execution cancelled")]
+    JsCancelled,
+
+    // Request budget errors
+    #[error("Request exceeded its {budget_ms}ms time budget")]
+    DeadlineExceeded {
+        budget_ms: u64,
+        /// Content fetched before the budget ran out
+        partial: Option<PartialContent>,
+    },
+
     // HTTP errors
     #[error("HTTP request failed: {0}")]
     Http(String),
@@ -41,6 +55,9 @@ pub enum EngineError {
     #[error("URL parse error: {0}")]
     UrlParse(String),
 
+    #[error("Source {source_url} is cooling down after being throttled, retry after {retry_after_secs}s")]
+    SourceCoolingDown { source_url: String, retry_after_secs: u64 },
+
     // Crypto errors
     #[error("Encryption error: {0}")]
     Encryption(String),
@@ -63,6 +80,12 @@ pub enum EngineError {
     #[error("Book source error: {0}")]
     BookSource(String),
 
+    #[error("Source rule missing: {0}")]
+    RuleMissing(String),
+
+    #[error("Failed to parse {stage} with rule '{rule}'")]
+    ParseFailed { rule: String, stage: String },
+
     #[error("No results found")]
     NoResults,
 
@@ -74,6 +97,14 @@ pub enum EngineError {
     Other(#[from] anyhow::Error),
 }
 
+/// Chapter content fetched before a request's deadline
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialContent {
+    /// Content pages fetched
+    pub pages: usize,
+    pub content: String,
+}
+
 /// Result type alias for engine operations
 pub type EngineResult<T> = Result<T, EngineError>;
 
@@ -88,11 +119,36 @@ impl EngineError {
         Self::JavaScript(msg.into())
     }
 
+    /// Create a JavaScript timeout error
+    pub fn js_timeout(source_url: impl Into<String>, timeout: std::time::Duration) -> Self {
+        Self::JsTimeout {
+            source_url: source_url.into(),
+            timeout_ms: timeout.as_millis() as u64,
+        }
+    }
+
+    /// Create a deadline error without partial results
+    pub fn deadline_exceeded(budget: std::time::Duration) -> Self {
+        Self::DeadlineExceeded {
+            budget_ms: budget.as_millis() as u64,
+            partial: None,
+        }
+    }
+
     /// Create an HTTP error
     pub fn http(msg: impl Into<String>) -> Self {
         Self::Http(msg.into())
     }
 
+    /// Create a cooldown error for a source throttled for another `remaining`
+    pub fn source_cooling_down(source_url: impl Into<String>, remaining: std::time::Duration) -> Self {
+        Self::SourceCoolingDown {
+            source_url: source_url.into(),
+            // Round up so a client waiting retryAfter seconds finds the cooldown over
+            retry_after_secs: remaining.as_millis().div_ceil(1000) as u64,
+        }
+    }
+
     /// Create an API execution error
     pub fn api_execution(msg: impl Into<String>) -> Self {
         Self::ApiExecution(msg.into())
@@ -103,6 +159,19 @@ impl EngineError {
         Self::BookSource(msg.into())
     }
 
+    /// Create a missing source rule error
+    pub fn rule_missing(field: impl Into<String>) -> Self {
+        Self::RuleMissing(field.into())
+    }
+
+    /// Create a parse failure error for a pipeline stage
+    pub fn parse_failed(rule: impl Into<String>, stage: impl Into<String>) -> Self {
+        Self::ParseFailed {
+            rule: rule.into(),
+            stage: stage.into(),
+        }
+    }
+
     /// Check if error is recoverable
     pub fn is_recoverable(&self) -> bool {
         matches!(
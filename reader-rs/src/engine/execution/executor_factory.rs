@@ -84,6 +84,7 @@ impl ExecutorFactory {
         // Convert ExecutionContext to NativeApiProvider's ExecutionContext
         let native_context = crate::engine::native_api::ExecutionContext {
             base_url: context.base_url.clone(),
+            ..Default::default()
         };
         
         // Convert variables
@@ -102,6 +103,7 @@ impl ExecutorFactory {
         
         let native_context = crate::engine::native_api::ExecutionContext {
             base_url: context.base_url.clone(),
+            ..Default::default()
         };
         let vars: HashMap<String, String> = context.variables.clone();
         
@@ -6,11 +6,20 @@
 //! Deploy: docker run -d --name flaresolverr -p 8191:8191 ghcr.io/flaresolverr/flaresolverr:latest
 
 use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, info};
 
+/// How long a clearance is reused when FlareSolverr reports no cookie expiry
+const DEFAULT_CLEARANCE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Body markers only present on Cloudflare challenge pages
+const CHALLENGE_MARKERS: [&str; 3] = ["__cf_chl", "_cf_chl_opt", "/cdn-cgi/challenge-platform/"];
+
 /// Flaresolverr API endpoint (configurable via env var)
 fn get_flaresolverr_url() -> String {
     std::env::var("FLARESOLVERR_URL").unwrap_or_else(|_| "http://localhost:8191/v1".to_string())
@@ -173,6 +182,95 @@ impl Default for FlareSolverrClient {
     }
 }
 
+/// Cookies and user-agent that passed a Cloudflare challenge for one host
+///
+/// Cloudflare binds `cf_clearance` to the user-agent that solved the
+/// challenge, so both are replayed together.
+#[derive(Debug, Clone)]
+pub struct Clearance {
+    pub cookies: Vec<(String, String)>,
+    pub user_agent: Option<String>,
+    pub expires_at: SystemTime,
+}
+
+impl Clearance {
+    /// Build from a FlareSolverr solution; expires with the `cf_clearance` cookie
+    pub fn from_solution(solution: &FlareSolverrSolution) -> Self {
+        let expires_at = solution
+            .cookies
+            .iter()
+            .find(|c| c.name == "cf_clearance")
+            .and_then(|c| c.expiry)
+            .filter(|secs| *secs > 0.0)
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs))
+            .unwrap_or_else(|| SystemTime::now() + DEFAULT_CLEARANCE_TTL);
+        Self {
+            cookies: solution
+                .cookies
+                .iter()
+                .map(|c| (c.name.clone(), c.value.clone()))
+                .collect(),
+            user_agent: solution.user_agent.clone().filter(|ua| !ua.is_empty()),
+            expires_at,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.expires_at
+    }
+}
+
+/// Process-wide clearance cache keyed by `host[:port]`
+///
+/// Engines are created per request, so the cache lives outside any single
+/// `HttpClient` and lets later requests skip FlareSolverr entirely.
+#[derive(Default)]
+pub struct ClearanceCache {
+    entries: RwLock<HashMap<String, Clearance>>,
+}
+
+static CLEARANCE_CACHE: Lazy<ClearanceCache> = Lazy::new(ClearanceCache::default);
+
+/// The global clearance cache
+pub fn clearance_cache() -> &'static ClearanceCache {
+    &CLEARANCE_CACHE
+}
+
+impl ClearanceCache {
+    /// Valid clearance for the host of `url`; expired entries are dropped
+    pub fn get(&self, url: &str) -> Option<Clearance> {
+        let key = clearance_key(url)?;
+        let clearance = self.entries.read().ok()?.get(&key).cloned()?;
+        if clearance.is_expired() {
+            self.invalidate(url);
+            return None;
+        }
+        Some(clearance)
+    }
+
+    pub fn store(&self, url: &str, clearance: Clearance) {
+        if let (Some(key), Ok(mut entries)) = (clearance_key(url), self.entries.write()) {
+            entries.insert(key, clearance);
+        }
+    }
+
+    pub fn invalidate(&self, url: &str) {
+        if let (Some(key), Ok(mut entries)) = (clearance_key(url), self.entries.write()) {
+            entries.remove(&key);
+        }
+    }
+}
+
+/// Cache key for a URL: its host plus any explicit port
+fn clearance_key(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    Some(match parsed.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host,
+    })
+}
+
 /// Check if HTML content indicates a Cloudflare challenge
 pub fn is_cloudflare_challenge(html: &str) -> bool {
     let indicators = [
@@ -189,21 +287,28 @@ pub fn is_cloudflare_challenge(html: &str) -> bool {
     indicators.iter().any(|indicator| html.contains(indicator))
 }
 
-/// Check if response status indicates Cloudflare block
-pub fn is_cloudflare_blocked(status: u16, html: &str) -> bool {
-    // Common Cloudflare block status codes
-    let cf_status_codes = [403, 503, 520, 521, 522, 523, 524, 525, 526];
-
-    if cf_status_codes.contains(&status) && is_cloudflare_challenge(html) {
+/// Check if a response is a Cloudflare challenge that FlareSolverr should solve
+///
+/// `headers` are keyed by lowercase name. A `cf-mitigated: challenge` header
+/// is conclusive; otherwise the status must be 403/503 and the body must carry
+/// challenge-only markers (or look like a challenge page served by Cloudflare).
+/// Body text alone never triggers, since ordinary pages can mention
+/// "Just a moment".
+pub fn is_cloudflare_blocked(status: u16, headers: &HashMap<String, String>, html: &str) -> bool {
+    if headers
+        .get("cf-mitigated")
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("challenge"))
+    {
         return true;
     }
-
-    // Also check for challenge in 200 response (interactive challenge)
-    if status == 200 && is_cloudflare_challenge(html) {
-        return true;
+    if !matches!(status, 403 | 503) {
+        return false;
     }
-
-    false
+    let served_by_cloudflare = headers
+        .get("server")
+        .is_some_and(|v| v.to_lowercase().contains("cloudflare"));
+    CHALLENGE_MARKERS.iter().any(|marker| html.contains(marker))
+        || (served_by_cloudflare && is_cloudflare_challenge(html))
 }
 
 /// Convert Flaresolverr cookies to cookie string
@@ -228,6 +333,37 @@ mod tests {
         assert!(!is_cloudflare_challenge("<html><body>Normal page</body></html>"));
     }
 
+    #[test]
+    fn test_is_cloudflare_blocked() {
+        let none = HashMap::new();
+        let mitigated = HashMap::from([("cf-mitigated".to_string(), "challenge".to_string())]);
+        let cloudflare = HashMap::from([("server".to_string(), "cloudflare".to_string())]);
+
+        assert!(is_cloudflare_blocked(403, &mitigated, ""));
+        assert!(is_cloudflare_blocked(503, &none, "<script src=\"/cdn-cgi/challenge-platform/h/b\">"));
+        assert!(is_cloudflare_blocked(403, &cloudflare, "<title>Just a moment...</title>"));
+        // Body text alone is not enough
+        assert!(!is_cloudflare_blocked(200, &cloudflare, "<title>Just a moment...</title>"));
+        assert!(!is_cloudflare_blocked(403, &none, "Just a moment, loading chapter"));
+        assert!(!is_cloudflare_blocked(200, &none, "<p>__cf_chl in a forum post</p>"));
+    }
+
+    #[test]
+    fn test_clearance_cache_expiry() {
+        let cache = ClearanceCache::default();
+        let clearance = |expires_at| Clearance {
+            cookies: vec![("cf_clearance".to_string(), "t".to_string())],
+            user_agent: Some("UA".to_string()),
+            expires_at,
+        };
+        cache.store("https://a.example.com/book/1", clearance(SystemTime::now() + Duration::from_secs(60)));
+        assert!(cache.get("https://A.example.com/search").is_some());
+        assert!(cache.get("https://a.example.com:8443/").is_none());
+
+        cache.store("https://b.example.com/", clearance(SystemTime::now() - Duration::from_secs(1)));
+        assert!(cache.get("https://b.example.com/").is_none());
+    }
+
     #[test]
     fn test_cookies_to_string() {
         let cookies = vec![
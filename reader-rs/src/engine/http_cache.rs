@@ -0,0 +1,333 @@
+//! HTTP Cache - On-disk cache of source responses with ETag / Last-Modified revalidation
+//!
+//! Entries are keyed by the md5 of method, URL and body. A stored response is
+//! reused without a request while it is fresh: younger than the call site's
+//! `cache_max_age` override or, without one, the response's own
+//! `Cache-Control: max-age`. Stale entries with validators are revalidated with
+//! `If-None-Match` / `If-Modified-Since`, and a 304 reuses the cached body.
+//!
+//! POST requests with a body and responses that set cookies are not cached
+//! unless explicitly allowed. Disable the cache with `HTTP_CACHE=false`; the
+//! directory is capped by `HTTP_CACHE_MAX_MB` (default 100 MB).
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+use super::http_client::{RawResponse, RequestConfig};
+use super::utils::get_cache_dir;
+
+/// Default size cap of the cache directory in megabytes
+pub const DEFAULT_HTTP_CACHE_MAX_MB: u64 = 100;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    url: String,
+    status_code: u16,
+    headers: HashMap<String, String>,
+    set_cookies: Vec<String>,
+    /// Base64 of the raw (undecoded) body
+    body: String,
+    stored_at: u64,
+    max_age: Option<u64>,
+}
+
+/// A cached response with its freshness information
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub response: RawResponse,
+    /// Unix seconds of the last full fetch or successful revalidation
+    pub stored_at: u64,
+    /// `Cache-Control: max-age` of the response, in seconds
+    pub max_age: Option<u64>,
+}
+
+impl CacheEntry {
+    /// Whether the entry can be served without contacting the server
+    ///
+    /// The call site's `max_age` override wins over the response's own max-age.
+    pub fn is_fresh(&self, max_age: Option<Duration>) -> bool {
+        let Some(max_age) = max_age.map(|d| d.as_secs()).or(self.max_age) else {
+            return false;
+        };
+        now_secs().saturating_sub(self.stored_at) < max_age
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.response.headers.get("etag").map(|s| s.as_str())
+    }
+
+    pub fn last_modified(&self) -> Option<&str> {
+        self.response.headers.get("last-modified").map(|s| s.as_str())
+    }
+
+    /// Add `If-None-Match` / `If-Modified-Since` for revalidation, returning
+    /// false when the response carried no validators
+    pub fn add_validators(&self, headers: &mut HashMap<String, String>) -> bool {
+        if let Some(etag) = self.etag() {
+            headers.insert("If-None-Match".to_string(), etag.to_string());
+        }
+        if let Some(last_modified) = self.last_modified() {
+            headers.insert("If-Modified-Since".to_string(), last_modified.to_string());
+        }
+        self.etag().is_some() || self.last_modified().is_some()
+    }
+}
+
+/// Directives of a `Cache-Control` header that matter to the cache
+#[derive(Debug, Default, PartialEq)]
+struct CacheControl {
+    no_store: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(header: Option<&String>) -> Self {
+        let mut control = Self::default();
+        for directive in header.map(|h| h.split(',')).into_iter().flatten() {
+            let directive = directive.trim().to_lowercase();
+            match directive.split_once('=') {
+                Some(("max-age", secs)) => control.max_age = secs.trim_matches('"').parse().ok(),
+                _ if directive == "no-store" => control.no_store = true,
+                // Always revalidate
+                _ if directive == "no-cache" => control.max_age = Some(0),
+                _ => {}
+            }
+        }
+        control
+    }
+}
+
+/// Directory of cached responses keyed by request hash
+pub struct HttpCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Cache POST requests that carry a body (e.g. search forms)
+    cache_post: bool,
+    /// Cache responses that set cookies
+    cache_cookies: bool,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            cache_post: false,
+            cache_cookies: false,
+        }
+    }
+
+    /// Cache under `data/cache/http`, or `None` when disabled by the HTTP_CACHE env var
+    pub fn open_default() -> Option<Self> {
+        let enabled = std::env::var("HTTP_CACHE")
+            .map(|v| !matches!(v.trim().to_lowercase().as_str(), "0" | "false" | "off"))
+            .unwrap_or(true);
+        if !enabled {
+            return None;
+        }
+        let max_mb = std::env::var("HTTP_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_HTTP_CACHE_MAX_MB);
+        Some(Self::new(get_cache_dir().join("http"), max_mb * 1024 * 1024))
+    }
+
+    /// Allow caching POST requests with a body
+    pub fn with_post(mut self, enabled: bool) -> Self {
+        self.cache_post = enabled;
+        self
+    }
+
+    /// Allow caching responses that set cookies
+    pub fn with_cookies(mut self, enabled: bool) -> Self {
+        self.cache_cookies = enabled;
+        self
+    }
+
+    /// Cache key of a request: md5 of method, URL and body
+    pub fn key(config: &RequestConfig) -> String {
+        let method = config.method.to_uppercase();
+        let body = config.body.as_deref().unwrap_or_default();
+        format!("{:x}", md5::compute(format!("{}\n{}\n{}", method, config.url, body)))
+    }
+
+    /// Whether requests like `config` may use the cache at all
+    pub fn accepts(&self, config: &RequestConfig) -> bool {
+        match config.method.to_uppercase().as_str() {
+            "GET" => true,
+            "POST" => self.cache_post || config.body.as_deref().is_none_or(str::is_empty),
+            _ => false,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        let data = fs::read(self.path(key)).ok()?;
+        let stored: StoredEntry = serde_json::from_slice(&data).ok()?;
+        let body = base64::engine::general_purpose::STANDARD.decode(&stored.body).ok()?;
+        Some(CacheEntry {
+            response: RawResponse {
+                url: stored.url,
+                status_code: stored.status_code,
+                headers: stored.headers,
+                set_cookies: stored.set_cookies,
+                body,
+            },
+            stored_at: stored.stored_at,
+            max_age: stored.max_age,
+        })
+    }
+
+    /// Store a full response if it is cacheable, returning whether it was stored
+    ///
+    /// Only 200 responses are kept, and only when they can be reused later:
+    /// they carry validators or a max-age, or the call site set `cache_max_age`.
+    pub fn store(&self, key: &str, config: &RequestConfig, response: &RawResponse) -> bool {
+        if response.status_code != 200 || (!response.set_cookies.is_empty() && !self.cache_cookies) {
+            return false;
+        }
+        let control = CacheControl::parse(response.headers.get("cache-control"));
+        let has_validators = response.headers.contains_key("etag") || response.headers.contains_key("last-modified");
+        let reusable = has_validators || control.max_age.is_some_and(|age| age > 0) || config.cache_max_age.is_some();
+        if control.no_store || !reusable {
+            return false;
+        }
+        self.write(
+            key,
+            &CacheEntry {
+                response: response.clone(),
+                stored_at: now_secs(),
+                max_age: control.max_age,
+            },
+        )
+    }
+
+    /// Mark an entry as just revalidated (after a 304)
+    pub fn refresh(&self, key: &str, entry: &CacheEntry) {
+        self.write(
+            key,
+            &CacheEntry {
+                stored_at: now_secs(),
+                ..entry.clone()
+            },
+        );
+    }
+
+    fn write(&self, key: &str, entry: &CacheEntry) -> bool {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            tracing::warn!("Failed to create HTTP cache dir: {}", e);
+            return false;
+        }
+        let stored = StoredEntry {
+            url: entry.response.url.clone(),
+            status_code: entry.response.status_code,
+            headers: entry.response.headers.clone(),
+            set_cookies: entry.response.set_cookies.clone(),
+            body: base64::engine::general_purpose::STANDARD.encode(&entry.response.body),
+            stored_at: entry.stored_at,
+            max_age: entry.max_age,
+        };
+        let written = serde_json::to_vec(&stored)
+            .map_err(std::io::Error::from)
+            .and_then(|data| fs::write(self.path(key), data));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write HTTP cache entry {}: {}", key, e);
+            return false;
+        }
+        self.prune();
+        true
+    }
+
+    /// Delete the oldest entries until the directory fits the cap
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                meta.is_file()
+                    .then(|| (meta.modified().unwrap_or(UNIX_EPOCH), meta.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)]) -> RawResponse {
+        RawResponse {
+            url: "https://a.com/toc".to_string(),
+            status_code: 200,
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            set_cookies: Vec::new(),
+            body: "目录".as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_store_policy() {
+        let dir = std::env::temp_dir().join("reader_tests_http_cache_policy");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = HttpCache::new(dir, u64::MAX);
+        let get = RequestConfig {
+            url: "https://a.com/toc".to_string(),
+            ..Default::default()
+        };
+        let key = HttpCache::key(&get);
+
+        assert!(!cache.store(&key, &get, &response(&[])));
+        assert!(!cache.store(&key, &get, &response(&[("etag", "\"1\""), ("cache-control", "no-store")])));
+        let mut with_cookie = response(&[("etag", "\"1\"")]);
+        with_cookie.set_cookies.push("sid=1".to_string());
+        assert!(!cache.store(&key, &get, &with_cookie));
+
+        assert!(cache.store(&key, &get, &response(&[("etag", "\"1\""), ("cache-control", "max-age=60")])));
+        let entry = cache.get(&key).unwrap();
+        assert_eq!(entry.response.body, "目录".as_bytes());
+        assert_eq!(entry.etag(), Some("\"1\""));
+        assert!(entry.is_fresh(None));
+        assert!(!entry.is_fresh(Some(Duration::ZERO)));
+
+        let post = RequestConfig {
+            method: "POST".to_string(),
+            body: Some("key=1".to_string()),
+            ..get.clone()
+        };
+        assert!(!cache.accepts(&post));
+        assert!(HttpCache::new(PathBuf::new(), 0).with_post(true).accepts(&post));
+        assert_ne!(HttpCache::key(&post), key);
+    }
+}
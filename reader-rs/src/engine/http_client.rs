@@ -4,19 +4,31 @@
 //! - URL template parsing ({{key}})
 //! - Request config parsing (URL,{JSON})
 //! - Custom headers, charset, proxy support
-//! - Cookie management with CookieManager
+//! - Cookie management with CookieManager, per host across redirect hops
 //! - Configurable retry with exponential backoff
+//! - Per-source User-Agent rotation, request jitter and 429 cooldowns (see `anti_ban`)
+//! - Optional on-disk response cache (see `http_cache`)
+//! - gzip/brotli/deflate decoding, HTTP/2, and browser profiles chosen by the
+//!   source `fingerprint`
 //! - Blocking Request (using reqwest::blocking)
 
+use super::anti_ban::{self, Cooldowns, Jitter, UaRotation, UserAgentRotator};
 use super::cookie::CookieManager;
-use super::flaresolverr::{is_cloudflare_challenge, FlareSolverrClient};
+use super::deadline;
+use super::error::EngineError;
+use super::flaresolverr::{clearance_cache, is_cloudflare_blocked, Clearance, FlareSolverrClient};
+use super::http_cache::HttpCache;
+use super::stats::STATS;
 use super::utils::resolve_absolute_url;
 use anyhow::{Context, Result};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE, USER_AGENT};
 use std::collections::HashMap;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
+/// Redirects followed for one request, as reqwest's default policy
+const MAX_REDIRECTS: usize = 10;
+
 /// Global Flaresolverr client (lazily initialized)
 static FLARESOLVERR_CLIENT: OnceLock<FlareSolverrClient> = OnceLock::new();
 
@@ -39,6 +51,13 @@ pub struct RequestConfig {
     pub web_view: bool,
     /// JavaScript to execute after page load (for webView)
     pub web_js: Option<String>,
+    /// Extra wait before evaluating `web_js` (`webViewDelay`, milliseconds)
+    pub web_view_delay: Option<Duration>,
+    /// CSS selector to wait for before evaluating `web_js` (`webViewSelector`)
+    pub web_view_selector: Option<String>,
+    /// Serve a cached response younger than this without a request,
+    /// overriding the response's own `Cache-Control: max-age`
+    pub cache_max_age: Option<Duration>,
 }
 
 impl Default for RequestConfig {
@@ -53,10 +72,33 @@ impl Default for RequestConfig {
             retry: 3,
             web_view: false,
             web_js: None,
+            web_view_delay: None,
+            web_view_selector: None,
+            cache_max_age: None,
         }
     }
 }
 
+/// Raw response body with its Content-Type
+#[derive(Debug, Clone)]
+pub struct BinaryResponse {
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Response as received, before the body is decoded
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    /// Final URL (after redirects)
+    pub url: String,
+    pub status_code: u16,
+    /// Headers keyed by lowercase name; repeated headers are joined with ", "
+    pub headers: HashMap<String, String>,
+    /// Raw `Set-Cookie` header values
+    pub set_cookies: Vec<String>,
+    pub body: Vec<u8>,
+}
+
 /// Response from a request that doesn't follow redirects
 #[derive(Debug, Clone)]
 pub struct RedirectResponse {
@@ -97,6 +139,37 @@ impl RedirectResponse {
     }
 }
 
+/// Full response of a request, as exposed to JS by `java.connect`
+#[derive(Debug, Clone, Default)]
+pub struct StrResponse {
+    /// Final URL (after redirects)
+    pub url: String,
+    /// HTTP status code
+    pub status_code: u16,
+    /// Response headers keyed by lowercase name; repeated headers are joined with ", "
+    pub headers: HashMap<String, String>,
+    /// Raw `Set-Cookie` header values
+    pub set_cookies: Vec<String>,
+    /// Body decoded with the requested or detected charset
+    pub body: String,
+}
+
+impl StrResponse {
+    /// Get a header value (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|s| s.as_str())
+    }
+
+    /// Get a cookie value set by this response
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.set_cookies.iter().find_map(|cookie| {
+            let pair = cookie.split(';').next()?;
+            let (key, value) = pair.split_once('=')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    }
+}
+
 /// Retry configuration with exponential backoff
 #[derive(Debug, Clone)]
 pub struct RetryConfig {
@@ -130,43 +203,163 @@ impl RetryConfig {
 }
 
 /// Rate limiter for controlling request frequency(Blocking)
+///
+/// Accepts the source `concurrentRate` formats:
+/// - `"3000"`: one request per 3000ms
+/// - `"5/1000"`: at most 5 requests in any 1000ms window
 #[derive(Debug)]
 pub struct RateLimiter {
-    last_request: std::sync::Mutex<std::time::Instant>,
+    /// Reserved start times of the most recent requests (at most `count`, may lie in the future)
+    history: std::sync::Mutex<std::collections::VecDeque<std::time::Instant>>,
+    count: usize,
     interval: Duration,
 }
 
 impl RateLimiter {
     pub fn new(rate_str: &str) -> Option<Self> {
-        let parts: Vec<&str> = rate_str.split('/').collect();
-        if parts.len() == 2 {
-            if let (Ok(_count), Ok(ms)) = (parts[0].parse::<u32>(), parts[1].parse::<u64>()) {
-                return Some(Self {
-                    last_request: std::sync::Mutex::new(std::time::Instant::now()),
-                    interval: Duration::from_millis(ms),
-                });
-            }
+        let rate_str = rate_str.trim();
+        let (count, ms) = match rate_str.split_once('/') {
+            Some((count, ms)) => (count.trim().parse::<usize>().ok()?, ms.trim().parse::<u64>().ok()?),
+            None => (1, rate_str.parse::<u64>().ok()?),
+        };
+        if count == 0 || ms == 0 {
+            return None;
         }
-        None
+        Some(Self {
+            history: std::sync::Mutex::new(std::collections::VecDeque::with_capacity(count)),
+            count,
+            interval: Duration::from_millis(ms),
+        })
     }
 
+    /// Block until another request is allowed
+    ///
+    /// The start time is reserved under the lock and the sleep happens outside it,
+    /// so concurrent callers are scheduled in order without serializing on the lock.
     pub fn wait(&self) {
-        let wait_time = {
-            let mut last = self.last_request.lock().unwrap();
-            let elapsed = last.elapsed();
-            if elapsed < self.interval {
-                Some(self.interval - elapsed)
+        let slot = {
+            let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+            let now = std::time::Instant::now();
+            let slot = if history.len() >= self.count {
+                history
+                    .pop_front()
+                    .map_or(now, |oldest| (oldest + self.interval).max(now))
             } else {
-                *last = std::time::Instant::now();
-                None
-            }
+                now
+            };
+            history.push_back(slot);
+            slot
         };
+        let delay = slot.saturating_duration_since(std::time::Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// Browser imitated by a client, chosen by the source `fingerprint`
+///
+/// Each profile sends that browser's User-Agent plus the headers it puts on a
+/// page navigation, in the browser's order. Source and request headers still
+/// override individual values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientProfile {
+    pub name: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+}
 
-        if let Some(duration) = wait_time {
-            std::thread::sleep(duration);
-            let mut last = self.last_request.lock().unwrap();
-            *last = std::time::Instant::now();
+impl ClientProfile {
+    /// Used when a source sets no fingerprint or an unknown one
+    pub const DEFAULT: Self = Self {
+        name: "default",
+        headers: &[(
+            "user-agent",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+        )],
+    };
+
+    pub const CHROME: Self = Self {
+        name: "chrome",
+        headers: &[
+            ("sec-ch-ua", r#""Not/A)Brand";v="8", "Chromium";v="126", "Google Chrome";v="126""#),
+            ("sec-ch-ua-mobile", "?0"),
+            ("sec-ch-ua-platform", r#""Windows""#),
+            ("upgrade-insecure-requests", "1"),
+            (
+                "user-agent",
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+            ),
+            (
+                "accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7",
+            ),
+            ("sec-fetch-site", "none"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-user", "?1"),
+            ("sec-fetch-dest", "document"),
+            ("accept-language", "zh-CN,zh;q=0.9,en;q=0.8"),
+        ],
+    };
+
+    pub const SAFARI: Self = Self {
+        name: "safari",
+        headers: &[
+            ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+            ("sec-fetch-site", "none"),
+            ("accept-language", "zh-CN,zh-Hans;q=0.9"),
+            ("sec-fetch-mode", "navigate"),
+            (
+                "user-agent",
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15",
+            ),
+            ("sec-fetch-dest", "document"),
+        ],
+    };
+
+    pub const FIREFOX: Self = Self {
+        name: "firefox",
+        headers: &[
+            (
+                "user-agent",
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:127.0) Gecko/20100101 Firefox/127.0",
+            ),
+            (
+                "accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+            ),
+            (
+                "accept-language",
+                "zh-CN,zh;q=0.8,zh-TW;q=0.7,zh-HK;q=0.5,en-US;q=0.3,en;q=0.2",
+            ),
+            ("upgrade-insecure-requests", "1"),
+            ("sec-fetch-dest", "document"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-site", "none"),
+            ("sec-fetch-user", "?1"),
+        ],
+    };
+
+    /// Profile for a source `fingerprint` such as `chrome` or `Firefox127`
+    pub fn from_fingerprint(fingerprint: Option<&str>) -> Self {
+        let Some(fingerprint) = fingerprint.map(|f| f.trim().to_lowercase()).filter(|f| !f.is_empty()) else {
+            return Self::DEFAULT;
+        };
+        [Self::CHROME, Self::SAFARI, Self::FIREFOX]
+            .into_iter()
+            .find(|profile| fingerprint.starts_with(profile.name))
+            .unwrap_or_else(|| {
+                tracing::warn!("Unknown fingerprint {}, using the default client profile", fingerprint);
+                Self::DEFAULT
+            })
+    }
+
+    /// Client default headers, in the profile's order
+    fn header_map(&self) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in self.headers {
+            map.insert(HeaderName::from_static(name), HeaderValue::from_static(value));
         }
+        map
     }
 }
 
@@ -178,6 +371,20 @@ pub struct HttpClient {
     rate_limiter: Option<RateLimiter>,
     cookie_manager: CookieManager,
     retry_config: RetryConfig,
+    /// Whether Cloudflare challenges are solved through FlareSolverr
+    cloudflare_bypass: bool,
+    /// FlareSolverr client to use instead of the global one
+    flaresolverr: Option<Arc<FlareSolverrClient>>,
+    /// Response cache, shared by clones of the engine's client
+    cache: Option<Arc<HttpCache>>,
+    /// Browser profile selected by the source `fingerprint`
+    profile: ClientProfile,
+    /// User-Agent rotation (`userAgents` / `uaRotation`)
+    user_agents: Option<UserAgentRotator>,
+    /// Random delay added after the rate limiter wait
+    jitter: Option<Jitter>,
+    /// Cooldown registry and the source URL this client's cooldowns are kept under
+    cooldowns: Option<(Arc<Cooldowns>, String)>,
 }
 
 impl HttpClient {
@@ -187,37 +394,24 @@ impl HttpClient {
     }
 
     /// Create a new HTTP client with source-level config
-    pub fn with_config(
-        base_url: &str,
-        headers_json: Option<&str>,
-        _fingerprint: Option<&str>,
-    ) -> Result<Self> {
-        // Build blocking client
+    pub fn with_config(base_url: &str, headers_json: Option<&str>, fingerprint: Option<&str>) -> Result<Self> {
+        let profile = ClientProfile::from_fingerprint(fingerprint);
+        tracing::debug!("Using client profile {} for {}", profile.name, base_url);
+
+        // Build blocking client; HTTP/2 is negotiated through ALPN on TLS connections
         let client = reqwest::blocking::Client::builder()
-            .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36")
+            .default_headers(profile.header_map())
             .timeout(Duration::from_secs(30))
             .cookie_store(true)
+            .redirect(reqwest::redirect::Policy::none())
             .gzip(true)
             .brotli(true)
+            .deflate(true)
             .build()
             .context("Failed to build HTTP client")?;
 
         // Parse source-level headers
-        let default_headers = if let Some(json_str) = headers_json {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
-                if let Some(obj) = json.as_object() {
-                    obj.iter()
-                        .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                        .collect()
-                } else {
-                    HashMap::new()
-                }
-            } else {
-                HashMap::new()
-            }
-        } else {
-            HashMap::new()
-        };
+        let default_headers = headers_json.and_then(parse_header_map).unwrap_or_default();
 
         Ok(Self {
             client,
@@ -226,6 +420,13 @@ impl HttpClient {
             rate_limiter: None,
             cookie_manager: CookieManager::new(),
             retry_config: RetryConfig::default(),
+            cloudflare_bypass: true,
+            flaresolverr: None,
+            cache: None,
+            profile,
+            user_agents: None,
+            jitter: None,
+            cooldowns: None,
         })
     }
 
@@ -250,6 +451,16 @@ impl HttpClient {
         &mut self.cookie_manager
     }
 
+    /// Browser profile the client imitates
+    pub fn profile(&self) -> &ClientProfile {
+        &self.profile
+    }
+
+    /// Replace the source-level headers (e.g. ones computed by a `header` JS rule)
+    pub fn set_default_headers(&mut self, headers: HashMap<String, String>) {
+        self.default_headers = headers;
+    }
+
     /// Set retry configuration
     pub fn set_retry_config(&mut self, config: RetryConfig) {
         self.retry_config = config;
@@ -258,80 +469,148 @@ impl HttpClient {
     /// Set rate limiter
     pub fn set_rate_limit(&mut self, rate_str: &str) {
         self.rate_limiter = RateLimiter::new(rate_str);
+        if self.rate_limiter.is_none() {
+            tracing::warn!("Invalid concurrentRate: {}", rate_str);
+        }
+    }
+
+    /// Enable or disable the FlareSolverr fallback (per-source opt-out)
+    pub fn set_cloudflare_bypass(&mut self, enabled: bool) {
+        self.cloudflare_bypass = enabled;
+    }
+
+    /// Use a specific FlareSolverr client instead of the global one
+    pub fn set_flaresolverr(&mut self, client: FlareSolverrClient) {
+        self.flaresolverr = Some(Arc::new(client));
     }
 
-    /// Parse URL template
+    /// Rotate the User-Agent through `agents`, or the built-in pool when empty
+    ///
+    /// `rotation` is the `uaRotation` mode (`random`, `per-session`); without a
+    /// list or a mode the profile's User-Agent is kept.
+    pub fn set_user_agents(&mut self, agents: &[String], rotation: Option<&str>) {
+        let rotation = rotation.map(str::trim).filter(|r| !r.is_empty());
+        let mode = rotation.and_then(UaRotation::parse);
+        if let (Some(rotation), None) = (rotation, mode) {
+            tracing::warn!("Invalid uaRotation: {}", rotation);
+        }
+        self.user_agents = UserAgentRotator::new(agents, mode);
+    }
+
+    /// Add a random delay of `min_ms`..=`max_ms` before each request
+    pub fn set_jitter(&mut self, min_ms: Option<u64>, max_ms: Option<u64>) {
+        self.jitter = Jitter::new(min_ms, max_ms);
+    }
+
+    /// Keep cooldowns of throttled responses in `cooldowns` under `source_url`
+    ///
+    /// Requests fail with `EngineError::SourceCoolingDown` while the source is cooling down.
+    pub fn set_cooldowns(&mut self, cooldowns: Arc<Cooldowns>, source_url: &str) {
+        self.cooldowns = Some((cooldowns, source_url.to_string()));
+    }
+
+    /// Cache responses in `cache` (or stop caching with `None`)
+    pub fn set_cache(&mut self, cache: Option<HttpCache>) {
+        self.cache = cache.map(Arc::new);
+    }
+
+    /// Substitute `{{key}}` placeholders
+    ///
+    /// Expressions such as `{{page-1}}` are rendered by the template executor.
     pub fn parse_url_template(&self, template: &str, vars: &HashMap<String, String>) -> String {
         let mut result = template.to_string();
         for (key, value) in vars {
             let placeholder = format!("{{{{{}}}}}", key);
             result = result.replace(&placeholder, value);
-            if let Ok(num) = value.parse::<i32>() {
-                let placeholder_minus = format!("{{{{{}-1}}}}", key);
-                result = result.replace(&placeholder_minus, &(num - 1).to_string());
-                let placeholder_plus = format!("{{{{{}+1}}}}", key);
-                result = result.replace(&placeholder_plus, &(num + 1).to_string());
-            }
         }
         result
     }
 
     /// Parse request config
     pub fn parse_request_config(&self, url_str: &str) -> RequestConfig {
+        self.parse_request_config_at(url_str, &self.base_url)
+    }
+
+    /// Parse request config, resolving relative URLs against `base_url` instead of the client's own
+    pub fn parse_request_config_at(&self, url_str: &str, base_url: &str) -> RequestConfig {
         let url_str = url_str.trim();
         let mut config = RequestConfig::default();
 
         if url_str.starts_with('{') {
             if let Ok(json) = serde_json::from_str::<serde_json::Value>(url_str) {
                 if let Some(url) = json.get("url").and_then(|v| v.as_str()) {
-                    config.url = self.absolute_url(url);
-                    config.method = json.get("method").and_then(|v| v.as_str()).unwrap_or("GET").to_string();
-                    config.body = json.get("body").and_then(|v| v.as_str()).map(|s| s.to_string());
-                    config.charset = json.get("charset").and_then(|v| v.as_str()).unwrap_or("UTF-8").to_string();
-                    config.web_view = json.get("webView").and_then(|v| v.as_bool()).unwrap_or(false);
-                    config.web_js = json.get("js").and_then(|v| v.as_str()).map(|s| s.to_string());
-                    self.parse_headers_from_json(&json, &mut config);
+                    config.url = resolve_absolute_url(base_url, url);
+                    self.apply_url_options(&json, &mut config);
                     return config;
                 }
             }
         }
 
-        if let Some(pos) = url_str.rfind(",{") {
-            let url_part = &url_str[..pos];
-            let json_part = &url_str[pos + 1..];
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_part) {
-                config.url = self.absolute_url(url_part);
-                config.method = json.get("method").and_then(|v| v.as_str()).unwrap_or("GET").to_string();
-                config.body = json.get("body").and_then(|v| v.as_str()).map(|s| s.to_string());
-                config.charset = json.get("charset").and_then(|v| v.as_str()).unwrap_or("UTF-8").to_string();
-                config.web_view = json.get("webView").and_then(|v| v.as_bool()).unwrap_or(false);
-                config.web_js = json.get("js").and_then(|v| v.as_str()).map(|s| s.to_string());
-                self.parse_headers_from_json(&json, &mut config);
-                return config;
-            }
+        if let Some((url_part, options)) = split_url_options(url_str) {
+            config.url = resolve_absolute_url(base_url, url_part);
+            self.apply_url_options(&serde_json::Value::Object(options), &mut config);
+            return config;
         }
 
-        config.url = self.absolute_url(url_str);
+        config.url = resolve_absolute_url(base_url, url_str);
         config
     }
 
-    fn request_internal(&self, config: &RequestConfig) -> Result<String> {
+    /// Apply the Legado URL options (method, body, headers, charset, retry...)
+    fn apply_url_options(&self, json: &serde_json::Value, config: &mut RequestConfig) {
+        config.method = json.get("method").and_then(|v| v.as_str()).unwrap_or("GET").to_uppercase();
+        // A body given as an object (e.g. from JSON.stringify of the options) is sent as JSON
+        config.body = match json.get("body") {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Null) | None => None,
+            Some(v) => Some(v.to_string()),
+        };
+        config.charset = json.get("charset").and_then(|v| v.as_str()).unwrap_or("UTF-8").to_string();
+        if let Some(retry) = json.get("retry").and_then(|v| v.as_u64()) {
+            config.retry = retry as u32;
+        }
+        config.web_view = json.get("webView").and_then(|v| v.as_bool()).unwrap_or(false);
+        config.web_js = json.get("js").and_then(|v| v.as_str()).map(|s| s.to_string());
+        config.web_view_delay = json
+            .get("webViewDelay")
+            .and_then(|v| v.as_u64().or_else(|| v.as_str()?.trim().parse().ok()))
+            .map(Duration::from_millis);
+        config.web_view_selector = json
+            .get("webViewSelector")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string());
+        self.parse_headers_from_json(json, config);
+    }
+
+    /// Build and send a request, applying headers, cookies, body encoding and rate limit
+    /// Send a request, following redirects
+    ///
+    /// Redirects are followed here rather than by reqwest so that every hop
+    /// sends the cookies of its own host and stores the cookies it sets under
+    /// that host. 301/302/303 turn a POST into a GET without body, as browsers do.
+    fn send(&self, config: &RequestConfig) -> Result<reqwest::blocking::Response> {
+        if let Some((cooldowns, source_url)) = &self.cooldowns {
+            cooldowns.check(source_url)?;
+        }
         if let Some(ref limiter) = self.rate_limiter {
             limiter.wait();
         }
+        if let Some(jitter) = self.jitter {
+            std::thread::sleep(deadline::trim(jitter.sample()));
+        }
 
-        let mut request = if config.method.to_uppercase() == "POST" {
-            self.client.post(&config.url)
-        } else {
-            self.client.get(&config.url)
-        };
-
-        let mut header_map = HeaderMap::new();
+        // Profile headers first, so they go out in the browser's order
+        let mut header_map = self.profile.header_map();
         for (key, value) in &self.default_headers {
             if let (Ok(name), Ok(val)) = (HeaderName::try_from(key.as_str()), HeaderValue::from_str(value)) {
                 header_map.insert(name, val);
             }
         }
+        // A rotated User-Agent replaces the source's; the request's own headers still win
+        if let Some(ua) = self.user_agents.as_ref().and_then(|r| HeaderValue::from_str(r.pick()).ok()) {
+            header_map.insert(USER_AGENT, ua);
+        }
         if let Some(ref headers) = config.headers {
             for (key, value) in headers {
                 if let (Ok(name), Ok(val)) = (HeaderName::try_from(key.as_str()), HeaderValue::from_str(value)) {
@@ -340,40 +619,123 @@ impl HttpClient {
             }
         }
 
-        let domain = extract_domain(&config.url);
-        if let Some(cookie_header) = self.cookie_manager.get_cookie_header(&domain) {
-            if let Ok(val) = HeaderValue::from_str(&cookie_header) {
-                header_map.insert(COOKIE, val);
-            }
+        let mut body = None;
+        if let Some(ref raw_body) = config.body {
+            let content_type = header_map
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string());
+            let encoded_body = if is_json_body(raw_body, content_type.as_deref()) {
+                if content_type.is_none() {
+                    header_map.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                }
+                raw_body.clone()
+            } else if raw_body.contains('=') {
+                if content_type.is_none() {
+                    header_map.insert(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
+                    );
+                }
+                encode_form_body(raw_body, &config.charset)
+            } else {
+                raw_body.clone()
+            };
+            body = Some(encoded_body);
         }
 
-        if !header_map.is_empty() {
-            tracing::debug!("Request headers for {}: {:?}", config.url, header_map);
-            request = request.headers(header_map);
+        let mut post = config.method.to_uppercase() == "POST";
+        let mut url = match post {
+            true => config.url.clone(),
+            false => encode_url_query(&config.url, &config.charset).into_owned(),
+        };
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send_once(&url, post, body.as_deref(), header_map.clone(), config.timeout)?;
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .filter(|_| status.is_redirection());
+            let Some(next) = location.and_then(|l| response.url().join(l).ok()) else {
+                self.start_cooldown(&response)?;
+                return Ok(response);
+            };
+
+            tracing::debug!("Following redirect {} from {} to {}", status.as_u16(), url, next);
+            if post && !matches!(status.as_u16(), 307 | 308) {
+                post = false;
+                body = None;
+                header_map.remove(CONTENT_TYPE);
+            }
+            url = next.to_string();
         }
+        Err(EngineError::http(format!("Too many redirects for {}", config.url)).into())
+    }
 
-        if let Some(ref body) = config.body {
-             let encoded_body = if body.contains('=') && !body.starts_with('{') {
-                body.split('&').map(|pair| {
-                        if let Some(eq_pos) = pair.find('=') {
-                            let key = &pair[..eq_pos];
-                            let value = &pair[eq_pos + 1..];
-                            let encoded_value = urlencoding::encode(value);
-                            format!("{}={}", key, encoded_value)
-                        } else {
-                            pair.to_string()
-                        }
-                    }).collect::<Vec<_>>().join("&")
-            } else {
-                body.clone()
-            };
-            request = request.body(encoded_body);
+    /// Put the source into cooldown when `response` says it is being throttled
+    fn start_cooldown(&self, response: &reqwest::blocking::Response) -> Result<()> {
+        let Some((cooldowns, source_url)) = &self.cooldowns else {
+            return Ok(());
+        };
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok());
+        let Some(duration) = anti_ban::cooldown_for(response.status().as_u16(), retry_after) else {
+            return Ok(());
+        };
+        tracing::warn!(
+            "{} answered HTTP {}, cooling {} down for {:?}",
+            response.url(),
+            response.status().as_u16(),
+            source_url,
+            duration
+        );
+        cooldowns.start(source_url, duration);
+        Err(EngineError::source_cooling_down(source_url, duration).into())
+    }
+
+    /// Send one hop of a request with the cookies of its host, storing the cookies it sets
+    fn send_once(
+        &self,
+        url: &str,
+        post: bool,
+        body: Option<&str>,
+        mut header_map: HeaderMap,
+        timeout: Duration,
+    ) -> Result<reqwest::blocking::Response> {
+        deadline::check()?;
+        let domain = extract_domain(url);
+        let mut cookie_header = self.cookie_manager.get_cookie_header(&domain);
+
+        // Replay a cached Cloudflare clearance with the user-agent it was issued to
+        if self.cloudflare_bypass {
+            if let Some(clearance) = clearance_cache().get(url) {
+                cookie_header = Some(merge_cookie_header(cookie_header, &clearance.cookies));
+                if let Some(ua) = clearance.user_agent.as_deref().and_then(|ua| HeaderValue::from_str(ua).ok()) {
+                    header_map.insert(USER_AGENT, ua);
+                }
+            }
+        }
+        if let Some(val) = cookie_header.and_then(|c| HeaderValue::from_str(&c).ok()) {
+            header_map.insert(COOKIE, val);
         }
 
-        request = request.timeout(config.timeout);
+        let mut request = match post {
+            true => self.client.post(url),
+            false => self.client.get(url),
+        };
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+        if !header_map.is_empty() {
+            tracing::debug!("Request headers for {}: {:?}", url, header_map);
+            request = request.headers(header_map);
+        }
 
-        // Blocking execute
-        let response = request.send()?;
+        // Blocking execute, within what is left of the request deadline
+        let response = request.timeout(deadline::trim(timeout)).send()?;
 
         for cookie in response.headers().get_all(SET_COOKIE) {
             if let Ok(cookie_str) = cookie.to_str() {
@@ -381,36 +743,164 @@ impl HttpClient {
             }
         }
 
-        let mut final_charset = config.charset.clone();
-        if final_charset == "UTF-8" || final_charset.is_empty() {
-            if let Some(content_type) = response.headers().get(reqwest::header::CONTENT_TYPE) {
-                if let Ok(ct_str) = content_type.to_str() {
-                    if let Some(pos) = ct_str.find("charset=") {
-                        let charset_part = &ct_str[pos + 8..];
-                        let end = charset_part.find(';').unwrap_or(charset_part.len());
-                        let detected = charset_part[..end].trim().to_uppercase();
-                        if !detected.is_empty() {
-                            final_charset = detected;
-                        }
-                    }
-                }
+        Ok(response)
+    }
+
+    fn request_internal(&self, config: &RequestConfig) -> Result<StrResponse> {
+        let response = self.fetch_internal(config)?;
+
+        if self.cloudflare_bypass && is_cloudflare_blocked(response.status_code, &response.headers, &response.body) {
+            tracing::info!("Cloudflare challenge detected for {}, trying Flaresolverr", config.url);
+            // Any cached clearance was rejected, solve again
+            clearance_cache().invalidate(&config.url);
+            return self.request_with_flaresolverr(config);
+        }
+
+        Ok(response)
+    }
+
+    /// Send a request and decode the body, keeping the status, headers and final URL
+    ///
+    /// Goes through the response cache when one is set and accepts the request.
+    fn fetch_internal(&self, config: &RequestConfig) -> Result<StrResponse> {
+        let Some(cache) = self.cache.as_deref().filter(|c| c.accepts(config)) else {
+            return Ok(decode_response(config, self.fetch_raw(config)?));
+        };
+        let key = HttpCache::key(config);
+        let cached = cache.get(&key);
+        if let Some(entry) = cached.as_ref().filter(|e| e.is_fresh(config.cache_max_age)) {
+            STATS.record_http_cache_hit();
+            return Ok(decode_response(config, entry.response.clone()));
+        }
+
+        let mut conditional = config.clone();
+        let revalidate = cached
+            .as_ref()
+            .is_some_and(|e| e.add_validators(conditional.headers.get_or_insert_with(HashMap::new)));
+        let raw = self.fetch_raw(if revalidate { &conditional } else { config })?;
+        if let (304, Some(entry)) = (raw.status_code, cached) {
+            tracing::debug!("HTTP cache revalidated {}", config.url);
+            STATS.record_http_cache_hit();
+            cache.refresh(&key, &entry);
+            return Ok(decode_response(config, entry.response));
+        }
+
+        STATS.record_http_cache_miss();
+        cache.store(&key, config, &raw);
+        Ok(decode_response(config, raw))
+    }
+
+    /// Send a request and read the undecoded body
+    fn fetch_raw(&self, config: &RequestConfig) -> Result<RawResponse> {
+        let response = self.send(config)?;
+        let url = response.url().to_string();
+        let status_code = response.status().as_u16();
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let mut set_cookies = Vec::new();
+        for (name, value) in response.headers() {
+            let Ok(value) = value.to_str() else { continue };
+            if name == SET_COOKIE {
+                set_cookies.push(value.to_string());
             }
+            headers
+                .entry(name.as_str().to_string())
+                .and_modify(|v| {
+                    v.push_str(", ");
+                    v.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+        Ok(RawResponse {
+            url,
+            status_code,
+            headers,
+            set_cookies,
+            body: response.bytes()?.to_vec(),
+        })
+    }
+
+    /// Fetch a binary resource (e.g. a cover image) without decoding
+    ///
+    /// Fails on non-2xx status or when the body is larger than `max_bytes`.
+    pub fn request_bytes(&self, config: &RequestConfig, max_bytes: usize) -> Result<BinaryResponse> {
+        use std::io::Read;
+
+        let response = self.send(config)?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("HTTP {} for {}", status.as_u16(), config.url);
+        }
+        if response.content_length().is_some_and(|len| len as usize > max_bytes) {
+            anyhow::bail!("Response body exceeds {} bytes", max_bytes);
         }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let mut data = Vec::new();
+        response.take(max_bytes as u64 + 1).read_to_end(&mut data)?;
+        if data.len() > max_bytes {
+            anyhow::bail!("Response body exceeds {} bytes", max_bytes);
+        }
+        Ok(BinaryResponse { content_type, data })
+    }
 
-        // Decode
-        let bytes = response.bytes()?;
-        let text = decode_with_charset(&bytes, &final_charset);
+    /// Fetch an image (e.g. a cover) for proxying
+    ///
+    /// A Referer pointing at the base URL is added unless the source headers
+    /// or the URL options already set one, to get past hotlink protection.
+    pub fn fetch_image(&self, url: &str, max_bytes: usize) -> Result<BinaryResponse> {
+        self.request_bytes(&self.media_request_config(url), max_bytes)
+    }
 
-        if is_cloudflare_challenge(&text) {
-             tracing::info!("Cloudflare challenge detected for {}, trying Flaresolverr", config.url);
-             return self.request_with_flaresolverr(config);
+    /// Request config for a media resource (image, audio) linked from the source's pages
+    ///
+    /// Adds a Referer pointing at the base URL unless one is already set.
+    pub fn media_request_config(&self, url: &str) -> RequestConfig {
+        let mut config = self.parse_request_config(url);
+        let headers = config.headers.get_or_insert_with(HashMap::new);
+        let has_referer = headers.keys().any(|k| k.eq_ignore_ascii_case("referer"))
+            || self.has_default_header("referer");
+        if !has_referer && self.base_url.contains("://") {
+            headers.insert(
+                "Referer".to_string(),
+                format!("{}/", self.base_url.trim_end_matches('/')),
+            );
         }
+        config
+    }
 
-        Ok(text)
+    /// Headers a request for `config` would carry: source headers, the config's own and cookies
+    ///
+    /// Lets a client outside this process (e.g. a browser's audio element) repeat the request.
+    pub fn request_headers(&self, config: &RequestConfig) -> HashMap<String, String> {
+        let mut headers = self.default_headers.clone();
+        for (key, value) in config.headers.iter().flatten() {
+            headers.retain(|k, _| !k.eq_ignore_ascii_case(key));
+            headers.insert(key.clone(), value.clone());
+        }
+        if let Some(cookie) = self.cookie_manager.get_cookie_header(&extract_domain(&config.url)) {
+            headers.insert("Cookie".to_string(), cookie);
+        }
+        headers
     }
 
-    fn request_with_flaresolverr(&self, config: &RequestConfig) -> Result<String> {
-        let client = get_flaresolverr();
+    /// Send a request and hand back the undecoded response for streaming
+    ///
+    /// Statuses other than 2xx and 416 (so a Range past the end can be relayed) are errors.
+    pub fn open_stream(&self, config: &RequestConfig) -> Result<reqwest::blocking::Response> {
+        let response = self.send(config)?;
+        let status = response.status();
+        if !status.is_success() && status != reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return Err(EngineError::http(format!("HTTP {} for {}", status.as_u16(), config.url)).into());
+        }
+        Ok(response)
+    }
+
+    fn request_with_flaresolverr(&self, config: &RequestConfig) -> Result<StrResponse> {
+        let client = self.flaresolverr.as_deref().unwrap_or_else(|| get_flaresolverr());
         let result = if config.method.to_uppercase() == "POST" {
             let body = config.body.clone().unwrap_or_default();
             client.solve_post(&config.url, &body)
@@ -425,8 +915,18 @@ impl HttpClient {
                     let cookie_str = format!("{}={}; Path=/", cookie.name, cookie.value);
                     self.cookie_manager.parse_set_cookie(&domain, &cookie_str);
                 }
+                clearance_cache().store(&config.url, Clearance::from_solution(&solution));
                 tracing::info!("Flaresolverr succeeded");
-                Ok(solution.response)
+                let url = match solution.url.is_empty() {
+                    true => config.url.clone(),
+                    false => solution.url,
+                };
+                Ok(StrResponse {
+                    url,
+                    status_code: u16::try_from(solution.status).unwrap_or(200),
+                    body: solution.response,
+                    ..Default::default()
+                })
             }
             Err(e) => {
                 tracing::warn!("Flaresolverr failed: {}", e);
@@ -436,22 +936,60 @@ impl HttpClient {
     }
 
     pub fn request(&self, config: &RequestConfig) -> Result<String> {
+        Ok(self.request_with_meta(config)?.body)
+    }
+
+    /// Execute a request like [`Self::request`], keeping the final URL after redirects
+    ///
+    /// Relative links on the page resolve against that URL rather than the one requested.
+    pub fn request_with_meta(&self, config: &RequestConfig) -> Result<StrResponse> {
         if config.web_view {
-            return self.request_webview(config);
+            return Ok(StrResponse {
+                url: config.url.clone(),
+                status_code: 200,
+                body: self.request_webview(config)?,
+                ..Default::default()
+            });
         }
-        let max_retries = config.retry.min(self.retry_config.max_retries);
-        if max_retries == 0 {
-            return self.request_internal(config);
+        self.with_retry(config, || self.request_internal(config))
+    }
+
+    /// Execute a request and return the full response (status, headers, final URL)
+    ///
+    /// Used by `java.connect`, which lets JS inspect more than the body.
+    pub fn fetch(&self, config: &RequestConfig) -> Result<StrResponse> {
+        if config.web_view {
+            return Ok(StrResponse {
+                url: config.url.clone(),
+                status_code: 200,
+                body: self.request_webview(config)?,
+                ..Default::default()
+            });
         }
+        self.with_retry(config, || self.fetch_internal(config))
+    }
+
+    /// Run `attempt_fn` with up to `config.retry` retries
+    ///
+    /// A retry is skipped when the request deadline can't cover the delay before it.
+    fn with_retry<T>(&self, config: &RequestConfig, attempt_fn: impl Fn() -> Result<T>) -> Result<T> {
+        let max_retries = config.retry.min(self.retry_config.max_retries);
         let mut last_error = None;
         for attempt in 0..=max_retries {
-            match self.request_internal(config) {
+            match attempt_fn() {
                 Ok(result) => return Ok(result),
                 Err(e) => {
-                    last_error = Some(e);
-                    if attempt < max_retries {
-                        std::thread::sleep(self.retry_config.delay_for_attempt(attempt));
+                    let e = deadline::attribute(e);
+                    if attempt == max_retries || deadline::is_exceeded(&e) || anti_ban::is_cooling_down(&e) {
+                        return Err(e);
                     }
+                    let delay = self.retry_config.delay_for_attempt(attempt);
+                    if deadline::current().is_some_and(|d| d.remaining() <= delay) {
+                        tracing::debug!("Not retrying {}: the request deadline is within {:?}", config.url, delay);
+                        return Err(e);
+                    }
+                    last_error = Some(e);
+                    std::thread::sleep(delay);
                 }
             }
         }
@@ -459,7 +997,7 @@ impl HttpClient {
     }
 
     fn request_webview(&self, config: &RequestConfig) -> Result<String> {
-        use super::webview::WebViewExecutor;
+        use super::webview::{RenderRequest, WebViewExecutor};
         tracing::info!("Using WebView for request to: {}", config.url);
         let executor = match WebViewExecutor::new() {
             Ok(e) => e,
@@ -475,7 +1013,13 @@ impl HttpClient {
              return executor.render(None, Some(&config.url), Some(&fetch_js));
         }
         let js = config.web_js.clone().unwrap_or_else(|| "document.documentElement.outerHTML".to_string());
-        executor.render(None, Some(&config.url), Some(&js))
+        executor.render_request(&RenderRequest {
+            url: Some(&config.url),
+            js: Some(&js),
+            wait_selector: config.web_view_selector.as_deref(),
+            wait: config.web_view_delay,
+            ..Default::default()
+        })
     }
 
     pub fn get(&self, url: &str) -> Result<String> {
@@ -514,7 +1058,7 @@ impl HttpClient {
             headers.entry(k.clone()).or_insert_with(|| v.clone());
         }
 
-        let mut header_map = HeaderMap::new();
+        let mut header_map = self.profile.header_map();
         for (key, value) in &headers {
             if let (Ok(name), Ok(val)) = (HeaderName::try_from(key.as_str()), HeaderValue::from_str(value)) {
                 header_map.insert(name, val);
@@ -567,6 +1111,21 @@ impl HttpClient {
         resolve_absolute_url(&self.base_url, url)
     }
 
+    /// Base URL used to resolve relative URLs
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Source-level headers sent with every request
+    pub fn default_headers(&self) -> &HashMap<String, String> {
+        &self.default_headers
+    }
+
+    /// Whether a source-level default header is set (case-insensitive)
+    pub fn has_default_header(&self, name: &str) -> bool {
+        self.default_headers.keys().any(|k| k.eq_ignore_ascii_case(name))
+    }
+
     fn parse_headers_from_json(&self, json: &serde_json::Value, config: &mut RequestConfig) {
         if let Some(headers) = json.get("headers") {
             if let Some(obj) = headers.as_object() {
@@ -584,14 +1143,230 @@ impl HttpClient {
     }
 }
 
+/// Decode a response body with the requested charset, or the one it declares
+///
+/// A non-UTF-8 charset set on the request wins, then the Content-Type charset.
+/// When neither names one (or they claim UTF-8) and the body is not valid UTF-8,
+/// a `<meta>` charset declaration in the first 1024 bytes decides.
+fn decode_response(config: &RequestConfig, raw: RawResponse) -> StrResponse {
+    let requested = config.charset.trim();
+    let declared = raw.headers.get("content-type").and_then(|ct| content_type_charset(ct));
+    let charset = if !requested.is_empty() && !is_utf8_label(requested) {
+        requested.to_string()
+    } else {
+        match declared {
+            Some(declared) if !is_utf8_label(&declared) => declared,
+            _ if std::str::from_utf8(&raw.body).is_ok() => "UTF-8".to_string(),
+            _ => meta_charset(&raw.body).unwrap_or_else(|| "UTF-8".to_string()),
+        }
+    };
+    tracing::debug!("Decoding {} ({} bytes) as {}", raw.url, raw.body.len(), charset);
+
+    StrResponse {
+        url: raw.url,
+        status_code: raw.status_code,
+        headers: raw.headers,
+        set_cookies: raw.set_cookies,
+        body: decode_with_charset(&raw.body, &charset),
+    }
+}
+
+fn is_utf8_label(charset: &str) -> bool {
+    charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8")
+}
+
+/// The `charset=` parameter of a Content-Type value, uppercased
+fn content_type_charset(content_type: &str) -> Option<String> {
+    let pos = content_type.to_ascii_lowercase().find("charset=")?;
+    let value = &content_type[pos + 8..];
+    let end = value.find(';').unwrap_or(value.len());
+    let charset = value[..end]
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_uppercase();
+    (!charset.is_empty()).then_some(charset)
+}
+
+/// The charset declared by `<meta charset>` or `<meta http-equiv="Content-Type">`
+/// within the first 1024 bytes of an HTML body
+fn meta_charset(body: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        let tag = &rest[start..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        if let Some(pos) = tag.find("charset") {
+            let value = tag[pos + 7..].trim_start();
+            if let Some(value) = value.strip_prefix('=') {
+                let value = value.trim_start().trim_start_matches(['"', '\'']).trim_start();
+                let end = value
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                    .unwrap_or(value.len());
+                if end > 0 {
+                    return Some(value[..end].to_uppercase());
+                }
+            }
+        }
+        rest = &rest[start + 5..];
+    }
+    None
+}
+
+/// Parse a `{"name": "value"}` header object, skipping non-string values
+pub fn parse_header_map(json: &str) -> Option<HashMap<String, String>> {
+    let json = serde_json::from_str::<serde_json::Value>(json).ok()?;
+    let obj = json.as_object()?;
+    Some(
+        obj.iter()
+            .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+            .collect(),
+    )
+}
+
+/// Add cookies to a `Cookie` header value, keeping existing cookies of the same name
+fn merge_cookie_header(header: Option<String>, cookies: &[(String, String)]) -> String {
+    let mut merged = header.unwrap_or_default();
+    for (name, value) in cookies {
+        let present = merged
+            .split(';')
+            .any(|pair| pair.split('=').next().map(str::trim) == Some(name.as_str()));
+        if !present {
+            if !merged.is_empty() {
+                merged.push_str("; ");
+            }
+            merged.push_str(&format!("{}={}", name, value));
+        }
+    }
+    merged
+}
+
+/// Split `url,{options}` into the URL part and its JSON options object
+///
+/// Tries each `,{` from the left so JSON bodies containing `,{` are kept intact.
+pub fn split_url_options(url_str: &str) -> Option<(&str, serde_json::Map<String, serde_json::Value>)> {
+    let mut search = 0;
+    while let Some(rel) = url_str[search..].find(",{") {
+        let pos = search + rel;
+        if let Ok(serde_json::Value::Object(map)) = serde_json::from_str(&url_str[pos + 1..]) {
+            return Some((&url_str[..pos], map));
+        }
+        search = pos + 2;
+    }
+    None
+}
+
+/// Whether a request body should be sent verbatim as JSON
+///
+/// An explicit Content-Type wins; otherwise a leading `{` or `[` marks JSON.
+pub fn is_json_body(body: &str, content_type: Option<&str>) -> bool {
+    if let Some(ct) = content_type.map(|s| s.to_lowercase()) {
+        if ct.contains("json") {
+            return true;
+        }
+        if ct.contains("form") {
+            return false;
+        }
+    }
+    let trimmed = body.trim_start();
+    trimmed.starts_with('{') || trimmed.starts_with('[')
+}
+
+/// Encode the values of a `k=v&k2=v2` form body with the given charset
+///
+/// Values that are already percent-encoded are left untouched.
+fn encode_form_body(body: &str, charset: &str) -> String {
+    body.split('&')
+        .map(|pair| match pair.find('=') {
+            Some(eq_pos) => {
+                let key = &pair[..eq_pos];
+                let value = &pair[eq_pos + 1..];
+                if is_percent_encoded(value) {
+                    pair.to_string()
+                } else {
+                    format!("{}={}", key, encode_with_charset(value, charset))
+                }
+            }
+            None => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Encode the non-ASCII query values of a URL with the given charset
+///
+/// reqwest would percent-encode them as UTF-8, which GBK sites read as mojibake.
+fn encode_url_query<'a>(url: &'a str, charset: &str) -> std::borrow::Cow<'a, str> {
+    let is_utf8 = charset.is_empty() || charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8");
+    let query_start = match url.find('?') {
+        Some(pos) if !is_utf8 && !url.is_ascii() => pos + 1,
+        _ => return std::borrow::Cow::Borrowed(url),
+    };
+    let (base, rest) = url.split_at(query_start);
+    let (query, fragment) = rest.split_at(rest.find('#').unwrap_or(rest.len()));
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !value.is_ascii() => format!("{}={}", key, encode_with_charset(value, charset)),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    std::borrow::Cow::Owned(format!("{}{}{}", base, query, fragment))
+}
+
+/// Whether a query or form value is already percent-encoded
+pub fn is_percent_encoded(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if !bytes.contains(&b'%') {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len() {
+                    return false;
+                }
+                if !(bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit()) {
+                    return false;
+                }
+                i += 3;
+            }
+            b if b.is_ascii_alphanumeric() || b"-_.~+".contains(&b) => i += 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Percent-encode a value using the bytes of the given charset
+pub fn encode_with_charset(value: &str, charset: &str) -> String {
+    use encoding_rs::{GB18030, GBK};
+    let bytes = match charset.to_lowercase().as_str() {
+        "gbk" | "gb2312" => GBK.encode(value).0.into_owned(),
+        "gb18030" => GB18030.encode(value).0.into_owned(),
+        _ => value.as_bytes().to_vec(),
+    };
+    let mut out = String::with_capacity(bytes.len() * 3);
+    for b in bytes {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
 fn decode_with_charset(bytes: &[u8], charset: &str) -> String {
-    use encoding_rs::{GB18030, GBK, UTF_8};
-    match charset.to_lowercase().as_str() {
-        "gbk" | "gb2312" => {
+    use encoding_rs::{Encoding, GB18030, GBK, UTF_8};
+    match charset.trim().to_lowercase().as_str() {
+        "gbk" => {
             let (result, _, _) = GBK.decode(bytes);
             result.into_owned()
         }
-        "gb18030" => {
+        // Pages labelled GB2312 routinely use characters only GB18030 covers
+        "gb2312" | "gb18030" => {
             let (result, _, _) = GB18030.decode(bytes);
             result.into_owned()
         }
@@ -604,7 +1379,10 @@ fn decode_with_charset(bytes: &[u8], charset: &str) -> String {
                 }
             }
         }
-        _ => String::from_utf8_lossy(bytes).into_owned()
+        label => match Encoding::for_label(label.as_bytes()) {
+            Some(encoding) => encoding.decode(bytes).0.into_owned(),
+            None => String::from_utf8_lossy(bytes).into_owned(),
+        },
     }
 }
 
@@ -618,3 +1396,610 @@ fn extract_domain(url: &str) -> String {
     }
     url.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::rule_analyzer::RuleAnalyzer;
+    use crate::engine::test_server::{header, spawn_server};
+    use crate::storage::kv::KvStore;
+    use crate::storage::FileStorage;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// Reply to each request with `content-type\n\nraw body`
+    fn spawn_echo_server() -> String {
+        spawn_server(|head, body| {
+            let content_type = header(head, "content-type").unwrap_or("");
+            let headers = vec![("Content-Type", "text/plain; charset=utf-8".to_string())];
+            (200, headers, format!("{}\n\n{}", content_type, body))
+        })
+    }
+
+    fn search(search_url: &str, key: &str, page: i32) -> (String, String) {
+        let fs = FileStorage::new("/tmp/reader_tests_http");
+        let analyzer = RuleAnalyzer::new(Arc::new(KvStore::new(fs, "test_kv_http.json"))).unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("key".to_string(), key.to_string());
+        vars.insert("page".to_string(), page.to_string());
+
+        let url = analyzer.evaluate_url(search_url, &vars).unwrap();
+        let client = HttpClient::new("").unwrap();
+        let mut config = client.parse_request_config(&url);
+        config.retry = 0;
+        let echoed = client.request(&config).unwrap();
+        let (content_type, body) = echoed.split_once("\n\n").unwrap();
+        (content_type.to_string(), body.to_string())
+    }
+
+    #[test]
+    fn test_json_post_search() {
+        let base = spawn_echo_server();
+        let search_url = format!(
+            r#"{}/api/search,{{"method":"POST","body":"{{\"kw\":\"{{{{key}}}}\",\"page\":{{{{page}}}}}}","headers":{{"Content-Type":"application/json"}}}}"#,
+            base
+        );
+        let (content_type, body) = search(&search_url, r#"say "hi", 中文 & more"#, 2);
+
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["kw"], r#"say "hi", 中文 & more"#);
+        assert_eq!(json["page"], 2);
+    }
+
+    #[test]
+    fn test_gbk_form_post_search() {
+        let base = spawn_echo_server();
+        let search_url = format!(
+            r#"{}/search.php,{{"method":"POST","body":"searchkey={{{{key}}}}&page={{{{page}}}}&type=%E4%B8%AD","charset":"gbk"}}"#,
+            base
+        );
+        let (content_type, body) = search(&search_url, "中文 书", 1);
+
+        assert_eq!(content_type, "application/x-www-form-urlencoded");
+        assert_eq!(body, "searchkey=%D6%D0%CE%C4%20%CA%E9&page=1&type=%E4%B8%AD");
+    }
+
+    /// A search keyword with JSON and URL syntax in it
+    const TRICKY_KEY: &str = r#"he said "hi" & left {maybe}"#;
+
+    #[test]
+    fn test_json_post_search_escapes_keyword() {
+        let base = spawn_echo_server();
+        let search_url = format!(
+            r#"{}/api/search,{{"method":"POST","body":"{{\"kw\":\"{{{{key}}}}\",\"page\":{{{{page}}}}}}"}}"#,
+            base
+        );
+        let (content_type, body) = search(&search_url, TRICKY_KEY, 3);
+
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["kw"], TRICKY_KEY);
+        assert_eq!(json["page"], 3);
+
+        let base = spawn_echo_server();
+        let search_url = format!(
+            r#"{}/search.php,{{"method":"POST","body":"searchkey={{{{key}}}}&page={{{{page}}}}"}}"#,
+            base
+        );
+        let (_, body) = search(&search_url, TRICKY_KEY, 1);
+        assert_eq!(body, "searchkey=he%20said%20%22hi%22%20%26%20left%20%7Bmaybe%7D&page=1");
+    }
+
+    #[test]
+    fn test_get_search_encodes_keyword() {
+        let base = spawn_server(|head, _| {
+            let path = head.split_whitespace().nth(1).unwrap_or_default();
+            let query: HashMap<String, String> = reqwest::Url::parse(&format!("http://x{}", path))
+                .unwrap()
+                .query_pairs()
+                .into_owned()
+                .collect();
+            let status = if query.get("q").map(String::as_str) == Some(TRICKY_KEY) && query["page"] == "2" {
+                200
+            } else {
+                404
+            };
+            (status, vec![], path.to_string())
+        });
+        let fs = FileStorage::new("/tmp/reader_tests_http");
+        let analyzer = RuleAnalyzer::new(Arc::new(KvStore::new(fs, "test_kv_http.json"))).unwrap();
+        let client = HttpClient::new("").unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("key".to_string(), TRICKY_KEY.to_string());
+        vars.insert("page".to_string(), "2".to_string());
+
+        for search_url in [
+            format!("{}/search?q={{{{key}}}}&page={{{{page}}}}", base),
+            format!(
+                r#"{}/search?q={{{{key}}}}&page={{{{page}}}},{{"headers":{{"X-Key":"{{{{key}}}}"}}}}"#,
+                base
+            ),
+            format!("{}/search?q={{{{java.encodeURI(key)}}}}&page={{{{page}}}}", base),
+        ] {
+            let url = analyzer.evaluate_url(&search_url, &vars).unwrap();
+            let mut config = client.parse_request_config(&url);
+            assert_eq!(config.method, "GET", "{}", url);
+            config.retry = 0;
+            let response = client.fetch(&config).unwrap();
+            assert_eq!(response.status_code, 200, "{} -> {}", search_url, response.body);
+        }
+    }
+
+    #[test]
+    fn test_gbk_query_search() {
+        let base = spawn_server(|head, _| {
+            let path = head.split_whitespace().nth(1).unwrap_or_default();
+            let status = if path == "/search?q=%D0%A1%CB%B5&p=1" { 200 } else { 404 };
+            (status, vec![], path.to_string())
+        });
+        let fs = FileStorage::new("/tmp/reader_tests_http");
+        let analyzer = RuleAnalyzer::new(Arc::new(KvStore::new(fs, "test_kv_http.json"))).unwrap();
+        let client = HttpClient::new("").unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("key".to_string(), "小说".to_string());
+
+        for search_url in [
+            format!(r#"{}/search?q={{{{java.encodeURI(key, "gbk")}}}}&p=1"#, base),
+            format!(r#"{}/search?q={{{{java.utf8ToGbk(key)}}}}&p=1"#, base),
+            format!(r#"{}/search?q={{{{key}}}}&p=1,{{"charset":"gbk"}}"#, base),
+        ] {
+            let url = analyzer.evaluate_url(&search_url, &vars).unwrap();
+            let mut config = client.parse_request_config(&url);
+            config.retry = 0;
+            let response = client.fetch(&config).unwrap();
+            assert_eq!(response.status_code, 200, "{} -> {}", search_url, response.body);
+        }
+    }
+
+    #[test]
+    fn test_encode_url_query() {
+        assert_eq!(encode_url_query("/s?q=小说&t=1#top", "gbk"), "/s?q=%D0%A1%CB%B5&t=1#top");
+        assert_eq!(encode_url_query("/s?q=%D0%A1&t=小", "GB2312"), "/s?q=%D0%A1&t=%D0%A1");
+        assert_eq!(encode_url_query("/s?q=小说", "UTF-8"), "/s?q=小说");
+    }
+
+    fn raw_response(content_type: Option<&str>, body: Vec<u8>) -> RawResponse {
+        RawResponse {
+            url: "https://example.com/book/1".to_string(),
+            status_code: 200,
+            headers: content_type
+                .map(|ct| HashMap::from([("content-type".to_string(), ct.to_string())]))
+                .unwrap_or_default(),
+            set_cookies: Vec::new(),
+            body,
+        }
+    }
+
+    #[test]
+    fn test_decode_response_charset() {
+        // "中文" in GBK
+        let gbk = [0xD6, 0xD0, 0xCE, 0xC4];
+        let page = |head: &str| [head.as_bytes(), &gbk[..], b"</body></html>"].concat();
+        let config = RequestConfig::default();
+
+        // No charset header, declared by <meta charset>
+        let body = page(r#"<html><head><meta charset="gbk"></head><body>"#);
+        let resp = decode_response(&config, raw_response(Some("text/html"), body));
+        assert!(resp.body.contains("中文"));
+
+        // Header claims UTF-8, the body declares gb2312 through http-equiv
+        let body =
+            page(r#"<html><head><META http-equiv="Content-Type" content="text/html; charset=gb2312"></head><body>"#);
+        let resp = decode_response(&config, raw_response(Some("text/html; charset=UTF-8"), body));
+        assert!(resp.body.contains("中文"));
+
+        // Valid UTF-8 is kept even when the meta disagrees
+        let body = r#"<meta charset="gbk"><p>中文</p>"#.as_bytes().to_vec();
+        assert!(decode_response(&config, raw_response(None, body)).body.contains("中文"));
+
+        // A header charset is trusted, and a request charset overrides both
+        let resp = decode_response(&config, raw_response(Some("text/html; charset=\"GBK\""), gbk.to_vec()));
+        assert_eq!(resp.body, "中文");
+        let config = RequestConfig {
+            charset: "gbk".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            decode_response(&config, raw_response(Some("text/html; charset=utf-8"), gbk.to_vec())).body,
+            "中文"
+        );
+
+        assert_eq!(
+            meta_charset(b"<meta content='text/html;charset = big5' http-equiv=content-type>").as_deref(),
+            Some("BIG5")
+        );
+        assert_eq!(meta_charset(b"<meta name=\"charset-less\"><p>charset=gbk</p>"), None);
+    }
+
+    #[test]
+    fn test_split_url_options() {
+        let (url, options) =
+            split_url_options(r#"/s,{"body":"{\"a\":[{},{}]}","method":"POST"}"#).unwrap();
+        assert_eq!(url, "/s");
+        assert_eq!(options["method"], "POST");
+        assert!(split_url_options("/s?a=1,{b}").is_none());
+    }
+
+    #[test]
+    fn test_web_view_options() {
+        let client = HttpClient::new("").unwrap();
+        let config = client.parse_request_config(
+            r##"https://a.com/1,{"webView":true,"webViewDelay":3000,"webViewSelector":"#content","js":"1"}"##,
+        );
+        assert!(config.web_view);
+        assert_eq!(config.web_view_delay, Some(Duration::from_millis(3000)));
+        assert_eq!(config.web_view_selector.as_deref(), Some("#content"));
+        assert_eq!(config.web_js.as_deref(), Some("1"));
+
+        let config = client.parse_request_config(r#"https://a.com/1,{"webView":true,"webViewDelay":"500"}"#);
+        assert_eq!(config.web_view_delay, Some(Duration::from_millis(500)));
+        assert_eq!(config.web_view_selector, None);
+    }
+
+    #[test]
+    fn test_rate_limiter_single_interval() {
+        let limiter = RateLimiter::new("200").unwrap();
+        let start = std::time::Instant::now();
+        limiter.wait();
+        assert!(start.elapsed() < Duration::from_millis(100));
+        limiter.wait();
+        limiter.wait();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_rate_limiter_sliding_window() {
+        let limiter = RateLimiter::new("3/300").unwrap();
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            limiter.wait();
+        }
+        // 首个窗口内的突发请求不受限
+        assert!(start.elapsed() < Duration::from_millis(150));
+
+        for _ in 0..3 {
+            limiter.wait();
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_millis(600));
+
+        assert!(RateLimiter::new("0/100").is_none());
+        assert!(RateLimiter::new("abc").is_none());
+    }
+
+    #[test]
+    fn test_rate_limiter_across_threads() {
+        let limiter = std::sync::Arc::new(RateLimiter::new("2/200").unwrap());
+        let start = std::time::Instant::now();
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                std::thread::spawn(move || {
+                    limiter.wait();
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let mut times: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        times.sort();
+        // 两个一组，每组间隔一个窗口
+        assert!(times[1] < Duration::from_millis(100));
+        assert!(times[2] >= Duration::from_millis(200));
+        assert!(times[4] >= Duration::from_millis(400));
+        assert!(times[5] < Duration::from_millis(700));
+    }
+
+    /// An origin behind a Cloudflare challenge that accepts `cf_clearance=tok` from `SolverUA`,
+    /// and a FlareSolverr stub counting its invocations
+    fn spawn_cloudflare_pair() -> (String, String, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let origin = spawn_server(|head, _| {
+            if head.contains("cf_clearance=tok") && head.contains("user-agent: SolverUA") {
+                (200, vec![], "real page".to_string())
+            } else {
+                let headers = vec![("cf-mitigated", "challenge".to_string()), ("Server", "cloudflare".to_string())];
+                (403, headers, "<title>Just a moment...</title>".to_string())
+            }
+        });
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let solver = spawn_server(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let solution = serde_json::json!({
+                "status": "ok",
+                "message": "",
+                "solution": {
+                    "url": "",
+                    "status": 200,
+                    "response": "solved page",
+                    "cookies": [{"name": "cf_clearance", "value": "tok"}],
+                    "userAgent": "SolverUA"
+                }
+            });
+            (200, vec![("Content-Type", "application/json".to_string())], solution.to_string())
+        });
+        (origin, format!("{}/v1", solver), calls)
+    }
+
+    #[test]
+    fn test_cloudflare_clearance_reused() {
+        use std::sync::atomic::Ordering;
+
+        let (origin, solver, calls) = spawn_cloudflare_pair();
+        let mut client = HttpClient::new(&origin).unwrap();
+        client.set_flaresolverr(FlareSolverrClient::with_url(&solver));
+
+        assert_eq!(client.get(&format!("{}/book/1", origin)).unwrap(), "solved page");
+        // A fresh client (as for the next engine) replays the cached clearance
+        let other = HttpClient::new(&origin).unwrap();
+        assert_eq!(other.get(&format!("{}/book/2", origin)).unwrap(), "real page");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_cloudflare_bypass_opt_out() {
+        use std::sync::atomic::Ordering;
+
+        let (origin, solver, calls) = spawn_cloudflare_pair();
+        let mut client = HttpClient::new(&origin).unwrap();
+        client.set_flaresolverr(FlareSolverrClient::with_url(&solver));
+        client.set_cloudflare_bypass(false);
+
+        let body = client.get(&format!("{}/book/1", origin)).unwrap();
+        assert!(body.contains("Just a moment"));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_compressed_responses_decoded() {
+        let text = "<p>第1章 正文</p>".repeat(20);
+        let body = text.clone();
+        let base = spawn_server(move |head, _| {
+            let mut encoded = Vec::new();
+            let encoding = if head.contains("/br ") {
+                assert!(head.contains("accept-encoding: gzip,deflate,br"), "{}", head);
+                let mut writer = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                writer.write_all(body.as_bytes()).unwrap();
+                drop(writer);
+                "br"
+            } else {
+                let mut writer = flate2::write::ZlibEncoder::new(&mut encoded, flate2::Compression::default());
+                writer.write_all(body.as_bytes()).unwrap();
+                writer.finish().unwrap();
+                "deflate"
+            };
+            let headers = vec![
+                ("Content-Encoding", encoding.to_string()),
+                ("Content-Type", "text/html; charset=utf-8".to_string()),
+            ];
+            (200, headers, encoded)
+        });
+
+        let client = HttpClient::new(&base).unwrap();
+        assert_eq!(client.get(&format!("{}/br", base)).unwrap(), text);
+        assert_eq!(client.get(&format!("{}/deflate", base)).unwrap(), text);
+    }
+
+    #[test]
+    fn test_fingerprint_profiles() {
+        let base = spawn_server(|head, _| (200, vec![], head.to_string()));
+
+        let chrome = HttpClient::with_config(&base, None, Some("Chrome")).unwrap();
+        assert_eq!(chrome.profile().name, "chrome");
+        let head = chrome.get(&base).unwrap();
+        assert!(head.contains("sec-ch-ua-platform: \"Windows\""), "{}", head);
+        assert!(head.contains("accept-language: zh-CN,zh;q=0.9,en;q=0.8"), "{}", head);
+        // Headers go out in the browser's order
+        let position = |name: &str| head.find(&format!("{}: ", name)).unwrap();
+        assert!(position("sec-ch-ua") < position("user-agent"));
+        assert!(position("user-agent") < position("accept"));
+        assert!(position("sec-fetch-dest") < position("accept-language"));
+        assert!(head.contains("Chrome/126.0.0.0 Safari/537.36"));
+
+        // Source headers override the profile
+        let custom = HttpClient::with_config(&base, Some(r#"{"User-Agent":"Custom"}"#), Some("firefox")).unwrap();
+        let head = custom.get(&base).unwrap();
+        assert_eq!(head.matches("user-agent: ").count(), 1);
+        assert!(head.contains("user-agent: Custom"), "{}", head);
+        assert!(head.contains("accept-language: zh-CN,zh;q=0.8"), "{}", head);
+
+        assert_eq!(ClientProfile::from_fingerprint(Some("okhttp")), ClientProfile::DEFAULT);
+        assert_eq!(ClientProfile::from_fingerprint(None), ClientProfile::DEFAULT);
+        assert_eq!(ClientProfile::from_fingerprint(Some("safari17")), ClientProfile::SAFARI);
+    }
+
+    #[test]
+    fn test_user_agent_rotation() {
+        let base = spawn_server(|head, _| {
+            let ua = head
+                .lines()
+                .find_map(|line| line.strip_prefix("user-agent: "))
+                .unwrap_or_default();
+            (200, vec![], ua.trim().to_string())
+        });
+
+        let mut client = HttpClient::with_config(&base, Some(r#"{"User-Agent":"Source"}"#), None).unwrap();
+        client.set_user_agents(&["UA-1".to_string(), "UA-2".to_string()], None);
+        let seen: Vec<String> = (0..3).map(|_| client.get(&base).unwrap()).collect();
+        assert_eq!(seen, ["UA-1", "UA-2", "UA-1"]);
+
+        // A User-Agent in the request options still wins
+        let own = client.get(&format!(r#"{},{{"headers":{{"User-Agent":"Request"}}}}"#, base)).unwrap();
+        assert_eq!(own, "Request");
+
+        client.set_user_agents(&[], Some("per-session"));
+        let first = client.get(&base).unwrap();
+        assert!(anti_ban::UA_POOL.contains(&first.as_str()));
+        assert_eq!(client.get(&base).unwrap(), first);
+    }
+
+    #[test]
+    fn test_429_starts_cooldown() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base = spawn_server(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (429, vec![("Retry-After", "120".to_string())], "slow down")
+        });
+
+        let cooldowns = Arc::new(Cooldowns::default());
+        let mut client = HttpClient::new(&base).unwrap();
+        client.set_cooldowns(cooldowns.clone(), "throttled-source");
+        client.set_jitter(Some(1), Some(5));
+
+        let config = RequestConfig {
+            retry: 3,
+            ..client.parse_request_config(&base)
+        };
+        let err = client.request(&config).unwrap_err();
+        assert!(anti_ban::is_cooling_down(&err), "{:?}", err);
+        // Not retried, and later requests don't reach the server
+        assert!(client.get(&base).is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let remaining = cooldowns.remaining("throttled-source").unwrap();
+        assert!(remaining > Duration::from_secs(110) && remaining <= Duration::from_secs(120));
+
+        cooldowns.clear("throttled-source");
+        assert!(client.get(&base).is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_http_cache_revalidates_with_etag() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let full_bodies = Arc::new(AtomicUsize::new(0));
+        let (count, full) = (requests.clone(), full_bodies.clone());
+        let base = spawn_server(move |head, _| {
+            count.fetch_add(1, Ordering::SeqCst);
+            if head.contains("if-none-match: \"v1\"") {
+                return (304, vec![("ETag", "\"v1\"".to_string())], String::new());
+            }
+            full.fetch_add(1, Ordering::SeqCst);
+            let mut headers = vec![("ETag", "\"v1\"".to_string())];
+            if head.contains("/login") {
+                headers.push(("Set-Cookie", "sid=1".to_string()));
+            }
+            (200, headers, "第1章 第2章".to_string())
+        });
+        let dir = std::env::temp_dir().join("reader_tests_http_cache_client");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut client = HttpClient::new(&base).unwrap();
+        client.set_cache(Some(HttpCache::new(dir, u64::MAX)));
+        let hits = STATS.http_cache_hits.load(Ordering::Relaxed);
+
+        let toc = format!("{}/toc", base);
+        for _ in 0..3 {
+            assert_eq!(client.get(&toc).unwrap(), "第1章 第2章");
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert_eq!(full_bodies.load(Ordering::SeqCst), 1);
+        assert!(STATS.http_cache_hits.load(Ordering::Relaxed) >= hits + 2);
+
+        // A max-age override skips the request entirely
+        let mut config = client.parse_request_config(&toc);
+        config.cache_max_age = Some(Duration::from_secs(600));
+        assert_eq!(client.request(&config).unwrap(), "第1章 第2章");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // POST with a body and responses setting cookies are never cached
+        for _ in 0..2 {
+            client.post(&format!("{}/search", base), "key=1").unwrap();
+            client.get(&format!("{}/login", base)).unwrap();
+        }
+        assert_eq!(full_bodies.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_engine_reads_cloudflare_opt_out() {
+        let source: crate::engine::book_source::BookSource = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": "https://cf.example.com",
+            "bookSourceName": "cf",
+            "enabledCloudflareBypass": false
+        }))
+        .unwrap();
+        assert_eq!(source.enabled_cloudflare_bypass, Some(false));
+
+        let full: crate::models::BookSourceFull = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": "https://cf.example.com",
+            "bookSourceName": "cf"
+        }))
+        .unwrap();
+        assert!(full.enabled_cloudflare_bypass);
+    }
+
+    #[test]
+    fn test_redirect_across_hosts_keeps_cookies_per_host() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let target = spawn_server(move |head, body| {
+            log.lock().unwrap().push((head.to_string(), body.to_string()));
+            (200, vec![("Set-Cookie", "final=2; Path=/".to_string())], "done")
+        })
+        .replace("127.0.0.1", "localhost");
+        let location = format!("{}/landing", target);
+        let origin = spawn_server(move |head, _| {
+            let status = if head.starts_with("POST") { 303 } else { 302 };
+            let headers = vec![
+                ("Location", location.clone()),
+                ("Set-Cookie", "hop=1; Path=/".to_string()),
+            ];
+            (status, headers, "")
+        });
+
+        let client = HttpClient::new(&origin).unwrap();
+        let response = client
+            .request_with_meta(&client.parse_request_config(&format!("{}/book/1", origin)))
+            .unwrap();
+        assert_eq!(response.url, format!("{}/landing", target));
+        assert_eq!(response.body, "done");
+        assert_eq!(client.cookie_manager().get_cookie("127.0.0.1", Some("hop")), "1");
+        assert_eq!(client.cookie_manager().get_cookie("localhost", Some("final")), "2");
+
+        // A POST redirected with 303 continues as a GET without the body
+        let post = format!(r#"{}/search,{{"method":"POST","body":"key=a"}}"#, origin);
+        client.request(&client.parse_request_config(&post)).unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert!(seen.iter().all(|(head, _)| !head.contains("hop=1")));
+        assert!(seen[1].0.starts_with("GET /landing"));
+        assert!(seen[1].0.contains("final=2"));
+        assert_eq!(seen[1].1, "");
+    }
+
+    #[test]
+    fn test_retry_skipped_when_deadline_cannot_cover_delay() {
+        use crate::engine::deadline::{self, Deadline};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Drops every connection so each attempt fails
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_server(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (0, vec![], "")
+        });
+
+        let mut client = HttpClient::new(&base).unwrap();
+        client.set_retry_config(RetryConfig {
+            base_delay_ms: 400,
+            ..Default::default()
+        });
+        let started = std::time::Instant::now();
+        let _deadline = deadline::enter(Some(Deadline::after(Duration::from_millis(1000))));
+        assert!(client.get(&format!("{}/page", base)).is_err());
+
+        // The 400ms delay fits the budget, the following 800ms one doesn't
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+}
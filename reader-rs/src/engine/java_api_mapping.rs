@@ -118,6 +118,7 @@ pub static JAVA_TO_NATIVE: Lazy<HashMap<&'static str, &'static str>> = Lazy::new
 
     // ============== Jsoup/DOM Helpers ==============
     m.insert("java.getElements", "native.getElements");
+    m.insert("java.getStrings", "native.getStrings");
     m.insert("java.getElement", "native.getElement");
     m.insert("java.getElementsByClass", "native.getElementsByClass");
     m.insert("java.getElementById", "native.getElementById");
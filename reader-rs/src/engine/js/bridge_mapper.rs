@@ -31,13 +31,15 @@ pub fn map_to_api(ns: &str, method: &str, args: &[String]) -> NativeApi {
         "md5Encode" | "md5" => NativeApi::Md5Encode,
         "md5Encode16" => NativeApi::Md5Encode16,
         "encodeURI" | "encodeUrl" => NativeApi::EncodeUri,
+        "utf8ToGbk" => NativeApi::Utf8ToGbk,
         "hexEncode" | "hexEncodeToString" | "byteToHexString" => NativeApi::HexEncode,
         "hexDecode" | "hexDecodeToString" | "hexStringToByte" => NativeApi::HexDecode,
         "htmlFormat" => NativeApi::HtmlFormat,
 
         // ============== Storage - Source Vars ==============
+        // java.put(key, value, saveTime) shares the TTL cache with the native matchers
         "put" | "set" => {
-            if ns == "cache" {
+            if ns == "cache" || ns == "java" {
                 NativeApi::CacheSet
             } else {
                 NativeApi::SourceVarSet
@@ -45,21 +47,26 @@ pub fn map_to_api(ns: &str, method: &str, args: &[String]) -> NativeApi {
         }
         "get" if ns == "cache" => NativeApi::CacheGet,
 
-        "putVariable" => NativeApi::SourceVarSet,
+        "putVariable" | "setVariable" => NativeApi::SourceVarSet,
         "getVariable" => NativeApi::SourceVarGet,
 
         // ============== Network ==============
-        "ajax" => NativeApi::HttpGet,
+        // httpGet/httpPost are what SourceRewriter turns java.ajax/java.post into
+        "ajax" | "httpGet" => NativeApi::HttpGet,
         "connect" => NativeApi::HttpGet,
         "get" => {
             let is_http = args.get(0).map(|s| s.starts_with("http")).unwrap_or(false);
             if args.len() > 1 || is_http {
                 NativeApi::HttpGet
+            } else if ns == "java" {
+                NativeApi::CacheGet
             } else {
                 NativeApi::SourceVarGet
             }
         }
-        "post" => NativeApi::HttpPost,
+        "post" | "httpPost" => NativeApi::HttpPost,
+        "head" => NativeApi::HttpHead,
+        "getSize" => NativeApi::HttpContentLength,
 
         // ============== Crypto ==============
         "aesEncode" | "aesEncrypt" => NativeApi::AesEncode,
@@ -69,6 +76,11 @@ pub fn map_to_api(ns: &str, method: &str, args: &[String]) -> NativeApi {
 
         // ============== String ==============
         "getString" => NativeApi::SourceVarGet,
+        "t2s" => NativeApi::ChineseT2s,
+        "s2t" => NativeApi::ChineseS2t,
+
+        // ============== File ==============
+        "getTxtInFolder" => NativeApi::GetTxtInFolder,
 
         // ============== Cookies ==============
         "getCookie" => NativeApi::GetCookie,
@@ -81,6 +93,12 @@ pub fn map_to_api(ns: &str, method: &str, args: &[String]) -> NativeApi {
         // ============== Misc ==============
         "randomUUID" => NativeApi::RandomUuid,
         "log" => NativeApi::Log,
+        // UI-only calls have nothing to show on the server
+        "toast" | "longToast" | "startBrowser" => NativeApi::UiNoop(method.to_string()),
+
+        // Font
+        "queryTTF" => NativeApi::QueryTtf,
+        "replaceFont" => NativeApi::ReplaceFont,
 
         _ => NativeApi::Unknown(format!("{}.{}", ns, method)),
     }
@@ -2,6 +2,48 @@
 // This script creates global proxies that forward calls to the Rust native bridge
 
 (function() {
+    function toArg(arg) {
+        if (arg === null || arg === undefined) return "";
+        if (typeof arg === 'object') return JSON.stringify(arg);
+        return String(arg);
+    }
+
+    // Response of java.connect(), backed by a response stored on the Rust side
+    function StrResponse(id) {
+        this._id = id;
+    }
+    StrResponse.prototype.body = function() { return _rust_response(this._id, "body", ""); };
+    StrResponse.prototype.code = function() { return Number(_rust_response(this._id, "code", "")); };
+    StrResponse.prototype.url = function() { return _rust_response(this._id, "url", ""); };
+    StrResponse.prototype.header = function(name) { return _rust_response(this._id, "header", toArg(name)); };
+    StrResponse.prototype.headers = function() { return JSON.parse(_rust_response(this._id, "headers", "") || "{}"); };
+    StrResponse.prototype.cookie = function(name) { return _rust_response(this._id, "cookie", toArg(name)); };
+    StrResponse.prototype.toString = function() { return this.body(); };
+
+    function connect(url, header) {
+        const id = _rust_http_connect(toArg(url), toArg(header));
+        if (id < 0) throw new Error("java.connect failed: " + url);
+        return new StrResponse(id);
+    }
+
+    // java.getElements(content, rule) / java.getStrings(content, rule): re-query
+    // with a full rule; with only a rule the current content is queried
+    function ruleQuery(elements) {
+        return function(content, rule) {
+            if (typeof _rust_rule_query !== 'function') return [];
+            if (rule === undefined) {
+                rule = content;
+                content = null;
+            } else if (Array.isArray(content)) {
+                content = content.join("\n");
+            }
+            const items = _rust_rule_query(content == null ? null : toArg(content), toArg(rule), elements);
+            return JSON.parse(items || "[]");
+        };
+    }
+    const getElements = ruleQuery(true);
+    const getStrings = ruleQuery(false);
+
     // Recursive Proxy Handler
     // Allows chaining like utils.base64.encode()
     // The 'path' accumulates the access path (e.g. "utils.base64.encode")
@@ -17,6 +59,10 @@
                 if (prop === 'then' || prop === 'catch' || prop === 'toJSON') return undefined;
                 if (prop === 'toString' || prop === Symbol.toPrimitive) return () => "[NativeBridgeProxy " + path + "]";
                 
+                if (path === 'java' && prop === 'connect') return connect;
+                if (path === 'java' && prop === 'getElements') return getElements;
+                if (path === 'java' && prop === 'getStrings') return getStrings;
+
                 // Continue chaining
                 const nextPath = path ? (path + '.' + prop) : prop;
                 return createRecursiveProxy(nextPath);
@@ -40,11 +86,7 @@
                 }
 
                 // Convert args to strings
-                const strArgs = args.map(arg => {
-                    if (arg === null || arg === undefined) return "";
-                    if (typeof arg === 'object') return JSON.stringify(arg);
-                    return String(arg);
-                });
+                const strArgs = args.map(toArg);
                 
                 return _rust_native_call(ns, method, strArgs);
             }
@@ -3,8 +3,10 @@
 //! This module analyzes JavaScript rule code to identify patterns that can be
 //! executed natively in Rust, avoiding the need for QuickJS execution.
 
+use crate::engine::native::string_ops::js_regex_to_rust;
 use crate::engine::preprocessor::NativeApi;
-use regex::Regex;
+use crate::engine::rule_context::BOOK_VARIABLE;
+use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 
 use std::sync::OnceLock;
@@ -42,6 +44,30 @@ pub enum ExprValue {
     NativeCall(Box<NativeExecution>),
 }
 
+impl std::fmt::Display for NativeExecution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}(", self.api)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl std::fmt::Display for ExprValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExprValue::Literal(s) => write!(f, "{}", serde_json::Value::String(s.clone())),
+            ExprValue::Variable(name) => f.write_str(name),
+            ExprValue::CurrentContent => f.write_str("result"),
+            ExprValue::NativeCall(exec) => write!(f, "{}", exec),
+        }
+    }
+}
+
 /// Pattern definition with regex and converter
 struct JsPattern {
     regex: Regex,
@@ -80,7 +106,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Base64Encode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -92,7 +118,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Base64Decode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -104,7 +130,35 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Md5Encode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
+                })
+            }),
+        });
+
+        // java.encodeURI(variable, "charset")
+        patterns.push(JsPattern {
+            regex: Regex::new(&format!(
+                r#"^java\.encodeURI\(\s*([^,()]+?)\s*,\s*{}\s*\)$"#,
+                STRING_LITERAL
+            ))
+            .unwrap(),
+            converter: Box::new(|caps| {
+                let arg = caps.get(1)?.as_str().trim();
+                Some(NativeExecution {
+                    api: NativeApi::EncodeUriWithEnc(string_literal(caps, 2)?),
+                    args: vec![parse_arg(arg)?],
+                })
+            }),
+        });
+
+        // java.utf8ToGbk(variable)
+        patterns.push(JsPattern {
+            regex: Regex::new(r#"^java\.utf8ToGbk\(([^)]+)\)$"#).unwrap(),
+            converter: Box::new(|caps| {
+                let arg = caps.get(1)?.as_str().trim();
+                Some(NativeExecution {
+                    api: NativeApi::Utf8ToGbk,
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -116,7 +170,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::EncodeUri,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -128,7 +182,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::HexEncode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -140,7 +194,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::HexDecode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -166,7 +220,7 @@ impl JsPatternAnalyzer {
                     args: vec![if arg.is_empty() {
                         ExprValue::Literal(String::new())
                     } else {
-                        parse_arg(arg)
+                        parse_arg(arg)?
                     }],
                 })
             }),
@@ -184,7 +238,7 @@ impl JsPatternAnalyzer {
 
                 Some(NativeExecution {
                     api: NativeApi::TimeFormat(Some(format_str)),
-                    args: vec![parse_arg(timestamp)],
+                    args: vec![parse_arg(timestamp)?],
                 })
             }),
         });
@@ -203,19 +257,25 @@ impl JsPatternAnalyzer {
             }),
         });
 
-        // result.replace(/pattern/g, "replacement")
+        // result.replace(/pattern/flags, "replacement") / replaceAll(/pattern/g, ...)
         patterns.push(JsPattern {
-            regex: Regex::new(r#"^(\w+)\.replace\(/(.+?)/g?\s*,\s*["']([^"']*)["']\)$"#).unwrap(),
+            regex: Regex::new(&format!(
+                r#"^(\w+)\.(replace|replaceAll)\(/(.+?)/([a-z]*)\s*,\s*{}\)$"#,
+                STRING_LITERAL
+            ))
+            .unwrap(),
             converter: Box::new(|caps| {
                 let var = caps.get(1)?.as_str();
-                let pattern = caps.get(2)?.as_str();
-                let replacement = caps.get(3)?.as_str();
-                let global =
-                    caps.get(0)?.as_str().contains("/g,") || caps.get(0)?.as_str().contains("/g ");
+                let flags = caps.get(4)?.as_str();
+                let global = flags.contains('g');
+                // replaceAll throws on a non-global regex
+                if caps.get(2)?.as_str() == "replaceAll" && !global {
+                    return None;
+                }
                 Some(NativeExecution {
                     api: NativeApi::StringReplace {
-                        pattern: pattern.to_string(),
-                        replacement: replacement.to_string(),
+                        pattern: js_regex_to_rust(caps.get(3)?.as_str(), flags)?,
+                        replacement: string_literal(caps, 5)?,
                         is_regex: true,
                         global,
                     },
@@ -224,20 +284,22 @@ impl JsPatternAnalyzer {
             }),
         });
 
-        // result.replace("literal", "replacement")
+        // result.replace("literal", "replacement") / replaceAll("literal", ...)
         patterns.push(JsPattern {
-            regex: Regex::new(r#"^(\w+)\.replace\(["']([^"']+)["']\s*,\s*["']([^"']*)["']\)$"#)
-                .unwrap(),
+            regex: Regex::new(&format!(
+                r#"^(\w+)\.(replace|replaceAll)\({}\s*,\s*{}\)$"#,
+                STRING_LITERAL, STRING_LITERAL
+            ))
+            .unwrap(),
             converter: Box::new(|caps| {
                 let var = caps.get(1)?.as_str();
-                let pattern = caps.get(2)?.as_str();
-                let replacement = caps.get(3)?.as_str();
                 Some(NativeExecution {
                     api: NativeApi::StringReplace {
-                        pattern: pattern.to_string(),
-                        replacement: replacement.to_string(),
+                        pattern: string_literal(caps, 3)?,
+                        replacement: string_literal(caps, 5)?,
                         is_regex: false,
-                        global: true,
+                        // A string pattern only replaces the first occurrence
+                        global: caps.get(2)?.as_str() == "replaceAll",
                     },
                     args: vec![ExprValue::Variable(var.to_string())],
                 })
@@ -282,7 +344,7 @@ impl JsPatternAnalyzer {
                 let url_arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::HttpGet,
-                    args: vec![parse_arg(url_arg)],
+                    args: vec![parse_arg(url_arg)?],
                 })
             }),
         });
@@ -295,9 +357,9 @@ impl JsPatternAnalyzer {
                 let body = caps.get(2)?.as_str().trim();
                 let headers = caps.get(3).map(|m| m.as_str().trim());
 
-                let mut args = vec![parse_arg(url), parse_arg(body)];
+                let mut args = vec![parse_arg(url)?, parse_arg(body)?];
                 if let Some(h) = headers {
-                    args.push(parse_arg(h));
+                    args.push(parse_arg(h)?);
                 }
 
                 Some(NativeExecution {
@@ -314,7 +376,7 @@ impl JsPatternAnalyzer {
                 let url_arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::HttpGet,
-                    args: vec![parse_arg(url_arg)],
+                    args: vec![parse_arg(url_arg)?],
                 })
             }),
         });
@@ -344,7 +406,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::JsonParse,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -356,7 +418,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::JsonStringify,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -370,7 +432,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Base64Encode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -382,7 +444,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Base64Decode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -394,7 +456,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::Md5Encode,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -406,7 +468,7 @@ impl JsPatternAnalyzer {
                 let arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::EncodeUri,
-                    args: vec![parse_arg(arg)],
+                    args: vec![parse_arg(arg)?],
                 })
             }),
         });
@@ -429,7 +491,7 @@ impl JsPatternAnalyzer {
                 let url_arg = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::HttpGet,
-                    args: vec![parse_arg(url_arg)],
+                    args: vec![parse_arg(url_arg)?],
                 })
             }),
         });
@@ -492,12 +554,7 @@ impl JsPatternAnalyzer {
             converter: Box::new(|caps| {
                 let var = caps.get(1)?.as_str();
                 Some(NativeExecution {
-                    api: NativeApi::StringReplace {
-                        pattern: "__toLowerCase__".to_string(),
-                        replacement: String::new(),
-                        is_regex: false,
-                        global: true,
-                    },
+                    api: NativeApi::StringToLowerCase,
                     args: vec![ExprValue::Variable(var.to_string())],
                 })
             }),
@@ -508,12 +565,7 @@ impl JsPatternAnalyzer {
             converter: Box::new(|caps| {
                 let var = caps.get(1)?.as_str();
                 Some(NativeExecution {
-                    api: NativeApi::StringReplace {
-                        pattern: "__toUpperCase__".to_string(),
-                        replacement: String::new(),
-                        is_regex: false,
-                        global: true,
-                    },
+                    api: NativeApi::StringToUpperCase,
                     args: vec![ExprValue::Variable(var.to_string())],
                 })
             }),
@@ -579,22 +631,26 @@ impl JsPatternAnalyzer {
 
                 Some(NativeExecution {
                     api: NativeApi::JsonPath,
-                    args: vec![parse_arg(var), ExprValue::Literal(json_path)],
+                    args: vec![parse_arg(var)?, ExprValue::Literal(json_path)],
                 })
             }),
         });
 
         // === KV Storage Patterns ===
 
-        // java.put(key, value)
+        // java.put(key, value) / java.put(key, value, saveTime)
         patterns.push(JsPattern {
-            regex: Regex::new(r#"^java\.put\(([^,]+),\s*([^)]+)\)$"#).unwrap(),
+            regex: Regex::new(r#"^java\.put\(([^,]+),\s*([^,)]+)(?:,\s*([^,)]+))?\)$"#).unwrap(),
             converter: Box::new(|caps| {
                 let key = caps.get(1)?.as_str().trim();
                 let value = caps.get(2)?.as_str().trim();
+                let mut args = vec![parse_arg(key)?, parse_arg(value)?];
+                if let Some(save_time) = caps.get(3) {
+                    args.push(parse_arg(save_time.as_str().trim())?);
+                }
                 Some(NativeExecution {
                     api: NativeApi::CacheSet,
-                    args: vec![parse_arg(key), parse_arg(value)],
+                    args,
                 })
             }),
         });
@@ -606,7 +662,7 @@ impl JsPatternAnalyzer {
                 let key = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::CacheGet,
-                    args: vec![parse_arg(key)],
+                    args: vec![parse_arg(key)?],
                 })
             }),
         });
@@ -619,7 +675,7 @@ impl JsPatternAnalyzer {
                 let value = caps.get(2)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::SourceVarSet,
-                    args: vec![parse_arg(key), parse_arg(value)],
+                    args: vec![parse_arg(key)?, parse_arg(value)?],
                 })
             }),
         });
@@ -631,7 +687,23 @@ impl JsPatternAnalyzer {
                 let key = caps.get(1)?.as_str().trim();
                 Some(NativeExecution {
                     api: NativeApi::SourceVarGet,
-                    args: vec![parse_arg(key)],
+                    args: vec![parse_arg(key)?],
+                })
+            }),
+        });
+
+        // book.getVariable(key), reading the book's variable JSON from the context
+        patterns.push(JsPattern {
+            regex: Regex::new(r#"^book\.getVariable\(([^)]*)\)$"#).unwrap(),
+            converter: Box::new(|caps| {
+                let key = caps.get(1)?.as_str().trim();
+                let mut args = vec![ExprValue::Variable(BOOK_VARIABLE.to_string())];
+                if !key.is_empty() {
+                    args.push(parse_arg(key)?);
+                }
+                Some(NativeExecution {
+                    api: NativeApi::BookVarGet,
+                    args,
                 })
             }),
         });
@@ -766,23 +838,36 @@ impl JsPatternAnalyzer {
 }
 
 /// Parse argument to ExprValue
-fn parse_arg(arg: &str) -> ExprValue {
+///
+/// Returns `None` for anything that is not a plain string literal or
+/// identifier (template literals, concatenations, calls, ...) so the pattern
+/// is rejected and the AST analyzer gets to handle the expression.
+fn parse_arg(arg: &str) -> Option<ExprValue> {
     let arg = arg.trim();
 
     // String literal
-    if (arg.starts_with('"') && arg.ends_with('"'))
-        || (arg.starts_with('\'') && arg.ends_with('\''))
-    {
-        ExprValue::Literal(arg[1..arg.len() - 1].to_string())
+    if let Some(quote) = arg.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        let inner = arg.strip_prefix(quote)?.strip_suffix(quote)?;
+        return (!inner.contains(quote)).then(|| ExprValue::Literal(inner.to_string()));
     }
     // Special keywords
-    else if arg == "result" || arg == "content" || arg == "src" {
-        ExprValue::CurrentContent
+    if arg == "result" || arg == "content" || arg == "src" {
+        return Some(ExprValue::CurrentContent);
     }
     // Variable reference
-    else {
-        ExprValue::Variable(arg.to_string())
-    }
+    let is_identifier = !arg.is_empty()
+        && arg.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.');
+    is_identifier.then(|| ExprValue::Variable(arg.to_string()))
+}
+
+/// A single- or double-quoted JS string without escapes, as two capture groups
+const STRING_LITERAL: &str = r#"(?:"([^"\\]*)"|'([^'\\]*)')"#;
+
+/// Value of a [`STRING_LITERAL`] whose first capture group is `index`
+fn string_literal(caps: &Captures, index: usize) -> Option<String> {
+    caps.get(index)
+        .or_else(|| caps.get(index + 1))
+        .map(|m| m.as_str().to_string())
 }
 
 /// Extract string value from quoted or unquoted arg
@@ -838,6 +923,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_uri_with_charset() {
+        match analyzer().analyze(r#"java.encodeURI(key, "gbk")"#) {
+            AnalysisResult::Native(exec) => {
+                assert_eq!(exec.api, NativeApi::EncodeUriWithEnc("gbk".to_string()));
+                assert!(matches!(&exec.args[..], [ExprValue::Variable(var)] if var == "key"));
+            }
+            _ => panic!("Expected Native result"),
+        }
+        match analyzer().analyze("java.utf8ToGbk(key)") {
+            AnalysisResult::Native(exec) => assert_eq!(exec.api, NativeApi::Utf8ToGbk),
+            _ => panic!("Expected Native result"),
+        }
+    }
+
     #[test]
     fn test_random_uuid() {
         let result = analyzer().analyze("java.randomUUID()");
@@ -869,11 +969,12 @@ mod tests {
                     pattern,
                     replacement,
                     is_regex,
-                    ..
+                    global,
                 } => {
                     assert_eq!(pattern, "old");
                     assert_eq!(replacement, "new");
                     assert!(!is_regex);
+                    assert!(!global);
                 }
                 _ => panic!("Expected StringReplace API"),
             },
@@ -952,6 +1053,15 @@ mod tests {
             assert_eq!(exec.api, NativeApi::CacheSet);
         }
 
+        // java.put with saveTime keeps the third argument
+        let result = analyzer.analyze("java.put('token', 'abc', 3600)");
+        if let AnalysisResult::Native(exec) = result {
+            assert_eq!(exec.api, NativeApi::CacheSet);
+            assert_eq!(exec.args.len(), 3);
+        } else {
+            panic!("Expected native java.put with saveTime");
+        }
+
         // java.get
         let result = analyzer.analyze("java.get('key')");
         assert!(matches!(result, AnalysisResult::Native(_)));
@@ -963,6 +1073,13 @@ mod tests {
         // source.getVariable
         let result = analyzer.analyze("source.getVariable('k')");
         assert!(matches!(result, AnalysisResult::Native(_)));
+
+        // book.getVariable reads the book's variable JSON
+        let result = analyzer.analyze("book.getVariable('token')");
+        assert!(matches!(
+            result,
+            AnalysisResult::Native(exec) if exec.api == NativeApi::BookVarGet && exec.args.len() == 2
+        ));
     }
 
     #[test]
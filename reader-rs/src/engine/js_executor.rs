@@ -2,39 +2,224 @@
 //!
 //! Provides ES2023 JavaScript execution with custom utils.* API
 
-use super::native_api::NativeApiProvider;
+use super::error::EngineError;
+use super::http_client::StrResponse;
+use super::native_api::{ExecutionContext, NativeApiProvider};
+use super::stats::STATS;
 use anyhow::Result;
 use rquickjs::{Context, Ctx, Function, IntoJs, Object, Runtime, Value};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Cache for JavaScript context data
 pub type JsCache = Arc<Mutex<HashMap<String, String>>>;
 
+/// Backend of `java.getElements` / `java.getStrings`: evaluates a rule on
+/// content, returning elements when the flag is set and strings otherwise
+pub type RuleQuery = Arc<dyn Fn(&str, &str, bool) -> Result<Vec<String>> + Send + Sync>;
+
+/// Responses kept for `java.connect` results; older ones are dropped
+const MAX_STORED_RESPONSES: usize = 32;
+
+/// Methods of the JS `book` binding; `variable` holds the book's variables as JSON
+const BOOK_METHODS: &str = r#"
+book.getVariable = function (key) {
+    var map = {};
+    try { map = JSON.parse(this.variable || "{}") || {}; } catch (e) {}
+    var value = map[key === undefined ? "custom" : key];
+    return value == null ? "" : String(value);
+};
+book.getCustomVariable = function () { return this.getVariable("custom"); };
+"#;
+
+/// Wall-clock limit of one evaluation unless the call site sets another
+pub const DEFAULT_JS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Memory one runtime may allocate; beyond it scripts get an out-of-memory error
+pub const JS_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Per-session state of an executor shared by several parse sessions
+///
+/// Installed for the duration of one evaluation while the runtime lock is
+/// held, so sessions evaluating concurrently never see each other's bindings.
+#[derive(Debug, Clone)]
+pub struct JsScope {
+    /// JSON of the `book` binding, empty when unset
+    pub book_json: String,
+    /// JSON of the `chapter` binding, empty when unset
+    pub chapter_json: String,
+    /// Content `java.getElements` / `java.getStrings` read when called without content
+    pub current_content: String,
+    /// URL of the page being parsed, bound to `baseUrl` instead of the source's base URL
+    pub base_url: Option<String>,
+    /// Wall-clock limit of one evaluation
+    pub timeout: Duration,
+    /// Raised by the caller to stop in-flight JS, e.g. when a search is cancelled
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for JsScope {
+    fn default() -> Self {
+        Self {
+            book_json: String::new(),
+            chapter_json: String::new(),
+            current_content: String::new(),
+            base_url: None,
+            timeout: DEFAULT_JS_TIMEOUT,
+            cancel: None,
+        }
+    }
+}
+
+/// Why the interrupt handler stopped the running script
+const STOPPED_NONE: u8 = 0;
+const STOPPED_TIMEOUT: u8 = 1;
+const STOPPED_CANCELLED: u8 = 2;
+
+/// Execution limits checked by the QuickJS interrupt handler
+struct JsLimits {
+    timeout: Mutex<Duration>,
+    /// End of the running evaluation; `None` between evaluations
+    deadline: Mutex<Option<Instant>>,
+    /// Raised by the caller to stop in-flight JS, e.g. when a search is cancelled
+    cancel: Mutex<Option<Arc<AtomicBool>>>,
+    stopped: AtomicU8,
+}
+
+impl JsLimits {
+    fn new() -> Self {
+        Self {
+            timeout: Mutex::new(DEFAULT_JS_TIMEOUT),
+            deadline: Mutex::new(None),
+            cancel: Mutex::new(None),
+            stopped: AtomicU8::new(STOPPED_NONE),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout.lock().map(|t| *t).unwrap_or(DEFAULT_JS_TIMEOUT)
+    }
+
+    /// Whether the running script must be interrupted
+    fn should_interrupt(&self) -> bool {
+        let cancelled = self
+            .cancel
+            .lock()
+            .ok()
+            .and_then(|flag| flag.as_ref().map(|f| f.load(Ordering::Relaxed)))
+            .unwrap_or(false);
+        let stopped = if cancelled {
+            STOPPED_CANCELLED
+        } else if self
+            .deadline
+            .lock()
+            .ok()
+            .and_then(|d| *d)
+            .is_some_and(|d| Instant::now() >= d)
+        {
+            STOPPED_TIMEOUT
+        } else {
+            return false;
+        };
+        self.stopped.store(stopped, Ordering::SeqCst);
+        true
+    }
+
+    /// Start the clock with the limits of `scope` unless an enclosing evaluation already did
+    fn arm(&self, scope: &JsScope) -> bool {
+        let Ok(mut deadline) = self.deadline.lock() else {
+            return false;
+        };
+        if deadline.is_some() {
+            return false;
+        }
+        if let Ok(mut timeout) = self.timeout.lock() {
+            *timeout = scope.timeout;
+        }
+        if let Ok(mut cancel) = self.cancel.lock() {
+            cancel.clone_from(&scope.cancel);
+        }
+        *deadline = Some(Instant::now() + scope.timeout);
+        self.stopped.store(STOPPED_NONE, Ordering::SeqCst);
+        true
+    }
+
+    /// Stop the clock, returning why the script was interrupted, if it was
+    fn disarm(&self) -> u8 {
+        if let Ok(mut deadline) = self.deadline.lock() {
+            *deadline = None;
+        }
+        self.stopped.swap(STOPPED_NONE, Ordering::SeqCst)
+    }
+}
+
+/// Rust-side storage behind the JS `StrResponse` objects returned by `java.connect`
+#[derive(Default)]
+struct ResponseStore {
+    next_id: i32,
+    responses: VecDeque<(i32, StrResponse)>,
+}
+
+impl ResponseStore {
+    fn insert(&mut self, response: StrResponse) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.responses.len() >= MAX_STORED_RESPONSES {
+            self.responses.pop_front();
+        }
+        self.responses.push_back((id, response));
+        id
+    }
+
+    /// Read a response field for the JS wrapper; `None` becomes `null`
+    fn field(&self, id: i32, field: &str, arg: &str) -> Option<String> {
+        let (_, response) = self.responses.iter().find(|(i, _)| *i == id)?;
+        match field {
+            "body" => Some(response.body.clone()),
+            "code" => Some(response.status_code.to_string()),
+            "url" => Some(response.url.clone()),
+            "header" => response.header(arg).map(|s| s.to_string()),
+            "headers" => serde_json::to_string(&response.headers).ok(),
+            "cookie" => response.cookie(arg),
+            _ => None,
+        }
+    }
+}
+
 /// JavaScript executor using QuickJS engine
 pub struct JsExecutor {
     runtime: Runtime,
     context: Context,
     cache: JsCache,
     base_url: String,
+    /// `bookSourceUrl` scoping source variables, exposed to the bridge as `__source_url__`
+    source_url: String,
     /// Flag to track if utils have been registered (to avoid re-registering and losing jsLib)
     initialized: AtomicBool,
     /// Source JSON for `source` binding (book source info)
-    source_json: std::cell::RefCell<String>,
-    /// Book JSON for `book` binding
-    book_json: std::cell::RefCell<String>,
-    /// Chapter JSON for `chapter` binding
-    /// Chapter JSON for `chapter` binding
-    chapter_json: std::cell::RefCell<String>,
+    source_json: parking_lot::Mutex<String>,
+    /// Bindings and limits of evaluations that do not pass their own scope
+    scope: parking_lot::Mutex<JsScope>,
     /// Native API provider for delegated execution
     native_api: Arc<NativeApiProvider>,
+    /// Responses returned to JS by `java.connect`
+    responses: Arc<Mutex<ResponseStore>>,
+    /// Rule evaluation for `java.getElements` / `java.getStrings`
+    rule_query: Option<RuleQuery>,
+    /// Time limit and cancellation flag of evaluations
+    limits: Arc<JsLimits>,
 }
 
 impl JsExecutor {
     /// Create a new JavaScript executor
     pub fn new(native_api: Arc<NativeApiProvider>) -> Result<Self> {
         let runtime = Runtime::new()?;
+        runtime.set_memory_limit(JS_MEMORY_LIMIT);
+        let limits = Arc::new(JsLimits::new());
+        let handler_limits = limits.clone();
+        runtime.set_interrupt_handler(Some(Box::new(move || handler_limits.should_interrupt())));
         let context = Context::full(&runtime)?;
 
         Ok(Self {
@@ -42,32 +227,89 @@ impl JsExecutor {
             context,
             cache: Arc::new(Mutex::new(HashMap::new())),
             base_url: String::new(),
+            source_url: String::new(),
             initialized: AtomicBool::new(false),
-            source_json: std::cell::RefCell::new(String::new()),
-            book_json: std::cell::RefCell::new(String::new()),
-            chapter_json: std::cell::RefCell::new(String::new()),
+            source_json: parking_lot::Mutex::new(String::new()),
+            scope: parking_lot::Mutex::new(JsScope::default()),
             native_api,
+            responses: Arc::new(Mutex::new(ResponseStore::default())),
+            rule_query: None,
+            limits,
+        })
+    }
+
+    /// Limit each evaluation to `timeout` of wall-clock time
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.scope.lock().timeout = timeout;
+    }
+
+    /// Interrupt evaluations once `flag` is raised
+    pub fn set_cancel_flag(&self, flag: Arc<AtomicBool>) {
+        self.scope.lock().cancel = Some(flag);
+    }
+
+    /// Run `f` in the context with the limits of `scope` armed
+    ///
+    /// Arming happens under the runtime lock, so evaluations of other sessions
+    /// waiting for the lock cannot change the limits of the running one.
+    fn with_limits<T>(&self, scope: &JsScope, f: impl for<'js> FnOnce(Ctx<'js>) -> Result<T>) -> Result<T> {
+        self.context.with(|ctx| {
+            let armed = self.limits.arm(scope);
+            let result = f(ctx);
+            self.finish_limited(armed, result)
         })
     }
 
+    /// Stop the clock started by `self.limits.arm()` and map an interrupted evaluation
+    ///
+    /// A script stopped by the interrupt handler fails with
+    /// `EngineError::JsTimeout` / `EngineError::JsCancelled`; the runtime stays usable.
+    fn finish_limited<T>(&self, armed: bool, result: Result<T>) -> Result<T> {
+        if !armed {
+            return result;
+        }
+        match (result, self.limits.disarm()) {
+            (Err(_), STOPPED_TIMEOUT) => {
+                let timeout = self.limits.timeout();
+                STATS.record_js_timeout();
+                tracing::warn!("JS of source {} exceeded {:?}, interrupted", self.source_url, timeout);
+                Err(EngineError::js_timeout(&self.source_url, timeout).into())
+            }
+            (Err(_), STOPPED_CANCELLED) => Err(EngineError::JsCancelled.into()),
+            (result, _) => result,
+        }
+    }
+
+    /// Back `java.getElements` / `java.getStrings` with `query`
+    ///
+    /// Must be set before the first evaluation, which registers the bridge.
+    pub fn set_rule_query(&mut self, query: RuleQuery) {
+        self.rule_query = Some(query);
+    }
+
     /// Set base URL for relative URL resolution
     pub fn set_base_url(&mut self, url: &str) {
         self.base_url = url.to_string();
     }
 
+    /// Set the source whose variables `source.getVariable()` reads
+    pub fn set_source_url(&mut self, url: &str) {
+        self.source_url = url.to_string();
+    }
+
     /// Set source JSON for JS `source` binding
     pub fn set_source(&self, source_json: &str) {
-        *self.source_json.borrow_mut() = source_json.to_string();
+        *self.source_json.lock() = source_json.to_string();
     }
 
     /// Set book JSON for JS `book` binding
     pub fn set_book(&self, book_json: &str) {
-        *self.book_json.borrow_mut() = book_json.to_string();
+        self.scope.lock().book_json = book_json.to_string();
     }
 
     /// Set chapter JSON for JS `chapter` binding
     pub fn set_chapter(&self, chapter_json: &str) {
-        *self.chapter_json.borrow_mut() = chapter_json.to_string();
+        self.scope.lock().chapter_json = chapter_json.to_string();
     }
 
     /// Preload JavaScript library code (jsLib from book source)
@@ -84,7 +326,8 @@ impl JsExecutor {
             return Ok(());
         }
 
-        self.context.with(|ctx| {
+        let scope = self.scope.lock().clone();
+        self.with_limits(&scope, |ctx| {
             // Register utils first so jsLib can use them
             self.register_universal_bridge(&ctx)?;
 
@@ -146,7 +389,11 @@ impl JsExecutor {
                     };
 
                     tracing::error!("CRITICAL: Failed to load jsLib: {}", exception_msg);
-                    Ok(())
+                    // An interrupted jsLib is reported by `finish_limited`
+                    match self.limits.stopped.load(Ordering::SeqCst) {
+                        STOPPED_NONE => Ok(()),
+                        _ => Err(EngineError::javascript(exception_msg).into()),
+                    }
                 }
             }
         })
@@ -154,9 +401,7 @@ impl JsExecutor {
 
     /// Set current content in the JS context (for java.getString)
     pub fn set_current_content(&self, content: &str) {
-        if let Ok(mut cache) = self.cache.lock() {
-            cache.insert("__current_content__".to_string(), content.to_string());
-        }
+        self.scope.lock().current_content = content.to_string();
     }
 
     /// Put a variable into the JS session cache
@@ -168,7 +413,8 @@ impl JsExecutor {
 
     /// Evaluate a JS rule within the engine context
     pub fn eval(&self, code: &str) -> Result<String> {
-        self.context.with(|ctx| {
+        let scope = self.scope.lock().clone();
+        self.with_limits(&scope, |ctx| {
             // Register utils object only if not already initialized
             if !self.initialized.load(Ordering::SeqCst) {
                 self.register_universal_bridge(&ctx)?;
@@ -185,14 +431,20 @@ impl JsExecutor {
 
     /// Evaluate with context variables
     pub fn eval_with_context(&self, code: &str, vars: &HashMap<String, String>) -> Result<String> {
-        let base_url = self.base_url.clone();
+        let scope = self.scope.lock().clone();
+        self.eval_in_scope(code, vars, &scope)
+    }
+
+    /// Evaluate with context variables and the bindings and limits of `scope`
+    pub fn eval_in_scope(&self, code: &str, vars: &HashMap<String, String>, scope: &JsScope) -> Result<String> {
+        let base_url = scope.base_url.clone().unwrap_or_else(|| self.base_url.clone());
 
         tracing::debug!(
-            "eval_with_context called, initialized={}",
+            "eval_in_scope called, initialized={}",
             self.initialized.load(Ordering::SeqCst)
         );
 
-        self.context.with(|ctx| {
+        self.with_limits(scope, |ctx| {
             // Register utils object only if not already initialized
             if !self.initialized.load(Ordering::SeqCst) {
                 tracing::debug!("Registering utils (first time)");
@@ -204,6 +456,10 @@ impl JsExecutor {
             let globals = ctx.globals();
             tracing::debug!("Setting {} context variables", vars.len());
             for (key, value) in vars {
+                // Dotted context keys (book.name, ...) reach JS through the book/chapter objects
+                if key.contains('.') {
+                    continue;
+                }
                 if key == "result" {
                     tracing::debug!("Setting JS var result len={}", value.len());
                 }
@@ -231,35 +487,48 @@ impl JsExecutor {
                 }
             }
 
-            // Set baseUrl
-            globals.set("baseUrl", base_url.as_str())?;
+            // Set baseUrl unless the caller passed the page URL
+            if !vars.contains_key("baseUrl") {
+                globals.set("baseUrl", base_url.as_str())?;
+            }
+            globals.set("__source_url__", self.source_url.as_str())?;
+
+            if let Ok(mut cache) = self.cache.lock() {
+                cache.insert("__current_content__".to_string(), scope.current_content.clone());
+            }
 
             // Set source/book/chapter bindings for Java parity
-            let source_json = self.source_json.borrow();
+            let source_json = self.source_json.lock();
             if !source_json.is_empty() {
                 if let Ok(v) = ctx.json_parse(source_json.as_str()) {
                     let _ = globals.set("source", v);
                 }
             }
 
-            let book_json = self.book_json.borrow();
-            if !book_json.is_empty() {
-                if let Ok(v) = ctx.json_parse(book_json.as_str()) {
-                    let _ = globals.set("book", v);
+            // Bindings of an earlier book/chapter must not leak into this evaluation
+            let unset = |name: &str| {
+                if !vars.contains_key(name) {
+                    let _ = globals.remove(name);
                 }
+            };
+            let book_json = &scope.book_json;
+            if book_json.is_empty() {
+                unset("book");
+            } else if let Ok(v) = ctx.json_parse(book_json.as_str()) {
+                let _ = globals.set("book", v);
+                ctx.eval::<(), _>(BOOK_METHODS)?;
             }
 
-            let chapter_json = self.chapter_json.borrow();
-            if !chapter_json.is_empty() {
-                if let Ok(v) = ctx.json_parse(chapter_json.as_str()) {
-                    let _ = globals.set("chapter", v);
-                    // Also extract title for convenience
-                    if let Ok(parsed) =
-                        serde_json::from_str::<serde_json::Value>(chapter_json.as_str())
-                    {
-                        if let Some(title) = parsed.get("title").and_then(|t| t.as_str()) {
-                            let _ = globals.set("title", title);
-                        }
+            let chapter_json = &scope.chapter_json;
+            if chapter_json.is_empty() {
+                unset("chapter");
+                unset("title");
+            } else if let Ok(v) = ctx.json_parse(chapter_json.as_str()) {
+                let _ = globals.set("chapter", v);
+                // Also extract title for convenience
+                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(chapter_json.as_str()) {
+                    if let Some(title) = parsed.get("title").and_then(|t| t.as_str()) {
+                        let _ = globals.set("title", title);
                     }
                 }
             }
@@ -296,7 +565,7 @@ impl JsExecutor {
                         "JS code that failed: {}",
                         code.chars().take(500).collect::<String>()
                     );
-                    Err(anyhow::anyhow!("JS eval error: {}", exception_msg))
+                    Err(EngineError::javascript(exception_msg).into())
                 }
             }
         })
@@ -319,8 +588,9 @@ impl JsExecutor {
                     // 2. Build Context
                     let globals = ctx.globals();
                     let base_url: String = globals.get("baseUrl").unwrap_or_default();
+                    let source_url: String = globals.get("__source_url__").unwrap_or_default();
                     let execution_context =
-                        crate::engine::native_api::ExecutionContext { base_url };
+                        crate::engine::native_api::ExecutionContext { base_url, source_url };
 
                     // 3. Execute via provider
                     api_provider
@@ -330,6 +600,68 @@ impl JsExecutor {
             )?,
         )?;
 
+        // java.connect(url, header): run the request and keep the response on the Rust side,
+        // returning a handle the shim wraps in a StrResponse object
+        let api_provider = self.native_api.clone();
+        let responses = self.responses.clone();
+        ctx.globals().set(
+            "_rust_http_connect",
+            Function::new(
+                ctx.clone(),
+                move |ctx: Ctx, url: String, headers: String| -> i32 {
+                    let base_url: String = ctx.globals().get("baseUrl").unwrap_or_default();
+                    let headers = Some(headers.as_str()).filter(|h| !h.is_empty());
+                    let context = ExecutionContext { base_url, ..Default::default() };
+                    match api_provider.connect(&url, headers, &context) {
+                        Ok(response) => responses.lock().map(|mut r| r.insert(response)).unwrap_or(-1),
+                        Err(e) => {
+                            tracing::warn!("java.connect failed for {}: {:#}", url, e);
+                            -1
+                        }
+                    }
+                },
+            )?,
+        )?;
+
+        let responses = self.responses.clone();
+        ctx.globals().set(
+            "_rust_response",
+            Function::new(
+                ctx.clone(),
+                move |id: i32, field: String, arg: String| -> Option<String> {
+                    responses.lock().ok()?.field(id, &field, &arg)
+                },
+            )?,
+        )?;
+
+        // java.getElements / java.getStrings: items as a JSON array; without
+        // content the rule runs on the current content
+        if let Some(query) = self.rule_query.clone() {
+            let cache = self.cache.clone();
+            ctx.globals().set(
+                "_rust_rule_query",
+                Function::new(
+                    ctx.clone(),
+                    move |content: Option<String>, rule: String, elements: bool| -> String {
+                        let content = content.unwrap_or_else(|| {
+                            cache
+                                .lock()
+                                .ok()
+                                .and_then(|c| c.get("__current_content__").cloned())
+                                .unwrap_or_default()
+                        });
+                        match query(&content, &rule, elements) {
+                            Ok(items) => serde_json::to_string(&items).unwrap_or_default(),
+                            Err(e) => {
+                                tracing::warn!("Rule query '{}' from JS failed: {:#}", rule, e);
+                                "[]".to_string()
+                            }
+                        }
+                    },
+                )?,
+            )?;
+        }
+
         // Inject the JS Shim to create proxies
         ctx.eval::<(), _>(include_str!("js/shim.js"))?;
 
@@ -378,6 +710,7 @@ mod tests {
     use super::*;
     use crate::engine::cookie::CookieManager;
     use crate::engine::native_api::NativeApiProvider;
+    use crate::engine::test_server::{header, spawn_server};
     use crate::storage::kv::KvStore;
     use crate::storage::FileStorage;
 
@@ -465,6 +798,18 @@ mod tests {
         assert_eq!(result, "myvalue");
     }
 
+    #[test]
+    fn test_java_put_save_time() {
+        let executor = JsExecutor::new(create_test_native_api()).unwrap();
+        executor.eval("java.put('ttl_key', 'short', 1)").unwrap();
+        executor.eval("java.put('ttl_forever', 'kept', 0)").unwrap();
+        assert_eq!(executor.eval("java.get('ttl_key')").unwrap(), "short");
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(executor.eval("java.get('ttl_key')").unwrap(), "");
+        assert_eq!(executor.eval("java.get('ttl_forever')").unwrap(), "kept");
+    }
+
     #[test]
     fn test_java_random_uuid() {
         let executor = JsExecutor::new(create_test_native_api()).unwrap();
@@ -535,6 +880,65 @@ mod tests {
         assert_eq!(k2, "v2");
     }
 
+    /// Echo the method, path, `X-Test` header and body of each request
+    fn spawn_echo_server() -> String {
+        spawn_server(|head, body| {
+            let mut parts = head.split_whitespace();
+            let payload = format!(
+                "{} {} x-test={} body={}",
+                parts.next().unwrap_or(""),
+                parts.next().unwrap_or(""),
+                header(head, "x-test").unwrap_or(""),
+                body
+            );
+            let headers = vec![
+                ("Content-Type", "text/plain; charset=utf-8".to_string()),
+                ("X-Token", "abc123".to_string()),
+                ("Set-Cookie", "sid=s1; Path=/".to_string()),
+            ];
+            (201, headers, payload)
+        })
+    }
+
+    #[test]
+    fn test_java_ajax_options() {
+        let base = spawn_echo_server();
+        let executor = JsExecutor::new(create_test_native_api()).unwrap();
+
+        let result = executor
+            .eval(&format!(
+                r#"java.ajax(JSON.stringify({{url: "{}/search", method: "POST", body: "key=abc", headers: {{"X-Test": "yes"}}}}))"#,
+                base
+            ))
+            .unwrap();
+        assert_eq!(result, "POST /search x-test=yes body=key=abc");
+
+        let result = executor
+            .eval(&format!(
+                r#"java.ajax('{}/list,{{"method":"POST","body":"page=2","headers":{{"X-Test":"suffix"}}}}')"#,
+                base
+            ))
+            .unwrap();
+        assert_eq!(result, "POST /list x-test=suffix body=page=2");
+    }
+
+    #[test]
+    fn test_java_connect_response() {
+        let base = spawn_echo_server();
+        let mut executor = JsExecutor::new(create_test_native_api()).unwrap();
+        executor.set_base_url(&base);
+
+        let rule = r#"
+            var res = java.connect('/book/1', '{"X-Test":"h"}');
+            [res.code(), res.header('x-token'), res.header('X-Missing'), res.cookie('sid'), res.url(), res.body()].join('|')
+        "#;
+        let result = executor.eval_with_context(rule, &HashMap::new()).unwrap();
+        assert_eq!(
+            result,
+            format!("201|abc123||s1|{}/book/1|GET /book/1 x-test=h body=", base)
+        );
+    }
+
     #[test]
     fn test_java_overloaded_get() {
         let executor = JsExecutor::new(create_test_native_api()).unwrap();
@@ -549,6 +953,60 @@ mod tests {
         // Universal Bridge returns JSON string, not object
         assert_eq!(result, "string");
     }
+
+    #[test]
+    fn test_runaway_script_times_out() {
+        let mut executor = JsExecutor::new(create_test_native_api()).unwrap();
+        executor.set_source_url("https://loop.example.com");
+
+        let started = Instant::now();
+        let err = executor
+            .eval_with_context("while(true){}", &HashMap::new())
+            .unwrap_err();
+        assert!(started.elapsed() < DEFAULT_JS_TIMEOUT + Duration::from_secs(1));
+        match err.downcast_ref::<EngineError>() {
+            Some(EngineError::JsTimeout { source_url, timeout_ms }) => {
+                assert_eq!(source_url, "https://loop.example.com");
+                assert_eq!(*timeout_ms, 5000);
+            }
+            other => panic!("expected JsTimeout, got {:?}", other),
+        }
+
+        // The runtime stays usable after the interrupt
+        assert_eq!(executor.eval("1 + 2").unwrap(), "3");
+        assert_eq!(
+            executor.eval_with_context("[1, 2].join('-')", &HashMap::new()).unwrap(),
+            "1-2"
+        );
+    }
+
+    #[test]
+    fn test_cancel_flag_and_memory_limit() {
+        let executor = JsExecutor::new(create_test_native_api()).unwrap();
+        executor.set_timeout(Duration::from_secs(30));
+        let flag = Arc::new(AtomicBool::new(false));
+        executor.set_cancel_flag(flag.clone());
+
+        let canceller = {
+            let flag = flag.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                flag.store(true, Ordering::Relaxed);
+            })
+        };
+        let started = Instant::now();
+        let err = executor.eval("while(true){}").unwrap_err();
+        canceller.join().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            err.downcast_ref::<EngineError>(),
+            Some(EngineError::JsCancelled)
+        ));
+
+        flag.store(false, Ordering::Relaxed);
+        assert!(executor.eval("let s = 'x'; while (true) { s += s; }").is_err());
+        assert_eq!(executor.eval("1 + 2").unwrap(), "3");
+    }
 }
 
 /// Ensure bytes array is exactly 16 bytes (for AES-128)
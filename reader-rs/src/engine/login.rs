@@ -2,15 +2,20 @@
 //!
 //! This module provides:
 //! - Login URL parsing
+//! - Login flow execution (loginUrl request or script, then loginCheckJs)
 //! - Login check JavaScript execution
 //! - Session/cookie persistence
 
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
 
+use super::book_source::BookSource;
 use super::cookie::CookieManager;
+use super::http_client::HttpClient;
 use super::js_executor::JsExecutor;
+use super::rule_analyzer::RuleAnalyzer;
 
 /// Login information parsed from loginUrl
 #[derive(Debug, Clone, Default)]
@@ -75,6 +80,118 @@ impl LoginInfo {
     }
 }
 
+/// Outcome of a login attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoginResult {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Run a source's login flow with the fields submitted from its loginUi form
+///
+/// A URL (or JSON request config) loginUrl is requested with `{{field}}`
+/// templates filled in; any other loginUrl is a script run with the fields
+/// as `result`, calling its `login()` function when it defines one. The
+/// login response is then passed as `result` to loginCheckJs, which must
+/// return true for the login to count as successful.
+///
+/// Cookies set along the way land in `http`'s cookie jar.
+pub fn run_login(
+    source: &BookSource,
+    fields: &HashMap<String, String>,
+    http: &HttpClient,
+    analyzer: &RuleAnalyzer,
+) -> Result<LoginResult> {
+    let login_url = source
+        .login_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| anyhow!("Book source has no loginUrl"))?;
+
+    let response = if is_login_script(login_url) {
+        let mut code = strip_js_prefix(login_url).to_string();
+        if code.contains("function login") {
+            code.push_str("\nlogin()");
+        }
+        let mut vars = HashMap::new();
+        vars.insert("result".to_string(), serde_json::to_string(fields)?);
+        analyzer.eval_js(&code, &vars)?
+    } else if login_url.starts_with('{') {
+        let info = LoginInfo::parse(login_url).unwrap_or_default();
+        let mut config = http.parse_request_config(&analyzer.process_templates(&info.url, fields));
+        if let Some(method) = info.method {
+            config.method = method.to_uppercase();
+        }
+        config.body = info.body.map(|body| analyzer.process_templates(&body, fields));
+        if let Some(headers) = info.headers {
+            config.headers.get_or_insert_with(HashMap::new).extend(headers);
+        }
+        http.request(&config)?
+    } else {
+        let url = analyzer.process_url_templates(login_url, fields);
+        http.request(&http.parse_request_config(&url))?
+    };
+
+    let check_js = match source.login_check_js.as_deref().map(str::trim) {
+        Some(js) if !js.is_empty() => strip_js_prefix(js),
+        _ => {
+            return Ok(LoginResult {
+                success: true,
+                message: "Login request completed".to_string(),
+            })
+        }
+    };
+    let mut vars = HashMap::new();
+    vars.insert("result".to_string(), response);
+    let checked = analyzer.eval_js(check_js, &vars)?;
+    Ok(if is_logged_in(&checked) {
+        LoginResult {
+            success: true,
+            message: "Logged in".to_string(),
+        }
+    } else {
+        LoginResult {
+            success: false,
+            message: format!("loginCheckJs returned {}", checked),
+        }
+    })
+}
+
+/// Drop the login session of a source: cookies of its host and its login host
+pub fn logout(book_source_url: &str, login_url: Option<&str>, cookie_manager: &CookieManager) {
+    for url in [Some(book_source_url), login_url].into_iter().flatten() {
+        if let Some(host) = reqwest::Url::parse(url.trim()).ok().and_then(|u| u.host_str().map(str::to_string)) {
+            cookie_manager.clear_cookies(&host);
+        }
+    }
+}
+
+/// Whether loginUrl is a script rather than a URL or request config
+fn is_login_script(login_url: &str) -> bool {
+    let url = login_url.trim_start();
+    !(url.starts_with("http://")
+        || url.starts_with("https://")
+        || url.starts_with('/')
+        || url.starts_with('{'))
+}
+
+fn strip_js_prefix(code: &str) -> &str {
+    let code = code.trim();
+    if let Some(code) = code.strip_prefix("@js:") {
+        return code;
+    }
+    code.strip_prefix("<js>")
+        .map(|c| c.strip_suffix("</js>").unwrap_or(c))
+        .unwrap_or(code)
+}
+
+/// Whether a loginCheckJs result means "logged in"
+fn is_logged_in(result: &str) -> bool {
+    let result = result.trim();
+    result.eq_ignore_ascii_case("true") || result == "1"
+}
+
 /// Login status for a book source
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoginStatus {
@@ -138,9 +255,8 @@ impl LoginManager {
         
         // Execute check JS
         let result = js_executor.eval(check_js)?;
-        let result_lower = result.to_lowercase();
         
-        let status = if result_lower == "true" || result == "1" {
+        let status = if is_logged_in(&result) {
             LoginStatus::LoggedIn
         } else {
             LoginStatus::NotLoggedIn
@@ -221,6 +337,16 @@ mod tests {
         assert_eq!(info.body.as_deref(), Some("user=test"));
     }
     
+    #[test]
+    fn test_login_script_detection() {
+        assert!(!is_login_script("https://example.com/login"));
+        assert!(!is_login_script("/login,{\"method\":\"POST\"}"));
+        assert!(!is_login_script(r#"{"url": "/login"}"#));
+        assert!(is_login_script("function login() { return java.ajax(source.getKey()) }"));
+        assert_eq!(strip_js_prefix("<js>login()</js>"), "login()");
+        assert_eq!(strip_js_prefix("@js:login()"), "login()");
+    }
+
     #[test]
     fn test_parse_empty() {
         assert!(LoginInfo::parse("").is_none());
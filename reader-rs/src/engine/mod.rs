@@ -1,13 +1,21 @@
 // New engine modules (rquickjs-based)
+pub mod anti_ban;
 pub mod book_source;
 pub mod config;
 pub mod cookie;
+pub mod deadline;
+pub mod engine_cache;
+pub mod http_cache;
 pub mod http_client;
 pub mod js_executor;
 pub mod login;
 pub mod parsers;
+pub mod pinyin;
 pub mod query_ttf;
 pub mod rule_analyzer;
+pub mod rule_cache;
+pub mod rule_context;
+pub mod rule_validator;
 pub mod utils;
 pub mod webview;
 pub mod flaresolverr;
@@ -23,6 +31,7 @@ pub mod native_executor;
 pub mod native_file;
 pub mod native_http;
 pub mod preprocessor;
+pub mod source_inspector;
 pub mod source_transformer;
 pub mod template;
 
@@ -30,6 +39,7 @@ pub mod template;
 pub mod java_api_mapping;
 pub mod source_rewriter;
 pub mod stats;
+pub mod trace;
 
 // AST-based JavaScript analysis (Oxc)
 pub mod ast;
@@ -52,3 +62,7 @@ pub mod prelude;
 // Benchmark tests
 #[cfg(test)]
 mod benchmarks;
+
+// Shared HTTP stub for tests
+#[cfg(test)]
+pub(crate) mod test_server;
@@ -61,16 +61,12 @@ impl ApiHandler for EncodingHandler {
             NativeApi::Base64DecodeWithFlags(flags) => {
                 super::encoding::base64_decode_with_flags(input, *flags)
             }
-            NativeApi::EncodeUri => super::encoding::encode_uri(input),
-            NativeApi::EncodeUriWithEnc(enc) => {
-                if enc.eq_ignore_ascii_case("gbk") || enc.eq_ignore_ascii_case("gb2312") {
-                    use encoding_rs::GBK;
-                    let (encoded, _, _) = GBK.encode(input);
-                    Ok(urlencoding::encode_binary(&encoded).to_string())
-                } else {
-                    super::encoding::encode_uri(input)
-                }
-            }
+            // java.encodeURI(str, charset) with a charset only known at run time
+            NativeApi::EncodeUri => match args.get(1) {
+                Some(enc) => super::encoding::encode_uri_with_enc(input, enc),
+                None => super::encoding::encode_uri(input),
+            },
+            NativeApi::EncodeUriWithEnc(enc) => super::encoding::encode_uri_with_enc(input, enc),
             NativeApi::Utf8ToGbk => super::encoding::utf8_to_gbk(input),
             NativeApi::HtmlFormat => super::encoding::html_format(input),
             NativeApi::HexEncode => super::encoding::hex_encode(input),
@@ -134,6 +130,8 @@ impl StringOpsHandler {
                 | NativeApi::StringToLowerCase
                 | NativeApi::StringToUpperCase
                 | NativeApi::HtmlToText
+                | NativeApi::ChineseT2s
+                | NativeApi::ChineseS2t
                 | NativeApi::StringReplace { .. }
                 | NativeApi::StringSplit { .. }
                 | NativeApi::StringSubstring { .. }
@@ -147,6 +145,8 @@ impl StringOpsHandler {
                 | NativeApi::StringEndsWith { .. }
                 | NativeApi::StringIndexOf { .. }
                 | NativeApi::StringLastIndexOf { .. }
+                | NativeApi::ArrayJoin { .. }
+                | NativeApi::ArrayIndex { .. }
         )
     }
 }
@@ -173,6 +173,8 @@ impl ApiHandler for StringOpsHandler {
                 let re = regex::Regex::new(r"<[^>]+>").unwrap();
                 Ok(re.replace_all(input, "").trim().to_string())
             }
+            NativeApi::ChineseT2s => Ok(super::chinese::t2s(input)),
+            NativeApi::ChineseS2t => Ok(super::chinese::s2t(input)),
             NativeApi::StringReplace {
                 pattern,
                 replacement,
@@ -234,6 +236,8 @@ impl ApiHandler for StringOpsHandler {
                     .unwrap_or(-1)
                     .to_string())
             }
+            NativeApi::ArrayJoin { separator } => super::string_ops::array_join(input, separator),
+            NativeApi::ArrayIndex { index } => super::string_ops::array_index(input, *index),
             _ => unreachable!(),
         }
     }
@@ -244,7 +248,7 @@ pub struct MiscHandler;
 
 impl MiscHandler {
     pub fn is_misc_api(api: &NativeApi) -> bool {
-        matches!(api, NativeApi::RandomUuid | NativeApi::Log)
+        matches!(api, NativeApi::RandomUuid | NativeApi::Log | NativeApi::UiNoop(_))
     }
 }
 
@@ -266,6 +270,10 @@ impl ApiHandler for MiscHandler {
                 super::misc::log_message(msg);
                 Ok(String::new())
             }
+            NativeApi::UiNoop(name) => {
+                super::misc::ui_noop(name, args);
+                Ok(String::new())
+            }
             _ => unreachable!(),
         }
     }
@@ -364,6 +372,55 @@ impl ApiHandler for JsonHandler {
     }
 }
 
+/// JS Globals Handler - type conversions and arithmetic
+pub struct JsGlobalsHandler;
+
+impl JsGlobalsHandler {
+    pub fn is_js_global_api(api: &NativeApi) -> bool {
+        matches!(
+            api,
+            NativeApi::ParseInt
+                | NativeApi::ParseFloat
+                | NativeApi::ToNumber { .. }
+                | NativeApi::ToJsString { .. }
+                | NativeApi::ToBoolean { .. }
+                | NativeApi::JsConcat
+                | NativeApi::JsArithmetic { .. }
+        )
+    }
+}
+
+impl ApiHandler for JsGlobalsHandler {
+    fn can_handle(&self, api: &NativeApi) -> bool {
+        Self::is_js_global_api(api)
+    }
+
+    fn execute(
+        &self,
+        api: &NativeApi,
+        args: &[String],
+        _context: &ExecutionContext,
+    ) -> Result<String> {
+        use super::js_globals;
+
+        let input = args.first().map(|s| s.as_str()).unwrap_or("");
+        let second = args.get(1).map(|s| s.as_str()).unwrap_or("");
+
+        match api {
+            NativeApi::ParseInt => Ok(js_globals::parse_int(input, second)),
+            NativeApi::ParseFloat => Ok(js_globals::parse_float(input)),
+            NativeApi::ToNumber { from } => {
+                Ok(js_globals::format_number(js_globals::to_number(input, *from)))
+            }
+            NativeApi::ToJsString { from } => Ok(js_globals::to_js_string(input, *from)),
+            NativeApi::ToBoolean { from } => Ok(js_globals::to_boolean(input, *from)),
+            NativeApi::JsConcat => Ok(args.concat()),
+            NativeApi::JsArithmetic { op } => Ok(js_globals::arithmetic(*op, input, second)),
+            _ => unreachable!(),
+        }
+    }
+}
+
 /// Handler Registry - Coordinates all API handlers
 pub struct HandlerRegistry {
     handlers: Vec<Box<dyn ApiHandler>>,
@@ -386,6 +443,7 @@ impl HandlerRegistry {
                 Box::new(MiscHandler),
                 Box::new(HashHandler),
                 Box::new(JsonHandler),
+                Box::new(JsGlobalsHandler),
             ],
         }
     }
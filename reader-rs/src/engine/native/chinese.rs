@@ -0,0 +1,123 @@
+//! Chinese Conversion - Traditional/simplified characters for java.t2s/java.s2t
+//!
+//! Converts character by character over a table of common characters, which
+//! covers the text book sources deal with (titles, authors, categories). Some
+//! simplified characters stand for several traditional ones (发: 發/髮, 后: 後/后);
+//! those extra traditional characters are only converted to simplified, so
+//! `s2t` keeps the simplified character's most common counterpart.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// Traditional/simplified pairs converted both ways
+const PAIRS: &str = "
+萬万 與与 專专 業业 叢丛 東东 絲丝 丟丢 兩两 嚴严 喪丧 個个 豐丰 臨临 為为 麗丽
+舉举 麼么 義义 烏乌 樂乐 喬乔 習习 鄉乡 書书 買买 亂乱 爭争 虧亏 亞亚 產产 畝亩
+親亲 億亿 僅仅 從从 侖仑 倉仓 儀仪 們们 價价 眾众 優优 會会 傘伞 偉伟 傳传 傷伤
+倫伦 偽伪 體体 傭佣 僉佥 俠侠 侶侣 僥侥 偵侦 側侧 僑侨 儈侩 儕侪 儂侬 債债 傾倾
+償偿 儲储 兒儿 兌兑 黨党 蘭兰 關关 興兴 茲兹 養养 獸兽 內内 岡冈 冊册 寫写 軍军
+農农 馮冯 決决 況况 凍冻 淨净 涼凉 減减 湊凑 凜凛 幾几 鳳凤 憑凭 凱凯 擊击 鑿凿
+芻刍 劉刘 則则 剛刚 創创 刪删 別别 劊刽 劑剂 剮剐 劍剑 剝剥 劇剧 勸劝 辦办 務务
+動动 勵励 勁劲 勞劳 勢势 勳勋 勻匀 匱匮 區区 醫医 華华 協协 單单 賣卖 盧卢 衛卫
+卻却 廠厂 廳厅 歷历 厲厉 壓压 厭厌 廁厕 廂厢 廈厦 廚厨 縣县 參参 雙双 發发 變变
+敘叙 葉叶 號号 嘆叹 嘰叽 嚇吓 呂吕 嗎吗 噸吨 聽听 啟启 吳吴 嘔呕 唄呗 員员 嗚呜
+詠咏 嚨咙 響响 啞哑 噠哒 嘩哗 喲哟 嘮唠 嘯啸 喚唤 問问 嗩唢 團团 園园 圍围 圖图
+圓圆 聖圣 場场 壞坏 塊块 堅坚 壇坛 壩坝 墳坟 墜坠 壟垄 壘垒 墾垦 執执 報报 堯尧
+塵尘 墊垫 牆墙 壯壮 聲声 殼壳 壺壶 處处 備备 夠够 頭头 誇夸 夾夹 奪夺 奮奋 獎奖
+奧奥 婦妇 媽妈 嫵妩 嫗妪 姍姗 婁娄 婭娅 嬌娇 孌娈 娛娱 媧娲 嫻娴 嬰婴 嬸婶 孫孙
+學学 孿孪 寧宁 寶宝 實实 寵宠 審审 憲宪 宮宫 寬宽 賓宾 寢寝 對对 尋寻 導导 壽寿
+將将 爾尔 嘗尝 屍尸 盡尽 層层 屬属 屢屡 嶼屿 歲岁 豈岂 嶇岖 崗岗 峴岘 島岛 嶺岭
+嶽岳 崢峥 巒峦 嶗崂 幣币 師师 帳帐 帶带 幫帮 幟帜 廣广 慶庆 廬庐 庫库 應应 廟庙
+龐庞 廢废 開开 異异 棄弃 張张 彌弥 彎弯 歸归 當当 錄录 彥彦 徹彻 徑径 憶忆 懺忏
+憂忧 懷怀 態态 慫怂 憐怜 總总 懟怼 戀恋 懇恳 惡恶 慟恸 惱恼 悵怅 愴怆 驚惊 慣惯
+慘惨 慚惭 憚惮 願愿 懶懒 憊惫 戰战 戲戏 戶户 紮扎 撲扑 擴扩 捫扪 掃扫 揚扬 擾扰
+撫抚 搶抢 護护 擔担 擬拟 攏拢 揀拣 擁拥 攔拦 擰拧 撥拨 擇择 掛挂 摯挚 攣挛 擋挡
+擠挤 揮挥 撈捞 損损 撿捡 換换 搗捣 據据 擄掳 擲掷 撣掸 攙搀 攬揽 摟搂 攪搅 攜携
+攝摄 擺摆 搖摇 擯摈 攤摊 撐撑 敵敌 數数 齋斋 斕斓 斬斩 斷断 無无 舊旧 時时 曠旷
+曇昙 晝昼 顯显 晉晋 曬晒 曉晓 曄晔 暈晕 暉晖 暫暂 曖暧 術术 機机 殺杀 雜杂 權权
+條条 來来 楊杨 極极 構构 樅枞 櫃柜 棗枣 櫪枥 棟栋 欄栏 樹树 棲栖 樣样 欒栾 橋桥
+樺桦 檜桧 樁桩 夢梦 檢检 櫛栉 梟枭 棧栈 檔档 櫥橱 樓楼 欖榄 榮荣 槍枪 標标 樞枢
+櫻樱 歡欢 歐欧 殲歼 殘残 殞殒 殤殇 殯殡 毆殴 畢毕 斃毙 氈毡 氣气 漢汉 湯汤 溝沟
+沒没 漚沤 瀝沥 淪沦 滄沧 瀘泸 濘泞 淚泪 澤泽 潑泼 潔洁 灑洒 窪洼 濁浊 測测 濟济
+瀏浏 渾浑 濃浓 濤涛 澇涝 澗涧 漲涨 漁渔 漬渍 滲渗 溫温 灣湾 濕湿 潰溃 濺溅 滾滚
+滿满 濾滤 灤滦 灘滩 瀟潇 瀾澜 瀨濑 災灾 燈灯 靈灵 爐炉 燉炖 煉炼 爛烂 燭烛 煙烟
+煩烦 燒烧 燁烨 熱热 煥焕 燜焖 營营 愛爱 爺爷 牘牍 犧牺 狀状 猶犹 狹狭 獅狮 獨独
+獄狱 猙狰 貓猫 獵猎 獻献 璣玑 瑪玛 環环 現现 璽玺 琺珐 瓏珑 璉琏 瑣琐 瓊琼 甕瓮
+甌瓯 電电 畫画 暢畅 瘧疟 療疗 瘡疮 瘋疯 皚皑 盞盏 鹽盐 監监 蓋盖 盤盘 睜睁 瞞瞒
+矚瞩 礦矿 碼码 磚砖 硯砚 礙碍 確确 禮礼 禍祸 離离 禿秃 種种 稱称 積积 穩稳 窮穷
+竊窃 竅窍 竄窜 窩窝 競竞 筆笔 筍笋 箏筝 籌筹 簽签 簡简 籃篮 類类 糧粮 糾纠 紅红
+約约 級级 紀纪 紉纫 純纯 紗纱 紙纸 紡纺 紛纷 線线 練练 組组 細细 織织 終终 絆绊
+經经 結结 繞绕 給给 絡络 絕绝 統统 絹绢 綁绑 綠绿 維维 綱纲 網网 綿绵 緊紧 編编
+緣缘 縫缝 縮缩 績绩 繩绳 繪绘 繼继 續续 纏缠 罰罚 罵骂 羅罗 聞闻 聯联 職职 聰聪
+膚肤 腎肾 腫肿 脹胀 膽胆 勝胜 脈脉 腦脑 臟脏 臉脸 艦舰 艱艰 藝艺 節节 蘆芦 蘇苏
+蘋苹 莖茎 薦荐 莊庄 莢荚 葦苇 藥药 萊莱 蓮莲 獲获 蕭萧 薩萨 蟲虫 雖虽 蝦虾 螞蚂
+蠶蚕 蠻蛮 補补 襯衬 裝装 襖袄 見见 規规 視视 覽览 覺觉 觀观 計计 訂订 認认 討讨
+讓让 訓训 議议 記记 講讲 許许 論论 設设 訪访 證证 評评 識识 詞词 譯译 試试 詩诗
+誠诚 話话 誕诞 詳详 該该 語语 誤误 說说 請请 諸诸 讀读 課课 誰谁 調调 談谈 謝谢
+謠谣 謎谜 謹谨 譜谱 豎竖 豬猪 貝贝 負负 財财 貢贡 貧贫 貨货 販贩 貪贪 責责 貴贵
+貸贷 費费 貿贸 賀贺 資资 賊贼 賈贾 賞赏 賠赔 賢贤 賬账 質质 賴赖 賺赚 購购 贈赠
+贊赞 趕赶 趙赵 躍跃 蹤踪 踐践 車车 軌轨 軟软 轉转 輪轮 載载 較较 輔辅 輕轻 輛辆
+輸输 輩辈 轎轿 辭辞 邊边 遼辽 達达 遷迁 過过 邁迈 運运 還还 這这 進进 遠远 違违
+連连 遲迟 適适 選选 遺遗 鄧邓 鄭郑 鄰邻 醬酱 釋释 針针 釣钓 鈔钞 鈴铃 鉛铅 銀银
+銅铜 鋼钢 錢钱 錯错 鍋锅 鍵键 鏡镜 鐵铁 鑰钥 鐘钟 長长 門门 閃闪 閉闭 閒闲 間间
+閣阁 閱阅 闊阔 隊队 陽阳 陰阴 陣阵 階阶 際际 陸陆 陳陈 險险 隨随 隱隐 難难 雞鸡
+霧雾 靜静 韓韩 韋韦 頁页 頂顶 項项 順顺 須须 預预 領领 頻频 題题 顏颜 額额 風风
+飛飞 飯饭 飲饮 飽饱 餃饺 館馆 饑饥 馬马 駕驾 驗验 騎骑 驅驱 驢驴 鬧闹 魚鱼 鮮鲜
+鳥鸟 鴨鸭 鵝鹅 鷹鹰 麥麦 黃黄 點点 齊齐 齒齿 龍龙 龜龟 國国 匯汇
+";
+
+/// Traditional characters that are only converted to simplified
+const T2S_ONLY: &str = "
+後后 裡里 裏里 麵面 乾干 幹干 鬆松 隻只 臺台 檯台 颱台 穫获 餘余 係系 繫系 準准
+雲云 於于 捨舍 徵征 沖冲 衝冲 曆历 復复 複复 製制 範范 鬥斗 彙汇 儘尽 劃划 佈布
+摺折 錶表 嚮向 鬍胡 鹹咸 齣出 醜丑 蔔卜 捲卷 瞭了 讚赞 薑姜 穀谷 週周 夥伙 傢家
+並并 併并 鬱郁 癒愈 嚐尝 髮发 鍾钟
+";
+
+fn pairs(table: &'static str) -> impl Iterator<Item = (char, char)> {
+    table.split_whitespace().filter_map(|pair| {
+        let mut chars = pair.chars();
+        Some((chars.next()?, chars.next()?))
+    })
+}
+
+static T2S: Lazy<HashMap<char, char>> = Lazy::new(|| pairs(PAIRS).chain(pairs(T2S_ONLY)).collect());
+
+static S2T: Lazy<HashMap<char, char>> = Lazy::new(|| pairs(PAIRS).map(|(t, s)| (s, t)).collect());
+
+fn convert(input: &str, table: &HashMap<char, char>) -> String {
+    input.chars().map(|c| table.get(&c).copied().unwrap_or(c)).collect()
+}
+
+/// Traditional to simplified (java.t2s)
+pub fn t2s(input: &str) -> String {
+    convert(input, &T2S)
+}
+
+/// Simplified to traditional (java.s2t)
+pub fn s2t(input: &str) -> String {
+    convert(input, &S2T)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_text() {
+        let traditional = "《鬥羅大陸》第1章 唐門外門弟子，頭髮與後來 ABC";
+        assert_eq!(t2s(traditional), "《斗罗大陆》第1章 唐门外门弟子，头发与后来 ABC");
+        assert_eq!(s2t("斗罗大陆 第1章 唐门弟子 abc"), "斗羅大陸 第1章 唐門弟子 abc");
+        // Already simplified/traditional text is left as is
+        assert_eq!(t2s("唐门弟子"), "唐门弟子");
+        assert_eq!(s2t("唐門弟子"), "唐門弟子");
+    }
+
+    #[test]
+    fn test_table_is_consistent() {
+        for (t, s) in pairs(PAIRS) {
+            assert_eq!(t2s(&s2t(&s.to_string())), s.to_string(), "{}{}", t, s);
+        }
+        assert!(pairs(T2S_ONLY).all(|(t, _)| !S2T.values().any(|&v| v == t)));
+    }
+}
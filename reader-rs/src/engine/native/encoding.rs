@@ -40,9 +40,9 @@ pub fn encode_uri(input: &str) -> Result<String> {
     Ok(urlencoding::encode(input).to_string())
 }
 
-/// URI encode with charset (charset parameter is ignored, always UTF-8)
-pub fn encode_uri_with_enc(input: &str, _enc: &str) -> Result<String> {
-    Ok(urlencoding::encode(input).to_string())
+/// URI encode the bytes of `input` in the given charset (GBK, GB2312, GB18030; UTF-8 otherwise)
+pub fn encode_uri_with_enc(input: &str, enc: &str) -> Result<String> {
+    Ok(crate::engine::http_client::encode_with_charset(input, enc))
 }
 
 /// URI decode a string
@@ -71,10 +71,9 @@ pub fn html_format(input: &str) -> Result<String> {
     Ok(html_escape::decode_html_entities(input).to_string())
 }
 
-/// UTF-8 to GBK conversion (placeholder - returns input as-is)
+/// UTF-8 to GBK conversion, percent-encoded as Legado sends it in URLs
 pub fn utf8_to_gbk(input: &str) -> Result<String> {
-    // GBK encoding requires external crate, returning as-is for now
-    Ok(input.to_string())
+    encode_uri_with_enc(input, "gbk")
 }
 
 #[cfg(test)]
@@ -96,6 +95,13 @@ mod tests {
         assert_eq!(encode_uri("hello world").unwrap(), "hello%20world");
     }
 
+    #[test]
+    fn test_encode_uri_gbk() {
+        assert_eq!(utf8_to_gbk("小说").unwrap(), "%D0%A1%CB%B5");
+        assert_eq!(encode_uri_with_enc("小说 a-1", "GBK").unwrap(), "%D0%A1%CB%B5%20a-1");
+        assert_eq!(encode_uri_with_enc("小说", "utf-8").unwrap(), encode_uri("小说").unwrap());
+    }
+
     #[test]
     fn test_hex_encode() {
         assert_eq!(hex_encode("AB").unwrap(), "4142");
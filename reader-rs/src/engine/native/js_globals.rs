@@ -0,0 +1,227 @@
+//! JS Globals - parseInt, parseFloat, Number(), String(), Boolean() and arithmetic
+//!
+//! Native values travel as strings, so every function here takes the string
+//! form of a JS value and returns the string form of the JS result, following
+//! the ECMAScript conversion rules (NaN, Infinity, radix prefixes, ...).
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::engine::preprocessor::{ArithmeticOp, JsType};
+
+/// StrDecimalLiteral without the Infinity forms
+static DECIMAL_LITERAL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?$").unwrap());
+
+/// Longest decimal prefix accepted by parseFloat
+static FLOAT_PREFIX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[+-]?(Infinity|(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?)").unwrap());
+
+/// JS whitespace also includes the BOM, which `char::is_whitespace` does not
+fn is_js_whitespace(c: char) -> bool {
+    c.is_whitespace() || c == '\u{feff}'
+}
+
+/// Number -> string, as `Number.prototype.toString()` prints it
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if n == 0.0 {
+        // Also covers -0
+        return "0".to_string();
+    }
+    let abs = n.abs();
+    if (1e-6..1e21).contains(&abs) {
+        return n.to_string();
+    }
+    // JS switches to exponent notation and always signs the exponent
+    let exp = format!("{:e}", n);
+    match exp.split_once('e') {
+        Some((mantissa, e)) if !e.starts_with('-') => format!("{}e+{}", mantissa, e),
+        _ => exp,
+    }
+}
+
+/// `Number(value)` for a string value
+pub fn string_to_number(value: &str) -> f64 {
+    let s = value.trim_matches(is_js_whitespace);
+    if s.is_empty() {
+        return 0.0;
+    }
+    match s {
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    for (prefix, radix) in [("0x", 16), ("0X", 16), ("0o", 8), ("0O", 8), ("0b", 2), ("0B", 2)] {
+        if let Some(digits) = s.strip_prefix(prefix) {
+            return parse_digits(digits, radix)
+                .filter(|(_, len)| *len == digits.len())
+                .map(|(n, _)| n)
+                .unwrap_or(f64::NAN);
+        }
+    }
+    if DECIMAL_LITERAL.is_match(s) {
+        s.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+/// `Number(value)` for a value of the given JS type
+pub fn to_number(value: &str, from: JsType) -> f64 {
+    match from {
+        JsType::Boolean => {
+            if value == "true" {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        JsType::Number | JsType::String => string_to_number(value),
+    }
+}
+
+/// Longest prefix of `digits` valid in `radix`: (value, bytes consumed)
+fn parse_digits(digits: &str, radix: u32) -> Option<(f64, usize)> {
+    let mut value = 0.0;
+    let mut len = 0;
+    for c in digits.chars() {
+        let Some(d) = c.to_digit(radix) else {
+            break;
+        };
+        value = value * radix as f64 + d as f64;
+        len += c.len_utf8();
+    }
+    (len > 0).then_some((value, len))
+}
+
+/// `parseInt(value, radix)`; an empty radix means undefined
+pub fn parse_int(value: &str, radix: &str) -> String {
+    let mut s = value.trim_start_matches(is_js_whitespace);
+    let mut sign = 1.0;
+    if let Some(rest) = s.strip_prefix('-') {
+        sign = -1.0;
+        s = rest;
+    } else if let Some(rest) = s.strip_prefix('+') {
+        s = rest;
+    }
+
+    let radix = string_to_number(radix);
+    let mut radix = if radix.is_finite() { radix.trunc() as i64 as i32 } else { 0 };
+    let strip_hex = match radix {
+        0 => {
+            radix = 10;
+            true
+        }
+        16 => true,
+        2..=36 => false,
+        _ => return "NaN".to_string(),
+    };
+    if strip_hex {
+        if let Some(rest) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            s = rest;
+            radix = 16;
+        }
+    }
+
+    match parse_digits(s, radix as u32) {
+        Some((n, _)) => format_number(sign * n),
+        None => "NaN".to_string(),
+    }
+}
+
+/// `parseFloat(value)`
+pub fn parse_float(value: &str) -> String {
+    let s = value.trim_start_matches(is_js_whitespace);
+    match FLOAT_PREFIX.find(s) {
+        Some(m) => format_number(string_to_number(m.as_str())),
+        None => "NaN".to_string(),
+    }
+}
+
+/// `String(value)`: numbers are reprinted in JS form, other values pass through
+pub fn to_js_string(value: &str, from: JsType) -> String {
+    match from {
+        JsType::Number => format_number(string_to_number(value)),
+        JsType::String | JsType::Boolean => value.to_string(),
+    }
+}
+
+/// `Boolean(value)`
+pub fn to_boolean(value: &str, from: JsType) -> String {
+    let truthy = match from {
+        JsType::String => !value.is_empty(),
+        JsType::Boolean => value == "true",
+        JsType::Number => {
+            let n = string_to_number(value);
+            n != 0.0 && !n.is_nan()
+        }
+    };
+    truthy.to_string()
+}
+
+/// Numeric binary operation with JS ToNumber coercion of both sides
+pub fn arithmetic(op: ArithmeticOp, left: &str, right: &str) -> String {
+    let (a, b) = (string_to_number(left), string_to_number(right));
+    let n = match op {
+        ArithmeticOp::Add => a + b,
+        ArithmeticOp::Sub => a - b,
+        ArithmeticOp::Mul => a * b,
+        ArithmeticOp::Div => a / b,
+        // Rust's float remainder truncates like JS `%`
+        ArithmeticOp::Mod => a % b,
+    };
+    format_number(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_number(1e21), "1e+21");
+        assert_eq!(format_number(1.5e-7), "1.5e-7");
+        assert_eq!(format_number(123456789012345680000.0), "123456789012345680000");
+        assert_eq!(format_number(f64::NAN), "NaN");
+        assert_eq!(format_number(f64::NEG_INFINITY), "-Infinity");
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("  42px", ""), "42");
+        assert_eq!(parse_int("-0x1F", ""), "-31");
+        assert_eq!(parse_int("0x1F", "16"), "31");
+        assert_eq!(parse_int("0x1F", "10"), "0");
+        assert_eq!(parse_int("ff", "16"), "255");
+        assert_eq!(parse_int("101", "2"), "5");
+        assert_eq!(parse_int("12", "1"), "NaN");
+        assert_eq!(parse_int("abc", ""), "NaN");
+        assert_eq!(parse_int("3.9", ""), "3");
+    }
+
+    #[test]
+    fn test_number_conversions() {
+        assert_eq!(format_number(string_to_number(" 12 ")), "12");
+        assert_eq!(format_number(string_to_number("")), "0");
+        assert_eq!(format_number(string_to_number("0x10")), "16");
+        assert_eq!(format_number(string_to_number("12px")), "NaN");
+        assert_eq!(format_number(string_to_number("inf")), "NaN");
+        assert_eq!(format_number(string_to_number("-Infinity")), "-Infinity");
+        assert_eq!(parse_float("3.14abc"), "3.14");
+        assert_eq!(parse_float(".5e1"), "5");
+        assert_eq!(to_boolean("0", JsType::Number), "false");
+        assert_eq!(to_boolean("0", JsType::String), "true");
+        assert_eq!(to_js_string("2.50", JsType::Number), "2.5");
+        assert_eq!(arithmetic(ArithmeticOp::Mod, "-7", "3"), "-1");
+        assert_eq!(arithmetic(ArithmeticOp::Div, "1", "0"), "Infinity");
+    }
+}
@@ -14,6 +14,12 @@ pub fn log_message(message: &str) {
     tracing::debug!("JS Log: {}", message);
 }
 
+/// UI-only call such as java.toast: nothing to show on the server, so only log it
+pub fn ui_noop(name: &str, args: &[String]) {
+    let message = args.first().map(|s| s.as_str()).unwrap_or("");
+    tracing::debug!("Ignoring UI call java.{}: {}", name, message);
+}
+
 /// Get Android ID (placeholder - returns random ID)
 pub fn get_android_id() -> String {
     // In a real Android environment, this would return the actual Android ID
@@ -5,11 +5,15 @@
 //! - storage: Cache, KvStore operations
 //! - string_ops: String manipulation
 //! - time: Time formatting
-//! - misc: UUID, logging
+//! - misc: UUID, logging, UI no-ops
+//! - chinese: Traditional/simplified conversion
+//! - js_globals: parseInt, Number(), String(), Boolean() and arithmetic
 //! - api_handler: Trait-based API dispatch
 
 pub mod api_handler;
+pub mod chinese;
 pub mod encoding;
+pub mod js_globals;
 pub mod misc;
 pub mod storage;
 pub mod string_ops;
@@ -1,4 +1,4 @@
-//! Storage Operations - Cache, KvStore, File operations
+//! Storage Operations - Cache and KvStore operations
 //!
 //! Native Rust implementations of storage APIs.
 
@@ -11,10 +11,13 @@ pub fn cache_get(kv_store: &Arc<KvStore>, key: &str) -> Result<String> {
     Ok(kv_store.get_cache(key).unwrap_or_default())
 }
 
-/// Set value in cache (with default 1 hour expiry)
-pub fn cache_set(kv_store: &Arc<KvStore>, key: &str, value: &str) -> Result<String> {
-    let expire_time = chrono::Utc::now().timestamp_millis() + 3600_000; // 1 hour
-    kv_store.set_cache(key, value, expire_time);
+/// Set value in cache, expiring after `save_time` seconds (missing or 0 = never)
+pub fn cache_set(kv_store: &Arc<KvStore>, key: &str, value: &str, save_time: Option<&str>) -> Result<String> {
+    let save_time = save_time
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .map(|s| s as i64)
+        .unwrap_or(0);
+    kv_store.set_cache_ttl(key, value, save_time);
     Ok(String::new())
 }
 
@@ -28,12 +31,14 @@ pub fn set_source_var(kv_store: &Arc<KvStore>, source_url: &str, key: &str, valu
     kv_store.set_source_var(source_url, key, value);
 }
 
-/// Delete file from cache
-pub fn delete_file(path: &str) -> bool {
-    std::fs::remove_file(path).is_ok()
-}
-
-/// Check if file exists
-pub fn file_exists(path: &str) -> bool {
-    std::path::Path::new(path).exists()
+/// Get a book variable from the book's variable JSON object
+pub fn get_book_var(variable: &str, key: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(variable)
+        .ok()
+        .and_then(|map| match map.get(key)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Null => None,
+            other => Some(other.to_string()),
+        })
+        .unwrap_or_default()
 }
@@ -3,14 +3,19 @@
 //! Native Rust implementations of string manipulation APIs.
 
 use anyhow::Result;
-use regex::Regex;
+use regex::{Captures, Regex};
 
 /// Trim whitespace from string
 pub fn string_trim(input: &str) -> Result<String> {
     Ok(input.trim().to_string())
 }
 
-/// Replace pattern in string
+/// Replace pattern in string with JavaScript `String.prototype.replace` semantics
+///
+/// A regex pattern is Rust regex syntax (see [`js_regex_to_rust`]), a literal
+/// pattern matches as-is. Only the first match is replaced unless `global`
+/// is set, and the replacement expands `$$`, `$&`, `` $` ``, `$'`, `$n` and
+/// `$<name>` the way JS does.
 pub fn string_replace(
     input: &str,
     pattern: &str,
@@ -18,24 +23,166 @@ pub fn string_replace(
     is_regex: bool,
     global: bool,
 ) -> Result<String> {
-    if is_regex {
-        if let Ok(re) = Regex::new(pattern) {
-            if global {
-                Ok(re.replace_all(input, replacement).to_string())
-            } else {
-                Ok(re.replace(input, replacement).to_string())
+    let re = if is_regex {
+        Regex::new(pattern)?
+    } else {
+        Regex::new(&regex::escape(pattern))?
+    };
+    let named = re.capture_names().flatten().next().is_some();
+    let limit = if global { 0 } else { 1 };
+    Ok(re
+        .replacen(input, limit, |caps: &Captures| {
+            expand_js_replacement(replacement, caps, input, named)
+        })
+        .into_owned())
+}
+
+/// Expand a JS replacement string for one match (ECMAScript GetSubstitution)
+fn expand_js_replacement(replacement: &str, caps: &Captures, input: &str, named: bool) -> String {
+    let Some(whole) = caps.get(0) else {
+        return replacement.to_string();
+    };
+    let groups = caps.len() - 1;
+    let group = |n: usize| caps.get(n).map_or("", |m| m.as_str());
+
+    let mut out = String::with_capacity(replacement.len());
+    let mut rest = replacement;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos + 1..];
+        let bytes = tail.as_bytes();
+        let consumed = match bytes.first() {
+            Some(b'$') => {
+                out.push('$');
+                1
+            }
+            Some(b'&') => {
+                out.push_str(whole.as_str());
+                1
             }
-        } else {
-            // Invalid regex, fall back to literal
-            Ok(input.replace(pattern, replacement))
+            Some(b'`') => {
+                out.push_str(&input[..whole.start()]);
+                1
+            }
+            Some(b'\'') => {
+                out.push_str(&input[whole.end()..]);
+                1
+            }
+            Some(d) if d.is_ascii_digit() => {
+                // Two digits win when they name an existing group
+                let one = (d - b'0') as usize;
+                let two = bytes
+                    .get(1)
+                    .filter(|b| b.is_ascii_digit())
+                    .map(|b| one * 10 + (b - b'0') as usize);
+                match two {
+                    Some(n) if (1..=groups).contains(&n) => {
+                        out.push_str(group(n));
+                        2
+                    }
+                    _ if (1..=groups).contains(&one) => {
+                        out.push_str(group(one));
+                        1
+                    }
+                    _ => {
+                        out.push('$');
+                        0
+                    }
+                }
+            }
+            Some(b'<') if named => match tail.find('>') {
+                Some(end) => {
+                    out.push_str(caps.name(&tail[1..end]).map_or("", |m| m.as_str()));
+                    end + 1
+                }
+                None => {
+                    out.push('$');
+                    0
+                }
+            },
+            _ => {
+                out.push('$');
+                0
+            }
+        };
+        rest = &tail[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Translate a JS regex literal (`/source/flags`) to an equivalent Rust regex
+///
+/// `i` and `s` become inline flags; `g` only affects how many matches
+/// are replaced and is left to the caller. `\d`, `\w` and `\b` stay ASCII-only
+/// and `.` excludes every JS line terminator. Returns `None` for flags or
+/// syntax the regex crate can't match identically (sticky matching,
+/// multiline anchors, lookaround, backreferences), so the rule is left to the JS engine.
+pub fn js_regex_to_rust(source: &str, flags: &str) -> Option<String> {
+    let mut inline = String::new();
+    for flag in flags.chars() {
+        match flag {
+            'g' | 'u' => {}
+            'i' | 's' => inline.push(flag),
+            // JS anchors match on both sides of \r\n, the regex crate's CRLF
+            // mode treats it as one terminator; without anchors `m` is a no-op
+            'm' if !source.contains(['^', '$']) => {}
+            _ => return None,
         }
+    }
+    let dot_all = flags.contains('s');
+
+    let mut out = if inline.is_empty() {
+        String::new()
     } else {
-        if global {
-            Ok(input.replace(pattern, replacement))
-        } else {
-            Ok(input.replacen(pattern, replacement, 1))
+        format!("(?{})", inline)
+    };
+    let mut in_class = false;
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next()?;
+                match (escaped, in_class) {
+                    ('d', false) => out.push_str("[0-9]"),
+                    ('D', false) => out.push_str("[^0-9]"),
+                    ('w', false) => out.push_str("[0-9A-Za-z_]"),
+                    ('W', false) => out.push_str("[^0-9A-Za-z_]"),
+                    ('b' | 'B', false) => {
+                        out.push_str("(?-u:\\");
+                        out.push(escaped);
+                        out.push(')');
+                    }
+                    ('d', true) => out.push_str("0-9"),
+                    ('w', true) => out.push_str("0-9A-Za-z_"),
+                    ('D' | 'W' | 'b' | 'B', true) => return None,
+                    (e, _) if e.is_ascii_alphanumeric() => {
+                        out.push('\\');
+                        out.push(e);
+                    }
+                    // Identity escape such as `\/`
+                    (e, _) => out.push_str(&regex::escape(e.encode_utf8(&mut [0; 4]))),
+                }
+            }
+            '[' if !in_class => {
+                in_class = true;
+                out.push(c);
+            }
+            ']' if in_class => {
+                in_class = false;
+                out.push(c);
+            }
+            // Nested classes and set operators are literal in JS classes
+            '[' | '&' | '~' if in_class => {
+                out.push('\\');
+                out.push(c);
+            }
+            '.' if !in_class && !dot_all => out.push_str(r"[^\n\r\u2028\u2029]"),
+            _ => out.push(c),
         }
     }
+    Regex::new(&out).ok()?;
+    Some(out)
 }
 
 /// Split string by delimiter
@@ -95,6 +242,47 @@ pub fn length(input: &str) -> i32 {
     input.chars().count() as i32
 }
 
+/// Parse a list-valued intermediate (the JSON array produced by split)
+fn parse_list(input: &str) -> Option<Vec<serde_json::Value>> {
+    if !input.trim_start().starts_with('[') {
+        return None;
+    }
+    serde_json::from_str(input).ok()
+}
+
+fn list_item_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Join a list-valued intermediate; non-list input is returned unchanged
+pub fn array_join(input: &str, separator: &str) -> Result<String> {
+    Ok(match parse_list(input) {
+        Some(items) => items
+            .iter()
+            .map(list_item_to_string)
+            .collect::<Vec<_>>()
+            .join(separator),
+        None => input.to_string(),
+    })
+}
+
+/// Index into a list-valued intermediate, or into the characters of a plain string
+///
+/// Out-of-range indexes yield an empty string (JS `undefined`).
+pub fn array_index(input: &str, index: i64) -> Result<String> {
+    let Ok(index) = usize::try_from(index) else {
+        return Ok(String::new());
+    };
+    Ok(match parse_list(input) {
+        Some(items) => items.get(index).map(list_item_to_string).unwrap_or_default(),
+        None => input.chars().nth(index).map(String::from).unwrap_or_default(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,10 +298,57 @@ mod tests {
             string_replace("hello world", "world", "rust", false, true).unwrap(),
             "hello rust"
         );
+        // Literal patterns replace the first occurrence unless global
+        assert_eq!(string_replace("a.a.a", ".", "-", false, false).unwrap(), "a-a.a");
+        assert_eq!(string_replace("a.a.a", ".", "-", false, true).unwrap(), "a-a-a");
+        assert_eq!(string_replace("x1y22", "[0-9]+", "#", true, false).unwrap(), "x#y22");
+        assert_eq!(string_replace("x1y22", "[0-9]+", "#", true, true).unwrap(), "x#y#");
+    }
+
+    #[test]
+    fn test_replace_expands_js_patterns() {
+        let re = r"(\w)(\d)";
+        assert_eq!(string_replace("a1b2", re, "$2$1", true, true).unwrap(), "1a2b");
+        assert_eq!(string_replace("a1", re, "[$&|$$|$3|$0]", true, false).unwrap(), "[a1|$|$3|$0]");
+        assert_eq!(string_replace("a1", re, "$10", true, false).unwrap(), "a0");
+        assert_eq!(string_replace("xay", "a", "$`$'", false, false).unwrap(), "xxyy");
+        assert_eq!(string_replace("ab", "a", "$1$<n>", false, false).unwrap(), "$1$<n>b");
+        assert_eq!(
+            string_replace("ab", "(?<n>a)", "$<n>$<m>!", true, false).unwrap(),
+            "a!b"
+        );
+    }
+
+    #[test]
+    fn test_js_regex_to_rust() {
+        assert_eq!(js_regex_to_rust(r"\s+", "g").unwrap(), r"\s+");
+        assert_eq!(js_regex_to_rust(r"a\/b", "").unwrap(), "a/b");
+        assert_eq!(js_regex_to_rust(r"[\d.]", "").unwrap(), "[0-9.]");
+        assert_eq!(js_regex_to_rust("x", "gi").unwrap(), "(?i)x");
+        // `\d` and `.` keep their JS meaning
+        let digits = Regex::new(&js_regex_to_rust(r"\d+", "").unwrap()).unwrap();
+        assert!(!digits.is_match("１２"));
+        let line = Regex::new(&js_regex_to_rust("^.+$", "").unwrap()).unwrap();
+        assert!(!line.is_match("a\r"));
+        // Sticky matching, lookaround and backreferences need the JS engine
+        assert!(js_regex_to_rust("a", "y").is_none());
+        assert!(js_regex_to_rust("^a", "m").is_none());
+        assert!(js_regex_to_rust("a(?=b)", "").is_none());
+        assert!(js_regex_to_rust(r"(a)\1", "").is_none());
     }
 
     #[test]
     fn test_substring() {
         assert_eq!(string_substring("hello", 1, Some(4)).unwrap(), "ell");
     }
+
+    #[test]
+    fn test_array_ops_on_split_result() {
+        let parts = serde_json::to_string(&string_split("a, b,c", ",").unwrap()).unwrap();
+        assert_eq!(array_join(&parts, "|").unwrap(), "a| b|c");
+        assert_eq!(array_index(&parts, 1).unwrap(), " b");
+        assert_eq!(array_index(&parts, 5).unwrap(), "");
+        assert_eq!(array_index("abc", 2).unwrap(), "c");
+        assert_eq!(array_join("plain", "|").unwrap(), "plain");
+    }
 }
@@ -5,13 +5,18 @@
 
 use super::cookie::CookieManager;
 use super::error::EngineError;
+use super::http_client::{HttpClient, RequestConfig, StrResponse};
 use super::native::HandlerRegistry;
 use super::preprocessor::NativeApi;
-use crate::storage::kv::KvStore;
+use super::query_ttf;
+use crate::models::BOOK_CUSTOM_VARIABLE_KEY;
+use crate::storage::kv::{KvStore, SOURCE_VARIABLE_KEY};
+use super::native_http::NativeHttpClient;
+use super::utils::resolve_absolute_url;
 use anyhow::Result;
-
-
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 
 /// Native API Provider - executes java.* APIs in pure Rust
 pub struct NativeApiProvider {
@@ -21,12 +26,32 @@ pub struct NativeApiProvider {
     kv_store: Arc<KvStore>,
     /// Handler registry for modular API dispatch
     handler_registry: HandlerRegistry,
+    /// Cache directory of cacheFile/importScript/readFile/fonts, under the storage directory
+    cache_dir: PathBuf,
+    /// Client of java.ajax/post/connect, built on first use so its connection pool is reused
+    http: OnceLock<HttpClient>,
+    /// Client of java.request/ajaxAll/cacheFile/importScript, built on first use
+    native_http: OnceLock<NativeHttpClient>,
+    /// Number of HTTP clients built by this provider
+    clients_built: AtomicUsize,
 }
 
 /// Execution context for Native API calls
 #[derive(Debug, Default, Clone)]
 pub struct ExecutionContext {
     pub base_url: String,
+    /// `bookSourceUrl` of the running source, the namespace of source variables
+    pub source_url: String,
+}
+
+impl ExecutionContext {
+    /// Namespace of source variables: the source URL, else the base URL
+    fn source_var_scope(&self) -> &str {
+        [self.source_url.as_str(), self.base_url.as_str()]
+            .into_iter()
+            .find(|url| !url.is_empty())
+            .unwrap_or("global")
+    }
 }
 
 impl NativeApiProvider {
@@ -34,18 +59,68 @@ impl NativeApiProvider {
     pub fn new(cookie_manager: Arc<CookieManager>, kv_store: Arc<KvStore>) -> Self {
         Self {
             cookie_manager,
+            cache_dir: kv_store.cache_path("native"),
             kv_store,
             handler_registry: HandlerRegistry::new(),
+            http: OnceLock::new(),
+            native_http: OnceLock::new(),
+            clients_built: AtomicUsize::new(0),
         }
     }
 
     /// Create with existing kv_store (deprecated name but keeping signature similar if needed, or just remove)
     pub fn with_store(cookie_manager: Arc<CookieManager>, kv_store: Arc<KvStore>) -> Self {
-        Self {
-            cookie_manager,
-            kv_store,
-            handler_registry: HandlerRegistry::new(),
+        Self::new(cookie_manager, kv_store)
+    }
+
+    /// HTTP client sharing this provider's cookies
+    ///
+    /// Relative URLs are resolved against the execution context's base URL
+    /// with `parse_request_config_at`, so one client serves every context.
+    fn http_client(&self) -> Result<&HttpClient> {
+        if let Some(client) = self.http.get() {
+            return Ok(client);
+        }
+        let client = HttpClient::with_cookie_manager("", None, (*self.cookie_manager).clone())?;
+        self.clients_built.fetch_add(1, Ordering::Relaxed);
+        Ok(self.http.get_or_init(|| client))
+    }
+
+    fn native_http_client(&self) -> Result<&NativeHttpClient> {
+        if let Some(client) = self.native_http.get() {
+            return Ok(client);
         }
+        let client = NativeHttpClient::new(self.cache_dir.clone())?;
+        self.clients_built.fetch_add(1, Ordering::Relaxed);
+        Ok(self.native_http.get_or_init(|| client))
+    }
+
+    /// Number of HTTP clients built so far (at most one of each kind)
+    pub fn clients_built(&self) -> usize {
+        self.clients_built.load(Ordering::Relaxed)
+    }
+
+    /// Execute a request given in the Legado URL format (`url,{options}` or an
+    /// options object with a `url` field) and return the full response
+    ///
+    /// `headers_json` is an optional JSON object of extra headers.
+    pub fn connect(
+        &self,
+        url: &str,
+        headers_json: Option<&str>,
+        context: &ExecutionContext,
+    ) -> Result<StrResponse> {
+        let client = self.http_client()?;
+        let mut config = client.parse_request_config_at(url, &context.base_url);
+        merge_headers(&mut config, headers_json);
+        client.fetch(&config)
+    }
+
+    /// Download a font for `java.queryTTF`, sending the source's headers and cookies
+    fn download_font(&self, url: &str, context: &ExecutionContext) -> Result<Vec<u8>> {
+        let client = self.http_client()?;
+        let config = client.parse_request_config_at(url, &context.base_url);
+        Ok(client.request_bytes(&config, query_ttf::MAX_FONT_BYTES)?.data)
     }
 
     /// Execute a native API call
@@ -326,16 +401,15 @@ impl NativeApiProvider {
                     .unwrap_or(Ok(String::new()))
             }
 
-            // Delete file
+            // Delete file inside the cache directory
             NativeApi::DeleteFile => {
+                use super::native_file::NativeFileOps;
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
                 if path.is_empty() {
                     return Ok("false".to_string());
                 }
-                match std::fs::remove_file(path) {
-                    Ok(_) => Ok("true".to_string()),
-                    Err(_) => Ok("false".to_string()),
-                }
+                let ops = NativeFileOps::new(self.cache_dir.clone());
+                Ok(ops.delete_file(path).to_string())
             }
 
             // ============== New APIs ==============
@@ -357,72 +431,62 @@ impl NativeApiProvider {
 
             // HTTP APIs - Delegate to native_http module
             // HTTP APIs
+            // java.ajax(url) / java.get(url, headers): the URL may carry Legado options
             NativeApi::HttpGet => {
-                use super::native_http::NativeHttpClient;
                 let url = args.first().map(|s| s.as_str()).unwrap_or("");
-                // Headers parsing if needed, assumed empty for now or parse args[1] if JSON
-                let headers = std::collections::HashMap::new();
-
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::with_headers(cache_dir, headers.clone())?;
-                let resp = client.get(url, &headers)?;
-                Ok(resp.body) // Return body string for legacy java.ajax compatibility
+                let headers = args.get(1).map(|s| s.as_str());
+                Ok(self.connect(url, headers, context)?.body)
             }
 
+            // java.post(url, body, headers)
             NativeApi::HttpPost => {
-                use super::native_http::NativeHttpClient;
                 let url = args.first().map(|s| s.as_str()).unwrap_or("");
-                let body = args.get(1).map(|s| s.as_str()).unwrap_or("");
-
-                let headers_json = args.get(2).map(|s| s.as_str()).unwrap_or("{}");
-                let headers: std::collections::HashMap<String, String> =
-                    serde_json::from_str(headers_json).unwrap_or_default();
-
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::with_headers(cache_dir, headers.clone())?;
-                let resp = client.post(url, body, &headers)?;
-                Ok(resp.body) // Return body string for legacy java.post compatibility
+                let client = self.http_client()?;
+                let mut config = client.parse_request_config_at(url, &context.base_url);
+                config.method = "POST".to_string();
+                config.body = Some(args.get(1).cloned().unwrap_or_default());
+                merge_headers(&mut config, args.get(2).map(|s| s.as_str()));
+                Ok(client.request(&config)?)
             }
 
             NativeApi::HttpRequest => {
                 // args: method, url, body, headers(json)?
-                use super::native_http::NativeHttpClient;
                 let method = args.first().map(|s| s.as_str()).unwrap_or("GET");
-                let url = args.get(1).map(|s| s.as_str()).unwrap_or("");
+                let url = resolve_absolute_url(&context.base_url, args.get(1).map(|s| s.as_str()).unwrap_or(""));
                 let body = args.get(2).map(|s| s.as_str());
                 let headers = std::collections::HashMap::new();
 
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::with_headers(cache_dir, headers.clone())?;
-                let resp = client.request(method, url, body, &headers)?;
+                let resp = self.native_http_client()?.request(method, &url, body, &headers)?;
                 Ok(resp.to_json())
             }
 
             NativeApi::HttpGetAll => {
                 // args: [url, url, ...] - all args are urls
-                use super::native_http::NativeHttpClient;
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::new(cache_dir)?;
-
-                let urls: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+                let urls: Vec<String> = args.iter().map(|url| resolve_absolute_url(&context.base_url, url)).collect();
 
-                let responses = client.get_all(&urls);
+                let responses = self.native_http_client()?.get_all(&urls);
                 let bodies: Vec<String> = responses.into_iter().map(|r| r.body).collect();
                 Ok(serde_json::to_string(&bodies).unwrap_or_default())
             }
 
+            // java.head(url, headers): response headers as a JSON object
+            NativeApi::HttpHead => {
+                let url = resolve_absolute_url(&context.base_url, args.first().map(|s| s.as_str()).unwrap_or(""));
+                let headers = args
+                    .get(1)
+                    .and_then(|h| serde_json::from_str(h).ok())
+                    .unwrap_or_default();
+                let resp = self.native_http_client()?.head(&url, &headers)?;
+                Ok(serde_json::to_string(&resp.headers)?)
+            }
+
+            // java.getSize(url): -1 when the server sends no Content-Length
+            NativeApi::HttpContentLength => {
+                let url = resolve_absolute_url(&context.base_url, args.first().map(|s| s.as_str()).unwrap_or(""));
+                let size = self.native_http_client()?.content_length(&url)?;
+                Ok(size.map_or_else(|| "-1".to_string(), |n| n.to_string()))
+            }
+
             // KV Storage - delegated to native::storage
             // KV Storage - delegated to native::storage
             NativeApi::CacheGet => {
@@ -432,32 +496,32 @@ impl NativeApiProvider {
             NativeApi::CacheSet => {
                 let key = args.first().map(|s| s.as_str()).unwrap_or("");
                 let value = args.get(1).map(|s| s.as_str()).unwrap_or("");
-                super::native::storage::cache_set(&self.kv_store, key, value)
+                let save_time = args.get(2).map(|s| s.as_str());
+                super::native::storage::cache_set(&self.kv_store, key, value, save_time)
             }
+            // `source.getVariable()` without a key reads the source variable
             NativeApi::SourceVarGet => {
-                let key = args.first().map(|s| s.as_str()).unwrap_or("");
-
-                // Source URL is required for source variables
-                let source_url = if context.base_url.is_empty() {
-                    "global"
-                } else {
-                    &context.base_url
-                };
-                Ok(
-                    super::native::storage::get_source_var(&self.kv_store, source_url, key)
-                        .unwrap_or_default(),
-                )
+                let key = args.first().map(|s| s.as_str()).unwrap_or(SOURCE_VARIABLE_KEY);
+                let scope = context.source_var_scope();
+                Ok(super::native::storage::get_source_var(&self.kv_store, scope, key).unwrap_or_default())
+            }
+            // `book.getVariable()` without a key reads the custom variable
+            NativeApi::BookVarGet => {
+                let variable = args.first().map(|s| s.as_str()).unwrap_or("");
+                let key = args.get(1).map(|s| s.as_str()).unwrap_or(BOOK_CUSTOM_VARIABLE_KEY);
+                Ok(super::native::storage::get_book_var(variable, key))
             }
+            // `source.setVariable(value)` has a single argument
             NativeApi::SourceVarSet => {
-                let key = args.first().map(|s| s.as_str()).unwrap_or("");
-                let value = args.get(1).map(|s| s.as_str()).unwrap_or("");
-
-                let source_url = if context.base_url.is_empty() {
-                    "global"
-                } else {
-                    &context.base_url
+                let (key, value) = match args {
+                    [value] => (SOURCE_VARIABLE_KEY, value.as_str()),
+                    _ => (
+                        args.first().map(|s| s.as_str()).unwrap_or(""),
+                        args.get(1).map(|s| s.as_str()).unwrap_or(""),
+                    ),
                 };
-                super::native::storage::set_source_var(&self.kv_store, source_url, key, value);
+                let scope = context.source_var_scope();
+                super::native::storage::set_source_var(&self.kv_store, scope, key, value);
                 Ok(String::new())
             }
 
@@ -471,37 +535,22 @@ impl NativeApiProvider {
             // File APIs - Delegate to native_file module
             // File APIs - Delegate to native_file module
             NativeApi::CacheFile => {
-                use super::native_http::NativeHttpClient;
-                let url = args.first().map(|s| s.as_str()).unwrap_or("");
+                let url = resolve_absolute_url(&context.base_url, args.first().map(|s| s.as_str()).unwrap_or(""));
                 let save_time = args.get(1).and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
-
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::new(cache_dir)?;
-                client.cache_file(url, save_time)
+                self.native_http_client()?.cache_file(&url, save_time)
             }
 
             NativeApi::ReadFile => {
                 use super::native_file::NativeFileOps;
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.read_file(path)
             }
 
             NativeApi::ReadTxtFile => {
                 use super::native_file::NativeFileOps;
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.read_txt_file(path)
             }
 
@@ -510,34 +559,46 @@ impl NativeApiProvider {
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
                 let charset = args.get(1).map(|s| s.as_str()).unwrap_or("");
 
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.read_txt_file_with_charset(path, charset)
             }
 
             NativeApi::GetFile => {
                 use super::native_file::NativeFileOps;
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
-                Ok(ops.get_file(path))
+                let ops = NativeFileOps::new(self.cache_dir.clone());
+                ops.get_file(path)
+            }
+
+            NativeApi::GetTxtInFolder => {
+                use super::native_file::NativeFileOps;
+                let path = args.first().map(|s| s.as_str()).unwrap_or("");
+                let ops = NativeFileOps::new(self.cache_dir.clone());
+                ops.get_txt_in_folder(path)
             }
 
             NativeApi::ImportScript => {
-                use super::native_http::NativeHttpClient;
                 let path = args.first().map(|s| s.as_str()).unwrap_or("");
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let client = NativeHttpClient::new(cache_dir)?;
-                client.import_script(path)
+                self.native_http_client()?.import_script(path)
+            }
+
+            // Font APIs
+            NativeApi::QueryTtf => {
+                let source = args.first().map(|s| s.as_str()).unwrap_or("");
+                query_ttf::query_ttf(source, &self.cache_dir, |url| self.download_font(url, context))
+            }
+
+            NativeApi::ReplaceFont => {
+                let text = args.first().map(|s| s.as_str()).unwrap_or("");
+                let font1 = args.get(1).map(|s| s.as_str()).unwrap_or("");
+                let font1 = query_ttf::resolve_font(font1, &self.cache_dir, |url| {
+                    self.download_font(url, context)
+                })?;
+                let font2 = args.get(2).map(|s| s.as_str()).unwrap_or("");
+                let font2 = query_ttf::resolve_font(font2, &self.cache_dir, |url| {
+                    self.download_font(url, context)
+                })?;
+                Ok(query_ttf::replace_font(text, &font1, &font2))
             }
 
             // ZIP APIs
@@ -546,11 +607,7 @@ impl NativeApiProvider {
                 let zip_source = args.first().map(|s| s.as_str()).unwrap_or("");
                 let file_path = args.get(1).map(|s| s.as_str()).unwrap_or("");
 
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.zip_read_string(zip_source, file_path)
             }
 
@@ -560,11 +617,7 @@ impl NativeApiProvider {
                 let file_path = args.get(1).map(|s| s.as_str()).unwrap_or("");
                 let charset = args.get(2).map(|s| s.as_str()).unwrap_or("");
 
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.zip_read_string_with_charset(zip_source, file_path, charset)
             }
 
@@ -573,22 +626,14 @@ impl NativeApiProvider {
                 let zip_source = args.first().map(|s| s.as_str()).unwrap_or("");
                 let file_path = args.get(1).map(|s| s.as_str()).unwrap_or("");
 
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.zip_read_bytes(zip_source, file_path)
             }
 
             NativeApi::ZipExtract => {
                 use super::native_file::NativeFileOps;
                 let zip_path = args.first().map(|s| s.as_str()).unwrap_or("");
-                let cache_dir = std::env::current_dir()
-                    .unwrap_or_default()
-                    .join("data")
-                    .join("cache");
-                let ops = NativeFileOps::new(cache_dir);
+                let ops = NativeFileOps::new(self.cache_dir.clone());
                 ops.zip_extract(zip_path)
             }
 
@@ -783,14 +828,9 @@ impl NativeApiProvider {
         self.kv_store.get_cache(key)
     }
 
-    /// Set a cached value
-    pub fn set_cache(&self, key: &str, value: &str) {
-        self.kv_store.set_cache(key, value, 0); // 0 = no expiry for now, or default
-                                                // Async save
-        let store = self.kv_store.clone();
-        tokio::task::spawn(async move {
-            let _ = store.save().await;
-        });
+    /// Set a cached value for `save_time` seconds (0 = never expires)
+    pub fn set_cache(&self, key: &str, value: &str, save_time: i64) {
+        self.kv_store.set_cache_ttl(key, value, save_time);
     }
 
     /// Get source variable
@@ -801,10 +841,23 @@ impl NativeApiProvider {
     /// Set source variable
     pub fn set_source_var(&self, source_url: &str, key: &str, value: &str) {
         self.kv_store.set_source_var(source_url, key, value);
-        let store = self.kv_store.clone();
-        tokio::task::spawn(async move {
-            let _ = store.save().await;
-        });
+    }
+}
+
+/// Add headers from a JSON object (e.g. the `header` argument of `java.get`) to a request
+fn merge_headers(config: &mut RequestConfig, headers_json: Option<&str>) {
+    let Some(json) = headers_json.filter(|s| !s.trim().is_empty()) else {
+        return;
+    };
+    if let Ok(serde_json::Value::Object(obj)) = serde_json::from_str(json) {
+        let headers = config.headers.get_or_insert_with(Default::default);
+        for (key, value) in obj {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            headers.insert(key, value);
+        }
     }
 }
 
@@ -827,6 +880,7 @@ fn ensure_8_bytes(input: &[u8]) -> [u8; 8] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::test_server::{request_path, spawn_server};
     use crate::storage::kv::KvStore;
     use crate::storage::FileStorage;
 
@@ -905,4 +959,31 @@ mod tests {
             .unwrap();
         assert!(result.len() == 36); // UUID format
     }
+
+    /// Answer each request with its request path
+    fn spawn_path_server() -> String {
+        spawn_server(|head, _| (200, vec![("Content-Type", "text/plain".to_string())], request_path(head).to_string()))
+    }
+
+    #[test]
+    fn test_http_get_reuses_client_and_resolves_relative_urls() {
+        let server = spawn_path_server();
+        let cm = Arc::new(CookieManager::new());
+        let provider = NativeApiProvider::new(cm, create_test_kv());
+        let context = ExecutionContext {
+            base_url: format!("{}/book/1/", server),
+            ..Default::default()
+        };
+
+        let absolute = provider
+            .execute(&NativeApi::HttpGet, &["/chapter/2".to_string()], &context)
+            .unwrap();
+        assert_eq!(absolute, "/chapter/2");
+        let relative = provider
+            .execute(&NativeApi::HttpGet, &["3.html".to_string()], &context)
+            .unwrap();
+        assert_eq!(relative, "/book/1/3.html");
+
+        assert_eq!(provider.clients_built(), 1);
+    }
 }
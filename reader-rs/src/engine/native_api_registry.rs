@@ -20,6 +20,7 @@ pub enum ApiCategory {
     String,
     Json,
     Storage,
+    Font,
     Misc,
 }
 
@@ -38,6 +39,7 @@ impl ApiCategory {
             ApiCategory::String => "String",
             ApiCategory::Json => "JSON",
             ApiCategory::Storage => "Storage",
+            ApiCategory::Font => "Font",
             ApiCategory::Misc => "Misc",
         }
     }
@@ -243,6 +245,21 @@ pub fn get_api_info(api: &NativeApi) -> Option<ApiInfo> {
             example: "java.desDecode(data, key, transformation, iv)",
         }),
 
+        // Font
+        NativeApi::QueryTtf => Some(ApiInfo {
+            java_name: "queryTTF",
+            category: ApiCategory::Font,
+            description: "Load a TTF font from URL or base64 data",
+            example: "java.queryTTF('https://example.com/font.ttf')",
+        }),
+
+        NativeApi::ReplaceFont => Some(ApiInfo {
+            java_name: "replaceFont",
+            category: ApiCategory::Font,
+            description: "Decode text drawn with an obfuscated font",
+            example: "java.replaceFont(text, java.queryTTF(url1), java.queryTTF(url2))",
+        }),
+
         // Default for unregistered APIs
         _ => None,
     }
@@ -294,7 +311,9 @@ pub fn get_api_category(api: &NativeApi) -> ApiCategory {
         NativeApi::HttpGet
         | NativeApi::HttpPost
         | NativeApi::HttpRequest
-        | NativeApi::HttpGetAll => ApiCategory::Http,
+        | NativeApi::HttpGetAll
+        | NativeApi::HttpHead
+        | NativeApi::HttpContentLength => ApiCategory::Http,
 
         // File
         NativeApi::CacheFile
@@ -303,6 +322,7 @@ pub fn get_api_category(api: &NativeApi) -> ApiCategory {
         | NativeApi::ReadTxtFileWithCharset
         | NativeApi::DeleteFile
         | NativeApi::GetFile
+        | NativeApi::GetTxtInFolder
         | NativeApi::ImportScript => ApiCategory::File,
 
         // Zip
@@ -317,6 +337,8 @@ pub fn get_api_category(api: &NativeApi) -> ApiCategory {
         | NativeApi::StringTrim
         | NativeApi::StringSubstring { .. }
         | NativeApi::HtmlToText
+        | NativeApi::ChineseT2s
+        | NativeApi::ChineseS2t
         | NativeApi::StringToLowerCase
         | NativeApi::StringToUpperCase
         | NativeApi::StringPadStart { .. }
@@ -328,7 +350,16 @@ pub fn get_api_category(api: &NativeApi) -> ApiCategory {
         | NativeApi::StringStartsWith { .. }
         | NativeApi::StringEndsWith { .. }
         | NativeApi::StringIndexOf { .. }
-        | NativeApi::StringLastIndexOf { .. } => ApiCategory::String,
+        | NativeApi::StringLastIndexOf { .. }
+        | NativeApi::ArrayJoin { .. }
+        | NativeApi::ArrayIndex { .. }
+        | NativeApi::ParseInt
+        | NativeApi::ParseFloat
+        | NativeApi::ToNumber { .. }
+        | NativeApi::ToJsString { .. }
+        | NativeApi::ToBoolean { .. }
+        | NativeApi::JsConcat
+        | NativeApi::JsArithmetic { .. } => ApiCategory::String,
 
         // JSON
         NativeApi::JsonPath | NativeApi::JsonParse | NativeApi::JsonStringify => ApiCategory::Json,
@@ -337,17 +368,21 @@ pub fn get_api_category(api: &NativeApi) -> ApiCategory {
         NativeApi::CacheGet
         | NativeApi::CacheSet
         | NativeApi::SourceVarGet
-        | NativeApi::SourceVarSet => ApiCategory::Storage,
+        | NativeApi::SourceVarSet
+        | NativeApi::BookVarGet => ApiCategory::Storage,
+
+        // Font
+        NativeApi::QueryTtf | NativeApi::ReplaceFont => ApiCategory::Font,
 
         // Misc
-        NativeApi::Log | NativeApi::Unknown(_) => ApiCategory::Misc,
+        NativeApi::Log | NativeApi::UiNoop(_) | NativeApi::Unknown(_) => ApiCategory::Misc,
     }
 }
 
 /// Get total count of registered native APIs
 pub fn get_api_count() -> usize {
     // Approximate count based on preprocessor.rs NativeApi enum
-    60
+    68
 }
 
 /// API statistics for coverage analysis
@@ -365,6 +400,7 @@ pub struct ApiCoverageStats {
     pub string: usize,
     pub json: usize,
     pub storage: usize,
+    pub font: usize,
     pub misc: usize,
 }
 
@@ -384,6 +420,7 @@ impl ApiCoverageStats {
             string: 17,
             json: 3,
             storage: 4,
+            font: 2,
             misc: 2,
         }
     }
@@ -402,6 +439,7 @@ impl ApiCoverageStats {
             + self.string
             + self.json
             + self.storage
+            + self.font
             + self.misc
     }
 }
@@ -105,6 +105,7 @@ mod tests {
         let executor = create_test_executor();
         let context = ExecutionContext {
             base_url: "http://test.com".to_string(),
+            ..Default::default()
         };
         let mut vars = HashMap::new();
         vars.insert("key".to_string(), "hello".to_string());
@@ -117,4 +118,59 @@ mod tests {
         let result = executor.execute(&exec, &context, &vars, None).unwrap();
         assert_eq!(result, "aGVsbG8=");
     }
+
+    /// Analyze `code` and run it natively with `page = 2` and `result = input`
+    fn eval_js(code: &str, input: &str) -> String {
+        use crate::engine::analysis::UnifiedJsAnalyzer;
+        use crate::engine::js_analyzer::AnalysisResult;
+
+        let exec = match UnifiedJsAnalyzer::new().analyze(code) {
+            AnalysisResult::Native(exec) => exec,
+            other => panic!("{} is not native: {:?}", code, other),
+        };
+        let mut vars = HashMap::new();
+        vars.insert("page".to_string(), "2".to_string());
+        create_test_executor()
+            .execute(&exec, &ExecutionContext::default(), &vars, Some(input))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_js_conversions_match_js() {
+        // (expression, result, expected JS output)
+        let cases = [
+            ("parseInt(result) + 1", "41", "42"),
+            ("parseInt(result, 16)", "ff", "255"),
+            ("parseInt(result)", "  0x1A", "26"),
+            ("parseInt(result)", "abc", "NaN"),
+            ("parseFloat(result) * 2", "1.25kg", "2.5"),
+            ("Number(result)", " 12 ", "12"),
+            ("Number(result)", "12px", "NaN"),
+            ("Number(result) / 4", "10", "2.5"),
+            ("String(page).padStart(3, '0')", "", "002"),
+            ("String(page * 1.5)", "", "3"),
+            ("Boolean(result)", "", "false"),
+            ("Boolean(parseInt(result))", "0", "false"),
+            ("'p=' + page", "", "p=2"),
+            ("result + 1", "7", "71"),
+            ("page + 1", "", "3"),
+            ("(page - 1) * 20", "", "20"),
+            ("result.indexOf('b') + 1", "abc", "2"),
+        ];
+        for (code, input, expected) in cases {
+            assert_eq!(eval_js(code, input), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_untyped_addition_falls_back_to_js() {
+        use crate::engine::analysis::UnifiedJsAnalyzer;
+        use crate::engine::js_analyzer::AnalysisResult;
+
+        // `x` may be a number or a string at runtime
+        for code in ["x + 1", "Boolean(x)", "true + 1"] {
+            let result = UnifiedJsAnalyzer::new().analyze(code);
+            assert!(matches!(result, AnalysisResult::RequiresJs(_)), "{}", code);
+        }
+    }
 }
@@ -2,7 +2,11 @@
 //!
 //! This module provides Rust-native implementations of java file and ZIP APIs,
 //! eliminating the need for JS execution.
+//!
+//! Paths come from book source rules, which are untrusted: every path is
+//! resolved inside the cache directory by [`sandbox::resolve`].
 
+use crate::storage::sandbox;
 use anyhow::Result;
 use std::fs;
 use std::io::Read;
@@ -19,22 +23,27 @@ impl NativeFileOps {
         Self { cache_dir }
     }
     
+    /// Resolve a rule-supplied path inside the cache directory
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        Ok(sandbox::resolve(&self.cache_dir, path)?)
+    }
+
     /// Read file as bytes (returns hex encoded string)
     pub fn read_file(&self, path: &str) -> Result<String> {
-        let bytes = fs::read(path)?;
+        let bytes = fs::read(self.resolve(path)?)?;
         Ok(hex::encode(&bytes))
     }
     
     /// Read text file
     pub fn read_txt_file(&self, path: &str) -> Result<String> {
-        Ok(fs::read_to_string(path)?)
+        Ok(fs::read_to_string(self.resolve(path)?)?)
     }
     
     /// Read text file with specific charset
     pub fn read_txt_file_with_charset(&self, path: &str, charset: &str) -> Result<String> {
         use encoding_rs::{GB18030, GBK, UTF_8};
         
-        let bytes = fs::read(path)?;
+        let bytes = fs::read(self.resolve(path)?)?;
         if bytes.is_empty() {
             return Ok(String::new());
         }
@@ -50,18 +59,40 @@ impl NativeFileOps {
     
     /// Delete file
     pub fn delete_file(&self, path: &str) -> bool {
-        fs::remove_file(path).is_ok()
+        self.resolve(path).is_ok_and(|path| fs::remove_file(path).is_ok())
     }
     
-    /// Get file path (resolve cache path)
-    pub fn get_file(&self, path: &str) -> String {
-        if path.starts_with('/') || path.starts_with('\\') {
-            self.cache_dir.join(&path[1..]).to_string_lossy().to_string()
+    /// Get file path (resolve cache path), a leading `/` is relative to the cache directory
+    pub fn get_file(&self, path: &str) -> Result<String> {
+        let relative = if Path::new(path).starts_with(&self.cache_dir) {
+            path
         } else {
-            self.cache_dir.join(path).to_string_lossy().to_string()
+            path.trim_start_matches(['/', '\\'])
+        };
+        Ok(self.resolve(relative)?.to_string_lossy().to_string())
+    }
+
+    /// Read all text files of a cache folder (e.g. one extracted by unzipFile),
+    /// in name order and joined by newlines, then delete the folder like Legado
+    pub fn get_txt_in_folder(&self, path: &str) -> Result<String> {
+        let folder = PathBuf::from(self.get_file(path)?);
+        let mut files: Vec<PathBuf> = fs::read_dir(&folder)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect();
+        files.sort();
+
+        let contents: Vec<String> = files
+            .iter()
+            .filter_map(|file| fs::read(file).ok())
+            .map(|bytes| decode_text(&bytes))
+            .collect();
+        if folder != self.cache_dir {
+            let _ = fs::remove_dir_all(&folder);
         }
+        Ok(contents.join("\n"))
     }
-    
+
     // ============== ZIP Operations ==============
     
     /// Read string content from ZIP file
@@ -130,7 +161,7 @@ impl NativeFileOps {
             return Ok(String::new());
         }
         
-        let zip_file = fs::File::open(zip_path)?;
+        let zip_file = fs::File::open(self.resolve(zip_path)?)?;
         let mut archive = ZipArchive::new(zip_file)?;
         
         // Create extraction directory
@@ -144,8 +175,12 @@ impl NativeFileOps {
         // Extract all files
         for i in 0..archive.len() {
             if let Ok(mut file) = archive.by_index(i) {
-                let file_path = extract_dir.join(file.name());
-                
+                // Entries like `../x` must not escape the extraction directory
+                let Ok(file_path) = sandbox::resolve(&extract_dir, file.name()) else {
+                    tracing::warn!("Skipping unsafe zip entry: {}", file.name());
+                    continue;
+                };
+
                 if file.is_dir() {
                     fs::create_dir_all(&file_path)?;
                 } else {
@@ -190,11 +225,21 @@ impl NativeFileOps {
     }
 }
 
+/// Decode a text file by its BOM, else as UTF-8, else as GB18030 (a superset of GBK)
+fn decode_text(bytes: &[u8]) -> String {
+    let encoding = match std::str::from_utf8(bytes) {
+        Ok(_) => encoding_rs::UTF_8,
+        Err(_) => encoding_rs::GB18030,
+    };
+    encoding.decode(bytes).0.into_owned()
+}
+
 /// Native String Operations
 pub struct NativeStringOps;
 
 impl NativeStringOps {
-    /// String replace (regex or literal)
+    /// String replace (regex or literal) with JS semantics, see
+    /// [`string_replace`](super::native::string_ops::string_replace)
     pub fn replace(
         input: &str, 
         pattern: &str, 
@@ -202,23 +247,8 @@ impl NativeStringOps {
         is_regex: bool, 
         global: bool
     ) -> String {
-        if is_regex {
-            if let Ok(re) = regex::Regex::new(pattern) {
-                if global {
-                    re.replace_all(input, replacement).to_string()
-                } else {
-                    re.replace(input, replacement).to_string()
-                }
-            } else {
-                input.to_string()
-            }
-        } else {
-            if global {
-                input.replace(pattern, replacement)
-            } else {
-                input.replacen(pattern, replacement, 1)
-            }
-        }
+        super::native::string_ops::string_replace(input, pattern, replacement, is_regex, global)
+            .unwrap_or_else(|_| input.to_string())
     }
     
     /// String split
@@ -350,4 +380,67 @@ mod tests {
         let result = NativeJsonOps::json_path(json, "name").unwrap();
         assert_eq!(result, "test");
     }
+
+    #[test]
+    fn test_get_txt_in_folder() {
+        let cache_dir = std::env::temp_dir().join(format!("reader_tests_native_file_{}", uuid::Uuid::new_v4()));
+        let folder = cache_dir.join("book");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("2.txt"), encoding_rs::GBK.encode("第二章").0).unwrap();
+        fs::write(folder.join("1.txt"), "第一章").unwrap();
+
+        let ops = NativeFileOps::new(cache_dir.clone());
+        assert_eq!(ops.get_txt_in_folder("/book").unwrap(), "第一章\n第二章");
+        assert!(!folder.exists());
+
+        // Folders outside the cache directory are neither read nor deleted
+        let outside = cache_dir.with_extension("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("1.txt"), "secret").unwrap();
+        let escape = format!("../{}", outside.file_name().unwrap().to_string_lossy());
+        assert!(ops.get_txt_in_folder(&escape).is_err());
+        assert!(outside.join("1.txt").exists());
+        let _ = fs::remove_dir_all(&outside);
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn test_paths_stay_in_cache_dir() {
+        use std::io::Write;
+
+        let root = std::env::temp_dir().join(format!("reader_tests_native_file_{}", uuid::Uuid::new_v4()));
+        let cache_dir = root.join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        let ops = NativeFileOps::new(cache_dir.clone());
+
+        let outside = root.join("secret.txt");
+        for path in ["../secret.txt", outside.to_str().unwrap()] {
+            assert!(ops.read_txt_file(path).is_err(), "{}", path);
+            assert!(ops.read_file(path).is_err(), "{}", path);
+            assert!(!ops.delete_file(path), "{}", path);
+        }
+        assert!(ops.get_file("/../secret.txt").is_err());
+        assert!(outside.exists());
+
+        // getFile paths round-trip through the other file APIs
+        let inside = ops.get_file("/a.txt").unwrap();
+        fs::write(&inside, "a").unwrap();
+        assert_eq!(ops.read_txt_file(&inside).unwrap(), "a");
+        assert_eq!(ops.read_txt_file("a.txt").unwrap(), "a");
+        assert!(ops.delete_file(&inside));
+
+        // Zip entries can't escape the extraction directory
+        let mut zip = zip::ZipWriter::new(fs::File::create(cache_dir.join("book.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in [("../../evil.txt", "evil"), ("1.txt", "ok")] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        assert_eq!(ops.zip_extract("book.zip").unwrap(), "/book");
+        assert_eq!(ops.get_txt_in_folder("/book").unwrap(), "ok");
+        assert!(!root.join("evil.txt").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
 }
@@ -8,6 +8,8 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use super::deadline;
+
 /// HTTP Response from native API
 #[derive(Debug, Clone)]
 pub struct NativeHttpResponse {
@@ -38,6 +40,11 @@ use std::sync::OnceLock;
 
 static GLOBAL_CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
 
+/// Timeout of one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Requests `get_all` runs at the same time
+const GET_ALL_CONCURRENCY: usize = 8;
+
 /// Native HTTP Client for direct Rust execution
 pub struct NativeHttpClient {
     client: reqwest::blocking::Client,
@@ -51,7 +58,7 @@ impl NativeHttpClient {
         let client = GLOBAL_CLIENT
             .get_or_init(|| {
                 reqwest::blocking::Client::builder()
-                    .timeout(Duration::from_secs(30))
+                    .timeout(REQUEST_TIMEOUT)
                     .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
                     .danger_accept_invalid_certs(true)
                     .gzip(true)
@@ -89,6 +96,22 @@ impl NativeHttpClient {
         self.request("POST", url, Some(body), headers)
     }
 
+    /// Execute HTTP HEAD request
+    pub fn head(&self, url: &str, headers: &HashMap<String, String>) -> Result<NativeHttpResponse> {
+        self.request("HEAD", url, None, headers)
+    }
+
+    /// Content length of a URL from a HEAD request, without downloading the body
+    ///
+    /// Returns None when the server doesn't send a Content-Length.
+    pub fn content_length(&self, url: &str) -> Result<Option<u64>> {
+        let response = self.head(url, &HashMap::new())?;
+        Ok(response
+            .headers
+            .get("content-length")
+            .and_then(|len| len.trim().parse().ok()))
+    }
+
     /// Execute generic HTTP request
     pub fn request(
         &self,
@@ -125,74 +148,59 @@ impl NativeHttpClient {
                 .body(body_str.to_string());
         }
 
-        let response = request.send()?;
+        if let Some(deadline) = deadline::current() {
+            deadline.check()?;
+            request = request.timeout(deadline.trim(REQUEST_TIMEOUT));
+        }
+        let response = request.send().map_err(|e| deadline::attribute(e.into()))?;
+        Ok(Self::read_response(response))
+    }
 
+    fn read_response(response: reqwest::blocking::Response) -> NativeHttpResponse {
         let status_code = response.status().as_u16();
-        let final_url = response.url().to_string();
+        let url = response.url().to_string();
 
         // Collect headers
-        let mut resp_headers = HashMap::new();
+        let mut headers = HashMap::new();
         for (name, value) in response.headers().iter() {
             if let Ok(val_str) = value.to_str() {
-                resp_headers.insert(name.as_str().to_string(), val_str.to_string());
+                headers.insert(name.as_str().to_string(), val_str.to_string());
             }
         }
 
         let body = response.text().unwrap_or_default();
 
-        Ok(NativeHttpResponse {
+        NativeHttpResponse {
             body,
-            headers: resp_headers,
+            headers,
             status_code,
-            url: final_url,
-        })
+            url,
+        }
     }
 
-    /// Execute concurrent GET requests
+    /// Execute concurrent GET requests on the shared pool, at most
+    /// `GET_ALL_CONCURRENCY` at a time; failed requests are left out
     pub fn get_all(&self, urls: &[String]) -> Vec<NativeHttpResponse> {
-        use std::thread;
-
-        let handles: Vec<_> = urls
-            .iter()
-            .map(|url| {
-                let url = url.clone();
-                let client = self.client.clone();
-
-                thread::spawn(move || {
-                    let response = client
-                        .get(&url)
-                        .header(
-                            "User-Agent",
-                            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
-                        )
-                        .send()
-                        .ok()?;
-
-                    let status_code = response.status().as_u16();
-                    let final_url = response.url().to_string();
-
-                    let mut headers = HashMap::new();
-                    for (name, value) in response.headers().iter() {
-                        if let Ok(val_str) = value.to_str() {
-                            headers.insert(name.as_str().to_string(), val_str.to_string());
-                        }
-                    }
-
-                    let body = response.text().unwrap_or_default();
-
-                    Some(NativeHttpResponse {
-                        body,
-                        headers,
-                        status_code,
-                        url: final_url,
-                    })
+        // The worker threads stay within the caller's request deadline
+        let deadline = deadline::current();
+        urls.chunks(GET_ALL_CONCURRENCY)
+            .flat_map(|batch| {
+                std::thread::scope(|scope| {
+                    let handles: Vec<_> = batch
+                        .iter()
+                        .map(|url| {
+                            scope.spawn(move || {
+                                let _deadline = deadline::enter(deadline);
+                                self.get(url, &HashMap::new()).ok()
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .filter_map(|h| h.join().ok().flatten())
+                        .collect::<Vec<_>>()
                 })
             })
-            .collect();
-
-        handles
-            .into_iter()
-            .filter_map(|h| h.join().ok().flatten())
             .collect()
     }
 
@@ -247,7 +255,8 @@ impl NativeHttpClient {
             // Download with permanent cache
             self.cache_file(path, 0)
         } else {
-            // Local file
+            // Local files are limited to the cache directory
+            let path = crate::storage::sandbox::resolve(&self.cache_dir, path)?;
             Ok(fs::read_to_string(path)?)
         }
     }
@@ -256,6 +265,7 @@ impl NativeHttpClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::test_server::spawn_server;
     use std::env;
 
     fn create_test_client() -> NativeHttpClient {
@@ -277,5 +287,30 @@ mod tests {
         assert!(json.contains("\"code\":200"));
     }
 
-    // HTTP tests would require network access, skip in unit tests
+    /// Answer HEAD requests with a fixed Content-Length and the request method
+    fn spawn_head_server() -> String {
+        spawn_server(|head, _| {
+            let method = head.split_whitespace().next().unwrap_or("").to_string();
+            let headers = vec![
+                ("Content-Type", "application/zip".to_string()),
+                ("Content-Length", "123456".to_string()),
+                ("X-Method", method),
+            ];
+            (200, headers, "")
+        })
+    }
+
+    #[test]
+    fn test_head_and_content_length() {
+        let client = create_test_client();
+
+        let url = format!("{}/book.zip", spawn_head_server());
+        let response = client.head(&url, &HashMap::new()).unwrap();
+        assert_eq!(response.headers["x-method"], "HEAD");
+        assert_eq!(response.headers["content-length"], "123456");
+        assert!(response.body.is_empty());
+
+        let size = client.content_length(&url).unwrap();
+        assert_eq!(size, Some(123456));
+    }
 }
@@ -2,7 +2,7 @@
 
 use anyhow::{Result, anyhow};
 use scraper::{Html, Selector};
-use super::Parser;
+use super::{Parser, RuleSyntaxError};
 
 pub struct CssParser;
 
@@ -110,6 +110,17 @@ impl Parser for CssParser {
     }
 }
 
+/// Check that the selector part of a CSS rule parses
+pub fn check(rule: &str) -> std::result::Result<(), RuleSyntaxError> {
+    let (selector_str, _) = parse_css_rule(strip_css_prefix(rule));
+    if selector_str.is_empty() {
+        return Ok(());
+    }
+    Selector::parse(&selector_str)
+        .map(|_| ())
+        .map_err(|e| RuleSyntaxError::new(format!("Invalid CSS selector '{}': {}", selector_str, e)))
+}
+
 /// Strip CSS prefixes from rule
 fn strip_css_prefix(rule: &str) -> &str {
     let rule_lower = rule.to_lowercase();
@@ -0,0 +1,312 @@
+//! JSON islands embedded in HTML pages
+//!
+//! Many sites ship their data as a script assignment (`window.__DATA__ = {...};`)
+//! or a `<script type="application/json">` block instead of a JSON API.
+//! `@jsonEmbed:<name>` extracts such an island by variable name or script id,
+//! `@jsonEmbed:<regex>` by the first capture group of a regex. The result is
+//! normalized JSON, so the next chain step is detected as JSONPath.
+//!
+//! Islands are often JS object literals rather than strict JSON, so parsing
+//! falls back to a lenient pass that quotes bare keys, converts single-quoted
+//! strings, drops trailing commas and maps `undefined` to `null`.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+
+use super::RuleSyntaxError;
+
+const EMBED_PREFIX: &str = "@jsonEmbed:";
+
+/// A JS identifier path such as `window.__INITIAL_STATE__` or `pageData`
+static IDENT_PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z_$][\w$]*(\.[A-Za-z_$][\w$]*)*$").unwrap());
+
+/// Strip the `@jsonEmbed:` prefix (case-insensitive), returning the target
+pub fn strip_prefix(rule: &str) -> Option<&str> {
+    let rule = rule.trim();
+    let head = rule.get(..EMBED_PREFIX.len())?;
+    head.eq_ignore_ascii_case(EMBED_PREFIX).then(|| rule[EMBED_PREFIX.len()..].trim())
+}
+
+/// Check an `@jsonEmbed:` target: a variable name or a valid regex
+pub fn check(target: &str) -> Result<(), RuleSyntaxError> {
+    if target.is_empty() {
+        return Err(RuleSyntaxError::new("@jsonEmbed: needs a variable name or regex"));
+    }
+    if IDENT_PATH.is_match(target) {
+        return Ok(());
+    }
+    Regex::new(target)
+        .map(|_| ())
+        .map_err(|e| RuleSyntaxError::new(format!("Invalid @jsonEmbed regex '{}': {}", target, e)))
+}
+
+/// Find the JSON island named by `target` in `content`
+pub fn extract(content: &str, target: &str) -> Option<Value> {
+    if IDENT_PATH.is_match(target) {
+        find_assignment(content, target).or_else(|| find_script_by_id(content, target))
+    } else {
+        let re = Regex::new(target).ok()?;
+        let value = re.captures_iter(content).find_map(|caps| {
+            let text = caps.get(1).or_else(|| caps.get(0))?.as_str();
+            parse_value_text(text)
+        });
+        value
+    }
+}
+
+/// Parse JSON, tolerating a BOM, surrounding whitespace, trailing semicolons
+/// and JS object literal syntax
+pub fn parse_lenient(content: &str) -> serde_json::Result<Value> {
+    let text = trim_json(content);
+    serde_json::from_str(text).or_else(|e| serde_json::from_str(&normalize(text)).map_err(|_| e))
+}
+
+/// Strip a BOM, whitespace and trailing semicolons around a JSON text
+pub fn trim_json(content: &str) -> &str {
+    content
+        .trim()
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+}
+
+/// `name = <value>` assignments, trying each occurrence until one parses
+fn find_assignment(content: &str, name: &str) -> Option<Value> {
+    content.match_indices(name).find_map(|(pos, _)| {
+        let before = content[..pos].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '$' | '.')) {
+            return None;
+        }
+        let rest = content[pos + name.len()..].trim_start();
+        let rest = rest.strip_prefix('=').filter(|r| !r.starts_with('='))?;
+        parse_value_text(rest)
+    })
+}
+
+/// `<script id="name">` blocks, as used by Next.js (`__NEXT_DATA__`)
+fn find_script_by_id(content: &str, id: &str) -> Option<Value> {
+    let pattern = format!(
+        r#"(?is)<script[^>]*\bid\s*=\s*["']{}["'][^>]*>(.*?)</script>"#,
+        regex::escape(id)
+    );
+    let re = Regex::new(&pattern).ok()?;
+    let value = re.captures_iter(content).find_map(|caps| parse_lenient(&caps[1]).ok());
+    value
+}
+
+/// Parse the value at the start of `text`: an object or array literal, a
+/// string literal holding JSON, or `JSON.parse(<string literal>)`
+fn parse_value_text(text: &str) -> Option<Value> {
+    let text = trim_json(text);
+    let text = match text.strip_prefix("JSON.parse(") {
+        Some(inner) => inner.trim_start(),
+        None => text,
+    };
+    let end = value_end(text)?;
+    let literal = &text[..end];
+    match literal.as_bytes()[0] {
+        b'"' | b'\'' => {
+            let decoded: String = serde_json::from_str(&normalize(literal)).ok()?;
+            parse_lenient(&decoded).ok()
+        }
+        _ => parse_lenient(literal).ok(),
+    }
+}
+
+/// Byte length of the object, array or string literal at the start of `text`
+fn value_end(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if let Some(&quote @ (b'"' | b'\'')) = bytes.first() {
+        return string_end(bytes, 0, quote);
+    }
+    if !matches!(bytes.first(), Some(b'{' | b'[')) {
+        return None;
+    }
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i = string_end(bytes, i, quote)?;
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// End (exclusive) of the string literal starting at `start`
+fn string_end(bytes: &[u8], start: usize, quote: u8) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b if b == quote => return Some(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Rewrite a JS object literal as JSON
+fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    out.push(c);
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                out.push('"');
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some((_, '\'')) => out.push('\''),
+                            Some((_, c)) => {
+                                out.push('\\');
+                                out.push(c);
+                            }
+                            None => {}
+                        },
+                        '"' => out.push_str("\\\""),
+                        '\'' => break,
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            ',' => {
+                let next = text[i + 1..].trim_start().chars().next();
+                if !matches!(next, Some('}' | ']')) {
+                    out.push(c);
+                }
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '$') {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                let word = &text[i..end];
+                let after_key_start = matches!(out.trim_end().chars().next_back(), Some('{' | ','));
+                if after_key_start && text[end..].trim_start().starts_with(':') {
+                    out.push('"');
+                    out.push_str(word);
+                    out.push('"');
+                } else if word == "undefined" {
+                    out.push_str("null");
+                } else {
+                    out.push_str(word);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Server-rendered state assigned on `window`, followed by more script
+    const STATE_PAGE: &str = r#"<html><head><title>书籍详情</title></head><body>
+<div id="app"></div>
+<script>window.__INITIAL_STATE__={"book":{"id":1024,"name":"诡秘之主","author":"爱潜水的乌贼","intro":"<p>蒸汽与机械</p>"},"chapters":[{"title":"第一章 绯红","url":"/1024/1.html"},{"title":"第二章 情况","url":"/1024/2.html"}]};(function(){var s;(s=document.currentScript||document.scripts[document.scripts.length-1]).parentNode.removeChild(s);}());</script>
+</body></html>"#;
+
+    /// A hand-written JS literal: bare keys, single quotes, trailing commas
+    const VAR_PAGE: &str = r#"<script type="text/javascript">
+    var bookId = 88;
+    var pageData = {
+        bookName: '雪中悍刀行',
+        author: "烽火戏诸侯",
+        lastChapter: 'Chapter \'1200\'',
+        cover: undefined,
+        chapterList: [
+            {name: '第一章 小二上酒', url: '/88/1.html', vip: false},
+            {name: '第二章 天下', url: '/88/2.html', vip: true,},
+        ],
+    };
+    </script>"#;
+
+    /// Next.js ships page props in a JSON script block
+    const NEXT_PAGE: &str = r#"<!DOCTYPE html><html><body><div id="__next"></div>
+<script id="__NEXT_DATA__" type="application/json">{"props":{"pageProps":{"books":[{"title":"三体","bid":"b1"},{"title":"球状闪电","bid":"b2"}]}},"page":"/search"}</script>
+<script>window.__CONFIG__ = JSON.parse('{"cdn":"https:\/\/img.example.com","size":20}');</script>
+</body></html>"#;
+
+    #[test]
+    fn test_extract_window_assignment() {
+        let value = extract(STATE_PAGE, "window.__INITIAL_STATE__").unwrap();
+        assert_eq!(value["book"]["name"], "诡秘之主");
+        assert_eq!(value["book"]["intro"], "<p>蒸汽与机械</p>");
+        assert_eq!(value["chapters"][1]["url"], "/1024/2.html");
+    }
+
+    #[test]
+    fn test_extract_js_literal_with_bare_keys() {
+        let value = extract(VAR_PAGE, "pageData").unwrap();
+        assert_eq!(value["bookName"], "雪中悍刀行");
+        assert_eq!(value["lastChapter"], "Chapter '1200'");
+        assert_eq!(value["cover"], Value::Null);
+        assert_eq!(value["chapterList"][1], json!({"name": "第二章 天下", "url": "/88/2.html", "vip": true}));
+        // `bookId` is a number, not an island
+        assert_eq!(extract(VAR_PAGE, "bookId"), None);
+    }
+
+    #[test]
+    fn test_extract_script_id_regex_and_json_parse() {
+        let value = extract(NEXT_PAGE, "__NEXT_DATA__").unwrap();
+        assert_eq!(value["props"]["pageProps"]["books"][0]["title"], "三体");
+        let config = extract(NEXT_PAGE, "window.__CONFIG__").unwrap();
+        assert_eq!(config, json!({"cdn": "https://img.example.com", "size": 20}));
+        let by_regex = extract(NEXT_PAGE, r#"(?s)type="application/json">(.*?)</script>"#).unwrap();
+        assert_eq!(by_regex["page"], "/search");
+        assert_eq!(extract(NEXT_PAGE, "window.__MISSING__"), None);
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        assert_eq!(parse_lenient("\u{feff} {\"a\": 1};\n").unwrap(), json!({"a": 1}));
+        assert_eq!(parse_lenient("{a: 'x', b: [1, 2,],}").unwrap(), json!({"a": "x", "b": [1, 2]}));
+        // Words inside strings are left alone
+        assert_eq!(parse_lenient("{'k': 'a: b, c'}").unwrap(), json!({"k": "a: b, c"}));
+        assert!(parse_lenient("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check("window.__DATA__").is_ok());
+        assert!(check(r"data\s*=\s*(\{.*?\});").is_ok());
+        assert!(check("").is_err());
+        assert!(check("data = ([").is_err());
+    }
+}
@@ -1,127 +1,318 @@
 //! JSONPath Parser using jsonpath-rust crate
+//!
+//! Supports `?()` filters (`==`, `!=`, `>`, `<`, `>=`, `<=`, `&&`, `||`),
+//! recursive descent `..`, wildcards, unions, slices with negative bounds,
+//! negative indexes (`[-1]`) and a trailing `.length()`.
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use jsonpath_rust::JsonPath;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde_json::Value;
-use super::Parser;
+use super::{json_embed, Parser, RuleSyntaxError};
+
+/// A negative single index such as `[-1]`, which the underlying crate rejects
+static NEGATIVE_INDEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\s*-(\d+)\s*\]").unwrap());
+
+const LENGTH_SUFFIX: &str = ".length()";
 
 pub struct JsonPathParser;
 
 impl Parser for JsonPathParser {
     fn get_string(&self, content: &str, rule: &str) -> Result<String> {
-        let rule = rule.trim_start_matches("@json:").trim();
-        let json: Value = serde_json::from_str(content)?;
-        
-        let path_str = if rule.starts_with('$') || rule.is_empty() {
-            rule.to_string()
-        } else {
-            format!("$.{}", rule)
-        };
-        
-        let path = JsonPath::try_from(path_str.as_str())?;
-        let result = path.find(&json);
-        
-        // find() returns a Value, which may be an array or single value
-        value_to_string(&result)
-    }
-    
+        // Multiple matches are joined with a newline, like the other parsers
+        let matches = find_matches(content, rule)?;
+        Ok(matches.iter().map(value_to_string).collect::<Vec<_>>().join("\n"))
+    }
+
     fn get_list(&self, content: &str, rule: &str) -> Result<Vec<String>> {
-        let rule = rule.trim_start_matches("@json:").trim();
-        let json: Value = serde_json::from_str(content)?;
-        
-        let path_str = if rule.starts_with('$') || rule.is_empty() {
-            rule.to_string()
-        } else {
-            format!("$.{}", rule)
-        };
-        
-        let path = JsonPath::try_from(path_str.as_str())?;
-        let result = path.find(&json);
-        
-        // If result is an array, extract each element
-        if let Value::Array(arr) = result {
-            Ok(arr.iter()
-                .map(|v| value_to_string(v).unwrap_or_default())
-                .collect())
-        } else {
-            Ok(vec![value_to_string(&result)?])
-        }
+        let matches = flatten_single_array(find_matches(content, rule)?);
+        Ok(matches.iter().map(value_to_string).collect())
     }
-    
+
     fn get_elements(&self, content: &str, rule: &str) -> Result<Vec<String>> {
-        let rule = rule.trim_start_matches("@json:").trim();
-        let json: Value = serde_json::from_str(content)?;
-        
-        let path_str = if rule.starts_with('$') || rule.is_empty() {
-            rule.to_string()
-        } else {
-            format!("$.{}", rule)
+        // Each element as compact JSON; object keys come out sorted, so the
+        // same response always yields the same element strings
+        let matches = flatten_single_array(find_matches(content, rule)?);
+        Ok(matches.iter().map(|v| v.to_string()).collect())
+    }
+}
+
+/// Evaluate a rule and return the matched values in order
+fn find_matches(content: &str, rule: &str) -> Result<Vec<Value>> {
+    if let Some(target) = json_embed::strip_prefix(rule) {
+        let island = json_embed::extract(content, target);
+        if island.is_none() {
+            tracing::debug!("No JSON island found for @jsonEmbed:{}", target);
+        }
+        return Ok(island.into_iter().collect());
+    }
+    let rule = strip_prefix(rule);
+    if rule.is_empty() {
+        return Ok(vec![]);
+    }
+    let (rule, length) = match rule.strip_suffix(LENGTH_SUFFIX) {
+        Some(base) => (base, true),
+        None => (rule, false),
+    };
+
+    let json = json_embed::parse_lenient(content)?;
+    let path_str = normalize_path(rule);
+    let path = JsonPath::try_from(path_str.as_str())?;
+    let matches = match path.find(&json) {
+        Value::Array(arr) => arr,
+        Value::Null => vec![],
+        value => vec![value],
+    };
+
+    if length {
+        // Length of a single matched array, otherwise the number of matches
+        let count = match matches.as_slice() {
+            [Value::Array(arr)] => arr.len(),
+            _ => matches.len(),
         };
-        
-        let path = JsonPath::try_from(path_str.as_str())?;
-        let result = path.find(&json);
-        
-        // Return each matched element as JSON string
-        if let Value::Array(arr) = result {
-            // Check if we have a single result that is itself an array (nested array)
-            // This happens when path selects an array field like $.rows -> [[item1, item2]]
-            // We want to return [item1, item2] so we can iterate over them
-            if arr.len() == 1 {
-                if let Some(Value::Array(inner)) = arr.first() {
-                    tracing::debug!("JsonPath matched single nested array with {} elements, flattening", inner.len());
-                    return Ok(inner.iter()
-                        .map(|v| v.to_string())
-                        .collect());
+        return Ok(vec![Value::from(count)]);
+    }
+    Ok(matches)
+}
+
+/// Check JSONPath syntax without evaluating it
+pub fn check(rule: &str) -> std::result::Result<(), RuleSyntaxError> {
+    if let Some(target) = json_embed::strip_prefix(rule) {
+        return json_embed::check(target);
+    }
+    let rule = strip_prefix(rule);
+    if rule.is_empty() {
+        return Ok(());
+    }
+    let rule = rule.strip_suffix(LENGTH_SUFFIX).unwrap_or(rule);
+    JsonPath::<Value>::try_from(normalize_path(rule).as_str())
+        .map(|_| ())
+        .map_err(|e| RuleSyntaxError::new(format!("Invalid JSONPath '{}': {}", rule, e)))
+}
+
+/// Strip the `@json:` / `json:` prefix (case-insensitive)
+fn strip_prefix(rule: &str) -> &str {
+    let rule = rule.trim();
+    for prefix in ["@json:", "json:"] {
+        let head = rule.get(..prefix.len());
+        if head.is_some_and(|head| head.eq_ignore_ascii_case(prefix)) {
+            return rule[prefix.len()..].trim();
+        }
+    }
+    rule
+}
+
+/// Add the `$` root to bare paths (`name`, `.name`, `..name`, `[0].x`), quote
+/// keys the JSONPath grammar rejects and rewrite `[-n]` as an equivalent slice
+fn normalize_path(rule: &str) -> String {
+    let path = if rule.starts_with('$') {
+        rule.to_string()
+    } else if rule.starts_with("..") || rule.starts_with('[') {
+        format!("${}", rule)
+    } else {
+        format!("$.{}", rule.strip_prefix('.').unwrap_or(rule))
+    };
+    let path = quote_keys(&path);
+    NEGATIVE_INDEX
+        .replace_all(&path, |caps: &regex::Captures| match &caps[1] {
+            "1" => "[-1:]".to_string(),
+            n => {
+                let n: i64 = n.parse().unwrap_or(1);
+                format!("[-{}:-{}]", n, n - 1)
+            }
+        })
+        .into_owned()
+}
+
+/// Rewrite dot-notation keys with CJK characters or spaces (`$.书名`) as `$['书名']`
+fn quote_keys(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut rest = path;
+    while let Some(c) = rest.chars().next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') if depth > 0 => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            (None, '.') if depth == 0 => {
+                let after = &rest[1..];
+                let key = &after[..after.find(['.', '[']).unwrap_or(after.len())];
+                if !key.is_empty() && !is_plain_key(key) {
+                    // `$..key` keeps its descent: `$..['key']`
+                    if out.ends_with('.') {
+                        out.push('.');
+                    }
+                    out.push_str(&format!("['{}']", key.replace('\'', "\\'")));
+                    rest = &after[key.len()..];
+                    continue;
                 }
             }
-            
-            Ok(arr.iter()
-                .map(|v| v.to_string())
-                .collect())
-        } else {
-            Ok(vec![result.to_string()])
+            _ => {}
         }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+fn is_plain_key(key: &str) -> bool {
+    key == "*" || key.ends_with("()") || key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A path selecting an array field (like `$.rows`) matches the array itself;
+/// iterate over its items instead
+fn flatten_single_array(matches: Vec<Value>) -> Vec<Value> {
+    match <[Value; 1]>::try_from(matches) {
+        Ok([Value::Array(inner)]) => {
+            tracing::debug!("JsonPath matched single nested array with {} elements, flattening", inner.len());
+            inner
+        }
+        Ok([single]) => vec![single],
+        Err(matches) => matches,
     }
 }
 
 /// Convert serde_json Value to String
-fn value_to_string(value: &Value) -> Result<String> {
+///
+/// Arrays and objects are serialized as JSON text so templates and `<js>`
+/// post-processing receive valid JSON.
+fn value_to_string(value: &Value) -> String {
     match value {
-        Value::String(s) => Ok(s.clone()),
-        Value::Number(n) => Ok(n.to_string()),
-        Value::Bool(b) => Ok(b.to_string()),
-        Value::Null => Ok(String::new()),
-        Value::Array(arr) => {
-            // For array, return first element as string
-            if let Some(first) = arr.first() {
-                value_to_string(first)
-            } else {
-                Err(anyhow!("Empty array"))
-            }
-        }
-        v => Ok(v.to_string()),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => String::new(),
+        v => v.to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    /// Shaped like a typical search API response
+    const API_RESPONSE: &str = r#"{
+        "code": 0,
+        "data": {
+            "total": 3,
+            "list": [
+                {"type": 1, "name": "斗破苍穹", "author": "天蚕土豆", "score": 9.1, "tags": ["玄幻", "热血"]},
+                {"type": 2, "name": "凡人修仙传", "author": "忘语", "score": 8.7, "tags": []},
+                {"type": 1, "name": "遮天", "author": "辰东", "score": 8.2, "tags": ["玄幻"]}
+            ],
+            "volumes": [
+                {"title": "第一卷", "chapters": [{"title": "第一章"}, {"title": "第二章"}]},
+                {"title": "第二卷", "chapters": [{"title": "第三章"}]}
+            ]
+        }
+    }"#;
+
+    fn list(rule: &str) -> Vec<String> {
+        JsonPathParser.get_list(API_RESPONSE, rule).unwrap()
+    }
+
     #[test]
     fn test_jsonpath_string() {
         let json = r#"{"name": "Test Book", "author": "Author"}"#;
         let parser = JsonPathParser;
-        
+
         let result = parser.get_string(json, "$.name").unwrap();
         assert_eq!(result, "Test Book");
     }
-    
+
     #[test]
     fn test_jsonpath_list() {
         let json = r#"{"books": [{"title": "A"}, {"title": "B"}]}"#;
         let parser = JsonPathParser;
-        
+
         let result = parser.get_list(json, "$.books[*].title").unwrap();
         assert_eq!(result, vec!["A", "B"]);
     }
+
+    #[test]
+    fn test_filter_expressions() {
+        assert_eq!(list("$.data.list[?(@.type==1)].name"), vec!["斗破苍穹", "遮天"]);
+        assert_eq!(list("$.data.list[?(@.type != 1)].name"), vec!["凡人修仙传"]);
+        assert_eq!(list("$.data.list[?(@.score > 8.5)].name"), vec!["斗破苍穹", "凡人修仙传"]);
+        assert_eq!(list("$.data.list[?(@.score < 8.5)].name"), vec!["遮天"]);
+        assert_eq!(list("$.data.list[?(@.author == '忘语')].name"), vec!["凡人修仙传"]);
+        assert_eq!(
+            list("$.data.list[?(@.type == 1 && @.score < 9)].name"),
+            vec!["遮天"]
+        );
+        assert_eq!(
+            list(r#"$.data.list[?(@.type == 2 || @.author == "辰东")].name"#),
+            vec!["凡人修仙传", "遮天"]
+        );
+    }
+
+    #[test]
+    fn test_recursive_descent_and_wildcards() {
+        assert_eq!(list("$..chapters[*].title"), vec!["第一章", "第二章", "第三章"]);
+        assert_eq!(list("$.data.volumes[*].title"), vec!["第一卷", "第二卷"]);
+        assert_eq!(list("data.list[*].author"), vec!["天蚕土豆", "忘语", "辰东"]);
+    }
+
+    #[test]
+    fn test_slices_and_negative_indexes() {
+        assert_eq!(list("$.data.list[-1:].name"), vec!["遮天"]);
+        assert_eq!(list("$.data.list[-2:].name"), vec!["凡人修仙传", "遮天"]);
+        assert_eq!(list("$.data.list[:2].name"), vec!["斗破苍穹", "凡人修仙传"]);
+        assert_eq!(list("$.data.list[-2].name"), vec!["凡人修仙传"]);
+        assert_eq!(list("$.data.list[0,2].name"), vec!["斗破苍穹", "遮天"]);
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(JsonPathParser.get_string(API_RESPONSE, "$.data.list.length()").unwrap(), "3");
+        assert_eq!(JsonPathParser.get_string(API_RESPONSE, "$..chapters[*].length()").unwrap(), "3");
+        assert_eq!(
+            JsonPathParser.get_string(API_RESPONSE, "$.data.list[?(@.type==1)].length()").unwrap(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn test_get_string_serializes_json() {
+        let tags = JsonPathParser.get_string(API_RESPONSE, "$.data.list[0].tags").unwrap();
+        assert_eq!(tags, r#"["玄幻","热血"]"#);
+        let volume = JsonPathParser.get_string(API_RESPONSE, "$.data.volumes[1]").unwrap();
+        let parsed: Value = serde_json::from_str(&volume).unwrap();
+        assert_eq!(parsed["chapters"][0]["title"], "第三章");
+        // Multiple matches are joined with a newline
+        assert_eq!(
+            JsonPathParser.get_string(API_RESPONSE, "@json:$.data.list[*].name").unwrap(),
+            "斗破苍穹\n凡人修仙传\n遮天"
+        );
+        assert_eq!(JsonPathParser.get_string(API_RESPONSE, "$.missing").unwrap(), "");
+    }
+
+    #[test]
+    fn test_negative_index_regression() {
+        // Used to fail to parse, which surfaced as an empty book name
+        assert_eq!(
+            JsonPathParser.get_string(API_RESPONSE, "$.data.list[-1].name").unwrap(),
+            "遮天"
+        );
+    }
+
+    #[test]
+    fn test_bare_paths() {
+        let p = JsonPathParser;
+        assert_eq!(p.get_string(API_RESPONSE, "data.total").unwrap(), "3");
+        assert_eq!(p.get_string(API_RESPONSE, ".data.total").unwrap(), "3");
+        assert_eq!(p.get_string(API_RESPONSE, "..total").unwrap(), "3");
+        assert_eq!(p.get_string(r#"[{"name":"遮天"}]"#, "[0].name").unwrap(), "遮天");
+        // Keys the grammar rejects in dot notation
+        let json = r#"{"书名": "遮天", "book info": {"作者": "辰东"}}"#;
+        assert_eq!(p.get_string(json, "书名").unwrap(), "遮天");
+        assert_eq!(p.get_string(json, "$.book info.作者").unwrap(), "辰东");
+        assert_eq!(p.get_string(json, "..作者").unwrap(), "辰东");
+        // A leading byte order mark is ignored
+        assert_eq!(p.get_string("\u{feff}{\"name\": \"遮天\"}", "name").unwrap(), "遮天");
+    }
 }
@@ -1,4 +1,4 @@
-use super::Parser;
+use super::{Parser, RuleSyntaxError};
 use anyhow::{anyhow, Result};
 use scraper::{ElementRef, Html, Selector};
 
@@ -129,7 +129,9 @@ impl Parser for JsoupDefaultParser {
                 .select(&selector)
                 .filter_map(|el| extract_content(&el, &attr).ok())
                 .collect();
-            return Ok(results);
+            if !results.is_empty() {
+                return Ok(results);
+            }
         }
 
         // Fallback
@@ -176,50 +178,15 @@ impl Parser for JsoupDefaultParser {
 
         // 1. Try Standard CSS Selector first (replace @ with space for CSS)
         // Also convert Legado syntax: id.xxx -> #xxx, class.xxx -> .xxx
-        let css_selector = {
-            let mut parts: Vec<String> = Vec::new();
-            for part in selector_to_use.split('@') {
-                let part = part.trim();
-                if part.is_empty() {
-                    continue;
-                }
-
-                // Check for incompatible Legado syntax
-                if part.contains('!') {
-                    return Err(anyhow::anyhow!("Legado exclusion syntax"));
-                }
-
-                // Check for index syntax (.0, .-1) which Legado uses for array indexing, not CSS classes
-                if let Some(pos) = part.find('.') {
-                    let suffix = &part[pos + 1..];
-                    if let Some(c) = suffix.chars().next() {
-                        if c.is_numeric() || c == '-' {
-                            return Err(anyhow::anyhow!("Legado index syntax"));
-                        }
-                    }
-                }
-
-                // Convert id.xxx to #xxx
-                if part.starts_with("id.") {
-                    parts.push(format!("#{}", &part[3..]));
-                }
-                // Convert class.xxx to .xxx
-                else if part.starts_with("class.") {
-                    parts.push(format!(".{}", &part[6..]));
-                } else {
-                    parts.push(part.to_string());
-                }
-            }
-            parts.join(" ")
-        };
+        let css_selector = legado_to_css(&selector_to_use);
 
         tracing::debug!(
-            "get_elements: rule='{}', css_selector='{}'",
+            "get_elements: rule='{}', css_selector={:?}",
             rule,
             css_selector
         );
 
-        if let Ok(selector) = Selector::parse(&css_selector) {
+        if let Some(selector) = css_selector.and_then(|s| Selector::parse(&s).ok()) {
             let results: Vec<String> = root
                 .select(&selector)
                 .map(|element| element.html())
@@ -230,7 +197,7 @@ impl Parser for JsoupDefaultParser {
         }
 
         // 2. Fallback to custom Jsoup parser
-        let (segments, _) = parse_jsoup_rule(&selector_to_use)?;
+        let segments = parse_selector_chain(&selector_to_use);
         let matches = apply_selectors(root, &segments)?;
 
         let results: Vec<String> = matches.iter().map(|el| el.html()).collect();
@@ -241,6 +208,54 @@ impl Parser for JsoupDefaultParser {
 
 // === Parsing Logic ===
 
+/// Check a JSOUP Default rule
+///
+/// Chains that translate to CSS must parse as a selector; chains using index
+/// or exclusion syntax are handled by the lenient custom matcher and accepted.
+pub fn check(rule: &str) -> std::result::Result<(), RuleSyntaxError> {
+    let (selector, _) = split_rule(rule);
+    match legado_to_css(&selector) {
+        Some(css) if !css.is_empty() => Selector::parse(&css)
+            .map(|_| ())
+            .map_err(|e| RuleSyntaxError::new(format!("Invalid selector '{}': {}", selector, e))),
+        _ => Ok(()),
+    }
+}
+
+/// Convert a Legado selector chain to CSS, or None when it uses index or
+/// exclusion syntax that only the custom parser understands
+fn legado_to_css(selector: &str) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for part in selector.split('@') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        if part.contains('!') || split_index_suffix(part).1.is_some() {
+            return None;
+        }
+        // Index syntax (.0, .-1, .1:3) is array indexing, not CSS classes
+        if part
+            .split('.')
+            .skip(1)
+            .any(|piece| piece.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == ':'))
+        {
+            return None;
+        }
+
+        if let Some(id) = part.strip_prefix("id.") {
+            parts.push(format!("#{}", id));
+        } else if let Some(class) = part.strip_prefix("class.") {
+            parts.push(format!(".{}", class));
+        } else if let Some(tag) = part.strip_prefix("tag.") {
+            parts.push(tag.to_string());
+        } else {
+            parts.push(part.to_string());
+        }
+    }
+    Some(parts.join(" "))
+}
+
 fn split_rule(rule: &str) -> (String, String) {
     if let Some(pos) = rule.rfind('@') {
         let mut selector = rule[..pos].trim();
@@ -330,8 +345,7 @@ fn extract_text_recursive(element: ElementRef, buffer: &mut String) {
 enum SelectorModifier {
     Class(String),
     Id(String),
-    Index(isize),
-    Exclude(isize),
+    Pick(IndexSpec),
     Ambiguous(String),  // Could be Class or Index (e.g. "-1", "0")
     TextFilter(String), // For "text" type
 }
@@ -344,14 +358,17 @@ struct SelectorSegment {
 
 fn parse_jsoup_rule(rule: &str) -> Result<(Vec<SelectorSegment>, String)> {
     let (selector_raw, attr) = split_rule(rule);
-    // Handle Legado syntax where @ separates hierarchal steps (same as space)
-    let selector = selector_raw.replace("@", " ");
+    Ok((parse_selector_chain(&selector_raw), attr))
+}
 
-    let segments: Vec<SelectorSegment> = selector
+/// Parse a selector chain without an attribute part
+fn parse_selector_chain(selector: &str) -> Vec<SelectorSegment> {
+    // Handle Legado syntax where @ separates hierarchal steps (same as space)
+    selector
+        .replace("@", " ")
         .split_whitespace()
         .filter_map(|s| parse_segment(s))
-        .collect();
-    Ok((segments, attr))
+        .collect()
 }
 
 fn parse_segment(part: &str) -> Option<SelectorSegment> {
@@ -359,9 +376,12 @@ fn parse_segment(part: &str) -> Option<SelectorSegment> {
         return None;
     }
 
-    // 1. Handle Exclusions (split by !)
-    let bang_parts: Vec<&str> = part.split('!').collect();
-    let main_selector = bang_parts[0];
+    // 1. Handle bracket index (tag.li[1:3], li[!0]) and exclusions (split by !)
+    let (part, bracket_index) = split_index_suffix(part);
+    let (main_selector, exclusion) = match part.split_once('!') {
+        Some((main, excluded)) => (main, IndexSpec::parse(&format!("!{}", excluded))),
+        None => (part, None),
+    };
 
     // 2. Parse main selector parts (split by .)
     let (tag, mut modifiers) = if main_selector.is_empty() {
@@ -378,9 +398,6 @@ fn parse_segment(part: &str) -> Option<SelectorSegment> {
 
         let pieces: Vec<&str> = s.split('.').collect();
         let mut tag = pieces[0].to_string();
-        if tag == "tag" {
-            tag = "*".to_string();
-        }
 
         let mut mods = Vec::new();
 
@@ -396,13 +413,18 @@ fn parse_segment(part: &str) -> Option<SelectorSegment> {
             for piece in pieces.iter().skip(2) {
                 parse_modifier(piece, &mut mods);
             }
+        } else if tag == "tag" && pieces.len() > 1 {
+            tag = pieces[1].to_string();
+            for piece in pieces.iter().skip(2) {
+                parse_modifier(piece, &mut mods);
+            }
         } else if tag == "text" {
             tag = "*".to_string();
             if let Some(text) = pieces.get(1) {
                 mods.push(SelectorModifier::TextFilter(text.to_string()));
             }
         } else {
-            if tag.is_empty() {
+            if tag.is_empty() || tag == "tag" {
                 tag = "*".to_string();
             }
             for piece in pieces.iter().skip(1) {
@@ -412,23 +434,134 @@ fn parse_segment(part: &str) -> Option<SelectorSegment> {
         (tag, mods)
     };
 
-    // 3. Add Exclusions (Handle ranges !0:1 by sorting descending)
-    let mut exclude_indices = Vec::new();
-    for exclusion in bang_parts.iter().skip(1) {
-        for ex_part in exclusion.split(':') {
-            if let Ok(idx) = ex_part.parse::<isize>() {
-                exclude_indices.push(idx);
+    // 3. Index expressions apply after the tag/class filters
+    modifiers.extend(exclusion.into_iter().map(SelectorModifier::Pick));
+    modifiers.extend(bracket_index.map(SelectorModifier::Pick));
+
+    Some(SelectorSegment { tag, modifiers })
+}
+
+/// Split a trailing `[index expression]` off a segment; CSS attribute
+/// selectors such as `a[href]` are left alone
+fn split_index_suffix(part: &str) -> (&str, Option<IndexSpec>) {
+    if let Some(inner) = part.strip_suffix(']') {
+        if let Some(open) = inner.rfind('[') {
+            if let Some(spec) = IndexSpec::parse(&inner[open + 1..]) {
+                return (&part[..open], Some(spec));
             }
         }
     }
-    // Sort descending to safely remove from list by index (removing larger indices first avoids shifting issues)
-    exclude_indices.sort_by(|a, b| b.cmp(a));
+    (part, None)
+}
+
+/// Index expression picking elements from a segment's matches
+///
+/// Items are separated by `,`: a single index (negative counts from the end)
+/// or a `start:end[:step]` range with inclusive end, either side optional. A
+/// range whose start is after its end is walked backwards. A leading `!`
+/// keeps every element except the listed ones.
+#[derive(Debug, Clone, PartialEq)]
+struct IndexSpec {
+    exclude: bool,
+    items: Vec<IndexItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum IndexItem {
+    Single(isize),
+    Range {
+        start: Option<isize>,
+        end: Option<isize>,
+        step: usize,
+    },
+}
 
-    for idx in exclude_indices {
-        modifiers.push(SelectorModifier::Exclude(idx));
+impl IndexSpec {
+    fn parse(expr: &str) -> Option<Self> {
+        let expr = expr.trim();
+        let (exclude, body) = match expr.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, expr),
+        };
+        if body.trim().is_empty() {
+            return None;
+        }
+        let items = body
+            .split(',')
+            .map(|item| IndexItem::parse(item.trim()))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { exclude, items })
     }
 
-    Some(SelectorSegment { tag, modifiers })
+    /// Positions picked out of `len` elements, in pick order
+    fn resolve(&self, len: usize) -> Vec<usize> {
+        let picked: Vec<usize> = self.items.iter().flat_map(|item| item.resolve(len)).collect();
+        if self.exclude {
+            (0..len).filter(|i| !picked.contains(i)).collect()
+        } else {
+            picked
+        }
+    }
+}
+
+impl IndexItem {
+    fn parse(item: &str) -> Option<Self> {
+        if !item.contains(':') {
+            return item.parse().ok().map(IndexItem::Single);
+        }
+        let parts: Vec<&str> = item.split(':').map(str::trim).collect();
+        if parts.len() > 3 {
+            return None;
+        }
+        let bound = |s: &str| -> Option<Option<isize>> {
+            if s.is_empty() {
+                Some(None)
+            } else {
+                s.parse().ok().map(Some)
+            }
+        };
+        let step = match parts.get(2) {
+            Some(s) if !s.is_empty() => s.parse::<usize>().ok().filter(|&step| step > 0)?,
+            _ => 1,
+        };
+        Some(IndexItem::Range {
+            start: bound(parts[0])?,
+            end: bound(parts[1])?,
+            step,
+        })
+    }
+
+    fn resolve(&self, len: usize) -> Vec<usize> {
+        let len = len as isize;
+        let normalize = |i: isize| if i < 0 { len + i } else { i };
+        match *self {
+            IndexItem::Single(i) => {
+                let i = normalize(i);
+                if (0..len).contains(&i) {
+                    vec![i as usize]
+                } else {
+                    vec![]
+                }
+            }
+            IndexItem::Range { start, end, step } => {
+                let last = len - 1;
+                let start = normalize(start.unwrap_or(0));
+                let end = normalize(end.unwrap_or(last));
+                if start <= end {
+                    (start.max(0)..=end.min(last))
+                        .step_by(step)
+                        .map(|i| i as usize)
+                        .collect()
+                } else {
+                    (end.max(0)..=start.min(last))
+                        .rev()
+                        .step_by(step)
+                        .map(|i| i as usize)
+                        .collect()
+                }
+            }
+        }
+    }
 }
 
 fn apply_selectors<'a>(
@@ -469,21 +602,12 @@ fn apply_selectors<'a>(
                         SelectorModifier::TextFilter(text) => {
                             candidates.retain(|el| el.text().collect::<String>().contains(text));
                         }
-                        SelectorModifier::Index(idx) => {
-                            if let Some(el) = apply_index(&candidates, *idx) {
-                                candidates = vec![el];
-                            } else {
-                                candidates.clear();
-                            }
-                        }
-                        SelectorModifier::Exclude(idx) => {
-                            if let Some(el_to_remove) = apply_index(&candidates, *idx) {
-                                if let Some(pos) =
-                                    candidates.iter().position(|x| *x == el_to_remove)
-                                {
-                                    candidates.remove(pos);
-                                }
-                            }
+                        SelectorModifier::Pick(spec) => {
+                            candidates = spec
+                                .resolve(candidates.len())
+                                .into_iter()
+                                .map(|i| candidates[i])
+                                .collect();
                         }
                         SelectorModifier::Ambiguous(val) => {
                             // Try Class
@@ -541,6 +665,9 @@ fn parse_modifier(piece: &str, modifiers: &mut Vec<SelectorModifier>) {
     // Use Ambiguous to let runtime decide based on whether class matches exist
     if piece.parse::<isize>().is_ok() {
         modifiers.push(SelectorModifier::Ambiguous(piece.to_string()));
+    } else if let Some(spec) = IndexSpec::parse(piece) {
+        // Ranges and lists (.1:3, .0,2) are never class names
+        modifiers.push(SelectorModifier::Pick(spec));
     } else {
         modifiers.push(SelectorModifier::Class(piece.to_string()));
     }
@@ -644,4 +771,75 @@ mod tests {
         let href = parser.get_string(&elements[0], "href").unwrap();
         assert_eq!(href, "/ch1");
     }
+
+    /// Ten list items with text `i0`..`i9`, the first two marked as "latest"
+    fn ten_items() -> String {
+        let items: String = (0..10)
+            .map(|i| {
+                let class = if i < 2 { "latest item" } else { "item" };
+                format!(r#"<li class="{}"><a href="/c/{}">i{}</a></li>"#, class, i, i)
+            })
+            .collect();
+        format!(r#"<div id="list"><ul>{}</ul></div>"#, items)
+    }
+
+    fn picked(texts: Vec<String>) -> Vec<usize> {
+        texts
+            .iter()
+            .map(|t| t.trim_start_matches('i').parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_jsoup_index_expressions() {
+        let html = ten_items();
+        let parser = JsoupDefaultParser;
+        let cases: &[(&str, &[usize])] = &[
+            ("tag.li.0", &[0]),
+            ("tag.li.-1", &[9]),
+            ("tag.li.-3", &[7]),
+            ("tag.li.20", &[]),
+            ("tag.li.2:4", &[2, 3, 4]),
+            ("tag.li.:2", &[0, 1, 2]),
+            ("tag.li.7:", &[7, 8, 9]),
+            ("tag.li.-2:", &[8, 9]),
+            ("tag.li.0:9:3", &[0, 3, 6, 9]),
+            ("tag.li.::4", &[0, 4, 8]),
+            ("tag.li.2:0", &[2, 1, 0]),
+            ("tag.li.1,3,-1", &[1, 3, 9]),
+            ("tag.li!0", &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+            ("tag.li!0:2", &[3, 4, 5, 6, 7, 8, 9]),
+            ("tag.li!-1", &[0, 1, 2, 3, 4, 5, 6, 7, 8]),
+            ("tag.li!0,9", &[1, 2, 3, 4, 5, 6, 7, 8]),
+            ("tag.li!:7", &[8, 9]),
+            ("tag.li[1:2]", &[1, 2]),
+            ("tag.li[!2:]", &[0, 1]),
+            ("li[-1]", &[9]),
+            ("class.item.-2", &[8]),
+            ("id.list@li!0:1", &[2, 3, 4, 5, 6, 7, 8, 9]),
+        ];
+
+        for (selector, expected) in cases {
+            let texts = parser.get_list(&html, &format!("{}@text", selector)).unwrap();
+            assert_eq!(&picked(texts), expected, "get_list {}", selector);
+
+            let elements = parser.get_elements(&html, selector).unwrap();
+            let texts = elements
+                .iter()
+                .map(|e| parser.get_string(e, "text").unwrap())
+                .collect();
+            assert_eq!(&picked(texts), expected, "get_elements {}", selector);
+        }
+    }
+
+    #[test]
+    fn test_jsoup_index_in_get_string() {
+        let html = ten_items();
+        let parser = JsoupDefaultParser;
+        assert_eq!(parser.get_string(&html, "tag.li.-1@text").unwrap(), "i9");
+        assert_eq!(parser.get_string(&html, "tag.li!0:7@text").unwrap(), "i8\ni9");
+        // The element picker also selects which element an attribute comes from
+        assert_eq!(parser.get_string(&html, "tag.li.-1@a@href").unwrap(), "/c/9");
+        assert_eq!(parser.get_string(&html, "class.latest!0@a@href").unwrap(), "/c/1");
+    }
 }
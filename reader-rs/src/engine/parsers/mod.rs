@@ -2,6 +2,7 @@
 //! Supports CSS, JSONPath, XPath, Regex, and JSOUP Default syntax
 
 pub mod css;
+pub mod json_embed;
 pub mod jsonpath;
 pub mod jsoup;
 pub mod parser_factory;
@@ -25,8 +26,42 @@ pub trait Parser: Send + Sync {
     fn get_elements(&self, content: &str, rule: &str) -> Result<Vec<String>>;
 }
 
+use once_cell::sync::Lazy;
+use ::regex::Regex;
+use scraper::Selector;
 use serde::{Deserialize, Serialize};
 
+/// A syntax error reported by a parser's `check` entry point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSyntaxError {
+    /// Byte offset into the checked rule, when the parser can locate the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl RuleSyntaxError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            offset: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn at(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset: Some(offset),
+            message: message.into(),
+        }
+    }
+
+    /// Move the offset by `base`, for errors found in a part of a larger rule
+    pub fn shifted(mut self, base: usize) -> Self {
+        self.offset = self.offset.map(|offset| offset + base);
+        self
+    }
+}
+
 /// Rule type detection
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RuleType {
@@ -46,6 +81,20 @@ pub enum RuleType {
 
 impl RuleType {
     /// Detect rule type from rule string
+    ///
+    /// Explicit prefixes (`@css:`, `@xpath:`, `@json:`, `@js:`, `##`, ...) always
+    /// win. An untyped rule is JSONPath for JSON content, raw CSS when it uses
+    /// selector syntax (combinators, `[attr]`, pseudo-classes, leading `.`/`#`)
+    /// on HTML content, and JSOUP Default otherwise - including every rule that
+    /// uses the `class.`/`tag.`/`id.`/`text.` prefixes, index syntax or `@` chains.
+    ///
+    /// `:pattern` is Legado's regex shorthand; on HTML content a rule that is a
+    /// valid pseudo-class selector (`:first-child a`) is CSS instead. JSON wins
+    /// over HTML, so a chain step reading JSON whose strings hold markup stays
+    /// on JSONPath/regex.
+    ///
+    /// `@jsonEmbed:` extracts a JSON island from HTML and is handled by the
+    /// JSONPath parser.
     pub fn detect(rule: &str, content: &str) -> Self {
         let rule_trimmed = rule.trim();
         let rule_lower = rule_trimmed.to_lowercase();
@@ -65,21 +114,26 @@ impl RuleType {
             RuleType::XPath
         } else if rule_lower.starts_with("@json:")
             || rule_lower.starts_with("json:")
+            || rule_lower.starts_with("@jsonembed:")
             || rule_trimmed.starts_with("$.")
             || rule_trimmed.starts_with("$[")
         {
             RuleType::JsonPath
-        } else if rule_trimmed.starts_with("##") || rule_trimmed.starts_with(":") {
+        } else if rule_trimmed.starts_with("##") {
             RuleType::Regex
-        } else {
-            // Intelligent fallback: if content looks like JSON, use JsonPath
-            let content_trimmed = content.trim();
-            if content_trimmed.starts_with('{') || content_trimmed.starts_with('[') {
-                RuleType::JsonPath
+        } else if rule_trimmed.len() > 1 && rule_trimmed.starts_with(':') {
+            if !is_json(content) && is_html(content) && Selector::parse(split_attr(rule_trimmed).0).is_ok() {
+                RuleType::Css
             } else {
-                // Default to JSOUP Default syntax (class.tag.0@text)
-                RuleType::JsoupDefault
+                RuleType::Regex
             }
+        } else if is_json(content) {
+            RuleType::JsonPath
+        } else if is_html(content) && is_raw_css(rule_trimmed) {
+            RuleType::Css
+        } else {
+            // JSOUP Default syntax (class.name.0@text) is Legado's default
+            RuleType::JsoupDefault
         };
 
         tracing::debug!(
@@ -92,3 +146,109 @@ impl RuleType {
         rule_type
     }
 }
+
+/// Documented JSOUP Default segment prefixes
+const JSOUP_PREFIXES: [&str; 5] = ["class.", "tag.", "id.", "text.", "children"];
+
+static HTML_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[a-zA-Z!/][^>]*>").unwrap());
+
+/// JSOUP index suffix such as `[0]`, `[-1]`, `[1:3]` or `[!0,2]`
+static INDEX_BRACKET: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[[\s\d!:,-]+\]").unwrap());
+
+fn is_json(content: &str) -> bool {
+    // API responses sometimes start with a byte order mark; script islands
+    // cut out by a regex keep their trailing `;`
+    let content = json_embed::trim_json(content);
+    (content.starts_with('{') || content.starts_with('['))
+        && (content.ends_with('}') || content.ends_with(']'))
+}
+
+fn is_html(content: &str) -> bool {
+    HTML_TAG.is_match(content)
+}
+
+/// Split `selector@attr` at the last `@`
+fn split_attr(rule: &str) -> (&str, Option<&str>) {
+    match rule.rsplit_once('@') {
+        Some((selector, attr)) => (selector.trim_end_matches('@').trim(), Some(attr)),
+        None => (rule, None),
+    }
+}
+
+/// Whether an untyped rule is a plain CSS selector, optionally followed by `@attr`
+fn is_raw_css(rule: &str) -> bool {
+    let (selector, _) = split_attr(rule);
+    // `a@b@text` chains and JSOUP prefixes / index syntax belong to JSOUP Default
+    if selector.contains('@')
+        || selector.contains('!')
+        || INDEX_BRACKET.is_match(selector)
+        || selector
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .any(|part| JSOUP_PREFIXES.iter().any(|prefix| part.starts_with(prefix)))
+        || selector
+            .split('.')
+            .skip(1)
+            .any(|piece| piece.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == ':'))
+    {
+        return false;
+    }
+
+    let css_syntax = selector.starts_with('.')
+        || selector.starts_with('#')
+        || selector.contains(|c: char| c.is_whitespace() || matches!(c, '>' | '+' | '~' | '[' | ':'));
+    css_syntax && Selector::parse(selector).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &str = "<div class=\"chapter-content\"><p>正文</p></div>";
+    const JSON: &str = r#"{"data": {"list": []}}"#;
+    const TEXT: &str = "第一章 开始\n作者：某人";
+
+    #[test]
+    fn test_detect_rule_types() {
+        let cases = [
+            // Explicit prefixes
+            ("@css:div.book", HTML, RuleType::Css),
+            ("css.title@text", HTML, RuleType::Css),
+            ("@xpath://div[@class='a']", HTML, RuleType::XPath),
+            ("//a/@href", HTML, RuleType::XPath),
+            ("$.data.list[*]", JSON, RuleType::JsonPath),
+            ("@json:$.name", HTML, RuleType::JsonPath),
+            ("@js:result.trim()", HTML, RuleType::JavaScript),
+            ("<js>result</js>", TEXT, RuleType::JavaScript),
+            ("##\\d+##", TEXT, RuleType::Regex),
+            // Raw CSS on HTML content
+            (".chapter-content p", HTML, RuleType::Css),
+            ("div.list > a@href", HTML, RuleType::Css),
+            ("a[href*=book]@href", HTML, RuleType::Css),
+            ("ul li:nth-child(2)@text", HTML, RuleType::Css),
+            ("#content", HTML, RuleType::Css),
+            (":first-child a@text", HTML, RuleType::Css),
+            // JSOUP Default
+            ("class.chapter-content@tag.p@text", HTML, RuleType::JsoupDefault),
+            ("id.list@tag.dd@tag.a@href", HTML, RuleType::JsoupDefault),
+            ("tag.li.0@text", HTML, RuleType::JsoupDefault),
+            ("class.item li.-1@text", HTML, RuleType::JsoupDefault),
+            ("dd[1:3]@text", HTML, RuleType::JsoupDefault),
+            ("text.下一页@href", HTML, RuleType::JsoupDefault),
+            ("h1@text", HTML, RuleType::JsoupDefault),
+            (".chapter-content p", TEXT, RuleType::JsoupDefault),
+            // Regex shorthand and JSON fallback
+            (":<a href=\"(.*?)\">(.*?)</a>", HTML, RuleType::Regex),
+            (":第(\\d+)章", TEXT, RuleType::Regex),
+            ("name", JSON, RuleType::JsonPath),
+            ("data.list", "\u{feff}{\"data\": {}}", RuleType::JsonPath),
+            // JSON islands and chain steps over JSON that holds markup
+            ("@jsonEmbed:window.__DATA__", HTML, RuleType::JsonPath),
+            ("list[*].name", "\n {list: [{name: 'a'}]};\n", RuleType::JsonPath),
+            (":first-child", r#"{"intro": "<p>简介</p>"}"#, RuleType::Regex),
+            ("h1@text", "[公告] <h1>标题</h1>", RuleType::JsoupDefault),
+        ];
+        for (rule, content, expected) in cases {
+            assert_eq!(RuleType::detect(rule, content), expected, "{}", rule);
+        }
+    }
+}
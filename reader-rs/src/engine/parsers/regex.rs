@@ -1,6 +1,6 @@
 //! Regex Parser using regex crate
 
-use super::Parser;
+use super::{Parser, RuleSyntaxError};
 use anyhow::{anyhow, Result};
 use regex::Regex;
 
@@ -8,13 +8,21 @@ pub struct RegexParser;
 
 impl Parser for RegexParser {
     fn get_string(&self, content: &str, rule: &str) -> Result<String> {
-        let (pattern, replacement) = parse_regex_rule(rule)?;
-        let re = Regex::new(&pattern)?;
+        let rule = parse_regex_rule(rule)?;
+        let re = Regex::new(&rule.pattern)?;
 
         if let Some(captures) = re.captures(content) {
             // If there's a replacement, apply it
-            if let Some(ref repl) = replacement {
-                Ok(re.replace_all(content, repl.as_str()).to_string())
+            if let Some(ref repl) = rule.replacement {
+                let repl = java_replacement(repl, &re);
+                if rule.replace_first {
+                    // Legado `###`: keep only the first match, rewritten
+                    let mut replaced = String::new();
+                    captures.expand(&repl, &mut replaced);
+                    Ok(replaced)
+                } else {
+                    Ok(re.replace_all(content, repl.as_str()).to_string())
+                }
             } else {
                 // Return first capture group or entire match
                 if captures.len() > 1 {
@@ -31,14 +39,16 @@ impl Parser for RegexParser {
                         .to_string())
                 }
             }
+        } else if rule.replace_first {
+            Ok(String::new())
         } else {
-            Err(anyhow!("No match found for regex: {}", pattern))
+            Err(anyhow!("No match found for regex: {}", rule.pattern))
         }
     }
 
     fn get_list(&self, content: &str, rule: &str) -> Result<Vec<String>> {
-        let (pattern, _) = parse_regex_rule(rule)?;
-        let re = Regex::new(&pattern)?;
+        let rule = parse_regex_rule(rule)?;
+        let re = Regex::new(&rule.pattern)?;
 
         let results: Vec<String> = re
             .captures_iter(content)
@@ -59,30 +69,203 @@ impl Parser for RegexParser {
     }
 }
 
-/// Parse regex rule: ##pattern## or ##pattern##replacement
-fn parse_regex_rule(rule: &str) -> Result<(String, Option<String>)> {
-    let has_prefix = rule.starts_with("##");
-    let rule_content = rule.trim_start_matches("##");
+/// A parsed `##pattern##replacement###` rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexRule {
+    pub pattern: String,
+    /// None extracts the match; an empty replacement deletes matches
+    pub replacement: Option<String>,
+    /// Trailing `###`: only the first match is kept and rewritten
+    pub replace_first: bool,
+}
+
+/// Parse regex rule: ##pattern##, ##pattern##replacement or ##pattern##replacement###
+///
+/// `:pattern` is Legado's all-in-one shorthand for `##pattern`.
+/// `##` inside a character class or after a backslash is part of the pattern.
+pub fn parse_regex_rule(rule: &str) -> Result<RegexRule> {
+    let stripped = rule.strip_prefix("##").or_else(|| rule.strip_prefix(':'));
+    let has_prefix = stripped.is_some();
+    let rule_content = stripped.unwrap_or(rule);
+    let (rule_content, replace_first) = match rule_content.strip_suffix("###") {
+        Some(rest) => (rest, true),
+        None => (rule_content, false),
+    };
 
     // Split by ## to get pattern and optional replacement
-    let mut parts: Vec<&str> = rule_content.split("##").collect();
+    let mut parts = split_pattern_parts(rule_content);
 
     // Handle trailing ## resulting in empty last part
     // Only strip it if we had a prefix (implying ##pattern## extraction syntax)
     // If no prefix, "pattern##" implies replacement with empty string
-    if has_prefix {
-        if let Some(&last) = parts.last() {
+    if has_prefix && !replace_first {
+        if let Some(last) = parts.last() {
             if last.is_empty() && parts.len() > 1 {
                 parts.pop();
             }
         }
     }
 
-    match parts.len() {
-        1 => Ok((parts[0].to_string(), None)),
-        2 => Ok((parts[0].to_string(), Some(parts[1].to_string()))),
-        _ => Err(anyhow!("Invalid regex rule format: {:?}", parts)),
+    let mut parts = parts.into_iter();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(pattern), replacement, None) => Ok(RegexRule {
+            pattern,
+            replacement: replacement.or_else(|| replace_first.then(String::new)),
+            replace_first,
+        }),
+        _ => Err(anyhow!("Invalid regex rule format: {}", rule)),
+    }
+}
+
+/// Check a regex rule without running it
+///
+/// Offsets point into `rule`. Patterns are compiled by the `regex` crate, so
+/// Java-only syntax such as look-around is reported here as it would fail at
+/// run time.
+pub fn check(rule: &str) -> std::result::Result<(), RuleSyntaxError> {
+    let parsed = parse_regex_rule(rule).map_err(|e| RuleSyntaxError::new(e.to_string()))?;
+    // The pattern directly follows the `##` / `:` prefix
+    let base = rule
+        .strip_prefix("##")
+        .or_else(|| rule.strip_prefix(':'))
+        .map_or(0, |rest| rule.len() - rest.len());
+    check_pattern(&parsed.pattern).map_err(|e| e.shifted(base))
+}
+
+/// Check a bare regex pattern; offsets point into `pattern`
+pub fn check_pattern(pattern: &str) -> std::result::Result<(), RuleSyntaxError> {
+    if let Err(e) = regex_syntax::Parser::new().parse(pattern) {
+        let (offset, message) = match &e {
+            regex_syntax::Error::Parse(e) => (e.span().start.offset, e.kind().to_string()),
+            regex_syntax::Error::Translate(e) => (e.span().start.offset, e.kind().to_string()),
+            _ => (0, e.to_string()),
+        };
+        return Err(RuleSyntaxError::at(offset, message));
+    }
+    // Size limits are only enforced when compiling
+    Regex::new(pattern).map(|_| ()).map_err(|e| RuleSyntaxError::new(e.to_string()))
+}
+
+/// Split on `##` outside character classes and escapes
+fn split_pattern_parts(rule: &str) -> Vec<String> {
+    let chars: Vec<char> = rule.chars().collect();
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_class = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(&next) = chars.get(i + 1) {
+                    current.push(next);
+                }
+                i += 2;
+                continue;
+            }
+            '[' if !in_class => {
+                in_class = true;
+                current.push(c);
+                // A `]` right after `[` or `[^` is a literal member of the class
+                if chars.get(i + 1) == Some(&'^') {
+                    current.push('^');
+                    i += 1;
+                }
+                if chars.get(i + 1) == Some(&']') {
+                    current.push(']');
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            ']' if in_class => in_class = false,
+            '#' if !in_class && chars.get(i + 1) == Some(&'#') => {
+                parts.push(std::mem::take(&mut current));
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+        i += 1;
+    }
+    parts.push(current);
+    parts
+}
+
+/// Translate a Java-style replacement (`$1`, `$0`, `$name`, `${name}`, `\$`)
+/// into the `regex` crate's syntax
+///
+/// Like Java, `$12` is group 12 only when the pattern has that many groups,
+/// otherwise group 1 followed by a literal `2`. A `$` not naming a group is literal.
+fn java_replacement(template: &str, re: &Regex) -> String {
+    let chars: Vec<char> = template.chars().collect();
+    let group_count = re.captures_len();
+    let mut out = String::with_capacity(template.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                if next == '$' {
+                    out.push_str("$$");
+                } else {
+                    out.push(next);
+                }
+                i += 2;
+            }
+            '$' => {
+                let rest = &chars[i + 1..];
+                if rest.first().is_some_and(|c| c.is_ascii_digit()) {
+                    let mut group = rest[0].to_digit(10).unwrap_or(0) as usize;
+                    let mut len = 1;
+                    while let Some(digit) = rest.get(len).and_then(|c| c.to_digit(10)) {
+                        let next = group * 10 + digit as usize;
+                        if next >= group_count {
+                            break;
+                        }
+                        group = next;
+                        len += 1;
+                    }
+                    out.push_str(&format!("${{{}}}", group));
+                    i += 1 + len;
+                } else if rest.first() == Some(&'{') {
+                    let end = rest.iter().position(|&c| c == '}');
+                    match end {
+                        Some(end) => {
+                            let name: String = rest[1..end].iter().collect();
+                            out.push_str(&format!("${{{}}}", name));
+                            i += 2 + end;
+                        }
+                        None => {
+                            out.push_str("$$");
+                            i += 1;
+                        }
+                    }
+                } else {
+                    let name: String = rest
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .collect();
+                    if !name.is_empty() && re.capture_names().flatten().any(|n| n == name) {
+                        out.push_str(&format!("${{{}}}", name));
+                        i += 1 + name.chars().count();
+                    } else {
+                        out.push_str("$$");
+                        i += 1;
+                    }
+                }
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
     }
+    out
 }
 
 #[cfg(test)]
@@ -106,5 +289,45 @@ mod tests {
 
         let result = parser.get_list(content, "##(item\\d)##").unwrap();
         assert_eq!(result, vec!["item1", "item2", "item3"]);
+
+        let result = parser.get_list(content, ":item(\\d)").unwrap();
+        assert_eq!(result, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_regex_replacement_forms() {
+        let parser = RegexParser;
+        let content = "第12章 第13章";
+
+        // Named groups, whole match and Java-style numeric groups
+        assert_eq!(
+            parser.get_string(content, r"##(?<num>\d+)##chapter $num").unwrap(),
+            "第chapter 12章 第chapter 13章"
+        );
+        assert_eq!(parser.get_string(content, r"\d+##[$0]").unwrap(), "第[12]章 第[13]章");
+        assert_eq!(parser.get_string(content, r"(\d)(\d)##$21").unwrap(), "第21章 第31章");
+        assert_eq!(parser.get_string(content, r"\d+##\$${0}").unwrap(), "第$12章 第$13章");
+
+        // ### keeps only the first match
+        assert_eq!(
+            parser.get_string(content, r"##(?<num>\d+)##chapter $num###").unwrap(),
+            "chapter 12"
+        );
+        assert_eq!(parser.get_string("无", r"##\d+##x###").unwrap(), "");
+
+        // Empty replacement deletes
+        assert_eq!(parser.get_string(content, r"第|章##").unwrap(), "12 13");
+    }
+
+    #[test]
+    fn test_hash_inside_character_class() {
+        let rule = parse_regex_rule(r"##[##＃]+##-").unwrap();
+        assert_eq!(rule.pattern, "[##＃]+");
+        assert_eq!(rule.replacement.as_deref(), Some("-"));
+        assert_eq!(RegexParser.get_string("a##b＃c", r"##[##＃]+##-").unwrap(), "a-b-c");
+
+        let rule = parse_regex_rule(r"[\]##]\###x").unwrap();
+        assert_eq!(rule.pattern, r"[\]##]\#");
+        assert_eq!(rule.replacement.as_deref(), Some("x"));
     }
 }
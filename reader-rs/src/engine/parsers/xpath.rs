@@ -1,204 +1,472 @@
 //! XPath Parser using sxd-xpath crate
 //!
 //! Parses rules like: @xpath://div[@class='title']/text()
+//!
+//! HTML is parsed leniently with html5ever (via scraper) and converted into an
+//! sxd document, so unclosed tags, `<br>` and entities work. Evaluation is
+//! XPath 1.0: axes, predicates, `position()`/`last()`, unions with `|` and the
+//! core functions (`contains`, `starts-with`, `substring-before/after`,
+//! `normalize-space`, `count`...). `string-join` and `ends-with` are added on
+//! top. Other XPath 2.0+ functions (`matches`, `replace`, `lower-case`,
+//! `tokenize`...) and namespace prefixes are not supported and return an
+//! error instead of silently matching nothing.
+//!
+//! Results:
+//! - attributes yield their value, `text()` yields the concatenated text nodes
+//!   of each element, other elements yield their text content
+//! - `get_string` joins multiple matches with a newline
+//! - `get_elements` returns outer HTML fragments; a fragment with a single
+//!   top-level element is evaluated with that element as context node, so
+//!   relative rules like `a/@href` work on list items
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node as HtmlNode};
+use sxd_document::dom::{self, ChildOfElement, ChildOfRoot, Document};
+use sxd_document::{parser, Package};
+use sxd_xpath::context::Evaluation;
+use sxd_xpath::function::{self, Args, Function};
+use sxd_xpath::nodeset::Node;
+use sxd_xpath::{Context, Factory, Value};
+use super::{Parser, RuleSyntaxError};
 
-use anyhow::Result;
-use sxd_document::parser;
-use sxd_xpath::{Factory, Context, Value};
-use super::Parser;
+/// String literals, removed before looking for namespace prefixes
+static STRING_LITERAL: Lazy<Regex> = Lazy::new(|| Regex::new(r#"'[^']*'|"[^"]*""#).unwrap());
+
+/// A prefixed name test such as `svg:image` (but not an axis like `child::`)
+static PREFIXED_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[A-Za-z_][\w.-]*:[A-Za-z_*]").unwrap());
+
+/// HTML elements serialized without a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
 
 pub struct XPathParser;
 
 impl Parser for XPathParser {
     fn get_string(&self, content: &str, rule: &str) -> Result<String> {
-        let rule = rule.trim_start_matches("@xpath:");
-        
-        if rule.trim().is_empty() {
-            return Ok(String::new());
-        }
-        
-        // Try to parse as XML first, then as HTML
-        let content = normalize_html_to_xml(content);
-        
-        let package = match parser::parse(&content) {
-            Ok(p) => p,
-            Err(e) => {
-                tracing::warn!("XPath: Failed to parse document: {:?}", e);
-                return Ok(String::new());
-            }
-        };
-        
-        let document = package.as_document();
-        let factory = Factory::new();
-        
-        let xpath = match factory.build(rule) {
-            Ok(Some(xpath)) => xpath,
-            Ok(None) => return Ok(String::new()),
-            Err(e) => {
-                tracing::warn!("XPath: Failed to compile expression '{}': {:?}", rule, e);
-                return Ok(String::new());
-            }
-        };
-        
-        let context = Context::new();
-        
-        let value = match xpath.evaluate(&context, document.root()) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!("XPath: Failed to evaluate '{}': {:?}", rule, e);
-                return Ok(String::new());
-            }
-        };
-        
-        Ok(value_to_string(value))
+        Ok(self.get_list(content, rule)?.join("\n"))
     }
-    
+
     fn get_list(&self, content: &str, rule: &str) -> Result<Vec<String>> {
-        let rule = rule.trim_start_matches("@xpath:");
-        
-        if rule.trim().is_empty() {
-            return Ok(vec![]);
-        }
-        
-        let content = normalize_html_to_xml(content);
-        
-        let package = match parser::parse(&content) {
-            Ok(p) => p,
-            Err(e) => {
-                tracing::warn!("XPath: Failed to parse document: {:?}", e);
-                return Ok(vec![]);
-            }
-        };
-        
-        let document = package.as_document();
-        let factory = Factory::new();
-        
-        let xpath = match factory.build(rule) {
-            Ok(Some(xpath)) => xpath,
-            Ok(None) => return Ok(vec![]),
-            Err(e) => {
-                tracing::warn!("XPath: Failed to compile expression '{}': {:?}", rule, e);
-                return Ok(vec![]);
-            }
-        };
-        
-        let context = Context::new();
-        
-        let value = match xpath.evaluate(&context, document.root()) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!("XPath: Failed to evaluate '{}': {:?}", rule, e);
-                return Ok(vec![]);
-            }
-        };
-        
-        Ok(value_to_list(value))
+        evaluate(content, rule, |value| value_to_list(value, false))
     }
-    
+
     fn get_elements(&self, content: &str, rule: &str) -> Result<Vec<String>> {
-        // For XPath, get_elements returns the same as get_list
-        // since we're working with text representations
-        self.get_list(content, rule)
+        evaluate(content, rule, |value| value_to_list(value, true))
     }
 }
 
-/// Convert XPath value to string
-fn value_to_string(value: Value) -> String {
-    match value {
-        Value::String(s) => s.trim().to_string(),
-        Value::Number(n) => {
-            if n.is_nan() {
-                String::new()
-            } else {
-                n.to_string()
-            }
+/// Strip the `@xpath:` / `xpath:` prefix (case-insensitive)
+fn strip_prefix(rule: &str) -> &str {
+    let rule = rule.trim();
+    for prefix in ["@xpath:", "xpath:"] {
+        if rule.len() >= prefix.len() && rule[..prefix.len()].eq_ignore_ascii_case(prefix) {
+            return rule[prefix.len()..].trim();
         }
-        Value::Boolean(b) => b.to_string(),
-        Value::Nodeset(nodes) => {
-            nodes.iter()
-                .map(|n| n.string_value())
-                .collect::<Vec<_>>()
-                .join("")
-                .trim()
-                .to_string()
+    }
+    rule
+}
+
+/// Check that an XPath rule compiles, without evaluating it
+pub fn check(rule: &str) -> std::result::Result<(), RuleSyntaxError> {
+    let rule = strip_prefix(rule);
+    if PREFIXED_NAME.is_match(&STRING_LITERAL.replace_all(rule, "")) {
+        return Err(RuleSyntaxError::new("Namespace prefixes are not supported"));
+    }
+    Factory::new()
+        .build(rule)
+        .map(|_| ())
+        .map_err(|e| RuleSyntaxError::new(format!("Invalid XPath '{}': {}", rule, e)))
+}
+
+/// Parse `content`, evaluate `rule` and convert the value with `convert`
+fn evaluate<T: Default>(
+    content: &str,
+    rule: &str,
+    convert: impl for<'d> FnOnce(Value<'d>) -> T,
+) -> Result<T> {
+    let rule = strip_prefix(rule);
+    if rule.is_empty() {
+        return Ok(T::default());
+    }
+
+    // sxd-xpath panics on unbound prefixes; documents built from HTML have no
+    // namespaces anyway
+    if PREFIXED_NAME.is_match(&STRING_LITERAL.replace_all(rule, "")) {
+        return Err(anyhow!("Namespace prefixes are not supported in XPath '{}'", rule));
+    }
+
+    let xpath = match Factory::new().build(rule) {
+        Ok(Some(xpath)) => xpath,
+        Ok(None) => return Ok(T::default()),
+        Err(e) => return Err(anyhow!("Invalid XPath '{}': {}", rule, e)),
+    };
+
+    let (package, fragment) = build_document(content);
+    let document = package.as_document();
+    let root = document.root();
+    let context_node: Node = match (fragment, document_element(&document)) {
+        (true, Some(element)) => element.into(),
+        _ => root.into(),
+    };
+
+    let mut context = Context::new();
+    context.set_function("string-join", StringJoin);
+    context.set_function("ends-with", EndsWith);
+
+    let value = xpath
+        .evaluate(&context, context_node)
+        .map_err(|e| anyhow!("Failed to evaluate XPath '{}': {}", rule, e))?;
+    Ok(convert(value))
+}
+
+/// Build an sxd document from HTML or XML content
+///
+/// Returns the package and whether the content was a fragment (evaluated
+/// relative to its top-level element rather than the document root).
+fn build_document(content: &str) -> (Package, bool) {
+    let trimmed = content.trim_start();
+    if trimmed.starts_with("<?xml") {
+        if let Ok(package) = parser::parse(trimmed) {
+            return (package, false);
         }
     }
+
+    let lower: String = trimmed.chars().take(9).collect::<String>().to_ascii_lowercase();
+    let full_document = lower.starts_with("<!doctype") || lower.starts_with("<html");
+
+    let package = Package::new();
+    {
+        let document = package.as_document();
+        if full_document {
+            let html = Html::parse_document(content);
+            let element = convert_element(&document, html.root_element());
+            document.root().append_child(element);
+        } else {
+            // Fragments are wrapped in <html> by html5ever; unwrap a single
+            // top-level element, otherwise keep everything under <root>
+            let html = Html::parse_fragment(content);
+            let top = html.root_element();
+            let mut elements = top.children().filter_map(ElementRef::wrap);
+            let only_element = match (elements.next(), elements.next()) {
+                (Some(element), None) => top
+                    .children()
+                    .all(|c| c.value().as_text().is_none_or(|t| t.trim().is_empty()))
+                    .then_some(element),
+                _ => None,
+            };
+            let element = match only_element {
+                Some(element) => convert_element(&document, element),
+                None => {
+                    let wrapper = document.create_element("root");
+                    append_children(&document, wrapper, top);
+                    wrapper
+                }
+            };
+            document.root().append_child(element);
+        }
+    }
+    (package, !full_document)
+}
+
+fn document_element<'d>(document: &Document<'d>) -> Option<dom::Element<'d>> {
+    document.root().children().into_iter().find_map(|c| match c {
+        ChildOfRoot::Element(e) => Some(e),
+        _ => None,
+    })
 }
 
-/// Convert XPath value to list of strings
-fn value_to_list(value: Value) -> Vec<String> {
-    match value {
-        Value::String(s) => {
-            let s = s.trim();
-            if s.is_empty() {
-                vec![]
-            } else {
-                vec![s.to_string()]
+fn convert_element<'d>(document: &Document<'d>, source: ElementRef) -> dom::Element<'d> {
+    let element = document.create_element(source.value().name());
+    for (name, value) in source.value().attrs() {
+        element.set_attribute_value(name, value);
+    }
+    append_children(document, element, source);
+    element
+}
+
+fn append_children<'d>(document: &Document<'d>, parent: dom::Element<'d>, source: ElementRef) {
+    for child in source.children() {
+        match child.value() {
+            HtmlNode::Text(text) => parent.append_child(document.create_text(text)),
+            HtmlNode::Element(_) => {
+                if let Some(child_ref) = ElementRef::wrap(child) {
+                    parent.append_child(convert_element(document, child_ref));
+                }
             }
+            _ => {}
         }
-        Value::Number(n) => {
-            if n.is_nan() {
-                vec![]
-            } else {
-                vec![n.to_string()]
+    }
+}
+
+/// Convert an XPath value to a list of strings
+///
+/// With `outer_html`, element nodes are serialized as HTML instead of text.
+fn value_to_list(value: Value, outer_html: bool) -> Vec<String> {
+    let items = match value {
+        Value::String(s) => vec![s],
+        Value::Number(n) if n.is_nan() => vec![],
+        Value::Number(n) => vec![n.to_string()],
+        Value::Boolean(b) => vec![b.to_string()],
+        Value::Nodeset(nodes) => nodes_to_strings(nodes.document_order(), outer_html),
+    };
+    items
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn nodes_to_strings(nodes: Vec<Node>, outer_html: bool) -> Vec<String> {
+    let mut items: Vec<String> = Vec::new();
+    let mut last_text_parent = None;
+    for node in nodes {
+        match node {
+            // Text nodes of the same element form one match
+            Node::Text(text) => {
+                let parent = node.parent();
+                match items.last_mut() {
+                    Some(last) if last_text_parent.is_some() && last_text_parent == parent => {
+                        last.push_str(text.text())
+                    }
+                    _ => items.push(text.text().to_string()),
+                }
+                last_text_parent = parent;
+                continue;
+            }
+            Node::Attribute(attribute) => items.push(attribute.value().to_string()),
+            Node::Element(element) if outer_html => {
+                let mut html = String::new();
+                write_outer_html(element, &mut html);
+                items.push(html);
             }
+            other => items.push(other.string_value()),
         }
-        Value::Boolean(b) => vec![b.to_string()],
-        Value::Nodeset(nodes) => {
-            nodes.iter()
-                .map(|n| n.string_value().trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect()
+        last_text_parent = None;
+    }
+    items
+}
+
+fn write_outer_html(element: dom::Element, out: &mut String) {
+    let name = element.name().local_part();
+    out.push('<');
+    out.push_str(name);
+    for attribute in element.attributes() {
+        out.push(' ');
+        out.push_str(attribute.name().local_part());
+        out.push_str("=\"");
+        out.push_str(&html_escape::encode_double_quoted_attribute(attribute.value()));
+        out.push('"');
+    }
+    out.push('>');
+    if VOID_ELEMENTS.contains(&name) {
+        return;
+    }
+    for child in element.children() {
+        match child {
+            ChildOfElement::Element(e) => write_outer_html(e, out),
+            ChildOfElement::Text(t) => out.push_str(&html_escape::encode_text(t.text())),
+            _ => {}
         }
     }
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
 }
 
-/// Normalize HTML to XML-compatible format
-/// sxd-document requires well-formed XML, so we do basic cleanup
-fn normalize_html_to_xml(html: &str) -> String {
-    // Remove doctype
-    let html = regex::Regex::new(r"(?i)<!DOCTYPE[^>]*>")
-        .map(|re| re.replace_all(html, ""))
-        .unwrap_or_else(|_| html.into());
-    
-    // Wrap in root element if not already wrapped
-    let html = html.trim();
-    if !html.starts_with("<?xml") && !html.starts_with("<html") && !html.starts_with("<root") {
-        format!("<root>{}</root>", html)
-    } else {
-        html.to_string()
+/// XPath 2.0 `string-join(nodes, separator?)`
+struct StringJoin;
+
+impl Function for StringJoin {
+    fn evaluate<'c, 'd>(
+        &self,
+        _context: &Evaluation<'c, 'd>,
+        args: Vec<Value<'d>>,
+    ) -> Result<Value<'d>, function::Error> {
+        let mut args = Args(args);
+        args.at_least(1)?;
+        args.at_most(2)?;
+        let separator = if args.len() == 2 { args.pop_string()? } else { String::new() };
+        let nodes = args.pop_nodeset()?;
+        let parts: Vec<String> = nodes.document_order().iter().map(|n| n.string_value()).collect();
+        Ok(Value::String(parts.join(&separator)))
+    }
+}
+
+/// XPath 2.0 `ends-with(string, suffix)`
+struct EndsWith;
+
+impl Function for EndsWith {
+    fn evaluate<'c, 'd>(
+        &self,
+        _context: &Evaluation<'c, 'd>,
+        args: Vec<Value<'d>>,
+    ) -> Result<Value<'d>, function::Error> {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let suffix = args.pop_string()?;
+        let value = args.pop_string()?;
+        Ok(Value::Boolean(value.ends_with(&suffix)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    /// A typical TOC page: unclosed <meta>, <br> and entities are not valid XML
+    const TOC_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>斗破苍穹最新章节</title></head>
+<body>
+<div id="info">
+  <h1>斗破苍穹</h1>
+  <p>作者：天蚕土豆</p>
+  <p class="intro">第一行<br>第二行&nbsp;</p>
+</div>
+<div class="listmain box">
+  <dl>
+    <dt>最新章节</dt>
+    <dd><a href="/b/3.html">第三章 新</a></dd>
+    <dt>正文卷</dt>
+    <dd><a href="/b/1.html">第一章 始</a></dd>
+    <dd><a href="/b/2.html" class="vip">第二章 续</a></dd>
+    <dd><a href="/b/3.html">第三章 新</a></dd>
+  </dl>
+</div>
+<div class="footer"><a href="/about">关于</a></div>
+</body>
+</html>"#;
+
+    fn list(rule: &str) -> Vec<String> {
+        XPathParser.get_list(TOC_HTML, rule).unwrap()
+    }
+
     #[test]
     fn test_xpath_simple() {
         let xml = r#"<root><book><title>Test Book</title></book></root>"#;
         let parser = XPathParser;
-        
+
         let result = parser.get_string(xml, "//title/text()").unwrap();
         assert_eq!(result, "Test Book");
     }
-    
+
     #[test]
     fn test_xpath_attribute() {
         let xml = r#"<root><a href="/books/1">Link</a></root>"#;
         let parser = XPathParser;
-        
+
         let result = parser.get_string(xml, "//a/@href").unwrap();
         assert_eq!(result, "/books/1");
     }
-    
+
     #[test]
-    #[ignore] // sxd_xpath Nodeset iteration order is non-deterministic
     fn test_xpath_list() {
         let xml = r#"<root><item>A</item><item>B</item><item>C</item></root>"#;
         let parser = XPathParser;
-        
+
         let result = parser.get_list(xml, "//item/text()").unwrap();
-        assert_eq!(result.len(), 3);
+        assert_eq!(result, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_attribute_values_in_document_order() {
+        assert_eq!(
+            list("//dd/a/@href"),
+            vec!["/b/3.html", "/b/1.html", "/b/2.html", "/b/3.html"]
+        );
+        assert_eq!(
+            XPathParser.get_string(TOC_HTML, "@xpath://div[@class='footer']/a/@href").unwrap(),
+            "/about"
+        );
+    }
+
+    #[test]
+    fn test_text_nodes() {
+        assert_eq!(list("//h1/text()"), vec!["斗破苍穹"]);
+        // Text nodes around <br> belong to the same element and are concatenated
+        assert_eq!(list("//p[@class='intro']/text()"), vec!["第一行第二行"]);
+        // Multiple elements are joined with a newline
+        assert_eq!(
+            XPathParser.get_string(TOC_HTML, "//dd[position()<3]/a/text()").unwrap(),
+            "第三章 新\n第一章 始"
+        );
+    }
+
+    #[test]
+    fn test_contains_and_starts_with() {
+        assert_eq!(
+            list(r#"//div[contains(@class,"listmain")]//a/text()"#).len(),
+            4
+        );
+        assert_eq!(list("//a[starts-with(@href,'/b/2')]/text()"), vec!["第二章 续"]);
+        assert_eq!(list("//a[ends-with(@href,'1.html')]/text()"), vec!["第一章 始"]);
+        assert_eq!(list("//a[contains(@class,'vip')]/@href"), vec!["/b/2.html"]);
+    }
+
+    #[test]
+    fn test_positional_predicates() {
+        // Skip the "latest chapters" block before the main volume
+        assert_eq!(
+            list("//dd[position()>1]/a/text()"),
+            vec!["第一章 始", "第二章 续", "第三章 新"]
+        );
+        assert_eq!(list("//dd[last()]/a/@href"), vec!["/b/3.html"]);
+        assert_eq!(list("(//dd)[2]/a/text()"), vec!["第一章 始"]);
+        assert_eq!(list("count(//dd)"), vec!["4"]);
+    }
+
+    #[test]
+    fn test_union_and_string_functions() {
+        assert_eq!(
+            list("//h1/text() | //div[@id='info']/p[1]/text()"),
+            vec!["斗破苍穹", "作者：天蚕土豆"]
+        );
+        assert_eq!(
+            list("substring-after(//div[@id='info']/p[1], '：')"),
+            vec!["天蚕土豆"]
+        );
+        assert_eq!(
+            list("substring-before(//dd[2]/a, ' ')"),
+            vec!["第一章"]
+        );
+        assert_eq!(
+            list("string-join(//dt/text(), ',')"),
+            vec!["最新章节,正文卷"]
+        );
+    }
+
+    #[test]
+    fn test_elements_are_outer_html() {
+        let elements = XPathParser.get_elements(TOC_HTML, "//dd[position()>1]").unwrap();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0], r#"<dd><a href="/b/1.html">第一章 始</a></dd>"#);
+        assert!(elements[1].contains(r#"class="vip""#));
+
+        // A fragment is evaluated relative to its top-level element
+        assert_eq!(XPathParser.get_string(&elements[0], "a/@href").unwrap(), "/b/1.html");
+        assert_eq!(XPathParser.get_string(&elements[0], "//a/text()").unwrap(), "第一章 始");
+        assert_eq!(XPathParser.get_string(&elements[0], "/dd/a/text()").unwrap(), "第一章 始");
+
+        // Void elements are not closed, text is escaped
+        let intro = XPathParser.get_elements(TOC_HTML, "//p[@class='intro']").unwrap();
+        assert_eq!(intro, vec!["<p class=\"intro\">第一行<br>第二行\u{a0}</p>"]);
+    }
+
+    #[test]
+    fn test_unsupported_features_error() {
+        // XPath 2.0 functions other than string-join/ends-with
+        assert!(XPathParser.get_list(TOC_HTML, "//a[matches(@href,'\\d')]").is_err());
+        assert!(XPathParser.get_list(TOC_HTML, "lower-case(//h1)").is_err());
+        // Namespace prefixes have no bindings
+        assert!(XPathParser.get_list(TOC_HTML, "//svg:image/@href").is_err());
+        // Syntax errors
+        assert!(XPathParser.get_list(TOC_HTML, "//dd[").is_err());
+        // Empty rule is not an error
+        assert_eq!(XPathParser.get_string(TOC_HTML, "@xpath:").unwrap(), "");
     }
 }
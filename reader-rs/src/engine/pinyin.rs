@@ -0,0 +1,900 @@
+//! Pinyin of Chinese characters for search keys
+//!
+//! Covers the 6763 characters of GB2312. Level-1 characters (0xB0A1..=0xD7F9)
+//! are ordered by pinyin, so a table of the first code of each syllable reads
+//! them; the level-2 characters are listed per syllable. Characters outside
+//! GB2312 (including traditional ones) have no reading here, and polyphones
+//! use their most common reading.
+
+use encoding_rs::GBK;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// First GB2312 code of each syllable, in code order
+const LEVEL1_SYLLABLES: &[(u16, &str)] = &[
+    (0xB0A1, "a"),
+    (0xB0A3, "ai"),
+    (0xB0B0, "an"),
+    (0xB0B9, "ang"),
+    (0xB0BC, "ao"),
+    (0xB0C5, "ba"),
+    (0xB0D7, "bai"),
+    (0xB0DF, "ban"),
+    (0xB0EE, "bang"),
+    (0xB0FA, "bao"),
+    (0xB1AD, "bei"),
+    (0xB1BC, "ben"),
+    (0xB1C0, "beng"),
+    (0xB1C6, "bi"),
+    (0xB1DE, "bian"),
+    (0xB1EA, "biao"),
+    (0xB1EE, "bie"),
+    (0xB1F2, "bin"),
+    (0xB1F8, "bing"),
+    (0xB2A3, "bo"),
+    (0xB2B8, "bu"),
+    (0xB2C1, "ca"),
+    (0xB2C2, "cai"),
+    (0xB2CD, "can"),
+    (0xB2D4, "cang"),
+    (0xB2D9, "cao"),
+    (0xB2DE, "ce"),
+    (0xB2E3, "ceng"),
+    (0xB2E5, "cha"),
+    (0xB2F0, "chai"),
+    (0xB2F3, "chan"),
+    (0xB2FD, "chang"),
+    (0xB3AC, "chao"),
+    (0xB3B5, "che"),
+    (0xB3BB, "chen"),
+    (0xB3C5, "cheng"),
+    (0xB3D4, "chi"),
+    (0xB3E4, "chong"),
+    (0xB3E9, "chou"),
+    (0xB3F5, "chu"),
+    (0xB4A7, "chuai"),
+    (0xB4A8, "chuan"),
+    (0xB4AF, "chuang"),
+    (0xB4B5, "chui"),
+    (0xB4BA, "chun"),
+    (0xB4C1, "chuo"),
+    (0xB4C3, "ci"),
+    (0xB4CF, "cong"),
+    (0xB4D5, "cou"),
+    (0xB4D6, "cu"),
+    (0xB4DA, "cuan"),
+    (0xB4DD, "cui"),
+    (0xB4E5, "cun"),
+    (0xB4E8, "cuo"),
+    (0xB4EE, "da"),
+    (0xB4F4, "dai"),
+    (0xB5A2, "dan"),
+    (0xB5B1, "dang"),
+    (0xB5B6, "dao"),
+    (0xB5C2, "de"),
+    (0xB5C5, "deng"),
+    (0xB5CC, "di"),
+    (0xB5DF, "dian"),
+    (0xB5EF, "diao"),
+    (0xB5F8, "die"),
+    (0xB6A1, "ding"),
+    (0xB6AA, "diu"),
+    (0xB6AB, "dong"),
+    (0xB6B5, "dou"),
+    (0xB6BC, "du"),
+    (0xB6CB, "duan"),
+    (0xB6D1, "dui"),
+    (0xB6D5, "dun"),
+    (0xB6DE, "duo"),
+    (0xB6EA, "e"),
+    (0xB6F7, "en"),
+    (0xB6F8, "er"),
+    (0xB7A2, "fa"),
+    (0xB7AA, "fan"),
+    (0xB7BB, "fang"),
+    (0xB7C6, "fei"),
+    (0xB7D2, "fen"),
+    (0xB7E1, "feng"),
+    (0xB7F0, "fo"),
+    (0xB7F1, "fou"),
+    (0xB7F2, "fu"),
+    (0xB8C1, "ga"),
+    (0xB8C3, "gai"),
+    (0xB8C9, "gan"),
+    (0xB8D4, "gang"),
+    (0xB8DD, "gao"),
+    (0xB8E7, "ge"),
+    (0xB8F8, "gei"),
+    (0xB8F9, "gen"),
+    (0xB8FB, "geng"),
+    (0xB9A4, "gong"),
+    (0xB9B3, "gou"),
+    (0xB9BC, "gu"),
+    (0xB9CE, "gua"),
+    (0xB9D4, "guai"),
+    (0xB9D7, "guan"),
+    (0xB9E2, "guang"),
+    (0xB9E5, "gui"),
+    (0xB9F5, "gun"),
+    (0xB9F8, "guo"),
+    (0xB9FE, "ha"),
+    (0xBAA1, "hai"),
+    (0xBAA8, "han"),
+    (0xBABB, "hang"),
+    (0xBABE, "hao"),
+    (0xBAC7, "he"),
+    (0xBAD9, "hei"),
+    (0xBADB, "hen"),
+    (0xBADF, "heng"),
+    (0xBAE4, "hong"),
+    (0xBAED, "hou"),
+    (0xBAF4, "hu"),
+    (0xBBA8, "hua"),
+    (0xBBB1, "huai"),
+    (0xBBB6, "huan"),
+    (0xBBC4, "huang"),
+    (0xBBD2, "hui"),
+    (0xBBE7, "hun"),
+    (0xBBED, "huo"),
+    (0xBBF7, "ji"),
+    (0xBCCE, "jia"),
+    (0xBCDF, "jian"),
+    (0xBDA9, "jiang"),
+    (0xBDB6, "jiao"),
+    (0xBDD2, "jie"),
+    (0xBDED, "jin"),
+    (0xBEA3, "jing"),
+    (0xBEBC, "jiong"),
+    (0xBEBE, "jiu"),
+    (0xBECF, "ju"),
+    (0xBEE8, "juan"),
+    (0xBEEF, "jue"),
+    (0xBEF9, "jun"),
+    (0xBFA6, "ka"),
+    (0xBFAA, "kai"),
+    (0xBFAF, "kan"),
+    (0xBFB5, "kang"),
+    (0xBFBC, "kao"),
+    (0xBFC0, "ke"),
+    (0xBFCF, "ken"),
+    (0xBFD3, "keng"),
+    (0xBFD5, "kong"),
+    (0xBFD9, "kou"),
+    (0xBFDD, "ku"),
+    (0xBFE4, "kua"),
+    (0xBFE9, "kuai"),
+    (0xBFED, "kuan"),
+    (0xBFEF, "kuang"),
+    (0xBFF7, "kui"),
+    (0xC0A4, "kun"),
+    (0xC0A8, "kuo"),
+    (0xC0AC, "la"),
+    (0xC0B3, "lai"),
+    (0xC0B6, "lan"),
+    (0xC0C5, "lang"),
+    (0xC0CC, "lao"),
+    (0xC0D5, "le"),
+    (0xC0D7, "lei"),
+    (0xC0E2, "leng"),
+    (0xC0E5, "li"),
+    (0xC1A9, "lia"),
+    (0xC1AA, "lian"),
+    (0xC1B8, "liang"),
+    (0xC1C3, "liao"),
+    (0xC1D0, "lie"),
+    (0xC1D5, "lin"),
+    (0xC1E1, "ling"),
+    (0xC1EF, "liu"),
+    (0xC1FA, "long"),
+    (0xC2A5, "lou"),
+    (0xC2AB, "lu"),
+    (0xC2BF, "lv"),
+    (0xC2CD, "luan"),
+    (0xC2D3, "lue"),
+    (0xC2D5, "lun"),
+    (0xC2DC, "luo"),
+    (0xC2E8, "ma"),
+    (0xC2F1, "mai"),
+    (0xC2F7, "man"),
+    (0xC3A2, "mang"),
+    (0xC3A8, "mao"),
+    (0xC3B4, "me"),
+    (0xC3B5, "mei"),
+    (0xC3C5, "men"),
+    (0xC3C8, "meng"),
+    (0xC3D0, "mi"),
+    (0xC3DE, "mian"),
+    (0xC3E7, "miao"),
+    (0xC3EF, "mie"),
+    (0xC3F1, "min"),
+    (0xC3F7, "ming"),
+    (0xC3FD, "miu"),
+    (0xC3FE, "mo"),
+    (0xC4B1, "mou"),
+    (0xC4B4, "mu"),
+    (0xC4C3, "na"),
+    (0xC4CA, "nai"),
+    (0xC4CF, "nan"),
+    (0xC4D2, "nang"),
+    (0xC4D3, "nao"),
+    (0xC4D8, "ne"),
+    (0xC4D9, "nei"),
+    (0xC4DB, "nen"),
+    (0xC4DC, "neng"),
+    (0xC4DD, "ni"),
+    (0xC4E8, "nian"),
+    (0xC4EF, "niang"),
+    (0xC4F1, "niao"),
+    (0xC4F3, "nie"),
+    (0xC4FA, "nin"),
+    (0xC4FB, "ning"),
+    (0xC5A3, "niu"),
+    (0xC5A7, "nong"),
+    (0xC5AB, "nu"),
+    (0xC5AE, "nv"),
+    (0xC5AF, "nuan"),
+    (0xC5B0, "nue"),
+    (0xC5B2, "nuo"),
+    (0xC5B6, "o"),
+    (0xC5B7, "ou"),
+    (0xC5BE, "pa"),
+    (0xC5C4, "pai"),
+    (0xC5CA, "pan"),
+    (0xC5D2, "pang"),
+    (0xC5D7, "pao"),
+    (0xC5DE, "pei"),
+    (0xC5E7, "pen"),
+    (0xC5E9, "peng"),
+    (0xC5F7, "pi"),
+    (0xC6AA, "pian"),
+    (0xC6AE, "piao"),
+    (0xC6B2, "pie"),
+    (0xC6B4, "pin"),
+    (0xC6B9, "ping"),
+    (0xC6C2, "po"),
+    (0xC6CB, "pu"),
+    (0xC6DA, "qi"),
+    (0xC6FE, "qia"),
+    (0xC7A3, "qian"),
+    (0xC7B9, "qiang"),
+    (0xC7C1, "qiao"),
+    (0xC7D0, "qie"),
+    (0xC7D5, "qin"),
+    (0xC7E0, "qing"),
+    (0xC7ED, "qiong"),
+    (0xC7EF, "qiu"),
+    (0xC7F7, "qu"),
+    (0xC8A6, "quan"),
+    (0xC8B1, "que"),
+    (0xC8B9, "qun"),
+    (0xC8BB, "ran"),
+    (0xC8BF, "rang"),
+    (0xC8C4, "rao"),
+    (0xC8C7, "re"),
+    (0xC8C9, "ren"),
+    (0xC8D3, "reng"),
+    (0xC8D5, "ri"),
+    (0xC8D6, "rong"),
+    (0xC8E0, "rou"),
+    (0xC8E3, "ru"),
+    (0xC8ED, "ruan"),
+    (0xC8EF, "rui"),
+    (0xC8F2, "run"),
+    (0xC8F4, "ruo"),
+    (0xC8F6, "sa"),
+    (0xC8F9, "sai"),
+    (0xC8FD, "san"),
+    (0xC9A3, "sang"),
+    (0xC9A6, "sao"),
+    (0xC9AA, "se"),
+    (0xC9AD, "sen"),
+    (0xC9AE, "seng"),
+    (0xC9AF, "sha"),
+    (0xC9B8, "shai"),
+    (0xC9BA, "shan"),
+    (0xC9CA, "shang"),
+    (0xC9D2, "shao"),
+    (0xC9DD, "she"),
+    (0xC9E9, "shen"),
+    (0xC9F9, "sheng"),
+    (0xCAA6, "shi"),
+    (0xCAD5, "shou"),
+    (0xCADF, "shu"),
+    (0xCBA2, "shua"),
+    (0xCBA4, "shuai"),
+    (0xCBA8, "shuan"),
+    (0xCBAA, "shuang"),
+    (0xCBAD, "shui"),
+    (0xCBB1, "shun"),
+    (0xCBB5, "shuo"),
+    (0xCBB9, "si"),
+    (0xCBC9, "song"),
+    (0xCBD1, "sou"),
+    (0xCBD4, "su"),
+    (0xCBE1, "suan"),
+    (0xCBE4, "sui"),
+    (0xCBEF, "sun"),
+    (0xCBF2, "suo"),
+    (0xCBFA, "ta"),
+    (0xCCA5, "tai"),
+    (0xCCAE, "tan"),
+    (0xCCC0, "tang"),
+    (0xCCCD, "tao"),
+    (0xCCD8, "te"),
+    (0xCCD9, "teng"),
+    (0xCCDD, "ti"),
+    (0xCCEC, "tian"),
+    (0xCCF4, "tiao"),
+    (0xCCF9, "tie"),
+    (0xCCFC, "ting"),
+    (0xCDA8, "tong"),
+    (0xCDB5, "tou"),
+    (0xCDB9, "tu"),
+    (0xCDC4, "tuan"),
+    (0xCDC6, "tui"),
+    (0xCDCC, "tun"),
+    (0xCDCF, "tuo"),
+    (0xCDDA, "wa"),
+    (0xCDE1, "wai"),
+    (0xCDE3, "wan"),
+    (0xCDF4, "wang"),
+    (0xCDFE, "wei"),
+    (0xCEC1, "wen"),
+    (0xCECB, "weng"),
+    (0xCECE, "wo"),
+    (0xCED7, "wu"),
+    (0xCEF4, "xi"),
+    (0xCFB9, "xia"),
+    (0xCFC6, "xian"),
+    (0xCFE0, "xiang"),
+    (0xCFF4, "xiao"),
+    (0xD0A8, "xie"),
+    (0xD0BD, "xin"),
+    (0xD0C7, "xing"),
+    (0xD0D6, "xiong"),
+    (0xD0DD, "xiu"),
+    (0xD0E6, "xu"),
+    (0xD0F9, "xuan"),
+    (0xD1A5, "xue"),
+    (0xD1AB, "xun"),
+    (0xD1B9, "ya"),
+    (0xD1C9, "yan"),
+    (0xD1EA, "yang"),
+    (0xD1FB, "yao"),
+    (0xD2AC, "ye"),
+    (0xD2BB, "yi"),
+    (0xD2F0, "yin"),
+    (0xD3A2, "ying"),
+    (0xD3B4, "yo"),
+    (0xD3B5, "yong"),
+    (0xD3C4, "you"),
+    (0xD3D9, "yu"),
+    (0xD4A7, "yuan"),
+    (0xD4BB, "yue"),
+    (0xD4C5, "yun"),
+    (0xD4D1, "za"),
+    (0xD4D4, "zai"),
+    (0xD4DB, "zan"),
+    (0xD4DF, "zang"),
+    (0xD4E2, "zao"),
+    (0xD4F0, "ze"),
+    (0xD4F4, "zei"),
+    (0xD4F5, "zen"),
+    (0xD4F6, "zeng"),
+    (0xD4FA, "zha"),
+    (0xD5AA, "zhai"),
+    (0xD5B0, "zhan"),
+    (0xD5C1, "zhang"),
+    (0xD5D0, "zhao"),
+    (0xD5DA, "zhe"),
+    (0xD5E4, "zhen"),
+    (0xD5F4, "zheng"),
+    (0xD6A5, "zhi"),
+    (0xD6D0, "zhong"),
+    (0xD6DB, "zhou"),
+    (0xD6E9, "zhu"),
+    (0xD7A5, "zhua"),
+    (0xD7A7, "zhuai"),
+    (0xD7A8, "zhuan"),
+    (0xD7AE, "zhuang"),
+    (0xD7B5, "zhui"),
+    (0xD7BB, "zhun"),
+    (0xD7BD, "zhuo"),
+    (0xD7C8, "zi"),
+    (0xD7D7, "zong"),
+    (0xD7DE, "zou"),
+    (0xD7E2, "zu"),
+    (0xD7EA, "zuan"),
+    (0xD7EC, "zui"),
+    (0xD7F0, "zun"),
+    (0xD7F2, "zuo"),
+];
+
+/// Last GB2312 level-1 code
+const LEVEL1_END: u16 = 0xD7F9;
+
+/// GB2312 level-2 characters by syllable
+const LEVEL2_SYLLABLES: &[(&str, &str)] = &[
+    ("a", "嗄锕"),
+    ("ai", "捱嗳嗌嫒瑷暧砹锿霭"),
+    ("an", "谙埯揞犴庵桉铵鹌黯"),
+    ("ao", "坳拗嗷岙廒遨媪骜獒聱螯鏊鳌鏖"),
+    ("ba", "茇菝岜灞钯粑鲅魃"),
+    ("bai", "捭掰"),
+    ("ban", "阪坂钣瘢癍舨"),
+    ("bang", "蒡浜"),
+    ("bao", "勹葆孢煲鸨褓趵龅"),
+    ("bei", "孛陂邶蓓呗悖碚鹎褙鐾鞴"),
+    ("ben", "畚坌贲锛"),
+    ("beng", "嘣甏"),
+    ("bi", "匕俾荜荸萆薜吡哔狴庳愎滗濞弼妣婢嬖璧畀铋秕裨筚箅篦舭襞跸髀"),
+    ("bian", "匾弁苄忭汴缏煸砭碥窆褊蝙笾鳊"),
+    ("biao", "婊骠杓飑飙飚灬镖镳瘭裱鳔髟"),
+    ("bie", "蹩"),
+    ("bin", "傧豳缤玢槟殡膑镔髌鬓"),
+    ("bing", "禀冫邴摒"),
+    ("bo", "亳啵饽檗擘礴钹鹁簸跛踣"),
+    ("bu", "卟逋瓿晡钚钸醭"),
+    ("ca", "嚓礤"),
+    ("can", "骖璨粲黪"),
+    ("cang", "伧"),
+    ("cao", "艹嘈漕螬艚"),
+    ("ce", "恻"),
+    ("cen", "岑涔"),
+    ("ceng", "噌"),
+    ("cha", "猹馇汊姹杈槎檫锸镲衩"),
+    ("chai", "侪钗瘥虿"),
+    ("chan", "冁谄蒇廛忏潺澶孱羼婵骣觇禅镡蟾躔"),
+    ("chang", "伥鬯苌菖徜怅惝阊娼嫦昶氅鲳"),
+    ("chao", "怊晁焯耖"),
+    ("che", "坼屮砗"),
+    ("chen", "谌谶抻嗔宸琛榇碜龀"),
+    ("cheng", "丞埕枨柽晟塍瞠铖裎蛏酲"),
+    ("chi", "傺坻墀茌叱哧啻嗤彳饬媸敕眵鸱瘛褫蚩螭笞篪踟魑"),
+    ("chong", "茺忡憧铳舂艟"),
+    ("chou", "俦帱惆瘳雠"),
+    ("chu", "亍刍怵憷绌杵楮樗褚蜍蹰黜"),
+    ("chuai", "搋膪踹"),
+    ("chuan", "舛遄巛氚钏舡"),
+    ("chuang", "怆"),
+    ("chui", "陲棰槌"),
+    ("chun", "莼鹑蝽"),
+    ("chuo", "啜辶辍踔龊"),
+    ("ci", "茈呲祠鹚糍"),
+    ("cong", "苁淙骢琮璁枞"),
+    ("cou", "辏腠"),
+    ("cu", "蔟徂猝殂酢蹙蹴"),
+    ("cuan", "汆撺爨镩"),
+    ("cui", "萃啐悴璀榱毳"),
+    ("cun", "忖皴"),
+    ("cuo", "厝嵯脞锉矬痤鹾蹉"),
+    ("da", "耷哒嗒怛妲沓褡笪靼鞑"),
+    ("dai", "埭甙呔岱迨骀绐玳黛"),
+    ("dan", "儋萏啖澹殚赕眈疸瘅聃箪"),
+    ("dang", "谠凼菪宕砀铛裆"),
+    ("dao", "刂叨忉氘焘纛"),
+    ("de", "锝"),
+    ("deng", "噔嶝戥磴镫簦"),
+    ("di", "氐籴诋谛邸荻嘀娣柢棣觌砥碲睇镝羝骶"),
+    ("dia", "嗲"),
+    ("dian", "阽坫巅玷钿癜癫簟踮"),
+    ("diao", "铞铫貂鲷"),
+    ("die", "垤堞揲喋牒瓞耋蹀鲽"),
+    ("ding", "仃啶玎腚碇铤疔耵酊"),
+    ("diu", "铥"),
+    ("dong", "垌咚岽峒氡胨胴硐鸫"),
+    ("dou", "蔸窦蚪篼"),
+    ("du", "芏嘟渎椟牍碡蠹笃髑黩"),
+    ("duan", "椴煅簖"),
+    ("dui", "怼憝碓镦"),
+    ("dun", "沌炖砘礅盹趸"),
+    ("duo", "咄哚缍柁铎裰踱"),
+    ("e", "噩谔垩苊莪萼呃愕阏屙婀轭腭锇锷鹗颚鳄"),
+    ("ei", "诶"),
+    ("en", "蒽摁嗯"),
+    ("er", "佴迩珥铒鸸鲕"),
+    ("fa", "垡砝"),
+    ("fan", "蕃蘩幡梵燔畈蹯"),
+    ("fang", "匚邡彷枋钫舫鲂"),
+    ("fei", "芾狒悱淝妃绯榧腓斐扉镄痱蜚篚翡霏鲱"),
+    ("fen", "偾瀵棼鲼鼢"),
+    ("feng", "俸酆葑唪沣砜"),
+    ("fou", "缶"),
+    (
+        "fu",
+        "匐凫阝郛芙苻茯莩菔拊呋呒幞怫滏艴孚驸绂绋桴赙祓砩黻黼罘稃馥蚨蜉蝠蝮麸趺跗鲋鳆",
+    ),
+    ("ga", "尬呷尕尜旮钆"),
+    ("gai", "丐陔垓戤赅"),
+    ("gan", "坩苷尴擀泔淦澉绀橄旰矸疳酐"),
+    ("gang", "戆罡筻"),
+    ("gao", "睾诰郜藁缟槔槁杲锆"),
+    ("ge", "鬲仡哿圪塥嗝纥搿膈硌镉袼虼舸骼"),
+    ("gen", "亘茛哏艮"),
+    ("geng", "哽赓绠鲠"),
+    ("gong", "廾珙肱蚣觥"),
+    ("gou", "佝诟岣遘媾缑枸觏彀笱篝鞲"),
+    ("gu", "嘏诂菰呱崮汩梏轱牯牿臌毂瞽罟钴锢鸪鹄痼蛄酤觚鲴鹘"),
+    ("gua", "卦诖栝胍鸹聒"),
+    ("guai", "掴"),
+    ("guan", "倌莞掼涫盥鹳鳏"),
+    ("guang", "咣犷桄胱"),
+    ("gui", "匦刿庋宄妫桧晷皈簋鲑鳜"),
+    ("gun", "丨衮绲磙鲧"),
+    ("guo", "馘埚呙帼崞猓椁虢蜾蝈"),
+    ("ha", "铪"),
+    ("hai", "胲醢"),
+    ("han", "邗菡撖阚瀚晗焓顸颔蚶鼾"),
+    ("hang", "沆绗珩颃"),
+    ("hao", "蒿薅嗥嚆濠灏昊皓颢蚝"),
+    ("he", "诃劾壑嗬阖曷盍颌蚵翮"),
+    ("heng", "蘅桁"),
+    ("hong", "黉訇讧荭蕻薨闳泓"),
+    ("hou", "堠後逅瘊篌糇鲎骺"),
+    ("hu", "冱唿囫岵猢怙惚浒滹琥槲轷觳烀煳戽扈祜瓠鹕鹱虍笏醐斛"),
+    ("hua", "骅桦铧"),
+    ("huai", "踝"),
+    ("huan", "郇奂萑擐圜獾洹浣漶寰逭缳锾鲩鬟"),
+    ("huang", "隍徨湟潢遑璜肓癀蟥篁鳇"),
+    ("hui", "诙茴荟蕙咴哕喙隳洄浍彗缋珲晖恚虺蟪麾"),
+    ("hun", "诨馄阍溷"),
+    ("huo", "劐藿攉嚯夥砉钬锪镬耠蠖"),
+    (
+        "ji",
+        "丌亟乩剞佶偈诘墼芨芰荠蒺蕺掎叽咭哜唧岌嵴洎彐屐骥畿玑楫殛戟戢赍觊犄齑矶羁嵇稷瘠虮",
+    ),
+    ("ji", "笈笄暨跻跽霁鲚鲫髻麂"),
+    ("jia", "伽郏葭岬浃迦珈戛胛恝铗镓痂瘕袷蛱笳袈跏"),
+    ("jian", "僭谏谫菅蒹搛囝湔蹇謇缣枧楗戋戬牮犍毽腱睑锏鹣裥笕翦趼踺鲣鞯"),
+    ("jiang", "茳洚绛缰犟礓耩糨豇"),
+    ("jiao", "佼僬艽茭挢噍峤徼湫姣敫皎鹪蛟醮跤鲛"),
+    ("jie", "讦卩拮喈嗟婕孑桀碣疖颉蚧羯鲒骱"),
+    ("jin", "卺荩堇噤馑廑妗缙瑾槿赆觐钅衿矜"),
+    ("jing", "刭儆阱菁獍憬泾迳弪婧肼胫腈旌靓"),
+    ("jiong", "冂迥炅扃"),
+    ("jiu", "僦啾阄柩桕鸠鹫赳鬏"),
+    ("ju", "倨讵苣苴莒菹掬遽屦琚椐榘榉橘犋飓钜锔窭裾趄醵踽龃雎鞫"),
+    ("juan", "鄄狷涓桊蠲锩镌隽"),
+    ("jue", "厥劂谲矍蕨噘噱崛獗孓珏桷橛爝镢蹶觖"),
+    ("jun", "捃皲麇"),
+    ("ka", "佧咔胩"),
+    ("kai", "剀垲蒈忾恺铠锎锴"),
+    ("kan", "侃莰戡龛瞰"),
+    ("kang", "伉闶钪"),
+    ("kao", "尻栲犒铐"),
+    ("ke", "嗑嗨岢恪溘骒缂珂轲氪瞌钶锞稞疴窠颏蝌髁"),
+    ("ken", "裉龈"),
+    ("keng", "铿"),
+    ("kong", "倥崆箜"),
+    ("kou", "芤蔻叩眍筘"),
+    ("ku", "刳堀喾绔骷"),
+    ("kua", "侉"),
+    ("kuai", "蒯郐哙狯脍"),
+    ("kuan", "髋"),
+    ("kuang", "诓诳邝圹夼哐纩贶"),
+    ("kui", "馗匮夔隗蒉揆喹喟悝愦逵暌睽聩蝰篑跬"),
+    ("kun", "悃阃琨锟醌鲲髡"),
+    ("kuo", "蛞"),
+    ("la", "剌邋旯砬瘌"),
+    ("lai", "崃徕涞濑赉睐铼癞籁"),
+    ("lan", "岚漤榄斓罱镧褴"),
+    ("lang", "莨蒗啷阆锒稂螂"),
+    ("lao", "唠崂栳铑铹痨耢醪"),
+    ("le", "仂叻泐鳓"),
+    ("lei", "羸诔嘞嫘缧檑耒酹"),
+    ("leng", "塄愣"),
+    (
+        "li",
+        "俪俚郦坜苈莅蓠藜呖唳喱猁溧澧逦娌嫠骊缡枥栎轹戾砺詈罹锂鹂疠疬蛎蜊蠡笠篥粝醴跞雳鲡",
+    ),
+    ("li", "鳢黧"),
+    ("lian", "蔹奁潋濂琏楝殓臁裢裣蠊鲢"),
+    ("liang", "墚椋踉魉"),
+    ("liao", "蓼尥嘹獠寮缭钌鹩"),
+    ("lie", "冽埒捩咧洌趔躐鬣"),
+    ("lin", "蔺啉嶙廪懔遴檩辚膦瞵粼躏麟"),
+    ("ling", "酃苓呤囹泠绫柃棂瓴聆蛉翎鲮"),
+    ("liu", "浏遛骝绺旒熘锍镏鹨鎏"),
+    ("long", "垅茏泷珑栊胧砻癃"),
+    ("lou", "偻蒌喽嵝镂瘘耧蝼髅"),
+    ("lu", "垆撸噜泸渌漉逯璐栌橹轳辂辘氇胪镥鸬鹭簏舻鲈"),
+    ("luan", "脔娈栾鸾銮"),
+    ("lue", "锊"),
+    ("lun", "囵"),
+    ("luo", "倮蠃荦摞猡泺漯珞椤脶镙瘰雒"),
+    ("lv", "捋闾榈膂稆褛"),
+    ("ma", "唛犸嬷杩蟆"),
+    ("mai", "劢荬霾"),
+    ("man", "墁幔缦熳镘颟螨鳗鞔"),
+    ("mang", "邙漭硭蟒"),
+    ("mao", "袤茆峁泖瑁昴牦耄旄懋瞀蝥蟊髦"),
+    ("mei", "莓嵋猸浼湄楣镅鹛袂魅"),
+    ("men", "扪焖懑钔"),
+    ("meng", "勐甍瞢懵朦礞虻蜢蠓艋艨"),
+    ("mi", "芈冖谧蘼咪嘧猕汨宓弭脒祢敉糸縻麋"),
+    ("mian", "沔渑湎宀腼眄黾"),
+    ("miao", "喵邈缈杪淼眇鹋"),
+    ("mie", "乜咩蠛篾"),
+    ("min", "苠岷闵泯缗珉愍鳘"),
+    ("ming", "冥茗溟暝瞑酩"),
+    ("mo", "谟茉蓦馍嫫殁镆秣瘼耱貊貘麽"),
+    ("mou", "侔哞缪眸蛑鍪"),
+    ("mu", "仫坶苜沐毪钼"),
+    ("na", "捺肭镎衲"),
+    ("nai", "鼐艿萘囡柰"),
+    ("nan", "喃楠腩蝻赧"),
+    ("nang", "攮囔馕曩"),
+    ("nao", "孬垴呶猱瑙硇铙蛲"),
+    ("ne", "讷疒"),
+    ("nen", "恁"),
+    ("ni", "伲坭猊怩昵旎睨铌鲵"),
+    ("nian", "廿埝辇黏鲇鲶"),
+    ("niao", "茑嬲脲袅"),
+    ("nie", "陧蘖嗫颞臬蹑"),
+    ("ning", "佞咛甯聍"),
+    ("niu", "狃忸妞"),
+    ("nong", "侬哝"),
+    ("nou", "耨"),
+    ("nu", "弩胬孥驽"),
+    ("nuo", "傩搦喏锘"),
+    ("nv", "恧钕衄"),
+    ("o", "喔噢"),
+    ("ou", "讴怄瓯耦"),
+    ("pa", "葩杷筢"),
+    ("pai", "俳蒎哌"),
+    ("pan", "拚爿泮袢襻蟠蹒"),
+    ("pang", "滂逄螃"),
+    ("pao", "匏狍庖脬疱"),
+    ("pei", "辔帔旆锫醅霈"),
+    ("pen", "湓"),
+    ("peng", "堋嘭怦蟛"),
+    ("pi", "丕仳陴邳郫圮埤鼙芘擗噼庀淠媲纰枇甓睥罴铍癖疋蚍蜱貔"),
+    ("pian", "谝骈犏胼翩蹁"),
+    ("piao", "剽嘌嫖缥殍瞟螵"),
+    ("pie", "丿苤氕"),
+    ("pin", "姘嫔榀牝颦"),
+    ("ping", "俜娉枰鲆"),
+    ("po", "叵鄱珀钋钷皤笸"),
+    ("pou", "裒掊"),
+    ("pu", "匍噗溥濮璞攴氆镤镨蹼"),
+    (
+        "qi",
+        "亓俟圻芑芪萁萋葺蕲嘁屺岐汔淇骐绮琪琦杞桤槭耆祺憩碛颀蛴蜞綦綮蹊鳍麒",
+    ),
+    ("qia", "葜髂"),
+    ("qian", "倩佥阡凵芊芡茜掮岍悭慊骞搴褰缱椠肷愆钤虔箝"),
+    ("qiang", "丬戕嫱樯戗炝锖锵镪襁蜣羟跄"),
+    ("qiao", "劁诮谯荞愀憔缲樵硗跷鞒"),
+    ("qie", "郄惬妾挈锲箧"),
+    ("qin", "芩揿吣嗪噙溱檎锓螓衾"),
+    ("qing", "苘圊檠磬蜻罄箐謦鲭黥"),
+    ("qiong", "邛茕穹蛩筇跫銎"),
+    ("qiu", "俅巯犰逑遒楸赇虬蚯蝤裘糗鳅鼽"),
+    ("qu", "诎劬蕖蘧岖衢阒璩觑氍朐祛磲鸲癯蛐蠼麴瞿黢"),
+    ("quan", "诠荃犭悛绻辁畎铨蜷筌鬈"),
+    ("que", "阕阙悫"),
+    ("qun", "逡"),
+    ("ran", "苒蚺髯"),
+    ("rang", "禳穰"),
+    ("rao", "荛娆桡"),
+    ("ren", "亻仞荏葚饪轫稔衽"),
+    ("rong", "嵘狨榕肜蝾"),
+    ("rou", "糅蹂鞣"),
+    ("ru", "蓐薷嚅洳溽濡缛铷襦颥"),
+    ("ruan", "朊"),
+    ("rui", "芮蕤枘睿蚋"),
+    ("ruo", "偌箬"),
+    ("sa", "卅仨挲脎飒"),
+    ("sai", "噻"),
+    ("san", "馓毵糁霰"),
+    ("sang", "搡磉颡"),
+    ("sao", "埽缫臊瘙鳋"),
+    ("se", "啬铯穑"),
+    ("sha", "唼歃铩痧裟霎鲨"),
+    ("shai", "酾"),
+    ("shan", "剡讪鄯埏芟彡潸姗嬗骟膻钐疝蟮舢跚鳝"),
+    ("shang", "垧绱殇熵觞"),
+    ("shao", "劭苕潲蛸筲艄"),
+    ("she", "厍佘猞滠歙畲麝"),
+    ("shen", "诜谂莘哂渖椹胂矧蜃"),
+    ("sheng", "嵊眚笙"),
+    ("shi", "谥埘莳蓍弑饣轼贳炻礻铈螫舐筮豉豕鲥鲺"),
+    ("shou", "扌狩绶艏"),
+    ("shu", "倏塾菽摅沭澍姝纾毹腧殳秫"),
+    ("shua", "唰"),
+    ("shuai", "蟀"),
+    ("shuan", "闩涮"),
+    ("shuang", "孀"),
+    ("shui", "氵"),
+    ("shuo", "蒴搠妁槊铄"),
+    ("si", "厮兕厶咝汜泗澌姒驷纟缌祀锶鸶耜蛳笥"),
+    ("song", "凇菘崧嵩忪悚淞竦"),
+    ("sou", "叟薮嗖嗾馊溲飕瞍锼螋"),
+    ("su", "夙谡蔌嗉愫涑簌觫稣"),
+    ("suan", "狻"),
+    ("sui", "谇荽濉邃攵燧眭睢"),
+    ("sun", "荪狲飧榫隼"),
+    ("suo", "唢嗦嗍娑桫睃羧"),
+    ("ta", "闼溻遢榻铊趿鳎"),
+    ("tai", "邰薹肽炱钛跆鲐"),
+    ("tan", "郯昙忐钽锬覃"),
+    ("tang", "傥帑饧溏瑭樘铴镗耥螗螳羰醣"),
+    ("tao", "鼗啕洮韬饕"),
+    ("te", "忒忑慝铽"),
+    ("teng", "滕"),
+    ("ti", "倜荑悌逖绨缇鹈裼醍"),
+    ("tian", "掭忝阗殄畋"),
+    ("tiao", "佻祧窕蜩笤粜龆鲦髫"),
+    ("tie", "萜餮"),
+    ("ting", "莛葶婷梃町蜓霆"),
+    ("tong", "佟僮仝茼嗵恸潼砼"),
+    ("tou", "亠钭骰"),
+    ("tu", "堍荼菟钍酴"),
+    ("tuan", "抟彖疃"),
+    ("tui", "煺"),
+    ("tun", "氽饨暾豚"),
+    ("tuo", "乇佗坨庹沲沱柝橐砣箨酡跎鼍"),
+    ("wa", "佤娲腽"),
+    ("wai", "崴"),
+    ("wan", "剜芄菀纨绾琬脘畹蜿"),
+    ("wang", "罔惘辋魍"),
+    ("wei", "偎诿隈圩葳薇囗帏帷嵬猥猬闱沩洧涠逶娓玮韪軎炜煨痿艉鲔"),
+    ("wen", "刎阌汶玟璺雯"),
+    ("weng", "蓊蕹"),
+    ("wo", "倭莴幄渥肟硪龌"),
+    ("wu", "兀仵阢邬圬芴唔庑怃忤浯寤迕妩婺骛杌牾焐鹉鹜痦蜈鋈鼯"),
+    (
+        "xi",
+        "僖兮隰郗菥葸蓰奚唏徙饩阋浠淅屣嬉玺樨曦觋欷熹禊禧皙穸蜥螅蟋舄舾羲粞翕醯鼷",
+    ),
+    ("xia", "狎遐瑕柙硖罅黠"),
+    ("xian", "冼苋莶藓岘猃暹娴氙燹祆鹇痫蚬筅籼酰跣跹"),
+    ("xiang", "芗葙饷庠骧缃蟓鲞飨"),
+    ("xiao", "哓崤潇逍骁绡枭枵筱箫魈"),
+    ("xie", "偕亵勰燮薤撷獬廨渫瀣邂绁缬榭榍躞"),
+    ("xin", "囟馨忄昕歆鑫"),
+    ("xing", "陉荇荥擤悻硎"),
+    ("xiong", "芎"),
+    ("xiu", "咻岫馐庥溴鸺貅髹"),
+    ("xu", "诩勖蓿洫溆顼栩煦盱胥糈醑"),
+    ("xuan", "儇谖萱揎泫渲漩璇楦暄炫煊碹铉镟痃"),
+    ("xue", "谑泶踅鳕"),
+    ("xun", "巽埙荀荨蕈薰峋徇獯恂洵浔曛窨醺鲟"),
+    ("ya", "伢垭揠吖岈迓娅琊桠氩砑睚痖"),
+    ("yan", "厣赝俨偃兖讠谳郾鄢芫菸崦恹闫湮滟妍嫣琰檐晏胭腌焱罨筵酽魇餍鼹"),
+    ("yang", "徉怏泱炀烊恙蛘鞅"),
+    ("yao", "夭爻吆崾徭幺珧杳轺曜肴鹞窈繇鳐"),
+    ("ye", "靥谒邺揶晔烨铘"),
+    (
+        "yi",
+        "刈劓佚佾诒圯埸懿苡薏弈奕挹弋呓咦咿噫峄嶷猗饴怿怡悒漪迤驿缢殪轶贻欹旖熠眙钇镒镱痍",
+    ),
+    ("yi", "瘗癔翊衤蜴舣羿翳酏黟"),
+    ("yin", "胤鄞廴垠堙茚吲喑狺夤洇氤铟瘾蚓霪"),
+    ("ying", "嬴郢茔莺萦蓥撄嘤膺滢潆瀛瑛璎楹媵鹦瘿颍罂"),
+    ("yo", "唷"),
+    ("yong", "俑壅墉喁慵邕镛甬鳙饔"),
+    ("you", "卣攸侑莠莜莸尢呦囿宥纡柚猷牖铕疣蚰蚴蝣鱿黝鼬"),
+    (
+        "yu",
+        "禺毓伛俣谀谕萸蓣揄圄圉嵛狳饫馀庾阈鬻妪妤瑜昱觎腴欤於煜燠肀聿钰鹆鹬瘐瘀窬窳蜮蝓竽",
+    ),
+    ("yu", "臾舁雩龉"),
+    ("yuan", "垸塬掾沅媛瑗橼爰眢鸢螈箢鼋"),
+    ("yue", "龠瀹樾刖钺"),
+    ("yun", "郓芸狁恽愠纭韫殒昀氲熨筠"),
+    ("za", "拶咂"),
+    ("zai", "崽甾"),
+    ("zan", "瓒昝簪糌趱錾"),
+    ("zang", "奘驵臧"),
+    ("zao", "唣"),
+    ("ze", "仄赜啧帻迮昃笮箦舴"),
+    ("zen", "谮"),
+    ("zeng", "缯甑罾锃"),
+    ("zha", "揸吒咤哳楂砟痄蚱齄"),
+    ("zhai", "砦瘵"),
+    ("zhan", "谵搌旃"),
+    ("zhang", "仉鄣幛嶂獐嫜璋蟑"),
+    ("zhao", "诏啁棹钊笊"),
+    ("zhe", "谪摺柘辄磔鹧褶蜇赭"),
+    ("zhen", "圳蓁浈缜桢榛轸赈胗朕祯畛稹鸩箴"),
+    ("zheng", "诤峥徵钲铮筝"),
+    (
+        "zhi",
+        "卮陟郅埴芷摭帙夂忮彘咫骘栉枳栀桎轵轾贽胝膣祉祗黹雉鸷痣蛭絷酯跖踬踯豸觯",
+    ),
+    ("zhong", "冢锺螽舯踵"),
+    ("zhou", "荮妯纣绉胄籀酎"),
+    ("zhu", "丶伫侏邾苎茱洙渚潴杼槠橥炷铢疰瘃竺箸舳翥躅麈"),
+    ("zhuan", "啭馔颛"),
+    ("zhui", "惴骓缒隹"),
+    ("zhun", "肫窀"),
+    ("zhuo", "倬诼擢浞涿濯禚斫镯"),
+    ("zi", "谘嵫姊孳缁梓辎赀恣眦锱秭耔笫粢趑觜訾龇鲻髭"),
+    ("zong", "偬腙粽"),
+    ("zou", "诹陬鄹驺楱鲰"),
+    ("zu", "俎镞"),
+    ("zuan", "攥缵躜"),
+    ("zui", "蕞"),
+    ("zun", "撙樽鳟"),
+    ("zuo", "阼唑嘬怍胙祚"),
+];
+
+static LEVEL2: Lazy<HashMap<char, &'static str>> = Lazy::new(|| {
+    LEVEL2_SYLLABLES
+        .iter()
+        .flat_map(|(syllable, chars)| chars.chars().map(move |c| (c, *syllable)))
+        .collect()
+});
+
+/// Full pinyin of a character, e.g. `斗` -> `dou`
+pub fn char_pinyin(c: char) -> Option<&'static str> {
+    if !('\u{4E00}'..='\u{9FA5}').contains(&c) {
+        return None;
+    }
+    let mut buf = [0u8; 4];
+    let (bytes, _, unmappable) = GBK.encode(c.encode_utf8(&mut buf));
+    let [hi, lo] = bytes[..] else {
+        return None;
+    };
+    let code = u16::from_be_bytes([hi, lo]);
+    // GBK extensions share the lead bytes but not the trail range of GB2312
+    if unmappable || lo < 0xA1 || code < LEVEL1_SYLLABLES[0].0 {
+        return None;
+    }
+    if code > LEVEL1_END {
+        return LEVEL2.get(&c).copied();
+    }
+    let idx = LEVEL1_SYLLABLES.partition_point(|(start, _)| *start <= code);
+    Some(LEVEL1_SYLLABLES[idx - 1].1)
+}
+
+/// Lowercase full pinyin and pinyin initials of `text`
+///
+/// ASCII letters and digits are kept as-is in both; other characters without
+/// a reading are dropped. `斗破苍穹` -> (`doupocangqiong`, `dpcq`).
+pub fn pinyin_keys(text: &str) -> (String, String) {
+    let mut full = String::new();
+    let mut initials = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            let c = c.to_ascii_lowercase();
+            full.push(c);
+            initials.push(c);
+        } else if let Some(syllable) = char_pinyin(c) {
+            full.push_str(syllable);
+            initials.extend(syllable.chars().next());
+        }
+    }
+    (full, initials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_pinyin() {
+        assert_eq!(char_pinyin('啊'), Some("a"));
+        assert_eq!(char_pinyin('斗'), Some("dou"));
+        assert_eq!(char_pinyin('穹'), Some("qiong"));
+        assert_eq!(char_pinyin('座'), Some("zuo"));
+        assert_eq!(char_pinyin('a'), None);
+        assert_eq!(char_pinyin('癅'), None);
+        // Traditional characters are outside GB2312
+        assert_eq!(char_pinyin('鬥'), None);
+    }
+
+    #[test]
+    fn test_pinyin_keys() {
+        assert_eq!(
+            pinyin_keys("斗破苍穹"),
+            ("doupocangqiong".to_string(), "dpcq".to_string())
+        );
+        assert_eq!(
+            pinyin_keys("天蚕土豆"),
+            ("tiancantudou".to_string(), "tctd".to_string())
+        );
+        assert_eq!(pinyin_keys("三体 II"), ("santiii".to_string(), "stii".to_string()));
+    }
+}
@@ -9,8 +9,11 @@
 //! - `{{key}}` - Variable substitution
 //! - `{{page}}` - Page number variable
 //! - `{{java.base64Encode(key)}}` - Native API call (can be Rust-executed)
-//! - `{{(page-1)*20}}` - JS expression (requires JS engine)
+//! - `{{(page-1)*20}}` - Arithmetic, comparison, ternary or string method
+//!   expression (evaluated natively by [`SimpleExpr`])
+//! - Anything else - JS expression (requires JS engine)
 
+use super::template::SimpleExpr;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -74,6 +77,10 @@ pub enum NativeApi {
     HttpPost,
     HttpRequest,
     HttpGetAll,
+    /// `java.head(url, headers?)`: response headers of a HEAD request as JSON
+    HttpHead,
+    /// `java.getSize(url)`: Content-Length from a HEAD request, -1 if unknown
+    HttpContentLength,
 
     // ============== File Operations ==============
     CacheFile,
@@ -82,8 +89,14 @@ pub enum NativeApi {
     ReadTxtFileWithCharset,
     DeleteFile,
     GetFile,
+    /// `java.getTxtInFolder(path)`: all text files of a cache folder, joined by newlines
+    GetTxtInFolder,
     ImportScript,
 
+    // ============== Font Deobfuscation ==============
+    QueryTtf,
+    ReplaceFont,
+
     // ============== ZIP Operations ==============
     ZipReadString,
     ZipReadStringWithCharset,
@@ -106,6 +119,10 @@ pub enum NativeApi {
         end: Option<i32>,
     },
     HtmlToText,
+    /// `java.t2s(text)`: traditional to simplified Chinese
+    ChineseT2s,
+    /// `java.s2t(text)`: simplified to traditional Chinese
+    ChineseS2t,
     StringToLowerCase,
     StringToUpperCase,
     StringPadStart {
@@ -140,6 +157,14 @@ pub enum NativeApi {
     StringLastIndexOf {
         search: String,
     },
+    /// `list.join(separator)` on a JSON array intermediate (e.g. a split result)
+    ArrayJoin {
+        separator: String,
+    },
+    /// `value[index]`: element of a JSON array, or character of a plain string
+    ArrayIndex {
+        index: i64,
+    },
 
     // ============== Time Operations ==============
     GetTimeMillis,
@@ -154,12 +179,53 @@ pub enum NativeApi {
     CacheSet,
     SourceVarGet,
     SourceVarSet,
+    /// `book.getVariable(key)`, called with the book's variable JSON and the key
+    BookVarGet,
+
+    // ============== JS Globals ==============
+    /// `parseInt(value, radix?)`
+    ParseInt,
+    /// `parseFloat(value)`
+    ParseFloat,
+    /// `Number(value)`
+    ToNumber { from: JsType },
+    /// `String(value)`
+    ToJsString { from: JsType },
+    /// `Boolean(value)`
+    ToBoolean { from: JsType },
+    /// `a + b` where either side is a string
+    JsConcat,
+    /// Numeric `+ - * / %` on two values
+    JsArithmetic { op: ArithmeticOp },
 
     // ============== Misc ==============
     Log,
+    /// UI-only call (`java.toast`, `java.longToast`, `java.startBrowser`), a no-op on the server
+    UiNoop(String),
     Unknown(String),
 }
 
+/// Static JS type of a value passed to a native conversion
+///
+/// Native values are always strings, so conversions whose result depends on
+/// the JS type (e.g. `Boolean(0)` vs `Boolean("0")`) carry it from analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JsType {
+    String,
+    Number,
+    Boolean,
+}
+
+/// Numeric binary operators executed natively
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
 /// Template expression types
 #[derive(Debug, Clone)]
 pub enum TemplateExpr {
@@ -175,6 +241,9 @@ pub enum TemplateExpr {
         args: Vec<Box<TemplateExpr>>,
     },
 
+    /// Expression evaluated natively: {{(page-1)*20}}, {{key.replace(' ','+')}}
+    Simple(SimpleExpr),
+
     /// JS expression that must be evaluated by JS engine
     JsExpr(String),
 }
@@ -284,13 +353,24 @@ impl SourcePreprocessor {
         native_apis.insert("httpPost".to_string(), |_| NativeApi::HttpPost);
         native_apis.insert("ajax".to_string(), |_| NativeApi::HttpGet);
         native_apis.insert("connect".to_string(), |_| NativeApi::HttpGet);
+        native_apis.insert("head".to_string(), |_| NativeApi::HttpHead);
+        native_apis.insert("getSize".to_string(), |_| NativeApi::HttpContentLength);
 
         // Storage
         native_apis.insert("putVariable".to_string(), |_| NativeApi::SourceVarSet);
+        native_apis.insert("setVariable".to_string(), |_| NativeApi::SourceVarSet);
         native_apis.insert("getVariable".to_string(), |_| NativeApi::SourceVarGet);
         native_apis.insert("cachePut".to_string(), |_| NativeApi::CacheSet);
         native_apis.insert("cacheGet".to_string(), |_| NativeApi::CacheGet);
 
+        // Font
+        native_apis.insert("queryTTF".to_string(), |_| NativeApi::QueryTtf);
+        native_apis.insert("replaceFont".to_string(), |_| NativeApi::ReplaceFont);
+
+        // String
+        native_apis.insert("t2s".to_string(), |_| NativeApi::ChineseT2s);
+        native_apis.insert("s2t".to_string(), |_| NativeApi::ChineseS2t);
+
         // Misc
         native_apis.insert("log".to_string(), |_| NativeApi::Log);
 
@@ -462,6 +542,10 @@ impl SourcePreprocessor {
             };
         }
 
+        if let Some(simple) = SimpleExpr::parse(expr) {
+            return TemplateExpr::Simple(simple);
+        }
+
         // Fallback to JS expression
         TemplateExpr::JsExpr(expr.to_string())
     }
@@ -13,9 +13,32 @@
 //! var font2 = java.queryTTF("http://example.com/standard.ttf");
 //! var decoded = java.replaceFont(encodedText, font1, font2);
 //! ```
+//!
+//! `queryTTF` accepts a font URL (downloaded once and cached on disk by URL
+//! hash) or base64 font data, and returns a handle that `replaceFont` resolves.
 
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use once_cell::sync::Lazy;
+
+/// Prefix of the handles returned by `java.queryTTF`
+pub const FONT_HANDLE_PREFIX: &str = "ttf:";
+
+/// Largest font file accepted by `java.queryTTF`
+pub const MAX_FONT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Parsed fonts are dropped once this many are loaded; sites that rotate
+/// fonts per chapter would otherwise grow the table without bound
+const MAX_LOADED_FONTS: usize = 64;
+
+/// Fonts parsed in this process, keyed by handle
+static LOADED_FONTS: Lazy<Mutex<HashMap<String, Arc<QueryTTF>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// TTF font parser and glyph mapper
 #[derive(Clone, Default)]
@@ -54,8 +77,9 @@ impl QueryTTF {
                         qtf.glyph_to_code.insert(id, codepoint);
                         
                         // Calculate glyph shape hash
-                        let hash = Self::calculate_glyph_hash(&face, glyph_id);
-                        qtf.glyph_hashes.insert(id, hash);
+                        if let Some(hash) = Self::calculate_glyph_hash(&face, glyph_id) {
+                            qtf.glyph_hashes.insert(id, hash);
+                        }
                         
                         if codepoint < min_code { min_code = codepoint; }
                         if codepoint > max_code { max_code = codepoint; }
@@ -74,25 +98,15 @@ impl QueryTTF {
         Some(qtf)
     }
     
-    /// Calculate a hash for a glyph's shape
-    fn calculate_glyph_hash(face: &ttf_parser::Face, glyph_id: ttf_parser::GlyphId) -> u64 {
+    /// Calculate a hash for a glyph's contours
+    ///
+    /// Only the outline is hashed, so the same shape matches across fonts
+    /// even when metrics differ. Glyphs without an outline (spaces) yield None.
+    fn calculate_glyph_hash(face: &ttf_parser::Face, glyph_id: ttf_parser::GlyphId) -> Option<u64> {
         use std::collections::hash_map::DefaultHasher;
         
         let mut hasher = DefaultHasher::new();
         
-        // Use bounding box and advance width as shape signature
-        if let Some(bbox) = face.glyph_bounding_box(glyph_id) {
-            bbox.x_min.hash(&mut hasher);
-            bbox.y_min.hash(&mut hasher);
-            bbox.x_max.hash(&mut hasher);
-            bbox.y_max.hash(&mut hasher);
-        }
-        
-        if let Some(advance) = face.glyph_hor_advance(glyph_id) {
-            advance.hash(&mut hasher);
-        }
-        
-        // Outline points for more accuracy
         struct OutlineHasher<'a> {
             hasher: &'a mut DefaultHasher,
         }
@@ -124,9 +138,9 @@ impl QueryTTF {
         }
         
         let mut outline_hasher = OutlineHasher { hasher: &mut hasher };
-        let _ = face.outline_glyph(glyph_id, &mut outline_hasher);
+        face.outline_glyph(glyph_id, &mut outline_hasher)?;
         
-        hasher.finish()
+        Some(hasher.finish())
     }
     
     /// Check if a character is within the font's defined range
@@ -171,32 +185,133 @@ impl QueryTTF {
     }
 }
 
+/// Map each code point of `font1` to the code point of `font2` whose glyph
+/// has the same contours
+///
+/// When `font2` draws one shape under several code points the lowest wins.
+pub fn font_mapping(font1: &QueryTTF, font2: &QueryTTF) -> HashMap<char, char> {
+    let mut by_shape: HashMap<u64, u32> = HashMap::new();
+    for (&code, glyph_id) in &font2.cmap {
+        if let Some(&hash) = font2.glyph_hashes.get(glyph_id) {
+            by_shape
+                .entry(hash)
+                .and_modify(|c| *c = (*c).min(code))
+                .or_insert(code);
+        }
+    }
+
+    font1
+        .cmap
+        .iter()
+        .filter_map(|(&code, glyph_id)| {
+            let target = by_shape.get(font1.glyph_hashes.get(glyph_id)?)?;
+            Some((char::from_u32(code)?, char::from_u32(*target)?))
+        })
+        .collect()
+}
+
 /// Replace characters in text using font mapping
 /// 
 /// Given text encoded with font1, decode it using font2 as reference
 pub fn replace_font(text: &str, font1: &QueryTTF, font2: &QueryTTF) -> String {
-    text.chars().map(|c| {
-        // If character is in font1's range
-        if font1.in_limit(c) {
-            let code = c as u32;
-            
-            // Get the glyph ID and its shape hash from font1
-            if let Some(glyph_id) = font1.get_glyph_by_code(code) {
-                if let Some(hash) = font1.get_glyph_hash(glyph_id) {
-                    // Find the same shape in font2
-                    if let Some(font2_glyph) = font2.find_glyph_by_hash(hash) {
-                        // Get the correct unicode for this glyph in font2
-                        if let Some(correct_code) = font2.get_code_by_glyph(font2_glyph) {
-                            if let Some(correct_char) = char::from_u32(correct_code) {
-                                return correct_char;
-                            }
-                        }
-                    }
-                }
+    let mapping = font_mapping(font1, font2);
+    text.chars()
+        .map(|c| mapping.get(&c).copied().unwrap_or(c))
+        .collect()
+}
+
+/// Handle for a font source, derived from the hash of its URL or data
+pub fn font_handle(source: &str) -> String {
+    format!("{}{:x}", FONT_HANDLE_PREFIX, md5::compute(source.trim()))
+}
+
+/// Load a font for `java.queryTTF` and return its handle
+///
+/// `source` is an http(s) URL, fetched with `download` on first use and then
+/// served from `cache_dir/ttf`, or base64 font data (optionally a `data:` URL).
+/// An existing handle is returned as is once its font is loaded.
+pub fn query_ttf<F>(source: &str, cache_dir: &Path, download: F) -> Result<String>
+where
+    F: FnOnce(&str) -> Result<Vec<u8>>,
+{
+    load_font(source, cache_dir, download).map(|(handle, _)| handle)
+}
+
+/// Resolve a `replaceFont` argument: a `queryTTF` handle or a font source
+pub fn resolve_font<F>(font: &str, cache_dir: &Path, download: F) -> Result<Arc<QueryTTF>>
+where
+    F: FnOnce(&str) -> Result<Vec<u8>>,
+{
+    load_font(font, cache_dir, download).map(|(_, font)| font)
+}
+
+fn load_font<F>(source: &str, cache_dir: &Path, download: F) -> Result<(String, Arc<QueryTTF>)>
+where
+    F: FnOnce(&str) -> Result<Vec<u8>>,
+{
+    let source = source.trim();
+    if source.is_empty() {
+        return Err(anyhow!("queryTTF requires a font URL or base64 data"));
+    }
+    let handle = if source.starts_with(FONT_HANDLE_PREFIX) {
+        source.to_string()
+    } else {
+        font_handle(source)
+    };
+    if let Some(font) = LOADED_FONTS.lock().unwrap().get(&handle) {
+        return Ok((handle, font.clone()));
+    }
+
+    let path = cache_path(cache_dir, &handle);
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(_) if source.starts_with(FONT_HANDLE_PREFIX) => {
+            return Err(anyhow!("Unknown font handle: {}", source));
+        }
+        Err(_) => {
+            let data = if source.starts_with("http://") || source.starts_with("https://") {
+                download(source)?
+            } else {
+                decode_font_data(source)?
+            };
+            if let Err(e) = write_cache(&path, &data) {
+                tracing::warn!("Failed to cache font {}: {}", source, e);
             }
+            data
         }
-        c
-    }).collect()
+    };
+
+    let font = Arc::new(
+        QueryTTF::new(&data).ok_or_else(|| anyhow!("Invalid TTF font: {}", handle))?,
+    );
+    let mut fonts = LOADED_FONTS.lock().unwrap();
+    if fonts.len() >= MAX_LOADED_FONTS {
+        fonts.clear();
+    }
+    fonts.insert(handle.clone(), font.clone());
+    Ok((handle, font))
+}
+
+fn cache_path(cache_dir: &Path, handle: &str) -> PathBuf {
+    let name = handle.trim_start_matches(FONT_HANDLE_PREFIX);
+    cache_dir.join("ttf").join(format!("{}.ttf", name))
+}
+
+fn write_cache(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, data)
+}
+
+fn decode_font_data(source: &str) -> Result<Vec<u8>> {
+    let data = match source.split_once(";base64,") {
+        Some((prefix, data)) if prefix.starts_with("data:") => data,
+        _ => source,
+    };
+    base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| anyhow!("Font is neither a URL nor base64 data: {}", e))
 }
 
 #[cfg(test)]
@@ -215,4 +330,48 @@ mod tests {
         assert!(qtf.is_empty());
         assert_eq!(qtf.len(), 0);
     }
+
+    const REFERENCE: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fonts/reference.ttf"));
+    const OBFUSCATED: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fonts/obfuscated.ttf"));
+
+    #[test]
+    fn test_replace_font_permuted_glyphs() {
+        let obfuscated = QueryTTF::new(OBFUSCATED).unwrap();
+        let reference = QueryTTF::new(REFERENCE).unwrap();
+        assert_eq!(obfuscated.len(), 3);
+
+        // U+E003 draws the square, U+E001 the triangle, U+E002 the diamond
+        let text = "第\u{E003}\u{E001}\u{E002}章";
+        assert_eq!(replace_font(text, &obfuscated, &reference), "第天地人章");
+    }
+
+    #[test]
+    fn test_query_ttf_caches_by_handle() {
+        let cache_dir = std::env::temp_dir().join("reader_tests_query_ttf");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let encoded = base64::engine::general_purpose::STANDARD.encode(OBFUSCATED);
+        let source = format!("data:font/ttf;base64,{}", encoded);
+
+        let handle = query_ttf(&source, &cache_dir, |_| unreachable!()).unwrap();
+        assert!(handle.starts_with(FONT_HANDLE_PREFIX));
+        assert!(cache_path(&cache_dir, &handle).exists());
+        assert_eq!(query_ttf(&handle, &cache_dir, |_| unreachable!()).unwrap(), handle);
+
+        // URLs are downloaded once, then served from the disk cache
+        let url = "https://fonts.example.com/reference.ttf";
+        let mut downloads = 0;
+        let font = resolve_font(url, &cache_dir, |_| {
+            downloads += 1;
+            Ok(REFERENCE.to_vec())
+        })
+        .unwrap();
+        assert_eq!(downloads, 1);
+        assert_eq!(font.len(), 3);
+        LOADED_FONTS.lock().unwrap().remove(&font_handle(url));
+        resolve_font(url, &cache_dir, |_| unreachable!()).unwrap();
+
+        assert!(query_ttf("ttf:missing", &cache_dir, |_| unreachable!()).is_err());
+    }
 }
@@ -7,57 +7,64 @@
 //! - Template variable replacement ({{key}})
 
 use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
 use regex::Regex;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use super::analysis::UnifiedJsAnalyzer;
 use super::cookie::CookieManager;
+use super::deadline;
+use super::http_client::{encode_with_charset, is_json_body, is_percent_encoded, split_url_options};
 use super::js_analyzer::AnalysisResult;
-use super::js_executor::JsExecutor;
+use super::js_executor::{JsExecutor, JsScope, RuleQuery};
 use super::native_api::NativeApiProvider;
 use super::parsers::{Parser, ParserFactory, RuleType};
-use super::preprocessor::SourcePreprocessor;
+use super::preprocessor::{SourcePreprocessor, TemplateExpr};
+use super::rule_context::{BookContext, ChapterContext, RuleContext};
 use super::template::{TemplateContext, TemplateExecutor};
+use super::trace::{self, TraceCollector};
 use crate::storage::kv::KvStore;
 
 /// Rule Analyzer for parsing content using Legado rules
+///
+/// The parsers, the JS runtime and the analysis memo are shared with every
+/// [`fork`](Self::fork); `@put` variables, capture groups and the book/chapter
+/// context belong to one parse session and start empty in a fork.
 pub struct RuleAnalyzer {
     /// Unified parser factory for all content parsers
-    parser_factory: ParserFactory,
-    js_executor: JsExecutor,
-    variables: std::cell::RefCell<HashMap<String, String>>,
-    result_list: std::cell::RefCell<Vec<String>>, // For $1, $2 capture groups
+    parser_factory: Arc<ParserFactory>,
+    js_executor: Arc<JsExecutor>,
+    variables: Mutex<HashMap<String, String>>,
+    result_list: Mutex<Vec<String>>, // For $1, $2 capture groups
     /// Source preprocessor for rule analysis
-    preprocessor: SourcePreprocessor,
+    preprocessor: Arc<SourcePreprocessor>,
     /// Native API provider for Rust-native execution
     native_api: Arc<NativeApiProvider>,
     /// Template executor for URL/rule templates
-    template_executor: TemplateExecutor,
+    template_executor: Arc<TemplateExecutor>,
     /// Unified JS analyzer (combines regex + AST analysis)
-    unified_analyzer: UnifiedJsAnalyzer,
-    /// Base URL for resolving relative links and source isolation
+    unified_analyzer: Arc<UnifiedJsAnalyzer>,
+    /// Base URL for resolving relative links
     base_url: String,
+    /// `bookSourceUrl` scoping source variables
+    source_url: String,
+    /// Book / chapter metadata exposed to rules
+    context: Mutex<RuleContext>,
+    /// JS bindings and limits of this session
+    js_scope: Mutex<JsScope>,
+    /// Optional debug trace of top-level rule evaluations
+    trace: Option<TraceCollector>,
+    /// Nesting depth of traced calls, so recursive evaluation is recorded once
+    trace_depth: AtomicUsize,
 }
 
 impl RuleAnalyzer {
     /// Create a new RuleAnalyzer
     pub fn new(kv_store: Arc<KvStore>) -> Result<Self> {
-        let cookie_manager = Arc::new(CookieManager::new());
-        let native_api = Arc::new(NativeApiProvider::new(cookie_manager, kv_store));
-        let template_executor = TemplateExecutor::new(native_api.clone());
-
-        Ok(Self {
-            parser_factory: ParserFactory::new(),
-            js_executor: JsExecutor::new(native_api.clone())?,
-            variables: std::cell::RefCell::new(HashMap::new()),
-            result_list: std::cell::RefCell::new(Vec::new()),
-            preprocessor: SourcePreprocessor::new(),
-            native_api,
-            template_executor,
-            unified_analyzer: UnifiedJsAnalyzer::new(),
-            base_url: String::new(),
-        })
+        Self::with_cookie_manager(Arc::new(CookieManager::new()), kv_store)
     }
 
     /// Create RuleAnalyzer with shared cookie manager
@@ -65,26 +72,169 @@ impl RuleAnalyzer {
         cookie_manager: Arc<CookieManager>,
         kv_store: Arc<KvStore>,
     ) -> Result<Self> {
-        let native_api = Arc::new(NativeApiProvider::new(cookie_manager, kv_store));
+        let native_api = Arc::new(NativeApiProvider::new(cookie_manager.clone(), kv_store.clone()));
         let template_executor = TemplateExecutor::new(native_api.clone());
+        let mut js_executor = JsExecutor::new(native_api.clone())?;
+        js_executor.set_rule_query(nested_rule_query(cookie_manager, kv_store));
 
         Ok(Self {
-            parser_factory: ParserFactory::new(),
-            js_executor: JsExecutor::new(native_api.clone())?,
-            variables: std::cell::RefCell::new(HashMap::new()),
-            result_list: std::cell::RefCell::new(Vec::new()),
-            preprocessor: SourcePreprocessor::new(),
+            parser_factory: Arc::new(ParserFactory::new()),
+            js_executor: Arc::new(js_executor),
+            variables: Mutex::new(HashMap::new()),
+            result_list: Mutex::new(Vec::new()),
+            preprocessor: Arc::new(SourcePreprocessor::new()),
             native_api,
-            template_executor,
-            unified_analyzer: UnifiedJsAnalyzer::new(),
+            template_executor: Arc::new(template_executor),
+            unified_analyzer: Arc::new(UnifiedJsAnalyzer::new()),
             base_url: String::new(),
+            source_url: String::new(),
+            context: Mutex::new(RuleContext::default()),
+            js_scope: Mutex::new(JsScope::default()),
+            trace: None,
+            trace_depth: AtomicUsize::new(0),
         })
     }
 
+    /// A new parse session sharing this analyzer's parsers, JS runtime and caches
+    ///
+    /// The fork starts without `@put` variables, capture groups, book, chapter,
+    /// trace or JS limits, so concurrent sessions cannot read each other's state.
+    pub fn fork(&self) -> Self {
+        Self {
+            parser_factory: self.parser_factory.clone(),
+            js_executor: self.js_executor.clone(),
+            variables: Mutex::new(HashMap::new()),
+            result_list: Mutex::new(Vec::new()),
+            preprocessor: self.preprocessor.clone(),
+            native_api: self.native_api.clone(),
+            template_executor: self.template_executor.clone(),
+            unified_analyzer: self.unified_analyzer.clone(),
+            base_url: self.base_url.clone(),
+            source_url: self.source_url.clone(),
+            context: Mutex::new(RuleContext {
+                base_url: self.base_url.clone(),
+                ..Default::default()
+            }),
+            js_scope: Mutex::new(JsScope::default()),
+            trace: None,
+            trace_depth: AtomicUsize::new(0),
+        }
+    }
+
     /// Set base URL for the JS executor
+    ///
+    /// Configures the shared executor, so it must be called before forking.
     pub fn set_base_url(&mut self, url: &str) {
         self.base_url = url.to_string();
-        self.js_executor.set_base_url(url);
+        self.context.get_mut().base_url = url.to_string();
+        unshared(&mut self.js_executor).set_base_url(url);
+    }
+
+    /// Set the source whose variables `source.getVariable()` / `putVariable` use
+    ///
+    /// Configures the shared executors, so it must be called before forking.
+    pub fn set_source_url(&mut self, url: &str) {
+        self.source_url = url.to_string();
+        unshared(&mut self.template_executor).set_source_url(url);
+        unshared(&mut self.js_executor).set_source_url(url);
+    }
+
+    /// Set the book exposed to rules as `book` / `book.name` ...
+    pub fn set_book(&self, book: Option<BookContext>) {
+        let mut context = self.context.lock();
+        context.book = book;
+        self.js_scope.lock().book_json = context.book_json();
+    }
+
+    /// Set the chapter exposed to rules as `chapter` / `chapter.index` / `title` ...
+    pub fn set_chapter(&self, chapter: Option<ChapterContext>) {
+        let mut context = self.context.lock();
+        context.chapter = chapter;
+        self.js_scope.lock().chapter_json = context.chapter_json();
+    }
+
+    /// Set the page being parsed (its URL after redirects), bound to `baseUrl` in templates and JS
+    pub fn set_page_url(&self, url: &str) {
+        self.context.lock().base_url = url.to_string();
+        self.js_scope.lock().base_url = Some(url.to_string());
+    }
+
+    /// URL of the page being parsed, or the source's base URL before any page was fetched
+    pub fn page_url(&self) -> String {
+        self.context.lock().base_url.clone()
+    }
+
+    /// Limit each JS evaluation to `timeout`
+    pub fn set_js_timeout(&self, timeout: Duration) {
+        self.js_scope.lock().timeout = timeout;
+    }
+
+    /// Interrupt running JS once `flag` is raised
+    pub fn set_js_cancel_flag(&self, flag: Arc<AtomicBool>) {
+        self.js_scope.lock().cancel = Some(flag);
+    }
+
+    /// Current book, if known
+    pub fn book(&self) -> Option<BookContext> {
+        self.context.lock().book.clone()
+    }
+
+    /// Flattened context variables (`baseUrl`, `book.name`, `chapter.index`, ...)
+    pub fn context_variables(&self) -> HashMap<String, String> {
+        self.context.lock().variables()
+    }
+
+    /// Set the content `java.getElements` / `java.getStrings` read by default
+    fn set_current_content(&self, content: &str) {
+        self.js_scope.lock().current_content = content.to_string();
+    }
+
+    /// Evaluate JS in QuickJS with this session's bindings and limits
+    ///
+    /// The evaluation's time limit is shortened to what is left of the request deadline.
+    fn eval_quickjs(&self, code: &str, vars: &HashMap<String, String>) -> Result<String> {
+        deadline::check()?;
+        let mut scope = self.js_scope.lock().clone();
+        scope.timeout = deadline::trim(scope.timeout);
+        self.js_executor
+            .eval_in_scope(code, vars, &scope)
+            .map_err(deadline::attribute)
+    }
+
+    /// `vars` on top of the context variables
+    fn with_context(&self, vars: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut merged = self.context_variables();
+        merged.extend(vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        merged
+    }
+
+    /// Record top-level rule evaluations into `trace`
+    pub fn set_trace(&mut self, trace: TraceCollector) {
+        self.trace = Some(trace);
+    }
+
+    /// Run a top-level rule evaluation, recording it when tracing is enabled
+    fn traced<T>(
+        &self,
+        content: &str,
+        rule: &str,
+        run: impl FnOnce() -> Result<T>,
+        describe: impl FnOnce(&T) -> String,
+    ) -> Result<T> {
+        let trace = match &self.trace {
+            Some(trace) if self.trace_depth.load(Ordering::Relaxed) == 0 && !rule.trim().is_empty() => trace,
+            _ => return run(),
+        };
+        self.trace_depth.store(1, Ordering::Relaxed);
+        let (result, execution) = trace::measure(run);
+        self.trace_depth.store(0, Ordering::Relaxed);
+
+        let value = match &result {
+            Ok(v) => Ok(describe(v)),
+            Err(e) => Err(format!("{:#}", e)),
+        };
+        trace.rule(rule, Some(RuleType::detect(rule, content)), execution, value);
+        result
     }
 
     /// Preload JavaScript library code (jsLib from book source)
@@ -108,14 +258,15 @@ impl RuleAnalyzer {
     pub fn build_url(&self, url_template: &str, vars: &[(&str, &str)]) -> Result<String> {
         let preprocessed = self.preprocessor.preprocess_url(url_template);
 
-        let mut ctx = TemplateContext::new();
+        let mut ctx = TemplateContext {
+            variables: self.context_variables(),
+        };
         for (key, value) in vars {
             ctx.set(key, value);
         }
 
         // Add stored variables
-        let stored_vars = self.variables.borrow();
-        for (k, v) in stored_vars.iter() {
+        for (k, v) in self.variables.lock().iter() {
             ctx.set(k, v);
         }
 
@@ -137,6 +288,16 @@ impl RuleAnalyzer {
         &self,
         exec: &super::js_analyzer::NativeExecution,
         content: &str,
+    ) -> Result<String> {
+        self.execute_native_js_with(exec, content, &self.context_variables())
+    }
+
+    /// Execute a native JS operation, resolving variables from `vars` first
+    fn execute_native_js_with(
+        &self,
+        exec: &super::js_analyzer::NativeExecution,
+        content: &str,
+        vars: &HashMap<String, String>,
     ) -> Result<String> {
         use super::js_analyzer::ExprValue;
 
@@ -148,26 +309,61 @@ impl RuleAnalyzer {
                 Ok(match arg {
                     ExprValue::Literal(s) => s.clone(),
                     ExprValue::Variable(name) => {
-                        // Check variables first, fall back to content
-                        self.variables
-                            .borrow()
-                            .get(name)
-                            .cloned()
-                            .unwrap_or_else(|| content.to_string())
+                        if let Some(value) = vars.get(name).cloned().or_else(|| self.variables.lock().get(name).cloned()) {
+                            value
+                        } else if self.context.lock().is_missing(name) {
+                            return Err(anyhow!("{} is not available yet", name));
+                        } else {
+                            // Fall back to content
+                            content.to_string()
+                        }
                     }
                     ExprValue::CurrentContent => content.to_string(),
-                    ExprValue::NativeCall(inner) => self.execute_native_js(inner, content)?,
+                    ExprValue::NativeCall(inner) => self.execute_native_js_with(inner, content, vars)?,
                 })
             })
             .collect::<Result<Vec<String>>>()?;
 
         // Execute the native API
         let context = crate::engine::native_api::ExecutionContext {
-            base_url: self.base_url.clone(),
+            base_url: self.context.lock().base_url.clone(),
+            source_url: self.source_url.clone(),
         };
         self.native_api.execute(&exec.api, &args, &context)
     }
 
+    /// Evaluate a `{{...}}` JS expression, natively when possible
+    ///
+    /// Fails when the expression reads book or chapter metadata that is not
+    /// known yet, so callers can keep the template for a later stage.
+    fn eval_template_js(&self, code: &str, vars: &HashMap<String, String>) -> Result<String> {
+        let content = vars.get("result").map(String::as_str).unwrap_or("");
+        self.run_js(code, content, vars)
+    }
+
+    /// Run a JS snippet with `content` as `result`
+    ///
+    /// Every JS rule goes through the unified analyzer, which memoizes its
+    /// decision per rule source: snippets it can compile run natively and
+    /// the rest fall back to QuickJS with `vars` bound.
+    fn run_js(&self, code: &str, content: &str, vars: &HashMap<String, String>) -> Result<String> {
+        match self.unified_analyzer.analyze(code) {
+            AnalysisResult::Native(exec) => self.execute_native_js_with(&exec, content, &self.with_context(vars)),
+            AnalysisResult::NativeChain(chain) => {
+                let Some((first, rest)) = chain.split_first() else {
+                    return Ok(content.to_string());
+                };
+                // Later steps read the previous step's output, not `vars`
+                let mut result = self.execute_native_js_with(first, content, &self.with_context(vars))?;
+                for exec in rest {
+                    result = self.execute_native_js(exec, &result)?;
+                }
+                Ok(result)
+            }
+            AnalysisResult::RequiresJs(_) => self.eval_quickjs(code, vars),
+        }
+    }
+
     // ============== Crypto API (Rust Native) ==============
 
     /// 3DES decode using Rust native implementation
@@ -218,13 +414,6 @@ impl RuleAnalyzer {
         super::crypto::CryptoProvider::aes_encode_args_base64(data, key_b64, mode, padding, iv_b64)
     }
 
-    // ============== File API (Rust Native) ==============
-
-    /// Delete a file from cache directory
-    pub fn delete_file(&self, path: &str) -> bool {
-        std::fs::remove_file(path).is_ok()
-    }
-
     // ============== Hash API (Rust Native) ==============
 
     /// Calculate digest hash (MD5, SHA1, SHA256, SHA512)
@@ -236,6 +425,7 @@ impl RuleAnalyzer {
                 &[data.to_string()],
                 &crate::engine::native_api::ExecutionContext {
                     base_url: self.base_url.clone(),
+                    ..Default::default()
                 },
             )
             .unwrap_or_default()
@@ -286,26 +476,22 @@ impl RuleAnalyzer {
 
     /// Put a variable for @put syntax
     pub fn put_variable(&self, key: &str, value: &str) {
-        self.variables
-            .borrow_mut()
-            .insert(key.to_string(), value.to_string());
-        // Also update JS cache for java.get/put access
-        self.js_executor.put_variable(key, value);
+        self.variables.lock().insert(key.to_string(), value.to_string());
     }
 
     /// Get a variable for @get syntax
     pub fn get_variable(&self, key: &str) -> Option<String> {
-        self.variables.borrow().get(key).cloned()
+        self.variables.lock().get(key).cloned()
     }
 
     /// Set result list for $1, $2 capture group references
     pub fn set_result_list(&self, list: Vec<String>) {
-        *self.result_list.borrow_mut() = list;
+        *self.result_list.lock() = list;
     }
 
     /// Replace $1, $2 etc. with values from result_list
     fn replace_capture_groups(&self, text: &str) -> String {
-        let list = self.result_list.borrow();
+        let list = self.result_list.lock();
         if list.is_empty() {
             return text.to_string();
         }
@@ -322,7 +508,7 @@ impl RuleAnalyzer {
     /// Replace @get:key and {{key}} placeholders with stored variables
     fn replace_variables(&self, text: &str) -> String {
         let mut result = text.to_string();
-        let vars = self.variables.borrow();
+        let vars = self.variables.lock();
 
         for (k, v) in vars.iter() {
             // Replace {{key}}
@@ -334,56 +520,73 @@ impl RuleAnalyzer {
         result
     }
 
-    /// Parse @put:{key:value} syntax and return the rule without @put part
-    fn parse_put_rule(&self, rule: &str) -> String {
-        // Format: rule@put:{"key":"value"} or rule@put:{key:value}
-        if let Some(pos) = rule.find("@put:") {
-            let base_rule = rule[..pos].trim();
-            let json_part = &rule[pos + 5..];
-
-            // Try to parse as JSON
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_part) {
-                if let Some(obj) = json.as_object() {
-                    for (k, v) in obj {
-                        if let Some(val) = v.as_str() {
-                            self.put_variable(k, val);
-                        } else {
-                            self.put_variable(k, &v.to_string());
-                        }
-                    }
-                }
-            }
-
-            return base_rule.to_string();
+    /// Store `@put` variables, evaluating each value as a rule against `content`
+    ///
+    /// Plain words and numbers are stored as written, so `@put:{"page":"1"}` keeps working.
+    pub fn put_rules(&self, content: &str, puts: &[(String, String)]) {
+        for (key, rule) in puts {
+            let value = if is_put_literal(rule) {
+                rule.clone()
+            } else {
+                self.get_string(content, rule).unwrap_or_else(|e| {
+                    tracing::debug!("@put rule for {} failed: {:#}", key, e);
+                    String::new()
+                })
+            };
+            self.put_variable(key, &value);
         }
-
-        rule.to_string()
     }
 
     /// Get a single string value from content using a rule
     pub fn get_string(&self, content: &str, rule: &str) -> Result<String> {
+        self.traced(content, rule, || self.eval_string(content, rule), |v| v.clone())
+    }
+
+    fn eval_string(&self, content: &str, rule: &str) -> Result<String> {
         let rule = rule.trim();
         if rule.is_empty() {
             return Ok(String::new());
         }
 
-        // Handle @put:{key:value} syntax - extract and store variables
-        let rule = self.parse_put_rule(rule);
+        // Split into steps using smarter logic that respects JS blocks and rule types.
+        // Each step stores its @put variables and substitutes @get/{{key}} only after
+        // the previous steps ran, so a later step reads what an earlier one stored.
+        let mut current_result = content.to_string();
+        for line in self.split_steps(rule, false) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            current_result = self.eval_step(&current_result, line)?;
+        }
+
+        Ok(current_result)
+    }
+
+    /// Evaluate one step of a chain rule against the previous step's result
+    fn eval_step(&self, content: &str, line: &str) -> Result<String> {
+        // Check if this line is a pure JS rule (starts with @js: or <js>)
+        let is_js = line.starts_with("@js:") || (line.starts_with("<js>") && line.contains("</js>"));
+        if is_js {
+            let line = self.replace_capture_groups(&self.replace_variables(line));
+            return self.execute_single_rule(content, &line);
+        }
+
+        // Handle @put:{key:rule} syntax - evaluate and store variables
+        let (line, puts) = split_put_rule(line);
+        self.put_rules(content, &puts);
 
         // Replace @get:key and {{key}} placeholders
-        let rule = self.replace_variables(&rule);
+        let line = self.replace_variables(&line);
 
         // Replace $1, $2 etc. capture group references
-        let rule = self.replace_capture_groups(&rule);
-        let rule = rule.as_str();
+        let line = self.replace_capture_groups(&line);
+        let line = line.as_str();
 
         // Handle || alternative rules (try each until one succeeds)
-        if rule.contains("||") && !rule.starts_with("<js>") {
-            for alt in rule.split("||") {
-                let alt = alt.trim();
-                if alt.is_empty() {
-                    continue;
-                }
+        let alternatives = split_rule_operator(line, "||");
+        if alternatives.len() > 1 {
+            for alt in &alternatives {
                 if let Ok(result) = self.get_string(content, alt) {
                     if !result.is_empty() && result != "null" {
                         return Ok(result);
@@ -393,69 +596,37 @@ impl RuleAnalyzer {
             return Ok(String::new());
         }
 
-        // Split into steps using smarter logic that respects JS blocks and rule types
-        let lines = self.split_steps(rule, false);
-        let mut current_result = content.to_string();
-        let mut first_line = true;
-
-        for line in lines {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-
-            // Check if this line is a pure JS rule (starts with @js: or <js>)
-            let is_js =
-                line.starts_with("@js:") || (line.starts_with("<js>") && line.contains("</js>"));
-
-            let line_result = if is_js {
-                self.execute_single_rule(if first_line { content } else { &current_result }, line)?
-            } else {
-                let mut vars = HashMap::new();
-                // Use original content for first line, previous result for subsequent lines
-                let effective_content = if first_line { content } else { &current_result };
-                vars.insert("result".to_string(), effective_content.to_string());
-                vars.insert("it".to_string(), effective_content.to_string());
-                vars.insert("src".to_string(), effective_content.to_string());
-
-                let processed_line = self.process_templates(line, &vars);
-                let rule_type = RuleType::detect(&processed_line, effective_content);
-
-                // Heuristic: If it's a Jsoup rule without @ or . or #, and it's after processing,
-                // it might be a literal if it contains spaces or ends with / or is purely numeric.
-                // But CSS selectors like ".chapter-content p" should NOT be treated as literals
-                let is_css_like = processed_line.starts_with('.')
-                    || processed_line.starts_with('#')
-                    || (processed_line.contains(':') && !processed_line.contains(": ")); // Colon implies pseudo-selector, but ": " implies text
-
-                let looks_like_literal = rule_type == RuleType::JsoupDefault
-                    && !is_css_like
-                    && !processed_line.contains('@')
-                    && (processed_line.is_empty()
-                        || processed_line.chars().all(|c| c.is_numeric() || c == '.')
-                        || (processed_line.contains(' ') && !is_css_like)
-                        || processed_line.starts_with("http")
-                        || processed_line.starts_with('<'));
-
-                if looks_like_literal {
-                    self.process_js_tags(&processed_line, &current_result)?
-                } else {
-                    self.execute_single_rule(
-                        if first_line { content } else { &current_result },
-                        &processed_line,
-                    )?
-                }
-            };
-
-            current_result = line_result;
-            first_line = false;
+        let mut vars = HashMap::new();
+        vars.insert("result".to_string(), content.to_string());
+        vars.insert("it".to_string(), content.to_string());
+        vars.insert("src".to_string(), content.to_string());
+
+        let processed_line = self.process_templates(line, &vars);
+        let rule_type = RuleType::detect(&processed_line, content);
+
+        // A rendered template that is not a selector (text with spaces, a URL,
+        // a number, markup) is a literal value; CSS selectors are detected as Css
+        let looks_like_literal = rule_type == RuleType::JsoupDefault
+            && !processed_line.contains('@')
+            && (processed_line.is_empty()
+                || processed_line.chars().all(|c| c.is_numeric() || c == '.')
+                || processed_line.contains(' ')
+                || processed_line.starts_with("http")
+                || processed_line.starts_with('<'));
+
+        if looks_like_literal {
+            self.process_js_tags(&processed_line, content)
+        } else {
+            self.execute_single_rule(content, &processed_line)
         }
-
-        Ok(current_result)
     }
 
     /// Get a list of strings from content using a rule
     pub fn get_list(&self, content: &str, rule: &str) -> Result<Vec<String>> {
+        self.traced(content, rule, || self.eval_list(content, rule), |v| describe_list(v))
+    }
+
+    fn eval_list(&self, content: &str, rule: &str) -> Result<Vec<String>> {
         let rule = rule.trim();
         if rule.is_empty() {
             return Ok(vec![]);
@@ -478,12 +649,9 @@ impl RuleAnalyzer {
         }
 
         // Handle || alternative rules for lists
-        if rule.contains("||") && !rule.starts_with("<js>") {
-            for alt in rule.split("||") {
-                let alt = alt.trim();
-                if alt.is_empty() {
-                    continue;
-                }
+        let alternatives = split_rule_operator(rule, "||");
+        if alternatives.len() > 1 && !rule.starts_with("<js>") {
+            for alt in &alternatives {
                 if let Ok(results) = self.get_list(content, alt) {
                     if !results.is_empty() {
                         return Ok(results);
@@ -501,13 +669,10 @@ impl RuleAnalyzer {
         };
 
         // Handle multi-rule (%%)
-        if rule.contains("%%") {
+        let sub_rules = split_rule_operator(rule, "%%");
+        if sub_rules.len() > 1 {
             let mut all_results = Vec::new();
-            for sub_rule in rule.split("%%") {
-                let sub_rule = sub_rule.trim();
-                if sub_rule.is_empty() {
-                    continue;
-                }
+            for sub_rule in &sub_rules {
                 if let Ok(mut results) = self.execute_list_rule(content, sub_rule) {
                     all_results.append(&mut results);
                 }
@@ -524,24 +689,24 @@ impl RuleAnalyzer {
         // Execute base rule
         let mut results = self.execute_list_rule(content, &base_rule)?;
 
+        // Apply JS post-processing to the whole list if present
+        if let Some(code) = js_code {
+            results = self.apply_js_to_list(content, results, &code)?;
+        }
+
         // Apply list reversal
         if should_reverse {
             results.reverse();
         }
-
-        // Apply JS post-processing if present
-        if let Some(code) = js_code {
-            results
-                .into_iter()
-                .map(|item| self.apply_js_postprocess(&item, &code))
-                .collect()
-        } else {
-            Ok(results)
-        }
+        Ok(results)
     }
 
     /// Get elements (HTML fragments) from content using a rule
     pub fn get_elements(&self, content: &str, rule: &str) -> Result<Vec<String>> {
+        self.traced(content, rule, || self.eval_elements(content, rule), |v| describe_list(v))
+    }
+
+    fn eval_elements(&self, content: &str, rule: &str) -> Result<Vec<String>> {
         let raw_rule = rule.trim();
         if raw_rule.is_empty() {
             return Ok(vec![]);
@@ -578,8 +743,7 @@ impl RuleAnalyzer {
             (content.to_string(), rule.to_string())
         };
 
-        let (base_rule, _) = self.extract_js_postprocess(&selector);
-        let mut results = self.execute_elements_rule(&base_content, &base_rule)?;
+        let mut results = self.collect_elements(&base_content, &selector)?;
 
         if is_reversed {
             results.reverse();
@@ -588,9 +752,46 @@ impl RuleAnalyzer {
         Ok(results)
     }
 
+    /// Resolve `||` alternatives and `%%`/`&&` merges for an elements selector
+    fn collect_elements(&self, content: &str, selector: &str) -> Result<Vec<String>> {
+        let alternatives = split_rule_operator(selector, "||");
+        if alternatives.len() > 1 {
+            for alt in &alternatives {
+                if let Ok(results) = self.collect_elements(content, alt) {
+                    if !results.is_empty() {
+                        return Ok(results);
+                    }
+                }
+            }
+            return Ok(Vec::new());
+        }
+
+        let parts: Vec<String> = split_rule_operator(selector, "%%")
+            .iter()
+            .flat_map(|part| split_rule_operator(part, "&&"))
+            .collect();
+        if parts.len() > 1 {
+            let mut all_results = Vec::new();
+            for part in &parts {
+                if let Ok(mut results) = self.collect_elements(content, part) {
+                    all_results.append(&mut results);
+                }
+            }
+            return Ok(all_results);
+        }
+
+        let (base_rule, js_code) = self.extract_js_postprocess(selector);
+        let results = self.execute_elements_rule(content, &base_rule)?;
+        match js_code {
+            Some(code) => self.apply_js_to_list(content, results, &code),
+            None => Ok(results),
+        }
+    }
+
     /// Execute a JavaScript rule
     pub(crate) fn eval_js(&self, code: &str, vars: &HashMap<String, String>) -> Result<String> {
-        self.js_executor.eval_with_context(code, vars)
+        let content = vars.get("result").map(String::as_str).unwrap_or("");
+        self.run_js(code, content, vars)
     }
 
     /// Process <js> tags in a rule string
@@ -630,6 +831,7 @@ impl RuleAnalyzer {
 
     /// Process {{key}} templates or {{ js_expression }} or {{ rule }} in a rule string
     pub fn process_templates(&self, rule: &str, vars: &HashMap<String, String>) -> String {
+        let vars = &self.with_context(vars);
         let mut output = rule.to_string();
 
         // 1. Simple replacements for variables
@@ -680,8 +882,12 @@ impl RuleAnalyzer {
                         let ctx = TemplateContext {
                             variables: vars.clone(),
                         };
+                        let rendered: Result<String> = parts
+                            .iter()
+                            .map(|part| self.execute_template_expr(part, &ctx))
+                            .collect();
 
-                        if let Ok(result) = self.template_executor.execute_parts(&parts, &ctx) {
+                        if let Ok(result) = rendered {
                             // If result matches the expression (literal fallback), it didn't really 'execute' in a useful way
                             // unless it was a literal. But here full_match includes {{}}.
                             // If template_executor returns a string that is not the original {{...}}, valid.
@@ -714,13 +920,8 @@ impl RuleAnalyzer {
 
     /// Handle alternative rules separated by ||
     fn get_string_with_alternatives(&self, content: &str, rule: &str) -> Result<String> {
-        for part in rule.split("||") {
-            let part = part.trim();
-            if part.is_empty() {
-                continue;
-            }
-
-            if let Ok(result) = self.get_string(content, part) {
+        for part in split_rule_operator(rule, "||") {
+            if let Ok(result) = self.get_string(content, &part) {
                 if !result.is_empty() {
                     return Ok(result);
                 }
@@ -734,13 +935,8 @@ impl RuleAnalyzer {
     fn get_string_with_concatenation(&self, content: &str, rule: &str) -> Result<String> {
         let mut results = Vec::new();
 
-        for part in rule.split("&&") {
-            let part = part.trim();
-            if part.is_empty() {
-                continue;
-            }
-
-            match self.get_string(content, part) {
+        for part in split_rule_operator(rule, "&&") {
+            match self.get_string(content, &part) {
                 Ok(result) => results.push(result),
                 Err(_) => results.push(String::new()),
             }
@@ -764,26 +960,80 @@ impl RuleAnalyzer {
         (rule.to_string(), None)
     }
 
+    /// Apply a `##regex##replacement` rule suffix, keeping `result` when it does not match
+    fn apply_regex_suffix(&self, result: String, suffix: &str) -> String {
+        // Regex suffix not matching is common and expected, don't log
+        self.parser_factory.regex().get_string(&result, suffix).unwrap_or(result)
+    }
+
+    /// Apply a rule's `##regex##replacement` suffix, then its trailing `<js>` block, to `result`
+    ///
+    /// Compiled rules evaluate their selector separately and finish with this,
+    /// matching what [`Self::get_string`] does for the whole rule string.
+    pub fn post_process(&self, result: String, regex_suffix: Option<&str>, js_post: Option<&str>) -> Result<String> {
+        let result = match regex_suffix {
+            Some(suffix) => self.apply_regex_suffix(result, &self.replace_variables(suffix)),
+            None => result,
+        };
+        match js_post {
+            Some(js_code) => self.apply_js_postprocess(&result, &self.replace_variables(js_code)),
+            None => Ok(result),
+        }
+    }
+
     /// Apply JS post-processing to a result
     fn apply_js_postprocess(&self, result: &str, js_code: &str) -> Result<String> {
         // Set current content for java.getString()
-        self.js_executor.set_current_content(result);
+        self.set_current_content(result);
 
         let mut vars = HashMap::new();
         vars.insert("result".to_string(), result.to_string());
         vars.insert("it".to_string(), result.to_string());
 
-        self.js_executor.eval_with_context(js_code, &vars)
+        self.run_js(js_code, result, &vars)
+    }
+
+    /// Run a list rule's `<js>` once over the whole list, like Legado
+    ///
+    /// `result` is a JS array of the items, so scripts can deduplicate or slice
+    /// the list. An array return value becomes the new list; anything else is
+    /// converted to a string and split on newlines. The script always runs in
+    /// QuickJS: the native analyzer treats `result` as a string.
+    fn apply_js_to_list(&self, content: &str, items: Vec<String>, code: &str) -> Result<Vec<String>> {
+        self.set_current_content(content);
+
+        let mut vars = HashMap::new();
+        let list = serde_json::to_string(&items)?;
+        vars.insert("result".to_string(), list.clone());
+        vars.insert("it".to_string(), list);
+        vars.insert("src".to_string(), content.to_string());
+
+        let output = self.eval_quickjs(code, &vars)?;
+        if output.trim_start().starts_with('[') {
+            if let Ok(values) = serde_json::from_str::<Vec<serde_json::Value>>(&output) {
+                return Ok(values
+                    .into_iter()
+                    .map(|value| match value {
+                        serde_json::Value::String(s) => s,
+                        other => other.to_string(),
+                    })
+                    .collect());
+            }
+        }
+        Ok(output
+            .split('\n')
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
     }
 
-    /// Execute a single rule (no || or &&)
     /// Execute a single rule (no || or &&)
     fn execute_single_rule(&self, content: &str, rule: &str) -> Result<String> {
         let rule = rule.trim();
-        if rule.contains("||") {
+        if split_rule_operator(rule, "||").len() > 1 {
             return self.get_string_with_alternatives(content, rule);
         }
-        if rule.contains("&&") {
+        if split_rule_operator(rule, "&&").len() > 1 {
             return self.get_string_with_concatenation(content, rule);
         }
 
@@ -795,7 +1045,9 @@ impl RuleAnalyzer {
         let (base_rule, regex_suffix) = if !base_rule_full.starts_with("##") {
             if let Some(pos) = base_rule_full.find("##") {
                 let base = base_rule_full[..pos].trim();
-                let suffix = base_rule_full[pos..].trim().trim_start_matches("##");
+                let suffix = base_rule_full[pos..].trim();
+                // Strip only the separator so a pattern starting with '#' survives
+                let suffix = suffix.strip_prefix("##").unwrap_or(suffix);
                 (base.to_string(), Some(suffix.to_string()))
             } else {
                 (base_rule_full, None)
@@ -819,30 +1071,12 @@ impl RuleAnalyzer {
                         &base_rule
                     };
 
-                    // Use unified analyzer (combines regex + AST analysis)
-                    match self.unified_analyzer.analyze_readonly(code) {
-                        AnalysisResult::Native(exec) => {
-                            // Execute natively
-                            self.execute_native_js(&exec, content)?
-                        }
-                        AnalysisResult::NativeChain(chain) => {
-                            // Execute chain natively
-                            let mut result = content.to_string();
-                            for exec in chain {
-                                result = self.execute_native_js(&exec, &result)?;
-                            }
-                            result
-                        }
-                        AnalysisResult::RequiresJs(_) => {
-                            // Fall back to QuickJS
-                            self.js_executor.set_current_content(content);
-                            let mut vars = HashMap::new();
-                            vars.insert("result".to_string(), content.to_string());
-                            vars.insert("it".to_string(), content.to_string());
-                            vars.insert("src".to_string(), content.to_string());
-                            self.js_executor.eval_with_context(code, &vars)?
-                        }
-                    }
+                    self.set_current_content(content);
+                    let mut vars = HashMap::new();
+                    vars.insert("result".to_string(), content.to_string());
+                    vars.insert("it".to_string(), content.to_string());
+                    vars.insert("src".to_string(), content.to_string());
+                    self.run_js(code, content, &vars)?
                 }
                 RuleType::Css | RuleType::JsonPath | RuleType::Regex | RuleType::JsoupDefault | RuleType::XPath => {
                     self.parser_factory.get_parser(&rule_type).get_string(content, &base_rule)?
@@ -851,16 +1085,9 @@ impl RuleAnalyzer {
         };
 
         // Apply regex suffix if present
-        let result = if let Some(suffix) = regex_suffix {
-            match self.parser_factory.regex().get_string(&initial_result, &suffix) {
-                Ok(r) => r,
-                Err(_) => {
-                    // Regex suffix not matching is common and expected, don't log
-                    initial_result
-                }
-            }
-        } else {
-            initial_result
+        let result = match regex_suffix {
+            Some(suffix) => self.apply_regex_suffix(initial_result, &suffix),
+            None => initial_result,
         };
 
         if let Some(js_code) = js_post {
@@ -880,7 +1107,7 @@ impl RuleAnalyzer {
                 let code = rule.trim_start_matches("@js:");
                 let mut vars = HashMap::new();
                 vars.insert("result".to_string(), content.to_string());
-                let result = self.js_executor.eval_with_context(code, &vars)?;
+                let result = self.run_js(code, content, &vars)?;
                 Ok(vec![result])
             }
             RuleType::Css | RuleType::JsonPath | RuleType::Regex | RuleType::JsoupDefault | RuleType::XPath => {
@@ -901,12 +1128,182 @@ impl RuleAnalyzer {
         }
     }
 
+    /// Process `{{...}}` templates in a URL that may carry `,{options}`
+    ///
+    /// Like [`Self::process_templates`], but the options JSON is evaluated
+    /// value by value and re-serialized, so results containing quotes or
+    /// backslashes are escaped instead of corrupting the JSON.
+    pub fn process_url_templates(&self, url: &str, vars: &HashMap<String, String>) -> String {
+        let Some((url_part, mut options)) = split_url_options(url) else {
+            return self.process_templates(url, vars);
+        };
+        let url_part = self.process_templates(url_part, vars);
+        for value in options.values_mut() {
+            self.process_json_templates(value, vars);
+        }
+        format!("{},{}", url_part, serde_json::Value::Object(options))
+    }
+
+    fn process_json_templates(&self, value: &mut serde_json::Value, vars: &HashMap<String, String>) {
+        match value {
+            serde_json::Value::String(s) if s.contains("{{") => {
+                *s = self.process_templates(s, vars);
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.process_json_templates(item, vars);
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for item in map.values_mut() {
+                    self.process_json_templates(item, vars);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Render the templates of one URL line
+    ///
+    /// The `,{options}` part is rendered value by value and re-serialized, so
+    /// substituted keys cannot break the options JSON. Inside a JSON body the
+    /// substituted values are additionally escaped as JSON string content.
+    fn render_url_line(&self, line: &str, ctx: &TemplateContext) -> Result<String> {
+        // A whole-line `{"url":...}` request is JSON throughout, not a URL
+        if line.starts_with('{') {
+            return self.render_template(line, ctx, TemplateEscape::None);
+        }
+        let Some((url_part, mut options)) = split_url_options(line) else {
+            return self.render_template(line, ctx, TemplateEscape::Url(""));
+        };
+
+        let charset = options
+            .get("charset")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let url = self.render_template(url_part, ctx, TemplateEscape::Url(&charset))?;
+        let json_body = options
+            .get("body")
+            .and_then(|v| v.as_str())
+            .map(|body| {
+                let content_type = options
+                    .get("headers")
+                    .and_then(|h| h.as_object())
+                    .and_then(|h| {
+                        h.iter()
+                            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+                            .and_then(|(_, v)| v.as_str())
+                    });
+                is_json_body(body, content_type)
+            })
+            .unwrap_or(false);
+
+        let body_escape = if json_body {
+            TemplateEscape::Json
+        } else {
+            TemplateEscape::Form(&charset)
+        };
+        for (key, value) in options.iter_mut() {
+            let escape = if key == "body" { body_escape } else { TemplateEscape::None };
+            self.render_json_value(value, ctx, escape)?;
+        }
+        Ok(format!("{},{}", url, serde_json::Value::Object(options)))
+    }
+
+    fn render_json_value(
+        &self,
+        value: &mut serde_json::Value,
+        ctx: &TemplateContext,
+        escape: TemplateEscape,
+    ) -> Result<()> {
+        match value {
+            serde_json::Value::String(s) if s.contains("{{") => {
+                *s = self.render_template(s, ctx, escape)?;
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.render_json_value(item, ctx, TemplateEscape::None)?;
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for item in map.values_mut() {
+                    self.render_json_value(item, ctx, TemplateEscape::None)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn render_template(&self, text: &str, ctx: &TemplateContext, escape: TemplateEscape) -> Result<String> {
+        let mut result = String::new();
+        let mut position = QueryPosition {
+            in_query: matches!(escape, TemplateEscape::Form(_)),
+            in_value: false,
+        };
+        for part in self.preprocessor.parse_template(text) {
+            let value = self
+                .execute_template_expr(&part, ctx)
+                .or_else(|e| {
+                    // Fall back to plain substitution and simple page arithmetic
+                    tracing::debug!("Template expression failed, using simple evaluation: {}", e);
+                    self.template_executor.execute_expr(&part, ctx)
+                })
+                .map_err(|e| {
+                    tracing::warn!("Template execution error: {}", e);
+                    e
+                })?;
+            match escape {
+                TemplateEscape::Json if !matches!(part, TemplateExpr::Literal(_)) => {
+                    let quoted = serde_json::Value::String(value).to_string();
+                    result.push_str(&quoted[1..quoted.len() - 1]);
+                }
+                // Only bare variables: wrapped ones like {{java.encodeURI(key)}} are encoded by the rule
+                TemplateEscape::Url(charset) | TemplateEscape::Form(charset)
+                    if matches!(part, TemplateExpr::Variable(_))
+                        && position.in_value
+                        && !is_percent_encoded(&value) =>
+                {
+                    result.push_str(&encode_with_charset(&value, charset));
+                }
+                _ => {
+                    position.advance(&value);
+                    result.push_str(&value);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Execute one template part; `{{js}}` expressions run natively or in QuickJS
+    fn execute_template_expr(&self, part: &TemplateExpr, ctx: &TemplateContext) -> Result<String> {
+        match part {
+            TemplateExpr::JsExpr(code) => self.eval_template_js(code, &ctx.variables),
+            TemplateExpr::Simple(expr) => expr.eval(ctx).or_else(|e| {
+                tracing::debug!("Native evaluation of {} failed: {}", expr.source(), e);
+                self.eval_template_js(expr.source(), &ctx.variables)
+            }),
+            TemplateExpr::Variable(name) if !ctx.variables.contains_key(name) => {
+                if self.context.lock().is_missing(name) {
+                    return Err(anyhow!("{} is not available yet", name));
+                }
+                self.template_executor.execute_expr(part, ctx)
+            }
+            _ => self.template_executor.execute_expr(part, ctx),
+        }
+    }
+
     /// Evaluate an URL rule, handling @js: if present
+    ///
+    /// JS steps see the URL variables (`key`, `page`, ...) and go through the
+    /// unified analyzer like any other rule, so string concatenations and
+    /// template literals of natively supported calls never start QuickJS.
     pub fn evaluate_url(&self, raw_url: &str, vars: &HashMap<String, String>) -> Result<String> {
         // If it starts with @js:, evaluate everything else as JS
         if raw_url.starts_with("@js:") {
             let js_code = &raw_url[4..];
-            return self.js_executor.eval_with_context(js_code, vars);
+            return self.eval_js(js_code, vars);
         }
 
         // Otherwise, process line by line using smarter split
@@ -915,7 +1312,7 @@ impl RuleAnalyzer {
 
         for (i, line) in lines.iter().enumerate() {
             let line = line.trim();
-            let mut line_vars = vars.clone();
+            let mut line_vars = self.with_context(vars);
             // result is passed from previous step
             if i > 0 {
                 line_vars.insert("result".to_string(), current_result.clone());
@@ -927,7 +1324,9 @@ impl RuleAnalyzer {
             let is_js_step =
                 line.starts_with("@js:") || (line.starts_with("<js>") && line.contains("</js>"));
 
-            let next_part = if is_js_step {
+            let next_part = if let Some(code) = url_js_code(line) {
+                self.run_js(code, &current_result, &line_vars)?
+            } else if is_js_step {
                 self.execute_single_rule(&current_result, line)?
             } else {
                 // Use Rust-native Template Executor
@@ -936,15 +1335,7 @@ impl RuleAnalyzer {
                 };
 
                 // Parse and execute template expressions
-                let parts = self.preprocessor.parse_template(line);
-
-                let processed_line = match self.template_executor.execute_parts(&parts, &ctx) {
-                    Ok(res) => res,
-                    Err(e) => {
-                        tracing::warn!("Template execution error: {}", e);
-                        return Err(e);
-                    }
-                };
+                let processed_line = self.render_url_line(line, &ctx)?;
 
                 // If it contains <js> tags that are not the whole line, process it
                 if processed_line.contains("<js>") {
@@ -1039,6 +1430,232 @@ impl RuleAnalyzer {
     }
 }
 
+/// How values substituted into a URL template are escaped
+#[derive(Debug, Clone, Copy)]
+enum TemplateEscape<'a> {
+    /// Inserted verbatim
+    None,
+    /// Inside a JSON string literal
+    Json,
+    /// A URL: variables in a query value are percent-encoded with the request charset
+    Url(&'a str),
+    /// A `k=v&k2=v2` form body: variables in a value are percent-encoded with the request charset
+    Form(&'a str),
+}
+
+/// Where the text rendered so far ends within a URL query or form body
+struct QueryPosition {
+    in_query: bool,
+    /// After the `=` of a `k=v` pair
+    in_value: bool,
+}
+
+impl QueryPosition {
+    fn advance(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '?' => self.in_query = true,
+                '&' => self.in_value = false,
+                '=' if self.in_query => self.in_value = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Code of a URL step that is entirely JS: `@js:...` or `<js>...</js>`
+fn url_js_code(line: &str) -> Option<&str> {
+    line.strip_prefix("@js:")
+        .or_else(|| line.strip_prefix("<js>")?.strip_suffix("</js>"))
+}
+
+/// Trace preview of a list result: item count plus the first item
+fn describe_list(items: &[String]) -> String {
+    match items.first() {
+        Some(first) => format!("[{} items] {}", items.len(), first),
+        None => "[0 items]".to_string(),
+    }
+}
+
+/// Mutable access to a shared component of an analyzer that has not been forked yet
+fn unshared<T>(component: &mut Arc<T>) -> &mut T {
+    Arc::get_mut(component).expect("analyzer must be configured before it is forked")
+}
+
+/// Backend of `java.getElements` / `java.getStrings`
+///
+/// The calling QuickJS context is busy evaluating the script, so each query
+/// runs in a fresh analyzer; its JS bridge is only set up if the queried rule
+/// itself uses JS.
+fn nested_rule_query(cookie_manager: Arc<CookieManager>, kv_store: Arc<KvStore>) -> RuleQuery {
+    Arc::new(move |content, rule, elements| {
+        let analyzer = RuleAnalyzer::with_cookie_manager(cookie_manager.clone(), kv_store.clone())?;
+        if elements {
+            analyzer.get_elements(content, rule)
+        } else {
+            analyzer.get_list(content, rule)
+        }
+    })
+}
+
+/// Split a rule on a two-character operator (`||`, `%%`, `&&`) at the top level.
+///
+/// Operators inside brackets, quotes, escapes or `<js>` blocks are kept, and in a
+/// run of repeated characters only the last pair is the operator, so a `##regex`
+/// ending in `|` stays intact. Empty parts are dropped.
+pub(crate) fn split_rule_operator(rule: &str, op: &str) -> Vec<String> {
+    let op_char = op.chars().next().unwrap_or('|');
+    let chars: Vec<char> = rule.chars().collect();
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if rule_has_at(&chars, i, "<js>") {
+            let rest: String = chars[i..].iter().collect();
+            let len = rest
+                .find("</js>")
+                .map(|end| rest[..end + 5].chars().count())
+                .unwrap_or(chars.len() - i);
+            current.extend(&chars[i..i + len]);
+            i += len;
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(&next) = chars.get(i + 1) {
+                    current.push(next);
+                }
+                i += 2;
+                continue;
+            }
+            '\'' | '"' if depth > 0 => match quote {
+                Some(q) if q == c => quote = None,
+                None => quote = Some(c),
+                _ => {}
+            },
+            '(' | '[' | '{' if quote.is_none() => depth += 1,
+            ')' | ']' | '}' if quote.is_none() => depth -= 1,
+            _ => {}
+        }
+
+        if c == op_char && depth <= 0 && quote.is_none() {
+            let run = chars[i..].iter().take_while(|&&ch| ch == op_char).count();
+            if run >= 2 {
+                current.extend(std::iter::repeat_n(op_char, run - 2));
+                let part = current.trim();
+                if !part.is_empty() {
+                    parts.push(part.to_string());
+                }
+                current.clear();
+                i += run;
+                continue;
+            }
+        }
+
+        current.push(c);
+        i += 1;
+    }
+
+    let part = current.trim();
+    if !part.is_empty() {
+        parts.push(part.to_string());
+    }
+    parts
+}
+
+fn rule_has_at(chars: &[char], i: usize, pat: &str) -> bool {
+    pat.chars()
+        .enumerate()
+        .all(|(k, p)| chars.get(i + k) == Some(&p))
+}
+
+/// Split `@put:{key:rule, ...}` off a rule, returning the rest of the rule and the puts
+///
+/// The object is JSON or Legado's lenient form with unquoted keys and rules.
+pub(crate) fn split_put_rule(rule: &str) -> (String, Vec<(String, String)>) {
+    let Some(start) = rule.find("@put:{") else {
+        return (rule.to_string(), Vec::new());
+    };
+    let object_start = start + "@put:".len();
+    let object_end = top_level_positions(&rule[object_start..], '}')
+        .into_iter()
+        .find(|&(_, depth)| depth == 0)
+        .map_or(rule.len(), |(i, _)| object_start + i + 1);
+    let object = &rule[object_start..object_end];
+    let rest = format!("{}{}", &rule[..start], &rule[object_end..]);
+
+    let puts = match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(object) {
+        Ok(vars) => vars
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(s) => (key, s),
+                other => (key, other.to_string()),
+            })
+            .collect(),
+        Err(_) => {
+            let body = object.strip_prefix('{').unwrap_or(object);
+            let body = body.strip_suffix('}').unwrap_or(body);
+            let mut entries = Vec::new();
+            let mut last = 0;
+            let commas = top_level_positions(body, ',').into_iter().filter(|&(_, depth)| depth == 0);
+            for end in commas.map(|(i, _)| i).chain([body.len()]) {
+                entries.push(&body[last..end]);
+                last = end + 1;
+            }
+            let unquote = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+            entries
+                .into_iter()
+                .filter_map(|entry| entry.split_once(':'))
+                .map(|(key, value)| (unquote(key), unquote(value)))
+                .filter(|(key, _)| !key.is_empty())
+                .collect()
+        }
+    };
+    (rest.trim().to_string(), puts)
+}
+
+/// Byte offsets of `target` outside quotes, with the bracket depth after each one
+fn top_level_positions(s: &str, target: char) -> Vec<(usize, i32)> {
+    let mut positions = Vec::new();
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' | '\'' => match quote {
+                Some(q) if q == c => quote = None,
+                None => quote = Some(c),
+                _ => {}
+            },
+            _ if quote.is_some() => {}
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+        if c == target && quote.is_none() {
+            positions.push((i, depth));
+        }
+    }
+    positions
+}
+
+/// A `@put` value that is a plain word or number, stored as written instead of evaluated
+fn is_put_literal(value: &str) -> bool {
+    value.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1068,6 +1685,42 @@ mod tests {
         assert_eq!(result, "Test");
     }
 
+    #[test]
+    fn test_jsonpath_filter_operators_not_split() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let json = r#"{"list": [{"t": 1, "n": "a"}, {"t": 2, "n": "b"}, {"t": 3, "n": "c"}]}"#;
+
+        let names = analyzer
+            .get_list(json, "$.list[?(@.t == 1 || @.t == 3)].n")
+            .unwrap();
+        assert_eq!(names, vec!["a", "c"]);
+        let name = analyzer
+            .get_string(json, "$.list[?(@.t > 1 && @.t < 3)].n")
+            .unwrap();
+        assert_eq!(name, "b");
+    }
+
+    #[test]
+    fn test_json_embed_chain() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let html = r#"<div id="app"></div><script>
+            var pageData = {bookName: '雪中悍刀行', chapterList: [{name: '第一章', url: '/1.html'}, {name: '第二章', url: '/2.html'},]};
+        </script>"#;
+
+        let names = analyzer
+            .get_list(html, "@jsonEmbed:pageData\n$.chapterList[*].name")
+            .unwrap();
+        assert_eq!(names, vec!["第一章", "第二章"]);
+        // The island is JSON, so the untyped step after it is JSONPath
+        let name = analyzer.get_string(html, "@jsonEmbed:pageData\nbookName").unwrap();
+        assert_eq!(name, "雪中悍刀行");
+        // A regex step leaving `{...};` behind still parses
+        let url = analyzer
+            .get_string(html, "##pageData = (\\{.*\\});##$1###\n$.chapterList[1].url")
+            .unwrap();
+        assert_eq!(url, "/2.html");
+    }
+
     #[test]
     fn test_alternative_rules() {
         let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
@@ -1102,6 +1755,123 @@ mod tests {
         assert_eq!(result, "ID: 223");
     }
 
+    #[test]
+    fn test_chained_js_runs_natively() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let text = " 张三 , 李四,王五 ";
+
+        for (rule, expected) in [
+            ("@js:result.trim().split(',').join('|')", "张三 | 李四|王五"),
+            ("@js:result.split(',')[1].trim()", "李四"),
+            ("@js:java.base64Encode(result).trim().toLowerCase()", "iow8oos4isasioadjuwbmyznjovkupqg"),
+        ] {
+            let before = crate::engine::stats::thread_counts();
+            assert_eq!(analyzer.get_string(text, rule).unwrap(), expected, "{}", rule);
+            let (native, js) = crate::engine::stats::thread_counts();
+            assert!(native >= before.0 + 3, "{} not native", rule);
+            assert_eq!(js, before.1, "{} fell back to JS", rule);
+        }
+    }
+
+    #[test]
+    fn test_ast_only_js_is_native_and_memoized() {
+        use crate::engine::stats::{thread_counts, STATS};
+
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let rule = "@css:p@text<js>java.base64Encode(`${result}-x`)</js>";
+
+        let before = thread_counts();
+        assert_eq!(analyzer.get_string("<p>abc</p>", rule).unwrap(), "YWJjLXg=");
+        let (native, js) = thread_counts();
+        assert!(native > before.0);
+        assert_eq!(js, before.1, "template literal argument fell back to JS");
+
+        let hits = STATS.snapshot().analysis_cache_hits;
+        assert_eq!(analyzer.get_string("<p>def</p>", rule).unwrap(), "ZGVmLXg=");
+        assert!(STATS.snapshot().analysis_cache_hits > hits);
+        assert_eq!(thread_counts().1, before.1);
+    }
+
+    #[test]
+    fn test_native_replace_matches_quickjs() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let cases = [
+            (r#"result.replace("a", "-")"#, "banana"),
+            (r#"result.replaceAll("a", "-")"#, "banana"),
+            (r#"result.replace(".", "$&$&")"#, "a.b.c"),
+            (r#"result.replace(/\d+/, "@")"#, "x12y345"),
+            (r#"result.replace(/\d+/g, "[$&]")"#, "x12y345"),
+            (r#"result.replaceAll(/(\w)(\d)/g, "$2$1$$")"#, "a1b2c"),
+            (r#"result.replace(/^\s+|\s+$/g, "")"#, "  第一章  "),
+            (r#"result.replace(/章/gi, "$`|$'")"#, "第一章完"),
+            (r#"result.replace(/.+/, "*")"#, "ab\r\ncd"),
+            (r#"result.replace(/\d/g, "")"#, "１２3"),
+            (r"result.replace('\u00a0', ' ')", "a\u{a0}b\u{a0}c"),
+        ];
+        for (code, input) in cases {
+            assert!(
+                !matches!(analyzer.unified_analyzer.analyze(code), AnalysisResult::RequiresJs(_)),
+                "{} was not analyzed as native",
+                code
+            );
+            let vars = HashMap::from([("result".to_string(), input.to_string())]);
+            let native = analyzer.eval_js(code, &vars).unwrap();
+            let js = analyzer.eval_quickjs(code, &vars).unwrap();
+            assert_eq!(native, js, "{} on {:?}", code, input);
+        }
+
+        // Flags and syntax without an exact Rust equivalent stay in JS
+        for code in [
+            r#"result.replace(/a/y, "")"#,
+            r#"result.replace(/^/gm, "> ")"#,
+            r#"result.replace(/a(?=b)/, "")"#,
+        ] {
+            assert!(matches!(analyzer.unified_analyzer.analyze(code), AnalysisResult::RequiresJs(_)));
+        }
+    }
+
+    #[test]
+    fn test_book_variable_native_and_js() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        analyzer.set_book(Some(BookContext {
+            name: "书".to_string(),
+            variable: Some(r#"{"token":"t-1","custom":"c"}"#.to_string()),
+            ..Default::default()
+        }));
+
+        let code = "book.getVariable('token')";
+        assert!(!matches!(
+            analyzer.unified_analyzer.analyze(code),
+            AnalysisResult::RequiresJs(_)
+        ));
+        let url = analyzer.process_templates("https://a.com/c/1?t={{book.getVariable('token')}}", &HashMap::new());
+        assert_eq!(url, "https://a.com/c/1?t=t-1");
+
+        let vars = HashMap::new();
+        let js = "[book.getVariable('token'), book.getVariable(), book.getVariable('none')].join('|')";
+        assert_eq!(analyzer.eval_quickjs(js, &vars).unwrap(), "t-1|c|");
+        assert_eq!(analyzer.eval_js("book.getVariable('none')", &vars).unwrap(), "");
+    }
+
+    #[test]
+    fn test_js_replace_font() {
+        use base64::Engine;
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let font = |name: &str| {
+            let path = format!("{}/tests/fixtures/fonts/{}", env!("CARGO_MANIFEST_DIR"), name);
+            let data = std::fs::read(path).unwrap();
+            base64::engine::general_purpose::STANDARD.encode(data)
+        };
+        let html = "<div class=\"content\">第\u{E003}\u{E001}\u{E002}章</div>";
+
+        let rule = format!(
+            "@css:.content@text<js>java.replaceFont(result, java.queryTTF('{}'), java.queryTTF('{}'))</js>",
+            font("obfuscated.ttf"),
+            font("reference.ttf")
+        );
+        assert_eq!(analyzer.get_string(html, &rule).unwrap(), "第天地人章");
+    }
+
     #[test]
     fn test_js_tags_anywhere() {
         let _analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
@@ -1121,6 +1891,35 @@ mod tests {
         let result = analyzer.evaluate_url(rule, &_vars).unwrap();
         assert_eq!(result, "http://example.com/1123");
     }
+
+    #[test]
+    fn test_js_url_rules_run_natively() {
+        use crate::engine::stats::thread_counts;
+
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let vars = HashMap::from([
+            ("key".to_string(), "斗 破".to_string()),
+            ("page".to_string(), "2".to_string()),
+        ]);
+        let rules = [
+            "@js:'https://x.com/search/' + java.encodeURI(key) + '/' + page + '/'",
+            "@js:`https://x.com/search/${java.encodeURI(key)}/${page}/`;",
+            "<js>'https://x.com/search/' + encodeURIComponent(key) + '/' + page + '/'</js>",
+            "https://x.com\n@js:result + '/search/' + java.encodeURI(key) + '/' + (page - 1) * 20",
+        ];
+        for rule in rules {
+            let before = thread_counts();
+            let url = analyzer.evaluate_url(rule, &vars).unwrap();
+            assert_eq!(thread_counts().1, before.1, "{} fell back to JS", rule);
+
+            let code = url_js_code(rule.lines().last().unwrap()).unwrap();
+            let mut js_vars = vars.clone();
+            js_vars.insert("result".to_string(), "https://x.com".to_string());
+            assert_eq!(url, analyzer.eval_quickjs(code, &js_vars).unwrap(), "{}", rule);
+        }
+        let url = analyzer.evaluate_url(rules[0], &vars).unwrap();
+        assert_eq!(url, "https://x.com/search/%E6%96%97%20%E7%A0%B4/2/");
+    }
     #[test]
     fn test_regex_suffix() {
         let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
@@ -1134,6 +1933,17 @@ mod tests {
         // ##regex##replacement
         let result = analyzer.get_string(content, "$.key##prefix_##").unwrap();
         assert_eq!(result, "123_suffix");
+
+        // Named group, first match only
+        let result = analyzer
+            .get_string(content, "$.key##(?<id>\\d+)##id=$id###")
+            .unwrap();
+        assert_eq!(result, "id=123");
+
+        // Pattern starting with a character class containing '#'
+        let content = r#"{"key": "a#b"}"#;
+        let result = analyzer.get_string(content, "$.key##[#]##-").unwrap();
+        assert_eq!(result, "a-b");
     }
 
     #[test]
@@ -1210,4 +2020,178 @@ mod tests {
         let sha256 = analyzer.digest_hex("hello", "SHA256");
         assert!(sha256.len() == 64); // SHA256 = 64 hex chars
     }
+
+    const TWO_LISTS_HTML: &str = r#"
+        <div id="list"><dl>
+            <dd><a href="/1.html">第一章</a></dd>
+            <dd><a href="/2.html">第二章</a></dd>
+        </dl></div>
+        <div id="list2"><dl>
+            <dd><a href="/3.html">第三章</a></dd>
+            <dd><a href="/4.html">第四章</a></dd>
+        </dl></div>
+    "#;
+
+    fn element_titles(analyzer: &RuleAnalyzer, elements: &[String]) -> Vec<String> {
+        elements
+            .iter()
+            .map(|e| analyzer.get_string(e, "@css:a@text").unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_elements_merge() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+
+        let elements = analyzer
+            .get_elements(TWO_LISTS_HTML, "@css:#list dd%%@css:#list2 dd")
+            .unwrap();
+        assert_eq!(
+            element_titles(&analyzer, &elements),
+            vec!["第一章", "第二章", "第三章", "第四章"]
+        );
+
+        let elements = analyzer
+            .get_elements(TWO_LISTS_HTML, "@css:#list dd&&@css:#list2 dd")
+            .unwrap();
+        assert_eq!(elements.len(), 4);
+
+        let elements = analyzer
+            .get_elements(TWO_LISTS_HTML, "-@css:#list dd%%@css:#list2 dd")
+            .unwrap();
+        assert_eq!(
+            element_titles(&analyzer, &elements),
+            vec!["第四章", "第三章", "第二章", "第一章"]
+        );
+    }
+
+    #[test]
+    fn test_get_elements_alternatives() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+
+        let elements = analyzer
+            .get_elements(TWO_LISTS_HTML, "@css:#missing dd||@css:#list2 dd")
+            .unwrap();
+        assert_eq!(element_titles(&analyzer, &elements), vec!["第三章", "第四章"]);
+    }
+
+    #[test]
+    fn test_list_js_runs_on_whole_list() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        // 12 "latest chapters" followed by the full 20-chapter list
+        let latest: String = (9..21).rev().map(|i| format!("<dd><a href=\"/{i}.html\">第{i}章</a></dd>")).collect();
+        let all: String = (1..21).map(|i| format!("<dd><a href=\"/{i}.html\">第{i}章</a></dd>")).collect();
+        let html = format!("<dl id=\"list\">{}{}</dl>", latest, all);
+
+        let elements = analyzer
+            .get_elements(&html, "@css:#list dd<js>result.slice(12)</js>")
+            .unwrap();
+        assert_eq!(elements.len(), 20);
+        assert_eq!(element_titles(&analyzer, &elements[..1]), vec!["第1章"]);
+
+        // A string result is split on newlines; put/get keep working
+        let titles = analyzer
+            .get_list(
+                &html,
+                "@css:#list dd a@text<js>java.put('n', String(result.length)); \
+                 Array.from(new Set(result)).join('\\n')</js>",
+            )
+            .unwrap();
+        assert_eq!(titles.len(), 20);
+        assert_eq!(analyzer.eval_js("java.get('n')", &HashMap::new()).unwrap(), "32");
+    }
+
+    #[test]
+    fn test_java_get_elements_and_strings() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+
+        let hrefs = analyzer
+            .get_list(
+                TWO_LISTS_HTML,
+                "@css:#list2@html<js>java.getStrings(result, '@css:dd a@href').concat(java.getStrings('@css:#list dd a@href'))</js>",
+            )
+            .unwrap();
+        assert_eq!(hrefs, vec!["/3.html", "/4.html", "/1.html", "/2.html"]);
+
+        let elements = analyzer
+            .get_elements(TWO_LISTS_HTML, "@css:body<js>java.getElements(result[0], '@css:dd').reverse()</js>")
+            .unwrap();
+        assert_eq!(element_titles(&analyzer, &elements), vec!["第四章", "第三章", "第二章", "第一章"]);
+    }
+
+    #[test]
+    fn test_ui_calls_are_no_ops() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let html = r#"<div class="name">鬥羅大陸</div>"#;
+
+        // Natively, and in JS next to other statements
+        assert_eq!(
+            analyzer
+                .get_string(html, "@css:.name<js>java.toast(result)</js>")
+                .unwrap(),
+            ""
+        );
+        let rule = "@css:.name<js>java.toast('加载中'); java.longToast(result); java.startBrowser(baseUrl, '验证'); java.t2s(result)</js>";
+        assert_eq!(analyzer.get_string(html, rule).unwrap(), "斗罗大陆");
+        assert_eq!(
+            analyzer
+                .get_string(html, "@css:.name<js>java.t2s(result)</js>")
+                .unwrap(),
+            "斗罗大陆"
+        );
+    }
+
+    #[test]
+    fn test_split_rule_operator() {
+        assert_eq!(
+            split_rule_operator("@css:.a@text##x|y|||@css:.b", "||"),
+            vec!["@css:.a@text##x|y|", "@css:.b"]
+        );
+        assert_eq!(
+            split_rule_operator("@css:a[href*='a||b']||@css:.c", "||"),
+            vec!["@css:a[href*='a||b']", "@css:.c"]
+        );
+        assert_eq!(
+            split_rule_operator("@css:.a<js>x||y</js>%%@css:.b", "%%"),
+            vec!["@css:.a<js>x||y</js>", "@css:.b"]
+        );
+        assert_eq!(split_rule_operator("@css:.a", "%%"), vec!["@css:.a"]);
+    }
+
+    #[test]
+    fn test_put_rules_feed_later_steps() {
+        let analyzer = RuleAnalyzer::new(create_test_kv()).unwrap();
+        let rule = "$.data.path@put:{token:$.token}\n<js>result + '?token=@get:token'</js>";
+
+        // The value is evaluated as a rule, and the second step reads what the first stored
+        for token in ["t0k", "n3w"] {
+            let json = format!(r#"{{"token":"{}","data":{{"path":"/toc"}}}}"#, token);
+            assert_eq!(analyzer.get_string(&json, rule).unwrap(), format!("/toc?token={}", token));
+        }
+
+        let json = r#"{"id":"7","name":"书名"}"#;
+        let rule = r#"$.name@put:{"bid":"$.id","page":"1"}"#;
+        assert_eq!(analyzer.get_string(json, rule).unwrap(), "书名");
+        assert_eq!(analyzer.get_variable("bid").as_deref(), Some("7"));
+        assert_eq!(analyzer.get_variable("page").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_split_put_rule() {
+        assert_eq!(
+            split_put_rule(r#"$.a@put:{"k":"$.b"}"#),
+            ("$.a".to_string(), vec![("k".to_string(), "$.b".to_string())])
+        );
+        assert_eq!(
+            split_put_rule("@css:a@put:{bid:@css:a@href, name:'x,y'}##\\?.*"),
+            (
+                "@css:a##\\?.*".to_string(),
+                vec![
+                    ("bid".to_string(), "@css:a@href".to_string()),
+                    ("name".to_string(), "x,y".to_string())
+                ]
+            )
+        );
+        assert_eq!(split_put_rule("$.a"), ("$.a".to_string(), vec![]));
+    }
 }
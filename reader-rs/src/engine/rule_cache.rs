@@ -0,0 +1,213 @@
+//! Rule Cache - On-disk cache of compiled book source rules
+//!
+//! `book_source::transform_source` stores the `TransformedSource` of every book source
+//! under the md5 of the source JSON. Each entry records the cache version it
+//! was written with; entries from another release or cache format are ignored
+//! and overwritten, so an upgraded binary never replays stale compiled plans.
+//!
+//! The directory is capped in size (`RULE_CACHE_MAX_MB`, default 200 MB) by
+//! evicting the least recently used entries first.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::source_transformer::TransformedSource;
+use super::utils::get_cache_dir;
+
+/// Bump whenever `TransformedSource`, `CompiledRule` or the transformer output changes
+pub const RULE_CACHE_FORMAT: u32 = 2;
+
+/// Default size cap of the cache directory in megabytes
+pub const DEFAULT_RULE_CACHE_MAX_MB: u64 = 200;
+
+/// Version marker written into every entry
+pub fn cache_version() -> String {
+    format!("{}+{}", env!("CARGO_PKG_VERSION"), RULE_CACHE_FORMAT)
+}
+
+#[derive(Serialize)]
+struct CacheEntryRef<'a> {
+    version: String,
+    rules: &'a TransformedSource,
+}
+
+#[derive(Deserialize)]
+struct CacheEntry {
+    version: String,
+    rules: TransformedSource,
+}
+
+/// Directory of compiled rule entries keyed by source hash
+pub struct RuleCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl RuleCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// Cache under `data/cache/rules`, capped by the RULE_CACHE_MAX_MB env var
+    pub fn open_default() -> Self {
+        let max_mb = std::env::var("RULE_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_RULE_CACHE_MAX_MB);
+        Self::new(get_cache_dir().join("rules"), max_mb * 1024 * 1024)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Load the entry for `key`; entries of another version count as misses
+    pub fn load(&self, key: &str) -> Option<TransformedSource> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&data).ok()?;
+        if entry.version != cache_version() {
+            return None;
+        }
+        // Refresh the mtime so eviction drops the least recently used entries
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(entry.rules)
+    }
+
+    /// Write the entry for `key`, then evict old entries beyond the size cap
+    pub fn store(&self, key: &str, rules: &TransformedSource) {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            tracing::warn!("Failed to create rule cache dir: {}", e);
+            return;
+        }
+        let entry = CacheEntryRef {
+            version: cache_version(),
+            rules,
+        };
+        let written = serde_json::to_vec(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|data| fs::write(self.path(key), data));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write rule cache entry {}: {}", key, e);
+        }
+        self.prune();
+    }
+
+    /// Remove every entry, returning how many were deleted
+    pub fn clear(&self) -> std::io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Delete the least recently used entries until the directory fits the cap
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                meta.is_file()
+                    .then(|| (meta.modified().unwrap_or(UNIX_EPOCH), meta.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::source_transformer::SourceTransformer;
+    use crate::models::BookSourceFull;
+    use std::time::Duration;
+
+    fn transformed(url: &str) -> TransformedSource {
+        let source = BookSourceFull {
+            book_source_url: url.to_string(),
+            book_source_name: url.to_string(),
+            ..Default::default()
+        };
+        SourceTransformer::new().transform(&source)
+    }
+
+    fn test_cache(name: &str, max_bytes: u64) -> RuleCache {
+        let dir = std::env::temp_dir().join(format!("reader_tests_rule_cache_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        RuleCache::new(dir, max_bytes)
+    }
+
+    #[test]
+    fn test_stale_version_is_rewritten() {
+        let cache = test_cache("stale", u64::MAX);
+        let rules = transformed("https://a.com");
+
+        let stale = serde_json::json!({ "version": "0.0.1+0", "rules": rules });
+        fs::write(cache.path("a"), stale.to_string()).unwrap();
+        assert!(cache.load("a").is_none());
+        // 旧版本直接写入的 TransformedSource 也视为未命中
+        fs::write(cache.path("a"), serde_json::to_string(&rules).unwrap()).unwrap();
+        assert!(cache.load("a").is_none());
+
+        cache.store("a", &rules);
+        let loaded = cache.load("a").unwrap();
+        assert_eq!(loaded.original.book_source_url, "https://a.com");
+        let written: serde_json::Value =
+            serde_json::from_slice(&fs::read(cache.path("a")).unwrap()).unwrap();
+        assert_eq!(written["version"], cache_version());
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache.load("a").is_none());
+    }
+
+    #[test]
+    fn test_prune_evicts_least_recently_used() {
+        let cache = test_cache("prune", u64::MAX);
+        for key in ["a", "b", "c"] {
+            cache.store(key, &transformed(&format!("https://{}.com", key)));
+        }
+        let now = SystemTime::now();
+        for (key, age) in [("a", 30), ("b", 10), ("c", 20)] {
+            let file = fs::File::options().write(true).open(cache.path(key)).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        let len = fs::metadata(cache.path("a")).unwrap().len();
+        let cache = RuleCache::new(cache.dir, len * 2 + len / 2);
+        cache.prune();
+        assert!(!cache.path("a").exists());
+        assert!(cache.path("b").exists());
+        assert!(cache.path("c").exists());
+    }
+}
@@ -0,0 +1,148 @@
+//! Rule Context - book and chapter metadata visible to rules
+//!
+//! Legado rules read `book.name`, `book.author`, `chapter.title`, `chapter.index`,
+//! `title` and `baseUrl` inside `{{}}` templates and `<js>` blocks. The engine
+//! records what it knows about the current book and chapter here: native
+//! execution resolves the flattened variables returned by [`RuleContext::variables`],
+//! and the JS executor receives the same data as `book` / `chapter` objects.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::ast::ContextKey;
+use super::book_source::BookItem;
+
+/// Flattened variable holding the book's variable JSON for `book.getVariable(key)`
+pub const BOOK_VARIABLE: &str = "book.variable";
+
+/// Book the rules are evaluated for, exposed to JS as `book`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookContext {
+    pub name: String,
+    pub author: String,
+    pub book_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intro: Option<String>,
+    /// Per-book variables as a JSON object string, read by `book.getVariable(key)`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variable: Option<String>,
+}
+
+impl From<&BookItem> for BookContext {
+    fn from(book: &BookItem) -> Self {
+        Self {
+            name: book.name.clone(),
+            author: book.author.clone(),
+            book_url: book.book_url.clone(),
+            toc_url: book.toc_url.clone(),
+            origin: None,
+            kind: book.kind.clone(),
+            intro: book.intro.clone(),
+            variable: None,
+        }
+    }
+}
+
+/// Chapter whose content is being fetched, exposed to JS as `chapter`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterContext {
+    pub title: String,
+    pub url: String,
+    /// Zero-based position in the table of contents
+    pub index: usize,
+}
+
+/// Metadata shared by every rule evaluation of an engine
+#[derive(Debug, Clone, Default)]
+pub struct RuleContext {
+    pub base_url: String,
+    pub book: Option<BookContext>,
+    pub chapter: Option<ChapterContext>,
+}
+
+impl RuleContext {
+    /// Flattened variables (`book.name`, `chapter.index`, `title`, ...) for
+    /// templates and native execution; only known values are included
+    pub fn variables(&self) -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        vars.insert(ContextKey::BaseUrl.name().to_string(), self.base_url.clone());
+        if let Some(book) = &self.book {
+            vars.insert(ContextKey::BookName.name().to_string(), book.name.clone());
+            vars.insert(ContextKey::BookAuthor.name().to_string(), book.author.clone());
+            vars.insert("book.bookUrl".to_string(), book.book_url.clone());
+            vars.insert(BOOK_VARIABLE.to_string(), book.variable.clone().unwrap_or_default());
+        }
+        if let Some(chapter) = &self.chapter {
+            vars.insert(ContextKey::ChapterTitle.name().to_string(), chapter.title.clone());
+            vars.insert(ContextKey::ChapterIndex.name().to_string(), chapter.index.to_string());
+            vars.insert("chapter.url".to_string(), chapter.url.clone());
+            vars.insert("title".to_string(), chapter.title.clone());
+        }
+        vars
+    }
+
+    /// Whether `name` refers to book or chapter metadata that is not known yet
+    ///
+    /// Such references are left for a later stage (e.g. `{{chapter.index}}`
+    /// in a chapter URL is rendered when the content is fetched).
+    pub fn is_missing(&self, name: &str) -> bool {
+        match name.split_once('.').map_or(name, |(object, _)| object) {
+            "book" => self.book.is_none(),
+            "chapter" | "title" => self.chapter.is_none(),
+            _ => false,
+        }
+    }
+
+    /// JSON for the JS `book` binding, empty when unknown
+    pub fn book_json(&self) -> String {
+        self.book
+            .as_ref()
+            .and_then(|b| serde_json::to_string(b).ok())
+            .unwrap_or_default()
+    }
+
+    /// JSON for the JS `chapter` binding, empty when unknown
+    pub fn chapter_json(&self) -> String {
+        self.chapter
+            .as_ref()
+            .and_then(|c| serde_json::to_string(c).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variables_follow_known_metadata() {
+        let mut ctx = RuleContext {
+            base_url: "https://a.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(ctx.variables().len(), 1);
+        assert!(ctx.is_missing("book.name"));
+        assert!(ctx.is_missing("title"));
+        assert!(!ctx.is_missing("baseUrl"));
+
+        ctx.chapter = Some(ChapterContext {
+            title: "第五章".to_string(),
+            url: "https://a.com/c/5".to_string(),
+            index: 4,
+        });
+        let vars = ctx.variables();
+        assert_eq!(vars["chapter.index"], "4");
+        assert_eq!(vars["title"], "第五章");
+        assert!(!ctx.is_missing("chapter.index"));
+        assert_eq!(ctx.chapter_json(), r#"{"title":"第五章","url":"https://a.com/c/5","index":4}"#);
+        assert_eq!(ctx.book_json(), "");
+    }
+}
@@ -0,0 +1,280 @@
+//! Rule validation for the source editor
+//!
+//! Checks a rule without fetching or running anything. The rule is split on
+//! `||` / `&&` / `%%`; in each part `@js:` and `<js>` code is parsed with Oxc,
+//! a `##regex##replacement` suffix is compiled, and the remaining selector goes
+//! to the `check` entry point of the parser chosen by [`RuleType::detect`].
+//! The unified analyzer reports whether the JS would run natively or needs
+//! QuickJS.
+//!
+//! With sample content, a rule that is valid and fully native is also run
+//! through [`RuleAnalyzer`] to preview the extraction result.
+
+use serde::Serialize;
+
+use super::analysis::UnifiedJsAnalyzer;
+use super::ast::JsAstParser;
+use super::js_analyzer::AnalysisResult;
+use super::parsers::{css, jsonpath, jsoup, regex, xpath, RuleSyntaxError, RuleType};
+use super::rule_analyzer::{split_rule_operator, RuleAnalyzer};
+
+/// Number of list items returned in a preview
+const PREVIEW_ITEMS: usize = 5;
+
+/// Result of validating a rule
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleValidation {
+    pub rule_type: RuleType,
+    pub valid: bool,
+    pub errors: Vec<RuleSyntaxError>,
+    /// Every JS snippet in the rule runs natively (true when there is none)
+    pub native_executable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<RulePreview>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_error: Option<String>,
+}
+
+/// Extraction result on sample content: a single string, or the first list items
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum RulePreview {
+    Text(String),
+    List(Vec<String>),
+}
+
+/// Check the syntax of `rule`; `content` only guides rule type detection
+pub fn validate_rule(rule: &str, content: &str) -> RuleValidation {
+    let mut checker = Checker {
+        content,
+        analyzer: UnifiedJsAnalyzer::new(),
+        errors: Vec::new(),
+        native: true,
+    };
+    for (part, base) in split_parts(rule) {
+        checker.check_part(&part, base);
+    }
+    RuleValidation {
+        rule_type: RuleType::detect(rule, content),
+        valid: checker.errors.is_empty(),
+        errors: checker.errors,
+        native_executable: checker.native,
+        preview: None,
+        preview_error: None,
+    }
+}
+
+impl RuleValidation {
+    /// Run a valid, fully native rule on `content` and keep the result
+    pub fn with_preview(mut self, analyzer: &RuleAnalyzer, rule: &str, content: &str) -> Self {
+        if !self.valid {
+            return self;
+        }
+        if !self.native_executable {
+            self.preview_error = Some("Rules that need QuickJS are not run in preview".to_string());
+            return self;
+        }
+        let preview = analyzer.get_list(content, rule).and_then(|items| {
+            if items.len() > 1 {
+                Ok(RulePreview::List(items.into_iter().take(PREVIEW_ITEMS).collect()))
+            } else {
+                analyzer.get_string(content, rule).map(RulePreview::Text)
+            }
+        });
+        match preview {
+            Ok(preview) => self.preview = Some(preview),
+            Err(e) => self.preview_error = Some(e.to_string()),
+        }
+        self
+    }
+}
+
+/// Split on the top-level operators, keeping each part's offset in `rule`
+fn split_parts(rule: &str) -> Vec<(String, usize)> {
+    let mut parts = vec![(rule.to_string(), 0)];
+    for op in ["||", "&&", "%%"] {
+        parts = parts
+            .into_iter()
+            .flat_map(|(text, base)| {
+                let mut cursor = 0;
+                split_rule_operator(&text, op)
+                    .into_iter()
+                    .map(|part| {
+                        let at = text[cursor..].find(&part).map_or(cursor, |pos| cursor + pos);
+                        cursor = at + part.len();
+                        (part, base + at)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    parts
+}
+
+/// Byte offset of `inner`, a subslice of `outer`
+fn offset_in(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
+}
+
+struct Checker<'a> {
+    content: &'a str,
+    analyzer: UnifiedJsAnalyzer,
+    errors: Vec<RuleSyntaxError>,
+    native: bool,
+}
+
+impl Checker<'_> {
+    /// Check one part of a rule (no `||` / `&&` / `%%`) starting at `base`
+    fn check_part(&mut self, part: &str, base: usize) {
+        // `@js:` runs the rest of the part as JS
+        if let Some(pos) = part.find("@js:") {
+            let code = &part[pos + 4..];
+            self.check_js(code, base + pos + 4);
+            self.check_selector(&part[..pos], base);
+            return;
+        }
+
+        // `<js>...</js>` blocks post-process the selector result
+        let mut selector_end = part.len();
+        let mut rest = part;
+        while let Some(start) = rest.find("<js>") {
+            let code_start = offset_in(part, rest) + start + 4;
+            selector_end = selector_end.min(code_start - 4);
+            match part[code_start..].find("</js>") {
+                Some(len) => {
+                    self.check_js(&part[code_start..code_start + len], base + code_start);
+                    rest = &part[code_start + len + 5..];
+                }
+                None => {
+                    self.errors.push(RuleSyntaxError::at(base + code_start - 4, "Unclosed <js> block"));
+                    break;
+                }
+            }
+        }
+        self.check_selector(&part[..selector_end], base);
+    }
+
+    /// Check a selector with an optional `##regex` suffix
+    fn check_selector(&mut self, selector: &str, base: usize) {
+        let trimmed = selector.trim();
+        if trimmed.is_empty() {
+            return;
+        }
+        let base = base + offset_in(selector, trimmed);
+
+        let (selector, regex_suffix) = match trimmed.find("##") {
+            Some(pos) if pos > 0 => (&trimmed[..pos], Some(pos)),
+            _ => (trimmed, None),
+        };
+        if let Some(pos) = regex_suffix {
+            if let Err(e) = regex::check(&trimmed[pos..]) {
+                self.errors.push(e.shifted(base + pos));
+            }
+        }
+
+        let selector = selector.trim_end();
+        // Templates and variables are only known at run time
+        if selector.is_empty()
+            || selector.contains("{{")
+            || selector.contains("@get:")
+            || selector.contains("@put:")
+        {
+            return;
+        }
+        let result = match RuleType::detect(selector, self.content) {
+            RuleType::Css => css::check(selector),
+            RuleType::JsonPath => jsonpath::check(selector),
+            RuleType::XPath => xpath::check(selector),
+            RuleType::Regex => regex::check(selector),
+            RuleType::JsoupDefault => jsoup::check(selector),
+            // `@js:` and `<js>` are split off by check_part
+            RuleType::JavaScript => Ok(()),
+        };
+        if let Err(e) = result {
+            self.errors.push(e.shifted(base));
+        }
+    }
+
+    /// Parse JS and record whether the analyzer can run it natively
+    fn check_js(&mut self, code: &str, base: usize) {
+        let errors = JsAstParser::syntax_errors(code);
+        if !errors.is_empty() {
+            self.native = false;
+            self.errors.extend(errors.into_iter().map(|e| e.shifted(base)));
+            return;
+        }
+        if matches!(self.analyzer.analyze(code.trim()), AnalysisResult::RequiresJs(_)) {
+            self.native = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::kv::KvStore;
+    use crate::storage::FileStorage;
+    use std::sync::Arc;
+
+    const HTML: &str = r#"<ul class="list"><li><a href="/b/1">一</a></li><li><a href="/b/2">二</a></li></ul>"#;
+
+    #[test]
+    fn test_bad_regex_is_located() {
+        let rule = "class.title@text##(第\\d+章##";
+        let result = validate_rule(rule, HTML);
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 1);
+        // The unclosed group starts right after the `##` separator
+        assert_eq!(result.errors[0].offset, rule.find('('));
+
+        let result = validate_rule("##[a-##", "");
+        assert_eq!(result.rule_type, RuleType::Regex);
+        assert!(!result.valid);
+    }
+
+    #[test]
+    fn test_bad_jsonpath() {
+        let result = validate_rule("$.data[?(@.id ==]", r#"{"data": []}"#);
+        assert_eq!(result.rule_type, RuleType::JsonPath);
+        assert!(!result.valid);
+        assert!(result.errors[0].message.contains("JSONPath"));
+
+        assert!(validate_rule("$.data[*].name || $.list[-1].name", "{}").valid);
+    }
+
+    #[test]
+    fn test_valid_css_with_preview() {
+        let rule = "ul.list > li a@href";
+        let result = validate_rule(rule, HTML);
+        assert_eq!(result.rule_type, RuleType::Css);
+        assert!(result.valid && result.native_executable);
+
+        let kv = Arc::new(KvStore::new(FileStorage::new("/tmp/reader_tests_rv"), "test_kv_rv.json"));
+        let analyzer = RuleAnalyzer::new(kv).unwrap();
+        let result = result.with_preview(&analyzer, rule, HTML);
+        assert_eq!(
+            result.preview,
+            Some(RulePreview::List(vec!["/b/1".to_string(), "/b/2".to_string()]))
+        );
+
+        let result = validate_rule("ul.list[ > li", HTML);
+        assert!(!result.valid);
+    }
+
+    #[test]
+    fn test_js_native_flag() {
+        let result = validate_rule("a@href@js:result.replace('/b/', '')", HTML);
+        assert!(result.valid && result.native_executable);
+
+        let rule = "@js:var list = JSON.parse(result); list.map(function (b) { return b.name; }).join('\\n')";
+        let result = validate_rule(rule, "{}");
+        assert_eq!(result.rule_type, RuleType::JavaScript);
+        assert!(result.valid);
+        assert!(!result.native_executable);
+
+        let result = validate_rule("h1@text<js>result.trim(</js>", HTML);
+        assert!(!result.valid);
+        assert!(result.errors[0].offset.is_some_and(|offset| offset >= "h1@text<js>".len()));
+    }
+}
@@ -1,11 +1,30 @@
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Query, QueryParser, RegexQuery, TermQuery};
 use tantivy::schema::*;
-use tantivy::{Index, IndexReader, IndexWriter, TantivyDocument, Term};
+use tantivy::{DocAddress, Index, IndexReader, IndexWriter, TantivyDocument, Term};
+
+use super::pinyin::pinyin_keys;
+use crate::models::Book;
+
+/// 拼音纠错只对不短于此长度的输入生效，过短的输入容易误匹配
+const FUZZY_MIN_LEN: usize = 4;
+
+/// 匹配程度，结果按 精确 > 前缀 > 模糊 排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    /// 书名、作者或其拼音、拼音首字母与输入完全相同
+    Exact,
+    /// 书名、作者或其拼音、拼音首字母以输入开头
+    Prefix,
+    /// 分词命中、书名作者包含输入或拼音相差一个字母
+    Fuzzy,
+}
 
 /// 搜索结果项
 #[derive(Debug, serde::Serialize)]
@@ -15,6 +34,35 @@ pub struct SearchResult {
     pub author: String,
     pub intro: String,
     pub score: f32,
+    pub match_kind: MatchKind,
+}
+
+/// 待索引的书籍
+#[derive(Debug, Clone, Default)]
+pub struct IndexedBook {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    pub intro: String,
+}
+
+impl IndexedBook {
+    /// 索引内容摘要，内容未变的书籍不必重写
+    fn digest(&self) -> String {
+        let content = format!("{}\u{1f}{}\u{1f}{}", self.title, self.author, self.intro);
+        format!("{:x}", md5::compute(content))
+    }
+}
+
+impl From<&Book> for IndexedBook {
+    fn from(book: &Book) -> Self {
+        Self {
+            id: book.book_url.clone(),
+            title: book.name.clone(),
+            author: book.author.clone(),
+            intro: book.intro.clone().unwrap_or_default(),
+        }
+    }
 }
 
 /// 搜索管理器
@@ -32,10 +80,33 @@ struct SearchFields {
     title: Field,
     author: Field,
     intro: Field,
+    /// 书名与作者 (小写、去空白)，用于精确、前缀与包含匹配
+    name_key: Field,
+    /// 书名与作者的全拼
+    pinyin: Field,
+    /// 书名与作者的拼音首字母
+    initials: Field,
+    digest: Field,
+}
+
+impl SearchFields {
+    fn key_fields(&self) -> [Field; 3] {
+        [self.name_key, self.pinyin, self.initials]
+    }
+}
+
+/// 匹配用的关键字：小写并去掉空白
+fn normalize_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
 }
 
 impl SearchEngine {
     /// 初始化搜索引擎
+    ///
+    /// 已有索引的字段与当前版本不一致时删除重建，索引内容由书架重新同步。
     pub fn new(storage_dir: &str) -> Result<Self> {
         let index_path = Path::new(storage_dir).join("index");
         if !index_path.exists() {
@@ -45,14 +116,10 @@ impl SearchEngine {
         // 定义 Schema
         let mut schema_builder = Schema::builder();
 
-        // 注册结巴分词器配置
-        // 注意：Tantivy 是按字段配置分词器的，我们需要先注册分词器
-        // 这里的注册逻辑稍后在 index 创建后进行
-
         // 定义字段
         // book_id: 存储，不索引（用于精确查找 update）或索引（用于删除）
         let book_id = schema_builder.add_text_field("book_id", STRING | STORED);
-        
+
         // 使用中文分词的文本字段
         let text_options = TextOptions::default()
             .set_indexing_options(
@@ -66,24 +133,35 @@ impl SearchEngine {
         let author = schema_builder.add_text_field("author", text_options.clone());
         let intro = schema_builder.add_text_field("intro", text_options);
 
+        // 不分词的匹配字段，每本书各有书名与作者两个值
+        let name_key = schema_builder.add_text_field("name_key", STRING);
+        let pinyin = schema_builder.add_text_field("pinyin", STRING);
+        let initials = schema_builder.add_text_field("initials", STRING);
+        let digest = schema_builder.add_text_field("digest", STRING | STORED);
+
         let schema = schema_builder.build();
 
-        // 打开或创建索引
         // 打开或创建索引
         let meta_path = index_path.join("meta.json");
-        let index = if meta_path.exists() {
-            Index::open_in_dir(&index_path)?
+        let existing = if meta_path.exists() {
+            Some(Index::open_in_dir(&index_path)?)
         } else {
-            Index::create_in_dir(&index_path, schema.clone())?
+            None
+        };
+        let index = match existing {
+            Some(index) if index.schema() == schema => index,
+            outdated => {
+                if outdated.is_some() {
+                    tracing::info!("Search index schema changed, recreating {:?}", index_path);
+                    fs::remove_dir_all(&index_path)?;
+                    fs::create_dir_all(&index_path)?;
+                }
+                Index::create_in_dir(&index_path, schema.clone())?
+            }
         };
-
-        // 注册结巴分词器
-
 
         // 创建 Reader
-        let reader = index
-            .reader_builder()
-            .try_into()?;
+        let reader = index.reader_builder().try_into()?;
 
         // 创建 Writer (分配 50MB 缓冲区)
         let writer = index.writer(50_000_000)?;
@@ -97,104 +175,304 @@ impl SearchEngine {
                 title,
                 author,
                 intro,
+                name_key,
+                pinyin,
+                initials,
+                digest,
             }),
         })
     }
 
+    /// 生成书籍的索引文档
+    fn document(&self, book: &IndexedBook) -> TantivyDocument {
+        let fields = &self.fields;
+        let mut doc = TantivyDocument::default();
+        doc.add_text(fields.book_id, &book.id);
+        doc.add_text(fields.title, &book.title);
+        doc.add_text(fields.author, &book.author);
+        doc.add_text(fields.intro, &book.intro);
+        for text in [&book.title, &book.author] {
+            let key = normalize_key(text);
+            if key.is_empty() {
+                continue;
+            }
+            let (full, initials) = pinyin_keys(text);
+            doc.add_text(fields.name_key, &key);
+            if !full.is_empty() {
+                doc.add_text(fields.pinyin, &full);
+                doc.add_text(fields.initials, &initials);
+            }
+        }
+        doc.add_text(fields.digest, book.digest());
+        doc
+    }
+
     /// 添加或更新书籍索引
     pub fn index_book(&self, id: &str, title: &str, author: &str, intro: &str) -> Result<()> {
+        let book = IndexedBook {
+            id: id.to_string(),
+            title: title.to_string(),
+            author: author.to_string(),
+            intro: intro.to_string(),
+        };
         let mut writer = self.writer.lock().unwrap();
-        
+
         // 先删除旧的（如果存在）
-        let term = Term::from_field_text(self.fields.book_id, id);
-        writer.delete_term(term);
+        writer.delete_term(Term::from_field_text(self.fields.book_id, id));
+        writer.add_document(self.document(&book))?;
+        writer.commit()?;
+        self.reader.reload()?;
 
-        // 添加新的
-        let mut doc = TantivyDocument::default();
-        doc.add_text(self.fields.book_id, id);
-        doc.add_text(self.fields.title, title);
-        doc.add_text(self.fields.author, author);
-        doc.add_text(self.fields.intro, intro);
+        Ok(())
+    }
 
-        writer.add_document(doc)?;
+    /// 按书架增量同步索引：删除已不在书架上的书籍，只重写新增或内容变化的书籍
+    ///
+    /// 全部修改只提交一次，返回新增、更新与删除的文档数。
+    pub fn sync_books(&self, books: &[IndexedBook]) -> Result<usize> {
+        let indexed = self.indexed_digests()?;
+        let wanted: HashSet<&str> = books.iter().map(|b| b.id.as_str()).collect();
+
+        let mut writer = self.writer.lock().unwrap();
+        let mut changed = 0;
+        for id in indexed.keys().filter(|id| !wanted.contains(id.as_str())) {
+            writer.delete_term(Term::from_field_text(self.fields.book_id, id));
+            changed += 1;
+        }
+        for book in books {
+            if indexed.get(&book.id) == Some(&book.digest()) {
+                continue;
+            }
+            writer.delete_term(Term::from_field_text(self.fields.book_id, &book.id));
+            writer.add_document(self.document(book))?;
+            changed += 1;
+        }
+        if changed > 0 {
+            writer.commit()?;
+            self.reader.reload()?;
+        }
+        Ok(changed)
+    }
+
+    /// 清空并按书架重建索引，返回索引的书籍数
+    pub fn rebuild(&self, books: &[IndexedBook]) -> Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.delete_all_documents()?;
+        for book in books {
+            writer.add_document(self.document(book))?;
+        }
         writer.commit()?;
-        
-        Ok(())
+        self.reader.reload()?;
+        Ok(books.len())
+    }
+
+    /// 已索引的书籍及其内容摘要
+    fn indexed_digests(&self) -> Result<HashMap<String, String>> {
+        let searcher = self.reader.searcher();
+        let mut digests = HashMap::new();
+        for address in searcher.search(&AllQuery, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            digests.insert(text(self.fields.book_id), text(self.fields.digest));
+        }
+        Ok(digests)
     }
 
     /// 删除书籍索引
-    pub fn delete_book(&self, id: &str) -> Result<()> {
+    pub fn remove_book(&self, id: &str) -> Result<()> {
+        self.remove_books(&[id])
+    }
+
+    /// 批量删除书籍索引，只提交一次
+    pub fn remove_books(&self, ids: &[&str]) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
-        let term = Term::from_field_text(self.fields.book_id, id);
-        writer.delete_term(term);
+        for id in ids {
+            writer.delete_term(Term::from_field_text(self.fields.book_id, id));
+        }
         writer.commit()?;
+        // 立即生效，避免已删除的书籍仍出现在搜索结果中
+        self.reader.reload()?;
         Ok(())
     }
 
-    /// 搜索
+    /// 各匹配程度的查询，按 精确、前缀、模糊 的顺序
+    fn tiered_queries(&self, query_str: &str, key: &str) -> Result<Vec<(MatchKind, Box<dyn Query>)>> {
+        let fields = &self.fields;
+        let terms = || {
+            fields
+                .key_fields()
+                .into_iter()
+                .map(|field| Term::from_field_text(field, key))
+        };
+
+        let exact: Vec<Box<dyn Query>> = terms()
+            .map(|term| Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>)
+            .collect();
+        let prefix: Vec<Box<dyn Query>> = terms()
+            .map(|term| Box::new(FuzzyTermQuery::new_prefix(term, 0, true)) as Box<dyn Query>)
+            .collect();
+
+        // 分词全文检索，设置字段权重
+        let mut query_parser = QueryParser::for_index(&self.index, vec![fields.title, fields.author, fields.intro]);
+        query_parser.set_field_boost(fields.title, 10.0);
+        query_parser.set_field_boost(fields.author, 5.0);
+        query_parser.set_field_boost(fields.intro, 1.0);
+        let (parsed, _) = query_parser.parse_query_lenient(query_str);
+        let mut fuzzy = vec![parsed];
+        // 书名、作者中间的片段，如 "破苍"
+        let infix = format!(".*{}.*", regex::escape(key));
+        fuzzy.push(Box::new(RegexQuery::from_pattern(&infix, fields.name_key)?));
+        // 拼音输错一个字母，如 "dopuo"
+        if key.len() >= FUZZY_MIN_LEN && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            for field in [fields.pinyin, fields.initials] {
+                let term = Term::from_field_text(field, key);
+                fuzzy.push(Box::new(FuzzyTermQuery::new_prefix(term, 1, true)));
+            }
+        }
+
+        Ok(vec![
+            (MatchKind::Exact, Box::new(BooleanQuery::union(exact))),
+            (MatchKind::Prefix, Box::new(BooleanQuery::union(prefix))),
+            (MatchKind::Fuzzy, Box::new(BooleanQuery::union(fuzzy))),
+        ])
+    }
+
+    /// 搜索书名、作者与简介
+    ///
+    /// 书名与作者支持前缀、拼音全拼与首字母 (如 "dpcq"、"doupo" 可搜到 "斗破苍穹")；
+    /// 结果按匹配程度排序，同一程度内按相关度排序。
     pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let key = normalize_key(query_str);
+        if key.is_empty() {
+            return Ok(Vec::new());
+        }
         let searcher = self.reader.searcher();
-        
-        // 定义查询解析器，设置权重
-        let mut query_parser = QueryParser::for_index(
-            &self.index,
-            vec![self.fields.title, self.fields.author, self.fields.intro],
-        );
-        
-        // 设置字段权重
-        query_parser.set_field_boost(self.fields.title, 10.0);
-        query_parser.set_field_boost(self.fields.author, 5.0);
-        query_parser.set_field_boost(self.fields.intro, 1.0);
-
-        let query = query_parser.parse_query(query_str)?;
-        
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+
+        let mut hits: Vec<(MatchKind, f32, DocAddress)> = Vec::new();
+        let mut seen = HashSet::new();
+        for (kind, query) in self.tiered_queries(query_str, &key)? {
+            if hits.len() >= limit {
+                break;
+            }
+            for (score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+                if seen.insert(address) {
+                    hits.push((kind, score, address));
+                }
+            }
+        }
+        hits.truncate(limit);
 
         let mut results = Vec::new();
-        for (score, doc_address) in top_docs {
+        for (match_kind, score, doc_address) in hits {
             let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
-            
-            let book_id = retrieved_doc
-                .get_first(self.fields.book_id)
-                .and_then(|v| v.as_str())
-                .unwrap_or_default()
-                .to_string();
-                
-            let title = retrieved_doc
-                .get_first(self.fields.title)
-                .and_then(|v| v.as_str())
-                .unwrap_or_default()
-                .to_string();
-
-            let author = retrieved_doc
-                .get_first(self.fields.author)
-                .and_then(|v| v.as_str())
-                .unwrap_or_default()
-                .to_string();
-
-            let intro = retrieved_doc
-                .get_first(self.fields.intro)
-                .and_then(|v| v.as_str())
-                .unwrap_or_default()
-                .to_string();
+            let text = |field| {
+                retrieved_doc
+                    .get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
 
             results.push(SearchResult {
-                book_id,
-                title,
-                author,
-                intro,
+                book_id: text(self.fields.book_id),
+                title: text(self.fields.title),
+                author: text(self.fields.author),
+                intro: text(self.fields.intro),
                 score,
+                match_kind,
             });
         }
 
         Ok(results)
     }
-    
+
     /// 重建索引（清空并重新添加）
     pub fn clear_index(&self) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
         writer.delete_all_documents()?;
         writer.commit()?;
+        self.reader.reload()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_engine(name: &str) -> SearchEngine {
+        let dir = std::env::temp_dir().join(format!("reader_tests_search_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        SearchEngine::new(dir.to_str().unwrap()).unwrap()
+    }
+
+    fn book(id: &str, title: &str, author: &str) -> IndexedBook {
+        IndexedBook {
+            id: id.to_string(),
+            title: title.to_string(),
+            author: author.to_string(),
+            intro: String::new(),
+        }
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.book_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_pinyin_and_prefix() {
+        let engine = create_engine("pinyin");
+        engine.index_book("doupo", "斗破苍穹", "天蚕土豆", "").unwrap();
+        engine.index_book("dune", "Dune", "Frank Herbert", "").unwrap();
+
+        for key in ["dpcq", "斗破", "doupo", "DouPo CangQiong", "破苍", "tctd"] {
+            let results = engine.search(key, 10).unwrap();
+            assert_eq!(ids(&results), vec!["doupo"], "{}", key);
+        }
+        assert_eq!(engine.search("dpcq", 10).unwrap()[0].match_kind, MatchKind::Exact);
+        assert_eq!(engine.search("斗破", 10).unwrap()[0].match_kind, MatchKind::Prefix);
+        assert_eq!(engine.search("dopuo", 10).unwrap()[0].match_kind, MatchKind::Fuzzy);
+        assert_eq!(ids(&engine.search("herbert", 10).unwrap()), vec!["dune"]);
+        assert!(engine.search("  ", 10).unwrap().is_empty());
+
+        engine.remove_book("doupo").unwrap();
+        assert!(engine.search("dpcq", 10).unwrap().is_empty());
+        assert!(engine.search("斗破", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_ranks_exact_before_prefix() {
+        let engine = create_engine("rank");
+        engine.index_book("sequel", "斗破苍穹之大主宰", "", "").unwrap();
+        engine.index_book("original", "斗破苍穹", "", "").unwrap();
+
+        let results = engine.search("斗破苍穹", 10).unwrap();
+        assert_eq!(ids(&results), vec!["original", "sequel"]);
+        assert_eq!(results[1].match_kind, MatchKind::Prefix);
+    }
+
+    #[test]
+    fn test_sync_books_is_incremental() {
+        let engine = create_engine("sync");
+        let mut books = vec![book("a", "斗破苍穹", "天蚕土豆"), book("b", "凡人修仙传", "忘语")];
+        assert_eq!(engine.sync_books(&books).unwrap(), 2);
+        assert_eq!(engine.sync_books(&books).unwrap(), 0);
+
+        // 改名的书籍重写，移出书架的书籍删除
+        books[0].title = "武动乾坤".to_string();
+        books.pop();
+        assert_eq!(engine.sync_books(&books).unwrap(), 2);
+        assert_eq!(ids(&engine.search("wdqk", 10).unwrap()), vec!["a"]);
+        assert!(engine.search("dpcq", 10).unwrap().is_empty());
+        assert!(engine.search("fanren", 10).unwrap().is_empty());
+
+        assert_eq!(engine.rebuild(&[book("c", "遮天", "辰东")]).unwrap(), 1);
+        assert!(engine.search("wdqk", 10).unwrap().is_empty());
+        assert_eq!(ids(&engine.search("zhetian", 10).unwrap()), vec!["c"]);
+    }
+}
@@ -0,0 +1,352 @@
+//! Source Inspector - Readable view of what a book source compiled into
+//!
+//! Walks a `TransformedSource` field by field and pairs each rule's original
+//! text with its compiled form. JavaScript rules go through the same analysis
+//! the engine runs at execution time, so the report says whether they end up
+//! native or in QuickJS, and why.
+
+use serde::Serialize;
+
+use super::analysis::{AnalysisStrategy, UnifiedJsAnalyzer};
+use super::js_analyzer::AnalysisResult;
+use super::source_transformer::{CompiledRule, JoinType, TransformedSource, UrlPart};
+
+/// Inspection of a whole source
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceInspection {
+    pub book_source_url: String,
+    pub book_source_name: String,
+    pub complexity_score: u8,
+    pub requires_js: bool,
+    pub js_required_apis: Vec<String>,
+    pub search_url: Option<UrlInspection>,
+    /// Non-empty rule fields, in source order
+    pub rules: Vec<RuleInspection>,
+    pub counts: RuleCounts,
+}
+
+/// Compiled search URL template
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlInspection {
+    pub original: String,
+    pub requires_js: bool,
+    /// Template parts, empty when the URL needs JS
+    pub parts: Vec<String>,
+}
+
+/// One rule field
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleInspection {
+    /// Field path such as `ruleSearch.bookList`
+    pub field: String,
+    /// Rule text as written in the source
+    pub rule: String,
+    pub compiled: CompiledInspection,
+}
+
+/// Compiled form of a rule, tagged by `kind`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum CompiledInspection {
+    Empty,
+    Selector {
+        rule_type: String,
+        selector: String,
+    },
+    /// Native calls, one per step
+    Native {
+        operations: Vec<String>,
+    },
+    /// JavaScript kept by the transformer, with the runtime analysis of it
+    JavaScript {
+        code: String,
+        /// `native` when the runtime analyzer compiles it, otherwise `quickjs`
+        runtime: String,
+        /// `regex`, `ast` or `js`
+        strategy: String,
+        /// Why QuickJS is needed
+        reason: Option<String>,
+        /// Native steps the analyzer found
+        operations: Vec<String>,
+    },
+    Composite {
+        join: String,
+        parts: Vec<CompiledInspection>,
+    },
+    Chain {
+        put: Vec<(String, String)>,
+        base: Box<CompiledInspection>,
+        regex_suffix: Option<String>,
+        js_post: Option<Box<CompiledInspection>>,
+    },
+}
+
+/// How many leaf rules run each way
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleCounts {
+    /// Non-empty rule fields
+    pub rules: usize,
+    pub selector: usize,
+    /// Native calls, including JavaScript the runtime analyzer compiles
+    pub native: usize,
+    /// JavaScript that runs in QuickJS
+    pub javascript: usize,
+}
+
+impl RuleCounts {
+    fn add(&mut self, compiled: &CompiledInspection) {
+        match compiled {
+            CompiledInspection::Empty => {}
+            CompiledInspection::Selector { .. } => self.selector += 1,
+            CompiledInspection::Native { .. } => self.native += 1,
+            CompiledInspection::JavaScript { runtime, .. } => {
+                if runtime == "native" {
+                    self.native += 1;
+                } else {
+                    self.javascript += 1;
+                }
+            }
+            CompiledInspection::Composite { parts, .. } => parts.iter().for_each(|part| self.add(part)),
+            CompiledInspection::Chain { base, js_post, .. } => {
+                self.add(base);
+                if let Some(js_post) = js_post {
+                    self.add(js_post);
+                }
+            }
+        }
+    }
+}
+
+/// Build the inspection of a transformed source
+pub fn inspect(transformed: &TransformedSource) -> SourceInspection {
+    let analyzer = UnifiedJsAnalyzer::new();
+    let original = &transformed.original;
+    let search = original.rule_search.clone().unwrap_or_default();
+    let info = original.rule_book_info.clone().unwrap_or_default();
+    let toc = original.rule_toc.clone().unwrap_or_default();
+    let content = original.rule_content.clone().unwrap_or_default();
+    let s = &transformed.search_rules;
+    let b = &transformed.book_info_rules;
+    let t = &transformed.toc_rules;
+    let c = &transformed.content_rules;
+
+    let fields: [(&str, &str, &CompiledRule); 26] = [
+        ("ruleSearch.bookList", &search.book_list, &s.book_list),
+        ("ruleSearch.name", &search.name, &s.name),
+        ("ruleSearch.author", &search.author, &s.author),
+        ("ruleSearch.kind", &search.kind, &s.kind),
+        ("ruleSearch.lastChapter", &search.last_chapter, &s.last_chapter),
+        ("ruleSearch.intro", &search.intro, &s.intro),
+        ("ruleSearch.coverUrl", &search.cover_url, &s.cover_url),
+        ("ruleSearch.bookUrl", &search.book_url, &s.book_url),
+        ("ruleSearch.wordCount", &search.word_count, &s.word_count),
+        ("ruleSearch.updateTime", &search.update_time, &s.update_time),
+        ("ruleBookInfo.init", &info.init, &b.init),
+        ("ruleBookInfo.name", &info.name, &b.name),
+        ("ruleBookInfo.author", &info.author, &b.author),
+        ("ruleBookInfo.kind", &info.kind, &b.kind),
+        ("ruleBookInfo.intro", &info.intro, &b.intro),
+        ("ruleBookInfo.coverUrl", &info.cover_url, &b.cover_url),
+        ("ruleBookInfo.tocUrl", &info.toc_url, &b.toc_url),
+        ("ruleBookInfo.lastChapter", &info.last_chapter, &b.last_chapter),
+        ("ruleBookInfo.wordCount", &info.word_count, &b.word_count),
+        ("ruleBookInfo.updateTime", &info.update_time, &b.update_time),
+        ("ruleToc.chapterList", &toc.chapter_list, &t.chapter_list),
+        ("ruleToc.chapterName", &toc.chapter_name, &t.chapter_name),
+        ("ruleToc.chapterUrl", &toc.chapter_url, &t.chapter_url),
+        ("ruleToc.nextTocUrl", &toc.next_toc_url, &t.next_toc_url),
+        ("ruleContent.content", &content.content, &c.content),
+        (
+            "ruleContent.nextContentUrl",
+            &content.next_content_url,
+            &c.next_content_url,
+        ),
+    ];
+
+    let mut counts = RuleCounts::default();
+    let rules: Vec<RuleInspection> = fields
+        .into_iter()
+        .filter(|(_, _, compiled)| !matches!(compiled, CompiledRule::Empty))
+        .map(|(field, rule, compiled)| {
+            let compiled = inspect_rule(&analyzer, compiled);
+            counts.add(&compiled);
+            RuleInspection {
+                field: field.to_string(),
+                rule: rule.to_string(),
+                compiled,
+            }
+        })
+        .collect();
+    counts.rules = rules.len();
+
+    SourceInspection {
+        book_source_url: original.book_source_url.clone(),
+        book_source_name: original.book_source_name.clone(),
+        complexity_score: transformed.complexity_score,
+        requires_js: transformed.requires_js,
+        js_required_apis: transformed.js_required_apis.clone(),
+        search_url: transformed.search_url.as_ref().map(|url| UrlInspection {
+            original: url.original.clone(),
+            requires_js: url.requires_js,
+            parts: url.parts.iter().flatten().map(url_part).collect(),
+        }),
+        rules,
+        counts,
+    }
+}
+
+fn inspect_rule(analyzer: &UnifiedJsAnalyzer, rule: &CompiledRule) -> CompiledInspection {
+    match rule {
+        CompiledRule::Empty => CompiledInspection::Empty,
+        CompiledRule::Selector { rule_type, selector } => CompiledInspection::Selector {
+            rule_type: format!("{:?}", rule_type),
+            selector: selector.clone(),
+        },
+        CompiledRule::Native(exec) => CompiledInspection::Native {
+            operations: vec![exec.to_string()],
+        },
+        CompiledRule::NativeChain(chain) => CompiledInspection::Native {
+            operations: chain.iter().map(ToString::to_string).collect(),
+        },
+        CompiledRule::JavaScript(code) => inspect_js(analyzer, code),
+        CompiledRule::Composite { parts, join_type } => CompiledInspection::Composite {
+            join: match join_type {
+                JoinType::FirstMatch => "firstMatch".to_string(),
+                JoinType::Concatenate => "concatenate".to_string(),
+            },
+            parts: parts.iter().map(|part| inspect_rule(analyzer, part)).collect(),
+        },
+        CompiledRule::Chain {
+            put,
+            base,
+            regex_suffix,
+            js_post,
+        } => CompiledInspection::Chain {
+            put: put.clone(),
+            base: Box::new(inspect_rule(analyzer, base)),
+            regex_suffix: regex_suffix.clone(),
+            js_post: js_post.as_deref().map(|code| Box::new(inspect_js(analyzer, code))),
+        },
+    }
+}
+
+fn inspect_js(analyzer: &UnifiedJsAnalyzer, code: &str) -> CompiledInspection {
+    let explanation = analyzer.explain(code);
+    let operations = match &explanation.result {
+        AnalysisResult::Native(exec) => vec![exec.to_string()],
+        AnalysisResult::NativeChain(chain) => chain.iter().map(ToString::to_string).collect(),
+        AnalysisResult::RequiresJs(_) => explanation.plans.iter().map(ToString::to_string).collect(),
+    };
+    let runtime = match explanation.strategy {
+        AnalysisStrategy::Js => "quickjs",
+        AnalysisStrategy::Regex | AnalysisStrategy::Ast => "native",
+    };
+    let strategy = match explanation.strategy {
+        AnalysisStrategy::Regex => "regex",
+        AnalysisStrategy::Ast => "ast",
+        AnalysisStrategy::Js => "js",
+    };
+    CompiledInspection::JavaScript {
+        code: code.to_string(),
+        runtime: runtime.to_string(),
+        strategy: strategy.to_string(),
+        reason: explanation.js_reason,
+        operations,
+    }
+}
+
+fn url_part(part: &UrlPart) -> String {
+    match part {
+        UrlPart::Literal(text) => text.clone(),
+        UrlPart::Variable(name) => format!("{{{{{}}}}}", name),
+        UrlPart::NativeCall(exec) => format!("{{{{{}}}}}", exec),
+        UrlPart::Expr(expr) => format!("{{{{{}}}}}", expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::source_transformer::SourceTransformer;
+    use crate::models::BookSourceFull;
+
+    fn fixture() -> TransformedSource {
+        let source: BookSourceFull = serde_json::from_value(serde_json::json!({
+            "bookSourceUrl": "https://inspect.example.com",
+            "bookSourceName": "Inspect",
+            "searchUrl": "/search?q={{key}}&page={{page}}",
+            "ruleSearch": {
+                "bookList": ".result li",
+                "name": "@js:java.base64Decode(result)",
+                "author": "@js:var s = ''; for (var i = 0; i < 3; i++) { s += i; } s",
+                "bookUrl": "a@href||b@href"
+            },
+            "ruleContent": {
+                "content": "#content@html##广告"
+            }
+        }))
+        .unwrap();
+        SourceTransformer::new().transform(&source)
+    }
+
+    fn rule<'a>(inspection: &'a SourceInspection, field: &str) -> &'a RuleInspection {
+        inspection.rules.iter().find(|r| r.field == field).unwrap()
+    }
+
+    #[test]
+    fn test_inspect_fixture_source() {
+        let inspection = inspect(&fixture());
+        let json = serde_json::to_value(&inspection).unwrap();
+        assert_eq!(json["bookSourceName"], "Inspect");
+
+        let search_url = &json["searchUrl"];
+        assert_eq!(search_url["requiresJs"], false);
+        assert!(search_url["parts"].as_array().unwrap().contains(&"{{key}}".into()));
+
+        let fields: Vec<&str> = inspection.rules.iter().map(|r| r.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "ruleSearch.bookList",
+                "ruleSearch.name",
+                "ruleSearch.author",
+                "ruleSearch.bookUrl",
+                "ruleContent.content"
+            ]
+        );
+
+        let list = serde_json::to_value(&rule(&inspection, "ruleSearch.bookList").compiled).unwrap();
+        assert_eq!(list["kind"], "selector");
+        assert_eq!(list["selector"], ".result li");
+        assert!(list["ruleType"].is_string());
+
+        let name = serde_json::to_value(&rule(&inspection, "ruleSearch.name").compiled).unwrap();
+        assert_eq!(name["kind"], "native");
+        assert_eq!(name["operations"][0], "Base64Decode(result)");
+
+        let author = serde_json::to_value(&rule(&inspection, "ruleSearch.author").compiled).unwrap();
+        assert_eq!(author["kind"], "javaScript");
+        assert_eq!(author["runtime"], "quickjs");
+        assert_eq!(author["strategy"], "js");
+        assert!(author["reason"].as_str().is_some_and(|r| !r.is_empty()));
+
+        let book_url = serde_json::to_value(&rule(&inspection, "ruleSearch.bookUrl").compiled).unwrap();
+        assert_eq!(book_url["kind"], "composite");
+        assert_eq!(book_url["join"], "firstMatch");
+        assert_eq!(book_url["parts"].as_array().unwrap().len(), 2);
+
+        let content = serde_json::to_value(&rule(&inspection, "ruleContent.content").compiled).unwrap();
+        assert_eq!(content["kind"], "chain");
+        assert_eq!(content["regexSuffix"], "广告");
+        assert_eq!(content["base"]["kind"], "selector");
+
+        let counts = &json["counts"];
+        assert_eq!(counts["rules"], 5);
+        assert_eq!(counts["selector"], 4);
+        assert_eq!(counts["native"], 1);
+        assert_eq!(counts["javascript"], 1);
+    }
+}
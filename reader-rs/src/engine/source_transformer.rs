@@ -7,6 +7,7 @@
 use super::js_analyzer::{AnalysisResult, ExprValue, JsPatternAnalyzer, NativeExecution};
 use super::parsers::RuleType;
 use super::preprocessor::{SourcePreprocessor, TemplateExpr};
+use super::rule_analyzer::{split_put_rule, split_rule_operator};
 use crate::models::{BookInfoRule, BookSourceFull, ContentRule, SearchRule, TocRule};
 use serde::{Deserialize, Serialize};
 
@@ -32,6 +33,16 @@ pub enum CompiledRule {
         /// Join with || (first match) or && (concatenate)
         join_type: JoinType,
     },
+    /// Rule with Legado post-processing: `@put:{...}` rules are evaluated
+    /// against the content and stored first, then the base result goes
+    /// through the `##regex##replacement` suffix and the trailing `<js>`
+    /// block, in that order
+    Chain {
+        put: Vec<(String, String)>,
+        base: Box<CompiledRule>,
+        regex_suffix: Option<String>,
+        js_post: Option<String>,
+    },
 }
 
 /// How to join multiple rule parts
@@ -63,6 +74,8 @@ pub enum UrlPart {
     Variable(String),
     /// Native API call result
     NativeCall(NativeExecution),
+    /// Arithmetic/ternary/string method expression evaluated without JS
+    Expr(String),
 }
 
 /// Transformed book source with optimization metadata
@@ -134,6 +147,10 @@ pub struct CompiledContentRules {
     pub content: CompiledRule,
     pub next_content_url: CompiledRule,
     pub replace_regex: Vec<(String, String)>,
+    /// JS evaluated in the page after a webView fetch; its result replaces the page
+    pub web_js: Option<String>,
+    /// Picks the content block to use when the content rule matches several
+    pub source_regex: Option<String>,
 }
 
 impl Default for CompiledRule {
@@ -267,6 +284,9 @@ impl SourceTransformer {
                     };
                     parts.push(UrlPart::NativeCall(exec));
                 }
+                TemplateExpr::Simple(expr) => {
+                    parts.push(UrlPart::Expr(expr.source().to_string()));
+                }
                 TemplateExpr::JsExpr(code) => {
                     url_requires_js = true;
                     *requires_js = true;
@@ -290,7 +310,7 @@ impl SourceTransformer {
         js_apis: &mut Vec<String>,
     ) -> CompiledSearchRules {
         CompiledSearchRules {
-            book_list: self.transform_rule_str(&rules.book_list, requires_js, js_apis),
+            book_list: self.transform_list_rule(&rules.book_list, requires_js, js_apis),
             name: self.transform_rule_str(&rules.name, requires_js, js_apis),
             author: self.transform_rule_str(&rules.author, requires_js, js_apis),
             kind: self.transform_rule_str(&rules.kind, requires_js, js_apis),
@@ -332,7 +352,7 @@ impl SourceTransformer {
         js_apis: &mut Vec<String>,
     ) -> CompiledTocRules {
         CompiledTocRules {
-            chapter_list: self.transform_rule_str(&rules.chapter_list, requires_js, js_apis),
+            chapter_list: self.transform_list_rule(&rules.chapter_list, requires_js, js_apis),
             chapter_name: self.transform_rule_str(&rules.chapter_name, requires_js, js_apis),
             chapter_url: self.transform_rule_str(&rules.chapter_url, requires_js, js_apis),
             is_volume: CompiledRule::Empty, // Not in model
@@ -358,6 +378,38 @@ impl SourceTransformer {
                 js_apis,
             ),
             replace_regex,
+            web_js: non_empty(&rules.web_js),
+            source_regex: non_empty(&rules.source_regex).filter(|pattern| is_valid_regex(pattern)),
+        }
+    }
+
+    /// Transform a rule that yields a list (bookList, chapterList)
+    ///
+    /// Selectors keep their `<js>` block and operators in one string: the
+    /// analyzer runs a list's `<js>` once over the whole list and merges
+    /// `||`/`&&` alternatives itself.
+    fn transform_list_rule(
+        &self,
+        rule: &str,
+        requires_js: &mut bool,
+        js_apis: &mut Vec<String>,
+    ) -> CompiledRule {
+        let rule = rule.trim();
+        if rule.is_empty() {
+            return CompiledRule::Empty;
+        }
+        match RuleType::detect(rule, "") {
+            RuleType::JavaScript => self.transform_single_rule(rule, requires_js, js_apis),
+            rule_type => {
+                if let Some((_, js)) = split_js_post(rule) {
+                    *requires_js = true;
+                    js_apis.push(format!("JS: {}", js.chars().take(30).collect::<String>()));
+                }
+                CompiledRule::Selector {
+                    rule_type,
+                    selector: self.strip_rule_prefix(rule),
+                }
+            }
         }
     }
 
@@ -388,32 +440,88 @@ impl SourceTransformer {
 
         let rule = rule.trim();
 
-        // Check for multi-rule operators
-        if rule.contains("||") {
-            let parts: Vec<_> = rule.split("||").map(|p| p.trim()).collect();
-            let compiled: Vec<_> = parts
-                .iter()
-                .map(|p| self.transform_single_rule(p, requires_js, js_apis))
-                .collect();
-            return CompiledRule::Composite {
-                parts: compiled,
-                join_type: JoinType::FirstMatch,
-            };
+        // Multi-step rules (one step per line) run through the analyzer as a whole
+        if rule.contains('\n') {
+            return self.transform_single_rule(rule, requires_js, js_apis);
         }
 
-        if rule.contains("&&") {
-            let parts: Vec<_> = rule.split("&&").map(|p| p.trim()).collect();
-            let compiled: Vec<_> = parts
-                .iter()
-                .map(|p| self.transform_single_rule(p, requires_js, js_apis))
-                .collect();
-            return CompiledRule::Composite {
-                parts: compiled,
-                join_type: JoinType::Concatenate,
-            };
+        // `@put:{...}` applies to the whole rule, before any operator
+        let (rule, put) = split_put_rule(rule);
+        let compiled = self.transform_operators(&rule, requires_js, js_apis);
+        if put.is_empty() {
+            compiled
+        } else {
+            CompiledRule::Chain {
+                put,
+                base: Box::new(compiled),
+                regex_suffix: None,
+                js_post: None,
+            }
         }
+    }
 
-        self.transform_single_rule(rule, requires_js, js_apis)
+    /// Split `||` / `&&` operators, leaving those inside `<js>` blocks, quotes or brackets
+    fn transform_operators(
+        &self,
+        rule: &str,
+        requires_js: &mut bool,
+        js_apis: &mut Vec<String>,
+    ) -> CompiledRule {
+        for (op, join_type) in [("||", JoinType::FirstMatch), ("&&", JoinType::Concatenate)] {
+            let parts = split_rule_operator(rule, op);
+            if parts.len() > 1 {
+                let compiled = parts
+                    .iter()
+                    .map(|p| self.transform_post_processed(p, requires_js, js_apis))
+                    .collect();
+                return CompiledRule::Composite {
+                    parts: compiled,
+                    join_type,
+                };
+            }
+        }
+        self.transform_post_processed(rule, requires_js, js_apis)
+    }
+
+    /// Transform a rule that may end with a `##regex##replacement` suffix and a `<js>` block
+    fn transform_post_processed(
+        &self,
+        rule: &str,
+        requires_js: &mut bool,
+        js_apis: &mut Vec<String>,
+    ) -> CompiledRule {
+        let rule = rule.trim();
+        if RuleType::detect(rule, "") == RuleType::JavaScript {
+            return self.transform_single_rule(rule, requires_js, js_apis);
+        }
+
+        let (base, js_post) = match split_js_post(rule) {
+            Some((base, js)) => (base, Some(js.to_string())),
+            None => (rule, None),
+        };
+        let (base, regex_suffix) = match base.find("##").filter(|&pos| pos > 0) {
+            Some(pos) => (
+                base[..pos].trim(),
+                Some(base[pos + 2..].trim_end().to_string()),
+            ),
+            None => (base, None),
+        };
+        if js_post.is_none() && regex_suffix.is_none() {
+            return self.transform_single_rule(rule, requires_js, js_apis);
+        }
+
+        if let Some(js) = &js_post {
+            if let AnalysisResult::RequiresJs(code) = self.analyzer.analyze(js) {
+                *requires_js = true;
+                js_apis.push(format!("JS: {}", code.chars().take(30).collect::<String>()));
+            }
+        }
+        CompiledRule::Chain {
+            put: Vec::new(),
+            base: Box::new(self.transform_rule_str(base, requires_js, js_apis)),
+            regex_suffix,
+            js_post,
+        }
     }
 
     /// Transform a single rule (no || or &&)
@@ -489,7 +597,7 @@ impl SourceTransformer {
                 };
                 ExprValue::NativeCall(Box::new(exec))
             }
-            TemplateExpr::JsExpr(_) => ExprValue::CurrentContent,
+            TemplateExpr::Simple(_) | TemplateExpr::JsExpr(_) => ExprValue::CurrentContent,
         }
     }
 
@@ -573,6 +681,8 @@ impl SourceTransformer {
                 CompiledRule::Native(_) => *native += 1,
                 CompiledRule::NativeChain(_) => *native += 1,
                 CompiledRule::JavaScript(_) => {}
+                CompiledRule::Chain { js_post: None, .. } => *native += 1,
+                CompiledRule::Chain { .. } => {}
                 CompiledRule::Composite { parts, .. } => {
                     for part in parts {
                         count_rule(part, native, total);
@@ -595,6 +705,28 @@ impl SourceTransformer {
     }
 }
 
+/// Split a `<js>...</js>` post-processing block off a rule, like the analyzer does
+fn split_js_post(rule: &str) -> Option<(&str, &str)> {
+    let start = rule.find("<js>")?;
+    let end = rule.find("</js>").filter(|&end| end > start)?;
+    Some((rule[..start].trim(), rule[start + 4..end].trim()))
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn is_valid_regex(pattern: &str) -> bool {
+    match regex::Regex::new(pattern) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!("Ignoring invalid sourceRegex {}: {}", pattern, e);
+            false
+        }
+    }
+}
+
 /// Compatibility report for a book source
 #[derive(Debug)]
 pub struct CompatibilityReport {
@@ -621,6 +753,7 @@ impl CompatibilityReport {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::SourceHealth;
 
     fn create_test_source() -> BookSourceFull {
         BookSourceFull {
@@ -629,7 +762,9 @@ mod tests {
             book_source_group: String::new(),
             book_source_type: 0,
             weight: 0,
+            respond_time: 0,
             enabled: true,
+            enabled_explore: true,
             search_url: "https://test.com/search?key={{key}}&page={{page}}".to_string(),
             rule_search: Some(SearchRule {
                 book_list: "div.book-list".to_string(),
@@ -650,7 +785,19 @@ mod tests {
             explore_url: String::new(),
             header: None,
             login_url: None,
+            login_ui: None,
+            login_check_js: None,
+            enabled_cloudflare_bypass: true,
+            user_agents: Vec::new(),
+            ua_rotation: None,
+            jitter_min_ms: None,
+            jitter_max_ms: None,
             js_lib: None,
+            last_test: None,
+            health: SourceHealth::Healthy,
+            subscription_url: None,
+            book_source_comment: None,
+            variable_comment: None,
         }
     }
 
@@ -715,6 +862,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transform_post_processed_rule() {
+        let transformer = SourceTransformer::new();
+        let mut requires_js = false;
+        let mut js_apis = Vec::new();
+
+        // `&&` inside the <js> block is not a rule operator
+        let rule = transformer.transform_rule(
+            Some(
+                r#"@css:#next@href##\?.*##<js>result && (result + ".html")</js>@put:{"bid":"42"}"#,
+            ),
+            &mut requires_js,
+            &mut js_apis,
+        );
+        let CompiledRule::Chain { put, base, .. } = rule else {
+            panic!("Expected Chain rule, got {:?}", rule);
+        };
+        assert_eq!(put, vec![("bid".to_string(), "42".to_string())]);
+        match *base {
+            CompiledRule::Chain {
+                base,
+                regex_suffix,
+                js_post,
+                ..
+            } => {
+                assert!(
+                    matches!(*base, CompiledRule::Selector { rule_type: RuleType::Css, ref selector } if selector == "#next@href")
+                );
+                assert_eq!(regex_suffix.as_deref(), Some(r"\?.*##"));
+                assert_eq!(js_post.as_deref(), Some(r#"result && (result + ".html")"#));
+            }
+            other => panic!("Expected Chain rule, got {:?}", other),
+        }
+        assert!(requires_js);
+
+        let rule = transformer.transform_rule(
+            Some("h1@text||h2@text<js>result.trim()</js>"),
+            &mut requires_js,
+            &mut js_apis,
+        );
+        match rule {
+            CompiledRule::Composite {
+                parts,
+                join_type: JoinType::FirstMatch,
+            } => {
+                assert!(matches!(parts[0], CompiledRule::Selector { .. }));
+                assert!(matches!(parts[1], CompiledRule::Chain { .. }));
+            }
+            other => panic!("Expected Composite rule, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_compatibility_report() {
         let source = create_test_source();
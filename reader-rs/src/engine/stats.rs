@@ -2,16 +2,231 @@
 //!
 //! This module tracks execution statistics to help optimize the engine
 //! by identifying which patterns are most commonly used.
+//!
+//! Besides the global counters, native calls, JS executions and JS fallback
+//! reasons are broken down per API, per reason and per book source, both
+//! since start and in per-minute buckets covering the last hour. Executions
+//! are attributed to the source entered with [`enter_source`] on the current
+//! thread.
 
 use once_cell::sync::Lazy;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Global execution statistics
 pub static STATS: Lazy<ExecutionStats> = Lazy::new(ExecutionStats::new);
 
+/// Minutes covered by the trailing window buckets
+pub const WINDOW_MINUTES: u64 = 60;
+
+/// Sources listed in the detail breakdown
+const TOP_SOURCES: usize = 10;
+
+thread_local! {
+    /// Per-thread (native, js) counters, used to attribute executions to a single rule
+    static THREAD_COUNTS: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+    /// Book source whose rules run on this thread
+    static CURRENT_SOURCE: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Native and JS executions recorded so far on the current thread
+pub fn thread_counts() -> (u64, u64) {
+    THREAD_COUNTS.with(|c| c.get())
+}
+
+/// Attribute executions on this thread to `source_url` until the guard is dropped
+pub fn enter_source(source_url: &str) -> SourceScope {
+    let previous = CURRENT_SOURCE.with(|s| s.replace(Some(Arc::from(source_url))));
+    SourceScope { previous }
+}
+
+/// Guard returned by [`enter_source`]; restores the enclosing source on drop
+pub struct SourceScope {
+    previous: Option<Arc<str>>,
+}
+
+impl Drop for SourceScope {
+    fn drop(&mut self) {
+        CURRENT_SOURCE.with(|s| *s.borrow_mut() = self.previous.take());
+    }
+}
+
+fn current_source() -> Option<Arc<str>> {
+    CURRENT_SOURCE.with(|s| s.borrow().clone())
+}
+
+/// Minutes since the Unix epoch
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 60)
+        .unwrap_or_default()
+}
+
+/// An execution recorded in the breakdowns
+enum Event<'a> {
+    Native(&'a str),
+    Js,
+    JsFallback(&'a str),
+    JsTimeout,
+}
+
+/// Execution counters for one slice of the statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Counters {
+    pub native_calls: u64,
+    pub js_calls: u64,
+    /// Native calls per API
+    pub apis: BTreeMap<String, u64>,
+    /// Snippets sent to QuickJS, per reason the analyzer gave up
+    pub js_fallback_reasons: BTreeMap<String, u64>,
+    /// Scripts interrupted for exceeding the execution time limit
+    pub js_timeouts: u64,
+}
+
+impl Counters {
+    fn record(&mut self, event: &Event) {
+        match event {
+            Event::Native(api) => {
+                self.native_calls += 1;
+                *self.apis.entry(api.to_string()).or_insert(0) += 1;
+            }
+            Event::Js => self.js_calls += 1,
+            Event::JsFallback(reason) => *self.js_fallback_reasons.entry(reason.to_string()).or_insert(0) += 1,
+            Event::JsTimeout => self.js_timeouts += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &Counters) {
+        self.native_calls += other.native_calls;
+        self.js_calls += other.js_calls;
+        self.js_timeouts += other.js_timeouts;
+        for (api, count) in &other.apis {
+            *self.apis.entry(api.clone()).or_insert(0) += count;
+        }
+        for (reason, count) in &other.js_fallback_reasons {
+            *self.js_fallback_reasons.entry(reason.clone()).or_insert(0) += count;
+        }
+    }
+}
+
+/// Counters overall and per book source
+#[derive(Debug, Clone, Default)]
+struct Breakdown {
+    total: Counters,
+    by_source: HashMap<Arc<str>, Counters>,
+}
+
+impl Breakdown {
+    fn record(&mut self, source: Option<&Arc<str>>, event: &Event) {
+        self.total.record(event);
+        if let Some(source) = source {
+            self.by_source.entry(source.clone()).or_default().record(event);
+        }
+    }
+
+    fn merge(&mut self, other: &Breakdown) {
+        self.total.merge(&other.total);
+        for (source, counters) in &other.by_source {
+            self.by_source.entry(source.clone()).or_default().merge(counters);
+        }
+    }
+
+    /// Counters of one source, or the total
+    fn counters(&self, source: Option<&str>) -> Counters {
+        match source {
+            Some(source) => self.by_source.get(source).cloned().unwrap_or_default(),
+            None => self.total.clone(),
+        }
+    }
+
+    /// Sources with the most JS executions
+    fn top_sources(&self) -> Vec<SourceCounters> {
+        let mut sources: Vec<SourceCounters> = self
+            .by_source
+            .iter()
+            .map(|(source, counters)| SourceCounters {
+                source: source.to_string(),
+                counters: counters.clone(),
+            })
+            .collect();
+        sources.sort_by(|a, b| {
+            b.counters
+                .js_calls
+                .cmp(&a.counters.js_calls)
+                .then(b.counters.native_calls.cmp(&a.counters.native_calls))
+                .then(a.source.cmp(&b.source))
+        });
+        sources.truncate(TOP_SOURCES);
+        sources
+    }
+}
+
+/// Per-minute buckets of the last [`WINDOW_MINUTES`], indexed by minute modulo
+/// the window; a bucket still holding an older minute is reset before reuse
+#[derive(Debug)]
+struct MinuteRing {
+    /// (minute since epoch, counters); `u64::MAX` marks an unused bucket
+    buckets: Vec<(u64, Breakdown)>,
+}
+
+impl MinuteRing {
+    fn new() -> Self {
+        Self {
+            buckets: vec![(u64::MAX, Breakdown::default()); WINDOW_MINUTES as usize],
+        }
+    }
+
+    fn bucket_mut(&mut self, minute: u64) -> &mut Breakdown {
+        let slot = &mut self.buckets[(minute % WINDOW_MINUTES) as usize];
+        if slot.0 != minute {
+            *slot = (minute, Breakdown::default());
+        }
+        &mut slot.1
+    }
+
+    /// Sum of the buckets for the `minutes` minutes ending at `now` (inclusive)
+    fn window(&self, now: u64, minutes: u64) -> Breakdown {
+        let mut sum = Breakdown::default();
+        for (minute, breakdown) in &self.buckets {
+            if *minute <= now && now - minute < minutes {
+                sum.merge(breakdown);
+            }
+        }
+        sum
+    }
+}
+
+/// Lifetime and windowed breakdowns
+#[derive(Debug)]
+struct Detail {
+    lifetime: Breakdown,
+    ring: MinuteRing,
+}
+
+impl Detail {
+    fn new() -> Self {
+        Self {
+            lifetime: Breakdown::default(),
+            ring: MinuteRing::new(),
+        }
+    }
+}
+
+/// Which breakdown a snapshot reports
+#[derive(Debug, Clone, Default)]
+pub struct StatsQuery {
+    /// Restrict the detail counters to one book source
+    pub source: Option<String>,
+    /// Also report the trailing window of this many minutes (at most [`WINDOW_MINUTES`])
+    pub window_minutes: Option<u64>,
+}
+
 /// Execution statistics tracker
 pub struct ExecutionStats {
     /// Number of native API executions
@@ -22,8 +237,18 @@ pub struct ExecutionStats {
     pub pattern_matches: AtomicU64,
     /// Number of pattern match failures (fallback to JS)
     pub pattern_misses: AtomicU64,
-    /// Per-API call counts
-    api_counts: RwLock<HashMap<String, u64>>,
+    /// Responses served from the HTTP cache (fresh or revalidated with a 304)
+    pub http_cache_hits: AtomicU64,
+    /// Cacheable requests that needed a full response
+    pub http_cache_misses: AtomicU64,
+    /// Multi-source searches abandoned by the client before finishing
+    pub searches_cancelled: AtomicU64,
+    /// JS analyses answered from a rule analyzer's memo
+    pub analysis_cache_hits: AtomicU64,
+    /// JS snippets analyzed from scratch
+    pub analysis_cache_misses: AtomicU64,
+    /// Per-API, per-reason and per-source breakdowns
+    detail: Mutex<Detail>,
 }
 
 impl ExecutionStats {
@@ -33,21 +258,48 @@ impl ExecutionStats {
             js_calls: AtomicU64::new(0),
             pattern_matches: AtomicU64::new(0),
             pattern_misses: AtomicU64::new(0),
-            api_counts: RwLock::new(HashMap::new()),
+            http_cache_hits: AtomicU64::new(0),
+            http_cache_misses: AtomicU64::new(0),
+            searches_cancelled: AtomicU64::new(0),
+            analysis_cache_hits: AtomicU64::new(0),
+            analysis_cache_misses: AtomicU64::new(0),
+            detail: Mutex::new(Detail::new()),
+        }
+    }
+
+    fn record_event(&self, event: Event) {
+        self.record_event_at(current_minute(), current_source().as_ref(), event);
+    }
+
+    fn record_event_at(&self, minute: u64, source: Option<&Arc<str>>, event: Event) {
+        if let Ok(mut detail) = self.detail.lock() {
+            detail.lifetime.record(source, &event);
+            detail.ring.bucket_mut(minute).record(source, &event);
         }
     }
 
     /// Record a native API call
     pub fn record_native(&self, api_name: &str) {
         self.native_calls.fetch_add(1, Ordering::Relaxed);
-        if let Ok(mut counts) = self.api_counts.write() {
-            *counts.entry(api_name.to_string()).or_insert(0) += 1;
-        }
+        THREAD_COUNTS.with(|c| c.set((c.get().0 + 1, c.get().1)));
+        self.record_event(Event::Native(api_name));
     }
 
     /// Record a JavaScript execution
     pub fn record_js(&self) {
         self.js_calls.fetch_add(1, Ordering::Relaxed);
+        THREAD_COUNTS.with(|c| c.set((c.get().0, c.get().1 + 1)));
+        self.record_event(Event::Js);
+    }
+
+    /// Record a snippet the analyzer could not run natively, with the reason label
+    pub fn record_js_fallback(&self, reason: &str) {
+        self.record_event(Event::JsFallback(reason));
+    }
+
+    /// Record a script interrupted by the execution time limit
+    pub fn record_js_timeout(&self) {
+        self.record_event(Event::JsTimeout);
     }
 
     /// Record a successful pattern match
@@ -60,8 +312,42 @@ impl ExecutionStats {
         self.pattern_misses.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a response served from the HTTP cache
+    pub fn record_http_cache_hit(&self) {
+        self.http_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a cacheable request that needed a full response
+    pub fn record_http_cache_miss(&self) {
+        self.http_cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a multi-source search cancelled by a client disconnect
+    pub fn record_search_cancelled(&self) {
+        self.searches_cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a JS analysis served from the memo
+    pub fn record_analysis_cache_hit(&self) {
+        self.analysis_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a JS analysis computed from scratch
+    pub fn record_analysis_cache_miss(&self) {
+        self.analysis_cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Get current statistics snapshot
     pub fn snapshot(&self) -> StatsSnapshot {
+        self.snapshot_with(&StatsQuery::default())
+    }
+
+    /// Snapshot whose detail covers the source and window in `query`
+    pub fn snapshot_with(&self, query: &StatsQuery) -> StatsSnapshot {
+        self.snapshot_at(query, current_minute())
+    }
+
+    fn snapshot_at(&self, query: &StatsQuery, now: u64) -> StatsSnapshot {
         let native = self.native_calls.load(Ordering::Relaxed);
         let js = self.js_calls.load(Ordering::Relaxed);
         let matches = self.pattern_matches.load(Ordering::Relaxed);
@@ -81,13 +367,34 @@ impl ExecutionStats {
             0.0
         };
 
-        let top_apis = self
-            .api_counts
-            .read()
-            .map(|counts| {
-                let mut sorted: Vec<_> = counts.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        let source = query.source.as_deref();
+        let (top_apis, detail) = self
+            .detail
+            .lock()
+            .map(|detail| {
+                let mut sorted: Vec<_> = detail.lifetime.total.apis.iter().map(|(k, v)| (k.clone(), *v)).collect();
                 sorted.sort_by(|a, b| b.1.cmp(&a.1));
-                sorted.into_iter().take(10).collect()
+                sorted.truncate(10);
+
+                let window = query.window_minutes.map(|minutes| {
+                    let minutes = minutes.clamp(1, WINDOW_MINUTES);
+                    (minutes, detail.ring.window(now, minutes))
+                });
+                let top_sources = match (&window, source) {
+                    (_, Some(_)) => Vec::new(),
+                    (Some((_, breakdown)), None) => breakdown.top_sources(),
+                    (None, None) => detail.lifetime.top_sources(),
+                };
+                let detail = StatsDetail {
+                    source: query.source.clone(),
+                    lifetime: detail.lifetime.counters(source),
+                    window: window.map(|(minutes, breakdown)| WindowStats {
+                        minutes,
+                        counters: breakdown.counters(source),
+                    }),
+                    top_sources,
+                };
+                (sorted, detail)
             })
             .unwrap_or_default();
 
@@ -99,7 +406,13 @@ impl ExecutionStats {
             pattern_matches: matches,
             pattern_misses: misses,
             pattern_match_ratio,
+            http_cache_hits: self.http_cache_hits.load(Ordering::Relaxed),
+            http_cache_misses: self.http_cache_misses.load(Ordering::Relaxed),
+            searches_cancelled: self.searches_cancelled.load(Ordering::Relaxed),
+            analysis_cache_hits: self.analysis_cache_hits.load(Ordering::Relaxed),
+            analysis_cache_misses: self.analysis_cache_misses.load(Ordering::Relaxed),
             top_apis,
+            detail,
         }
     }
 
@@ -109,8 +422,13 @@ impl ExecutionStats {
         self.js_calls.store(0, Ordering::Relaxed);
         self.pattern_matches.store(0, Ordering::Relaxed);
         self.pattern_misses.store(0, Ordering::Relaxed);
-        if let Ok(mut counts) = self.api_counts.write() {
-            counts.clear();
+        self.http_cache_hits.store(0, Ordering::Relaxed);
+        self.http_cache_misses.store(0, Ordering::Relaxed);
+        self.searches_cancelled.store(0, Ordering::Relaxed);
+        self.analysis_cache_hits.store(0, Ordering::Relaxed);
+        self.analysis_cache_misses.store(0, Ordering::Relaxed);
+        if let Ok(mut detail) = self.detail.lock() {
+            *detail = Detail::new();
         }
     }
 }
@@ -133,8 +451,55 @@ pub struct StatsSnapshot {
     pub pattern_misses: u64,
     /// Pattern match success ratio (0.0 - 1.0)
     pub pattern_match_ratio: f64,
+    /// Responses served from the HTTP cache
+    pub http_cache_hits: u64,
+    /// Cacheable requests that needed a full response
+    pub http_cache_misses: u64,
+    /// Multi-source searches abandoned by the client
+    pub searches_cancelled: u64,
+    /// JS analyses answered from the per-rule memo
+    pub analysis_cache_hits: u64,
+    /// JS snippets analyzed from scratch
+    pub analysis_cache_misses: u64,
     /// Top 10 most called APIs
     pub top_apis: Vec<(String, u64)>,
+    /// Per-API, per-reason and per-source breakdowns
+    pub detail: StatsDetail,
+}
+
+/// Breakdown section of [`StatsSnapshot`]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsDetail {
+    /// Source the counters are restricted to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Counters since start or the last reset
+    pub lifetime: Counters,
+    /// Counters of the requested trailing window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<WindowStats>,
+    /// Sources with the most JS executions (in the window when one is requested);
+    /// empty when a source is selected
+    pub top_sources: Vec<SourceCounters>,
+}
+
+/// Counters of a trailing window
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowStats {
+    pub minutes: u64,
+    #[serde(flatten)]
+    pub counters: Counters,
+}
+
+/// Counters of one book source
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCounters {
+    pub source: String,
+    #[serde(flatten)]
+    pub counters: Counters,
 }
 
 #[cfg(test)]
@@ -167,5 +532,83 @@ mod tests {
         let snapshot = stats.snapshot();
         assert_eq!(snapshot.native_calls, 0);
         assert_eq!(snapshot.js_calls, 0);
+        assert!(snapshot.detail.lifetime.apis.is_empty());
+    }
+
+    #[test]
+    fn test_minute_ring_window() {
+        let mut ring = MinuteRing::new();
+        let event = Event::Js;
+        ring.bucket_mut(100).record(None, &event);
+        ring.bucket_mut(130).record(None, &event);
+        ring.bucket_mut(130).record(None, &event);
+        ring.bucket_mut(159).record(None, &event);
+
+        assert_eq!(ring.window(159, 60).total.js_calls, 4);
+        assert_eq!(ring.window(159, 30).total.js_calls, 3);
+        assert_eq!(ring.window(159, 1).total.js_calls, 1);
+        // Minute 100 falls out of the window one hour later
+        assert_eq!(ring.window(160, 60).total.js_calls, 3);
+        // Buckets newer than `now` are not counted
+        assert_eq!(ring.window(130, 60).total.js_calls, 3);
+    }
+
+    #[test]
+    fn test_minute_ring_reuses_stale_bucket() {
+        let mut ring = MinuteRing::new();
+        ring.bucket_mut(5).record(None, &Event::Native("md5"));
+        // Minute 65 maps to the same slot and resets it
+        ring.bucket_mut(65).record(None, &Event::Js);
+
+        let window = ring.window(65, 60).total;
+        assert_eq!(window.native_calls, 0);
+        assert_eq!(window.js_calls, 1);
+        assert_eq!(ring.window(5, 60).total.js_calls, 0);
+    }
+
+    #[test]
+    fn test_breakdown_by_source() {
+        let stats = ExecutionStats::new();
+        let a: Arc<str> = Arc::from("https://a.example");
+        let b: Arc<str> = Arc::from("https://b.example");
+        stats.record_event_at(1000, Some(&a), Event::Native("md5"));
+        stats.record_event_at(1000, Some(&a), Event::JsFallback("dynamicPropertyAccess"));
+        stats.record_event_at(1010, Some(&b), Event::Js);
+        stats.record_event_at(1050, Some(&b), Event::Js);
+
+        let query = StatsQuery {
+            source: None,
+            window_minutes: Some(30),
+        };
+        let detail = stats.snapshot_at(&query, 1050).detail;
+        assert_eq!(detail.lifetime.native_calls, 1);
+        assert_eq!(detail.lifetime.js_fallback_reasons.get("dynamicPropertyAccess"), Some(&1));
+        let window = detail.window.unwrap();
+        assert_eq!((window.minutes, window.counters.js_calls), (30, 1));
+        assert_eq!(detail.top_sources.len(), 1);
+        assert_eq!(detail.top_sources[0].source, "https://b.example");
+
+        let query = StatsQuery {
+            source: Some("https://a.example".to_string()),
+            window_minutes: None,
+        };
+        let detail = stats.snapshot_at(&query, 1050).detail;
+        assert_eq!(detail.lifetime.apis.get("md5"), Some(&1));
+        assert_eq!(detail.lifetime.js_calls, 0);
+        assert!(detail.top_sources.is_empty());
+    }
+
+    #[test]
+    fn test_source_scope_nesting() {
+        assert!(current_source().is_none());
+        {
+            let _outer = enter_source("https://a.example");
+            {
+                let _inner = enter_source("https://b.example");
+                assert_eq!(current_source().as_deref(), Some("https://b.example"));
+            }
+            assert_eq!(current_source().as_deref(), Some("https://a.example"));
+        }
+        assert!(current_source().is_none());
     }
 }
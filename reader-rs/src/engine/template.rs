@@ -42,6 +42,8 @@ pub struct TemplateExecutor {
     native_api: Arc<NativeApiProvider>,
     /// JS executor for fallback (lazy initialized)
     js_executor: Option<Arc<JsExecutor>>,
+    /// `bookSourceUrl` scoping source variables
+    source_url: String,
 }
 
 impl TemplateExecutor {
@@ -50,14 +52,21 @@ impl TemplateExecutor {
         Self {
             native_api,
             js_executor: None,
+            source_url: String::new(),
         }
     }
 
+    /// Set the source whose variables `source.getVariable()` reads
+    pub fn set_source_url(&mut self, url: &str) {
+        self.source_url = url.to_string();
+    }
+
     /// Create with both native API and JS executor
     pub fn with_js(native_api: Arc<NativeApiProvider>, js_executor: Arc<JsExecutor>) -> Self {
         Self {
             native_api,
             js_executor: Some(js_executor),
+            source_url: String::new(),
         }
     }
 
@@ -104,15 +113,22 @@ impl TemplateExecutor {
                     return self.execute_js_fallback(name, &arg_values, ctx);
                 }
 
-                // Execute natively
                 // Execute natively
                 self.native_api.execute(
                     api,
                     &arg_values,
-                    &crate::engine::native_api::ExecutionContext::default(),
+                    &crate::engine::native_api::ExecutionContext {
+                        base_url: ctx.get("baseUrl").cloned().unwrap_or_default(),
+                        source_url: self.source_url.clone(),
+                    },
                 )
             }
 
+            TemplateExpr::Simple(expr) => expr.eval(ctx).or_else(|e| {
+                tracing::debug!("Native evaluation of {} failed: {}", expr.source(), e);
+                self.execute_js(expr.source(), ctx)
+            }),
+
             TemplateExpr::JsExpr(code) => self.execute_js(code, ctx),
         }
     }
@@ -122,8 +138,14 @@ impl TemplateExecutor {
         if let Some(ref js) = self.js_executor {
             js.eval_with_context(code, &ctx.variables)
         } else {
-            // No JS executor, try to evaluate simple expressions
-            self.evaluate_simple_expr(code, ctx)
+            // No JS executor, evaluate what the native expression evaluator covers
+            match SimpleExpr::parse(code) {
+                Some(expr) => expr.eval(ctx),
+                None => {
+                    tracing::debug!("Cannot evaluate expr without JS: {}", code);
+                    Ok(String::new())
+                }
+            }
         }
     }
 
@@ -147,92 +169,418 @@ impl TemplateExecutor {
             Err(anyhow::anyhow!("No JS executor for java.{}", method))
         }
     }
+}
 
-    /// Try to evaluate simple expressions without JS
-    fn evaluate_simple_expr(&self, expr: &str, ctx: &TemplateContext) -> Result<String> {
-        let expr = expr.trim();
+/// Expression inside `{{ }}` that is evaluated without QuickJS
+///
+/// Covers the forms book sources use to build URLs: integer arithmetic with
+/// parentheses, comparisons, the ternary operator, string concatenation with
+/// `+`, and `replace`/`replaceAll`/`trim`/`toLowerCase`/`toUpperCase`/`length`
+/// on values. Variables holding a canonical integer behave as numbers (as
+/// Legado's `page` does), everything else as strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleExpr {
+    source: String,
+    node: Node,
+}
 
-        // Simple arithmetic: (page-1)*20
-        if expr.contains("page") && (expr.contains('+') || expr.contains('-') || expr.contains('*'))
-        {
-            if let Some(page) = ctx.get("page").and_then(|s| s.parse::<i64>().ok()) {
-                // Very simple evaluation
-                let expr = expr.replace("page", &page.to_string());
-                if let Ok(result) = self.eval_simple_math(&expr) {
-                    return Ok(result.to_string());
-                }
-            }
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Num(i64),
+    Str(String),
+    Bool(bool),
+    Var(String),
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Ternary(Box<Node>, Box<Node>, Box<Node>),
+    Method(Box<Node>, Method, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    StrictEq,
+    StrictNe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Replace,
+    ReplaceAll,
+    Trim,
+    ToLowerCase,
+    ToUpperCase,
+    Length,
+}
+
+impl Method {
+    /// Method (or property, with `None` arguments) and its argument count
+    fn parse(name: &str) -> Option<(Self, Option<usize>)> {
+        Some(match name {
+            "replace" => (Self::Replace, Some(2)),
+            "replaceAll" => (Self::ReplaceAll, Some(2)),
+            "trim" => (Self::Trim, Some(0)),
+            "toLowerCase" => (Self::ToLowerCase, Some(0)),
+            "toUpperCase" => (Self::ToUpperCase, Some(0)),
+            "length" => (Self::Length, None),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Num(i64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn into_string(self) -> String {
+        match self {
+            Value::Num(n) => n.to_string(),
+            Value::Str(s) => s,
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+
+    /// JS `Number()` coercion, restricted to integers
+    fn to_num(&self) -> Result<i64> {
+        match self {
+            Value::Num(n) => Ok(*n),
+            Value::Bool(b) => Ok(*b as i64),
+            Value::Str(s) if s.trim().is_empty() => Ok(0),
+            Value::Str(s) => s
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Not an integer: {:?}", s)),
         }
+    }
 
-        // Ternary: page - 1 == 0 ? "" : page
-        if expr.contains('?') && expr.contains(':') {
-            // Extract condition and branches
-            if let Some(q_pos) = expr.find('?') {
-                let _condition = expr[..q_pos].trim();
-                let rest = &expr[q_pos + 1..];
-                if let Some(c_pos) = rest.find(':') {
-                    let _true_val = rest[..c_pos].trim();
-                    let _false_val = rest[c_pos + 1..].trim();
-                    // For now, just return the variable if it exists
-                    if let Some(page) = ctx.get("page") {
-                        return Ok(page.clone());
-                    }
-                }
-            }
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Num(n) => *n != 0,
+            Value::Str(s) => !s.is_empty(),
+            Value::Bool(b) => *b,
         }
+    }
+}
 
-        // If we can't evaluate, return empty or the expression itself
-        tracing::debug!("Cannot evaluate simple expr: {}", expr);
-        Ok(String::new())
+impl SimpleExpr {
+    /// Parse `expr`, or `None` when it needs a JS engine
+    pub fn parse(expr: &str) -> Option<Self> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let node = parser.ternary()?;
+        if parser.pos != parser.tokens.len() {
+            return None;
+        }
+        Some(Self {
+            source: expr.to_string(),
+            node,
+        })
     }
 
-    /// Evaluate simple math expression
-    fn eval_simple_math(&self, expr: &str) -> Result<i64> {
-        // Very basic: only handles (a-b)*c or a*b patterns
-        let expr = expr.replace(' ', "");
+    /// The expression as written in the template
+    pub fn source(&self) -> &str {
+        &self.source
+    }
 
-        // Handle parentheses
-        if expr.starts_with('(') {
-            if let Some(close) = expr.find(')') {
-                let inner = &expr[1..close];
-                let inner_result = self.eval_simple_math(inner)?;
-                let rest = &expr[close + 1..];
+    /// Evaluate against `ctx`; fails on unknown variables or non-integer math
+    pub fn eval(&self, ctx: &TemplateContext) -> Result<String> {
+        Ok(eval_node(&self.node, ctx)?.into_string())
+    }
+}
 
-                if rest.starts_with('*') {
-                    let multiplier: i64 = rest[1..].parse()?;
-                    return Ok(inner_result * multiplier);
-                } else if rest.is_empty() {
-                    return Ok(inner_result);
+fn eval_node(node: &Node, ctx: &TemplateContext) -> Result<Value> {
+    Ok(match node {
+        Node::Num(n) => Value::Num(*n),
+        Node::Str(s) => Value::Str(s.clone()),
+        Node::Bool(b) => Value::Bool(*b),
+        Node::Var(name) => {
+            let value = ctx
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("{} is not defined", name))?;
+            match value.parse::<i64>() {
+                Ok(n) if n.to_string() == *value => Value::Num(n),
+                _ => Value::Str(value.clone()),
+            }
+        }
+        Node::Neg(inner) => Value::Num(
+            eval_node(inner, ctx)?
+                .to_num()?
+                .checked_neg()
+                .ok_or_else(|| anyhow::anyhow!("Integer overflow"))?,
+        ),
+        Node::Ternary(cond, then, otherwise) => {
+            if eval_node(cond, ctx)?.truthy() {
+                eval_node(then, ctx)?
+            } else {
+                eval_node(otherwise, ctx)?
+            }
+        }
+        Node::Binary(op, lhs, rhs) => eval_binary(*op, eval_node(lhs, ctx)?, eval_node(rhs, ctx)?)?,
+        Node::Method(target, method, args) => {
+            let target = eval_node(target, ctx)?.into_string();
+            let args = args
+                .iter()
+                .map(|a| eval_node(a, ctx).map(Value::into_string))
+                .collect::<Result<Vec<_>>>()?;
+            match method {
+                Method::Replace | Method::ReplaceAll => {
+                    // `$&`-style patterns in the replacement are left to QuickJS
+                    if args[1].contains('$') {
+                        return Err(anyhow::anyhow!("Replacement patterns are not supported"));
+                    }
+                    if *method == Method::Replace {
+                        Value::Str(target.replacen(&args[0], &args[1], 1))
+                    } else {
+                        Value::Str(target.replace(&args[0], &args[1]))
+                    }
                 }
+                Method::Trim => Value::Str(target.trim().to_string()),
+                Method::ToLowerCase => Value::Str(target.to_lowercase()),
+                Method::ToUpperCase => Value::Str(target.to_uppercase()),
+                Method::Length => Value::Num(target.encode_utf16().count() as i64),
             }
         }
+    })
+}
 
-        // Simple subtraction
-        if let Some(pos) = expr.find('-') {
-            if pos > 0 {
-                let a: i64 = expr[..pos].parse()?;
-                let b: i64 = expr[pos + 1..].parse()?;
-                return Ok(a - b);
+fn eval_binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value> {
+    let overflow = || anyhow::anyhow!("Integer overflow");
+    Ok(match op {
+        BinaryOp::Add => match (&lhs, &rhs) {
+            (Value::Str(_), _) | (_, Value::Str(_)) => Value::Str(lhs.into_string() + &rhs.into_string()),
+            _ => Value::Num(lhs.to_num()?.checked_add(rhs.to_num()?).ok_or_else(overflow)?),
+        },
+        BinaryOp::Sub => Value::Num(lhs.to_num()?.checked_sub(rhs.to_num()?).ok_or_else(overflow)?),
+        BinaryOp::Mul => Value::Num(lhs.to_num()?.checked_mul(rhs.to_num()?).ok_or_else(overflow)?),
+        BinaryOp::Div | BinaryOp::Mod => {
+            let (a, b) = (lhs.to_num()?, rhs.to_num()?);
+            // JS division yields fractions and Infinity, leave those to QuickJS
+            if b == 0 || (op == BinaryOp::Div && a % b != 0) {
+                return Err(anyhow::anyhow!("Non-integer result of {} / {}", a, b));
             }
+            Value::Num(if op == BinaryOp::Div { a / b } else { a % b })
+        }
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (&lhs, &rhs) {
+                (Value::Str(a), Value::Str(b)) => a.cmp(b),
+                _ => lhs.to_num()?.cmp(&rhs.to_num()?),
+            };
+            Value::Bool(match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        BinaryOp::Eq | BinaryOp::Ne => {
+            let equal = match (&lhs, &rhs) {
+                (Value::Str(a), Value::Str(b)) => a == b,
+                _ => matches!((lhs.to_num(), rhs.to_num()), (Ok(a), Ok(b)) if a == b),
+            };
+            Value::Bool(equal == (op == BinaryOp::Eq))
         }
+        BinaryOp::StrictEq | BinaryOp::StrictNe => Value::Bool((lhs == rhs) == (op == BinaryOp::StrictEq)),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+/// Split `expr` into tokens, `None` on anything outside the supported syntax
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    const PUNCTS: [&str; 19] = [
+        "===", "!==", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "(", ")", "?", ":", ".", ",",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            // Decimals are left to QuickJS
+            if rest[end..].starts_with('.') {
+                return None;
+            }
+            tokens.push(Token::Num(rest[..end].parse().ok()?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next()? {
+                    (i, ch) if ch == c => break i,
+                    (_, '\\') => value.push(match chars.next()?.1 {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    }),
+                    (_, ch) => value.push(ch),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end + 1..];
+        } else {
+            let punct = PUNCTS.iter().find(|p| rest.starts_with(**p))?;
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Some(tokens)
+}
+
+/// Recursive descent parser over the tokens of a [`SimpleExpr`]
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
 
-        // Simple addition
-        if let Some(pos) = expr.find('+') {
-            let a: i64 = expr[..pos].parse()?;
-            let b: i64 = expr[pos + 1..].parse()?;
-            return Ok(a + b);
+impl Parser {
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Punct(p)) => Some(p),
+            _ => None,
         }
+    }
 
-        // Simple multiplication
-        if let Some(pos) = expr.find('*') {
-            let a: i64 = expr[..pos].parse()?;
-            let b: i64 = expr[pos + 1..].parse()?;
-            return Ok(a * b);
+    fn eat(&mut self, punct: &str) -> bool {
+        let matched = self.peek_punct() == Some(punct);
+        if matched {
+            self.pos += 1;
         }
+        matched
+    }
 
-        // Just a number
-        expr.parse()
-            .map_err(|e| anyhow::anyhow!("Parse error: {}", e))
+    fn ternary(&mut self) -> Option<Node> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Some(cond);
+        }
+        let then = self.ternary()?;
+        if !self.eat(":") {
+            return None;
+        }
+        let otherwise = self.ternary()?;
+        Some(Node::Ternary(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    /// Binary operators by precedence level: equality, comparison, additive, multiplicative
+    fn binary(&mut self, level: usize) -> Option<Node> {
+        const LEVELS: [&[(&str, BinaryOp)]; 4] = [
+            &[
+                ("===", BinaryOp::StrictEq),
+                ("!==", BinaryOp::StrictNe),
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+            ],
+            &[
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Mod)],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = ops.iter().find(|(p, _)| self.peek_punct() == Some(*p)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<Node> {
+        if self.eat("-") {
+            return Some(Node::Neg(Box::new(self.unary()?)));
+        }
+        let mut node = self.primary()?;
+        while self.eat(".") {
+            let Some(Token::Ident(name)) = self.tokens.get(self.pos) else {
+                return None;
+            };
+            let (method, arity) = Method::parse(name)?;
+            self.pos += 1;
+            let args = match arity {
+                Some(arity) => {
+                    let args = self.args()?;
+                    (args.len() == arity).then_some(args)?
+                }
+                None => Vec::new(),
+            };
+            node = Node::Method(Box::new(node), method, args);
+        }
+        Some(node)
+    }
+
+    fn args(&mut self) -> Option<Vec<Node>> {
+        if !self.eat("(") {
+            return None;
+        }
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Some(args);
+        }
+        loop {
+            args.push(self.ternary()?);
+            if self.eat(")") {
+                return Some(args);
+            }
+            if !self.eat(",") {
+                return None;
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Option<Node> {
+        let token = self.tokens.get(self.pos)?.clone();
+        self.pos += 1;
+        match token {
+            Token::Num(n) => Some(Node::Num(n)),
+            Token::Str(s) => Some(Node::Str(s)),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Some(Node::Bool(true)),
+                "false" => Some(Node::Bool(false)),
+                // Function calls and globals (java, source, book...) need QuickJS
+                _ if self.peek_punct() == Some("(") => None,
+                "java" | "source" | "book" | "chapter" | "cookie" | "undefined" | "null" => None,
+                _ => Some(Node::Var(name)),
+            },
+            Token::Punct("(") => {
+                let node = self.ternary()?;
+                self.eat(")").then_some(node)
+            }
+            Token::Punct(_) => None,
+        }
     }
 }
 
@@ -297,13 +645,58 @@ mod tests {
     }
 
     #[test]
-    fn test_simple_math() {
-        let exec = create_executor();
+    fn test_simple_exprs_skip_js() {
+        use crate::engine::preprocessor::SourcePreprocessor;
+        use crate::engine::stats::thread_counts;
+
+        let cm = Arc::new(CookieManager::new());
+        let native_api = Arc::new(NativeApiProvider::new(cm, create_test_kv()));
+        let js = Arc::new(JsExecutor::new(native_api.clone()).unwrap());
+        let exec = TemplateExecutor::with_js(native_api, js);
+        let preprocessor = SourcePreprocessor::new();
+        let ctx = TemplateContext::new()
+            .with_var("page", "3")
+            .with_var("key", "Hello World");
+
+        let before = thread_counts();
+        for (template, expected) in [
+            ("{{page-1}}", "2"),
+            ("{{page+1}}", "4"),
+            ("{{(page-1)*20}}", "40"),
+            ("{{(page - 1) * 20 + 1}}", "41"),
+            ("{{page%2}}", "1"),
+            ("{{-page+10}}", "7"),
+            ("{{key.replace(' ','+')}}", "Hello+World"),
+            ("{{key.toLowerCase()}}", "hello world"),
+            ("{{(' '+key+' ').trim()}}", "Hello World"),
+            ("{{key.replaceAll(\"o\", '0')}}", "Hell0 W0rld"),
+            ("/list{{page<2?'':'_'+page}}.html", "/list_3.html"),
+            ("{{page==1?'index':'index_'+page}}", "index_3"),
+            ("{{page>=3?page*2:0}}", "6"),
+            ("{{key.length>5?'long':'short'}}", "long"),
+            ("p{{'-'+(page+1)}}.html", "p-4.html"),
+        ] {
+            let parts = preprocessor.parse_template(template);
+            assert!(
+                parts
+                    .iter()
+                    .all(|p| matches!(p, TemplateExpr::Literal(_) | TemplateExpr::Simple(_))),
+                "{} not parsed natively",
+                template
+            );
+            assert_eq!(exec.execute_parts(&parts, &ctx).unwrap(), expected, "{}", template);
+        }
+        assert_eq!(thread_counts().1, before.1, "template expressions fell back to JS");
 
-        assert_eq!(exec.eval_simple_math("5-1").unwrap(), 4);
-        assert_eq!(exec.eval_simple_math("(5-1)*20").unwrap(), 80);
-        assert_eq!(exec.eval_simple_math("3+7").unwrap(), 10);
-        assert_eq!(exec.eval_simple_math("3*5").unwrap(), 15);
+        let first = TemplateContext::new().with_var("page", "1");
+        let parts = preprocessor.parse_template("/list{{page<2?'':'_'+page}}.html");
+        assert_eq!(exec.execute_parts(&parts, &first).unwrap(), "/list.html");
+
+        // Regex replace, globals and fractions are left to QuickJS
+        for expr in ["key.replace(/ /g,'+')", "Math.floor(page/2)", "book.name+page", "page*1.5"] {
+            assert!(SimpleExpr::parse(expr).is_none(), "{}", expr);
+        }
+        assert!(SimpleExpr::parse("page/2").unwrap().eval(&ctx).is_err());
     }
 
     #[test]
@@ -0,0 +1,125 @@
+//! Minimal HTTP server for tests that need a real upstream
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// Status, extra headers and body returned by a handler; status 0 drops
+/// the connection without a response
+pub(crate) type Reply<B> = (u16, Vec<(&'static str, String)>, B);
+
+/// Serve requests one at a time until the test ends; `handler` gets the request
+/// head (lowercased header names) and body and returns status, headers and body
+pub(crate) fn spawn_server<F, B>(handler: F) -> String
+where
+    F: Fn(&str, &str) -> Reply<B> + Send + 'static,
+    B: AsRef<[u8]>,
+{
+    spawn_raw_server(move |head, body| handler(head, &String::from_utf8_lossy(body)))
+}
+
+/// Like [`spawn_server`], but hands the request body over as raw bytes
+pub(crate) fn spawn_raw_server<F, B>(handler: F) -> String
+where
+    F: Fn(&str, &[u8]) -> Reply<B> + Send + 'static,
+    B: AsRef<[u8]>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            serve(stream.unwrap(), &handler);
+        }
+    });
+    format!("http://{}", addr)
+}
+
+/// Like [`spawn_server`], but handles each connection on its own thread
+pub(crate) fn spawn_concurrent_server<F, B>(handler: F) -> String
+where
+    F: Fn(&str, &str) -> Reply<B> + Send + Sync + 'static,
+    B: AsRef<[u8]>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(move |head: &str, body: &[u8]| handler(head, &String::from_utf8_lossy(body)));
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let handler = handler.clone();
+            std::thread::spawn(move || serve(stream, &*handler));
+        }
+    });
+    format!("http://{}", addr)
+}
+
+fn serve<F, B>(stream: TcpStream, handler: &F)
+where
+    F: Fn(&str, &[u8]) -> Reply<B> + ?Sized,
+    B: AsRef<[u8]>,
+{
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.to_lowercase();
+            if name == "content-length" {
+                content_length = value.trim().parse().unwrap();
+            }
+            head.push_str(&format!("{}:{}", name, value));
+        } else {
+            head.push_str(&line);
+        }
+    }
+    let mut body = vec![0; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+
+    let (status, headers, body) = handler(&head, &body);
+    if status == 0 {
+        return;
+    }
+    let body = body.as_ref();
+    let mut response = format!("HTTP/1.1 {} X\r\nConnection: close\r\n", status);
+    // A handler answering HEAD sets its own Content-Length
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+        response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    let mut stream = reader.into_inner();
+    if stream.write_all(response.as_bytes()).is_ok() {
+        let _ = stream.write_all(body);
+    }
+}
+
+/// The request path from a handler head
+pub(crate) fn request_path(head: &str) -> &str {
+    head.split_whitespace().nth(1).unwrap_or("")
+}
+
+/// The value of a header (lowercase `name`) from a handler head
+pub(crate) fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key == name).then(|| value.trim())
+    })
+}
+
+/// All headers (lowercase names) from a handler head
+pub(crate) fn headers(head: &str) -> HashMap<String, String> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect()
+}
@@ -0,0 +1,194 @@
+//! Debug Trace - Step-by-step record of a book source run
+//!
+//! A `TraceCollector` can be attached to a `BookSourceEngine` (and through it
+//! to its `RuleAnalyzer`). While attached, every HTTP request and every
+//! top-level rule evaluation is recorded under the current stage, which is
+//! what the source debug screen renders.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use super::http_client::RequestConfig;
+use super::parsers::RuleType;
+use super::stats;
+
+/// Maximum characters kept for body and value previews
+pub const PREVIEW_CHARS: usize = 500;
+
+/// Pipeline stage a trace entry belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TraceStage {
+    Search,
+    Pick,
+    BookInfo,
+    Toc,
+    Content,
+}
+
+/// How a rule was executed, using the same categories as `engine::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutionKind {
+    /// Pure parser rule (CSS, XPath, JSONPath, regex...)
+    Selector,
+    /// Handled by native Rust APIs without a JS engine
+    Native,
+    /// Fell back to the JavaScript engine
+    Js,
+}
+
+impl ExecutionKind {
+    /// Classify from `stats::thread_counts()` taken before and after a run
+    pub fn from_counts(before: (u64, u64), after: (u64, u64)) -> Self {
+        if after.1 > before.1 {
+            ExecutionKind::Js
+        } else if after.0 > before.0 {
+            ExecutionKind::Native
+        } else {
+            ExecutionKind::Selector
+        }
+    }
+}
+
+/// A single recorded event
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TraceEvent {
+    #[serde(rename_all = "camelCase")]
+    Request {
+        url: String,
+        method: String,
+        headers: BTreeMap<String, String>,
+        response_length: usize,
+        body_preview: String,
+        error: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Rule {
+        rule: String,
+        rule_type: String,
+        execution: ExecutionKind,
+        value_preview: String,
+        error: Option<String>,
+    },
+    Message { text: String },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEntry {
+    pub stage: TraceStage,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+#[derive(Default)]
+struct TraceState {
+    stage: Option<TraceStage>,
+    entries: Vec<TraceEntry>,
+}
+
+/// Shared collector for trace entries; clones record into the same trace
+#[derive(Clone, Default)]
+pub struct TraceCollector {
+    state: Arc<Mutex<TraceState>>,
+}
+
+impl TraceCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the stage subsequent events are recorded under
+    pub fn set_stage(&self, stage: TraceStage) {
+        if let Ok(mut state) = self.state.lock() {
+            state.stage = Some(stage);
+        }
+    }
+
+    fn push(&self, event: TraceEvent) {
+        if let Ok(mut state) = self.state.lock() {
+            let stage = state.stage.unwrap_or(TraceStage::Search);
+            state.entries.push(TraceEntry { stage, event });
+        }
+    }
+
+    /// Record an HTTP request and its outcome
+    pub fn request(
+        &self,
+        config: &RequestConfig,
+        default_headers: &std::collections::HashMap<String, String>,
+        result: &anyhow::Result<String>,
+    ) {
+        let mut headers: BTreeMap<String, String> = default_headers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if let Some(extra) = &config.headers {
+            headers.extend(extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        let (response_length, body_preview, error) = match result {
+            Ok(body) => (body.len(), preview(body), None),
+            Err(e) => (0, String::new(), Some(format!("{:#}", e))),
+        };
+        self.push(TraceEvent::Request {
+            url: config.url.clone(),
+            method: config.method.clone(),
+            headers,
+            response_length,
+            body_preview,
+            error,
+        });
+    }
+
+    /// Record a rule evaluation
+    pub fn rule(
+        &self,
+        rule: &str,
+        rule_type: Option<RuleType>,
+        execution: ExecutionKind,
+        value: Result<String, String>,
+    ) {
+        let (value_preview, error) = match value {
+            Ok(v) => (preview(&v), None),
+            Err(e) => (String::new(), Some(e)),
+        };
+        self.push(TraceEvent::Rule {
+            rule: preview(rule),
+            rule_type: rule_type.map(|t| format!("{:?}", t)).unwrap_or_else(|| "Native".to_string()),
+            execution,
+            value_preview,
+            error,
+        });
+    }
+
+    pub fn message(&self, text: impl Into<String>) {
+        self.push(TraceEvent::Message { text: text.into() });
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        self.push(TraceEvent::Error { message: message.into() });
+    }
+
+    /// Entries recorded so far
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.state.lock().map(|s| s.entries.clone()).unwrap_or_default()
+    }
+}
+
+/// Run `f` and classify it by the executions it recorded on this thread
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, ExecutionKind) {
+    let before = stats::thread_counts();
+    let result = f();
+    (result, ExecutionKind::from_counts(before, stats::thread_counts()))
+}
+
+/// Truncate text to `PREVIEW_CHARS` characters
+pub fn preview(text: &str) -> String {
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
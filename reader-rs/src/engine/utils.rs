@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 /// Robustly resolve an absolute URL from a base and a relative path.
 ///
 /// If the base is not a valid URL (e.g. it's an ID like "DQuestQBall"),
@@ -189,6 +191,175 @@ pub fn to_num_chapter(s: &str) -> String {
     .to_string()
 }
 
+/// Decode numeric character references (`&#20013;`, `&#x4e2d;`) left in extracted text
+///
+/// Named entities are kept as-is; invalid code points are left undecoded.
+pub fn decode_numeric_entities(s: &str) -> std::borrow::Cow<'_, str> {
+    if !s.contains("&#") {
+        return std::borrow::Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find("&#") {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos + 2..];
+        let (digits, radix) = match tail.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (tail, 10),
+        };
+        let len = digits
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(digits.len());
+        let decoded = (len > 0 && digits[len..].starts_with(';'))
+            .then(|| {
+                u32::from_str_radix(&digits[..len], radix)
+                    .ok()
+                    .and_then(char::from_u32)
+            })
+            .flatten();
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &digits[len + 1..];
+            }
+            None => {
+                out.push_str("&#");
+                rest = tail;
+            }
+        }
+    }
+    out.push_str(rest);
+    std::borrow::Cow::Owned(out)
+}
+
+/// Paragraph formatting of chapter text, applied after the content filters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContentFormatOptions {
+    /// Whether to format at all; lets a book opt out of formatting requested by default
+    pub enabled: bool,
+    /// Prefix of every paragraph once its own indentation is stripped
+    pub indent: String,
+    /// Collapse runs of blank lines to a single blank line
+    pub collapse_blank_lines: bool,
+    /// Join lines hard-wrapped mid-sentence back into one paragraph
+    pub merge_broken_lines: bool,
+    /// Full-width punctuation next to CJK text, half-width letters and digits
+    pub normalize_punctuation: bool,
+}
+
+impl Default for ContentFormatOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            indent: "\u{3000}\u{3000}".to_string(),
+            collapse_blank_lines: true,
+            merge_broken_lines: true,
+            normalize_punctuation: false,
+        }
+    }
+}
+
+/// Characters a paragraph may end with without continuing on the next line
+const SENTENCE_END: &[char] = &[
+    '。', '！', '？', '…', '」', '』', '”', '’', '"', '\'', '）', ')', '】', '》', '；', ';', '：', ':', '～', '~',
+    '—', '.', '!', '?', '*', '#', '=', '-',
+];
+
+/// Re-format chapter text into evenly indented paragraphs
+///
+/// Every line loses its leading and trailing whitespace (including full-width
+/// spaces and `&nbsp;`) and is re-indented with `options.indent`. Lines that do
+/// not end a sentence are joined with the next line when `merge_broken_lines`
+/// is set; blank lines only ever separate paragraphs.
+pub fn format_content(content: &str, options: &ContentFormatOptions) -> String {
+    if !options.enabled {
+        return content.to_string();
+    }
+    let text = content.replace("\r\n", "\n").replace('\r', "\n").replace("&nbsp;", " ");
+
+    // An empty string stands for a blank line
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.trim_matches(|c: char| c.is_whitespace() || c == '\u{3000}');
+        if line.is_empty() {
+            // Leading blank lines go, and with collapsing every blank line after the first
+            let after_blank = lines.last().is_some_and(|l| l.is_empty());
+            let dropped = lines.is_empty() || (options.collapse_blank_lines && after_blank);
+            if !dropped {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        let line = if options.normalize_punctuation {
+            normalize_punctuation_width(line)
+        } else {
+            line.to_string()
+        };
+        let continues = |previous: &String| !previous.is_empty() && !previous.ends_with(SENTENCE_END);
+        match lines.last_mut() {
+            Some(previous) if options.merge_broken_lines && continues(previous) => join_wrapped_line(previous, &line),
+            _ => lines.push(line),
+        }
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+
+    lines
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", options.indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Append a hard-wrapped continuation, keeping a space between two words of Latin text
+fn join_wrapped_line(previous: &mut String, next: &str) {
+    let ends_word = previous.ends_with(|c: char| c.is_ascii_alphanumeric() || c == ',');
+    let starts_word = next.starts_with(|c: char| c.is_ascii_alphanumeric());
+    if ends_word && starts_word {
+        previous.push(' ');
+    }
+    previous.push_str(next);
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff60}')
+}
+
+/// Full-width `，！？：；（）` next to CJK text, half-width letters and digits everywhere
+fn normalize_punctuation_width(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    for (i, &c) in chars.iter().enumerate() {
+        // Full-width letters and digits (Ａ, ０) to ASCII
+        if c.is_alphanumeric() && ('\u{ff10}'..='\u{ff5a}').contains(&c) {
+            out.push(char::from_u32(c as u32 - 0xfee0).unwrap_or(c));
+            continue;
+        }
+        let after_cjk = i > 0 && is_cjk(chars[i - 1]);
+        let before_cjk = chars.get(i + 1).is_some_and(|&next| is_cjk(next));
+        let full = match c {
+            ',' if after_cjk => '，',
+            '!' if after_cjk => '！',
+            '?' if after_cjk => '？',
+            ':' if after_cjk => '：',
+            ';' if after_cjk => '；',
+            '(' if before_cjk => '（',
+            ')' if after_cjk => '）',
+            _ => c,
+        };
+        out.push(full);
+    }
+    out
+}
+
 /// Get the common cache directory
 pub fn get_cache_dir() -> std::path::PathBuf {
     std::env::current_dir()
@@ -196,3 +367,116 @@ pub fn get_cache_dir() -> std::path::PathBuf {
         .join("data")
         .join("cache")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain() -> ContentFormatOptions {
+        ContentFormatOptions {
+            indent: String::new(),
+            collapse_blank_lines: false,
+            merge_broken_lines: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_collapses_blank_lines() {
+        let content = "第一段。\n\n\n\n第二段。\n\n第三段。\n\n\n";
+        let options = ContentFormatOptions {
+            collapse_blank_lines: true,
+            ..plain()
+        };
+        assert_eq!(format_content(content, &options), "第一段。\n\n第二段。\n\n第三段。");
+        // Without collapsing only the trailing blank lines go
+        let kept = format_content(content, &plain());
+        assert_eq!(kept, "第一段。\n\n\n\n第二段。\n\n第三段。");
+    }
+
+    #[test]
+    fn test_format_reindents_paragraphs() {
+        let content = "&nbsp;&nbsp;&nbsp;&nbsp;他说。\r\n\u{3000}\u{3000}她笑了。\r\n\t  \u{a0}风停了。  ";
+        let options = ContentFormatOptions {
+            indent: "\u{3000}\u{3000}".to_string(),
+            ..plain()
+        };
+        assert_eq!(
+            format_content(content, &options),
+            "\u{3000}\u{3000}他说。\n\u{3000}\u{3000}她笑了。\n\u{3000}\u{3000}风停了。"
+        );
+        assert_eq!(format_content(content, &plain()), "他说。\n她笑了。\n风停了。");
+    }
+
+    #[test]
+    fn test_format_merges_hard_wrapped_lines() {
+        let options = ContentFormatOptions {
+            merge_broken_lines: true,
+            ..plain()
+        };
+        let content = "他推开门，院子里的雪\n已经积了半尺深。\n“走吧，”他说，\n“天快黑了。”\n远处传来几声\n\n犬吠。";
+        assert_eq!(
+            format_content(content, &options),
+            "他推开门，院子里的雪已经积了半尺深。\n“走吧，”他说，“天快黑了。”\n远处传来几声\n\n犬吠。"
+        );
+        let content = "He opened the\ndoor and looked out.\nSnow everywhere!\nIt was\n3 feet deep.";
+        assert_eq!(
+            format_content(content, &options),
+            "He opened the door and looked out.\nSnow everywhere!\nIt was 3 feet deep."
+        );
+    }
+
+    #[test]
+    fn test_format_normalizes_punctuation() {
+        let options = ContentFormatOptions {
+            normalize_punctuation: true,
+            ..plain()
+        };
+        assert_eq!(
+            format_content("他说,你好!(笑)真的?ＡＢＣ１２３", &options),
+            "他说，你好！（笑）真的？ABC123"
+        );
+        // Latin text and numbers keep their half-width punctuation
+        assert_eq!(format_content("3.14, ok: (yes)", &options), "3.14, ok: (yes)");
+        assert_eq!(format_content("他说,你好!", &plain()), "他说,你好!");
+    }
+
+    #[test]
+    fn test_format_composition() {
+        let content = concat!(
+            "&nbsp;&nbsp;&nbsp;&nbsp;第一章 雪夜\n",
+            "\n\n\n",
+            "    他推开门,院子里的雪\n",
+            "已经积了半尺深。\n",
+            "\n",
+            "\u{3000}\u{3000}远处传来犬吠!\n",
+            "\n\n\n\n",
+        );
+        let options = ContentFormatOptions {
+            normalize_punctuation: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_content(content, &options),
+            "\u{3000}\u{3000}第一章 雪夜\n\n\u{3000}\u{3000}他推开门，院子里的雪已经积了半尺深。\n\n\u{3000}\u{3000}远处传来犬吠！"
+        );
+        let disabled = ContentFormatOptions {
+            enabled: false,
+            ..options
+        };
+        assert_eq!(format_content(content, &disabled), content);
+    }
+
+    #[test]
+    fn test_format_options_defaults() {
+        let options: ContentFormatOptions = serde_json::from_str(r#"{"indent":"  "}"#).unwrap();
+        assert_eq!(
+            options,
+            ContentFormatOptions {
+                indent: "  ".to_string(),
+                ..Default::default()
+            }
+        );
+        assert!(!options.normalize_punctuation);
+    }
+}
@@ -3,134 +3,326 @@
 //! This module provides WebView rendering capabilities using headless Chrome.
 //! It is compiled conditionally with the "webview" feature flag.
 //!
+//! Browsers are kept in a small pool of long-lived instances. A request checks
+//! an instance out, navigates its tab and resets it to `about:blank` on checkin,
+//! so Chrome only starts once per instance. Instances that crash are dropped and
+//! relaunched on the next checkout.
+//!
+//! Configuration (environment variables):
+//! - `WEBVIEW_POOL_SIZE` - number of browser instances (default 2)
+//! - `WEBVIEW_CHROME_PATH` - Chrome/Chromium binary (default: auto-detect)
+//! - `WEBVIEW_ARGS` - extra launch arguments, separated by whitespace
+//! - `WEBVIEW_CHECKOUT_TIMEOUT` - seconds to wait for a free instance (default 30)
+//!
 //! Usage:
 //! ```toml
 //! reader-rs = { features = ["webview"] }
 //! ```
 
 use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Default number of pooled browser instances
+pub const DEFAULT_POOL_SIZE: usize = 2;
+
+/// Default time to wait for a free browser instance
+pub const DEFAULT_CHECKOUT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pool and launch settings for WebView browsers
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebViewConfig {
+    pub pool_size: usize,
+    pub chrome_path: Option<PathBuf>,
+    pub extra_args: Vec<String>,
+    pub checkout_timeout: Duration,
+}
 
-#[cfg(feature = "webview")]
-use std::sync::{Arc, Mutex};
+impl Default for WebViewConfig {
+    fn default() -> Self {
+        Self {
+            pool_size: DEFAULT_POOL_SIZE,
+            chrome_path: None,
+            extra_args: Vec::new(),
+            checkout_timeout: DEFAULT_CHECKOUT_TIMEOUT,
+        }
+    }
+}
 
-/// Global browser pool for reusing Chrome instance
-#[cfg(feature = "webview")]
-static BROWSER_POOL: Mutex<Option<Arc<WebViewPool>>> = Mutex::new(None);
+impl WebViewConfig {
+    /// Read the configuration from `WEBVIEW_*` environment variables
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
 
-/// WebView pool for managing a shared Chrome browser instance
-#[cfg(feature = "webview")]
-pub struct WebViewPool {
-    browser: headless_chrome::Browser,
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Self::default();
+        let number = |key: &str| var(key).and_then(|v| v.trim().parse::<u64>().ok());
+        Self {
+            pool_size: number("WEBVIEW_POOL_SIZE")
+                .map(|n| (n as usize).max(1))
+                .unwrap_or(defaults.pool_size),
+            chrome_path: var("WEBVIEW_CHROME_PATH")
+                .filter(|p| !p.trim().is_empty())
+                .map(PathBuf::from),
+            extra_args: var("WEBVIEW_ARGS")
+                .map(|args| args.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            checkout_timeout: number("WEBVIEW_CHECKOUT_TIMEOUT")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.checkout_timeout),
+        }
+    }
 }
 
-#[cfg(feature = "webview")]
-impl WebViewPool {
-    /// Get or create the global browser pool (lazy singleton)
-    pub fn global() -> Result<Arc<Self>> {
-        let mut lock = BROWSER_POOL.lock().map_err(|e| anyhow!("Failed to lock browser pool: {}", e))?;
-        
-        if let Some(pool) = lock.as_ref() {
-            return Ok(pool.clone());
+/// A single page render
+#[derive(Debug, Clone, Default)]
+pub struct RenderRequest<'a> {
+    /// HTML content to load directly (data: URL)
+    pub html: Option<&'a str>,
+    /// URL to navigate to (used if html is None)
+    pub url: Option<&'a str>,
+    /// JavaScript to evaluate after load; the page HTML is returned when None
+    pub js: Option<&'a str>,
+    /// CSS selector to wait for before evaluating `js`
+    pub wait_selector: Option<&'a str>,
+    /// Extra time to wait before evaluating `js` (`webViewDelay`)
+    pub wait: Option<Duration>,
+}
+
+/// A browser instance that can be pooled
+pub trait BrowserInstance: Send {
+    fn render(&mut self, request: &RenderRequest) -> Result<String>;
+
+    /// Return the instance to a blank state before it is reused
+    fn reset(&mut self) -> Result<()>;
+
+    /// Whether the underlying browser process is still usable
+    fn is_alive(&self) -> bool;
+}
+
+type Launcher<I> = Box<dyn Fn() -> Result<I> + Send + Sync>;
+
+struct PoolState<I> {
+    idle: Vec<I>,
+    /// Instances launched and not yet dropped, idle or checked out
+    live: usize,
+}
+
+/// Fixed-size pool of browser instances, launched lazily
+pub struct InstancePool<I: BrowserInstance> {
+    launcher: Launcher<I>,
+    size: usize,
+    checkout_timeout: Duration,
+    state: Mutex<PoolState<I>>,
+    available: Condvar,
+}
+
+impl<I: BrowserInstance> InstancePool<I> {
+    pub fn new(
+        size: usize,
+        checkout_timeout: Duration,
+        launcher: impl Fn() -> Result<I> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            launcher: Box::new(launcher),
+            size: size.max(1),
+            checkout_timeout,
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                live: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<I>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take an idle instance, launching one while below the pool size,
+    /// otherwise wait up to the checkout timeout for one to be returned
+    pub fn checkout(&self) -> Result<PooledInstance<'_, I>> {
+        let deadline = Instant::now() + self.checkout_timeout;
+        let mut state = self.lock();
+        loop {
+            while let Some(instance) = state.idle.pop() {
+                if instance.is_alive() {
+                    return Ok(PooledInstance {
+                        pool: self,
+                        instance: Some(instance),
+                    });
+                }
+                tracing::warn!("Dropping crashed WebView browser instance");
+                state.live -= 1;
+            }
+
+            if state.live < self.size {
+                state.live += 1;
+                drop(state);
+                return match (self.launcher)() {
+                    Ok(instance) => Ok(PooledInstance {
+                        pool: self,
+                        instance: Some(instance),
+                    }),
+                    Err(e) => {
+                        self.lock().live -= 1;
+                        self.available.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(anyhow!(
+                    "Timed out after {:?} waiting for a WebView browser",
+                    self.checkout_timeout
+                ));
+            }
+            state = self
+                .available
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Reset an instance and return it to the pool; broken instances are dropped
+    fn checkin(&self, mut instance: I) {
+        let reusable = instance.is_alive() && instance.reset().is_ok();
+        let mut state = self.lock();
+        if reusable {
+            state.idle.push(instance);
+        } else {
+            tracing::warn!("WebView browser instance crashed, it will be relaunched");
+            state.live -= 1;
         }
+        drop(state);
+        self.available.notify_one();
+    }
+
+    /// Number of launched instances (idle or checked out)
+    pub fn live(&self) -> usize {
+        self.lock().live
+    }
 
-        let pool = Arc::new(Self::new_internal()?);
-        *lock = Some(pool.clone());
-        Ok(pool)
+    /// Number of instances waiting in the pool
+    pub fn idle(&self) -> usize {
+        self.lock().idle.len()
     }
+}
+
+/// An instance checked out of the pool, returned on drop
+pub struct PooledInstance<'a, I: BrowserInstance> {
+    pool: &'a InstancePool<I>,
+    instance: Option<I>,
+}
 
-    /// Internal: Create a new browser instance with stealth mode enabled
-    fn new_internal() -> Result<Self> {
+impl<I: BrowserInstance> std::ops::Deref for PooledInstance<'_, I> {
+    type Target = I;
+
+    fn deref(&self) -> &I {
+        self.instance.as_ref().expect("instance present until drop")
+    }
+}
+
+impl<I: BrowserInstance> std::ops::DerefMut for PooledInstance<'_, I> {
+    fn deref_mut(&mut self) -> &mut I {
+        self.instance.as_mut().expect("instance present until drop")
+    }
+}
+
+impl<I: BrowserInstance> Drop for PooledInstance<'_, I> {
+    fn drop(&mut self) {
+        if let Some(instance) = self.instance.take() {
+            self.pool.checkin(instance);
+        }
+    }
+}
+
+/// Global browser pool, created on first use
+#[cfg(feature = "webview")]
+static BROWSER_POOL: once_cell::sync::OnceCell<InstancePool<ChromeInstance>> =
+    once_cell::sync::OnceCell::new();
+
+/// A headless Chrome process with one reusable tab
+#[cfg(feature = "webview")]
+pub struct ChromeInstance {
+    browser: headless_chrome::Browser,
+    tab: std::sync::Arc<headless_chrome::Tab>,
+}
+
+#[cfg(feature = "webview")]
+impl ChromeInstance {
+    /// Launch a browser with stealth mode enabled
+    pub fn launch(config: &WebViewConfig) -> Result<Self> {
         use headless_chrome::{Browser, LaunchOptions};
-        use std::ffi::OsStr;
-        use std::time::Duration;
+        use std::ffi::{OsStr, OsString};
 
-        tracing::info!("Initializing WebView browser pool with stealth mode (one-time startup)");
+        tracing::info!("Launching WebView browser instance with stealth mode");
 
         // Stealth mode arguments to bypass bot detection (e.g., Cloudflare)
-        let stealth_args = vec![
+        let mut args: Vec<OsString> = [
             // Disable automation detection flags
-            OsStr::new("--disable-blink-features=AutomationControlled"),
+            "--disable-blink-features=AutomationControlled",
             // Disable infobars that reveal automation
-            OsStr::new("--disable-infobars"),
+            "--disable-infobars",
             // Standard performance flags
-            OsStr::new("--disable-dev-shm-usage"),
-            OsStr::new("--no-first-run"),
-            OsStr::new("--no-default-browser-check"),
+            "--disable-dev-shm-usage",
+            "--no-first-run",
+            "--no-default-browser-check",
             // Reduce fingerprinting surface
-            OsStr::new("--disable-extensions"),
-            OsStr::new("--disable-popup-blocking"),
+            "--disable-extensions",
+            "--disable-popup-blocking",
             // Use realistic window size
-            OsStr::new("--window-size=1920,1080"),
+            "--window-size=1920,1080",
             // Set User-Agent to realistic Chrome
-            OsStr::new("--user-agent=Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"),
-        ];
+            "--user-agent=Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        args.extend(config.extra_args.iter().map(OsString::from));
 
         let browser = Browser::new(LaunchOptions {
             headless: true,
             sandbox: false,
             enable_gpu: false,
             enable_logging: false,
-            idle_browser_timeout: Duration::from_secs(300), // 5 minutes idle
-            args: stealth_args,
+            path: config.chrome_path.clone(),
+            // Pooled instances stay up between requests
+            idle_browser_timeout: Duration::from_secs(24 * 60 * 60),
+            args: args.iter().map(OsString::as_os_str).collect::<Vec<&OsStr>>(),
             ..Default::default()
         })?;
+        let tab = browser
+            .new_tab()
+            .map_err(|e| anyhow!("Failed to create tab: {}", e))?;
+        // Set a longer timeout for Cloudflare-protected sites
+        tab.set_default_timeout(Duration::from_secs(60));
 
-        Ok(Self { browser })
+        Ok(Self { browser, tab })
     }
 
-    /// Create a new tab for a request
-    pub fn new_tab(&self) -> Result<std::sync::Arc<headless_chrome::Tab>> {
-        self.browser
-            .new_tab()
-            .map_err(|e| anyhow!("Failed to create tab: {}", e))
+    fn title_is_challenge(&self) -> Result<bool> {
+        let title = self.tab.evaluate("document.title", true)?;
+        Ok(match &title.value {
+            Some(serde_json::Value::String(s)) => s.contains("Just a moment") || s.contains("请稍候"),
+            _ => false,
+        })
     }
 }
 
-/// WebView executor for rendering dynamic pages
-///
-/// This uses headless Chrome to render JavaScript-heavy pages
-/// that cannot be parsed with simple HTTP requests.
-#[cfg(feature = "webview")]
-pub struct WebViewExecutor {
-    pool: Arc<WebViewPool>,
-}
-
 #[cfg(feature = "webview")]
-impl WebViewExecutor {
-    /// Create a new WebView executor (uses global pool)
-    ///
-    /// This reuses the shared Chrome browser instance.
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            pool: WebViewPool::global()?,
-        })
-    }
-
-    /// Render a page and optionally execute JavaScript
-    ///
-    /// # Arguments
-    /// * `html` - Optional HTML content to load directly (data: URL)
-    /// * `url` - URL to navigate to (used if html is None, or as base for relative resources)
-    /// * `js` - Optional JavaScript to execute after page load
-    ///
-    /// # Returns
-    /// The result of the JavaScript execution, or the page HTML if no JS provided
-    pub fn render(
-        &self,
-        html: Option<&str>,
-        url: Option<&str>,
-        js: Option<&str>,
-    ) -> Result<String> {
+impl BrowserInstance for ChromeInstance {
+    fn render(&mut self, request: &RenderRequest) -> Result<String> {
         use std::thread;
-        use std::time::Duration;
 
-        let tab = self.pool.new_tab()?;
-
-        // Set a longer timeout for Cloudflare-protected sites
-        tab.set_default_timeout(Duration::from_secs(60));
+        let tab = &self.tab;
 
         // Inject stealth JavaScript to hide automation markers
-        // This runs before any page navigation to set up the environment
         let stealth_js = r#"
             // Hide navigator.webdriver
             Object.defineProperty(navigator, 'webdriver', {
@@ -153,12 +345,10 @@ impl WebViewExecutor {
         "#;
 
         // Navigate to URL or load HTML directly
-        if let Some(html_content) = html {
-            // Load HTML as data URL
+        if let Some(html_content) = request.html {
             let encoded = urlencoding::encode(html_content);
-            let data_url = format!("data:text/html;charset=utf-8,{}", encoded);
-            tab.navigate_to(&data_url)?;
-        } else if let Some(page_url) = url {
+            tab.navigate_to(&format!("data:text/html;charset=utf-8,{}", encoded))?;
+        } else if let Some(page_url) = request.url {
             tab.navigate_to(page_url)?;
         } else {
             return Err(anyhow!("Either html or url must be provided"));
@@ -171,27 +361,12 @@ impl WebViewExecutor {
         let _ = tab.evaluate(stealth_js, false);
 
         // Check if we hit a Cloudflare challenge page
-        let title_check = tab.evaluate("document.title", true)?;
-        let is_cloudflare = match &title_check.value {
-            Some(serde_json::Value::String(s)) => {
-                s.contains("Just a moment") || s.contains("请稍候")
-            }
-            _ => false,
-        };
-
-        if is_cloudflare {
+        if self.title_is_challenge()? {
             tracing::debug!("Cloudflare challenge detected, waiting for resolution...");
             // Wait for Cloudflare JS challenge to complete (up to 10 seconds)
             for _ in 0..20 {
                 thread::sleep(Duration::from_millis(500));
-                let title_check = tab.evaluate("document.title", true)?;
-                let still_challenging = match &title_check.value {
-                    Some(serde_json::Value::String(s)) => {
-                        s.contains("Just a moment") || s.contains("请稍候")
-                    }
-                    _ => false,
-                };
-                if !still_challenging {
+                if !self.title_is_challenge()? {
                     tracing::debug!("Cloudflare challenge passed!");
                     break;
                 }
@@ -200,28 +375,87 @@ impl WebViewExecutor {
             let _ = tab.evaluate(stealth_js, false);
         }
 
-        // Small delay to ensure page is fully rendered
-        thread::sleep(Duration::from_millis(500));
+        if let Some(selector) = request.wait_selector {
+            tab.wait_for_element(selector)?;
+        }
+        // Small delay to ensure page is fully rendered, or the source's webViewDelay
+        thread::sleep(request.wait.unwrap_or(Duration::from_millis(500)));
+
+        match request.js {
+            Some(js_code) => {
+                let eval_result = tab.evaluate(js_code, true)?;
+                Ok(match eval_result.value {
+                    Some(serde_json::Value::String(s)) => s,
+                    Some(v) => v.to_string(),
+                    None => String::new(),
+                })
+            }
+            None => Ok(tab.get_content()?),
+        }
+    }
 
-        // Execute JavaScript if provided
-        let result = if let Some(js_code) = js {
-            // Execute the JS and get result
-            let eval_result = tab.evaluate(js_code, true)?;
+    fn reset(&mut self) -> Result<()> {
+        self.tab.navigate_to("about:blank")?.wait_until_navigated()?;
+        Ok(())
+    }
 
-            match eval_result.value {
-                Some(serde_json::Value::String(s)) => s,
-                Some(v) => v.to_string(),
-                None => String::new(),
-            }
-        } else {
-            // Return page source
-            tab.get_content()?
-        };
+    fn is_alive(&self) -> bool {
+        self.browser.get_version().is_ok()
+    }
+}
 
-        // Close the tab
-        drop(tab);
+/// WebView executor for rendering dynamic pages
+///
+/// This uses headless Chrome to render JavaScript-heavy pages
+/// that cannot be parsed with simple HTTP requests.
+#[cfg(feature = "webview")]
+pub struct WebViewExecutor {
+    pool: &'static InstancePool<ChromeInstance>,
+}
 
-        Ok(result)
+#[cfg(feature = "webview")]
+impl WebViewExecutor {
+    /// Create a WebView executor backed by the global browser pool
+    pub fn new() -> Result<Self> {
+        let pool = BROWSER_POOL.get_or_init(|| {
+            let config = WebViewConfig::from_env();
+            tracing::info!(
+                "Initializing WebView browser pool ({} instances)",
+                config.pool_size
+            );
+            InstancePool::new(config.pool_size, config.checkout_timeout, move || {
+                ChromeInstance::launch(&config)
+            })
+        });
+        Ok(Self { pool })
+    }
+
+    /// Render a page on a pooled browser
+    pub fn render_request(&self, request: &RenderRequest) -> Result<String> {
+        self.pool.checkout()?.render(request)
+    }
+
+    /// Render a page and optionally execute JavaScript
+    ///
+    /// # Arguments
+    /// * `html` - Optional HTML content to load directly (data: URL)
+    /// * `url` - URL to navigate to (used if html is None, or as base for relative resources)
+    /// * `js` - Optional JavaScript to execute after page load
+    ///
+    /// # Returns
+    /// The result of the JavaScript execution, or the page HTML if no JS provided
+    pub fn render(
+        &self,
+        html: Option<&str>,
+        url: Option<&str>,
+        js: Option<&str>,
+    ) -> Result<String> {
+        self.render_request(&RenderRequest {
+            html,
+            url,
+            js,
+            ..Default::default()
+        })
     }
 
     /// Simple fetch with WebView (for pages requiring JavaScript)
@@ -248,6 +482,11 @@ impl WebViewExecutor {
         ))
     }
 
+    /// Stub render_request method
+    pub fn render_request(&self, _request: &RenderRequest) -> Result<String> {
+        Err(anyhow!("WebView support not enabled"))
+    }
+
     /// Stub render method
     pub fn render(
         &self,
@@ -263,7 +502,7 @@ impl WebViewExecutor {
         Err(anyhow!("WebView support not enabled"))
     }
 
-    /// Stub execute_js method  
+    /// Stub execute_js method
     pub fn execute_js(&self, _url: &str, _js: &str) -> Result<String> {
         Err(anyhow!("WebView support not enabled"))
     }
@@ -282,6 +521,68 @@ pub fn try_create_webview() -> Option<WebViewExecutor> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Fake browser: renders its id and the requested URL, can be crashed from the test
+    struct FakeInstance {
+        id: usize,
+        crashed: Arc<AtomicBool>,
+        resets: Arc<AtomicUsize>,
+    }
+
+    impl BrowserInstance for FakeInstance {
+        fn render(&mut self, request: &RenderRequest) -> Result<String> {
+            Ok(format!("{}:{}", self.id, request.url.unwrap_or("")))
+        }
+
+        fn reset(&mut self) -> Result<()> {
+            self.resets.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn is_alive(&self) -> bool {
+            !self.crashed.load(Ordering::SeqCst)
+        }
+    }
+
+    struct FakeLauncher {
+        launched: Arc<AtomicUsize>,
+        crashed: Arc<Mutex<Vec<Arc<AtomicBool>>>>,
+        resets: Arc<AtomicUsize>,
+    }
+
+    fn fake_pool(size: usize, timeout: Duration) -> (InstancePool<FakeInstance>, FakeLauncher) {
+        let launcher = FakeLauncher {
+            launched: Arc::new(AtomicUsize::new(0)),
+            crashed: Arc::new(Mutex::new(Vec::new())),
+            resets: Arc::new(AtomicUsize::new(0)),
+        };
+        let (launched, crashed, resets) = (
+            launcher.launched.clone(),
+            launcher.crashed.clone(),
+            launcher.resets.clone(),
+        );
+        let pool = InstancePool::new(size, timeout, move || {
+            let flag = Arc::new(AtomicBool::new(false));
+            crashed.lock().unwrap().push(flag.clone());
+            Ok(FakeInstance {
+                id: launched.fetch_add(1, Ordering::SeqCst),
+                crashed: flag,
+                resets: resets.clone(),
+            })
+        });
+        (pool, launcher)
+    }
+
+    fn render(instance: &mut FakeInstance, url: &str) -> String {
+        instance
+            .render(&RenderRequest {
+                url: Some(url),
+                ..Default::default()
+            })
+            .unwrap()
+    }
 
     #[test]
     fn test_webview_availability() {
@@ -295,4 +596,97 @@ mod tests {
         // When webview is disabled, new() should fail
         assert!(WebViewExecutor::new().is_err());
     }
+
+    #[test]
+    fn test_config_from_vars() {
+        let vars = |key: &str| match key {
+            "WEBVIEW_POOL_SIZE" => Some("4".to_string()),
+            "WEBVIEW_CHROME_PATH" => Some("/usr/bin/chromium".to_string()),
+            "WEBVIEW_ARGS" => Some("--proxy-server=127.0.0.1:8080  --lang=zh-CN".to_string()),
+            _ => None,
+        };
+        let config = WebViewConfig::from_vars(vars);
+        assert_eq!(config.pool_size, 4);
+        assert_eq!(config.chrome_path, Some(PathBuf::from("/usr/bin/chromium")));
+        assert_eq!(config.extra_args, vec!["--proxy-server=127.0.0.1:8080", "--lang=zh-CN"]);
+        assert_eq!(config.checkout_timeout, DEFAULT_CHECKOUT_TIMEOUT);
+        assert_eq!(WebViewConfig::from_vars(|_| None), WebViewConfig::default());
+    }
+
+    #[test]
+    fn test_checkout_reuses_instances() {
+        let (pool, launcher) = fake_pool(2, Duration::from_millis(50));
+
+        for url in ["a", "b", "c"] {
+            let mut instance = pool.checkout().unwrap();
+            assert_eq!(render(&mut instance, url), format!("0:{}", url));
+        }
+        assert_eq!(launcher.launched.load(Ordering::SeqCst), 1);
+        assert_eq!(launcher.resets.load(Ordering::SeqCst), 3);
+
+        // Concurrent checkouts launch up to the pool size, then time out
+        let first = pool.checkout().unwrap();
+        let second = pool.checkout().unwrap();
+        assert_eq!((first.id, second.id), (0, 1));
+        let err = pool.checkout().err().unwrap();
+        assert!(err.to_string().contains("Timed out"));
+        assert_eq!(pool.live(), 2);
+
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(pool.checkout().unwrap().id, 0);
+    }
+
+    #[test]
+    fn test_waiting_checkout_gets_returned_instance() {
+        let (pool, _launcher) = fake_pool(1, Duration::from_secs(5));
+        let pool = Arc::new(pool);
+        let held = pool.checkout().unwrap();
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.checkout().map(|i| i.id).unwrap())
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        drop(held);
+        assert_eq!(waiter.join().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_crashed_instances_are_replaced() {
+        let (pool, launcher) = fake_pool(1, Duration::from_millis(50));
+
+        // Crash while checked out: dropped on checkin instead of pooled
+        let instance = pool.checkout().unwrap();
+        launcher.crashed.lock().unwrap()[0].store(true, Ordering::SeqCst);
+        drop(instance);
+        assert_eq!((pool.live(), pool.idle()), (0, 0));
+        assert_eq!(pool.checkout().unwrap().id, 1);
+
+        // Crash while idle: dropped on the next checkout
+        launcher.crashed.lock().unwrap()[1].store(true, Ordering::SeqCst);
+        let mut instance = pool.checkout().unwrap();
+        assert_eq!(render(&mut instance, "x"), "2:x");
+        assert_eq!(launcher.launched.load(Ordering::SeqCst), 3);
+        assert_eq!(pool.live(), 1);
+    }
+
+    #[test]
+    fn test_failed_launch_frees_slot() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let pool = InstancePool::new(1, Duration::from_millis(50), move || {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(anyhow!("chrome not found"));
+            }
+            Ok(FakeInstance {
+                id: 7,
+                crashed: Arc::new(AtomicBool::new(false)),
+                resets: Arc::new(AtomicUsize::new(0)),
+            })
+        });
+        assert!(pool.checkout().is_err());
+        assert_eq!(pool.live(), 0);
+        assert_eq!(pool.checkout().unwrap().id, 7);
+    }
 }
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 use axum::Router;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tower_http::services::{ServeDir, ServeFile};
@@ -37,10 +39,21 @@ async fn main() {
     let serve_dir = ServeDir::new(web_dir)
         .not_found_service(ServeFile::new(format!("{}/index.html", web_dir)));
 
+    let state = Arc::new(services::AppState::new());
+    state.spawn_kv_maintenance();
+    state.spawn_bookshelf_refresher();
+    state.spawn_source_health_monitor();
+    state.spawn_subscription_refresher();
+
+    let access = api::AccessConfig::from_env();
+    if access.token.is_some() {
+        tracing::info!("Access token required for /reader3 API");
+    }
+
     // 构建应用路由
     let app = Router::new()
         // API 路由
-        .nest("/reader3", api::routes())
+        .nest("/reader3", api::routes(state.clone(), &access))
         // 静态文件 (前端)
         .fallback_service(serve_dir)
         .layer(CorsLayer::permissive())
@@ -51,5 +64,49 @@ async fn main() {
     tracing::info!("🚀 Reader-RS server running on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let (signal_tx, signal_rx) = tokio::sync::oneshot::channel();
+    // 按 IP 限流需要连接的对端地址
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = signal_tx.send(());
+    });
+
+    // 收到退出信号后等待进行中的请求结束，最多 SHUTDOWN_TIMEOUT
+    tokio::select! {
+        result = server.into_future() => result.unwrap(),
+        _ = async {
+            if signal_rx.await.is_ok() {
+                tokio::time::sleep(services::SHUTDOWN_TIMEOUT).await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+        } => tracing::warn!("Requests still running after {:?}, exiting", services::SHUTDOWN_TIMEOUT),
+    }
+
+    // 写入防抖中的数据后再退出
+    state.shutdown().await;
+    tracing::info!("Reader-RS server stopped");
+}
+
+/// 等待 Ctrl+C 或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::HashMap;
+
+use crate::engine::utils::ContentFormatOptions;
 
 /// 书籍模型
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Book {
     pub book_url: String,
@@ -9,8 +13,18 @@ pub struct Book {
     pub author: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_url: Option<String>,
+    /// 手动设置的封面，书源更新与换源后保留
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom_cover_url: Option<String>,
+    /// 手动设置的书名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_name: Option<String>,
+    /// 手动设置的作者
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_author: Option<String>,
+    /// 手动设置的简介
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_intro: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub toc_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -38,12 +52,222 @@ pub struct Book {
     pub total_chapter_num: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub latest_chapter_title: Option<String>,
+    /// 最新章节变化的时间 (毫秒时间戳)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_chapter_time: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub can_update: Option<bool>,
+    /// 最近一次更新检查的时间 (毫秒时间戳)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_check_time: Option<i64>,
+    /// 最近一次获取书籍详情或目录失败的原因，成功后清除
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_update_error: Option<BookUpdateError>,
+    /// 更新检查发现新章节，打开阅读后清除
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_new_chapter: Option<bool>,
+    /// 书籍变量，JSON 对象字符串 (同 Legado)，规则中通过 `book.getVariable(key)` 读取
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variable: Option<String>,
+    /// 正文排版选项，getBookContent 未指定 format 时使用
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub formatting: Option<ContentFormatOptions>,
+}
+
+/// 书籍更新失败所在的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateStage {
+    /// 查找或加载书源 (书源缺失、已禁用或 jsLib 出错)
+    Source,
+    /// 获取书籍详情
+    Info,
+    /// 获取目录
+    Toc,
+}
+
+/// 书籍最近一次更新失败的原因
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookUpdateError {
+    /// 失败时间 (毫秒时间戳)
+    pub time: i64,
+    pub stage: UpdateStage,
+    /// 错误类型，同接口错误的 errorCode (如 NETWORK、PARSE_FAILED)
+    pub kind: String,
+    pub message: String,
+}
+
+/// 未指定 key 时读写的书籍变量 (Legado 的 customVariable)
+pub const BOOK_CUSTOM_VARIABLE_KEY: &str = "custom";
+
+impl Book {
+    /// 书籍变量表，variable 缺失或无法解析时为空
+    pub fn variable_map(&self) -> HashMap<String, String> {
+        self.variable
+            .as_deref()
+            .and_then(|v| serde_json::from_str(v).ok())
+            .unwrap_or_default()
+    }
+
+    /// 设置书籍变量，value 为空时删除该变量
+    pub fn put_variable(&mut self, key: &str, value: &str) {
+        let mut map = self.variable_map();
+        if value.is_empty() {
+            map.remove(key);
+        } else {
+            map.insert(key.to_string(), value.to_string());
+        }
+        self.variable = (!map.is_empty()).then(|| serde_json::to_string(&map).unwrap_or_default());
+    }
+
+    /// 手动设置的书籍信息
+    pub fn metadata(&self) -> BookMetadata {
+        BookMetadata {
+            custom_cover_url: self.custom_cover_url.clone(),
+            custom_name: self.custom_name.clone(),
+            custom_author: self.custom_author.clone(),
+            custom_intro: self.custom_intro.clone(),
+        }
+    }
+
+    /// 合并手动设置的书籍信息：未指定的项保持不变，空字符串清除该项
+    pub fn set_metadata(&mut self, metadata: BookMetadata) {
+        let fields = [
+            (&mut self.custom_cover_url, metadata.custom_cover_url),
+            (&mut self.custom_name, metadata.custom_name),
+            (&mut self.custom_author, metadata.custom_author),
+            (&mut self.custom_intro, metadata.custom_intro),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                let value = value.trim();
+                *field = (!value.is_empty()).then(|| value.to_string());
+            }
+        }
+    }
+
+    /// 清除全部手动设置，恢复书源提供的书籍信息
+    pub fn clear_metadata(&mut self) {
+        self.custom_cover_url = None;
+        self.custom_name = None;
+        self.custom_author = None;
+        self.custom_intro = None;
+    }
+
+    /// 返回给前端的书籍：书名、作者、简介与封面以手动设置的值为准
+    ///
+    /// 手动设置的各项仍随书籍返回，书源提供的原值保存在书架中，清除设置后恢复。
+    pub fn with_metadata(mut self) -> Self {
+        if let Some(name) = &self.custom_name {
+            self.name = name.clone();
+        }
+        if let Some(author) = &self.custom_author {
+            self.author = author.clone();
+        }
+        if self.custom_intro.is_some() {
+            self.intro = self.custom_intro.clone();
+        }
+        if self.custom_cover_url.is_some() {
+            self.cover_url = self.custom_cover_url.clone();
+        }
+        self
+    }
+
+    /// 保存前端提交的书籍时保留已保存的手动设置
+    ///
+    /// 提交的书籍来自 [`Self::with_metadata`]，与手动设置相同的书名等换回书源提供的原值。
+    pub fn keep_metadata(&mut self, old: &Book) {
+        let mut metadata = self.metadata();
+        let old_metadata = old.metadata();
+        for (field, old_field) in [
+            (&mut metadata.custom_cover_url, old_metadata.custom_cover_url),
+            (&mut metadata.custom_name, old_metadata.custom_name),
+            (&mut metadata.custom_author, old_metadata.custom_author),
+            (&mut metadata.custom_intro, old_metadata.custom_intro),
+        ] {
+            if field.is_none() {
+                *field = old_field;
+            }
+        }
+        self.set_metadata(metadata);
+
+        if self.custom_name.as_ref() == Some(&self.name) {
+            self.name = old.name.clone();
+        }
+        if self.custom_author.as_ref() == Some(&self.author) {
+            self.author = old.author.clone();
+        }
+        if self.custom_intro.is_some() && self.custom_intro == self.intro {
+            self.intro = old.intro.clone();
+        }
+        if self.custom_cover_url.is_some() && self.custom_cover_url == self.cover_url {
+            self.cover_url = old.cover_url.clone();
+        }
+    }
+}
+
+/// 手动设置的书籍信息，用于修正聚合书源错误或缺失的封面、作者等
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_cover_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_intro: Option<String>,
+}
+
+/// 阅读进度
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookProgress {
+    pub dur_chapter_index: i32,
+    #[serde(default)]
+    pub dur_chapter_pos: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dur_chapter_title: Option<String>,
+    #[serde(default)]
+    pub dur_chapter_time: i64,
+}
+
+impl BookProgress {
+    /// 从书籍中读取进度
+    pub fn from_book(book: &Book) -> Self {
+        Self {
+            dur_chapter_index: book.dur_chapter_index.unwrap_or(0),
+            dur_chapter_pos: book.dur_chapter_pos.unwrap_or(0),
+            dur_chapter_title: book.dur_chapter_title.clone(),
+            dur_chapter_time: book.dur_chapter_time.unwrap_or(0),
+        }
+    }
+
+    /// 写入书籍
+    pub fn apply_to(&self, book: &mut Book) {
+        book.dur_chapter_index = Some(self.dur_chapter_index);
+        book.dur_chapter_pos = Some(self.dur_chapter_pos);
+        book.dur_chapter_title = self.dur_chapter_title.clone();
+        book.dur_chapter_time = Some(self.dur_chapter_time);
+    }
+
+    /// 按 durChapterTime 合并：已存储的进度更新时保留已存储的值
+    ///
+    /// 返回最终进度，以及是否需要持久化。
+    pub fn reconcile(stored: &BookProgress, incoming: BookProgress) -> (BookProgress, bool) {
+        if stored.dur_chapter_time > incoming.dur_chapter_time {
+            (stored.clone(), false)
+        } else {
+            (incoming, true)
+        }
+    }
 }
 
 /// 搜索结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResult {
     pub book_url: String,
@@ -66,3 +290,101 @@ pub struct SearchResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub origin: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(index: i32, time: i64) -> BookProgress {
+        BookProgress {
+            dur_chapter_index: index,
+            dur_chapter_pos: index * 10,
+            dur_chapter_title: Some(format!("第{}章", index)),
+            dur_chapter_time: time,
+        }
+    }
+
+    #[test]
+    fn test_reconcile_last_writer_wins() {
+        let stored = progress(5, 2000);
+
+        // 较旧的进度不覆盖已存储的
+        let (result, changed) = BookProgress::reconcile(&stored, progress(3, 1000));
+        assert!(!changed);
+        assert_eq!(result, stored);
+
+        // 较新的进度覆盖
+        let (result, changed) = BookProgress::reconcile(&stored, progress(7, 3000));
+        assert!(changed);
+        assert_eq!(result.dur_chapter_index, 7);
+    }
+
+    #[test]
+    fn test_progress_round_trip() {
+        let mut book = Book {
+            book_url: "https://example.com/book/1".to_string(),
+            name: "书".to_string(),
+            ..Default::default()
+        };
+        progress(12, 1700000000000).apply_to(&mut book);
+
+        let json = serde_json::to_string(&book).unwrap();
+        assert!(json.contains("\"durChapterPos\":120"));
+        let restored: Book = serde_json::from_str(&json).unwrap();
+        assert_eq!(BookProgress::from_book(&restored), progress(12, 1700000000000));
+    }
+
+    #[test]
+    fn test_book_variable() {
+        let mut book: Book =
+            serde_json::from_str(r#"{"bookUrl":"u","name":"书","author":"","variable":"{\"custom\":\"a\"}"}"#).unwrap();
+        assert_eq!(book.variable_map()["custom"], "a");
+
+        book.put_variable("token", "t1");
+        assert_eq!(book.variable_map().len(), 2);
+        book.put_variable("custom", "");
+        book.put_variable("token", "");
+        assert_eq!(book.variable, None);
+    }
+
+    #[test]
+    fn test_book_metadata_override_and_revert() {
+        let mut book = Book {
+            book_url: "u".to_string(),
+            name: "书源书名".to_string(),
+            author: "书源作者".to_string(),
+            cover_url: Some("https://example.com/cover.jpg".to_string()),
+            ..Default::default()
+        };
+        book.set_metadata(BookMetadata {
+            custom_name: Some("正确书名".to_string()),
+            custom_cover_url: Some("reader3://cover/abc".to_string()),
+            ..Default::default()
+        });
+
+        let shown = book.clone().with_metadata();
+        assert_eq!(shown.name, "正确书名");
+        assert_eq!(shown.author, "书源作者");
+        assert_eq!(shown.cover_url.as_deref(), Some("reader3://cover/abc"));
+
+        // 前端回存展示的书籍：原值保持书源提供的值，手动设置保留
+        let mut saved = shown.clone();
+        saved.custom_name = None;
+        saved.keep_metadata(&book);
+        assert_eq!(saved.name, "书源书名");
+        assert_eq!(saved.cover_url.as_deref(), Some("https://example.com/cover.jpg"));
+        assert_eq!(saved.custom_name.as_deref(), Some("正确书名"));
+
+        // 空字符串只清除该项
+        book.set_metadata(BookMetadata {
+            custom_name: Some(String::new()),
+            ..Default::default()
+        });
+        assert_eq!(book.custom_name, None);
+        assert!(book.custom_cover_url.is_some());
+
+        book.clear_metadata();
+        assert_eq!(book.metadata(), BookMetadata::default());
+        assert_eq!(book.clone().with_metadata().name, "书源书名");
+    }
+}
@@ -0,0 +1,167 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// 内置净化规则的 ID 前缀
+pub const BUILTIN_FILTER_PREFIX: &str = "builtin-";
+
+/// 内置净化规则：常见的分页提示与加载残留
+const BUILTIN_PATTERNS: [(&str, &str); 7] = [
+    ("本章未完提示", r"（本章未完，请点击下一页继续阅读）"),
+    ("分页页码", r"\(第\d+/\d+页\)"),
+    ("单页页码", r"\(第\d+页\)"),
+    ("下一页提示", r"请点击下一页继续阅读"),
+    ("本章未完", r"本章未完，点击下一页继续阅读"),
+    ("加载提示", r"加载中..."),
+    ("翻页箭头", r"-->>"),
+];
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 正文净化规则，在书源清理之后、替换规则之前应用于正文
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentFilter {
+    /// 新规则保存时自动生成
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// 正则表达式
+    pub pattern: String,
+    /// 替换内容，支持 $1 等分组引用，为空表示删除
+    #[serde(default)]
+    pub replacement: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 作用的书源 URL，逗号分隔，为空表示全部书源
+    #[serde(default)]
+    pub scope: String,
+    /// 内置规则不可删除，只能停用
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+impl ContentFilter {
+    /// 是否作用于指定书源
+    pub fn in_scope(&self, origin: Option<&str>) -> bool {
+        let mut entries = self
+            .scope
+            .split([',', ';', '，'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .peekable();
+
+        if entries.peek().is_none() {
+            return true;
+        }
+        entries.any(|s| Some(s) == origin)
+    }
+
+    /// 编译正则，空或非法的规则返回错误信息
+    pub fn compile(&self) -> Result<Regex, String> {
+        if self.pattern.is_empty() {
+            return Err("pattern is empty".to_string());
+        }
+        Regex::new(&self.pattern).map_err(|e| format!("invalid pattern '{}': {}", self.pattern, e))
+    }
+}
+
+/// 内置的默认净化规则
+pub fn builtin_content_filters() -> Vec<ContentFilter> {
+    BUILTIN_PATTERNS
+        .iter()
+        .enumerate()
+        .map(|(i, (name, pattern))| ContentFilter {
+            id: format!("{}{}", BUILTIN_FILTER_PREFIX, i + 1),
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            replacement: String::new(),
+            enabled: true,
+            scope: String::new(),
+            builtin: true,
+        })
+        .collect()
+}
+
+/// 预编译的已启用净化规则
+#[derive(Debug, Default)]
+pub struct CompiledFilters {
+    entries: Vec<(ContentFilter, Regex)>,
+}
+
+impl CompiledFilters {
+    /// 编译已启用的规则，非法规则记录警告后跳过
+    pub fn compile(filters: &[ContentFilter]) -> Self {
+        let entries = filters
+            .iter()
+            .filter(|f| f.enabled)
+            .filter_map(|f| match f.compile() {
+                Ok(re) => Some((f.clone(), re)),
+                Err(e) => {
+                    tracing::warn!("Skip content filter '{}': {}", f.id, e);
+                    None
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// 按顺序应用作用域匹配的规则
+    pub fn apply(&self, text: &str, origin: Option<&str>) -> String {
+        self.entries
+            .iter()
+            .filter(|(filter, _)| filter.in_scope(origin))
+            .fold(text.to_string(), |acc, (filter, re)| {
+                re.replace_all(&acc, filter.replacement.as_str()).into_owned()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(pattern: &str, replacement: &str, scope: &str) -> ContentFilter {
+        ContentFilter {
+            id: pattern.to_string(),
+            name: String::new(),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            enabled: true,
+            scope: scope.to_string(),
+            builtin: false,
+        }
+    }
+
+    #[test]
+    fn test_builtin_filters() {
+        let compiled = CompiledFilters::compile(&builtin_content_filters());
+        let out = compiled.apply("正文(第1/3页)请点击下一页继续阅读-->>", None);
+        assert_eq!(out, "正文");
+    }
+
+    #[test]
+    fn test_scope_restricted_to_source_urls() {
+        let mut disabled = filter("正文", "X", "");
+        disabled.enabled = false;
+        let compiled = CompiledFilters::compile(&[
+            filter("广告", "", "https://a.com, https://b.com"),
+            filter(r"第(\d+)章", "Chapter $1", "https://b.com"),
+            disabled,
+        ]);
+
+        assert_eq!(compiled.apply("第1章正文广告", Some("https://a.com")), "第1章正文");
+        assert_eq!(compiled.apply("第1章正文广告", Some("https://b.com")), "Chapter 1正文");
+        assert_eq!(compiled.apply("第1章正文广告", Some("https://c.com")), "第1章正文广告");
+        assert_eq!(compiled.apply("第1章正文广告", None), "第1章正文广告");
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(filter("(unclosed", "", "").compile().unwrap_err().contains("invalid pattern"));
+        assert!(filter("", "", "").compile().is_err());
+    }
+}
@@ -1,11 +1,20 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// 书籍分组模型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BookGroup {
+    /// 新建分组时为 0 (前端只提交 groupName)
+    #[serde(default)]
     pub group_id: i64,
     pub group_name: String,
+    #[serde(default)]
     pub order: i32,
+    #[serde(default = "default_show")]
     pub show: bool,
 }
+
+fn default_show() -> bool {
+    true
+}
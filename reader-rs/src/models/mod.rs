@@ -1,16 +1,22 @@
 
 mod book;
 mod chapter;
-mod source;
+mod content_filter;
 mod source_rule;
+mod source_subscription;
+mod source_test;
 mod replace_rule;
 mod group;
 mod response;
+mod user;
 
 pub use book::*;
 pub use chapter::*;
-pub use source::*;
+pub use content_filter::*;
 pub use source_rule::*;
+pub use source_subscription::*;
+pub use source_test::*;
 pub use replace_rule::*;
 pub use group::*;
 pub use response::*;
+pub use user::*;
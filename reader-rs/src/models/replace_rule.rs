@@ -1,7 +1,9 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// 替换规则模型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReplaceRule {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -9,6 +11,7 @@ pub struct ReplaceRule {
     pub name: String,
     pub pattern: String,
     pub replacement: String,
+    #[serde(default)]
     pub scope: String,
     #[serde(rename = "isEnabled")]
     pub is_enabled: bool,
@@ -16,4 +19,148 @@ pub struct ReplaceRule {
     pub is_regex: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub group: Option<String>,
+    /// 是否同时作用于章节标题
+    #[serde(default)]
+    pub scope_title: bool,
+    /// 执行顺序，越小越先执行
+    #[serde(default)]
+    pub order: i32,
+}
+
+impl ReplaceRule {
+    /// 是否作用于指定书籍 (scope 为空表示全局，否则为逗号分隔的书名或书源 URL)
+    pub fn in_scope(&self, book_name: &str, origin: Option<&str>) -> bool {
+        let mut entries = self
+            .scope
+            .split([',', ';', '，'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .peekable();
+
+        if entries.peek().is_none() {
+            return true;
+        }
+        entries.any(|s| s == book_name || Some(s) == origin)
+    }
+}
+
+/// 作用于一本书的已启用替换规则，按 order 排序，正则只编译一次
+#[derive(Debug, Default)]
+pub struct CompiledReplaceRules {
+    /// 正则规则附带编译结果，字面量规则为 None
+    entries: Vec<(ReplaceRule, Option<Regex>)>,
+}
+
+impl CompiledReplaceRules {
+    /// 编译启用且作用域匹配的规则 (`is_title` 时只取作用于标题的规则)，非法正则记录警告后跳过
+    pub fn compile(rules: &[ReplaceRule], book_name: &str, origin: Option<&str>, is_title: bool) -> Self {
+        let mut active: Vec<&ReplaceRule> = rules
+            .iter()
+            .filter(|r| r.is_enabled && !r.pattern.is_empty() && (!is_title || r.scope_title))
+            .filter(|r| r.in_scope(book_name, origin))
+            .collect();
+        active.sort_by_key(|r| r.order);
+
+        let entries = active
+            .into_iter()
+            .filter_map(|rule| {
+                if !rule.is_regex {
+                    return Some((rule.clone(), None));
+                }
+                match Regex::new(&rule.pattern) {
+                    Ok(re) => Some((rule.clone(), Some(re))),
+                    Err(e) => {
+                        tracing::warn!("Skip invalid replace rule '{}': {}", rule.name, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// 按顺序应用到文本
+    pub fn apply(&self, text: &str) -> String {
+        self.entries.iter().fold(text.to_string(), |acc, (rule, re)| match re {
+            Some(re) => re.replace_all(&acc, rule.replacement.as_str()).into_owned(),
+            None => acc.replace(&rule.pattern, &rule.replacement),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_replace_rules(
+        rules: &[ReplaceRule],
+        text: &str,
+        book_name: &str,
+        origin: Option<&str>,
+        is_title: bool,
+    ) -> String {
+        CompiledReplaceRules::compile(rules, book_name, origin, is_title).apply(text)
+    }
+
+    fn rule(pattern: &str, replacement: &str, is_regex: bool, scope: &str, order: i32) -> ReplaceRule {
+        ReplaceRule {
+            id: None,
+            name: pattern.to_string(),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            scope: scope.to_string(),
+            is_enabled: true,
+            is_regex,
+            group: None,
+            scope_title: false,
+            order,
+        }
+    }
+
+    #[test]
+    fn test_apply_order_and_kind() {
+        let rules = vec![
+            // 后执行：把 B 替换为 C
+            rule("B", "C", false, "", 2),
+            // 先执行：把数字替换为 B
+            rule(r"\d+", "B", true, "", 1),
+            // 字面量规则不应按正则解释
+            rule("a.c", "X", false, "", 3),
+        ];
+
+        let out = apply_replace_rules(&rules, "123 abc a.c", "书", None, false);
+        assert_eq!(out, "C abc X");
+    }
+
+    #[test]
+    fn test_scope_filtering() {
+        let rules = vec![
+            rule("广告", "", false, "其他书,https://other.com", 0),
+            rule("作者", "某人", false, "测试书", 1),
+            rule("！", "!", false, "https://source.com", 2),
+        ];
+
+        let out = apply_replace_rules(&rules, "广告作者！", "测试书", Some("https://source.com"), false);
+        assert_eq!(out, "广告某人!");
+    }
+
+    #[test]
+    fn test_invalid_regex_and_disabled_skipped() {
+        let mut disabled = rule("正文", "X", false, "", 0);
+        disabled.is_enabled = false;
+        let rules = vec![rule("(unclosed", "X", true, "", 0), disabled, rule("正文", "内容", false, "", 1)];
+
+        let out = apply_replace_rules(&rules, "正文(unclosed", "书", None, false);
+        assert_eq!(out, "内容(unclosed");
+    }
+
+    #[test]
+    fn test_title_scope() {
+        let mut title_rule = rule("第(\\d+)章", "Chapter $1", true, "", 0);
+        title_rule.scope_title = true;
+        let rules = vec![title_rule, rule("章", "节", false, "", 1)];
+
+        let out = apply_replace_rules(&rules, "第12章", "书", None, true);
+        assert_eq!(out, "Chapter 12");
+    }
 }
@@ -6,25 +6,56 @@ use serde::{Deserialize, Serialize};
 pub struct ApiResponse<T> {
     pub is_success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error_msg: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
+    /// 分页查询时过滤后的总数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
 }
 
 impl<T> ApiResponse<T> {
     pub fn success(data: T) -> Self {
         Self {
             is_success: true,
+            error_code: None,
             error_msg: None,
+            detail: None,
             data: Some(data),
+            total: None,
         }
     }
 
+    /// 附带总数的成功响应
+    pub fn with_total(mut self, total: usize) -> Self {
+        self.total = Some(total);
+        self
+    }
+
     pub fn error(msg: &str) -> Self {
         Self {
             is_success: false,
+            error_code: None,
+            error_msg: Some(msg.to_string()),
+            detail: None,
+            data: None,
+            total: None,
+        }
+    }
+
+    /// 带错误码与详情的失败响应
+    pub fn error_with(code: &str, msg: &str, detail: Option<serde_json::Value>) -> Self {
+        Self {
+            is_success: false,
+            error_code: Some(code.to_string()),
             error_msg: Some(msg.to_string()),
+            detail,
             data: None,
+            total: None,
         }
     }
 }
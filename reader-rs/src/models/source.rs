@@ -1,30 +0,0 @@
-use serde::{Deserialize, Serialize};
-
-/// 书源模型
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BookSource {
-    pub book_url: String,
-    pub origin: String,
-    pub origin_name: String,
-    pub name: String,
-    pub author: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub latest_chapter_title: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub time: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(rename = "type")]
-    pub source_type: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub cover_url: Option<String>,
-}
-
-/// 书源分组
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BookSourceGroup {
-    pub name: String,
-    pub value: String,
-    pub count: i32,
-}
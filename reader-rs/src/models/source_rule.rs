@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{SourceHealth, SourceTestSummary};
 
 /// 书源完整定义 (用于解析规则)
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BookSourceFull {
     /// 书源 URL
@@ -17,9 +20,30 @@ pub struct BookSourceFull {
     /// 排序权重
     #[serde(default)]
     pub weight: i32,
+    /// 搜索响应时间的移动平均 (毫秒)，0 表示尚未测量
+    #[serde(default)]
+    pub respond_time: u64,
     /// 是否启用
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// 是否在发现页中显示
+    #[serde(default = "default_true")]
+    pub enabled_explore: bool,
+    /// 最近一次书源测试的结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_test: Option<SourceTestSummary>,
+    /// 导入该书源的订阅地址，删除订阅时可一并删除其书源
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_url: Option<String>,
+    /// 健康状态，连续测试失败达到阈值时标记为 broken (不会禁用书源)
+    #[serde(default, skip_serializing_if = "SourceHealth::is_healthy")]
+    pub health: SourceHealth,
+    /// 书源说明
+    #[serde(default)]
+    pub book_source_comment: Option<String>,
+    /// 书源变量说明，提示用户填写 `source.getVariable()` 读取的变量 (如 API token)
+    #[serde(default)]
+    pub variable_comment: Option<String>,
 
     // === 搜索规则 ===
     #[serde(default)]
@@ -52,6 +76,29 @@ pub struct BookSourceFull {
     pub header: Option<String>,
     #[serde(default)]
     pub login_url: Option<String>,
+    /// 登录界面字段定义 (JSON 数组)
+    #[serde(default)]
+    pub login_ui: Option<String>,
+    /// 登录检测脚本，返回 true 表示已登录
+    #[serde(default)]
+    pub login_check_js: Option<String>,
+    /// 遇到 Cloudflare 验证时是否通过 FlareSolverr 绕过
+    #[serde(default = "default_true")]
+    pub enabled_cloudflare_bypass: bool,
+
+    // === 防封禁 ===
+    /// 轮换使用的 User-Agent 列表，每个请求取下一个
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub user_agents: Vec<String>,
+    /// User-Agent 轮换方式: "random" 每次随机, "per-session" 每个会话固定一个；未设置 userAgents 时从内置列表中选取
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ua_rotation: Option<String>,
+    /// 每个请求在限速等待之外额外随机延迟的下限 (毫秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter_min_ms: Option<u64>,
+    /// 随机延迟上限 (毫秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter_max_ms: Option<u64>,
 
     // === JS 库 ===
     #[serde(default)]
@@ -63,7 +110,7 @@ fn default_true() -> bool {
 }
 
 /// 搜索规则
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchRule {
     #[serde(default)]
@@ -89,7 +136,7 @@ pub struct SearchRule {
 }
 
 /// 书籍信息规则
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BookInfoRule {
     #[serde(default)]
@@ -115,7 +162,7 @@ pub struct BookInfoRule {
 }
 
 /// 目录规则
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TocRule {
     #[serde(default)]
@@ -129,19 +176,25 @@ pub struct TocRule {
 }
 
 /// 正文规则
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentRule {
     #[serde(default)]
     pub content: String,
     #[serde(default)]
     pub next_content_url: String,
+    /// webView 加载正文页后执行的 JS，返回值作为页面内容
+    #[serde(default)]
+    pub web_js: String,
+    /// 正文规则匹配到多个内容块时，选用第一个匹配此正则的
+    #[serde(default)]
+    pub source_regex: String,
     #[serde(default)]
     pub replace_regex: String,
 }
 
 /// 发现规则
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ExploreRule {
     #[serde(default)]
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// 书源订阅：从远程书源文件导入书源，可每天自动更新
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceSubscription {
+    /// 远程书源文件地址
+    pub url: String,
+    #[serde(default)]
+    pub name: String,
+    /// 是否每天自动更新
+    #[serde(default)]
+    pub auto_update: bool,
+    /// 最近一次成功更新的时间 (毫秒时间戳)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_update_time: Option<i64>,
+    /// 最近一次更新新增的书源数
+    #[serde(default)]
+    pub added: usize,
+    /// 最近一次更新中规则有变化的书源数
+    #[serde(default)]
+    pub updated: usize,
+    /// 最近一次更新中未变化的书源数
+    #[serde(default)]
+    pub unchanged: usize,
+    /// 最近一次更新中被跳过的无效书源数
+    #[serde(default)]
+    pub invalid: usize,
+    /// 最近一次更新中因同 URL 的书源是手动导入或属于其他订阅而跳过的书源数
+    #[serde(default)]
+    pub skipped: usize,
+    /// 最近一次更新失败的原因，成功后清除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
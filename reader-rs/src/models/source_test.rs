@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// 书源测试的阶段，按执行顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceTestStage {
+    Search,
+    BookInfo,
+    Toc,
+    Content,
+}
+
+impl SourceTestStage {
+    pub const ALL: [SourceTestStage; 4] = [Self::Search, Self::BookInfo, Self::Toc, Self::Content];
+}
+
+/// 单个阶段的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StageStatus {
+    Passed,
+    Failed,
+    Timeout,
+    /// 前一阶段失败，未执行
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StageResult {
+    pub stage: SourceTestStage,
+    pub status: StageStatus,
+    /// 阶段耗时 (毫秒)
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 书源测试成绩单：搜索 → 详情 → 目录 → 首章正文
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceScorecard {
+    pub book_source_url: String,
+    pub book_source_name: String,
+    /// 全部阶段通过
+    pub passed: bool,
+    pub stages: Vec<StageResult>,
+    /// 搜索使用的关键字
+    pub key: String,
+    pub book_name: Option<String>,
+    pub chapter_count: Option<usize>,
+    /// 首章正文的前 200 个字符
+    pub content_sample: Option<String>,
+    /// 总耗时 (毫秒)
+    pub elapsed_ms: u64,
+    /// 测试时间 (毫秒时间戳)
+    pub tested_at: i64,
+}
+
+impl SourceScorecard {
+    /// 第一个未通过的阶段
+    pub fn failed_stage(&self) -> Option<&StageResult> {
+        self.stages.iter().find(|s| s.status != StageStatus::Passed)
+    }
+}
+
+/// 保存在书源上的最近一次测试结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceTestSummary {
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_stage: Option<SourceTestStage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 测试时间 (毫秒时间戳)
+    pub tested_at: i64,
+    /// 截至本次的连续失败次数
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub consecutive_failures: u32,
+    /// 连续失败中第一次失败的时间 (毫秒时间戳)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<i64>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl From<&SourceScorecard> for SourceTestSummary {
+    fn from(card: &SourceScorecard) -> Self {
+        let failed = card.failed_stage();
+        Self {
+            passed: card.passed,
+            failed_stage: failed.map(|s| s.stage),
+            error: failed.and_then(|s| s.error.clone()),
+            tested_at: card.tested_at,
+            consecutive_failures: 0,
+            failing_since: None,
+        }
+    }
+}
+
+/// 书源健康状态，由定期检查与书源测试的连续失败次数决定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceHealth {
+    #[default]
+    Healthy,
+    /// 最近的测试失败，尚未达到失效条件
+    Degraded,
+    /// 长时间连续失败，书源仍保持启用
+    Broken,
+}
+
+impl SourceHealth {
+    pub fn is_healthy(&self) -> bool {
+        *self == Self::Healthy
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// 多用户模式下的账号 (users.json)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub username: String,
+    #[serde(default)]
+    pub salt: String,
+    /// 加盐后的密码哈希 (hex)
+    #[serde(default)]
+    pub password_hash: String,
+    /// 手工编辑 users.json 时可只填明文密码，加载后替换为哈希
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
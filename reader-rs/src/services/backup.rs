@@ -0,0 +1,767 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local};
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+use std::time::Duration;
+use tokio::fs;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::local_epub::{element_text, find_elements, parse_xml};
+use super::{Migration, ServiceError, KV_FILE};
+use crate::models::{BookGroup, BookSourceFull, ContentFilter, ReplaceRule};
+use crate::storage::bookshelf::{BookshelfStore, LEGACY_BOOKSHELF_FILE};
+use crate::storage::kv::KvData;
+use crate::storage::FileStorage;
+
+/// 备份包含的数据文件 (阅读进度保存在书架中)
+pub const BACKUP_FILES: &[&str] = &[
+    "bookshelf.json",
+    "bookSources.json",
+    "replaceRules.json",
+    "contentFilters.json",
+    "bookGroups.json",
+    KV_FILE,
+];
+
+/// 备份清单文件名，记录应用版本与备份时间
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// 备份包格式版本，格式不兼容时递增
+const BACKUP_FORMAT: u32 = 1;
+
+/// 导入前自动保存的回滚备份目录 (位于数据目录下)
+const ROLLBACK_DIR: &str = "rollback";
+
+/// 恢复时备份包内必须存在的文件
+const REQUIRED_FILES: &[&str] = &["bookshelf.json", "bookSources.json"];
+
+/// 备份文件名前缀与后缀
+const BACKUP_PREFIX: &str = "backup-";
+const BACKUP_SUFFIX: &str = ".zip";
+
+/// 恢复时的临时目录 (位于数据目录下，保证 rename 不跨文件系统)
+const RESTORE_TMP_DIR: &str = ".restore";
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getlastmodified/><d:getcontentlength/></d:prop></d:propfind>"#;
+
+/// 备份清单
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupManifest {
+    pub format: u32,
+    /// 生成备份的应用版本
+    pub version: String,
+    /// 备份时间 (毫秒时间戳)
+    pub created_at: i64,
+    /// 备份包内的数据文件
+    pub files: Vec<String>,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataImportSummary {
+    /// 被替换的数据文件，备份包中没有的文件保持不变
+    pub files: Vec<String>,
+    /// 导入前数据的回滚备份文件名
+    pub rollback: String,
+}
+
+/// WebDAV 连接配置
+#[derive(Clone, Deserialize, utoipa::ToSchema)]
+pub struct WebdavConfig {
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// 备份目录，相对于 url
+    #[serde(default)]
+    pub path: String,
+    /// 恢复时指定的备份文件名，为空则取最新的备份
+    #[serde(default, rename = "fileName")]
+    pub file_name: Option<String>,
+}
+
+// 手动实现 Debug，避免密码出现在日志中
+impl fmt::Debug for WebdavConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebdavConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &"***")
+            .field("path", &self.path)
+            .field("file_name", &self.file_name)
+            .finish()
+    }
+}
+
+impl WebdavConfig {
+    /// 备份目录的完整 URL，以 `/` 结尾
+    fn collection_url(&self) -> Result<String, ServiceError> {
+        let base = self.url.trim();
+        if !base.starts_with("http://") && !base.starts_with("https://") {
+            return Err(ServiceError::invalid_input("WebDAV url must start with http:// or https://"));
+        }
+        let mut url = base.trim_end_matches('/').to_string();
+        for segment in self.path.split('/').filter(|s| !s.is_empty()) {
+            url.push('/');
+            url.push_str(&urlencoding::encode(segment));
+        }
+        url.push('/');
+        Ok(url)
+    }
+}
+
+/// WebDAV 目录中的文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteFile {
+    pub name: String,
+    pub last_modified: Option<DateTime<FixedOffset>>,
+}
+
+/// 是否为本服务生成的备份文件名
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) && !name.contains('/')
+}
+
+/// 解析 PROPFIND 返回的 multistatus，只保留备份文件
+pub fn parse_propfind(xml: &str) -> Result<Vec<RemoteFile>> {
+    let files = parse_xml(xml, |root| {
+        let files = find_elements(root, "response")
+            .into_iter()
+            .filter_map(|response| {
+                let href = find_elements(response, "href").first().map(|e| element_text(*e))?;
+                let name = href.trim().trim_end_matches('/').rsplit('/').next()?.to_string();
+                let name = urlencoding::decode(&name).map(|n| n.into_owned()).unwrap_or(name);
+                if !is_backup_name(&name) {
+                    return None;
+                }
+                let last_modified = find_elements(response, "getlastmodified")
+                    .first()
+                    .and_then(|e| DateTime::parse_from_rfc2822(element_text(*e).trim()).ok());
+                Some(RemoteFile { name, last_modified })
+            })
+            .collect::<Vec<_>>();
+        Some(files)
+    })?;
+    Ok(files.unwrap_or_default())
+}
+
+/// 选出最新的备份，修改时间相同时按文件名 (含时间戳) 比较
+pub fn newest_backup(files: &[RemoteFile]) -> Option<&RemoteFile> {
+    files
+        .iter()
+        .max_by(|a, b| (a.last_modified, &a.name).cmp(&(b.last_modified, &b.name)))
+}
+
+/// 请求错误不带 URL，避免 URL 中的认证信息进入日志
+fn request_error(e: reqwest::Error) -> anyhow::Error {
+    anyhow!("WebDAV request failed: {}", e.without_url())
+}
+
+fn check_status(resp: Response, action: &str) -> Result<Response> {
+    match resp.status() {
+        status if status.is_success() => Ok(resp),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            bail!("WebDAV {} failed: authentication rejected ({})", action, resp.status())
+        }
+        status => bail!("WebDAV {} failed: {}", action, status),
+    }
+}
+
+pub struct BackupService {
+    storage: FileStorage,
+    client: Client,
+}
+
+impl BackupService {
+    pub fn with_storage(storage: FileStorage) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed to create WebDAV client");
+        Self { storage, client }
+    }
+
+    fn request(&self, method: Method, url: &str, config: &WebdavConfig) -> reqwest::RequestBuilder {
+        let builder = self.client.request(method, url);
+        if config.username.is_empty() {
+            builder
+        } else {
+            builder.basic_auth(&config.username, Some(&config.password))
+        }
+    }
+
+    /// 打包数据目录并上传到 WebDAV，返回备份文件名
+    pub async fn backup_to_webdav(&self, config: &WebdavConfig) -> Result<String> {
+        let collection = config.collection_url()?;
+        let archive = self.create_archive().await?;
+        let file_name = Local::now().format("backup-%Y%m%d-%H%M%S.zip").to_string();
+
+        self.ensure_collection(config).await?;
+        let resp = self
+            .request(Method::PUT, &format!("{}{}", collection, file_name), config)
+            .header(reqwest::header::CONTENT_TYPE, "application/zip")
+            .body(archive)
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(resp, "PUT")?;
+
+        tracing::info!("Uploaded WebDAV backup {}", file_name);
+        Ok(file_name)
+    }
+
+    /// 逐级创建备份目录，已存在时服务器返回 405，忽略即可
+    async fn ensure_collection(&self, config: &WebdavConfig) -> Result<()> {
+        let mut partial = WebdavConfig {
+            path: String::new(),
+            ..config.clone()
+        };
+        for segment in config.path.split('/').filter(|s| !s.is_empty()) {
+            partial.path = format!("{}/{}", partial.path, segment);
+            let mkcol = Method::from_bytes(b"MKCOL").expect("valid method");
+            self.request(mkcol, &partial.collection_url()?, config)
+                .send()
+                .await
+                .map_err(request_error)?;
+        }
+        Ok(())
+    }
+
+    /// 列出 WebDAV 目录中的备份文件
+    pub async fn list_backups(&self, config: &WebdavConfig) -> Result<Vec<RemoteFile>> {
+        let collection = config.collection_url()?;
+        let propfind = Method::from_bytes(b"PROPFIND").expect("valid method");
+        let resp = self
+            .request(propfind, &collection, config)
+            .header("Depth", "1")
+            .header(reqwest::header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let body = check_status(resp, "PROPFIND")?.text().await.map_err(request_error)?;
+        parse_propfind(&body)
+    }
+
+    /// 从 WebDAV 下载备份 (指定文件名或最新的一个) 并恢复，返回恢复的文件名
+    pub async fn restore_from_webdav(&self, config: &WebdavConfig) -> Result<String> {
+        let collection = config.collection_url()?;
+        let backups = self.list_backups(config).await?;
+        let file_name = match config.file_name.as_deref().filter(|n| !n.is_empty()) {
+            Some(name) => backups
+                .iter()
+                .find(|f| f.name == name)
+                .map(|f| f.name.clone())
+                .ok_or_else(|| ServiceError::not_found("Backup", name))?,
+            None => newest_backup(&backups)
+                .map(|f| f.name.clone())
+                .ok_or_else(|| ServiceError::not_found("Backup", collection.clone()))?,
+        };
+
+        let resp = self
+            .request(
+                Method::GET,
+                &format!("{}{}", collection, urlencoding::encode(&file_name)),
+                config,
+            )
+            .send()
+            .await
+            .map_err(request_error)?;
+        let data = check_status(resp, "GET")?.bytes().await.map_err(request_error)?;
+        self.restore_archive(&data).await?;
+
+        tracing::info!("Restored WebDAV backup {}", file_name);
+        Ok(file_name)
+    }
+
+    /// 将数据文件与备份清单打包为 zip
+    pub async fn create_archive(&self) -> Result<Vec<u8>> {
+        // 防抖中的进度也要进入备份
+        self.storage.flush().await?;
+        let shelf_store = BookshelfStore::new(self.storage.clone());
+        let mut entries = Vec::new();
+        for name in BACKUP_FILES {
+            // 书架按书存放，备份中仍合并为单个 bookshelf.json
+            let content = if *name == LEGACY_BOOKSHELF_FILE && shelf_store.is_initialized().await {
+                Ok(serde_json::to_vec_pretty(&shelf_store.load().await?)?)
+            } else {
+                fs::read(self.storage.file_path(name)).await
+            };
+            match content {
+                Ok(data) => entries.push((*name, data)),
+                // 从未保存过的必需文件以空列表代替，保证备份可被恢复
+                Err(_) if REQUIRED_FILES.contains(name) => entries.push((*name, b"[]".to_vec())),
+                Err(_) => {}
+            }
+        }
+
+        let manifest = BackupManifest {
+            format: BACKUP_FORMAT,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Local::now().timestamp_millis(),
+            files: entries.iter().map(|(name, _)| name.to_string()).collect(),
+        };
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(MANIFEST_FILE, deflated)?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        for (name, data) in entries {
+            zip.start_file(name, deflated)?;
+            zip.write_all(&data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// 校验备份包并替换数据文件 (WebDAV 恢复)
+    ///
+    /// 备份包必须包含书架与书源，清单可缺省以兼容旧备份。
+    pub async fn restore_archive(&self, data: &[u8]) -> Result<Vec<String>> {
+        let (_, entries) = read_archive(data)?;
+        if let Some(name) = REQUIRED_FILES.iter().find(|name| !entries.iter().any(|(n, _)| n == *name)) {
+            return Err(ServiceError::invalid_input(format!("Backup is missing {}", name)).into());
+        }
+        self.replace_files(entries).await
+    }
+
+    /// 导入 exportData 导出的备份包
+    ///
+    /// 备份包必须带有清单；只替换包内存在的文件，其余数据保持不变。
+    /// 替换前先将当前数据保存为回滚备份。
+    pub async fn import_archive(&self, data: &[u8]) -> Result<DataImportSummary> {
+        let (manifest, entries) = read_archive(data)?;
+        let manifest = manifest
+            .ok_or_else(|| ServiceError::invalid_input(format!("Backup is missing {}", MANIFEST_FILE)))?;
+
+        let rollback = self.save_rollback().await?;
+        let files = self.replace_files(entries).await?;
+        tracing::info!(
+            "Imported {} data files from backup of v{}, rollback saved as {}",
+            files.len(),
+            manifest.version,
+            rollback
+        );
+        Ok(DataImportSummary { files, rollback })
+    }
+
+    /// 将当前数据打包保存到回滚目录，返回文件名
+    async fn save_rollback(&self) -> Result<String> {
+        let archive = self.create_archive().await?;
+        let file_name = Local::now().format("rollback-%Y%m%d-%H%M%S%3f.zip").to_string();
+        let dir = self.storage.file_path(ROLLBACK_DIR);
+        fs::create_dir_all(&dir).await?;
+        fs::write(dir.join(&file_name), archive)
+            .await
+            .context("Failed to save rollback backup")?;
+        Ok(file_name)
+    }
+
+    /// 替换数据文件
+    ///
+    /// 先写入临时目录，再逐个 rename 覆盖，写入失败时不会改动现有数据。
+    async fn replace_files(&self, entries: ArchiveEntries) -> Result<Vec<String>> {
+        // 先写入防抖中的数据，避免其在恢复后覆盖备份内容
+        self.storage.flush().await?;
+
+        let tmp_dir = self.storage.file_path(RESTORE_TMP_DIR);
+        let _ = fs::remove_dir_all(&tmp_dir).await;
+        let staged = async {
+            fs::create_dir_all(&tmp_dir).await?;
+            for (name, data) in &entries {
+                fs::write(tmp_dir.join(name), data).await?;
+            }
+            for (name, _) in &entries {
+                fs::rename(tmp_dir.join(name), self.storage.file_path(name)).await?;
+                // 旧的 .bak 已过时，不能再用于损坏恢复
+                let _ = fs::remove_file(self.storage.file_path(&format!("{}.bak", name))).await;
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        let _ = fs::remove_dir_all(&tmp_dir).await;
+        staged.context("Failed to replace data files")?;
+
+        Ok(entries.into_iter().map(|(name, _)| name).collect())
+    }
+}
+
+/// 备份包中的数据文件 (文件名, 内容)
+type ArchiveEntries = Vec<(String, Vec<u8>)>;
+
+/// 读取并校验备份包中的数据文件
+///
+/// 先完整解压并校验到内存，任一文件损坏或无法解析时整体拒绝。
+/// 旧版格式的书架数据经 `Migration` 转换为当前格式。
+fn read_archive(data: &[u8]) -> Result<(Option<BackupManifest>, ArchiveEntries)> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| ServiceError::invalid_input(format!("Invalid backup archive: {}", e)))?;
+
+    let manifest = match read_entry(&mut archive, MANIFEST_FILE)? {
+        Some(content) => Some(parse_manifest(&content)?),
+        None => None,
+    };
+
+    let mut entries = Vec::new();
+    for name in BACKUP_FILES {
+        let Some(content) = read_entry(&mut archive, name)? else {
+            continue;
+        };
+        entries.push((name.to_string(), prepare_entry(name, content)?));
+    }
+
+    if let Some(manifest) = &manifest {
+        if let Some(missing) = manifest.files.iter().find(|f| !entries.iter().any(|(n, _)| n == *f)) {
+            return Err(ServiceError::invalid_input(format!("Backup is missing {}", missing)).into());
+        }
+    }
+    Ok((manifest, entries))
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<Vec<u8>>, ServiceError> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)
+        .map_err(|e| ServiceError::invalid_input(format!("Corrupted backup entry {}: {}", name, e)))?;
+    Ok(Some(content))
+}
+
+fn parse_manifest(content: &[u8]) -> Result<BackupManifest, ServiceError> {
+    let manifest: BackupManifest = serde_json::from_slice(content)
+        .map_err(|e| ServiceError::invalid_input(format!("Invalid {}: {}", MANIFEST_FILE, e)))?;
+    if manifest.format > BACKUP_FORMAT {
+        return Err(ServiceError::invalid_input(format!(
+            "Backup format {} from v{} is newer than supported format {}",
+            manifest.format, manifest.version, BACKUP_FORMAT
+        )));
+    }
+    Ok(manifest)
+}
+
+/// 校验数据文件能解析为当前模型，返回要写入的内容
+fn prepare_entry(name: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+    fn invalid(name: &str, e: impl fmt::Display) -> anyhow::Error {
+        ServiceError::invalid_input(format!("Invalid {} in backup: {}", name, e)).into()
+    }
+    fn check<T: DeserializeOwned>(name: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+        serde_json::from_slice::<T>(&content)
+            .map(|_| content)
+            .map_err(|e| invalid(name, e))
+    }
+    match name {
+        "bookshelf.json" => {
+            let books = Migration::parse_books(&content).map_err(|e| invalid(name, e))?;
+            Ok(serde_json::to_vec_pretty(&books)?)
+        }
+        "bookSources.json" => check::<Vec<BookSourceFull>>(name, content),
+        "replaceRules.json" => check::<Vec<ReplaceRule>>(name, content),
+        "contentFilters.json" => check::<Vec<ContentFilter>>(name, content),
+        "bookGroups.json" => check::<Vec<BookGroup>>(name, content),
+        KV_FILE => check::<KvData>(name, content),
+        _ => Ok(content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_server::{header, spawn_raw_server};
+    use crate::models::Book;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct DavState {
+        files: HashMap<String, Vec<u8>>,
+        /// (method, path, authorization)
+        requests: Vec<(String, String, String)>,
+        /// GET 只返回前一半数据，模拟下载中断
+        truncate_get: bool,
+    }
+
+    fn spawn_webdav_stub(state: Arc<Mutex<DavState>>) -> String {
+        spawn_raw_server(move |head, body| {
+            let mut parts = head.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+            let auth = header(head, "authorization").unwrap_or_default().to_string();
+
+            let mut state = state.lock().unwrap();
+            state.requests.push((method.clone(), path.clone(), auth));
+            let (status, response) = match method.as_str() {
+                "MKCOL" => (405, Vec::new()),
+                "PUT" => {
+                    state.files.insert(path, body.to_vec());
+                    (201, Vec::new())
+                }
+                "PROPFIND" => {
+                    let mut xml = format!(
+                        r#"<?xml version="1.0"?><D:multistatus xmlns:D="DAV:"><D:response><D:href>{}</D:href><D:propstat><D:prop/></D:propstat></D:response>"#,
+                        path
+                    );
+                    for name in state.files.keys() {
+                        xml.push_str(&format!(
+                            "<D:response><D:href>{}</D:href><D:propstat><D:prop><D:getlastmodified>Mon, 12 Oct 2026 08:00:00 GMT</D:getlastmodified></D:prop></D:propstat></D:response>",
+                            name
+                        ));
+                    }
+                    xml.push_str("</D:multistatus>");
+                    (207, xml.into_bytes())
+                }
+                "GET" => match state.files.get(&path) {
+                    Some(data) if state.truncate_get => (200, data[..data.len() / 2].to_vec()),
+                    Some(data) => (200, data.clone()),
+                    None => (404, Vec::new()),
+                },
+                _ => (405, Vec::new()),
+            };
+            (status, vec![], response)
+        })
+    }
+
+    fn temp_storage(name: &str) -> FileStorage {
+        let dir = std::env::temp_dir().join(format!("reader_tests_backup_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        FileStorage::new(dir)
+    }
+
+    fn config(base: &str) -> WebdavConfig {
+        WebdavConfig {
+            url: format!("{}/dav/", base),
+            username: "reader".to_string(),
+            password: "secret".to_string(),
+            path: "reader/backups".to_string(),
+            file_name: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_propfind() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/dav/reader/</d:href></d:response>
+  <d:response>
+    <d:href>/dav/reader/backup-20260101-080000.zip</d:href>
+    <d:propstat><d:prop><d:getlastmodified>Thu, 01 Jan 2026 08:00:00 GMT</d:getlastmodified></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/reader/backup-20251231-080000.zip</d:href>
+    <d:propstat><d:prop><d:getlastmodified>Fri, 02 Jan 2026 08:00:00 GMT</d:getlastmodified></d:prop></d:propstat>
+  </d:response>
+  <d:response><d:href>/dav/reader/notes%20backup.txt</d:href></d:response>
+</d:multistatus>"#;
+        let files = parse_propfind(xml).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].name, "backup-20260101-080000.zip");
+        // 以修改时间为准
+        assert_eq!(newest_backup(&files).unwrap().name, "backup-20251231-080000.zip");
+        assert!(format!("{:?}", config("http://x")).contains("***"));
+        assert!(!format!("{:?}", config("http://x")).contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore_roundtrip() {
+        let state = Arc::new(Mutex::new(DavState::default()));
+        let base = spawn_webdav_stub(state.clone());
+
+        let source = temp_storage("source");
+        let book = Book {
+            book_url: "https://example.com/book/1".to_string(),
+            name: "测试书".to_string(),
+            ..Default::default()
+        };
+        source.write_json("bookshelf.json", &vec![book]).await.unwrap();
+        source.write_file("bookGroups.json", r#"[{"groupId":1,"groupName":"追更","order":0,"show":true}]"#).await.unwrap();
+
+        let backup = BackupService::with_storage(source);
+        let file_name = backup.backup_to_webdav(&config(&base)).await.unwrap();
+        assert!(is_backup_name(&file_name));
+        {
+            let state = state.lock().unwrap();
+            let key = format!("/dav/reader/backups/{}", file_name);
+            assert!(state.files.contains_key(&key));
+            let (method, _, auth) = state.requests.iter().find(|r| r.0 == "PUT").unwrap();
+            assert_eq!(method, "PUT");
+            assert!(auth.starts_with("Basic "));
+        }
+
+        let target = temp_storage("target");
+        let restore = BackupService::with_storage(target.clone());
+        let restored = restore.restore_from_webdav(&config(&base)).await.unwrap();
+        assert_eq!(restored, file_name);
+
+        let books: Vec<Book> = target.read_json("bookshelf.json").await.unwrap();
+        assert_eq!(books[0].name, "测试书");
+        let groups: Vec<BookGroup> = target.read_json("bookGroups.json").await.unwrap();
+        assert_eq!(groups[0].group_name, "追更");
+        assert_eq!(target.read_file("bookSources.json").await.unwrap(), "[]");
+    }
+
+    #[tokio::test]
+    async fn test_restore_failure_keeps_existing_data() {
+        let state = Arc::new(Mutex::new(DavState::default()));
+        let base = spawn_webdav_stub(state.clone());
+
+        let storage = temp_storage("atomic");
+        storage.write_file("bookshelf.json", r#"[{"bookUrl":"a","name":"原书","author":""}]"#).await.unwrap();
+        let service = BackupService::with_storage(storage.clone());
+        service.backup_to_webdav(&config(&base)).await.unwrap();
+        storage.write_file("bookshelf.json", "[]").await.unwrap();
+
+        // 下载中断
+        state.lock().unwrap().truncate_get = true;
+        assert!(service.restore_from_webdav(&config(&base)).await.is_err());
+        assert_eq!(storage.read_file("bookshelf.json").await.unwrap(), "[]");
+
+        // 内容校验失败
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("bookshelf.json", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"[]").unwrap();
+        zip.start_file("bookSources.json", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"{not json").unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        let err = service.restore_archive(&archive).await.unwrap_err();
+        assert!(err.downcast_ref::<ServiceError>().is_some());
+        assert_eq!(storage.read_file("bookshelf.json").await.unwrap(), "[]");
+        assert!(!storage.file_path(RESTORE_TMP_DIR).exists());
+
+        // 指定不存在的备份
+        let mut missing = config(&base);
+        missing.file_name = Some("backup-19700101-000000.zip".to_string());
+        let err = service.restore_from_webdav(&missing).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<ServiceError>(), Some(ServiceError::NotFound { .. })));
+    }
+
+    fn zip_with(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn read_value(storage: &FileStorage, name: &str) -> serde_json::Value {
+        serde_json::from_slice(&std::fs::read(storage.file_path(name)).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_export_import_roundtrip() {
+        let source = temp_storage("export");
+        let book = Book {
+            book_url: "https://example.com/book/1".to_string(),
+            name: "测试书".to_string(),
+            dur_chapter_index: Some(12),
+            ..Default::default()
+        };
+        source.write_json("bookshelf.json", &vec![book]).await.unwrap();
+        source
+            .write_file("bookSources.json", r#"[{"bookSourceUrl":"https://a.com","bookSourceName":"A"}]"#)
+            .await
+            .unwrap();
+        source.write_file("bookGroups.json", r#"[{"groupId":1,"groupName":"追更","order":0,"show":true}]"#).await.unwrap();
+        source
+            .write_file(KV_FILE, r#"{"source_vars":{"https://a.com":{"token":"t"}},"cache":{}}"#)
+            .await
+            .unwrap();
+        let archive = BackupService::with_storage(source.clone()).create_archive().await.unwrap();
+
+        let mut zip = ZipArchive::new(Cursor::new(archive.as_slice())).unwrap();
+        let manifest: BackupManifest = serde_json::from_reader(zip.by_name(MANIFEST_FILE).unwrap()).unwrap();
+        assert_eq!(manifest.version, env!("CARGO_PKG_VERSION"));
+        assert!(manifest.files.contains(&KV_FILE.to_string()));
+
+        let target = temp_storage("import");
+        target.write_file("bookshelf.json", r#"[{"bookUrl":"old","name":"旧书","author":""}]"#).await.unwrap();
+        let service = BackupService::with_storage(target.clone());
+        let summary = service.import_archive(&archive).await.unwrap();
+        assert_eq!(summary.files, manifest.files);
+
+        for name in &manifest.files {
+            assert_eq!(read_value(&target, name), read_value(&source, name), "{}", name);
+        }
+        // 回滚备份保存了导入前的书架
+        let rollback = std::fs::read(target.file_path(ROLLBACK_DIR).join(&summary.rollback)).unwrap();
+        let (_, entries) = read_archive(&rollback).unwrap();
+        let (_, shelf) = entries.iter().find(|(n, _)| n == "bookshelf.json").unwrap();
+        assert!(String::from_utf8_lossy(shelf).contains("旧书"));
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_corrupt_archive() {
+        let storage = temp_storage("import_corrupt");
+        let shelf = r#"[{"bookUrl":"a","name":"原书","author":""}]"#;
+        storage.write_file("bookshelf.json", shelf).await.unwrap();
+        let service = BackupService::with_storage(storage.clone());
+        let manifest = serde_json::to_vec(&BackupManifest {
+            format: BACKUP_FORMAT,
+            version: "0.1.0".to_string(),
+            created_at: 0,
+            files: vec!["bookshelf.json".to_string(), "bookSources.json".to_string()],
+        })
+        .unwrap();
+
+        let valid = zip_with(&[(MANIFEST_FILE, &manifest), ("bookshelf.json", b"[]"), ("bookSources.json", b"[]")]);
+        let newer = serde_json::json!({ "format": BACKUP_FORMAT + 1, "version": "9.0.0", "createdAt": 0, "files": [] });
+        let corrupt = [
+            b"not a zip".to_vec(),
+            valid[..valid.len() / 2].to_vec(),
+            zip_with(&[("bookshelf.json", b"[]")]),
+            zip_with(&[(MANIFEST_FILE, &manifest), ("bookshelf.json", b"[]")]),
+            zip_with(&[(MANIFEST_FILE, &manifest), ("bookshelf.json", b"[]"), ("bookSources.json", b"{oops")]),
+            zip_with(&[(MANIFEST_FILE, newer.to_string().as_bytes()), ("bookshelf.json", b"[]")]),
+        ];
+        for (i, archive) in corrupt.iter().enumerate() {
+            let err = service.import_archive(archive).await.unwrap_err();
+            assert!(err.downcast_ref::<ServiceError>().is_some(), "case {}: {}", i, err);
+            assert_eq!(storage.read_file("bookshelf.json").await.unwrap(), shelf);
+        }
+        assert!(!storage.file_path("bookSources.json").exists());
+        assert!(!storage.file_path(ROLLBACK_DIR).exists());
+    }
+
+    #[tokio::test]
+    async fn test_partial_import_keeps_other_data() {
+        let storage = temp_storage("import_partial");
+        let shelf = r#"[{"bookUrl":"a","name":"原书","author":""}]"#;
+        storage.write_file("bookshelf.json", shelf).await.unwrap();
+        let manifest = serde_json::to_vec(&BackupManifest {
+            format: BACKUP_FORMAT,
+            version: "0.1.0".to_string(),
+            created_at: 0,
+            files: vec!["bookSources.json".to_string()],
+        })
+        .unwrap();
+        let sources = br#"[{"bookSourceUrl":"https://a.com","bookSourceName":"A"}]"#;
+        let archive = zip_with(&[(MANIFEST_FILE, &manifest), ("bookSources.json", sources)]);
+
+        let service = BackupService::with_storage(storage.clone());
+        let summary = service.import_archive(&archive).await.unwrap();
+        assert_eq!(summary.files, vec!["bookSources.json"]);
+        assert_eq!(storage.read_file("bookshelf.json").await.unwrap(), shelf);
+        let imported: Vec<BookSourceFull> = storage.read_json("bookSources.json").await.unwrap();
+        assert_eq!(imported[0].book_source_name, "A");
+    }
+
+    #[test]
+    fn test_legacy_bookshelf_is_migrated() {
+        let legacy = br#"[{"bookUrl":"a","name":"legacy","durChapterIndex":3,"type":0}]"#;
+        assert!(serde_json::from_slice::<Vec<Book>>(legacy).is_err());
+        let migrated = prepare_entry("bookshelf.json", legacy.to_vec()).unwrap();
+        let books: Vec<Book> = serde_json::from_slice(&migrated).unwrap();
+        assert_eq!(books[0].name, "legacy");
+        assert_eq!(books[0].dur_chapter_index, Some(3));
+    }
+}
@@ -1,114 +1,453 @@
 use axum::response::sse::Event;
 use futures::stream::Stream;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-
-
-use crate::engine::book_source::{BookSource, BookSourceEngine};
-use crate::models::{Book, BookSourceFull, Chapter, SearchResult};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock, RwLockMappedWriteGuard, RwLockReadGuard, RwLockWriteGuard, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::engine::book_source::{
+    AudioContent, BookItem, BookSource, BookSourceEngine, ImageContent, SOURCE_TYPE_AUDIO, SOURCE_TYPE_IMAGE,
+};
+use crate::engine::deadline::Deadline;
+use crate::engine::http_client::HttpClient;
+use crate::engine::engine_cache::EngineCache;
+use crate::engine::rule_context::{BookContext, ChapterContext};
+use crate::engine::stats::STATS;
+use crate::engine::utils::{format_content, ContentFormatOptions};
+use crate::models::{Book, BookGroup, BookMetadata, BookProgress, BookSourceFull, BookUpdateError, Chapter, CompiledReplaceRules, ReplaceRule, SearchResult, UpdateStage};
+use super::bookshelf::{self, RefreshSummary, Shelf, ShelfPage, ShelfQuery};
+use super::change_source::{rank_candidates, ChangeSourceEvent, ChangeSourceQuery, SourceCandidate};
+use super::epub::{EpubBook, EpubChapter, EpubCover};
+use super::local_book::{self, ChapterSplitter, LocalChapter, LOCAL_ORIGIN, LOCAL_URL_PREFIX};
+use super::local_epub;
+use super::prefetch::PrefetchChapter;
+use super::search_filter::SearchFilter;
+use super::shelf_export::{ShelfReport, ShelfReportRow, SHELF_REPORT_SCHEMA_VERSION};
+use super::search_merge::{truncate_origins, MergedSearch, SearchAggregator, SearchOrigin, SearchSessions};
+use super::source_stats::{SearchOutcome, SourceStats};
+use super::text_diff::{self, DiffOp};
+use super::{ContentFilterService, Migration, ReplaceService, ServiceError};
+use crate::storage::audio_cache::AudioCache;
+use crate::storage::bookshelf::BookshelfStore;
+use crate::storage::content_cache::{ChapterVersion, ContentCache};
+use crate::storage::cover_cache::{is_local_cover, CachedCover, CoverCache};
 use crate::storage::kv::KvStore;
-use crate::storage::FileStorage;
-use crate::engine::search_engine::SearchEngine;
+use crate::storage::{sandbox, FileStorage, ReclaimedCache};
+use crate::engine::search_engine::{IndexedBook, SearchEngine};
 
-/// 书架存储文件名
-const BOOKSHELF_FILE: &str = "bookshelf.json";
 const SOURCES_FILE: &str = "bookSources.json";
+/// 单个书源的搜索结果
+struct SourceSearchOutcome {
+    source_name: String,
+    source_url: String,
+    result: Result<Vec<BookItem>, anyhow::Error>,
+    /// 响应耗时 (毫秒)
+    respond_time: u64,
+    timed_out: bool,
+}
+
+impl SourceSearchOutcome {
+    fn stat_outcome(&self) -> SearchOutcome {
+        match (&self.result, self.timed_out) {
+            (_, true) => SearchOutcome::Timeout,
+            (Ok(_), _) => SearchOutcome::Success,
+            (Err(_), _) => SearchOutcome::Failure,
+        }
+    }
+}
+
+/// 多书源搜索的取消标记
+///
+/// 搜索流或请求 future 被丢弃 (客户端断开) 时取消尚未开始的书源搜索，
+/// 搜索正常结束时调用 `finish`。
+struct SearchCancellation {
+    token: CancellationToken,
+    finished: bool,
+}
+
+impl SearchCancellation {
+    fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            finished: false,
+        }
+    }
+
+    fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for SearchCancellation {
+    fn drop(&mut self) {
+        if !self.finished {
+            tracing::info!("Search abandoned by client, cancelling remaining sources");
+            self.token.cancel();
+            STATS.record_search_cancelled();
+        }
+    }
+}
+
+/// 单个书源的搜索超时
+const SOURCE_SEARCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// 搜索时单次 JS 执行的超时，比默认值短，避免失控脚本拖慢整次搜索
+const SEARCH_JS_TIMEOUT: Duration = Duration::from_secs(3);
+/// 封面代理允许的最大图片大小
+const MAX_COVER_BYTES: usize = 5 * 1024 * 1024;
+/// 漫画图片代理允许的最大图片大小
+const MAX_COMIC_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// 漫画图片代理的接口路径
+const IMAGE_PROXY_PATH: &str = "/reader3/imageProxy";
+/// 书架更新检查同时处理的书源数
+const REFRESH_CONCURRENCY: usize = 8;
+/// 书架搜索返回的最大结果数
+const SHELF_SEARCH_LIMIT: usize = 200;
+/// 目录缓存的默认有效期 (分钟)
+const DEFAULT_CHAPTER_LIST_TTL_MINUTES: u64 = 360;
+/// 音频代理每次读取并转发的字节数
+const AUDIO_CHUNK_SIZE: usize = 64 * 1024;
+/// 音频代理转发给浏览器的上游响应头
+const PROXIED_AUDIO_HEADERS: [&str; 6] = [
+    "content-type",
+    "content-length",
+    "content-range",
+    "accept-ranges",
+    "etag",
+    "last-modified",
+];
+
+/// 音频代理的上游响应
+pub struct AudioStream {
+    pub status: u16,
+    /// 需转发的响应头 (PROXIED_AUDIO_HEADERS 中上游返回的部分)
+    pub headers: Vec<(String, String)>,
+    /// 响应体分块，在阻塞线程中读取；接收端丢弃后停止读取
+    pub body: tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>,
+}
+
+/// 章节两个版本间的差异
+#[derive(Debug, Clone, PartialEq, serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterDiff {
+    pub from: u32,
+    pub to: u32,
+    /// 新增行数
+    pub added: usize,
+    /// 删除行数
+    pub removed: usize,
+    /// 按行比较的 unified diff，两个版本相同时为空
+    pub diff: String,
+}
+
+/// 目录缓存有效期，由环境变量 CHAPTER_LIST_TTL_MINUTES 配置，0 表示每次都重新获取
+fn chapter_list_ttl() -> Duration {
+    let minutes = std::env::var("CHAPTER_LIST_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CHAPTER_LIST_TTL_MINUTES);
+    Duration::from_secs(minutes * 60)
+}
 
 #[derive(Clone)]
 pub struct BookService {
     storage: FileStorage,
-    bookshelf: Arc<RwLock<Vec<Book>>>,
+    /// 书架，首次使用时从存储加载 (None 表示尚未加载)；修改均在写锁内完成
+    bookshelf: Arc<RwLock<Option<Shelf>>>,
+    shelf_store: BookshelfStore,
     sources: Arc<RwLock<Vec<BookSourceFull>>>,
-    kv_store: Arc<KvStore>,
+    /// 按书源复用的书源引擎
+    engines: Arc<EngineCache>,
     search_engine: Arc<SearchEngine>,
+    content_cache: ContentCache,
+    cover_cache: CoverCache,
+    /// 漫画图片代理的缓存
+    image_cache: CoverCache,
+    audio_cache: AudioCache,
+    replace_service: ReplaceService,
+    content_filters: ContentFilterService,
+    search_sessions: SearchSessions,
+    source_stats: SourceStats,
+    /// 避免手动与定时的更新检查同时进行
+    refresh_lock: Arc<Mutex<()>>,
+    /// 目录缓存在该时长内直接使用，过期后重新获取
+    chapter_list_ttl: Duration,
 }
 
 impl BookService {
-    pub fn new(search_engine: Arc<SearchEngine>) -> Self {
+    pub fn new(search_engine: Arc<SearchEngine>, replace_service: ReplaceService) -> Self {
         let storage = FileStorage::default();
-        let kv_store = Arc::new(KvStore::new(storage.clone(), "kv_store.json"));
+        let kv_store = Arc::new(KvStore::new(storage.clone(), super::KV_FILE));
+        let engines = Arc::new(EngineCache::from_env(kv_store));
+        let sources = Arc::new(RwLock::new(Vec::new()));
+        let source_stats = SourceStats::new(storage.clone(), sources.clone());
+        let content_filters = ContentFilterService::with_storage(storage.clone());
+        Self::with_storage(storage, engines, search_engine, replace_service, content_filters, sources, source_stats)
+    }
+
+    pub fn with_storage(
+        storage: FileStorage,
+        engines: Arc<EngineCache>,
+        search_engine: Arc<SearchEngine>,
+        replace_service: ReplaceService,
+        content_filters: ContentFilterService,
+        sources: Arc<RwLock<Vec<BookSourceFull>>>,
+        source_stats: SourceStats,
+    ) -> Self {
+        let content_cache = ContentCache::new(storage.clone());
+        let cover_cache = CoverCache::new(storage.clone());
+        let image_cache = CoverCache::with_dir(storage.clone(), "images");
+        let audio_cache = AudioCache::new(storage.clone());
+        let shelf_store = BookshelfStore::new(storage.clone());
         Self {
             storage,
-            bookshelf: Arc::new(RwLock::new(Vec::new())),
-            sources: Arc::new(RwLock::new(Vec::new())),
-            kv_store,
+            bookshelf: Arc::new(RwLock::new(None)),
+            shelf_store,
+            sources,
+            engines,
             search_engine,
+            content_cache,
+            cover_cache,
+            image_cache,
+            audio_cache,
+            replace_service,
+            content_filters,
+            search_sessions: SearchSessions::default(),
+            source_stats,
+            refresh_lock: Arc::new(Mutex::new(())),
+            chapter_list_ttl: chapter_list_ttl(),
         }
     }
 
+    /// 指定目录缓存有效期
+    pub fn with_chapter_list_ttl(mut self, ttl: Duration) -> Self {
+        self.chapter_list_ttl = ttl;
+        self
+    }
+
 
     /// 初始化加载数据
     pub async fn init(&self) -> anyhow::Result<()> {
-        let books: Vec<Book> = self.storage.read_json_or_default(BOOKSHELF_FILE).await;
-        let sources: Vec<BookSourceFull> = self.storage.read_json_or_default(SOURCES_FILE).await;
-
         let mut shelf = self.bookshelf.write().await;
-        *shelf = books.clone();
+        let books = self.load_books().await?;
+        *shelf = Some(Shelf::new(books.clone()));
+        drop(shelf);
+
+        let sources: Vec<BookSourceFull> = self.storage.read_json_or_default(SOURCES_FILE).await;
 
         let mut src = self.sources.write().await;
         *src = sources;
 
-        // 异步重建索引
+        // 异步同步索引，只重写有变化的书籍
         let search_engine = self.search_engine.clone();
-        let books_clone = books.clone();
-        
-        tokio::task::spawn_blocking(move || {
-            tracing::info!("Starting search index rebuild for {} books...", books_clone.len());
-            for book in books_clone {
-                 let intro = book.intro.unwrap_or_default();
-                 if let Err(e) = search_engine.index_book(&book.book_url, &book.name, &book.author, &intro) {
-                     tracing::warn!("Failed to index book {}: {}", book.name, e);
-                 }
-            }
-            tracing::info!("Search index rebuild completed.");
+        let entries: Vec<IndexedBook> = books.iter().map(IndexedBook::from).collect();
+        tokio::task::spawn_blocking(move || match search_engine.sync_books(&entries) {
+            Ok(changed) => tracing::info!("Search index synced: {} of {} books updated", changed, entries.len()),
+            Err(e) => tracing::warn!("Failed to sync search index: {}", e),
         });
 
         Ok(())
     }
 
-    /// 获取书架列表
-    pub async fn get_bookshelf(&self, _refresh: bool) -> Result<Vec<Book>, anyhow::Error> {
-        {
-            let shelf = self.bookshelf.read().await;
-            if !shelf.is_empty() {
-                return Ok(shelf.clone());
+    /// 从存储加载书架，旧版单文件书架先迁移为按书存放
+    async fn load_books(&self) -> anyhow::Result<Vec<Book>> {
+        Migration::with_storage(self.storage.clone()).migrate_bookshelf().await?;
+        self.shelf_store.load().await
+    }
+
+    /// 书架读锁，尚未加载时先加载
+    async fn shelf(&self) -> anyhow::Result<RwLockReadGuard<'_, Shelf>> {
+        loop {
+            match RwLockReadGuard::try_map(self.bookshelf.read().await, Option::as_ref) {
+                Ok(shelf) => return Ok(shelf),
+                Err(unloaded) => drop(unloaded),
             }
+            drop(self.shelf_mut().await?);
         }
+    }
 
-        let books: Vec<Book> = self.storage.read_json_or_default(BOOKSHELF_FILE).await;
-        {
-            let mut shelf = self.bookshelf.write().await;
-            *shelf = books.clone();
+    /// 书架写锁，尚未加载时先加载
+    ///
+    /// 修改书架与写入对应文件都在写锁内完成，并发请求的修改依次进行，不会互相覆盖。
+    async fn shelf_mut(&self) -> anyhow::Result<RwLockMappedWriteGuard<'_, Shelf>> {
+        let mut guard = self.bookshelf.write().await;
+        if guard.is_none() {
+            *guard = Some(Shelf::new(self.load_books().await?));
         }
-        Ok(books)
+        Ok(RwLockWriteGuard::map(guard, |shelf| shelf.get_or_insert_with(Shelf::default)))
+    }
+
+    /// 获取书架列表
+    pub async fn get_bookshelf(&self, _refresh: bool) -> Result<Vec<Book>, anyhow::Error> {
+        Ok(self.shelf().await?.to_vec())
+    }
+
+    /// 书架报表：书籍信息、阅读进度与更新状态，章节数取自缓存的目录
+    pub async fn shelf_report(&self, groups: &[BookGroup]) -> Result<ShelfReport, anyhow::Error> {
+        let books = self.get_bookshelf(false).await?;
+        let mut rows = Vec::with_capacity(books.len());
+        for book in &books {
+            let titles = self
+                .cached_chapter_list(&Self::chapter_list_key(&book.book_url))
+                .await
+                .map(|(chapters, _)| chapters.into_iter().map(|c| c.title).collect::<Vec<_>>());
+            rows.push(ShelfReportRow::new(book, titles.as_deref(), groups));
+        }
+        Ok(ShelfReport {
+            schema_version: SHELF_REPORT_SCHEMA_VERSION,
+            exported_at: chrono::Utc::now().timestamp_millis(),
+            books: rows,
+        })
+    }
+
+    /// 按分组过滤、排序并分页获取书架
+    pub async fn query_bookshelf(&self, refresh: bool, query: &ShelfQuery) -> Result<ShelfPage, anyhow::Error> {
+        let books = self.get_bookshelf(refresh).await?;
+        Ok(bookshelf::query_bookshelf(books, query))
     }
 
-    /// 获取章节列表
+    /// 在书架中搜索书名、作者与简介 (本地全文索引)，按相关度排序
+    pub async fn search_bookshelf(&self, key: &str) -> Result<Vec<Book>, anyhow::Error> {
+        let hits = self
+            .search_engine
+            .search(key, SHELF_SEARCH_LIMIT)
+            .map_err(|e| ServiceError::invalid_input(format!("Invalid search query: {}", e)))?;
+        let shelf = self.shelf().await?;
+        let mut seen = HashSet::new();
+        Ok(hits
+            .iter()
+            .filter(|hit| seen.insert(hit.book_id.as_str()))
+            .filter_map(|hit| shelf.get(&hit.book_id).cloned())
+            .collect())
+    }
+
+    /// 清空并按书架重建本地全文索引，返回索引的书籍数
+    pub async fn rebuild_search_index(&self) -> Result<usize, anyhow::Error> {
+        let entries: Vec<IndexedBook> = self.shelf().await?.iter().map(IndexedBook::from).collect();
+        let search_engine = self.search_engine.clone();
+        tokio::task::spawn_blocking(move || search_engine.rebuild(&entries)).await?
+    }
+
+    /// 获取章节列表 (标题已应用替换规则)
     pub async fn get_chapter_list(
         &self,
         book_url: &str,
         origin: Option<&str>,
         refresh: bool,
     ) -> Result<Vec<Chapter>, anyhow::Error> {
-        let cache_key = format!("chapters/{}.json", Self::url_to_key(book_url));
+        let mut chapters = self.load_chapter_list(book_url, origin, refresh).await?;
 
-        // 尝试从缓存读取
-        if !refresh {
-            if let Ok(content) = self.storage.read_cache(&cache_key).await {
-                if let Ok(chapters) = serde_json::from_str::<Vec<Chapter>>(&content) {
-                    return Ok(chapters);
-                }
+        let (rules, book_name, book_origin) = self.replace_scope(book_url).await;
+        let rules = CompiledReplaceRules::compile(&rules, &book_name, book_origin.as_deref().or(origin), true);
+        for chapter in chapters.iter_mut() {
+            chapter.title = rules.apply(&chapter.title);
+        }
+
+        Ok(chapters)
+    }
+
+    /// 获取原始章节列表
+    ///
+    /// 有效期内的缓存直接返回；缓存过期后重新获取，获取失败时退回过期的缓存。
+    async fn load_chapter_list(
+        &self,
+        book_url: &str,
+        origin: Option<&str>,
+        refresh: bool,
+    ) -> Result<Vec<Chapter>, anyhow::Error> {
+        let cache_key = Self::chapter_list_key(book_url);
+
+        // 本地书籍的目录在导入时生成，只从缓存读取
+        if local_book::is_local_book(book_url) {
+            let content = self
+                .storage
+                .read_cache(&cache_key)
+                .await
+                .map_err(|_| ServiceError::not_found("Book", book_url))?;
+            return Ok(serde_json::from_str(&content)?);
+        }
+
+        let cached = match refresh {
+            true => None,
+            false => self.cached_chapter_list(&cache_key).await,
+        };
+        if let Some((chapters, age)) = &cached {
+            if *age < self.chapter_list_ttl {
+                return Ok(chapters.clone());
             }
         }
 
-        // 获取书源：优先使用 origin 参数，否则从 book info 中获取
-        let source = if let Some(origin_url) = origin {
-            self.get_source(origin_url).await?
-        } else {
-            let book = self.get_book_info(book_url, None).await?;
-            self.get_source(&book.origin.unwrap_or_default()).await?
+        let fetched = self.fetch_chapter_list(book_url, origin).await;
+        let now = chrono::Utc::now().timestamp_millis();
+        let failure = fetched
+            .as_ref()
+            .err()
+            .map(|(stage, e)| bookshelf::update_error(*stage, e, now));
+        if let Err(e) = self.record_update_error(book_url, failure).await {
+            tracing::warn!("Failed to record update result of {}: {:#}", book_url, e);
+        }
+
+        match fetched {
+            Ok(chapters) => {
+                self.store_chapter_list(book_url, &chapters).await?;
+                Ok(chapters)
+            }
+            Err((_, e)) => match cached {
+                Some((chapters, _)) => {
+                    tracing::warn!("Using stale chapter list of {}: {:#}", book_url, e);
+                    Ok(chapters)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// 缓存的目录及其缓存时长
+    async fn cached_chapter_list(&self, cache_key: &str) -> Option<(Vec<Chapter>, Duration)> {
+        let content = self.storage.read_cache(cache_key).await.ok()?;
+        let chapters = serde_json::from_str::<Vec<Chapter>>(&content).ok()?;
+        let age = self.storage.cache_age(cache_key).await.unwrap_or(Duration::MAX);
+        Some((chapters, age))
+    }
+
+    /// 记录书架书籍的更新结果：失败时写入 lastUpdateError，成功时清除；不在书架上的书忽略
+    async fn record_update_error(&self, book_url: &str, error: Option<BookUpdateError>) -> anyhow::Result<()> {
+        let mut shelf = self.shelf_mut().await?;
+        let Some(book) = shelf.get_mut(book_url) else {
+            return Ok(());
         };
+        if book.last_update_error.is_none() && error.is_none() {
+            return Ok(());
+        }
+        book.last_update_error = error;
+        self.shelf_store.write_book(book).await
+    }
+
+    /// 从书源获取目录，失败时同时返回失败的阶段
+    async fn fetch_chapter_list(
+        &self,
+        book_url: &str,
+        origin: Option<&str>,
+    ) -> Result<Vec<Chapter>, (UpdateStage, anyhow::Error)> {
+        // 获取书源：优先使用 origin 参数，否则从 book info 中获取
+        let source = match origin {
+            Some(origin_url) => self.get_source(origin_url).await,
+            None => {
+                let book = self
+                    .get_book_info(book_url, None)
+                    .await
+                    .map_err(|e| (UpdateStage::Info, e))?;
+                self.get_source(&book.origin.unwrap_or_default()).await
+            }
+        }
+        .map_err(|e| (UpdateStage::Source, e))?;
 
         // Step 1: Get book info to resolve toc_url
         // The toc_url template may reference book info fields like {{$.resourceID}}
@@ -125,122 +464,810 @@ impl BookService {
         );
 
         // 使用 BookSourceEngine 获取章节
-        let source_json = serde_json::to_string(&source)?;
+        let source_json = serde_json::to_string(&source).map_err(|e| (UpdateStage::Source, e.into()))?;
         let toc_url_clone = toc_url.clone();
-        let kv_dist = self.kv_store.clone();
-        let chapters = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<Chapter>> {
-            let engine_source: BookSource = serde_json::from_str(&source_json)?;
-            let engine = BookSourceEngine::new(engine_source, kv_dist.clone())?;
-            let engine_chapters = engine.get_chapters(&toc_url_clone)?;
+        let engines = self.engines.clone();
+        let book = book_info.as_ref().map(book_context);
+        tokio::task::spawn_blocking(move || {
+            let engine = serde_json::from_str::<BookSource>(&source_json)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| engines.engine(&source))
+                .map_err(|e| (UpdateStage::Source, e))?;
+            if let Some(book) = book {
+                engine.set_book(book);
+            }
+            fetch_toc(&engine, &toc_url_clone).map_err(|e| (UpdateStage::Toc, e))
+        })
+        .await
+        .map_err(|e| (UpdateStage::Toc, e.into()))?
+    }
 
-            Ok(engine_chapters
-                .into_iter()
-                .enumerate()
-                .map(|(i, c)| Chapter {
-                    title: c.title,
-                    url: c.url,
-                    index: i as i32,
+    /// 缓存新获取的目录
+    ///
+    /// 目录变动时，从第一个 URL 不一致的章节起清除正文缓存。
+    async fn store_chapter_list(&self, book_url: &str, chapters: &[Chapter]) -> Result<(), anyhow::Error> {
+        if chapters.is_empty() {
+            return Ok(());
+        }
+        let cache_key = Self::chapter_list_key(book_url);
+        if let Ok(old) = self.storage.read_cache(&cache_key).await {
+            if let Ok(old_chapters) = serde_json::from_str::<Vec<Chapter>>(&old) {
+                if let Some(index) = Self::first_changed_chapter(&old_chapters, chapters) {
+                    tracing::info!("Chapter list of {} shifted at index {}", book_url, index);
+                    self.content_cache.invalidate_from(book_url, index).await?;
+                }
+            }
+        }
+
+        let content = serde_json::to_string(chapters)?;
+        let _ = self.storage.write_cache(&cache_key, &content).await;
+        Ok(())
+    }
+
+    /// 检查书架书籍的更新
+    ///
+    /// 指定 `book_url` 时只检查该书，否则检查所有允许更新的书籍。同一书源的书籍
+    /// 共用一个引擎顺序获取目录，使书源的 concurrentRate 生效；不同书源并行，
+    /// 同时处理的书源数有上限。单本书失败只记录 lastUpdateError。
+    ///
+    /// `toc_max_age` 内缓存的目录页直接复用，不再请求；为 `None` 时仍会用 ETag 等校验缓存。
+    pub async fn refresh_bookshelf(
+        &self,
+        book_url: Option<&str>,
+        toc_max_age: Option<Duration>,
+    ) -> Result<RefreshSummary, anyhow::Error> {
+        let _guard = self.refresh_lock.lock().await;
+        let shelf = self.get_bookshelf(false).await?;
+        let books: Vec<Book> = match book_url {
+            Some(url) => {
+                let book = shelf
+                    .into_iter()
+                    .find(|b| b.book_url == url)
+                    .ok_or_else(|| ServiceError::not_found("Book", url))?;
+                if local_book::is_local_book(url) {
+                    return Err(ServiceError::invalid_input("Local books cannot be refreshed").into());
+                }
+                vec![book]
+            }
+            None => shelf.into_iter().filter(bookshelf::can_refresh).collect(),
+        };
+
+        let mut by_source: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for book in &books {
+            let toc_url = book.toc_url.clone().unwrap_or_else(|| book.book_url.clone());
+            by_source
+                .entry(book.origin.clone().unwrap_or_default())
+                .or_default()
+                .push((book.book_url.clone(), toc_url));
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let semaphore = Arc::new(Semaphore::new(REFRESH_CONCURRENCY));
+        let mut handles = Vec::new();
+        for (origin, targets) in by_source {
+            let source = self.get_source(&origin).await.and_then(|s| Ok(serde_json::to_string(&s)?));
+            let engines = self.engines.clone();
+            let semaphore = semaphore.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let source_json = match source {
+                    Ok(json) => json,
+                    Err(e) => {
+                        let error = bookshelf::update_error(UpdateStage::Source, &e, now);
+                        return targets.into_iter().map(|(url, _)| (url, Err(error.clone()))).collect();
+                    }
+                };
+                let fetched = tokio::task::spawn_blocking(move || {
+                    let engine = serde_json::from_str::<BookSource>(&source_json)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|source| engines.engine(&source))
+                        .map(|mut engine| {
+                            engine.set_toc_max_age(toc_max_age);
+                            engine
+                        });
+                    targets
+                        .into_iter()
+                        .map(|(url, toc_url)| {
+                            let result = match &engine {
+                                Ok(engine) => fetch_toc(engine, &toc_url)
+                                    .map_err(|e| bookshelf::update_error(UpdateStage::Toc, &e, now)),
+                                Err(e) => Err(bookshelf::update_error(UpdateStage::Source, e, now)),
+                            };
+                            (url, result)
+                        })
+                        .collect::<Vec<_>>()
                 })
-                .collect())
-        })
-        .await??;
+                .await;
+                fetched.unwrap_or_default()
+            }));
+        }
 
-        // 缓存
-        if !chapters.is_empty() {
-            let content = serde_json::to_string(&chapters)?;
-            let _ = self.storage.write_cache(&cache_key, &content).await;
+        let mut results: HashMap<String, Result<Vec<Chapter>, BookUpdateError>> = HashMap::new();
+        for handle in handles {
+            results.extend(handle.await.unwrap_or_default());
+        }
+        for (url, result) in results.iter_mut() {
+            if let Ok(chapters) = result {
+                if let Err(e) = self.store_chapter_list(url, chapters).await {
+                    tracing::warn!("Failed to cache chapter list of {}: {}", url, e);
+                }
+            }
         }
 
-        Ok(chapters)
+        let mut summary = RefreshSummary::default();
+        let mut shelf = self.shelf_mut().await?;
+        let mut checked = Vec::new();
+        for (url, result) in results {
+            let Some(book) = shelf.get_mut(&url) else {
+                continue;
+            };
+            summary.checked += 1;
+            if let Err(e) = &result {
+                tracing::warn!("Update check failed for {}: {}", book.name, e.message);
+                summary.failed += 1;
+            }
+            if bookshelf::apply_update_check(book, result.as_deref().map_err(Clone::clone), now) {
+                summary.updated += 1;
+            }
+            checked.push(book.clone());
+        }
+        for book in &checked {
+            self.shelf_store.write_book(book).await?;
+        }
+        Ok(summary)
+    }
+
+    /// 设置书籍是否参与书架更新检查
+    pub async fn set_can_update(&self, book_url: &str, can_update: bool) -> Result<Book, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
+        let book = shelf
+            .get_mut(book_url)
+            .ok_or_else(|| ServiceError::not_found("Book", book_url))?;
+        book.can_update = Some(can_update);
+        self.shelf_store.write_book(book).await?;
+        Ok(book.clone())
+    }
+
+    /// 最近一次更新失败的书籍，按失败时间倒序
+    pub async fn update_failures(&self) -> Result<Vec<Book>, anyhow::Error> {
+        let mut books: Vec<Book> = self
+            .get_bookshelf(false)
+            .await?
+            .into_iter()
+            .filter(|b| b.last_update_error.is_some())
+            .collect();
+        books.sort_by_key(|b| std::cmp::Reverse(b.last_update_error.as_ref().map_or(0, |e| e.time)));
+        Ok(books)
     }
 
-    /// 获取章节内容
+    /// 获取章节内容 (缓存原文，返回时应用净化、排版与替换规则)
+    ///
+    /// `max_pages` 限制本次抓取跟随 nextContentUrl 的页数，命中缓存时不生效。
+    /// `format` 见 [`Self::content_format`]。`deadline` 之后不再发起书源请求，超时返回已抓取的页数与内容。
+    /// 漫画书源返回 [`ImageContent`] 的 JSON，图片地址改写为经由图片代理。
     pub async fn get_book_content(
         &self,
         book_url: &str,
         index: i32,
+        refresh: bool,
+        max_pages: Option<usize>,
+        format: Option<bool>,
+        deadline: Option<Deadline>,
     ) -> Result<String, anyhow::Error> {
-        let cache_key = format!("content/{}/{}.txt", Self::url_to_key(book_url), index);
+        // 本地书籍没有可刷新的来源
+        let refresh = refresh && !local_book::is_local_book(book_url);
+        let content = self
+            .content_cache
+            .get_or_fetch(book_url, index, refresh, || {
+                self.fetch_book_content(book_url, index, max_pages, deadline)
+            })
+            .await?;
+        self.render_content(book_url, &content, format).await
+    }
 
-        // 尝试从缓存读取
-        if let Ok(content) = self.storage.read_cache(&cache_key).await {
-            return Ok(content);
+    /// 对缓存的原文应用净化、排版与替换规则；漫画章节的图片地址改写为经由图片代理
+    async fn render_content(
+        &self,
+        book_url: &str,
+        content: &str,
+        format: Option<bool>,
+    ) -> Result<String, anyhow::Error> {
+        let (rules, book_name, origin) = self.replace_scope(book_url).await;
+        // 漫画章节不经净化与替换规则，以免改坏图片地址
+        if let Some(images) = ImageContent::from_content(content) {
+            return Ok(serde_json::to_string(&proxy_images(images, origin.as_deref()))?);
         }
+        let filters = self.content_filters.compiled().await;
+        let mut content = filters.apply(content, origin.as_deref());
+        if let Some(options) = self.content_format(book_url, format).await {
+            content = format_content(&content, &options);
+        }
+        Ok(CompiledReplaceRules::compile(&rules, &book_name, origin.as_deref(), false).apply(&content))
+    }
 
-        // 获取章节列表
-        let chapters = self.get_chapter_list(book_url, None, false).await?;
-        let chapter = chapters
-            .get(index as usize)
-            .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+    /// 正文排版选项
+    ///
+    /// 请求指定 `format` 时优先：true 使用书籍保存的选项 (未保存或已关闭时用默认选项)，
+    /// false 不排版；未指定时使用书籍保存且开启的选项。
+    async fn content_format(&self, book_url: &str, format: Option<bool>) -> Option<ContentFormatOptions> {
+        let saved = match self.shelf().await {
+            Ok(shelf) => shelf.get(book_url).and_then(|b| b.formatting.clone()),
+            Err(_) => None,
+        };
+        let saved = saved.filter(|options| options.enabled);
+        match format {
+            Some(true) => Some(saved.unwrap_or_default()),
+            Some(false) => None,
+            None => saved,
+        }
+    }
 
-        // 获取书源
-        let book = self.get_book_info(book_url, None).await?;
-        let source = self.get_source(&book.origin.unwrap_or_default()).await?;
+    /// 获取替换规则及其作用域所需的书名与书源
+    async fn replace_scope(&self, book_url: &str) -> (Vec<ReplaceRule>, String, Option<String>) {
+        let rules = self.replace_service.get_all_rules().await.unwrap_or_default();
+        let book = match self.shelf().await {
+            Ok(shelf) => shelf.get(book_url).map(|b| (b.name.clone(), b.origin.clone())),
+            Err(_) => None,
+        };
+        match book {
+            Some((name, origin)) => (rules, name, origin),
+            None => (rules, String::new(), None),
+        }
+    }
+
+    /// 获取章节内容 (SSE)，每抓取一页即推送该页内容
+    ///
+    /// 每页内容先经书源清理，再应用净化、排版与替换规则后以 `chunk` 事件 ({pageIndex, text}) 推送，
+    /// 最后发送 `done` 事件 ({length})，失败时发送 `error` 事件 ({message})。
+    /// 拼接后的原文与 get_book_content 一样写入缓存；命中缓存时整章作为一个 chunk 推送。
+    pub fn get_book_content_sse(
+        &self,
+        book_url: String,
+        index: i32,
+        refresh: bool,
+        max_pages: Option<usize>,
+        format: Option<bool>,
+    ) -> impl Stream<Item = Result<Event, Infallible>> {
+        let service = self.clone();
+
+        async_stream::stream! {
+            let filters = service.content_filters.compiled().await;
+            let (rules, book_name, origin) = service.replace_scope(&book_url).await;
+            let rules = CompiledReplaceRules::compile(&rules, &book_name, origin.as_deref(), false);
+            let format = service.content_format(&book_url, format).await;
+            let replace = |text: &str| {
+                if let Some(images) = ImageContent::from_content(text) {
+                    return serde_json::to_string(&proxy_images(images, origin.as_deref())).unwrap_or_default();
+                }
+                let mut text = filters.apply(text, origin.as_deref());
+                if let Some(options) = &format {
+                    text = format_content(&text, options);
+                }
+                rules.apply(&text)
+            };
+
+            let refresh = refresh && !local_book::is_local_book(&book_url);
+            let cached = if refresh { None } else { service.content_cache.get(&book_url, index).await };
+
+            let content = match cached {
+                Some(content) => {
+                    yield Ok(content_chunk_event(0, &replace(&content)));
+                    Ok(content)
+                }
+                None => match service.content_fetch_input(&book_url, index, max_pages).await {
+                    Ok(input) => {
+                        // 正文在阻塞线程中逐页抓取，每页通过通道发送回来
+                        let (tx, mut rx) = tokio::sync::mpsc::channel::<(usize, String)>(4);
+                        let engines = service.engines.clone();
+                        let fetch = tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+                            let (engine, chapter_url) = input.engine(&engines)?;
+                            engine.get_content_pages(&chapter_url, |page, text| {
+                                tx.blocking_send((page, text))
+                                    .map_err(|_| anyhow::anyhow!("Content stream closed"))
+                            })
+                        });
+
+                        while let Some((page, text)) = rx.recv().await {
+                            yield Ok(content_chunk_event(page, &replace(&text)));
+                        }
+                        match fetch.await {
+                            Ok(Ok(content)) => {
+                                if !content.is_empty() {
+                                    if let Err(e) = service.content_cache.put(&book_url, index, &content).await {
+                                        tracing::warn!("Failed to cache chapter {} of {}: {}", index, book_url, e);
+                                    }
+                                }
+                                Ok(content)
+                            }
+                            Ok(Err(e)) => Err(e),
+                            Err(e) => Err(e.into()),
+                        }
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+
+            match content {
+                Ok(content) => {
+                    let done = serde_json::json!({ "length": replace(&content).chars().count() });
+                    yield Ok(Event::default().event("done").data(done.to_string()));
+                }
+                Err(e) => {
+                    let error = serde_json::json!({ "message": format!("{:#}", e) });
+                    yield Ok(Event::default().event("error").data(error.to_string()));
+                }
+            }
+        }
+    }
+
+    /// 从书源获取章节内容
+    async fn fetch_book_content(
+        &self,
+        book_url: &str,
+        index: i32,
+        max_pages: Option<usize>,
+        deadline: Option<Deadline>,
+    ) -> Result<String, anyhow::Error> {
+        let input = self.content_fetch_input(book_url, index, max_pages).await?;
 
         // 使用 BookSourceEngine 获取内容
-        let source_json = serde_json::to_string(&source)?;
-        let chapter_url = chapter.url.clone();
-        let kv_dist = self.kv_store.clone();
-        let content = tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
-            let engine_source: BookSource = serde_json::from_str(&source_json)?;
-            let engine = BookSourceEngine::new(engine_source, kv_dist.clone())?;
+        let engines = self.engines.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+            let (mut engine, chapter_url) = input.engine(&engines)?;
+            engine.set_deadline(deadline);
             engine.get_content(&chapter_url)
         })
-        .await??;
+        .await?
+    }
 
-        // 缓存
+    /// 预取一章正文写入缓存 (阻塞调用)，已缓存时返回 false
+    ///
+    /// `engine` 在同一本书的连续预取间复用，使书源的 concurrentRate 限制对整批请求生效。
+    pub(super) fn prefetch_content(
+        &self,
+        rt: &tokio::runtime::Handle,
+        engine: &mut Option<BookSourceEngine>,
+        book_url: &str,
+        index: i32,
+    ) -> anyhow::Result<bool> {
+        if rt.block_on(self.content_cache.contains(book_url, index)) {
+            return Ok(false);
+        }
+        let input = rt.block_on(self.content_fetch_input(book_url, index, None))?;
+        let engine = match engine {
+            Some(engine) => engine,
+            None => engine.insert(self.engines.engine(&input.source)?),
+        };
+        let chapter_url = input.prepare(engine);
+        let content = engine.get_content(&chapter_url)?;
         if !content.is_empty() {
-            let _ = self.storage.write_cache(&cache_key, &content).await;
+            rt.block_on(self.content_cache.put(book_url, index, &content))?;
         }
+        Ok(true)
+    }
 
-        Ok(content)
+    /// 目录中 [start, end] 范围内的章节序号，start 缺省为第一章、end 缺省为最后一章
+    pub(super) async fn chapter_range(
+        &self,
+        book_url: &str,
+        start: Option<i32>,
+        end: Option<i32>,
+    ) -> Result<Vec<i32>, anyhow::Error> {
+        let total = self.load_chapter_list(book_url, None, false).await?.len() as i32;
+        let start = start.unwrap_or(0).max(0);
+        let end = end.unwrap_or(total - 1).min(total - 1);
+        if start > end {
+            return Err(ServiceError::invalid_input(format!("Invalid chapter range: {}..{}", start, end)).into());
+        }
+        Ok((start..=end).collect())
     }
 
-    /// 获取书籍信息
-    pub async fn get_book_info(
+    /// 并发抓取多章正文写入缓存 (阻塞调用)
+    ///
+    /// 已缓存的章节直接跳过，其余章节由最多 `concurrency` 个线程抓取；各线程使用同一引擎的分叉，
+    /// 共享书源的 concurrentRate 限制。每章结束后以 Ok(true) 抓取、Ok(false) 已缓存或错误调用 `on_chapter`，
+    /// `cancel` 取消后不再开始新的章节。
+    pub(super) fn cache_contents(
         &self,
+        rt: &tokio::runtime::Handle,
         book_url: &str,
-        origin: Option<&str>,
-    ) -> Result<Book, anyhow::Error> {
-        let shelf = self.bookshelf.read().await;
-        if let Some(book) = shelf.iter().find(|b| b.book_url == book_url) {
-            return Ok(book.clone());
+        indices: &[i32],
+        concurrency: usize,
+        cancel: &CancellationToken,
+        on_chapter: impl Fn(i32, anyhow::Result<bool>) + Sync,
+    ) -> anyhow::Result<()> {
+        let mut pending = Vec::new();
+        for &index in indices {
+            if rt.block_on(self.content_cache.contains(book_url, index)) {
+                on_chapter(index, Ok(false));
+            } else {
+                pending.push(index);
+            }
         }
-        drop(shelf);
+        let Some(&first) = pending.first() else {
+            return Ok(());
+        };
 
-        // 如果不在书架上，尝试从外部加载 (例如搜索结果详情)
-        self.get_book_info_from_web(book_url, origin).await
+        let input = rt.block_on(self.content_fetch_input(book_url, first, None))?;
+        let chapters = rt.block_on(self.load_chapter_list(book_url, None, false))?;
+        let engine = self.engines.engine(&input.source)?;
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency.clamp(1, pending.len()) {
+                scope.spawn(|| {
+                    let mut engine = engine.fork();
+                    engine.set_chapter_urls(input.chapter_urls.clone());
+                    engine.set_book(input.book.clone());
+                    while !cancel.is_cancelled() {
+                        let Some(&index) = pending.get(next.fetch_add(1, Ordering::SeqCst)) else {
+                            return;
+                        };
+                        let result = self.cache_chapter(rt, &engine, book_url, &chapters, index);
+                        on_chapter(index, result.map(|_| true));
+                    }
+                });
+            }
+        });
+        Ok(())
     }
 
-    /// 从网络获取书籍详细信息
-    async fn get_book_info_from_web(
+    /// 抓取一章正文写入缓存 (阻塞调用)，正文为空视为失败
+    fn cache_chapter(
         &self,
+        rt: &tokio::runtime::Handle,
+        engine: &BookSourceEngine,
         book_url: &str,
-        origin: Option<&str>,
-    ) -> Result<Book, anyhow::Error> {
-        use crate::engine::book_source::{BookSource, BookSourceEngine};
+        chapters: &[Chapter],
+        index: i32,
+    ) -> anyhow::Result<()> {
+        let chapter = chapters
+            .get(index as usize)
+            .ok_or_else(|| ServiceError::not_found("Chapter", index.to_string()))?;
+        engine.set_chapter(ChapterContext {
+            title: chapter.title.clone(),
+            url: chapter.url.clone(),
+            index: index as usize,
+        });
+        let content = engine.get_content(&chapter.url)?;
+        if content.is_empty() {
+            anyhow::bail!("Empty content");
+        }
+        rt.block_on(self.content_cache.put(book_url, index, &content))?;
+        Ok(())
+    }
 
-        // 尝试用 origin 找到匹配源，否则用 URL 猜测
-        let source = if let Some(origin_url) = origin {
-            self.get_source(origin_url).await?
-        } else {
-            self.get_source_by_url(book_url).await?
+    /// 第 `after` 章之后最多 count 章的缓存情况，`after` 缺省时取书架记录的阅读进度
+    ///
+    /// 只读取已缓存的目录，目录未缓存时返回空。
+    pub async fn upcoming_cache_status(&self, book_url: &str, after: Option<i32>, count: usize) -> Vec<PrefetchChapter> {
+        let after = match after {
+            Some(after) => after,
+            None => match self.shelf().await {
+                Ok(shelf) => shelf.get(book_url).and_then(|b| b.dur_chapter_index).unwrap_or(-1),
+                Err(_) => -1,
+            },
         };
-        let source_json = serde_json::to_string(&source)?;
+        let total = match self.storage.read_cache(&Self::chapter_list_key(book_url)).await {
+            Ok(content) => serde_json::from_str::<Vec<Chapter>>(&content).map_or(0, |c| c.len() as i32),
+            Err(_) => 0,
+        };
+        let mut chapters = Vec::new();
+        for index in (after + 1..total).take(count) {
+            chapters.push(PrefetchChapter {
+                index,
+                cached: self.content_cache.contains(book_url, index).await,
+            });
+        }
+        chapters
+    }
 
-        let book_url_str = book_url.to_string();
-        let kv_dist = self.kv_store.clone();
-        let result = tokio::task::spawn_blocking(move || {
-            let engine_source: BookSource = serde_json::from_str(&source_json)?;
-            let engine = BookSourceEngine::new(engine_source, kv_dist.clone())?;
-            engine.get_book_info(&book_url_str)
+    /// 章节缓存的各个版本 (刷新后内容变化时保留的旧版本及当前版本)
+    pub async fn get_chapter_versions(&self, book_url: &str, index: i32) -> Result<Vec<ChapterVersion>, anyhow::Error> {
+        let versions = self.content_cache.versions(book_url, index).await;
+        if versions.is_empty() {
+            return Err(ServiceError::not_found("Cached chapter", format!("{}#{}", book_url, index)).into());
+        }
+        Ok(versions)
+    }
+
+    /// 比较章节的两个版本；from 缺省为上一个版本，to 缺省为当前版本
+    pub async fn get_chapter_diff(
+        &self,
+        book_url: &str,
+        index: i32,
+        from: Option<u32>,
+        to: Option<u32>,
+    ) -> Result<ChapterDiff, anyhow::Error> {
+        let versions = self.get_chapter_versions(book_url, index).await?;
+        let current = versions.last().map_or(1, |v| v.version);
+        let to = to.unwrap_or(current);
+        let from = match from {
+            Some(from) => from,
+            None => versions
+                .iter()
+                .rev()
+                .map(|v| v.version)
+                .find(|v| *v < to)
+                .ok_or_else(|| ServiceError::not_found("Chapter version", format!("before v{}", to)))?,
+        };
+        let read = |version: u32| async move {
+            self.content_cache
+                .get_version(book_url, index, version)
+                .await
+                .ok_or_else(|| ServiceError::not_found("Chapter version", format!("v{}", version)))
+        };
+        let (old, new) = (read(from).await?, read(to).await?);
+
+        let ops = text_diff::diff_lines(&old, &new);
+        Ok(ChapterDiff {
+            from,
+            to,
+            added: ops.iter().filter(|op| matches!(op, DiffOp::Insert(_))).count(),
+            removed: ops.iter().filter(|op| matches!(op, DiffOp::Delete(_))).count(),
+            diff: text_diff::unified_diff(&ops, &format!("v{}", from), &format!("v{}", to)),
         })
-        .await?;
+    }
 
-        match result {
-            Ok(item) => Ok(Book {
-                book_url: book_url.to_string(),
-                name: item.name,
+    /// 抓取章节内容所需的书源与章节 URL
+    async fn content_fetch_input(
+        &self,
+        book_url: &str,
+        index: i32,
+        max_pages: Option<usize>,
+    ) -> Result<ContentFetchInput, anyhow::Error> {
+        // 本地书籍正文在导入时已全部写入缓存
+        if local_book::is_local_book(book_url) {
+            return Err(ServiceError::not_found("Chapter", index.to_string()).into());
+        }
+
+        // 获取章节列表
+        let chapters = self.load_chapter_list(book_url, None, false).await?;
+        let chapter = chapters
+            .get(index as usize)
+            .ok_or_else(|| ServiceError::not_found("Chapter", index.to_string()))?;
+
+        // 获取书源
+        let book = self.get_book_info(book_url, None).await?;
+        let source = self.get_source(book.origin.as_deref().unwrap_or_default()).await?;
+        Ok(ContentFetchInput {
+            source: serde_json::from_value(serde_json::to_value(&source)?)?,
+            book: book_context(&book),
+            chapter: ChapterContext {
+                title: chapter.title.clone(),
+                url: chapter.url.clone(),
+                index: index as usize,
+            },
+            chapter_urls: chapters.iter().map(|c| c.url.clone()).collect(),
+            max_pages,
+        })
+    }
+
+    /// 清除单本书的正文缓存
+    pub async fn clear_book_cache(&self, book_url: &str) -> Result<(), anyhow::Error> {
+        // 本地书籍的缓存即正文本身，不能清除
+        if local_book::is_local_book(book_url) {
+            return Ok(());
+        }
+        self.content_cache.clear_book(book_url).await?;
+        Ok(())
+    }
+
+    /// 解析数据目录下的本地文件路径，拒绝越出数据目录的路径
+    pub fn local_file_path(&self, filename: &str) -> Result<std::path::PathBuf, ServiceError> {
+        let invalid = || ServiceError::invalid_input(format!("Invalid file path: {}", filename));
+        if filename.is_empty() {
+            return Err(invalid());
+        }
+        sandbox::resolve(&self.storage.file_path(""), filename).map_err(|_| invalid())
+    }
+
+    /// 导入本地书籍 (TXT / EPUB)
+    ///
+    /// 正文写入章节缓存，目录写入目录缓存，最后加入书架。
+    /// TXT 按 toc_rules 切分章节 (为空时使用默认规则)；EPUB 按 spine 顺序生成章节。
+    pub async fn import_local_book(
+        &self,
+        path: &std::path::Path,
+        toc_rules: &[String],
+    ) -> Result<Book, anyhow::Error> {
+        let file = std::fs::File::open(path)
+            .map_err(|_| ServiceError::not_found("File", path.display().to_string()))?;
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let book_id = format!("{:x}", md5::compute(canonical.to_string_lossy().as_bytes()));
+        let book_url = format!("{}{}", LOCAL_URL_PREFIX, book_id);
+        self.content_cache.clear_book(&book_url).await?;
+
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (name, author) = local_book::parse_file_name(&stem);
+        let mut book = Book {
+            book_url: book_url.clone(),
+            name,
+            author,
+            origin: Some(LOCAL_ORIGIN.to_string()),
+            origin_name: Some("本地".to_string()),
+            can_update: Some(false),
+            ..Default::default()
+        };
+
+        let is_epub = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("epub"));
+        let chapters = if is_epub {
+            self.import_epub_chapters(file, &book_id, &mut book).await?
+        } else {
+            self.import_txt_chapters(file, toc_rules, &book_url).await?
+        };
+
+        if chapters.is_empty() {
+            return Err(ServiceError::invalid_input(format!("Empty book file: {}", path.display())).into());
+        }
+        tracing::info!("Imported local book {} ({} chapters)", path.display(), chapters.len());
+
+        let cache_key = Self::chapter_list_key(&book_url);
+        self.storage
+            .write_cache(&cache_key, &serde_json::to_string(&chapters)?)
+            .await?;
+
+        book.total_chapter_num = Some(chapters.len() as i32);
+        book.latest_chapter_title = chapters.last().map(|c| c.title.clone());
+        self.save_book(book).await
+    }
+
+    /// 流式切分 TXT，章节逐个写入缓存
+    async fn import_txt_chapters(
+        &self,
+        file: std::fs::File,
+        toc_rules: &[String],
+        book_url: &str,
+    ) -> Result<Vec<Chapter>, anyhow::Error> {
+        let splitter = ChapterSplitter::new(toc_rules)
+            .map_err(|e| ServiceError::invalid_input(format!("{:#}", e)))?;
+
+        // 解析在阻塞线程中进行，章节逐个发送回来写入缓存
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<LocalChapter>(16);
+        let parse = tokio::task::spawn_blocking(move || {
+            let reader = std::io::BufReader::new(file);
+            splitter.split(reader, |chapter| {
+                tx.blocking_send(chapter)
+                    .map_err(|_| anyhow::anyhow!("Import cancelled"))
+            })
+        });
+
+        let mut chapters = Vec::new();
+        while let Some(chapter) = rx.recv().await {
+            chapters.push(self.put_local_chapter(book_url, chapters.len() as i32, chapter).await?);
+        }
+        let encoding = parse.await??;
+        tracing::debug!("Local TXT {} decoded as {}", book_url, encoding.name());
+        Ok(chapters)
+    }
+
+    /// 解析 EPUB，图片解压到资源目录，章节写入缓存
+    async fn import_epub_chapters(
+        &self,
+        file: std::fs::File,
+        book_id: &str,
+        book: &mut Book,
+    ) -> Result<Vec<Chapter>, anyhow::Error> {
+        let asset_dir = self.storage.cache_path(&format!("assets/{}", book_id));
+        let asset_prefix = format!("/reader3/assets/{}/", book_id);
+        let epub = tokio::task::spawn_blocking(move || {
+            if asset_dir.exists() {
+                std::fs::remove_dir_all(&asset_dir)?;
+            }
+            local_epub::read_epub(std::io::BufReader::new(file), &asset_dir, &asset_prefix)
+        })
+        .await?
+        .map_err(|e| ServiceError::invalid_input(format!("{:#}", e)))?;
+
+        if !epub.title.is_empty() {
+            book.name = epub.title;
+        }
+        if !epub.author.is_empty() {
+            book.author = epub.author;
+        }
+        book.intro = epub.intro;
+        book.cover_url = epub.cover_url;
+
+        let mut chapters = Vec::new();
+        for chapter in epub.chapters {
+            chapters.push(self.put_local_chapter(&book.book_url, chapters.len() as i32, chapter).await?);
+        }
+        Ok(chapters)
+    }
+
+    async fn put_local_chapter(
+        &self,
+        book_url: &str,
+        index: i32,
+        chapter: LocalChapter,
+    ) -> Result<Chapter, anyhow::Error> {
+        self.content_cache.put(book_url, index, &chapter.content).await?;
+        Ok(Chapter {
+            title: chapter.title,
+            url: format!("{}/{}", book_url, index),
+            index,
+        })
+    }
+
+    /// 读取本地书籍的资源文件 (EPUB 图片)，返回 (Content-Type, 内容)
+    pub async fn get_local_asset(
+        &self,
+        book_id: &str,
+        path: &str,
+    ) -> Result<(String, Vec<u8>), anyhow::Error> {
+        let asset_dir = self.storage.cache_path(&format!("assets/{}", book_id));
+        let file = local_epub::safe_join(&asset_dir, path)
+            .filter(|_| !book_id.contains(['/', '\\', '.']))
+            .ok_or_else(|| ServiceError::invalid_input(format!("Invalid asset path: {}", path)))?;
+        let data = tokio::fs::read(&file)
+            .await
+            .map_err(|_| ServiceError::not_found("Asset", path))?;
+
+        let ext = file
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let content_type = match ext.as_str() {
+            "jpg" | "jpeg" => "image/jpeg",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            "css" => "text/css",
+            _ => "application/octet-stream",
+        };
+        Ok((content_type.to_string(), data))
+    }
+
+    /// 获取书籍信息
+    pub async fn get_book_info(
+        &self,
+        book_url: &str,
+        origin: Option<&str>,
+    ) -> Result<Book, anyhow::Error> {
+        let book = self.shelf().await?.get(book_url).cloned();
+        if let Some(book) = book {
+            return Ok(book);
+        }
+
+        // 如果不在书架上，尝试从外部加载 (例如搜索结果详情)
+        self.get_book_info_from_web(book_url, origin).await
+    }
+
+    /// 从网络获取书籍详细信息
+    async fn get_book_info_from_web(
+        &self,
+        book_url: &str,
+        origin: Option<&str>,
+    ) -> Result<Book, anyhow::Error> {
+        use crate::engine::book_source::BookSource;
+
+        // 尝试用 origin 找到匹配源，否则用 URL 猜测
+        let source = if let Some(origin_url) = origin {
+            self.get_source(origin_url).await?
+        } else {
+            self.get_source_by_url(book_url).await?
+        };
+        let source_json = serde_json::to_string(&source)?;
+
+        let book_url_str = book_url.to_string();
+        let engines = self.engines.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let engine_source: BookSource = serde_json::from_str(&source_json)?;
+            let engine = engines.engine(&engine_source)?;
+            engine.get_book_info(&book_url_str)
+        })
+        .await?;
+
+        match result {
+            Ok(item) => Ok(Book {
+                book_url: book_url.to_string(),
+                name: item.name,
                 author: item.author,
                 cover_url: item.cover_url,
                 intro: item.intro,
@@ -334,12 +1361,19 @@ impl BookService {
         }
 
         tracing::error!("Source not found for URL: {}", book_url);
-        Err(anyhow::anyhow!("Source not found for URL: {}", book_url))
+        Err(ServiceError::not_found("Source", book_url).into())
     }
 
-    /// 搜索书籍 (使用新引擎)
-    pub async fn search(&self, key: &str) -> Result<Vec<SearchResult>, anyhow::Error> {
-        use crate::engine::book_source::{BookSource, BookSourceEngine};
+    /// 搜索书籍 (使用新引擎)，返回第一个有满足过滤条件结果的书源
+    ///
+    /// 依次尝试各书源，`deadline` 到达后不再尝试剩余书源。
+    pub async fn search(
+        &self,
+        key: &str,
+        filter: &SearchFilter,
+        deadline: Option<Deadline>,
+    ) -> Result<Vec<SearchResult>, anyhow::Error> {
+        use crate::engine::book_source::BookSource;
 
         // Lazy load sources if not already loaded
         {
@@ -354,29 +1388,47 @@ impl BookService {
             }
         }
 
-        let sources = self.sources.read().await;
-        tracing::debug!("Searching across {} sources", sources.len());
-
-        for source in sources
+        // 搜索期间不持有书源锁：记录响应时间需要写锁
+        let sources: Vec<BookSourceFull> = self
+            .sources
+            .read()
+            .await
             .iter()
             .filter(|s| s.enabled && !s.search_url.is_empty())
-        {
+            .filter(|s| !self.source_stats.is_cooling_down(&s.book_source_url))
+            .filter(|s| filter.includes_source(s))
+            .cloned()
+            .collect();
+        tracing::debug!("Searching across {} sources", sources.len());
+
+        for source in &sources {
+            if let Some(deadline) = deadline {
+                deadline.check()?;
+            }
             // 使用 JSON 序列化转换书源格式 (避免手动字段映射)
             let source_json = serde_json::to_string(source)?;
 
             // 在阻塞线程中运行新引擎
-            let key = key.to_string();
+            let search_key = key.to_string();
             let source_name = source.book_source_name.clone();
-            let kv_dist = self.kv_store.clone();
+            let engines = self.engines.clone();
+            let started = Instant::now();
             let result = tokio::task::spawn_blocking(move || {
                 let engine_source: BookSource = serde_json::from_str(&source_json)?;
-                match BookSourceEngine::new(engine_source, kv_dist.clone()) {
-                    Ok(engine) => engine.search(&key, 1),
-                    Err(e) => Err(e),
-                }
+                let mut engine = engines.engine(&engine_source)?;
+                engine.set_deadline(deadline);
+                engine.search(&search_key, 1)
             })
             .await;
-
+            let outcome = match result {
+                Ok(Ok(_)) => SearchOutcome::Success,
+                _ => SearchOutcome::Failure,
+            };
+            self.source_stats
+                .record(&source.book_source_url, started.elapsed().as_millis() as u64, outcome)
+                .await;
+
+            let result = result.map(|r| r.map(|books| filter.apply(key, books)));
             match result {
                 Ok(Ok(books)) if !books.is_empty() => {
                     // 转换为 SearchResult 格式
@@ -396,6 +1448,7 @@ impl BookService {
                             origin: Some(source.book_source_url.clone()),
                         })
                         .collect();
+                    self.persist_source_stats().await;
                     return Ok(results);
                 }
                 Ok(Err(e)) => {
@@ -406,25 +1459,27 @@ impl BookService {
             }
         }
 
+        self.persist_source_stats().await;
         Ok(vec![])
     }
 
-    /// 多书源搜索 (SSE)
+    async fn persist_source_stats(&self) {
+        if let Err(e) = self.source_stats.persist().await {
+            tracing::warn!("Failed to save source stats: {}", e);
+        }
+    }
+
+    /// 多书源搜索 (SSE)，不满足过滤条件的结果不推送
     pub fn search_multi_sse(
         &self,
         key: String,
-        exact_match: bool,
-        match_author: Option<String>,
         concurrent_count: usize,
+        filter: SearchFilter,
     ) -> impl Stream<Item = Result<Event, Infallible>> {
-        use crate::engine::book_source::{BookSource, BookSourceEngine};
-
         let sources = self.sources.clone();
         let storage = self.storage.clone();
-        let kv_store = self.kv_store.clone();
-        
-        let target_title = if exact_match { Some(key.trim().to_lowercase()) } else { None };
-        let target_author = match_author.map(|a| a.trim().to_lowercase());
+        let engines = self.engines.clone();
+        let source_stats = self.source_stats.clone();
 
         async_stream::stream! {
             // 确保书源已加载
@@ -437,7 +1492,8 @@ impl BookService {
             }
 
             let enabled_sources: Vec<_> = sources_guard.iter()
-                .filter(|s| s.enabled && !s.search_url.is_empty())
+                .filter(|s| s.enabled && !s.search_url.is_empty() && !source_stats.is_cooling_down(&s.book_source_url))
+                .filter(|s| filter.includes_source(s))
                 .cloned()
                 .collect();
             drop(sources_guard);
@@ -450,7 +1506,8 @@ impl BookService {
 
             // 并发搜索所有书源
             // 使用 Semaphore 限制最大并发数
-            let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrent_count));
+            let semaphore = Arc::new(Semaphore::new(concurrent_count));
+            let cancellation = SearchCancellation::new();
 
             // 使用 FuturesUnordered 来无序处理结果 (谁先完成谁先返回)
             use futures::stream::FuturesUnordered;
@@ -459,56 +1516,9 @@ impl BookService {
             let mut tasks = FuturesUnordered::new();
 
             for source in &enabled_sources {
-                let key = key.clone();
-                let source_name = source.book_source_name.clone();
-                let source_name_closure = source_name.clone(); // Clone for closure
-                let source_url = source.book_source_url.clone();
-                let source_json = match serde_json::to_string(&source) {
-                    Ok(json) => json,
-                    Err(e) => {
-                        tracing::error!("Failed to serialize source {}: {}", source_name, e);
-                        continue;
-                    }
-                };
-
-                let semaphore = semaphore.clone(); // Clone semaphore for task
-                let kv_dist = kv_store.clone();
-
-                tasks.push(tokio::task::spawn(async move {
-                    // 在任务内部获取 permit，这样循环不会阻塞
-                    let permit = semaphore.acquire_owned().await;
-
-                    // 确保 permit 在任务结束前一直被持有
-                    let _permit = permit;
-
-                    // 使用 timeout 包装阻塞任务
-                    let kv_dist_inner = kv_dist.clone();
-                    let result = tokio::time::timeout(
-                        std::time::Duration::from_secs(15),
-                        tokio::task::spawn_blocking(move || {
-                            let engine_source: BookSource = match serde_json::from_str(&source_json) {
-                                Ok(s) => s,
-                                Err(e) => return Err(anyhow::anyhow!("Failed to parse source: {}", e)),
-                            };
-
-                            match BookSourceEngine::new(engine_source, kv_dist_inner) {
-                                Ok(engine) => {
-                                    tracing::debug!("Searching source: {}", source_name_closure);
-                                    engine.search(&key, 1)
-                                },
-                                Err(e) => Err(anyhow::anyhow!("Failed to create engine: {}", e)),
-                            }
-                        })
-                    ).await;
-
-                    let final_result = match result {
-                        Ok(Ok(engine_res)) => engine_res, // success
-                        Ok(Err(e)) => Err(anyhow::anyhow!("Task join error: {}", e)), // join error
-                        Err(_) => Err(anyhow::anyhow!("Search timed out")), // timeout
-                    };
-
-                    (source_name, source_url, final_result)
-                }));
+                if let Some(task) = spawn_source_search(source, &key, engines.clone(), semaphore.clone(), cancellation.token()) {
+                    tasks.push(task);
+                }
             }
 
             // 处理结果流
@@ -527,21 +1537,13 @@ impl BookService {
                 yield Ok(Event::default().data(progress_json));
 
                 // task_result 是 JOIN 句柄的结果 (Result<..., JoinError>)
-                if let Ok((source_name, source_url, search_result)) = task_result {
+                if let Ok(outcome) = task_result {
+                    source_stats.record(&outcome.source_url, outcome.respond_time, outcome.stat_outcome()).await;
+                    let SourceSearchOutcome { source_name, source_url, result: search_result, .. } = outcome;
                     match search_result {
                         Ok(books) => {
                             tracing::info!("Found {} results from {}", books.len(), source_name);
-                            for mut book in books {
-                                // Strict Filtering Logic
-                                if let Some(ref t) = target_title {
-                                    if book.name.trim().to_lowercase() != *t { continue; }
-                                }
-                                if let Some(ref a) = target_author {
-                                    // Author match - handle empty author cases gracefully
-                                    let b_author = book.author.trim().to_lowercase();
-                                    if b_author.is_empty() || b_author != *a { continue; }
-                                }
-
+                            for mut book in filter.apply(&key, books) {
                                 // 补充来源信息
                                 book.kind = Some(source_name.clone());
 
@@ -581,10 +1583,217 @@ impl BookService {
                 }
             }
 
+            cancellation.finish();
+            if let Err(e) = source_stats.persist().await {
+                tracing::warn!("Failed to save source stats: {}", e);
+            }
             yield Ok(Event::default().data(r#"{"type":"end"}"#));
         }
     }
 
+    /// 多书源搜索并按书名与作者合并排序
+    ///
+    /// 完整结果保存在搜索会话中，每本书只内联返回最快的几个来源。
+    pub async fn search_merged(
+        &self,
+        key: &str,
+        concurrent_count: usize,
+        filter: &SearchFilter,
+    ) -> Result<MergedSearch, anyhow::Error> {
+        use futures::stream::FuturesUnordered;
+        use futures::StreamExt;
+
+        // Lazy load sources if not already loaded
+        {
+            let sources = self.sources.read().await;
+            if sources.is_empty() {
+                drop(sources);
+                let loaded: Vec<BookSourceFull> =
+                    self.storage.read_json_or_default(SOURCES_FILE).await;
+                tracing::info!("Lazy loaded {} sources for merged search", loaded.len());
+                let mut sources = self.sources.write().await;
+                *sources = loaded;
+            }
+        }
+
+        let semaphore = Arc::new(Semaphore::new(concurrent_count.max(1)));
+        let cancellation = SearchCancellation::new();
+        let mut tasks: FuturesUnordered<_> = self
+            .sources
+            .read()
+            .await
+            .iter()
+            .filter(|s| s.enabled && !s.search_url.is_empty())
+            .filter(|s| !self.source_stats.is_cooling_down(&s.book_source_url))
+            .filter(|s| filter.includes_source(s))
+            .filter_map(|s| spawn_source_search(s, key, self.engines.clone(), semaphore.clone(), cancellation.token()))
+            .collect();
+
+        let mut aggregator = SearchAggregator::new(key);
+        while let Some(task_result) = tasks.next().await {
+            let outcome = match task_result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    tracing::warn!("search spawn error: {}", e);
+                    continue;
+                }
+            };
+            self.source_stats
+                .record(&outcome.source_url, outcome.respond_time, outcome.stat_outcome())
+                .await;
+            match outcome.result {
+                Ok(books) => aggregator.add(
+                    &outcome.source_url,
+                    &outcome.source_name,
+                    filter.apply(key, books),
+                    outcome.respond_time,
+                ),
+                Err(e) => tracing::debug!("Merged search failed for {}: {}", outcome.source_name, e),
+            }
+        }
+        cancellation.finish();
+        self.persist_source_stats().await;
+
+        let books = aggregator.ranked();
+        let search_id = self.search_sessions.insert(books.clone());
+        Ok(MergedSearch {
+            search_id,
+            books: truncate_origins(books),
+        })
+    }
+
+    /// 换源：并发搜索所有已启用的书源，返回同一本书的候选来源
+    pub async fn available_sources(&self, query: ChangeSourceQuery) -> Result<Vec<SourceCandidate>, anyhow::Error> {
+        use futures::StreamExt;
+
+        let mut events = Box::pin(self.change_source_events(query));
+        while let Some(event) = events.next().await {
+            if let ChangeSourceEvent::Done(candidates) = event {
+                return Ok(candidates);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// 换源搜索的事件流，供 JSON 与 SSE 接口共用
+    ///
+    /// 搜索完成后按需统计前 top_n 个候选的目录章节数，最后发送排序后的全部候选。
+    pub fn change_source_events(&self, query: ChangeSourceQuery) -> impl Stream<Item = ChangeSourceEvent> {
+        let service = self.clone();
+
+        async_stream::stream! {
+            use futures::stream::FuturesUnordered;
+            use futures::StreamExt;
+
+            let sources = service.search_sources(query.group.as_deref()).await;
+            let total = sources.len();
+            let semaphore = Arc::new(Semaphore::new(query.concurrent.max(1)));
+            let cancellation = SearchCancellation::new();
+            let mut tasks: FuturesUnordered<_> = sources
+                .iter()
+                .filter_map(|s| {
+                    spawn_source_search(s, &query.name, service.engines.clone(), semaphore.clone(), cancellation.token())
+                })
+                .collect();
+
+            let mut candidates = Vec::new();
+            let mut current = 0;
+            while let Some(task_result) = tasks.next().await {
+                current += 1;
+                yield ChangeSourceEvent::Progress { current, total };
+
+                let outcome = match task_result {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        tracing::warn!("search spawn error: {}", e);
+                        continue;
+                    }
+                };
+                service
+                    .source_stats
+                    .record(&outcome.source_url, outcome.respond_time, outcome.stat_outcome())
+                    .await;
+                let books = match outcome.result {
+                    Ok(books) => books,
+                    Err(e) => {
+                        tracing::debug!("Change source search failed for {}: {}", outcome.source_name, e);
+                        continue;
+                    }
+                };
+                // 每个书源只取第一个匹配的结果
+                if let Some(book) = books.into_iter().find(|b| query.matches(b)) {
+                    let candidate = SourceCandidate {
+                        source_url: outcome.source_url,
+                        source_name: outcome.source_name,
+                        book_url: book.book_url,
+                        last_chapter: book.last_chapter,
+                        chapter_count: None,
+                        respond_time: outcome.respond_time,
+                    };
+                    candidates.push(candidate.clone());
+                    yield ChangeSourceEvent::Found(candidate);
+                }
+            }
+            service.persist_source_stats().await;
+
+            rank_candidates(&mut candidates);
+            if query.with_chapter_count {
+                let counts: Vec<_> = candidates
+                    .iter()
+                    .take(query.top_n)
+                    .map(|c| {
+                        let source = sources.iter().find(|s| s.book_source_url == c.source_url);
+                        spawn_chapter_count(source, &c.book_url, service.engines.clone(), semaphore.clone())
+                    })
+                    .collect();
+                for (candidate, count) in candidates.iter_mut().zip(futures::future::join_all(counts).await) {
+                    candidate.chapter_count = count.ok().flatten();
+                }
+                rank_candidates(&mut candidates);
+            }
+            cancellation.finish();
+            yield ChangeSourceEvent::Done(candidates);
+        }
+    }
+
+    /// 可用于搜索的书源 (已启用且有搜索地址)，可按分组过滤
+    async fn search_sources(&self, group: Option<&str>) -> Vec<BookSourceFull> {
+        // Lazy load sources if not already loaded
+        {
+            let sources = self.sources.read().await;
+            if sources.is_empty() {
+                drop(sources);
+                let loaded: Vec<BookSourceFull> =
+                    self.storage.read_json_or_default(SOURCES_FILE).await;
+                tracing::info!("Lazy loaded {} sources for change source", loaded.len());
+                let mut sources = self.sources.write().await;
+                *sources = loaded;
+            }
+        }
+
+        self.sources
+            .read()
+            .await
+            .iter()
+            .filter(|s| s.enabled && !s.search_url.is_empty())
+            .filter(|s| !self.source_stats.is_cooling_down(&s.book_source_url))
+            .filter(|s| group.is_none_or(|g| s.book_source_group.contains(g)))
+            .cloned()
+            .collect()
+    }
+
+    /// 从搜索会话中获取某本书的全部来源
+    pub fn search_merged_origins(
+        &self,
+        search_id: &str,
+        name: &str,
+        author: &str,
+    ) -> Result<Vec<SearchOrigin>, anyhow::Error> {
+        self.search_sessions
+            .origins(search_id, name, author)
+            .ok_or_else(|| ServiceError::not_found("Search result", format!("{} {}", name, author)).into())
+    }
+
     /// 获取书源
     async fn get_source(&self, source_url: &str) -> Result<BookSourceFull, anyhow::Error> {
         // Lazy load sources if cache is empty
@@ -605,22 +1814,48 @@ impl BookService {
             .iter()
             .find(|s| s.book_source_url == source_url)
             .cloned()
-            .ok_or_else(|| anyhow::anyhow!("Source not found: {}", source_url))
+            .ok_or_else(|| ServiceError::not_found("Source", source_url).into())
     }
 
     /// 保存书籍到书架
-    pub async fn save_book(&self, book: Book) -> Result<Book, anyhow::Error> {
-        let mut shelf = self.bookshelf.write().await;
+    pub async fn save_book(&self, mut book: Book) -> Result<Book, anyhow::Error> {
+        // 来自音频、漫画书源的书籍标记对应类型，前端据此显示播放器或图片阅读器
+        if book.book_type.is_none() {
+            if let Some(origin) = book.origin.as_deref() {
+                if let Ok(source) = self.get_source(origin).await {
+                    book.book_type = match source.book_source_type {
+                        SOURCE_TYPE_AUDIO => Some(bookshelf::BOOK_TYPE_AUDIO),
+                        SOURCE_TYPE_IMAGE => Some(bookshelf::BOOK_TYPE_IMAGE),
+                        _ => None,
+                    };
+                }
+            }
+        }
+        let mut shelf = self.shelf_mut().await?;
 
-        // 检查是否已存在
-        if let Some(pos) = shelf.iter().position(|b| b.book_url == book.book_url) {
-            shelf[pos] = book.clone();
-        } else {
-            shelf.push(book.clone());
+        // 前端保存书籍时不带书籍变量与排版选项，沿用已保存的值；手动设置的书籍信息同样保留
+        if let Some(old) = shelf.get(&book.book_url) {
+            if book.variable.is_none() {
+                book.variable = old.variable.clone();
+            }
+            if book.formatting.is_none() {
+                book.formatting = old.formatting.clone();
+            }
+            book.keep_metadata(old);
         }
 
-        // 保存到文件
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
+        // 新书或书名、分组变化时才需重写索引
+        let index_changed = shelf
+            .get(&book.book_url)
+            .is_none_or(|old| old.name != book.name || old.group != book.group);
+        shelf.upsert(book.clone());
+
+        // 先写书籍文件，索引不会指向不存在的文件
+        self.shelf_store.write_book(&book).await?;
+        if index_changed {
+            self.shelf_store.write_index(shelf.iter()).await?;
+        }
+        drop(shelf);
 
         // 更新索引
         let search_engine = self.search_engine.clone();
@@ -635,84 +1870,539 @@ impl BookService {
         Ok(book)
     }
 
-    /// 删除书籍
-    pub async fn delete_book(&self, book_url: &str) -> Result<(), anyhow::Error> {
-        let mut shelf = self.bookshelf.write().await;
-        shelf.retain(|b| b.book_url != book_url);
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
-        
-        // 删除索引
+    /// 删除书籍 (阅读进度随书籍记录一并删除)
+    ///
+    /// delete_cache 时同时删除正文、目录、封面、朗读音频与 EPUB 资源缓存，返回回收量。
+    pub async fn delete_book(&self, book_url: &str, delete_cache: bool) -> Result<ReclaimedCache, anyhow::Error> {
+        self.delete_books(&[book_url.to_string()], delete_cache).await
+    }
+
+    /// 批量删除书籍，参见 [`Self::delete_book`]
+    pub async fn delete_books(
+        &self,
+        book_urls: &[String],
+        delete_cache: bool,
+    ) -> Result<ReclaimedCache, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
+        let removed: Vec<Book> = book_urls.iter().filter_map(|url| shelf.remove(url)).collect();
+        if !removed.is_empty() {
+            self.shelf_store.write_index(shelf.iter()).await?;
+            for book in &removed {
+                self.shelf_store.delete_book(&book.book_url).await?;
+            }
+        }
+        // 仍被其他书籍使用的封面保留
+        let covers_in_use: HashSet<String> = shelf
+            .iter()
+            .flat_map(|b| [b.cover_url.clone(), b.custom_cover_url.clone()])
+            .flatten()
+            .collect();
+        drop(shelf);
+
         let search_engine = self.search_engine.clone();
-        let id_clone = book_url.to_string();
+        let ids = book_urls.to_vec();
+        let indexed = tokio::task::spawn_blocking(move || {
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            search_engine.remove_books(&ids)
+        })
+        .await?;
+        if let Err(e) = indexed {
+            tracing::warn!("Failed to remove {} books from the search index: {}", book_urls.len(), e);
+        }
+
+        let mut reclaimed = ReclaimedCache::default();
+        if delete_cache {
+            for book_url in book_urls {
+                reclaimed += self.purge_book_cache(book_url).await?;
+            }
+            let covers: HashSet<&str> = removed
+                .iter()
+                .flat_map(|b| [b.cover_url.as_deref(), b.custom_cover_url.as_deref()])
+                .flatten()
+                .collect();
+            for cover in covers.into_iter().filter(|c| !covers_in_use.contains(*c)) {
+                reclaimed += self.cover_cache.remove(cover).await?;
+            }
+        }
+        Ok(reclaimed)
+    }
+
+    /// 删除一本书的正文、目录、朗读音频与 EPUB 资源缓存
+    async fn purge_book_cache(&self, book_url: &str) -> Result<ReclaimedCache, anyhow::Error> {
+        let mut reclaimed = self.content_cache.clear_book(book_url).await?;
+        reclaimed += self.storage.remove_cache(&Self::chapter_list_key(book_url)).await?;
+        reclaimed += self.audio_cache.clear_book(book_url).await?;
+        let local_id = book_url
+            .strip_prefix(LOCAL_URL_PREFIX)
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()));
+        if let Some(book_id) = local_id {
+            reclaimed += self.storage.remove_cache(&format!("assets/{}", book_id)).await?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// 导出书架书籍为 EPUB，返回 (文件名, 文件内容)
+    ///
+    /// 正文只从缓存读取，不访问书源；未缓存的章节以占位页代替，导出前可先缓存全书。
+    pub async fn export_epub(&self, book_url: &str) -> Result<(String, Vec<u8>), anyhow::Error> {
+        let book = {
+            let shelf = self.get_bookshelf(false).await?;
+            shelf
+                .into_iter()
+                .find(|b| b.book_url == book_url)
+                .ok_or_else(|| ServiceError::not_found("Book", book_url))?
+        };
+
+        let chapters = self
+            .get_chapter_list(book_url, book.origin.as_deref(), false)
+            .await?;
+
+        let mut epub_chapters = Vec::with_capacity(chapters.len());
+        let mut missing = 0;
+        for chapter in &chapters {
+            let content = match self.content_cache.get(book_url, chapter.index).await {
+                Some(content) => self.render_content(book_url, &content, None).await?,
+                None => {
+                    missing += 1;
+                    "本章未缓存".to_string()
+                }
+            };
+            epub_chapters.push(EpubChapter {
+                title: chapter.title.clone(),
+                content,
+            });
+        }
+
+        if missing > 0 {
+            tracing::warn!(
+                "Export: {} of {} chapters of {} are not cached",
+                missing,
+                chapters.len(),
+                book.name
+            );
+        }
+
+        let book = book.with_metadata();
+        let cover = match book.cover_url.as_deref() {
+            Some(url) if is_local_cover(url) => self.cover_cache.get(url).await.map(|cover| EpubCover {
+                data: cover.data,
+                media_type: cover.content_type,
+            }),
+            Some(url) if url.starts_with("http") => Self::download_cover(url).await,
+            _ => None,
+        };
+
+        let epub = EpubBook {
+            identifier: format!("urn:reader:{:x}", md5::compute(book_url)),
+            title: book.name.clone(),
+            author: book.author.clone(),
+            intro: book.intro.clone(),
+            cover,
+            chapters: epub_chapters,
+        };
+        let data = tokio::task::spawn_blocking(move || epub.build()).await??;
+
+        let filename = if book.author.is_empty() {
+            format!("{}.epub", book.name)
+        } else {
+            format!("{}-{}.epub", book.name, book.author)
+        };
+        Ok((filename, data))
+    }
+
+    /// 获取封面图片 (封面代理，带磁盘缓存)
+    ///
+    /// 指定 source_url 时通过该书源的请求头与 Cookie 获取，否则以图片所在站点作为 Referer。
+    /// 上传的本地封面只从缓存读取。
+    pub async fn get_cover(
+        &self,
+        url: &str,
+        source_url: Option<&str>,
+    ) -> Result<CachedCover, anyhow::Error> {
+        if is_local_cover(url) {
+            return self
+                .cover_cache
+                .get(url)
+                .await
+                .ok_or_else(|| ServiceError::not_found("Cover", url).into());
+        }
+        self.fetch_cached_image(&self.cover_cache, url, source_url, MAX_COVER_BYTES).await
+    }
+
+    /// 漫画图片代理：与封面代理相同地获取图片，缓存在单独的目录中
+    pub async fn get_image(&self, src: &str, source_url: Option<&str>) -> Result<CachedCover, anyhow::Error> {
+        self.fetch_cached_image(&self.image_cache, src, source_url, MAX_COMIC_IMAGE_BYTES).await
+    }
+
+    async fn fetch_cached_image(
+        &self,
+        cache: &CoverCache,
+        url: &str,
+        source_url: Option<&str>,
+        max_bytes: usize,
+    ) -> Result<CachedCover, anyhow::Error> {
+        if let Some(image) = cache.get(url).await {
+            return Ok(image);
+        }
+
+        let source = match source_url {
+            Some(source_url) => Some(self.get_source(source_url).await?),
+            None => None,
+        };
+        let url_owned = url.to_string();
+        let engines = self.engines.clone();
+        let resp = tokio::task::spawn_blocking(move || match source {
+            Some(source) => {
+                let engine_source: BookSource = serde_json::from_value(serde_json::to_value(&source)?)?;
+                engines.engine(&engine_source)?.fetch_image(&url_owned, max_bytes)
+            }
+            None => {
+                let origin = reqwest::Url::parse(&url_owned)?.origin().ascii_serialization();
+                HttpClient::new(&origin)?.fetch_image(&url_owned, max_bytes)
+            }
+        })
+        .await??;
+
+        let content_type = image_content_type(resp.content_type.as_deref(), &resp.data)
+            .ok_or_else(|| anyhow::anyhow!("Not an image: {}", url))?;
+        let image = CachedCover {
+            content_type,
+            data: resp.data,
+        };
+        if let Err(e) = cache.put(url, &image).await {
+            tracing::warn!("Failed to cache image {}: {}", url, e);
+        }
+        Ok(image)
+    }
+
+    /// 获取音频书源章节的播放地址及请求所需的请求头 (不缓存，音频地址常带有时效)
+    pub async fn get_audio_url(&self, book_url: &str, index: i32) -> Result<AudioContent, anyhow::Error> {
+        let input = self.content_fetch_input(book_url, index, None).await?;
+        if input.source.book_source_type != Some(SOURCE_TYPE_AUDIO) {
+            let message = format!("Not an audio source: {}", input.source.book_source_url);
+            return Err(ServiceError::invalid_input(message).into());
+        }
+        let engines = self.engines.clone();
         tokio::task::spawn_blocking(move || {
-           if let Err(e) = search_engine.delete_book(&id_clone) {
-               tracing::warn!("Failed to delete index for book {}: {}", id_clone, e);
-           }
+            let (engine, chapter_url) = input.engine(&engines)?;
+            engine.get_audio(&chapter_url)
+        })
+        .await?
+    }
+
+    /// 音频代理：以书源的请求头、Cookie 与 Referer 请求音频，并转发浏览器的 Range
+    ///
+    /// 上游的 2xx 与 416 响应原样转发，其他状态返回错误。
+    pub async fn open_audio_stream(
+        &self,
+        url: &str,
+        source_url: &str,
+        range: Option<&str>,
+    ) -> Result<AudioStream, anyhow::Error> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(ServiceError::invalid_input(format!("Invalid audio url: {}", url)).into());
+        }
+        let source = self.get_source(source_url).await?;
+        let engine_source: BookSource = serde_json::from_value(serde_json::to_value(&source)?)?;
+        let (url, range) = (url.to_string(), range.map(str::to_string));
+        let engines = self.engines.clone();
+
+        let (head_tx, head_rx) = tokio::sync::oneshot::channel();
+        let (body_tx, body) = tokio::sync::mpsc::channel(4);
+        tokio::task::spawn_blocking(move || {
+            use std::io::Read;
+
+            let opened = engines
+                .engine(&engine_source)
+                .and_then(|engine| engine.open_media(&url, range.as_deref()));
+            let mut response = match opened {
+                Ok(response) => response,
+                Err(e) => {
+                    let _ = head_tx.send(Err(e));
+                    return;
+                }
+            };
+            let headers = PROXIED_AUDIO_HEADERS
+                .iter()
+                .filter_map(|name| {
+                    let value = response.headers().get(*name)?.to_str().ok()?;
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect();
+            if head_tx.send(Ok((response.status().as_u16(), headers))).is_err() {
+                return;
+            }
+
+            let mut buf = vec![0; AUDIO_CHUNK_SIZE];
+            loop {
+                let chunk = match response.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => Ok(buf[..n].to_vec()),
+                    Err(e) => Err(e),
+                };
+                let failed = chunk.is_err();
+                if body_tx.blocking_send(chunk).is_err() || failed {
+                    break;
+                }
+            }
         });
 
-        Ok(())
+        let (status, headers) = head_rx.await??;
+        Ok(AudioStream { status, headers, body })
     }
 
-    /// 批量删除书籍
-    pub async fn delete_books(&self, books: Vec<Book>) -> Result<(), anyhow::Error> {
-        let urls: Vec<&str> = books.iter().map(|b| b.book_url.as_str()).collect();
-        let mut shelf = self.bookshelf.write().await;
-        shelf.retain(|b| !urls.contains(&b.book_url.as_str()));
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
-        Ok(())
+    /// 下载封面图片，失败时忽略
+    async fn download_cover(url: &str) -> Option<EpubCover> {
+        let resp = reqwest::get(url).await.ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+        let media_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or(v).trim().to_string())
+            .filter(|v| v.starts_with("image/"))
+            .unwrap_or_else(|| "image/jpeg".to_string());
+        let data = resp.bytes().await.ok()?.to_vec();
+        Some(EpubCover { data, media_type })
+    }
+
+    /// 切换书源 (不能切换到已禁用的书源)
+    pub async fn set_book_source(
+        &self,
+        book_url: &str,
+        new_url: &str,
+        source_url: &str,
+    ) -> Result<Book, anyhow::Error> {
+        let source = self.get_source(source_url).await?;
+        if !source.enabled {
+            return Err(ServiceError::source_disabled(source_url).into());
+        }
+
+        let mut shelf = self.shelf_mut().await?;
+        let book = shelf
+            .rekey(book_url, new_url)
+            .ok_or_else(|| ServiceError::not_found("Book", book_url))?;
+
+        book.origin = Some(source.book_source_url.clone());
+        book.origin_name = Some(source.book_source_name.clone());
+        book.toc_url = None;
+        let updated = book.clone();
+
+        self.shelf_store.write_book(&updated).await?;
+        if book_url != new_url {
+            self.shelf_store.write_index(shelf.iter()).await?;
+            self.shelf_store.delete_book(book_url).await?;
+        }
+        drop(shelf);
+
+        // 书籍地址变化时索引随之更换
+        if book_url != new_url {
+            let search_engine = self.search_engine.clone();
+            let (old_id, entry) = (book_url.to_string(), IndexedBook::from(&updated));
+            let indexed = tokio::task::spawn_blocking(move || {
+                search_engine.remove_book(&old_id)?;
+                search_engine.index_book(&entry.id, &entry.title, &entry.author, &entry.intro)
+            })
+            .await?;
+            if let Err(e) = indexed {
+                tracing::warn!("Failed to reindex book {}: {}", updated.name, e);
+            }
+        }
+
+        // 旧书源的目录与正文均已失效
+        for url in [book_url, new_url] {
+            let _ = self
+                .storage
+                .delete_cache(&Self::chapter_list_key(url))
+                .await;
+            self.content_cache.clear_book(url).await?;
+        }
+
+        Ok(updated)
+    }
+
+    /// 保存手动设置的书籍信息 (未指定的项保持不变，空字符串清除该项)，返回应用设置后的书籍
+    pub async fn save_book_metadata(&self, book_url: &str, metadata: BookMetadata) -> Result<Book, anyhow::Error> {
+        self.update_book_metadata(book_url, |book| book.set_metadata(metadata)).await
+    }
+
+    /// 清除手动设置的书籍信息，恢复书源提供的值
+    pub async fn clear_book_metadata(&self, book_url: &str) -> Result<Book, anyhow::Error> {
+        self.update_book_metadata(book_url, Book::clear_metadata).await
+    }
+
+    /// 保存上传的封面并设为书籍封面
+    pub async fn upload_book_cover(
+        &self,
+        book_url: &str,
+        content_type: Option<&str>,
+        data: Vec<u8>,
+    ) -> Result<Book, anyhow::Error> {
+        if self.shelf().await?.get(book_url).is_none() {
+            return Err(ServiceError::not_found("Book", book_url).into());
+        }
+        if data.len() > MAX_COVER_BYTES {
+            let message = format!("Cover exceeds {} bytes", MAX_COVER_BYTES);
+            return Err(ServiceError::invalid_input(message).into());
+        }
+        let content_type = image_content_type(content_type, &data)
+            .ok_or_else(|| ServiceError::invalid_input("Uploaded cover is not an image"))?;
+        let url = self.cover_cache.put_local(&CachedCover { content_type, data }).await?;
+        let metadata = BookMetadata {
+            custom_cover_url: Some(url),
+            ..Default::default()
+        };
+        self.save_book_metadata(book_url, metadata).await
+    }
+
+    async fn update_book_metadata(&self, book_url: &str, update: impl FnOnce(&mut Book)) -> Result<Book, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
+        let book = shelf
+            .get_mut(book_url)
+            .ok_or_else(|| ServiceError::not_found("Book", book_url))?;
+        update(book);
+        self.shelf_store.write_book(book).await?;
+        Ok(book.clone().with_metadata())
+    }
+
+    /// 获取阅读进度，书籍不在书架上时返回 None
+    pub async fn get_progress(&self, book_url: &str) -> Result<Option<BookProgress>, anyhow::Error> {
+        Ok(self.shelf().await?.get(book_url).map(BookProgress::from_book))
     }
 
     /// 保存阅读进度
-    pub async fn save_progress(&self, book_url: &str, index: i32) -> Result<(), anyhow::Error> {
-        let mut shelf = self.bookshelf.write().await;
+    ///
+    /// 已存储的进度 durChapterTime 更新时保留已存储的值，返回最终生效的进度；
+    /// 书籍不在书架上时返回 None。进度更新频繁，书籍文件采用防抖写入。
+    pub async fn save_progress(
+        &self,
+        book_url: &str,
+        mut progress: BookProgress,
+    ) -> Result<Option<BookProgress>, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
 
-        if let Some(book) = shelf.iter_mut().find(|b| b.book_url == book_url) {
-            book.dur_chapter_index = Some(index);
-            book.dur_chapter_time = Some(chrono::Utc::now().timestamp_millis());
+        let Some(book) = shelf.get_mut(book_url) else {
+            return Ok(None);
+        };
+
+        if progress.dur_chapter_time <= 0 {
+            progress.dur_chapter_time = chrono::Utc::now().timestamp_millis();
+        }
+        let (result, changed) = BookProgress::reconcile(&BookProgress::from_book(book), progress);
+        if changed {
+            result.apply_to(book);
+            // 打开阅读即视为已看到新章节
+            book.has_new_chapter = None;
+            self.shelf_store.write_book_debounced(book).await?;
         }
+        Ok(Some(result))
+    }
 
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
-        Ok(())
+    /// 获取书籍变量 (规则中 `book.getVariable(key)` 的值)
+    pub async fn get_book_variable(&self, book_url: &str) -> Result<HashMap<String, String>, anyhow::Error> {
+        self.shelf()
+            .await?
+            .get(book_url)
+            .map(Book::variable_map)
+            .ok_or_else(|| ServiceError::not_found("Book", book_url).into())
+    }
+
+    /// 保存书籍变量并立即写盘，value 为空时删除该变量，返回保存后的全部变量
+    pub async fn save_book_variable(
+        &self,
+        book_url: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<HashMap<String, String>, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
+        let book = shelf
+            .get_mut(book_url)
+            .ok_or_else(|| ServiceError::not_found("Book", book_url))?;
+        book.put_variable(key, value);
+        self.shelf_store.write_book(book).await?;
+        Ok(book.variable_map())
     }
 
-    /// 批量加入分组
+    /// 批量加入分组 (保留书籍已有的其他分组位)
     pub async fn add_books_to_group(
         &self,
         group_id: i64,
         books: Vec<Book>,
     ) -> Result<(), anyhow::Error> {
-        let urls: Vec<&str> = books.iter().map(|b| b.book_url.as_str()).collect();
-        let mut shelf = self.bookshelf.write().await;
-
-        for book in shelf.iter_mut() {
-            if urls.contains(&book.book_url.as_str()) {
-                book.group = Some(group_id);
+        let mut shelf = self.shelf_mut().await?;
+        let mut changed = Vec::new();
+        for url in books.iter().map(|b| b.book_url.as_str()) {
+            if let Some(book) = shelf.get_mut(url) {
+                book.group = Some(book.group.unwrap_or(0) | group_id);
+                changed.push(book.clone());
             }
         }
 
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
+        for book in &changed {
+            self.shelf_store.write_book(book).await?;
+        }
+        self.shelf_store.write_index(shelf.iter()).await?;
         Ok(())
     }
 
-    /// 批量移出分组
+    /// 批量移出分组 (只清除该分组位)
     pub async fn remove_books_from_group(
         &self,
-        _group_id: i64,
+        group_id: i64,
         books: Vec<Book>,
     ) -> Result<(), anyhow::Error> {
-        let urls: Vec<&str> = books.iter().map(|b| b.book_url.as_str()).collect();
-        let mut shelf = self.bookshelf.write().await;
-
-        for book in shelf.iter_mut() {
-            if urls.contains(&book.book_url.as_str()) {
-                book.group = None;
+        let mut shelf = self.shelf_mut().await?;
+        let mut changed = Vec::new();
+        for url in books.iter().map(|b| b.book_url.as_str()) {
+            if let Some(book) = shelf.get_mut(url) {
+                book.group = Some(book.group.unwrap_or(0) & !group_id).filter(|g| *g != 0);
+                changed.push(book.clone());
             }
         }
 
-        self.storage.write_json(BOOKSHELF_FILE, &*shelf).await?;
+        for book in &changed {
+            self.shelf_store.write_book(book).await?;
+        }
+        self.shelf_store.write_index(shelf.iter()).await?;
         Ok(())
     }
 
+    /// 从所有书籍清除分组位 (删除分组时调用)，返回修改的书籍数
+    pub async fn clear_group(&self, group_id: i64) -> Result<usize, anyhow::Error> {
+        let mut shelf = self.shelf_mut().await?;
+        let urls: Vec<String> = shelf
+            .iter()
+            .filter(|b| b.group.unwrap_or(0) & group_id != 0)
+            .map(|b| b.book_url.clone())
+            .collect();
+        for url in &urls {
+            if let Some(book) = shelf.get_mut(url) {
+                book.group = book.group.map(|g| g & !group_id).filter(|g| *g != 0);
+                self.shelf_store.write_book(book).await?;
+            }
+        }
+        if !urls.is_empty() {
+            self.shelf_store.write_index(shelf.iter()).await?;
+        }
+        Ok(urls.len())
+    }
+
+    /// 找到新旧目录中第一个 URL 不一致的章节索引
+    fn first_changed_chapter(old: &[Chapter], new: &[Chapter]) -> Option<i32> {
+        old.iter()
+            .zip(new.iter())
+            .position(|(o, n)| o.url != n.url)
+            .or_else(|| (old.len() > new.len()).then_some(new.len()))
+            .map(|i| i as i32)
+    }
+
+    /// 目录缓存 key
+    fn chapter_list_key(book_url: &str) -> String {
+        format!("chapters/{}.json", Self::url_to_key(book_url))
+    }
+
     /// URL 转缓存 key (移除特殊字符)
     fn url_to_key(url: &str) -> String {
         url.chars()
@@ -721,4 +2411,228 @@ impl BookService {
     }
 }
 
+/// 抓取章节正文所需的书源，以及规则中可用的书籍与章节
+struct ContentFetchInput {
+    source: BookSource,
+    book: BookContext,
+    chapter: ChapterContext,
+    /// 全部章节 URL，分页链接指向其他章节时停止
+    chapter_urls: Vec<String>,
+    max_pages: Option<usize>,
+}
+
+impl ContentFetchInput {
+    /// 创建书源引擎并设置 book / chapter 与分页限制，返回引擎与章节 URL (阻塞调用)
+    fn engine(self, engines: &EngineCache) -> anyhow::Result<(BookSourceEngine, String)> {
+        let mut engine = engines.engine(&self.source)?;
+        let chapter_url = self.prepare(&mut engine);
+        Ok((engine, chapter_url))
+    }
+
+    /// 在已有引擎上设置 book / chapter 与分页限制，返回章节 URL
+    fn prepare(self, engine: &mut BookSourceEngine) -> String {
+        engine.set_chapter_urls(self.chapter_urls);
+        if let Some(max_pages) = self.max_pages {
+            engine.set_max_content_pages(max_pages);
+        }
+        let chapter_url = self.chapter.url.clone();
+        engine.set_book(self.book);
+        engine.set_chapter(self.chapter);
+        chapter_url
+    }
+}
+
+/// 书架书籍在规则中的 `book`
+fn book_context(book: &Book) -> BookContext {
+    BookContext {
+        name: book.name.clone(),
+        author: book.author.clone(),
+        book_url: book.book_url.clone(),
+        toc_url: book.toc_url.clone(),
+        origin: book.origin.clone(),
+        kind: book.kind.clone(),
+        intro: book.intro.clone(),
+        variable: book.variable.clone(),
+    }
+}
+
+/// 获取目录并编号 (阻塞调用)
+fn fetch_toc(engine: &BookSourceEngine, toc_url: &str) -> anyhow::Result<Vec<Chapter>> {
+    Ok(engine
+        .get_chapters(toc_url)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| Chapter {
+            title: c.title,
+            url: c.url,
+            index: i as i32,
+        })
+        .collect())
+}
+
+/// 在后台统计书籍目录的章节数；书源缺失、超时或失败时返回 None
+fn spawn_chapter_count(
+    source: Option<&BookSourceFull>,
+    book_url: &str,
+    engines: Arc<EngineCache>,
+    semaphore: Arc<Semaphore>,
+) -> JoinHandle<Option<usize>> {
+    let source_json = source.and_then(|s| serde_json::to_string(s).ok());
+    let book_url = book_url.to_string();
+
+    tokio::task::spawn(async move {
+        let source_json = source_json?;
+        let _permit = semaphore.acquire_owned().await;
+        let count = tokio::time::timeout(
+            SOURCE_SEARCH_TIMEOUT,
+            tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+                let engine_source: BookSource = serde_json::from_str(&source_json)?;
+                let engine = engines.engine(&engine_source)?;
+                let info = engine.get_book_info(&book_url)?;
+                let toc_url = info.toc_url.filter(|u| !u.is_empty()).unwrap_or(book_url);
+                Ok(fetch_toc(&engine, &toc_url)?.len())
+            }),
+        )
+        .await;
+        match count {
+            Ok(Ok(Ok(count))) => Some(count),
+            Ok(Ok(Err(e))) => {
+                tracing::debug!("Failed to count chapters: {}", e);
+                None
+            }
+            _ => None,
+        }
+    })
+}
 
+/// 正文 SSE 的 chunk 事件
+fn content_chunk_event(page_index: usize, text: &str) -> Event {
+    let chunk = serde_json::json!({ "pageIndex": page_index, "text": text });
+    Event::default().event("chunk").data(chunk.to_string())
+}
+
+/// 在后台搜索单个书源，受信号量限制并发；书源无法序列化时返回 None
+///
+/// 等待并发许可时被取消则不再发起请求。
+fn spawn_source_search(
+    source: &BookSourceFull,
+    key: &str,
+    engines: Arc<EngineCache>,
+    semaphore: Arc<Semaphore>,
+    cancel: CancellationToken,
+) -> Option<JoinHandle<SourceSearchOutcome>> {
+    let source_name = source.book_source_name.clone();
+    let source_url = source.book_source_url.clone();
+    let source_json = match serde_json::to_string(source) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Failed to serialize source {}: {}", source_name, e);
+            return None;
+        }
+    };
+    let key = key.to_string();
+
+    Some(tokio::task::spawn(async move {
+        // 在任务内部获取 permit，这样循环不会阻塞；permit 在任务结束前一直被持有
+        let _permit = tokio::select! {
+            permit = semaphore.acquire_owned() => permit,
+            _ = cancel.cancelled() => {
+                return SourceSearchOutcome {
+                    source_name,
+                    source_url,
+                    result: Err(anyhow::anyhow!("Search cancelled")),
+                    respond_time: 0,
+                    timed_out: false,
+                };
+            }
+        };
+        let started = Instant::now();
+
+        // 使用 timeout 包装阻塞任务；取消或超时后通过 js_cancel 中断仍在运行的 JS
+        let source_name_closure = source_name.clone();
+        let js_cancel = Arc::new(AtomicBool::new(false));
+        let engine_cancel = js_cancel.clone();
+        let search = tokio::time::timeout(
+            SOURCE_SEARCH_TIMEOUT,
+            tokio::task::spawn_blocking(move || {
+                let engine_source: BookSource = match serde_json::from_str(&source_json) {
+                    Ok(s) => s,
+                    Err(e) => return Err(anyhow::anyhow!("Failed to parse source: {}", e)),
+                };
+
+                match engines.engine(&engine_source) {
+                    Ok(engine) => {
+                        tracing::debug!("Searching source: {}", source_name_closure);
+                        engine.set_js_timeout(SEARCH_JS_TIMEOUT);
+                        engine.set_js_cancel_flag(engine_cancel);
+                        engine.search(&key, 1)
+                    }
+                    Err(e) => Err(anyhow::anyhow!("Failed to create engine: {}", e)),
+                }
+            }),
+        );
+        let result = tokio::select! {
+            result = search => Some(result),
+            _ = cancel.cancelled() => None,
+        };
+        js_cancel.store(true, Ordering::Relaxed);
+
+        let timed_out = matches!(result, Some(Err(_)));
+        let final_result = match result {
+            Some(Ok(Ok(engine_res))) => engine_res, // success
+            Some(Ok(Err(e))) => Err(anyhow::anyhow!("Task join error: {}", e)), // join error
+            Some(Err(_)) => Err(anyhow::anyhow!("Search timed out")), // timeout
+            None => Err(anyhow::anyhow!("Search cancelled")), // cancelled
+        };
+
+        SourceSearchOutcome {
+            source_name,
+            source_url,
+            result: final_result,
+            respond_time: started.elapsed().as_millis() as u64,
+            timed_out,
+        }
+    }))
+}
+
+/// 将漫画图片地址改写为经由图片代理，代理以书源的请求头与 Cookie 请求图片
+fn proxy_images(content: ImageContent, source_url: Option<&str>) -> ImageContent {
+    let images = content
+        .images
+        .iter()
+        .map(|src| {
+            let mut url = format!("{}?src={}", IMAGE_PROXY_PATH, urlencoding::encode(src));
+            if let Some(source_url) = source_url {
+                url.push_str("&bookSourceUrl=");
+                url.push_str(&urlencoding::encode(source_url));
+            }
+            url
+        })
+        .collect();
+    ImageContent { images }
+}
+
+/// 确定图片的 Content-Type；未声明类型时按文件头识别，非图片返回 None
+fn image_content_type(declared: Option<&str>, data: &[u8]) -> Option<String> {
+    let declared = declared
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_lowercase())
+        .filter(|v| !v.is_empty() && v != "application/octet-stream");
+    match declared {
+        Some(ct) if ct.starts_with("image/") => Some(ct),
+        Some(_) => None,
+        None => {
+            let sniffed = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+                "image/jpeg"
+            } else if data.starts_with(b"\x89PNG") {
+                "image/png"
+            } else if data.starts_with(b"GIF8") {
+                "image/gif"
+            } else if data.len() > 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+                "image/webp"
+            } else {
+                return None;
+            };
+            Some(sniffed.to_string())
+        }
+    }
+}
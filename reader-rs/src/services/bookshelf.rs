@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::api::ApiError;
+use crate::models::{Book, BookUpdateError, Chapter, UpdateStage};
+use super::local_book;
+
+/// 虚拟分组：全部书籍
+pub const GROUP_ALL: i64 = -1;
+/// 虚拟分组：本地书籍
+pub const GROUP_LOCAL: i64 = -2;
+/// 虚拟分组：未分组 (group 为 0)
+pub const GROUP_UNGROUPED: i64 = -3;
+/// 虚拟分组：更新失败
+pub const GROUP_UPDATE_FAILED: i64 = -4;
+/// 虚拟分组：音频书籍
+pub const GROUP_AUDIO: i64 = -5;
+
+/// Legado 的音频书籍类型 (旧版 type 为 1，新版为位标记)
+pub(super) const BOOK_TYPE_AUDIO: i32 = 0b10_0000;
+/// Legado 的图片 (漫画) 书籍类型
+pub(super) const BOOK_TYPE_IMAGE: i32 = 0b100_0000;
+
+/// 书架排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShelfSort {
+    /// 最近阅读在前
+    RecentRead,
+    /// 最近更新在前
+    UpdateTime,
+    /// 按书名
+    Name,
+}
+
+/// 书架查询条件
+#[derive(Debug, Clone, Default)]
+pub struct ShelfQuery {
+    pub group: Option<i64>,
+    pub sort: Option<ShelfSort>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// 隐藏分组的位掩码；未指定分组时，只属于隐藏分组的书籍不显示
+    pub hidden_groups: i64,
+}
+
+/// 一页书架数据
+#[derive(Debug, Clone)]
+pub struct ShelfPage {
+    /// 过滤后、分页前的书籍总数
+    pub total: usize,
+    pub books: Vec<Book>,
+}
+
+/// 内存中的书架，按 bookUrl 索引并保留书架顺序
+#[derive(Debug, Default)]
+pub struct Shelf {
+    books: HashMap<String, Book>,
+    order: Vec<String>,
+}
+
+impl Shelf {
+    pub fn new(books: Vec<Book>) -> Self {
+        let mut shelf = Self::default();
+        for book in books {
+            shelf.upsert(book);
+        }
+        shelf
+    }
+
+    pub fn get(&self, book_url: &str) -> Option<&Book> {
+        self.books.get(book_url)
+    }
+
+    pub fn get_mut(&mut self, book_url: &str) -> Option<&mut Book> {
+        self.books.get_mut(book_url)
+    }
+
+    /// 按书架顺序遍历
+    pub fn iter(&self) -> impl Iterator<Item = &Book> {
+        self.order.iter().filter_map(|url| self.books.get(url))
+    }
+
+    pub fn to_vec(&self) -> Vec<Book> {
+        self.iter().cloned().collect()
+    }
+
+    /// 加入或替换书籍，返回是否为新加入的书
+    pub fn upsert(&mut self, book: Book) -> bool {
+        let added = !self.books.contains_key(&book.book_url);
+        if added {
+            self.order.push(book.book_url.clone());
+        }
+        self.books.insert(book.book_url.clone(), book);
+        added
+    }
+
+    pub fn remove(&mut self, book_url: &str) -> Option<Book> {
+        let book = self.books.remove(book_url)?;
+        self.order.retain(|url| url != book_url);
+        Some(book)
+    }
+
+    /// 修改书籍的 bookUrl (换源)，保持其在书架中的位置
+    pub fn rekey(&mut self, old_url: &str, new_url: &str) -> Option<&mut Book> {
+        if old_url != new_url {
+            let mut book = self.books.remove(old_url)?;
+            self.remove(new_url);
+            book.book_url = new_url.to_string();
+            if let Some(url) = self.order.iter_mut().find(|url| *url == old_url) {
+                *url = new_url.to_string();
+            }
+            self.books.insert(new_url.to_string(), book);
+        }
+        self.books.get_mut(new_url)
+    }
+}
+
+/// 书籍是否属于分组
+///
+/// 正数分组 ID 为位掩码 (与 Legado 相同，每个分组占一位)，
+/// 书籍 group 与之有交集即匹配；负数为虚拟分组。
+pub fn matches_group(book: &Book, group: i64) -> bool {
+    let book_group = book.group.unwrap_or(0);
+    match group {
+        GROUP_ALL => true,
+        GROUP_LOCAL => local_book::is_local_book(&book.book_url),
+        GROUP_UNGROUPED => book_group == 0,
+        GROUP_UPDATE_FAILED => book.last_update_error.is_some(),
+        GROUP_AUDIO => is_audio_book(book),
+        0 => book_group == 0,
+        group if group > 0 => book_group & group != 0,
+        _ => false,
+    }
+}
+
+fn is_audio_book(book: &Book) -> bool {
+    book.book_type.is_some_and(|t| t == 1 || t & BOOK_TYPE_AUDIO != 0)
+}
+
+/// 按条件过滤、排序并分页
+pub fn query_bookshelf(books: Vec<Book>, query: &ShelfQuery) -> ShelfPage {
+    let mut books: Vec<Book> = match query.group {
+        Some(group) => books.into_iter().filter(|b| matches_group(b, group)).collect(),
+        None if query.hidden_groups != 0 => books
+            .into_iter()
+            .filter(|b| {
+                let group = b.group.unwrap_or(0);
+                group == 0 || group & !query.hidden_groups != 0
+            })
+            .collect(),
+        None => books,
+    };
+
+    // 稳定排序，相同键保持书架原有顺序
+    match query.sort {
+        Some(ShelfSort::RecentRead) => books.sort_by_key(|b| std::cmp::Reverse(b.dur_chapter_time.unwrap_or(0))),
+        Some(ShelfSort::UpdateTime) => books.sort_by_key(|b| std::cmp::Reverse(b.latest_chapter_time.unwrap_or(0))),
+        Some(ShelfSort::Name) => books.sort_by(|a, b| a.name.cmp(&b.name)),
+        None => {}
+    }
+
+    let total = books.len();
+    // canUpdate 未设置时填入实际生效的值，客户端无需自行推断
+    let books = books
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|mut book| {
+            book.can_update = Some(can_refresh(&book));
+            book
+        })
+        .collect();
+    ShelfPage { total, books }
+}
+
+/// 一次书架更新检查的汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshSummary {
+    pub checked: usize,
+    /// 发现新章节的书籍数
+    pub updated: usize,
+    pub failed: usize,
+}
+
+/// 书籍是否参与自动更新检查 (canUpdate 未设置时与 Legado 一样默认允许)
+pub fn can_refresh(book: &Book) -> bool {
+    book.can_update.unwrap_or(true) && !local_book::is_local_book(&book.book_url)
+}
+
+/// 更新失败的记录，kind 与 message 同该错误作为接口错误返回时的 errorCode 与 errorMsg
+pub fn update_error(stage: UpdateStage, err: &anyhow::Error, now: i64) -> BookUpdateError {
+    let err = ApiError::from(err);
+    BookUpdateError {
+        time: now,
+        stage,
+        kind: err.code().to_string(),
+        message: err.to_string(),
+    }
+}
+
+/// 将一次更新检查的结果写入书籍，返回是否发现了新章节
+///
+/// 首次检查 (尚无 latestChapterTitle) 只记录最新章节，不视为更新。
+pub fn apply_update_check(book: &mut Book, result: Result<&[Chapter], BookUpdateError>, now: i64) -> bool {
+    book.last_check_time = Some(now);
+    let chapters = match result {
+        Ok(chapters) => chapters,
+        Err(e) => {
+            book.last_update_error = Some(e);
+            return false;
+        }
+    };
+    book.last_update_error = None;
+
+    let Some(latest) = chapters.last() else {
+        return false;
+    };
+    let changed = book
+        .latest_chapter_title
+        .as_deref()
+        .is_some_and(|title| title != latest.title);
+    if changed || book.latest_chapter_title.is_none() {
+        book.latest_chapter_time = Some(now);
+    }
+    if changed {
+        book.has_new_chapter = Some(true);
+    }
+    book.latest_chapter_title = Some(latest.title.clone());
+    book.total_chapter_num = Some(chapters.len() as i32);
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::error::EngineError;
+
+    fn book(url: &str, group: i64) -> Book {
+        Book {
+            book_url: url.to_string(),
+            name: url.to_string(),
+            group: Some(group),
+            ..Default::default()
+        }
+    }
+
+    fn urls(page: &ShelfPage) -> Vec<&str> {
+        page.books.iter().map(|b| b.book_url.as_str()).collect()
+    }
+
+    fn shelf() -> Vec<Book> {
+        let mut failed = book("https://a.com/failed", 2);
+        failed.last_update_error = Some(update_error(UpdateStage::Toc, &EngineError::http("timeout").into(), 0));
+        vec![
+            book("https://a.com/one", 1),
+            book("https://a.com/both", 1 | 4),
+            book("https://a.com/four", 4),
+            Book {
+                group: None,
+                ..book("https://a.com/none", 0)
+            },
+            book("local://txt", 0),
+            failed,
+        ]
+    }
+
+    fn by_group(group: i64) -> Vec<String> {
+        let query = ShelfQuery {
+            group: Some(group),
+            ..Default::default()
+        };
+        urls(&query_bookshelf(shelf(), &query)).into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_group_bitmask() {
+        assert_eq!(by_group(1), vec!["https://a.com/one", "https://a.com/both"]);
+        assert_eq!(by_group(4), vec!["https://a.com/both", "https://a.com/four"]);
+        // 多位掩码匹配任一分组
+        assert_eq!(
+            by_group(1 | 2),
+            vec!["https://a.com/one", "https://a.com/both", "https://a.com/failed"]
+        );
+        assert!(by_group(8).is_empty());
+    }
+
+    #[test]
+    fn test_virtual_groups() {
+        assert_eq!(by_group(GROUP_ALL).len(), 6);
+        assert_eq!(by_group(GROUP_LOCAL), vec!["local://txt"]);
+        assert_eq!(by_group(GROUP_UNGROUPED), vec!["https://a.com/none", "local://txt"]);
+        assert_eq!(by_group(GROUP_UPDATE_FAILED), vec!["https://a.com/failed"]);
+        assert!(by_group(-9).is_empty());
+
+        let audio = Book {
+            book_type: Some(BOOK_TYPE_AUDIO | 0b1000),
+            ..book("https://a.com/audio", 0)
+        };
+        let query = ShelfQuery {
+            group: Some(GROUP_AUDIO),
+            ..Default::default()
+        };
+        assert_eq!(urls(&query_bookshelf(vec![audio], &query)), vec!["https://a.com/audio"]);
+    }
+
+    #[test]
+    fn test_hidden_groups_excluded_by_default() {
+        let query = ShelfQuery {
+            hidden_groups: 4,
+            ..Default::default()
+        };
+        let page = query_bookshelf(shelf(), &query);
+        // 同时属于可见分组的书籍仍然显示
+        assert_eq!(page.total, 5);
+        assert!(!urls(&page).contains(&"https://a.com/four"));
+
+        // 明确查询隐藏分组时不过滤
+        let query = ShelfQuery {
+            group: Some(4),
+            hidden_groups: 4,
+            ..Default::default()
+        };
+        assert_eq!(query_bookshelf(shelf(), &query).total, 2);
+    }
+
+    #[test]
+    fn test_sort_and_paginate() {
+        let mut books = shelf();
+        for (i, book) in books.iter_mut().enumerate() {
+            book.dur_chapter_time = Some(i as i64);
+            book.latest_chapter_time = Some(10 - (i as i64 % 3));
+        }
+
+        let query = ShelfQuery {
+            sort: Some(ShelfSort::RecentRead),
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = query_bookshelf(books.clone(), &query);
+        assert_eq!(page.total, 6);
+        assert_eq!(urls(&page), vec!["local://txt", "https://a.com/none"]);
+
+        let query = ShelfQuery {
+            sort: Some(ShelfSort::UpdateTime),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = query_bookshelf(books.clone(), &query);
+        assert_eq!(urls(&page), vec!["https://a.com/one", "https://a.com/none"]);
+
+        let query = ShelfQuery {
+            group: Some(GROUP_UNGROUPED),
+            sort: Some(ShelfSort::Name),
+            offset: 5,
+            ..Default::default()
+        };
+        let page = query_bookshelf(books, &query);
+        assert_eq!(page.total, 2);
+        assert!(page.books.is_empty());
+    }
+
+    fn chapters(count: usize) -> Vec<Chapter> {
+        (0..count)
+            .map(|i| Chapter {
+                title: format!("第{}章", i + 1),
+                url: format!("/c/{}", i + 1),
+                index: i as i32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_update_check() {
+        let mut book = book("https://a.com/one", 0);
+
+        // 首次检查只记录最新章节
+        assert!(!apply_update_check(&mut book, Ok(&chapters(2)), 100));
+        assert_eq!(book.latest_chapter_title.as_deref(), Some("第2章"));
+        assert_eq!(book.has_new_chapter, None);
+
+        let error = update_error(UpdateStage::Toc, &EngineError::http("timeout").into(), 200);
+        assert!(!apply_update_check(&mut book, Err(error), 200));
+        let error = book.last_update_error.as_ref().unwrap();
+        assert_eq!(error.stage, UpdateStage::Toc);
+        assert_eq!((error.kind.as_str(), error.time), ("NETWORK", 200));
+        assert!(error.message.contains("timeout"));
+        assert_eq!(book.latest_chapter_title.as_deref(), Some("第2章"));
+
+        assert!(apply_update_check(&mut book, Ok(&chapters(3)), 300));
+        assert_eq!(book.has_new_chapter, Some(true));
+        assert_eq!(book.last_update_error, None);
+        assert_eq!((book.latest_chapter_time, book.last_check_time), (Some(300), Some(300)));
+        assert_eq!(book.total_chapter_num, Some(3));
+    }
+}
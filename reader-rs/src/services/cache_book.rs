@@ -0,0 +1,220 @@
+//! 缓存整本书：后台并发抓取指定范围的章节正文写入缓存，通过任务 ID 查询进度或取消
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::{BookService, JobScheduler, ServiceError};
+
+/// 默认同时抓取的章节数
+const DEFAULT_CACHE_BOOK_CONCURRENCY: usize = 4;
+/// 进度中保留的最近错误数
+const MAX_RECENT_ERRORS: usize = 20;
+/// 保留进度的已结束任务数
+const MAX_FINISHED_JOBS: usize = 32;
+
+/// 同时抓取的章节数，由环境变量 CACHE_BOOK_CONCURRENCY 配置
+fn cache_book_concurrency() -> usize {
+    std::env::var("CACHE_BOOK_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_CACHE_BOOK_CONCURRENCY)
+}
+
+/// 一章抓取失败的原因
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheChapterError {
+    pub index: i32,
+    pub message: String,
+}
+
+/// 缓存任务的进度
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheBookProgress {
+    pub job_id: String,
+    pub book_url: String,
+    /// 范围内的章节数
+    pub total: usize,
+    /// 已完成的章节数 (含跳过的已缓存章节)
+    pub done: usize,
+    /// 开始前已缓存而跳过的章节数
+    pub skipped: usize,
+    pub failed: usize,
+    pub running: bool,
+    pub cancelled: bool,
+    /// 任务本身的错误 (如书源或目录获取失败)
+    pub error: Option<String>,
+    /// 最近的章节错误，最多 20 条
+    pub errors: Vec<CacheChapterError>,
+}
+
+/// 一个缓存任务
+struct CacheJob {
+    progress: Mutex<CacheBookProgress>,
+    cancel: CancellationToken,
+}
+
+impl CacheJob {
+    fn update(&self, f: impl FnOnce(&mut CacheBookProgress)) {
+        f(&mut self.progress.lock());
+    }
+
+    fn record(&self, index: i32, result: anyhow::Result<bool>) {
+        self.update(|progress| match result {
+            Ok(fetched) => {
+                progress.done += 1;
+                if !fetched {
+                    progress.skipped += 1;
+                }
+            }
+            Err(e) => {
+                progress.failed += 1;
+                if progress.errors.len() >= MAX_RECENT_ERRORS {
+                    progress.errors.remove(0);
+                }
+                progress.errors.push(CacheChapterError {
+                    index,
+                    message: format!("{:#}", e),
+                });
+            }
+        });
+    }
+}
+
+/// 缓存任务登记表，同一本书同一时间只有一个任务在运行
+pub struct BookCacher {
+    book_service: BookService,
+    jobs: Arc<JobScheduler>,
+    concurrency: usize,
+    tasks: Mutex<Vec<Arc<CacheJob>>>,
+}
+
+impl BookCacher {
+    pub fn new(book_service: BookService, jobs: Arc<JobScheduler>) -> Self {
+        Self::with_concurrency(book_service, jobs, cache_book_concurrency())
+    }
+
+    pub fn with_concurrency(book_service: BookService, jobs: Arc<JobScheduler>, concurrency: usize) -> Self {
+        Self {
+            book_service,
+            jobs,
+            concurrency: concurrency.max(1),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 开始缓存第 start 到 end 章 (含)，返回任务的初始进度 (需在 tokio 运行时中调用)
+    ///
+    /// 该书已有任务在运行时返回该任务的进度；再次缓存同一范围即从中断处继续。
+    pub async fn start(
+        &self,
+        book_url: &str,
+        start: Option<i32>,
+        end: Option<i32>,
+    ) -> Result<CacheBookProgress, anyhow::Error> {
+        let indices = self.book_service.chapter_range(book_url, start, end).await?;
+
+        let job = Arc::new(CacheJob {
+            progress: Mutex::new(CacheBookProgress {
+                job_id: uuid::Uuid::new_v4().to_string(),
+                book_url: book_url.to_string(),
+                total: indices.len(),
+                running: true,
+                ..Default::default()
+            }),
+            // 退出时随任务调度器一同取消
+            cancel: self.jobs.child_token(),
+        });
+        let progress = job.progress.lock().clone();
+        if let Some(running) = self.register(job.clone()) {
+            return Ok(running);
+        }
+
+        let service = self.book_service.clone();
+        let book_url = book_url.to_string();
+        let concurrency = self.concurrency;
+        let rt = tokio::runtime::Handle::current();
+        let worker = tokio::task::spawn_blocking(move || {
+            let result = service.cache_contents(&rt, &book_url, &indices, concurrency, &job.cancel, |index, result| {
+                job.record(index, result)
+            });
+            job.update(|progress| {
+                progress.running = false;
+                progress.cancelled = job.cancel.is_cancelled();
+                progress.error = result.err().map(|e| format!("{:#}", e));
+            });
+        });
+        self.jobs.spawn_named("cache book", async move {
+            worker.await?;
+            Ok(())
+        });
+        Ok(progress)
+    }
+
+    /// 任务进度
+    pub fn progress(&self, job_id: &str) -> Result<CacheBookProgress, ServiceError> {
+        Ok(self.job(job_id)?.progress.lock().clone())
+    }
+
+    /// 取消任务，已开始抓取的章节仍会完成
+    pub fn cancel(&self, job_id: &str) -> Result<CacheBookProgress, ServiceError> {
+        let job = self.job(job_id)?;
+        job.cancel.cancel();
+        let progress = job.progress.lock().clone();
+        Ok(progress)
+    }
+
+    /// 取消该书正在运行的任务 (换源、删除书籍时调用)
+    pub fn cancel_book(&self, book_url: &str) {
+        if let Some(job) = self.running_job(book_url) {
+            job.cancel.cancel();
+        }
+    }
+
+    fn job(&self, job_id: &str) -> Result<Arc<CacheJob>, ServiceError> {
+        self.tasks
+            .lock()
+            .iter()
+            .find(|job| job.progress.lock().job_id == job_id)
+            .cloned()
+            .ok_or_else(|| ServiceError::not_found("Job", job_id))
+    }
+
+    fn running_job(&self, book_url: &str) -> Option<Arc<CacheJob>> {
+        self.tasks
+            .lock()
+            .iter()
+            .find(|job| {
+                let progress = job.progress.lock();
+                progress.running && progress.book_url == book_url
+            })
+            .cloned()
+    }
+
+    /// 登记新任务，只保留最近的已结束任务；该书已有任务在运行时不登记，返回其进度
+    fn register(&self, job: Arc<CacheJob>) -> Option<CacheBookProgress> {
+        let book_url = job.progress.lock().book_url.clone();
+        let mut tasks = self.tasks.lock();
+        for task in tasks.iter() {
+            let progress = task.progress.lock();
+            if progress.running && progress.book_url == book_url {
+                return Some(progress.clone());
+            }
+        }
+
+        let finished = tasks.iter().filter(|task| !task.progress.lock().running).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS - 1);
+        tasks.retain(|task| {
+            let drop = excess > 0 && !task.progress.lock().running;
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+        tasks.push(job);
+        None
+    }
+}
@@ -0,0 +1,153 @@
+use axum::response::sse::Event;
+use serde::Serialize;
+
+use crate::engine::book_source::BookItem;
+use super::search_merge::{normalize_author, normalize_name};
+
+/// 默认统计目录章节数的候选来源数
+pub const DEFAULT_CHAPTER_COUNT_TOP_N: usize = 5;
+
+/// 换源候选：另一个书源中的同一本书
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCandidate {
+    pub source_url: String,
+    pub source_name: String,
+    pub book_url: String,
+    pub last_chapter: Option<String>,
+    /// 目录章节数，仅在请求 withChapterCount 时统计前几个候选
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapter_count: Option<usize>,
+    /// 书源响应耗时 (毫秒)
+    pub respond_time: u64,
+}
+
+/// 换源搜索条件
+#[derive(Debug, Clone)]
+pub struct ChangeSourceQuery {
+    pub name: String,
+    pub author: String,
+    /// 只搜索该分组的书源
+    pub group: Option<String>,
+    pub with_chapter_count: bool,
+    /// 统计章节数的候选数
+    pub top_n: usize,
+    /// 同时搜索的书源数
+    pub concurrent: usize,
+}
+
+impl ChangeSourceQuery {
+    pub fn new(name: impl Into<String>, author: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            author: author.into(),
+            group: None,
+            with_chapter_count: false,
+            top_n: DEFAULT_CHAPTER_COUNT_TOP_N,
+            concurrent: 20,
+        }
+    }
+
+    /// 搜索结果是否为要换源的书：书名一致，作者相同或互相包含
+    ///
+    /// 未指定作者时只比较书名。
+    pub fn matches(&self, item: &BookItem) -> bool {
+        if normalize_name(&item.name) != normalize_name(&self.name) {
+            return false;
+        }
+        let target = normalize_author(&self.author);
+        if target.is_empty() {
+            return true;
+        }
+        let author = normalize_author(&item.author);
+        !author.is_empty() && (author.contains(&target) || target.contains(&author))
+    }
+}
+
+/// 换源搜索过程中的事件，JSON 接口只取最终结果，SSE 接口逐个推送
+#[derive(Debug, Clone)]
+pub enum ChangeSourceEvent {
+    Progress { current: usize, total: usize },
+    Found(SourceCandidate),
+    /// 排序后的全部候选
+    Done(Vec<SourceCandidate>),
+}
+
+impl ChangeSourceEvent {
+    pub fn to_sse(&self) -> Event {
+        let data = match self {
+            Self::Progress { current, total } => {
+                serde_json::json!({ "type": "progress", "current": current, "total": total })
+            }
+            // 与多书源搜索一致，包装为 { "data": [candidate] }
+            Self::Found(candidate) => serde_json::json!({ "data": [candidate] }),
+            Self::Done(candidates) => serde_json::json!({ "type": "end", "data": candidates }),
+        };
+        Event::default().data(data.to_string())
+    }
+}
+
+/// 按章节数 (多者优先，未统计的排后)、响应耗时、书源 URL 排序
+pub fn rank_candidates(candidates: &mut [SourceCandidate]) {
+    candidates.sort_by(|a, b| {
+        b.chapter_count
+            .cmp(&a.chapter_count)
+            .then_with(|| a.respond_time.cmp(&b.respond_time))
+            .then_with(|| a.source_url.cmp(&b.source_url))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, author: &str) -> BookItem {
+        BookItem {
+            name: name.to_string(),
+            author: author.to_string(),
+            book_url: String::new(),
+            cover_url: None,
+            intro: None,
+            kind: None,
+            word_count: None,
+            last_chapter: None,
+            update_time: None,
+            toc_url: None,
+        }
+    }
+
+    fn candidate(url: &str, chapter_count: Option<usize>, respond_time: u64) -> SourceCandidate {
+        SourceCandidate {
+            source_url: url.to_string(),
+            source_name: url.to_string(),
+            book_url: String::new(),
+            last_chapter: None,
+            chapter_count,
+            respond_time,
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_author_match() {
+        let query = ChangeSourceQuery::new("斗破苍穹", "天蚕土豆");
+        assert!(query.matches(&item("《斗破苍穹》", "作者：天蚕土豆")));
+        assert!(query.matches(&item("斗破苍穹", "天蚕土豆著")));
+        assert!(!query.matches(&item("斗破苍穹", "唐家三少")));
+        assert!(!query.matches(&item("斗破苍穹", "")));
+        assert!(!query.matches(&item("斗破苍穹外传", "天蚕土豆")));
+        assert!(ChangeSourceQuery::new("斗破苍穹", "").matches(&item("斗破苍穹", "佚名")));
+    }
+
+    #[test]
+    fn test_rank_candidates() {
+        let mut candidates = vec![
+            candidate("a", None, 10),
+            candidate("b", Some(100), 300),
+            candidate("c", Some(120), 500),
+            candidate("d", None, 5),
+        ];
+        rank_candidates(&mut candidates);
+        let order: Vec<&str> = candidates.iter().map(|c| c.source_url.as_str()).collect();
+        assert_eq!(order, vec!["c", "b", "d", "a"]);
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::ServiceError;
+use crate::models::{builtin_content_filters, CompiledFilters, ContentFilter, BUILTIN_FILTER_PREFIX};
+use crate::storage::FileStorage;
+
+/// 正文净化规则存储文件名
+pub const FILTERS_FILE: &str = "contentFilters.json";
+
+#[derive(Default)]
+struct FilterState {
+    loaded: bool,
+    /// 内置规则在前，用户规则按添加顺序在后
+    filters: Vec<ContentFilter>,
+    compiled: Arc<CompiledFilters>,
+}
+
+impl FilterState {
+    fn set(&mut self, filters: Vec<ContentFilter>) {
+        self.compiled = Arc::new(CompiledFilters::compile(&filters));
+        self.filters = filters;
+        self.loaded = true;
+    }
+}
+
+/// 正文净化规则服务
+#[derive(Clone)]
+pub struct ContentFilterService {
+    storage: FileStorage,
+    state: Arc<RwLock<FilterState>>,
+}
+
+impl ContentFilterService {
+    pub fn new() -> Self {
+        Self::with_storage(FileStorage::default())
+    }
+
+    pub fn with_storage(storage: FileStorage) -> Self {
+        Self {
+            storage,
+            state: Arc::new(RwLock::new(FilterState::default())),
+        }
+    }
+
+    /// 从磁盘重新加载 (恢复备份后调用)
+    pub async fn reload(&self) {
+        let stored: Vec<ContentFilter> = self.storage.read_json_or_default(FILTERS_FILE).await;
+        self.state.write().await.set(merge_builtins(stored));
+    }
+
+    async fn ensure_loaded(&self) {
+        if !self.state.read().await.loaded {
+            self.reload().await;
+        }
+    }
+
+    /// 获取所有规则 (含内置规则)
+    pub async fn get_all_filters(&self) -> Vec<ContentFilter> {
+        self.ensure_loaded().await;
+        self.state.read().await.filters.clone()
+    }
+
+    /// 已编译的启用规则，用于处理正文
+    pub async fn compiled(&self) -> Arc<CompiledFilters> {
+        self.ensure_loaded().await;
+        self.state.read().await.compiled.clone()
+    }
+
+    /// 批量新增或更新规则，返回保存后的全部规则
+    ///
+    /// 任一正则非法时整批拒绝；内置规则只能修改启用状态。
+    pub async fn save_filters(&self, incoming: Vec<ContentFilter>) -> Result<Vec<ContentFilter>, anyhow::Error> {
+        self.ensure_loaded().await;
+        let mut state = self.state.write().await;
+        let mut filters = state.filters.clone();
+
+        for mut filter in incoming {
+            if filter.id.starts_with(BUILTIN_FILTER_PREFIX) {
+                let builtin = filters
+                    .iter_mut()
+                    .find(|f| f.builtin && f.id == filter.id)
+                    .ok_or_else(|| ServiceError::not_found("Content filter", filter.id.clone()))?;
+                builtin.enabled = filter.enabled;
+                continue;
+            }
+
+            filter.builtin = false;
+            filter.compile().map_err(ServiceError::invalid_input)?;
+            if filter.id.is_empty() {
+                filter.id = uuid::Uuid::new_v4().simple().to_string();
+            }
+            match filters.iter().position(|f| f.id == filter.id) {
+                Some(pos) => filters[pos] = filter,
+                None => filters.push(filter),
+            }
+        }
+
+        self.storage.write_json(FILTERS_FILE, &filters).await?;
+        state.set(filters.clone());
+        Ok(filters)
+    }
+
+    /// 删除规则，内置规则不可删除
+    pub async fn delete_filters(&self, to_delete: Vec<ContentFilter>) -> Result<(), anyhow::Error> {
+        self.ensure_loaded().await;
+        if let Some(builtin) = to_delete.iter().find(|f| f.id.starts_with(BUILTIN_FILTER_PREFIX)) {
+            return Err(ServiceError::invalid_input(format!(
+                "built-in content filter {} cannot be deleted, disable it instead",
+                builtin.id
+            ))
+            .into());
+        }
+
+        let mut state = self.state.write().await;
+        let mut filters = state.filters.clone();
+        filters.retain(|f| !to_delete.iter().any(|d| d.id == f.id));
+        self.storage.write_json(FILTERS_FILE, &filters).await?;
+        state.set(filters);
+        Ok(())
+    }
+}
+
+impl Default for ContentFilterService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 以当前内置规则为准合并存储的规则，只保留内置规则的启用状态
+fn merge_builtins(stored: Vec<ContentFilter>) -> Vec<ContentFilter> {
+    let mut filters = builtin_content_filters();
+    for builtin in &mut filters {
+        if let Some(saved) = stored.iter().find(|f| f.id == builtin.id) {
+            builtin.enabled = saved.enabled;
+        }
+    }
+    filters.extend(
+        stored
+            .into_iter()
+            .filter(|f| !f.id.starts_with(BUILTIN_FILTER_PREFIX) && !f.id.is_empty())
+            .map(|f| ContentFilter { builtin: false, ..f }),
+    );
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_service(name: &str) -> ContentFilterService {
+        let dir = format!("/tmp/reader_tests_content_filter_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        ContentFilterService::with_storage(FileStorage::new(&dir))
+    }
+
+    fn filter(pattern: &str, scope: &str) -> ContentFilter {
+        ContentFilter {
+            id: String::new(),
+            name: String::new(),
+            pattern: pattern.to_string(),
+            replacement: String::new(),
+            enabled: true,
+            scope: scope.to_string(),
+            builtin: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_reload() {
+        let service = test_service("save");
+        let builtin_count = service.get_all_filters().await.len();
+
+        let mut disable = builtin_content_filters().pop().unwrap();
+        disable.enabled = false;
+        let saved = service
+            .save_filters(vec![filter("广告", "https://a.com"), disable])
+            .await
+            .unwrap();
+        assert_eq!(saved.len(), builtin_count + 1);
+        assert!(!saved[builtin_count - 1].enabled);
+        let added = saved.last().unwrap().clone();
+        assert!(!added.id.is_empty());
+
+        let compiled = service.compiled().await;
+        assert_eq!(compiled.apply("广告(第1页)-->>", Some("https://a.com")), "-->>");
+        assert_eq!(compiled.apply("广告", Some("https://b.com")), "广告");
+
+        service.storage.flush().await.unwrap();
+        let reloaded = ContentFilterService::with_storage(service.storage.clone());
+        assert_eq!(reloaded.get_all_filters().await, saved);
+
+        reloaded.delete_filters(vec![added]).await.unwrap();
+        assert_eq!(reloaded.get_all_filters().await.len(), builtin_count);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_pattern_and_builtin_delete_rejected() {
+        let service = test_service("invalid");
+        let err = service
+            .save_filters(vec![filter("有效", ""), filter("(unclosed", "")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref::<ServiceError>(), Some(ServiceError::InvalidInput(_))));
+        assert!(err.to_string().contains("(unclosed"));
+        // 整批拒绝，有效的规则也未保存
+        assert!(service.get_all_filters().await.iter().all(|f| f.builtin));
+
+        let builtin = builtin_content_filters().remove(0);
+        let err = service.delete_filters(vec![builtin]).await.unwrap_err();
+        assert!(err.to_string().contains("cannot be deleted"));
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::Result;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// 导出用章节
+#[derive(Debug, Clone)]
+pub struct EpubChapter {
+    pub title: String,
+    pub content: String,
+}
+
+/// 导出用封面
+#[derive(Debug, Clone)]
+pub struct EpubCover {
+    pub data: Vec<u8>,
+    pub media_type: String,
+}
+
+/// EPUB 书籍元数据与章节
+#[derive(Debug, Clone, Default)]
+pub struct EpubBook {
+    pub identifier: String,
+    pub title: String,
+    pub author: String,
+    pub intro: Option<String>,
+    pub cover: Option<EpubCover>,
+    pub chapters: Vec<EpubChapter>,
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+impl EpubBook {
+    /// 生成 EPUB 3 文件内容
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // mimetype 必须是第一个且不压缩
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+
+        if let Some(cover) = &self.cover {
+            zip.start_file(format!("OEBPS/{}", self.cover_href(cover)), stored)?;
+            zip.write_all(&cover.data)?;
+        }
+
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            zip.start_file(format!("OEBPS/{}", Self::chapter_href(i)), deflated)?;
+            zip.write_all(Self::chapter_xhtml(chapter).as_bytes())?;
+        }
+
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav_xhtml().as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.content_opf().as_bytes())?;
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn chapter_href(index: usize) -> String {
+        format!("Text/chapter_{:05}.xhtml", index + 1)
+    }
+
+    fn cover_href(&self, cover: &EpubCover) -> String {
+        let ext = match cover.media_type.as_str() {
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            _ => "jpg",
+        };
+        format!("Images/cover.{}", ext)
+    }
+
+    fn content_opf(&self) -> String {
+        let mut manifest = String::from(
+            r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+"#,
+        );
+        let mut spine = String::new();
+
+        if let Some(cover) = &self.cover {
+            manifest.push_str(&format!(
+                "    <item id=\"cover-image\" href=\"{}\" media-type=\"{}\" properties=\"cover-image\"/>\n",
+                self.cover_href(cover),
+                escape_attr(&cover.media_type)
+            ));
+        }
+
+        for i in 0..self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"chapter_{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                i + 1,
+                Self::chapter_href(i)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"chapter_{}\"/>\n", i + 1));
+        }
+
+        let description = self
+            .intro
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| format!("    <dc:description>{}</dc:description>\n", escape_text(s.trim())))
+            .unwrap_or_default();
+        let cover_meta = if self.cover.is_some() {
+            "    <meta name=\"cover\" content=\"cover-image\"/>\n"
+        } else {
+            ""
+        };
+        let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="zh">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:creator>{}</dc:creator>
+    <dc:language>zh</dc:language>
+{}{}    <meta property="dcterms:modified">{}</meta>
+  </metadata>
+  <manifest>
+{}  </manifest>
+  <spine>
+{}  </spine>
+</package>
+"#,
+            escape_text(&self.identifier),
+            escape_text(&self.title),
+            escape_text(&self.author),
+            description,
+            cover_meta,
+            modified,
+            manifest,
+            spine
+        )
+    }
+
+    fn nav_xhtml(&self) -> String {
+        let items: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                format!(
+                    "      <li><a href=\"{}\">{}</a></li>\n",
+                    Self::chapter_href(i),
+                    escape_text(&c.title)
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="zh">
+<head><title>{}</title></head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>目录</h1>
+    <ol>
+{}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+            escape_text(&self.title),
+            items
+        )
+    }
+
+    fn chapter_xhtml(chapter: &EpubChapter) -> String {
+        let paragraphs: String = chapter
+            .content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("  <p>{}</p>\n", escape_text(line)))
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="zh">
+<head><title>{title}</title></head>
+<body>
+  <h2>{title}</h2>
+{paragraphs}</body>
+</html>
+"#,
+            title = escape_text(&chapter.title),
+            paragraphs = paragraphs
+        )
+    }
+}
+
+fn escape_text(s: &str) -> String {
+    html_escape::encode_text(s).to_string()
+}
+
+fn escape_attr(s: &str) -> String {
+    html_escape::encode_double_quoted_attribute(s).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn fixture_book() -> EpubBook {
+        EpubBook {
+            identifier: "urn:reader:test".to_string(),
+            title: "测试书 & 续".to_string(),
+            author: "作者".to_string(),
+            intro: Some("简介".to_string()),
+            cover: Some(EpubCover {
+                data: vec![0x89, b'P', b'N', b'G'],
+                media_type: "image/png".to_string(),
+            }),
+            chapters: vec![
+                EpubChapter {
+                    title: "第一章".to_string(),
+                    content: "第一段\n\n第二段 <b>".to_string(),
+                },
+                EpubChapter {
+                    title: "第二章".to_string(),
+                    content: "内容".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut s = String::new();
+        archive.by_name(name).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn test_epub_structure() {
+        let data = fixture_book().build().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        {
+            let first = archive.by_index(0).unwrap();
+            assert_eq!(first.name(), "mimetype");
+            assert_eq!(first.compression(), CompressionMethod::Stored);
+        }
+        assert_eq!(read_entry(&mut archive, "mimetype"), "application/epub+zip");
+        assert!(read_entry(&mut archive, "META-INF/container.xml")
+            .contains(r#"full-path="OEBPS/content.opf""#));
+
+        let chapter = read_entry(&mut archive, "OEBPS/Text/chapter_00001.xhtml");
+        assert!(chapter.contains("<p>第一段</p>"));
+        assert!(chapter.contains("<p>第二段 &lt;b&gt;</p>"));
+        assert!(archive.by_name("OEBPS/Images/cover.png").is_ok());
+    }
+
+    #[test]
+    fn test_epub_opf_references() {
+        let data = fixture_book().build().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let opf = read_entry(&mut archive, "OEBPS/content.opf");
+
+        assert!(opf.contains("<dc:title>测试书 &amp; 续</dc:title>"));
+        assert!(opf.contains("<dc:creator>作者</dc:creator>"));
+        assert!(opf.contains(r#"properties="cover-image""#));
+
+        // manifest 中每个 href 都必须存在于压缩包内
+        let hrefs: Vec<String> = opf
+            .split("href=\"")
+            .skip(1)
+            .map(|s| s[..s.find('"').unwrap()].to_string())
+            .collect();
+        assert_eq!(hrefs.len(), 4);
+        for href in &hrefs {
+            assert!(archive.by_name(&format!("OEBPS/{}", href)).is_ok(), "{}", href);
+        }
+
+        // spine 保持章节顺序
+        let first = opf.find(r#"<itemref idref="chapter_1"/>"#).unwrap();
+        let second = opf.find(r#"<itemref idref="chapter_2"/>"#).unwrap();
+        assert!(first < second);
+    }
+}
@@ -1,90 +1,295 @@
+use serde::Deserialize;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockMappedWriteGuard, RwLockWriteGuard};
 
+use super::bookshelf::{GROUP_ALL, GROUP_AUDIO, GROUP_LOCAL, GROUP_UNGROUPED};
+use super::shelf_export::ShelfReport;
+use super::{BookService, ServiceError};
 use crate::models::BookGroup;
 use crate::storage::FileStorage;
-use crate::api::group::GroupOrderItem;
 
 /// 分组存储文件名
 const GROUPS_FILE: &str = "bookGroups.json";
+/// 可分配的分组位数 (保留符号位，负数 ID 为内置的虚拟分组)
+const MAX_GROUP_BITS: u32 = 63;
+
+/// 内置分组：不能删除，但可以隐藏、排序与改名
+const BUILTIN_GROUPS: [(i64, &str); 4] = [
+    (GROUP_ALL, "全部"),
+    (GROUP_LOCAL, "本地"),
+    (GROUP_AUDIO, "音频"),
+    (GROUP_UNGROUPED, "未分组"),
+];
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct GroupOrderItem {
+    #[serde(rename = "groupId")]
+    pub group_id: i64,
+    pub order: i32,
+}
 
 pub struct GroupService {
     storage: FileStorage,
-    groups: Arc<RwLock<Vec<BookGroup>>>,
+    /// 首次使用时从存储加载并补全内置分组 (None 表示尚未加载)
+    groups: Arc<RwLock<Option<Vec<BookGroup>>>>,
+    /// 删除分组时清除书籍上的分组位
+    book_service: BookService,
 }
 
 impl GroupService {
-    pub fn new() -> Self {
+    pub fn with_storage(storage: FileStorage, book_service: BookService) -> Self {
         Self {
-            storage: FileStorage::default(),
-            groups: Arc::new(RwLock::new(Vec::new())),
+            storage,
+            groups: Arc::new(RwLock::new(None)),
+            book_service,
         }
     }
 
-    /// 获取所有分组
-    pub async fn get_all_groups(&self) -> Result<Vec<BookGroup>, anyhow::Error> {
-        let groups = self.groups.read().await;
-        
-        if groups.is_empty() {
-            drop(groups);
+    /// 从磁盘重新加载 (恢复备份后调用)
+    pub async fn reload(&self) {
+        *self.groups.write().await = None;
+    }
+
+    async fn groups_mut(&self) -> RwLockMappedWriteGuard<'_, Vec<BookGroup>> {
+        let mut guard = self.groups.write().await;
+        if guard.is_none() {
             let loaded: Vec<BookGroup> = self.storage.read_json_or_default(GROUPS_FILE).await;
-            let mut cache = self.groups.write().await;
-            *cache = loaded.clone();
-            return Ok(loaded);
+            *guard = Some(with_builtin_groups(loaded));
         }
-        
-        Ok(groups.clone())
+        RwLockWriteGuard::map(guard, |groups| groups.get_or_insert_with(Vec::new))
+    }
+
+    /// 获取所有分组 (含内置分组)，按 order 排序
+    pub async fn get_all_groups(&self) -> Result<Vec<BookGroup>, anyhow::Error> {
+        Ok(self.groups_mut().await.clone())
+    }
+
+    /// 书架报表，分组位掩码解析为分组名称
+    pub async fn shelf_report(&self) -> Result<ShelfReport, anyhow::Error> {
+        let groups = self.get_all_groups().await?;
+        self.book_service.shelf_report(&groups).await
     }
 
-    /// 保存分组
+    /// 隐藏的自定义分组的位掩码
+    pub async fn hidden_groups(&self) -> i64 {
+        self.groups_mut()
+            .await
+            .iter()
+            .filter(|g| g.group_id > 0 && !g.show)
+            .fold(0, |acc, g| acc | g.group_id)
+    }
+
+    /// 保存分组；groupId 为 0 时新建并分配最低的未用位
     pub async fn save_group(&self, mut group: BookGroup) -> Result<BookGroup, anyhow::Error> {
-        let mut groups = self.groups.write().await;
-        
-        // 生成 ID
-        if group.group_id == 0 {
-            let max_id = groups.iter().map(|g| g.group_id).max().unwrap_or(0);
-            group.group_id = max_id + 1;
+        group.group_name = group.group_name.trim().to_string();
+        if group.group_name.is_empty() {
+            return Err(ServiceError::invalid_input("Group name is empty").into());
         }
-        
-        // 更新或添加
-        if let Some(pos) = groups.iter().position(|g| g.group_id == group.group_id) {
-            groups[pos] = group.clone();
-        } else {
-            groups.push(group.clone());
+        let mut groups = self.groups_mut().await;
+        if groups
+            .iter()
+            .any(|g| g.group_id != group.group_id && g.group_name == group.group_name)
+        {
+            return Err(ServiceError::invalid_input(format!("Group already exists: {}", group.group_name)).into());
         }
-        
-        self.storage.write_json(GROUPS_FILE, &*groups).await?;
+
+        match group.group_id {
+            // 分组 ID 是书籍 group 位掩码中的一位，删除分组后其位可被复用
+            0 => {
+                let used = groups.iter().fold(0i64, |acc, g| acc | g.group_id.max(0));
+                group.group_id = (0..MAX_GROUP_BITS)
+                    .map(|bit| 1i64 << bit)
+                    .find(|id| used & id == 0)
+                    .ok_or_else(|| ServiceError::invalid_input(format!("At most {} book groups", MAX_GROUP_BITS)))?;
+            }
+            id if id < 0 && !groups.iter().any(|g| g.group_id == id) => {
+                return Err(ServiceError::not_found("Group", id.to_string()).into());
+            }
+            id if id > 0 && id.count_ones() != 1 => {
+                return Err(ServiceError::invalid_input(format!("Group id must be a single bit: {}", id)).into());
+            }
+            _ => {}
+        }
+
+        let mut updated = groups.clone();
+        match updated.iter_mut().find(|g| g.group_id == group.group_id) {
+            Some(existing) => *existing = group.clone(),
+            None => updated.push(group.clone()),
+        }
+        updated.sort_by_key(|g| g.order);
+        self.storage.write_json(GROUPS_FILE, &updated).await?;
+        *groups = updated;
         Ok(group)
     }
 
-    /// 删除分组
+    /// 删除分组，并从所有书籍清除该分组位
     pub async fn delete_group(&self, group_id: i64) -> Result<(), anyhow::Error> {
-        let mut groups = self.groups.write().await;
-        groups.retain(|g| g.group_id != group_id);
-        self.storage.write_json(GROUPS_FILE, &*groups).await?;
+        if group_id < 0 {
+            return Err(ServiceError::invalid_input("Built-in groups cannot be deleted").into());
+        }
+        let mut groups = self.groups_mut().await;
+        if !groups.iter().any(|g| g.group_id == group_id) {
+            return Err(ServiceError::not_found("Group", group_id.to_string()).into());
+        }
+        // 先清除书籍上的位，之后复用该位的新分组不会带上这些书
+        self.book_service.clear_group(group_id).await?;
+
+        let updated: Vec<BookGroup> = groups.iter().filter(|g| g.group_id != group_id).cloned().collect();
+        self.storage.write_json(GROUPS_FILE, &updated).await?;
+        *groups = updated;
         Ok(())
     }
 
-    /// 保存分组顺序
-    pub async fn save_group_order(&self, order: Vec<GroupOrderItem>) -> Result<(), anyhow::Error> {
-        let mut groups = self.groups.write().await;
-        
-        for item in order {
-            if let Some(group) = groups.iter_mut().find(|g| g.group_id == item.group_id) {
-                group.order = item.order;
+    /// 保存分组顺序：需列出全部分组 (含内置分组)，按 order 排列后依次编号
+    pub async fn save_group_order(&self, mut order: Vec<GroupOrderItem>) -> Result<(), anyhow::Error> {
+        let mut groups = self.groups_mut().await;
+        let ids: HashSet<i64> = order.iter().map(|item| item.group_id).collect();
+        if ids.len() != order.len() || ids.len() != groups.len() || groups.iter().any(|g| !ids.contains(&g.group_id)) {
+            return Err(ServiceError::invalid_input("The order must list every group exactly once").into());
+        }
+
+        // 稳定排序，order 相同时保持列表顺序
+        order.sort_by_key(|item| item.order);
+        let mut updated = groups.clone();
+        for (position, item) in order.iter().enumerate() {
+            if let Some(group) = updated.iter_mut().find(|g| g.group_id == item.group_id) {
+                group.order = position as i32;
             }
         }
-        
-        // 按顺序排序
-        groups.sort_by_key(|g| g.order);
-        
-        self.storage.write_json(GROUPS_FILE, &*groups).await?;
+        updated.sort_by_key(|g| g.order);
+
+        self.storage.write_json(GROUPS_FILE, &updated).await?;
+        *groups = updated;
         Ok(())
     }
 }
 
-impl Default for GroupService {
-    fn default() -> Self {
-        Self::new()
+/// 补全缺少的内置分组 (排在已有分组之前)，并按 order 排序
+fn with_builtin_groups(mut groups: Vec<BookGroup>) -> Vec<BookGroup> {
+    for (i, (group_id, name)) in BUILTIN_GROUPS.iter().enumerate() {
+        if !groups.iter().any(|g| g.group_id == *group_id) {
+            groups.push(BookGroup {
+                group_id: *group_id,
+                group_name: name.to_string(),
+                order: i as i32 - BUILTIN_GROUPS.len() as i32,
+                show: true,
+            });
+        }
+    }
+    groups.sort_by_key(|g| g.order);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Book;
+    use crate::services::AppState;
+
+    fn group(id: i64, name: &str) -> BookGroup {
+        BookGroup {
+            group_id: id,
+            group_name: name.to_string(),
+            order: 10,
+            show: true,
+        }
+    }
+
+    fn state(name: &str) -> AppState {
+        let dir = format!("/tmp/reader_tests_groups_{}", name);
+        let _ = std::fs::remove_dir_all(&dir);
+        AppState::with_storage_dir(&dir)
+    }
+
+    #[tokio::test]
+    async fn test_group_bits_assigned_and_reused() {
+        let state = state("bits");
+        let groups = &state.group_service;
+
+        let mut ids = Vec::new();
+        for name in ["追更", "完结", "养肥"] {
+            ids.push(groups.save_group(group(0, name)).await.unwrap().group_id);
+        }
+        assert_eq!(ids, vec![1, 2, 4]);
+
+        // 重名与非单个位的 ID 被拒绝
+        assert!(groups.save_group(group(0, " 追更 ")).await.is_err());
+        assert!(groups.save_group(group(3, "双位")).await.is_err());
+
+        groups.delete_group(2).await.unwrap();
+        assert_eq!(groups.save_group(group(0, "新分组")).await.unwrap().group_id, 2);
+
+        for bit in 3..MAX_GROUP_BITS {
+            groups.save_group(group(0, &format!("分组{}", bit))).await.unwrap();
+        }
+        assert!(groups.save_group(group(0, "太多")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_builtin_groups_protected_and_ordered() {
+        let state = state("builtin");
+        let groups = &state.group_service;
+
+        let all = groups.get_all_groups().await.unwrap();
+        let names: Vec<&str> = all.iter().map(|g| g.group_name.as_str()).collect();
+        assert_eq!(names, vec!["全部", "本地", "音频", "未分组"]);
+        assert!(groups.delete_group(GROUP_LOCAL).await.is_err());
+
+        // 内置分组可以隐藏
+        let mut local = all[1].clone();
+        local.show = false;
+        groups.save_group(local).await.unwrap();
+        let custom = groups.save_group(group(0, "追更")).await.unwrap();
+
+        // 顺序需列出全部分组
+        let partial = vec![GroupOrderItem {
+            group_id: custom.group_id,
+            order: 0,
+        }];
+        assert!(groups.save_group_order(partial).await.is_err());
+        let order = [custom.group_id, GROUP_UNGROUPED, GROUP_ALL, GROUP_AUDIO, GROUP_LOCAL]
+            .into_iter()
+            .enumerate()
+            .map(|(i, group_id)| GroupOrderItem {
+                group_id,
+                order: i as i32 * 10,
+            })
+            .collect();
+        groups.save_group_order(order).await.unwrap();
+
+        groups.reload().await;
+        let all = groups.get_all_groups().await.unwrap();
+        let names: Vec<&str> = all.iter().map(|g| g.group_name.as_str()).collect();
+        assert_eq!(names, vec!["追更", "未分组", "全部", "音频", "本地"]);
+        assert_eq!(all.iter().map(|g| g.order).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(!all[4].show);
+    }
+
+    #[tokio::test]
+    async fn test_delete_group_clears_book_bits() {
+        let state = state("delete");
+        let groups = &state.group_service;
+        let follow = groups.save_group(group(0, "追更")).await.unwrap().group_id;
+        let done = groups.save_group(group(0, "完结")).await.unwrap().group_id;
+        for (url, group) in [("https://a.com/1", follow), ("https://a.com/2", follow | done)] {
+            let book = Book {
+                book_url: url.to_string(),
+                name: url.to_string(),
+                group: Some(group),
+                ..Default::default()
+            };
+            state.book_service.save_book(book).await.unwrap();
+        }
+
+        groups.delete_group(follow).await.unwrap();
+        let shelf = state.book_service.get_bookshelf(false).await.unwrap();
+        let bits: Vec<Option<i64>> = shelf.iter().map(|b| b.group).collect();
+        assert_eq!(bits, vec![None, Some(done)]);
+
+        // 复用该位的新分组不包含之前的书
+        let reused = groups.save_group(group(0, "新分组")).await.unwrap().group_id;
+        assert_eq!(reused, follow);
+        assert!(shelf.iter().all(|b| b.group.unwrap_or(0) & reused == 0));
     }
 }
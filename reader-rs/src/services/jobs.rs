@@ -0,0 +1,288 @@
+//! 后台任务调度：具名任务与周期任务统一登记，记录运行状态，退出时一并取消
+
+use futures::FutureExt;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+/// 一个后台任务的运行状态
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub name: String,
+    /// 周期任务的执行间隔 (秒)，一次性任务为 None
+    pub interval_secs: Option<u64>,
+    /// 正在执行
+    pub running: bool,
+    /// 已结束的执行次数 (含失败)
+    pub runs: u64,
+    /// 失败或 panic 的次数
+    pub failures: u64,
+    /// 最近一次开始执行的时间 (毫秒时间戳)
+    pub last_run: Option<i64>,
+    /// 最近一次失败的错误信息
+    pub last_error: Option<String>,
+}
+
+type Statuses = Arc<Mutex<BTreeMap<String, JobStatus>>>;
+
+/// 后台任务调度器
+///
+/// 任务返回的错误与 panic 只记入状态并写日志，不会让周期任务停止；
+/// `shutdown` 通过取消令牌结束全部任务。需在 tokio 运行时中使用。
+#[derive(Default)]
+pub struct JobScheduler {
+    cancel: CancellationToken,
+    statuses: Statuses,
+    handles: Mutex<Vec<(String, JoinHandle<()>)>>,
+}
+
+/// 更新一个任务的状态记录
+struct Recorder {
+    statuses: Statuses,
+    name: String,
+}
+
+impl Recorder {
+    fn new(statuses: &Statuses, name: &str, interval: Option<Duration>) -> Self {
+        let mut map = statuses.lock();
+        let status = map.entry(name.to_string()).or_insert_with(|| JobStatus {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        status.interval_secs = interval.map(|i| i.as_secs());
+        Self {
+            statuses: statuses.clone(),
+            name: name.to_string(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.statuses.lock().get_mut(&self.name) {
+            f(status);
+        }
+    }
+
+    fn start(&self) {
+        self.update(|status| {
+            status.running = true;
+            status.last_run = Some(chrono::Utc::now().timestamp_millis());
+        });
+    }
+
+    fn finish(&self, result: Result<(), String>) {
+        self.update(|status| {
+            status.running = false;
+            status.runs += 1;
+            if let Err(e) = result {
+                status.failures += 1;
+                status.last_error = Some(e);
+            }
+        });
+    }
+
+    fn cancelled(&self) {
+        self.update(|status| status.running = false);
+    }
+}
+
+impl JobScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 退出时随之取消的令牌，供阻塞线程等无法直接取消的工作检查
+    pub fn child_token(&self) -> CancellationToken {
+        self.cancel.child_token()
+    }
+
+    /// 启动一个一次性任务；同名任务共用一条状态记录
+    pub fn spawn_named<F>(&self, name: impl Into<String>, job: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let recorder = Recorder::new(&self.statuses, &name, None);
+        let cancel = self.cancel.clone();
+        recorder.start();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => recorder.cancelled(),
+                result = run_guarded(&recorder.name, job) => recorder.finish(result),
+            }
+        });
+        self.track(name, handle);
+    }
+
+    /// 启动周期任务：首次在一个间隔之后执行，之后每隔 interval 执行一次
+    ///
+    /// 上一次执行超过间隔时顺延，不会连续补跑。
+    pub fn spawn_periodic<F, Fut>(&self, name: impl Into<String>, interval: Duration, job: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let recorder = Recorder::new(&self.statuses, &name, Some(interval));
+        let cancel = self.cancel.clone();
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => return,
+                    _ = ticker.tick() => {}
+                }
+                recorder.start();
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => {
+                        recorder.cancelled();
+                        return;
+                    }
+                    result = run_guarded(&recorder.name, job()) => recorder.finish(result),
+                }
+            }
+        });
+        self.track(name, handle);
+    }
+
+    fn track(&self, name: String, handle: JoinHandle<()>) {
+        let mut handles = self.handles.lock();
+        handles.retain(|(_, handle)| !handle.is_finished());
+        handles.push((name, handle));
+    }
+
+    /// 全部任务的状态，按名称排序
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.lock().values().cloned().collect()
+    }
+
+    /// 取消全部任务并在 timeout 内等待其结束，超时的任务被强制中止
+    ///
+    /// 返回超时任务的名称。之后启动的任务会立即被取消。
+    pub async fn shutdown(&self, timeout: Duration) -> Vec<String> {
+        self.cancel.cancel();
+        let handles = std::mem::take(&mut *self.handles.lock());
+        let deadline = Instant::now() + timeout;
+        let mut stuck = Vec::new();
+        for (name, mut handle) in handles {
+            if tokio::time::timeout_at(deadline, &mut handle).await.is_err() {
+                handle.abort();
+                tracing::warn!("Background job {} did not stop in time, aborted", name);
+                stuck.push(name);
+            } else {
+                tracing::debug!("Stopped background job: {}", name);
+            }
+        }
+        stuck
+    }
+}
+
+/// 执行一次任务，把错误与 panic 转为状态中的错误信息
+async fn run_guarded<F>(name: &str, job: F) -> Result<(), String>
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    match AssertUnwindSafe(job).catch_unwind().await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => {
+            tracing::warn!("Background job {} failed: {:#}", name, e);
+            Err(format!("{:#}", e))
+        }
+        Err(panic) => {
+            let message = format!("panicked: {}", panic_message(panic.as_ref()));
+            tracing::error!("Background job {} {}", name, message);
+            Err(message)
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn status(jobs: &JobScheduler, name: &str) -> JobStatus {
+        jobs.statuses().into_iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_periodic_job_survives_panic() {
+        let jobs = JobScheduler::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        jobs.spawn_periodic("flaky", Duration::from_millis(10), move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call == 0 {
+                    panic!("first tick fails");
+                }
+                Ok(())
+            }
+        });
+        jobs.spawn_named("failing", async { Err(anyhow::anyhow!("disk full")) });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while calls.load(Ordering::SeqCst) < 3 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::task::yield_now().await;
+
+        let flaky = status(&jobs, "flaky");
+        assert!(flaky.runs >= 2, "{:?}", flaky);
+        assert_eq!(flaky.failures, 1);
+        assert_eq!(flaky.last_error.as_deref(), Some("panicked: first tick fails"));
+        assert_eq!(flaky.interval_secs, Some(0));
+        let failing = status(&jobs, "failing");
+        assert_eq!((failing.runs, failing.failures), (1, 1));
+        assert_eq!(failing.last_error.as_deref(), Some("disk full"));
+        assert!(!failing.running);
+
+        assert!(jobs.shutdown(Duration::from_secs(1)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_jobs() {
+        let jobs = JobScheduler::new();
+        jobs.spawn_named("forever", std::future::pending());
+        jobs.spawn_periodic("slow", Duration::from_millis(5), || async {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        });
+        let token = jobs.child_token();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(status(&jobs, "forever").running);
+        assert!(status(&jobs, "slow").running);
+
+        let started = Instant::now();
+        assert!(jobs.shutdown(Duration::from_secs(1)).await.is_empty());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(token.is_cancelled());
+        assert!(jobs.statuses().iter().all(|s| !s.running));
+
+        // 退出后启动的任务立即取消
+        jobs.spawn_named("late", std::future::pending());
+        assert!(jobs.shutdown(Duration::from_millis(100)).await.is_empty());
+    }
+}
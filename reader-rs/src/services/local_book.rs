@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, GB18030, UTF_8};
+use regex::Regex;
+use std::io::Read;
+
+/// 本地书籍的 origin 标记
+pub const LOCAL_ORIGIN: &str = "local";
+
+/// 本地书籍 bookUrl 前缀
+pub const LOCAL_URL_PREFIX: &str = "local://";
+
+/// 默认章节标题规则
+pub const DEFAULT_TOC_RULES: &[&str] = &[
+    r"^第[0-9０-９零〇一二两三四五六七八九十百千万]+[章节卷回集部篇].*$",
+    r"^(序章|序言|楔子|引子|尾声|后记|番外)([\s:：].*)?$",
+    r"^(?i:chapter)\s*\d+.*$",
+];
+
+/// 超过该长度的行不视为章节标题
+const MAX_TITLE_CHARS: usize = 50;
+/// 每次读取的字节数
+const READ_CHUNK: usize = 64 * 1024;
+
+/// 是否为本地导入的书籍
+pub fn is_local_book(book_url: &str) -> bool {
+    book_url.starts_with(LOCAL_URL_PREFIX)
+}
+
+/// 切分出的章节
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalChapter {
+    pub title: String,
+    pub content: String,
+}
+
+/// 根据 BOM 或内容猜测编码
+///
+/// 样本可能截断在多字节字符中间，末尾不完整的 UTF-8 序列不视为错误。
+pub fn detect_encoding(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => UTF_8,
+        Err(e) if e.error_len().is_none() => UTF_8,
+        // GB18030 兼容 GBK 与 GB2312
+        Err(_) => GB18030,
+    }
+}
+
+/// TXT 章节切分器
+pub struct ChapterSplitter {
+    rules: Vec<Regex>,
+}
+
+impl ChapterSplitter {
+    /// 使用自定义规则创建，规则为空时使用默认规则
+    pub fn new(rules: &[String]) -> Result<Self> {
+        let rules = if rules.is_empty() {
+            DEFAULT_TOC_RULES.iter().map(|r| Regex::new(r)).collect::<Result<Vec<_>, _>>()?
+        } else {
+            rules
+                .iter()
+                .map(|r| Regex::new(r).with_context(|| format!("Invalid toc rule: {}", r)))
+                .collect::<Result<Vec<_>>>()?
+        };
+        Ok(Self { rules })
+    }
+
+    /// 判断一行是否为章节标题
+    pub fn is_title(&self, line: &str) -> bool {
+        let line = line.trim();
+        !line.is_empty()
+            && line.chars().count() <= MAX_TITLE_CHARS
+            && self.rules.iter().any(|r| r.is_match(line))
+    }
+
+    /// 流式读取并切分章节，每切出一章调用一次 on_chapter，返回识别到的编码
+    pub fn split<R, F>(&self, mut reader: R, on_chapter: F) -> Result<&'static Encoding>
+    where
+        R: Read,
+        F: FnMut(LocalChapter) -> Result<()>,
+    {
+        let mut chunk = vec![0u8; READ_CHUNK];
+        let mut read = read_full(&mut reader, &mut chunk)?;
+        let encoding = detect_encoding(&chunk[..read]);
+        let mut decoder = encoding.new_decoder_with_bom_removal();
+
+        let mut state = SplitState::new(self, on_chapter);
+        let mut text = String::new();
+        loop {
+            let last = read == 0;
+            let needed = decoder
+                .max_utf8_buffer_length(read)
+                .unwrap_or(read * 3 + 16);
+            text.reserve(needed);
+            let _ = decoder.decode_to_string(&chunk[..read], &mut text, last);
+
+            // 只处理完整的行，剩余部分留到下一轮
+            if let Some(end) = text.rfind('\n') {
+                for line in text[..end].split('\n') {
+                    state.push_line(line)?;
+                }
+                text.drain(..=end);
+            }
+
+            if last {
+                break;
+            }
+            read = read_full(&mut reader, &mut chunk)?;
+        }
+        if !text.is_empty() {
+            state.push_line(&text)?;
+        }
+        state.finish()?;
+        Ok(encoding)
+    }
+}
+
+/// 尽量读满缓冲区，返回 0 表示已到文件末尾
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+struct SplitState<'a, F> {
+    splitter: &'a ChapterSplitter,
+    on_chapter: F,
+    /// 第一个标题之前的内容
+    preface: String,
+    current: Option<LocalChapter>,
+    found_title: bool,
+}
+
+impl<'a, F> SplitState<'a, F>
+where
+    F: FnMut(LocalChapter) -> Result<()>,
+{
+    fn new(splitter: &'a ChapterSplitter, on_chapter: F) -> Self {
+        Self {
+            splitter,
+            on_chapter,
+            preface: String::new(),
+            current: None,
+            found_title: false,
+        }
+    }
+
+    fn push_line(&mut self, line: &str) -> Result<()> {
+        let line = line.trim_end_matches('\r');
+        if self.splitter.is_title(line) {
+            let title = line.trim().to_string();
+            // 连续重复的标题行 (标题后紧跟同名标题) 只保留一个
+            if let Some(current) = &self.current {
+                if current.title == title && current.content.trim().is_empty() {
+                    return Ok(());
+                }
+            }
+
+            if !self.found_title {
+                self.found_title = true;
+                let preface = std::mem::take(&mut self.preface);
+                if !preface.trim().is_empty() {
+                    self.emit("前言".to_string(), preface)?;
+                }
+            }
+            if let Some(current) = self.current.take() {
+                self.emit(current.title, current.content)?;
+            }
+            self.current = Some(LocalChapter {
+                title,
+                content: String::new(),
+            });
+            return Ok(());
+        }
+
+        let buf = match self.current.as_mut() {
+            Some(current) => &mut current.content,
+            None => &mut self.preface,
+        };
+        buf.push_str(line);
+        buf.push('\n');
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if let Some(current) = self.current.take() {
+            self.emit(current.title, current.content)?;
+        } else if !self.preface.trim().is_empty() {
+            // 没有匹配到任何章节标题
+            let content = std::mem::take(&mut self.preface);
+            self.emit("正文".to_string(), content)?;
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, title: String, content: String) -> Result<()> {
+        let content = content.trim_matches(|c| c == '\n' || c == '\r').to_string();
+        (self.on_chapter)(LocalChapter { title, content })
+    }
+}
+
+/// 从文件名中解析书名与作者，支持 `《书名》作者：某某` 与 `书名 作者：某某`
+pub fn parse_file_name(stem: &str) -> (String, String) {
+    let stem = stem.trim();
+    let (name_part, author) = match stem.find("作者") {
+        Some(pos) => {
+            let author = stem[pos + "作者".len()..]
+                .trim_start_matches([':', '：', ' '])
+                .trim()
+                .to_string();
+            (&stem[..pos], author)
+        }
+        None => (stem, String::new()),
+    };
+
+    let name_part = name_part.trim();
+    let name = match (name_part.find('《'), name_part.find('》')) {
+        (Some(start), Some(end)) if start < end => &name_part[start + '《'.len_utf8()..end],
+        _ => name_part,
+    };
+    (name.trim().to_string(), author)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::GBK;
+
+    fn split_text(bytes: &[u8]) -> (Vec<LocalChapter>, &'static Encoding) {
+        let splitter = ChapterSplitter::new(&[]).unwrap();
+        let mut chapters = Vec::new();
+        let encoding = splitter
+            .split(bytes, |c| {
+                chapters.push(c);
+                Ok(())
+            })
+            .unwrap();
+        (chapters, encoding)
+    }
+
+    #[test]
+    fn test_default_title_rules() {
+        let splitter = ChapterSplitter::new(&[]).unwrap();
+        assert!(splitter.is_title("第一章 初入江湖"));
+        assert!(splitter.is_title("  第123节"));
+        assert!(splitter.is_title("第十二卷 风起"));
+        assert!(splitter.is_title("楔子"));
+        assert!(splitter.is_title("Chapter 7 The End"));
+        assert!(!splitter.is_title("他说第一章写得不好，于是又重写了一遍，直到天亮才终于把稿子交给了编辑部的老王"));
+        assert!(!splitter.is_title("第一次见面"));
+        assert!(!splitter.is_title(""));
+    }
+
+    #[test]
+    fn test_split_gbk() {
+        let text = "简介内容\r\n第一章 开始\r\n正文一\r\n\r\n第二章 继续\r\n正文二\r\n";
+        let (bytes, _, _) = GBK.encode(text);
+        let (chapters, encoding) = split_text(&bytes);
+
+        assert_eq!(encoding, GB18030);
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[0].title, "前言");
+        assert_eq!(chapters[0].content, "简介内容");
+        assert_eq!(chapters[1].title, "第一章 开始");
+        assert_eq!(chapters[1].content, "正文一");
+        assert_eq!(chapters[2].content, "正文二");
+    }
+
+    #[test]
+    fn test_split_without_titles() {
+        let (chapters, encoding) = split_text("\u{feff}只有一段\n没有标题".as_bytes());
+        assert_eq!(encoding, UTF_8);
+        assert_eq!(chapters.len(), 1);
+        assert_eq!(chapters[0].title, "正文");
+        assert_eq!(chapters[0].content, "只有一段\n没有标题");
+    }
+
+    #[test]
+    fn test_split_duplicate_titles() {
+        let text = "第一章 重复\n第一章 重复\n内容\n第二章 下一章\n内容二";
+        let (chapters, _) = split_text(text.as_bytes());
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "第一章 重复");
+        assert_eq!(chapters[0].content, "内容");
+        assert_eq!(chapters[1].content, "内容二");
+    }
+
+    #[test]
+    fn test_split_across_chunks() {
+        // 多字节字符与行跨越读取边界
+        let mut text = String::new();
+        for i in 1..=3000 {
+            text.push_str(&format!("第{}章 标题\n这是第{}章的内容，中文字符跨越缓冲区边界。\n", i, i));
+        }
+        let (bytes, _, _) = GBK.encode(&text);
+        assert!(bytes.len() > READ_CHUNK * 2);
+
+        let (chapters, _) = split_text(&bytes);
+        assert_eq!(chapters.len(), 3000);
+        assert_eq!(chapters[2999].title, "第3000章 标题");
+        assert_eq!(chapters[1500].content, "这是第1501章的内容，中文字符跨越缓冲区边界。");
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("《斗破苍穹》作者：天蚕土豆"),
+            ("斗破苍穹".to_string(), "天蚕土豆".to_string())
+        );
+        assert_eq!(parse_file_name("凡人修仙传"), ("凡人修仙传".to_string(), String::new()));
+    }
+}
@@ -0,0 +1,538 @@
+use anyhow::{anyhow, Context, Result};
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::Path;
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+use zip::ZipArchive;
+
+use super::local_book::LocalChapter;
+
+/// 从 EPUB 中读取的书籍
+#[derive(Debug, Clone, Default)]
+pub struct LocalEpub {
+    pub title: String,
+    pub author: String,
+    pub intro: Option<String>,
+    /// 封面图片的资源 URL
+    pub cover_url: Option<String>,
+    pub chapters: Vec<LocalChapter>,
+}
+
+/// 清单条目
+struct ManifestItem {
+    path: String,
+    media_type: String,
+    properties: String,
+}
+
+/// 解析 EPUB
+///
+/// 按 spine 顺序生成章节，标题优先取 NCX / nav 目录；正文转换为阅读器使用的
+/// `<p>` 段落格式，图片解压到 asset_dir 并改写为 `{asset_url_prefix}{包内路径}`。
+pub fn read_epub<R: Read + Seek>(
+    reader: R,
+    asset_dir: &Path,
+    asset_url_prefix: &str,
+) -> Result<LocalEpub> {
+    let mut archive = ZipArchive::new(reader).context("Not a valid EPUB (zip) file")?;
+
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let opf_path = parse_xml(&container, |root| {
+        find_elements(root, "rootfile")
+            .first()
+            .and_then(|e| e.attribute_value("full-path"))
+            .map(|s| s.to_string())
+    })?
+    .ok_or_else(|| anyhow!("EPUB container.xml has no rootfile"))?;
+    let opf_dir = parent_dir(&opf_path);
+
+    let opf = read_entry(&mut archive, &opf_path)?;
+    let package = sxd_document::parser::parse(&strip_doctype(&opf))
+        .map_err(|e| anyhow!("Invalid OPF {}: {:?}", opf_path, e))?;
+    let doc = package.as_document();
+    let root = document_root(&doc).ok_or_else(|| anyhow!("Empty OPF {}", opf_path))?;
+
+    let mut manifest = HashMap::new();
+    for item in find_elements(root, "item") {
+        let (Some(id), Some(href)) = (item.attribute_value("id"), item.attribute_value("href")) else {
+            continue;
+        };
+        manifest.insert(
+            id.to_string(),
+            ManifestItem {
+                path: resolve_path(&opf_dir, href),
+                media_type: item.attribute_value("media-type").unwrap_or_default().to_string(),
+                properties: item.attribute_value("properties").unwrap_or_default().to_string(),
+            },
+        );
+    }
+
+    let spine: Vec<String> = find_elements(root, "itemref")
+        .iter()
+        .filter_map(|e| e.attribute_value("idref"))
+        .filter_map(|id| manifest.get(id))
+        .map(|item| item.path.clone())
+        .collect();
+
+    let metadata_text = |name: &str| {
+        find_elements(root, name)
+            .first()
+            .map(|e| element_text(*e).trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let mut book = LocalEpub {
+        title: metadata_text("title").unwrap_or_default(),
+        author: metadata_text("creator").unwrap_or_default(),
+        intro: metadata_text("description").map(|s| html_to_text(&s)),
+        ..Default::default()
+    };
+
+    // 目录标题：EPUB3 nav 优先，其次 NCX
+    let mut toc_titles = HashMap::new();
+    if let Some(nav) = manifest.values().find(|i| i.properties.split_whitespace().any(|p| p == "nav")) {
+        if let Ok(xhtml) = read_entry(&mut archive, &nav.path) {
+            toc_titles = parse_nav(&xhtml, &parent_dir(&nav.path));
+        }
+    }
+    if toc_titles.is_empty() {
+        if let Some(ncx) = manifest.values().find(|i| i.media_type == "application/x-dtbncx+xml") {
+            if let Ok(xml) = read_entry(&mut archive, &ncx.path) {
+                toc_titles = parse_ncx(&xml, &parent_dir(&ncx.path));
+            }
+        }
+    }
+
+    // 解压图片资源
+    for item in manifest.values().filter(|i| i.media_type.starts_with("image/")) {
+        let Some(dest) = safe_join(asset_dir, &item.path) else {
+            continue;
+        };
+        if let Ok(data) = read_entry_bytes(&mut archive, &item.path) {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&dest, data)?;
+        }
+    }
+    let cover = manifest
+        .values()
+        .find(|i| i.properties.split_whitespace().any(|p| p == "cover-image"))
+        .or_else(|| {
+            find_elements(root, "meta")
+                .iter()
+                .find(|m| m.attribute_value("name") == Some("cover"))
+                .and_then(|m| m.attribute_value("content"))
+                .and_then(|id| manifest.get(id))
+        });
+    book.cover_url = cover.map(|i| asset_url(asset_url_prefix, &i.path));
+
+    for path in spine {
+        let Ok(xhtml) = read_entry(&mut archive, &path) else {
+            tracing::warn!("EPUB spine item missing: {}", path);
+            continue;
+        };
+        let (heading, content) = convert_xhtml(&xhtml, &parent_dir(&path), asset_url_prefix);
+        if content.is_empty() {
+            continue;
+        }
+        let title = toc_titles
+            .get(&path)
+            .cloned()
+            .or(heading)
+            .unwrap_or_else(|| format!("第{}章", book.chapters.len() + 1));
+        book.chapters.push(LocalChapter { title, content });
+    }
+
+    Ok(book)
+}
+
+fn read_entry_bytes<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>> {
+    let mut file = archive
+        .by_name(name)
+        .with_context(|| format!("EPUB entry not found: {}", name))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<String> {
+    let data = read_entry_bytes(archive, name)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// sxd-document 不支持 DOCTYPE，解析前去掉
+fn strip_doctype(xml: &str) -> String {
+    match (xml.find("<!DOCTYPE"), xml.find("<!doctype")) {
+        (Some(start), _) | (None, Some(start)) => match xml[start..].find('>') {
+            Some(end) => format!("{}{}", &xml[..start], &xml[start + end + 1..]),
+            None => xml.to_string(),
+        },
+        _ => xml.to_string(),
+    }
+}
+
+pub(super) fn parse_xml<T>(xml: &str, f: impl FnOnce(Element) -> Option<T>) -> Result<Option<T>> {
+    let package = sxd_document::parser::parse(&strip_doctype(xml))
+        .map_err(|e| anyhow!("Invalid XML: {:?}", e))?;
+    let doc = package.as_document();
+    Ok(document_root(&doc).and_then(f))
+}
+
+fn document_root<'d>(doc: &sxd_document::dom::Document<'d>) -> Option<Element<'d>> {
+    doc.root().children().into_iter().find_map(|c| match c {
+        ChildOfRoot::Element(e) => Some(e),
+        _ => None,
+    })
+}
+
+/// 按本地名 (忽略命名空间) 查找所有后代元素
+pub(super) fn find_elements<'d>(root: Element<'d>, local_name: &str) -> Vec<Element<'d>> {
+    let mut found = Vec::new();
+    let mut stack = vec![root];
+    while let Some(element) = stack.pop() {
+        if element.name().local_part() == local_name {
+            found.push(element);
+        }
+        let children: Vec<_> = element
+            .children()
+            .into_iter()
+            .filter_map(|c| match c {
+                ChildOfElement::Element(e) => Some(e),
+                _ => None,
+            })
+            .collect();
+        stack.extend(children.into_iter().rev());
+    }
+    found
+}
+
+pub(super) fn element_text(element: Element) -> String {
+    let mut text = String::new();
+    for child in element.children() {
+        match child {
+            ChildOfElement::Text(t) => text.push_str(t.text()),
+            ChildOfElement::Element(e) => text.push_str(&element_text(e)),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// NCX 目录：包内路径 -> 标题
+fn parse_ncx(xml: &str, base_dir: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let _ = parse_xml(xml, |root| {
+        for point in find_elements(root, "navPoint") {
+            let label = find_elements(point, "text")
+                .first()
+                .map(|e| element_text(*e).trim().to_string());
+            let src = find_elements(point, "content")
+                .first()
+                .and_then(|e| e.attribute_value("src"));
+            if let (Some(label), Some(src)) = (label, src) {
+                if !label.is_empty() {
+                    titles.entry(resolve_path(base_dir, src)).or_insert(label);
+                }
+            }
+        }
+        Some(())
+    });
+    titles
+}
+
+/// EPUB3 nav 目录：包内路径 -> 标题
+fn parse_nav(xhtml: &str, base_dir: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let html = Html::parse_document(xhtml);
+    let nav_selector = Selector::parse("nav").unwrap();
+    let link_selector = Selector::parse("a[href]").unwrap();
+
+    let navs: Vec<_> = html.select(&nav_selector).collect();
+    let toc_nav = navs
+        .iter()
+        .find(|n| n.value().attr("epub:type") == Some("toc"))
+        .or(navs.first());
+    if let Some(nav) = toc_nav {
+        for link in nav.select(&link_selector) {
+            let label = collapse_whitespace(&link.text().collect::<String>());
+            if let (false, Some(href)) = (label.is_empty(), link.value().attr("href")) {
+                titles.entry(resolve_path(base_dir, href)).or_insert(label);
+            }
+        }
+    }
+    titles
+}
+
+/// 块级元素，结束时另起一段
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "blockquote", "li", "h1", "h2", "h3", "h4", "h5", "h6",
+    "pre", "table", "tr", "ul", "ol", "figure", "header", "footer",
+];
+
+/// 将章节 XHTML 转为 `<p>` 段落，返回 (首个标题, 正文)
+fn convert_xhtml(xhtml: &str, base_dir: &str, asset_url_prefix: &str) -> (Option<String>, String) {
+    let html = Html::parse_document(xhtml);
+    let body_selector = Selector::parse("body").unwrap();
+    let heading_selector = Selector::parse("h1, h2, h3").unwrap();
+
+    let heading = html
+        .select(&heading_selector)
+        .map(|h| collapse_whitespace(&h.text().collect::<String>()))
+        .find(|t| !t.is_empty());
+
+    let mut writer = ParagraphWriter::default();
+    if let Some(body) = html.select(&body_selector).next() {
+        writer.walk(body, base_dir, asset_url_prefix);
+    }
+    writer.end_paragraph();
+    (heading, writer.paragraphs.join("\n"))
+}
+
+#[derive(Default)]
+struct ParagraphWriter {
+    paragraphs: Vec<String>,
+    current: String,
+}
+
+impl ParagraphWriter {
+    fn walk(&mut self, element: ElementRef, base_dir: &str, asset_url_prefix: &str) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(raw) => {
+                    let text = collapse_whitespace(raw);
+                    if !text.is_empty() {
+                        // 保留原文中相邻文本之间的空白，中文内联标签之间不额外加空格
+                        let spaced = raw.starts_with(char::is_whitespace);
+                        if spaced && !self.current.is_empty() && !self.current.ends_with('>') {
+                            self.current.push(' ');
+                        }
+                        self.current.push_str(&html_escape::encode_text(&text));
+                    }
+                }
+                Node::Element(e) => {
+                    let name = e.name();
+                    match name {
+                        "br" => self.current.push_str("<br>"),
+                        "img" | "image" => {
+                            let src = e
+                                .attr("src")
+                                .or_else(|| e.attr("xlink:href"))
+                                .or_else(|| e.attr("href"));
+                            if let Some(src) = src {
+                                let url = asset_url(asset_url_prefix, &resolve_path(base_dir, src));
+                                self.current.push_str(&format!(
+                                    "<img src=\"{}\">",
+                                    html_escape::encode_double_quoted_attribute(&url)
+                                ));
+                            }
+                        }
+                        "script" | "style" | "head" | "title" => {}
+                        _ => {
+                            let Some(child_ref) = ElementRef::wrap(child) else {
+                                continue;
+                            };
+                            let is_block = BLOCK_TAGS.contains(&name);
+                            if is_block {
+                                self.end_paragraph();
+                            }
+                            self.walk(child_ref, base_dir, asset_url_prefix);
+                            if is_block {
+                                self.end_paragraph();
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        let paragraph = std::mem::take(&mut self.current);
+        let trimmed = paragraph.trim_end_matches("<br>").trim();
+        if !trimmed.is_empty() {
+            self.paragraphs.push(format!("<p>{}</p>", trimmed));
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn html_to_text(html: &str) -> String {
+    let doc = Html::parse_fragment(html);
+    collapse_whitespace(&doc.root_element().text().collect::<Vec<_>>().join(" "))
+}
+
+fn parent_dir(path: &str) -> String {
+    match path.rfind('/') {
+        Some(pos) => path[..=pos].to_string(),
+        None => String::new(),
+    }
+}
+
+/// 解析包内相对路径 (去掉锚点、解码百分号、处理 `..`)
+fn resolve_path(base_dir: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or(href);
+    let href = urlencoding::decode(href)
+        .map(|s| s.into_owned())
+        .unwrap_or_else(|_| href.to_string());
+
+    let mut parts: Vec<&str> = if href.starts_with('/') {
+        Vec::new()
+    } else {
+        base_dir.split('/').filter(|s| !s.is_empty()).collect()
+    };
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            s => parts.push(s),
+        }
+    }
+    parts.join("/")
+}
+
+fn asset_url(prefix: &str, path: &str) -> String {
+    let encoded: Vec<_> = path.split('/').map(|s| urlencoding::encode(s).into_owned()).collect();
+    format!("{}{}", prefix, encoded.join("/"))
+}
+
+/// 拼接资源路径，拒绝越出资源目录的路径
+pub fn safe_join(dir: &Path, relative: &str) -> Option<std::path::PathBuf> {
+    let relative = Path::new(relative);
+    let safe = !relative.as_os_str().is_empty()
+        && relative
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+    safe.then(|| dir.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn fixture_epub() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let files: &[(&str, &[u8])] = &[
+            ("mimetype", b"application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>测试 EPUB</dc:title>
+    <dc:creator>作者甲</dc:creator>
+    <meta name="cover" content="cover"/>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="cover" href="Images/cover.jpg" media-type="image/jpeg"/>
+    <item id="pic" href="Images/插图 1.png" media-type="image/png"/>
+    <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="Text/c2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c3" href="Text/c3.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="c2"/>
+    <itemref idref="c1"/>
+    <itemref idref="c3"/>
+  </spine>
+</package>"#
+                    .as_bytes(),
+            ),
+            (
+                "OEBPS/toc.ncx",
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE ncx PUBLIC "-//NISO//DTD ncx 2005-1//EN" "http://www.daisy.org/z3986/2005/ncx-2005-1.dtd">
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1"><navLabel><text>第一章 起点</text></navLabel><content src="Text/c1.xhtml"/></navPoint>
+    <navPoint id="n2"><navLabel><text>序章</text></navLabel><content src="Text/c2.xhtml#top"/></navPoint>
+  </navMap>
+</ncx>"#
+                    .as_bytes(),
+            ),
+            (
+                "OEBPS/Text/c1.xhtml",
+                r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>c1</title></head><body>
+<h1>第一章 起点</h1>
+<p>第一段&nbsp;文字</p>
+<div><img src="../Images/%E6%8F%92%E5%9B%BE%201.png" alt=""/></div>
+<p>第二行<br/>换行</p>
+</body></html>"#
+                    .as_bytes(),
+            ),
+            (
+                "OEBPS/Text/c2.xhtml",
+                r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>序章内容</p></body></html>"#.as_bytes(),
+            ),
+            (
+                "OEBPS/Text/c3.xhtml",
+                r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><h2>尾声</h2><p>结束 &lt;完&gt;</p></body></html>"#
+                    .as_bytes(),
+            ),
+            ("OEBPS/Images/cover.jpg", &[0xFF, 0xD8, 0xFF, 0xE0]),
+            ("OEBPS/Images/插图 1.png", b"\x89PNG"),
+        ];
+        for (name, data) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_epub_chapters() {
+        let asset_dir = Path::new("/tmp/reader_tests_local_epub/assets");
+        let _ = std::fs::remove_dir_all(asset_dir);
+        let book = read_epub(Cursor::new(fixture_epub()), asset_dir, "/reader3/assets/b1/").unwrap();
+
+        assert_eq!(book.title, "测试 EPUB");
+        assert_eq!(book.author, "作者甲");
+        assert_eq!(book.cover_url.as_deref(), Some("/reader3/assets/b1/OEBPS/Images/cover.jpg"));
+
+        // spine 顺序，标题来自 NCX，缺失时取正文标题
+        let titles: Vec<_> = book.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["序章", "第一章 起点", "尾声"]);
+        assert_eq!(book.chapters[0].content, "<p>序章内容</p>");
+        assert_eq!(book.chapters[2].content, "<p>尾声</p>\n<p>结束 &lt;完&gt;</p>");
+    }
+
+    #[test]
+    fn test_read_epub_rewrites_images() {
+        let asset_dir = Path::new("/tmp/reader_tests_local_epub/images");
+        let _ = std::fs::remove_dir_all(asset_dir);
+        let book = read_epub(Cursor::new(fixture_epub()), asset_dir, "/reader3/assets/b1/").unwrap();
+
+        let content = &book.chapters[1].content;
+        assert!(content.contains("<p>第一段\u{a0}文字</p>") || content.contains("<p>第一段 文字</p>"));
+        assert!(content.contains(
+            r#"<p><img src="/reader3/assets/b1/OEBPS/Images/%E6%8F%92%E5%9B%BE%201.png"></p>"#
+        ));
+        assert!(content.contains("<p>第二行<br>换行</p>"));
+        assert!(asset_dir.join("OEBPS/Images/插图 1.png").exists());
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(resolve_path("OEBPS/Text/", "../Images/a.jpg#x"), "OEBPS/Images/a.jpg");
+        assert_eq!(resolve_path("", "Text/a%20b.xhtml"), "Text/a b.xhtml");
+        assert!(safe_join(Path::new("/tmp"), "../etc/passwd").is_none());
+    }
+}
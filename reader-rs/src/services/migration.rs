@@ -1,11 +1,77 @@
-use std::path::Path;
-use tokio::fs;
 use anyhow::Result;
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
 
-use crate::models::{Book, BookSourceFull, ReplaceRule, BookGroup};
+use super::local_book::is_local_book;
+use super::{AppState, ServiceError};
+use crate::models::{Book, BookGroup, BookProgress, ReplaceRule};
+use crate::storage::bookshelf::{BookshelfStore, LEGACY_BOOKSHELF_FILE};
 use crate::storage::FileStorage;
 
+/// 迁移为按书存放后，旧版单文件书架的备份文件名
+pub const LEGACY_BOOKSHELF_BACKUP: &str = "bookshelf.legacy.json";
+
+/// reader3 用户目录下的数据文件
+const LEGACY_SHELF_FILE: &str = "bookshelf.json";
+const LEGACY_SOURCES_FILE: &str = "bookSource.json";
+const LEGACY_RULES_FILE: &str = "replaceRule.json";
+const LEGACY_GROUPS_FILE: &str = "bookGroup.json";
+/// reader3 单用户模式的用户目录名
+const LEGACY_DEFAULT_USER: &str = "default";
+/// reader3 本地书籍的 origin
+const LEGACY_LOCAL_ORIGIN: &str = "loc_book";
+/// 小于该值的时间戳按秒处理 (1973 年之后的毫秒时间戳都大于该值)
+const SECONDS_TIMESTAMP_LIMIT: i64 = 100_000_000_000;
+
+/// 旧版数据导入报告
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyImportReport {
+    /// 读取的旧版用户目录
+    pub user_dir: String,
+    pub groups: ImportCategory,
+    pub sources: ImportCategory,
+    pub replace_rules: ImportCategory,
+    pub books: ImportCategory,
+    /// 书架上已有书籍的阅读进度，以及新导入书籍带有的进度
+    pub progress: ImportCategory,
+}
+
+/// 单类数据的导入结果
+#[derive(Debug, Default, Serialize)]
+pub struct ImportCategory {
+    pub imported: usize,
+    pub skipped: Vec<ImportIssue>,
+    pub failed: Vec<ImportIssue>,
+}
+
+/// 跳过或失败的条目及原因
+#[derive(Debug, Serialize)]
+pub struct ImportIssue {
+    /// 书名、书源名、规则名等
+    pub item: String,
+    pub reason: String,
+}
+
+impl ImportCategory {
+    fn skip(&mut self, item: impl Into<String>, reason: impl Into<String>) {
+        self.skipped.push(ImportIssue {
+            item: item.into(),
+            reason: reason.into(),
+        });
+    }
+
+    fn fail(&mut self, item: impl Into<String>, reason: impl Into<String>) {
+        self.failed.push(ImportIssue {
+            item: item.into(),
+            reason: reason.into(),
+        });
+    }
+}
+
 /// 数据迁移工具 - 从旧版 Kotlin 后端迁移数据
 pub struct Migration {
     storage: FileStorage,
@@ -18,81 +84,89 @@ impl Migration {
         }
     }
 
-    /// 从旧版存储目录迁移所有数据
-    pub async fn migrate_from_legacy(&self, legacy_path: &str) -> Result<MigrationResult> {
-        let mut result = MigrationResult::default();
-
-        // 迁移书源
-        let sources_path = format!("{}/bookSource.json", legacy_path);
-        if Path::new(&sources_path).exists() {
-            let count = self.migrate_sources(&sources_path).await?;
-            result.sources_migrated = count;
-        }
+    pub fn with_storage(storage: FileStorage) -> Self {
+        Self { storage }
+    }
 
-        // 迁移书架
-        let books_path = format!("{}/bookshelf.json", legacy_path);
-        if Path::new(&books_path).exists() {
-            let count = self.migrate_books(&books_path).await?;
-            result.books_migrated = count;
+    /// 将单文件书架 `bookshelf.json` 迁移为按书存放的格式，返回迁移的书籍数
+    ///
+    /// 旧文件重命名为 `bookshelf.legacy.json` 保留。恢复备份会重新写入 `bookshelf.json`，
+    /// 此时其内容整体替换现有书架。没有单文件书架时返回 None。
+    pub async fn migrate_bookshelf(&self) -> Result<Option<usize>> {
+        if !self.storage.exists(LEGACY_BOOKSHELF_FILE).await {
+            return Ok(None);
         }
+        // read_json 负责从 .bak 恢复损坏的文件
+        let raw: Value = self.storage.read_json(LEGACY_BOOKSHELF_FILE).await?;
+        let books = Self::parse_books(&serde_json::to_vec(&raw)?)?;
+        BookshelfStore::new(self.storage.clone()).replace_all(&books).await?;
 
-        // 迁移替换规则
-        let rules_path = format!("{}/replaceRule.json", legacy_path);
-        if Path::new(&rules_path).exists() {
-            let count = self.migrate_replace_rules(&rules_path).await?;
-            result.rules_migrated = count;
-        }
+        // 新格式写入完成后再移走旧文件，中途失败时下次启动会重新迁移
+        fs::rename(
+            self.storage.file_path(LEGACY_BOOKSHELF_FILE),
+            self.storage.file_path(LEGACY_BOOKSHELF_BACKUP),
+        )
+        .await?;
+        let _ = fs::remove_file(self.storage.file_path(&format!("{}.bak", LEGACY_BOOKSHELF_FILE))).await;
+        tracing::info!("Migrated {} books to per-book bookshelf storage", books.len());
+        Ok(Some(books.len()))
+    }
 
-        // 迁移分组
-        let groups_path = format!("{}/bookGroup.json", legacy_path);
-        if Path::new(&groups_path).exists() {
-            let count = self.migrate_groups(&groups_path).await?;
-            result.groups_migrated = count;
-        }
+    /// 从旧版 Kotlin reader3 的存储目录导入数据，经由各服务写入
+    ///
+    /// `path` 为 reader3 的 storage 目录 (读取其中 data/{user}，缺省为 default 用户) 或用户目录本身。
+    /// 已有的分组、书源、替换规则与书籍不会被覆盖，可重复执行；书架上已有的书籍只在旧版进度
+    /// 更新时同步进度。目录与正文缓存不导入，之后按需重新获取。
+    pub async fn import_legacy(state: &AppState, path: &str, user: Option<&str>) -> Result<LegacyImportReport> {
+        let dir = legacy_user_dir(Path::new(path), user)?;
+        let mut report = LegacyImportReport {
+            user_dir: dir.display().to_string(),
+            ..Default::default()
+        };
 
-        Ok(result)
-    }
+        // 分组先导入，书籍的分组位按新分配的位转换
+        let group_bits = import_legacy_groups(state, &dir, &mut report.groups).await?;
+        import_legacy_sources(state, &dir, &mut report.sources).await?;
+        import_legacy_rules(state, &dir, &mut report.replace_rules).await?;
+        import_legacy_books(state, &dir, &group_bits, &mut report).await?;
 
-    /// 迁移书源
-    async fn migrate_sources(&self, path: &str) -> Result<usize> {
-        let content = fs::read_to_string(path).await?;
-        let sources: Vec<BookSourceFull> = serde_json::from_str(&content)?;
-        let count = sources.len();
-        self.storage.write_json("bookSources.json", &sources).await?;
-        tracing::info!("Migrated {} book sources", count);
-        Ok(count)
+        tracing::info!(
+            "Imported reader3 data from {}: {} groups, {} sources, {} replace rules, {} books",
+            report.user_dir,
+            report.groups.imported,
+            report.sources.imported,
+            report.replace_rules.imported,
+            report.books.imported
+        );
+        Ok(report)
     }
 
-    /// 迁移书架
-    async fn migrate_books(&self, path: &str) -> Result<usize> {
-        let content = fs::read_to_string(path).await?;
-        
-        // 尝试解析为标准格式
-        let books: Vec<Book> = match serde_json::from_str(&content) {
-            Ok(b) => b,
-            Err(_) => {
-                // 尝试兼容旧版格式
-                let raw: Vec<Value> = serde_json::from_str(&content)?;
-                raw.into_iter()
-                    .filter_map(|v| self.convert_legacy_book(v).ok())
-                    .collect()
-            }
-        };
-        
-        let count = books.len();
-        self.storage.write_json("bookshelf.json", &books).await?;
-        tracing::info!("Migrated {} books", count);
-        Ok(count)
+    /// 解析书架数据，标准格式解析失败时按旧版格式逐本转换
+    pub fn parse_books(content: &[u8]) -> Result<Vec<Book>> {
+        if let Ok(books) = serde_json::from_slice(content) {
+            return Ok(books);
+        }
+        let raw: Vec<Value> = serde_json::from_slice(content)?;
+        Ok(raw
+            .into_iter()
+            .filter_map(|v| Self::convert_legacy_book(v).ok())
+            .collect())
     }
 
-    /// 转换旧版书籍格式
-    fn convert_legacy_book(&self, value: Value) -> Result<Book> {
+    /// 转换旧版书籍格式 (时间戳统一为毫秒)
+    fn convert_legacy_book(value: Value) -> Result<Book> {
+        if value["bookUrl"].as_str().is_none_or(str::is_empty) {
+            anyhow::bail!("Missing bookUrl");
+        }
         Ok(Book {
             book_url: value["bookUrl"].as_str().unwrap_or_default().to_string(),
             name: value["name"].as_str().unwrap_or_default().to_string(),
             author: value["author"].as_str().unwrap_or_default().to_string(),
             cover_url: value["coverUrl"].as_str().map(|s| s.to_string()),
             custom_cover_url: value["customCoverUrl"].as_str().map(|s| s.to_string()),
+            custom_name: None,
+            custom_author: None,
+            custom_intro: value["customIntro"].as_str().map(|s| s.to_string()),
             toc_url: value["tocUrl"].as_str().map(|s| s.to_string()),
             origin: value["origin"].as_str().map(|s| s.to_string()),
             origin_name: value["originName"].as_str().map(|s| s.to_string()),
@@ -102,48 +176,309 @@ impl Migration {
             group: value["group"].as_i64(),
             dur_chapter_index: value["durChapterIndex"].as_i64().map(|i| i as i32),
             dur_chapter_pos: value["durChapterPos"].as_i64().map(|i| i as i32),
-            dur_chapter_time: value["durChapterTime"].as_i64(),
+            dur_chapter_time: legacy_millis(&value["durChapterTime"]),
             dur_chapter_title: value["durChapterTitle"].as_str().map(|s| s.to_string()),
             total_chapter_num: value["totalChapterNum"].as_i64().map(|i| i as i32),
             latest_chapter_title: value["latestChapterTitle"].as_str().map(|s| s.to_string()),
+            latest_chapter_time: legacy_millis(&value["latestChapterTime"]),
             can_update: value["canUpdate"].as_bool(),
+            last_check_time: legacy_millis(&value["lastCheckTime"]),
+            last_update_error: None,
+            has_new_chapter: None,
+            variable: value["variable"].as_str().map(|s| s.to_string()),
+            formatting: None,
         })
     }
+}
+
+/// 定位 reader3 用户目录：`root` 本身，或其下的 data/{user}、storage/data/{user}
+fn legacy_user_dir(root: &Path, user: Option<&str>) -> Result<PathBuf, ServiceError> {
+    let is_user_dir = |dir: &Path| {
+        [LEGACY_SHELF_FILE, LEGACY_SOURCES_FILE]
+            .iter()
+            .any(|f| dir.join(f).is_file())
+    };
+    let user = user.map(str::trim).filter(|u| !u.is_empty());
+    match user {
+        Some(user) => {
+            let mut components = Path::new(user).components();
+            if !matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            ) {
+                return Err(ServiceError::invalid_input(format!("Invalid legacy user: {}", user)));
+            }
+        }
+        None if is_user_dir(root) => return Ok(root.to_path_buf()),
+        None => {}
+    }
+    let user = user.unwrap_or(LEGACY_DEFAULT_USER);
+    ["data", "storage/data"]
+        .iter()
+        .map(|data| root.join(data).join(user))
+        .find(|dir| is_user_dir(dir))
+        .ok_or_else(|| ServiceError::not_found("Legacy user directory", format!("{} ({})", root.display(), user)))
+}
+
+/// 读取旧版数据文件中的数组；文件不存在时为空，无法读取或解析时记为失败
+async fn read_legacy_items(dir: &Path, name: &str, category: &mut ImportCategory) -> Vec<Value> {
+    let content = match fs::read(dir.join(name)).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            category.fail(name, e.to_string());
+            return Vec::new();
+        }
+    };
+    let text = String::from_utf8_lossy(&content);
+    match serde_json::from_str(text.trim_start_matches('\u{feff}')) {
+        Ok(items) => items,
+        Err(e) => {
+            category.fail(name, format!("Invalid JSON: {}", e));
+            Vec::new()
+        }
+    }
+}
+
+/// 旧版时间戳转为毫秒 (reader3 部分字段以秒记录)，0 或缺失时为 None
+fn legacy_millis(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str()?.trim().parse().ok())
+        .filter(|t| *t > 0)
+        .map(|t| if t < SECONDS_TIMESTAMP_LIMIT { t * 1000 } else { t })
+}
+
+/// 导入自定义分组，同名分组沿用已有分组；返回旧分组位到新分组位的映射
+async fn import_legacy_groups(
+    state: &AppState,
+    dir: &Path,
+    category: &mut ImportCategory,
+) -> Result<HashMap<i64, i64>> {
+    let mut existing = state.group_service.get_all_groups().await?;
+    let mut bits = HashMap::new();
+    for value in read_legacy_items(dir, LEGACY_GROUPS_FILE, category).await {
+        let name = value["groupName"].as_str().unwrap_or_default().trim().to_string();
+        let id = value["groupId"].as_i64().unwrap_or(0);
+        if id < 0 {
+            category.skip(name, "Built-in group");
+            continue;
+        }
+        if id.count_ones() != 1 {
+            category.fail(name, format!("Invalid group id: {}", id));
+            continue;
+        }
+        if let Some(group) = existing.iter().find(|g| g.group_name == name) {
+            bits.insert(id, group.group_id);
+            category.skip(name, "Group already exists");
+            continue;
+        }
+        let group = BookGroup {
+            group_id: 0,
+            group_name: name.clone(),
+            order: value["order"].as_i64().unwrap_or(0) as i32,
+            show: value["show"].as_bool().unwrap_or(true),
+        };
+        match state.group_service.save_group(group).await {
+            Ok(saved) => {
+                bits.insert(id, saved.group_id);
+                existing.push(saved);
+                category.imported += 1;
+            }
+            Err(e) => category.fail(name, format!("{:#}", e)),
+        }
+    }
+    Ok(bits)
+}
+
+/// 按映射转换书籍的分组位掩码，没有对应分组的位被丢弃
+fn convert_group_bits(group: i64, bits: &HashMap<i64, i64>) -> i64 {
+    bits.iter()
+        .filter(|(legacy, _)| group & **legacy != 0)
+        .fold(0, |acc, (_, new)| acc | new)
+}
+
+/// 导入书源，已有的书源 (按 bookSourceUrl) 保持不变
+async fn import_legacy_sources(state: &AppState, dir: &Path, category: &mut ImportCategory) -> Result<()> {
+    let existing: HashSet<String> = state
+        .source_service
+        .get_all_sources()
+        .await?
+        .into_iter()
+        .map(|s| s.book_source_url)
+        .collect();
+    let label = |value: &Value| {
+        value["bookSourceName"]
+            .as_str()
+            .or(value["bookSourceUrl"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
 
-    /// 迁移替换规则
-    async fn migrate_replace_rules(&self, path: &str) -> Result<usize> {
-        let content = fs::read_to_string(path).await?;
-        let rules: Vec<ReplaceRule> = serde_json::from_str(&content)?;
-        let count = rules.len();
-        self.storage.write_json("replaceRules.json", &rules).await?;
-        tracing::info!("Migrated {} replace rules", count);
-        Ok(count)
+    let mut new_sources = Vec::new();
+    for value in read_legacy_items(dir, LEGACY_SOURCES_FILE, category).await {
+        if value["bookSourceUrl"]
+            .as_str()
+            .is_some_and(|url| existing.contains(url))
+        {
+            category.skip(label(&value), "Source already exists");
+        } else {
+            new_sources.push(value);
+        }
+    }
+    if new_sources.is_empty() {
+        return Ok(());
     }
 
-    /// 迁移分组
-    async fn migrate_groups(&self, path: &str) -> Result<usize> {
-        let content = fs::read_to_string(path).await?;
-        let groups: Vec<BookGroup> = serde_json::from_str(&content)?;
-        let count = groups.len();
-        self.storage.write_json("bookGroups.json", &groups).await?;
-        tracing::info!("Migrated {} book groups", count);
-        Ok(count)
+    let report = state
+        .source_service
+        .import_sources(&serde_json::to_string(&new_sources)?, false)
+        .await?;
+    for invalid in &report.invalid {
+        category.fail(label(&new_sources[invalid.index]), invalid.reason.clone());
     }
+    category.imported += report.accepted();
+    Ok(())
 }
 
-/// 迁移结果
-#[derive(Default)]
-pub struct MigrationResult {
-    pub sources_migrated: usize,
-    pub books_migrated: usize,
-    pub rules_migrated: usize,
-    pub groups_migrated: usize,
+/// 导入替换规则，与已有规则相同 (模式、替换内容、作用范围一致) 时跳过
+async fn import_legacy_rules(state: &AppState, dir: &Path, category: &mut ImportCategory) -> Result<()> {
+    let mut existing = state.replace_service.get_all_rules().await?;
+    for value in read_legacy_items(dir, LEGACY_RULES_FILE, category).await {
+        let rule = match convert_legacy_rule(&value) {
+            Ok(rule) => rule,
+            Err(reason) => {
+                category.fail(value["name"].as_str().unwrap_or_default(), reason);
+                continue;
+            }
+        };
+        let same = |r: &ReplaceRule| {
+            r.pattern == rule.pattern
+                && r.replacement == rule.replacement
+                && r.is_regex == rule.is_regex
+                && r.scope == rule.scope
+        };
+        if existing.iter().any(same) {
+            category.skip(rule.name, "Replace rule already exists");
+            continue;
+        }
+        let name = rule.name.clone();
+        match state.replace_service.save_rule(rule).await {
+            Ok(saved) => {
+                existing.push(saved);
+                category.imported += 1;
+            }
+            Err(e) => category.fail(name, format!("{:#}", e)),
+        }
+    }
+    Ok(())
 }
 
-impl MigrationResult {
-    pub fn total(&self) -> usize {
-        self.sources_migrated + self.books_migrated + self.rules_migrated + self.groups_migrated
+/// 转换旧版替换规则 (兼容更早的 enable / serialNumber 字段，isRegex 缺省为 true)
+fn convert_legacy_rule(value: &Value) -> Result<ReplaceRule, String> {
+    let pattern = value["pattern"].as_str().unwrap_or_default();
+    if pattern.is_empty() {
+        return Err("Missing pattern".to_string());
     }
+    let text = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+    Ok(ReplaceRule {
+        id: None,
+        name: Some(text("name"))
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| pattern.to_string()),
+        pattern: pattern.to_string(),
+        replacement: text("replacement"),
+        scope: text("scope"),
+        is_enabled: value["isEnabled"]
+            .as_bool()
+            .or(value["enable"].as_bool())
+            .unwrap_or(true),
+        is_regex: value["isRegex"].as_bool().unwrap_or(true),
+        group: value["group"].as_str().map(str::to_string),
+        scope_title: value["scopeTitle"].as_bool().unwrap_or(false),
+        order: value["order"].as_i64().or(value["serialNumber"].as_i64()).unwrap_or(0) as i32,
+    })
+}
+
+/// 导入书架：新书连同进度加入书架，已有的书只在旧版进度更新时同步进度
+async fn import_legacy_books(
+    state: &AppState,
+    dir: &Path,
+    group_bits: &HashMap<i64, i64>,
+    report: &mut LegacyImportReport,
+) -> Result<()> {
+    let mut shelf: HashMap<String, Book> = state
+        .book_service
+        .get_bookshelf(false)
+        .await?
+        .into_iter()
+        .map(|b| (b.book_url.clone(), b))
+        .collect();
+
+    for value in read_legacy_items(dir, LEGACY_SHELF_FILE, &mut report.books).await {
+        let label = value["name"]
+            .as_str()
+            .or(value["bookUrl"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let mut book = match Migration::convert_legacy_book(value) {
+            Ok(book) => book,
+            Err(e) => {
+                report.books.fail(label, e.to_string());
+                continue;
+            }
+        };
+        if book.origin.as_deref() == Some(LEGACY_LOCAL_ORIGIN) || is_local_book(&book.book_url) {
+            report.books.skip(label, "Local book files are not migrated");
+            continue;
+        }
+        if let Some(local) = shelf.get(&book.book_url) {
+            report.books.skip(label.clone(), "Book already on bookshelf");
+            import_legacy_progress(state, local, &book, &mut report.progress).await?;
+            continue;
+        }
+
+        book.group = book
+            .group
+            .map(|g| convert_group_bits(g, group_bits))
+            .filter(|g| *g != 0);
+        match state.book_service.save_book(book).await {
+            Ok(saved) => {
+                report.books.imported += 1;
+                if saved.dur_chapter_index.is_some() {
+                    report.progress.imported += 1;
+                }
+                shelf.insert(saved.book_url.clone(), saved);
+            }
+            Err(e) => report.books.fail(label, format!("{:#}", e)),
+        }
+    }
+    Ok(())
+}
+
+/// 旧版进度比书架上的进度更新时写入，不会用旧进度覆盖新进度
+async fn import_legacy_progress(
+    state: &AppState,
+    local: &Book,
+    legacy: &Book,
+    category: &mut ImportCategory,
+) -> Result<()> {
+    if legacy.dur_chapter_index.is_none() {
+        return Ok(());
+    }
+    let legacy_time = legacy.dur_chapter_time.unwrap_or(0);
+    if legacy_time <= 0 {
+        category.skip(&legacy.name, "Legacy progress has no timestamp");
+    } else if local.dur_chapter_time.unwrap_or(0) >= legacy_time {
+        category.skip(&legacy.name, "Local progress is newer");
+    } else {
+        state
+            .book_service
+            .save_progress(&local.book_url, BookProgress::from_book(legacy))
+            .await?;
+        category.imported += 1;
+    }
+    Ok(())
 }
 
 impl Default for Migration {
@@ -151,3 +486,129 @@ impl Default for Migration {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migrate_single_file_bookshelf() {
+        let dir = std::env::temp_dir().join(format!("reader_tests_migrate_shelf_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = FileStorage::new(&dir).with_sync(false);
+        let fixture = format!("{}/tests/fixtures/bookshelf/legacy_bookshelf.json", env!("CARGO_MANIFEST_DIR"));
+        let legacy = std::fs::read_to_string(fixture).unwrap();
+        storage.write_file(LEGACY_BOOKSHELF_FILE, &legacy).await.unwrap();
+
+        let migration = Migration::with_storage(storage.clone());
+        assert_eq!(migration.migrate_bookshelf().await.unwrap(), Some(3));
+        // 旧文件保留为备份，不会再次迁移
+        assert!(!storage.exists(LEGACY_BOOKSHELF_FILE).await);
+        assert_eq!(storage.read_file(LEGACY_BOOKSHELF_BACKUP).await.unwrap(), legacy);
+        assert_eq!(migration.migrate_bookshelf().await.unwrap(), None);
+
+        let books = BookshelfStore::new(storage.clone()).load().await.unwrap();
+        let expected: Vec<Book> = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(
+            serde_json::to_value(&books).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+        assert_eq!(books[0].dur_chapter_pos, Some(340));
+
+        let index: Value = storage.read_json("bookshelf/index.json").await.unwrap();
+        assert_eq!(index[0]["id"], BookshelfStore::book_id("https://a.com/book/1"));
+        assert_eq!(index[0]["name"], "诡秘之主");
+        assert_eq!(index[0]["group"], 3);
+        assert_eq!(index.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_import_reader3_storage() {
+        let dir = "/tmp/reader_tests_migrate_reader3";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = AppState::with_storage_dir(dir);
+        let legacy = format!("{}/tests/fixtures/reader3/storage", env!("CARGO_MANIFEST_DIR"));
+
+        // 本地已有的分组、书源、规则与书籍
+        let follow = BookGroup {
+            group_id: 0,
+            group_name: "追更".to_string(),
+            order: 0,
+            show: true,
+        };
+        let follow = state.group_service.save_group(follow).await.unwrap().group_id;
+        let source = r#"{"bookSourceUrl":"https://a.com","bookSourceName":"本地甲站"}"#;
+        state.source_service.save_source(source).await.unwrap();
+        let rule = convert_legacy_rule(&serde_json::json!({ "name": "本地去广告", "pattern": "本站网址.*" })).unwrap();
+        state.replace_service.save_rule(rule).await.unwrap();
+        for (url, time) in [
+            ("https://a.com/book/1", 1_650_000_000_000),
+            ("https://b.com/book/2", 1_600_000_000_000),
+        ] {
+            let book = Book {
+                book_url: url.to_string(),
+                name: url.to_string(),
+                dur_chapter_index: Some(1),
+                dur_chapter_time: Some(time),
+                ..Default::default()
+            };
+            state.book_service.save_book(book).await.unwrap();
+        }
+
+        assert!(Migration::import_legacy(&state, &legacy, Some("../etc")).await.is_err());
+        assert!(Migration::import_legacy(&state, &legacy, Some("alice")).await.is_err());
+        let report = Migration::import_legacy(&state, &legacy, None).await.unwrap();
+        assert!(report.user_dir.ends_with("data/default"));
+        let items = |issues: &[ImportIssue]| issues.iter().map(|i| i.item.clone()).collect::<Vec<_>>();
+
+        assert_eq!(report.groups.imported, 2);
+        assert_eq!(items(&report.groups.skipped), vec!["全部", "追更"]);
+        assert_eq!(items(&report.groups.failed), vec!["坏分组"]);
+        assert_eq!(report.sources.imported, 1);
+        assert_eq!(items(&report.sources.skipped), vec!["甲站"]);
+        assert_eq!(items(&report.sources.failed), vec!["缺少地址"]);
+        assert_eq!(report.replace_rules.imported, 1);
+        assert_eq!(items(&report.replace_rules.skipped), vec!["去广告"]);
+        assert_eq!(items(&report.replace_rules.failed), vec!["空规则"]);
+        assert_eq!(report.books.imported, 1);
+        assert_eq!(items(&report.books.skipped), vec!["诡秘之主", "雪中悍刀行", "三体"]);
+        assert_eq!(report.books.failed.len(), 1);
+        // 新书带有进度；已有书籍只接受更新的进度
+        assert_eq!(report.progress.imported, 2);
+        assert_eq!(items(&report.progress.skipped), vec!["诡秘之主"]);
+
+        let shelf = state.book_service.get_bookshelf(false).await.unwrap();
+        let book = |url: &str| shelf.iter().find(|b| b.book_url == url).unwrap().clone();
+        assert_eq!(book("https://a.com/book/1").dur_chapter_index, Some(1));
+        let updated = book("https://b.com/book/2");
+        assert_eq!(updated.dur_chapter_index, Some(88));
+        assert_eq!(updated.dur_chapter_time, Some(1_700_000_000_000));
+
+        let groups = state.group_service.get_all_groups().await.unwrap();
+        let group_id = |name: &str| groups.iter().find(|g| g.group_name == name).unwrap().group_id;
+        assert_eq!(group_id("追更"), follow);
+        assert!(!groups.iter().find(|g| g.group_name == "养肥").unwrap().show);
+        let imported = book("https://c.com/book/3");
+        assert_eq!(imported.group, Some(group_id("完结") | group_id("养肥")));
+        assert_eq!(imported.latest_chapter_time, Some(1_710_000_000_000));
+        assert_eq!(imported.last_check_time, Some(1_710_000_123_000));
+
+        let rules = state.replace_service.get_all_rules().await.unwrap();
+        let legacy_rule = rules.iter().find(|r| r.name == "旧字段").unwrap();
+        assert!(!legacy_rule.is_enabled && legacy_rule.is_regex);
+        assert_eq!(legacy_rule.order, 5);
+
+        // 再次导入不会重复
+        let again = Migration::import_legacy(&state, &legacy, None).await.unwrap();
+        let imported = [
+            &again.groups,
+            &again.sources,
+            &again.replace_rules,
+            &again.books,
+            &again.progress,
+        ];
+        assert!(imported.iter().all(|c| c.imported == 0));
+        assert_eq!(state.book_service.get_bookshelf(false).await.unwrap().len(), 3);
+        assert_eq!(state.source_service.get_all_sources().await.unwrap().len(), 2);
+    }
+}
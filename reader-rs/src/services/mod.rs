@@ -1,41 +1,352 @@
+mod backup;
 mod book;
+mod bookshelf;
+mod cache_book;
+mod change_source;
+mod content_filter;
+mod epub;
+mod local_book;
+mod local_epub;
 mod source;
+mod source_import;
 mod replace;
 mod group;
 mod http;
+mod jobs;
 mod migration;
+mod opds;
+mod prefetch;
+mod reading_stats;
+mod request_budget;
+mod search_filter;
+mod search_merge;
+mod shelf_export;
+mod shutdown;
+mod source_health;
+mod source_stats;
+mod source_test;
+mod text_diff;
+pub mod tts;
+mod user;
 
-pub use book::BookService;
-pub use source::SourceService;
+pub use backup::{BackupService, DataImportSummary, WebdavConfig};
+pub use book::{BookService, ChapterDiff};
+pub use bookshelf::{RefreshSummary, ShelfQuery, ShelfSort, GROUP_ALL};
+pub use cache_book::{BookCacher, CacheBookProgress};
+pub use change_source::{ChangeSourceEvent, ChangeSourceQuery, SourceCandidate};
+pub use content_filter::ContentFilterService;
+pub use source::{DebugSourceRequest, SourceLoginInfo, SourceService, SourceVariable, ValidateRuleRequest};
+pub use source_import::{decode_payload, fetch_remote_sources, ImportReport};
 pub use replace::ReplaceService;
-pub use group::GroupService;
-pub use migration::Migration;
+pub use group::{GroupOrderItem, GroupService};
+pub use jobs::{JobScheduler, JobStatus};
+pub use migration::{LegacyImportReport, Migration};
+pub use opds::{
+    find_group, BookPage, OpdsCatalog, ACQUISITION_FEED_TYPE, ENTRY_TYPE, NAVIGATION_FEED_TYPE, OPENSEARCH_TYPE,
+};
+pub use prefetch::{PrefetchStatus, Prefetcher};
+pub use reading_stats::{Heartbeat, HistoryEntry, ReadingStats, ReadingStatsService};
+pub use request_budget::RequestBudgets;
+pub use search_filter::SearchFilter;
+pub use search_merge::{MergedSearch, SearchOrigin};
+pub use shutdown::SHUTDOWN_TIMEOUT;
+pub use source_health::{SourceFailure, SourceHealthReport};
+pub use source_stats::SourceStatInfo;
+pub use source_test::SourceTestOptions;
+pub use tts::TtsService;
+pub(crate) use user::constant_time_eq;
+pub use user::{UserConfig, UserServices, TOKEN_TTL};
 
+use crate::engine::anti_ban::Cooldowns;
+use crate::engine::engine_cache::EngineCache;
 use crate::engine::search_engine::SearchEngine;
+use crate::storage::kv::KvStore;
+use crate::storage::FileStorage;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// 书源变量与 java.put 缓存的存储文件名
+pub const KV_FILE: &str = "kv_store.json";
+
+/// 过期缓存清理间隔
+const KV_PURGE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 书架自动更新检查的默认间隔 (分钟)
+const DEFAULT_BOOKSHELF_REFRESH_MINUTES: u64 = 60;
+
+/// 自动更新检查时可直接复用的目录页缓存时长
+const REFRESH_TOC_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// 检查书源订阅是否到期需要自动更新的间隔
+const SUBSCRIPTION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 书架自动更新检查间隔，由环境变量 BOOKSHELF_REFRESH_MINUTES 配置，0 表示关闭
+fn bookshelf_refresh_interval() -> Option<Duration> {
+    let minutes = std::env::var("BOOKSHELF_REFRESH_MINUTES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_BOOKSHELF_REFRESH_MINUTES);
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+/// 服务层错误
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("{kind} not found: {key}")]
+    NotFound { kind: &'static str, key: String },
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Source disabled: {0}")]
+    SourceDisabled(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+}
+
+impl ServiceError {
+    pub fn not_found(kind: &'static str, key: impl Into<String>) -> Self {
+        Self::NotFound {
+            kind,
+            key: key.into(),
+        }
+    }
+
+    pub fn invalid_input(msg: impl Into<String>) -> Self {
+        Self::InvalidInput(msg.into())
+    }
+
+    pub fn source_disabled(source_url: impl Into<String>) -> Self {
+        Self::SourceDisabled(source_url.into())
+    }
+
+    pub fn unauthorized(msg: impl Into<String>) -> Self {
+        Self::Unauthorized(msg.into())
+    }
+}
 
 /// 应用全局状态
 pub struct AppState {
     pub book_service: BookService,
     pub source_service: SourceService,
     pub replace_service: ReplaceService,
+    pub content_filter_service: ContentFilterService,
     pub group_service: GroupService,
+    pub backup_service: BackupService,
+    pub tts_service: TtsService,
+    pub reading_stats: ReadingStatsService,
+    /// 阅读后预取后续章节
+    pub prefetcher: Prefetcher,
+    /// 缓存整本书的后台任务
+    pub book_cacher: BookCacher,
     pub search_engine: Arc<SearchEngine>,
+    pub kv_store: Arc<KvStore>,
+    /// 按书源复用的书源引擎，请求使用其分叉
+    pub engines: Arc<EngineCache>,
+    pub storage: FileStorage,
+    /// 多用户模式下各用户的服务；单用户模式为 None
+    pub users: Option<Arc<UserServices>>,
+    /// 后台任务，退出时取消
+    pub jobs: Arc<JobScheduler>,
+    /// 各接口的请求时间预算
+    pub budgets: RequestBudgets,
+    /// 被限流 (429) 后暂停请求的书源，由书源引擎写入
+    pub cooldowns: Arc<Cooldowns>,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let storage_dir = "./storage"; // TODO: Configure this via env or config
+        Self::with_config("./storage", &UserConfig::from_env()) // TODO: Configure this via env or config
+    }
+
+    /// 使用指定存储目录构建全部服务
+    pub fn with_storage_dir(storage_dir: &str) -> Self {
+        Self::build(storage_dir, None)
+    }
+
+    /// 构建全部服务；多用户模式下各用户的数据位于 storage/users/{name}
+    pub fn with_config(storage_dir: &str, config: &UserConfig) -> Self {
+        let mut state = Self::build(storage_dir, None);
+        if config.multi_user {
+            state.users = Some(Arc::new(UserServices::new(storage_dir, config, &state.source_service)));
+        }
+        state
+    }
+
+    /// 使用指定存储目录构建全部服务，`source_service` 为多个用户共享的书源服务
+    fn build(storage_dir: &str, source_service: Option<SourceService>) -> Self {
+        let storage = FileStorage::new(storage_dir);
         let search_engine = Arc::new(SearchEngine::new(storage_dir).expect("Failed to initialize search engine"));
-        
+
+        let replace_service = ReplaceService::with_storage(storage.clone());
+        let content_filter_service = ContentFilterService::with_storage(storage.clone());
+        let kv_store = Arc::new(KvStore::new(storage.clone(), KV_FILE));
+
+        let engines = Arc::new(EngineCache::from_env(kv_store.clone()));
+        let source_service = source_service.unwrap_or_else(|| {
+            SourceService::with_storage(storage.clone(), kv_store.clone()).with_engines(engines.clone())
+        });
+
+        let jobs = Arc::new(JobScheduler::new());
+        let cooldowns = source_service.stats().cooldowns();
+        let book_service = BookService::with_storage(
+            storage.clone(),
+            engines.clone(),
+            search_engine.clone(),
+            replace_service.clone(),
+            content_filter_service.clone(),
+            source_service.shared_sources(),
+            source_service.stats(),
+        );
+
         Self {
-            book_service: BookService::new(search_engine.clone()),
-            source_service: SourceService::new(),
-            replace_service: ReplaceService::new(),
-            group_service: GroupService::new(),
+            prefetcher: Prefetcher::new(book_service.clone(), jobs.clone()),
+            book_cacher: BookCacher::new(book_service.clone(), jobs.clone()),
+            group_service: GroupService::with_storage(storage.clone(), book_service.clone()),
+            book_service,
+            source_service,
+            replace_service,
+            content_filter_service,
+            backup_service: BackupService::with_storage(storage.clone()),
+            tts_service: TtsService::with_storage(storage.clone()),
+            reading_stats: ReadingStatsService::with_storage(storage.clone()),
             search_engine,
+            kv_store,
+            engines,
+            storage,
+            users: None,
+            jobs,
+            budgets: RequestBudgets::from_env(),
+            cooldowns,
         }
     }
+
+    /// 加载 KV 存储，在后台写盘，并定期清理过期缓存 (需在 tokio 运行时中调用)
+    pub fn spawn_kv_maintenance(&self) {
+        let kv_store = self.kv_store.clone();
+        self.jobs.spawn_named("kv load", async move { kv_store.load().await });
+        if let Some(flusher) = self.kv_store.take_flusher() {
+            self.jobs.spawn_named("kv flush", async move {
+                flusher.await;
+                Ok(())
+            });
+        }
+        let kv_store = self.kv_store.clone();
+        self.jobs.spawn_periodic("kv purge", KV_PURGE_INTERVAL, move || {
+            let kv_store = kv_store.clone();
+            async move {
+                // 清理后由 KV 存储的后台任务写盘
+                let purged = kv_store.purge_expired();
+                if purged > 0 {
+                    tracing::debug!("Purged {} expired cache entries", purged);
+                }
+                Ok(())
+            }
+        });
+    }
+
+    /// 定期检查书架书籍的更新 (需在 tokio 运行时中调用)
+    ///
+    /// 启动后等待一个周期再开始，避免与启动加载争抢。
+    pub fn spawn_bookshelf_refresher(&self) {
+        let Some(period) = bookshelf_refresh_interval() else {
+            tracing::info!("Bookshelf auto refresh disabled");
+            return;
+        };
+        let book_service = self.book_service.clone();
+        self.jobs.spawn_periodic("bookshelf refresh", period, move || {
+            let book_service = book_service.clone();
+            async move {
+                let summary = book_service.refresh_bookshelf(None, Some(REFRESH_TOC_MAX_AGE)).await?;
+                tracing::info!(
+                    "Bookshelf refreshed: {} checked, {} updated, {} failed",
+                    summary.checked,
+                    summary.updated,
+                    summary.failed
+                );
+                Ok(())
+            }
+        });
+    }
+
+    /// 每小时检查一次，更新超过一天未更新的自动更新订阅 (需在 tokio 运行时中调用)
+    ///
+    /// 按订阅的上次更新时间判断是否到期，服务每天重启也不会错过更新。
+    pub fn spawn_subscription_refresher(&self) {
+        let source_service = self.source_service.clone();
+        self.jobs.spawn_periodic("source subscriptions", SUBSCRIPTION_CHECK_INTERVAL, move || {
+            let source_service = source_service.clone();
+            async move {
+                let now = chrono::Utc::now().timestamp_millis();
+                let refreshed = source_service.refresh_auto_subscriptions(now).await;
+                if refreshed > 0 {
+                    tracing::info!("Refreshed {} source subscriptions", refreshed);
+                }
+                Ok(())
+            }
+        });
+    }
+
+    /// 按 SOURCE_HEALTH_CHECKS_PER_HOUR 的速率轮流检查书源健康状态 (需在 tokio 运行时中调用)
+    pub fn spawn_source_health_monitor(&self) {
+        let Some(period) = source_health::check_interval() else {
+            tracing::info!("Source health monitor disabled");
+            return;
+        };
+        let source_service = self.source_service.clone();
+        self.jobs.spawn_periodic("source health", period, move || {
+            let source_service = source_service.clone();
+            async move {
+                if let Some(card) = source_service.check_next_source_health().await? {
+                    tracing::debug!(
+                        "Health check of {}: {}",
+                        card.book_source_name,
+                        if card.passed { "passed" } else { "failed" }
+                    );
+                }
+                Ok(())
+            }
+        });
+    }
+
+    /// 退出前取消后台任务，并在 SHUTDOWN_TIMEOUT 内写入各服务的状态
+    ///
+    /// 多用户模式下先写入各用户的数据。返回未能写入的步骤。
+    pub async fn shutdown(&self) -> Vec<&'static str> {
+        let mut failed = match &self.users {
+            Some(users) => users.shutdown().await,
+            None => Vec::new(),
+        };
+        failed.extend(self.shutdown_services().await);
+        if !failed.is_empty() {
+            tracing::error!("Exiting without flushing: {}", failed.join(", "));
+        }
+        failed
+    }
+
+    /// 取消本组服务的后台任务并写入状态
+    ///
+    /// 书源统计先进入防抖写入，因此文件存储最后落盘。
+    async fn shutdown_services(&self) -> Vec<&'static str> {
+        self.jobs.shutdown(SHUTDOWN_TIMEOUT).await;
+
+        let mut steps = shutdown::ShutdownSteps::new(SHUTDOWN_TIMEOUT);
+        steps.run("source stats and cookies", &self.source_service).await;
+        steps.run("KV store", &*self.kv_store).await;
+        steps.run("pending file writes", &self.storage).await;
+        steps.finish()
+    }
+
+    /// 数据文件被整体替换 (如恢复备份) 后，重新加载各服务的内存缓存
+    pub async fn reload_data(&self) -> anyhow::Result<()> {
+        self.book_service.init().await?;
+        self.source_service.init().await?;
+        self.replace_service.reload().await;
+        self.content_filter_service.reload().await;
+        self.group_service.reload().await;
+        self.reading_stats.reload().await;
+        self.kv_store.load().await?;
+        self.engines.clear();
+        Ok(())
+    }
 }
 
 impl Default for AppState {
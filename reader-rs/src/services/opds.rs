@@ -0,0 +1,605 @@
+//! OPDS 1.2 目录：书架分组导航、分组书籍列表、单本书条目与 OpenSearch 描述
+//!
+//! 链接均为以接口前缀 (如 `/reader3`) 开头的绝对路径，封面走封面代理，
+//! 获取链接指向 EPUB 导出接口。
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use std::fmt::Write;
+
+use super::bookshelf::{matches_group, GROUP_ALL};
+use crate::models::{Book, BookGroup};
+use crate::storage::cover_cache::is_local_cover;
+
+/// 导航目录的内容类型
+pub const NAVIGATION_FEED_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+/// 书籍列表的内容类型
+pub const ACQUISITION_FEED_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+/// 单本书条目的内容类型
+pub const ENTRY_TYPE: &str = "application/atom+xml;type=entry;profile=opds-catalog";
+/// OpenSearch 描述文档的内容类型
+pub const OPENSEARCH_TYPE: &str = "application/opensearchdescription+xml";
+/// 书籍列表每页的条目数
+pub const OPDS_PAGE_SIZE: usize = 50;
+
+const ATOM_NAMESPACES: &str = r#"xmlns="http://www.w3.org/2005/Atom" xmlns:opds="http://opds-spec.org/2010/catalog" xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/""#;
+const REL_ACQUISITION: &str = "http://opds-spec.org/acquisition";
+const REL_IMAGE: &str = "http://opds-spec.org/image";
+const REL_THUMBNAIL: &str = "http://opds-spec.org/image/thumbnail";
+
+/// 目录中的链接
+#[derive(Debug, Clone)]
+struct Link {
+    rel: &'static str,
+    href: String,
+    kind: Option<&'static str>,
+}
+
+impl Link {
+    fn new(rel: &'static str, href: String, kind: &'static str) -> Self {
+        Self {
+            rel,
+            href,
+            kind: Some(kind),
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: &str) {
+        let _ = write!(
+            out,
+            r#"{}<link rel="{}" href="{}""#,
+            indent,
+            self.rel,
+            escape_attr(&self.href)
+        );
+        if let Some(kind) = self.kind {
+            let _ = write!(out, r#" type="{}""#, kind);
+        }
+        out.push_str("/>\n");
+    }
+}
+
+/// 一页书籍列表
+#[derive(Debug, Clone)]
+pub struct BookPage {
+    /// 从 1 开始的页码
+    pub page: usize,
+    /// 全部页的书籍总数
+    pub total: usize,
+    pub books: Vec<Book>,
+}
+
+impl BookPage {
+    /// 取第 `page` 页 (从 1 开始)
+    pub fn paginate(books: Vec<Book>, page: usize) -> Self {
+        let page = page.max(1);
+        let total = books.len();
+        let books = books
+            .into_iter()
+            .skip((page - 1) * OPDS_PAGE_SIZE)
+            .take(OPDS_PAGE_SIZE)
+            .collect();
+        Self { page, total, books }
+    }
+
+    fn has_next(&self) -> bool {
+        self.page * OPDS_PAGE_SIZE < self.total
+    }
+}
+
+/// 生成 OPDS 文档，`base` 为接口前缀 (如 `/reader3`)
+pub struct OpdsCatalog {
+    base: String,
+    now: DateTime<Utc>,
+}
+
+impl OpdsCatalog {
+    pub fn new(base: &str) -> Self {
+        Self::at(base, Utc::now())
+    }
+
+    fn at(base: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            base: base.trim_end_matches('/').to_string(),
+            now,
+        }
+    }
+
+    fn root_href(&self) -> String {
+        format!("{}/opds", self.base)
+    }
+
+    /// 分组书籍列表的地址
+    pub fn group_href(&self, group: i64, page: usize) -> String {
+        let href = format!("{}/opds/books?group={}", self.base, group);
+        with_page(href, page)
+    }
+
+    /// 书架搜索结果的地址
+    pub fn search_href(&self, key: &str, page: usize) -> String {
+        let href = format!("{}/opds/search?q={}", self.base, urlencoding::encode(key));
+        with_page(href, page)
+    }
+
+    fn opensearch_href(&self) -> String {
+        format!("{}/opds/opensearch.xml", self.base)
+    }
+
+    /// 根目录：列出显示中的书架分组及其书籍数量
+    pub fn root(&self, groups: &[BookGroup], books: &[Book]) -> String {
+        let mut groups: Vec<&BookGroup> = groups.iter().filter(|g| g.show).collect();
+        groups.sort_by_key(|g| g.order);
+        let all = all_books_group();
+        if !groups.iter().any(|g| g.group_id == GROUP_ALL) {
+            groups.insert(0, &all);
+        }
+
+        let mut out = self.feed_start(
+            "urn:reader:opds",
+            "书架",
+            Link::new("self", self.root_href(), NAVIGATION_FEED_TYPE),
+            &[],
+        );
+        for group in groups {
+            let count = books.iter().filter(|b| matches_group(b, group.group_id)).count();
+            out.push_str("  <entry>\n");
+            write_element(&mut out, "    ", "title", &group.group_name);
+            write_element(
+                &mut out,
+                "    ",
+                "id",
+                &format!("urn:reader:opds:group:{}", group.group_id),
+            );
+            write_element(&mut out, "    ", "updated", &timestamp(self.now));
+            let _ = writeln!(out, r#"    <content type="text">{} 本</content>"#, count);
+            Link::new("subsection", self.group_href(group.group_id, 1), ACQUISITION_FEED_TYPE).write(&mut out, "    ");
+            out.push_str("  </entry>\n");
+        }
+        out.push_str("</feed>\n");
+        out
+    }
+
+    /// 一个分组的书籍列表
+    pub fn group(&self, group: &BookGroup, page: &BookPage) -> String {
+        let id = format!("urn:reader:opds:group:{}", group.group_id);
+        self.books_feed(&id, &group.group_name, page, |page| {
+            self.group_href(group.group_id, page)
+        })
+    }
+
+    /// 书架搜索结果
+    pub fn search(&self, key: &str, page: &BookPage) -> String {
+        let id = format!("urn:reader:opds:search:{}", urlencoding::encode(key));
+        let title = format!("搜索：{}", key);
+        self.books_feed(&id, &title, page, |page| self.search_href(key, page))
+    }
+
+    /// 单本书的完整条目
+    pub fn entry(&self, book: &Book) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        self.write_book(&mut out, book, Some(ATOM_NAMESPACES));
+        out
+    }
+
+    /// OpenSearch 描述，搜索书架
+    pub fn opensearch(&self) -> String {
+        let template = format!("{}/opds/search?q={{searchTerms}}", self.base);
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>书架</ShortName>
+  <Description>搜索书架中的书名、作者与简介</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <OutputEncoding>UTF-8</OutputEncoding>
+  <Url type="{}" template="{}"/>
+</OpenSearchDescription>
+"#,
+            escape_attr(ACQUISITION_FEED_TYPE),
+            escape_attr(&template)
+        )
+    }
+
+    fn books_feed(&self, id: &str, title: &str, page: &BookPage, href: impl Fn(usize) -> String) -> String {
+        let mut links = vec![
+            Link::new("up", self.root_href(), NAVIGATION_FEED_TYPE),
+            Link::new("first", href(1), ACQUISITION_FEED_TYPE),
+        ];
+        if page.page > 1 {
+            links.push(Link::new("previous", href(page.page - 1), ACQUISITION_FEED_TYPE));
+        }
+        if page.has_next() {
+            links.push(Link::new("next", href(page.page + 1), ACQUISITION_FEED_TYPE));
+        }
+
+        let mut out = self.feed_start(
+            id,
+            title,
+            Link::new("self", href(page.page), ACQUISITION_FEED_TYPE),
+            &links,
+        );
+        let _ = writeln!(
+            out,
+            "  <opensearch:totalResults>{}</opensearch:totalResults>",
+            page.total
+        );
+        let _ = writeln!(
+            out,
+            "  <opensearch:itemsPerPage>{}</opensearch:itemsPerPage>",
+            OPDS_PAGE_SIZE
+        );
+        let _ = writeln!(
+            out,
+            "  <opensearch:startIndex>{}</opensearch:startIndex>",
+            (page.page - 1) * OPDS_PAGE_SIZE + 1
+        );
+        for book in &page.books {
+            self.write_book(&mut out, book, None);
+        }
+        out.push_str("</feed>\n");
+        out
+    }
+
+    /// 文档开头到 `<entry>` 之前：标题、更新时间与 self / start / search 链接
+    fn feed_start(&self, id: &str, title: &str, self_link: Link, links: &[Link]) -> String {
+        let mut out = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed {}>\n",
+            ATOM_NAMESPACES
+        );
+        write_element(&mut out, "  ", "id", id);
+        write_element(&mut out, "  ", "title", title);
+        write_element(&mut out, "  ", "updated", &timestamp(self.now));
+        out.push_str("  <author><name>reader</name></author>\n");
+        self_link.write(&mut out, "  ");
+        Link::new("start", self.root_href(), NAVIGATION_FEED_TYPE).write(&mut out, "  ");
+        Link::new("search", self.opensearch_href(), OPENSEARCH_TYPE).write(&mut out, "  ");
+        for link in links {
+            link.write(&mut out, "  ");
+        }
+        out
+    }
+
+    /// 书籍条目；`namespaces` 非空时作为独立文档的根元素
+    fn write_book(&self, out: &mut String, book: &Book, namespaces: Option<&str>) {
+        let indent = if namespaces.is_some() { "  " } else { "    " };
+        let close = &indent[2..];
+        match namespaces {
+            Some(ns) => {
+                let _ = writeln!(out, "<entry {}>", ns);
+            }
+            None => out.push_str("  <entry>\n"),
+        }
+        write_element(out, indent, "title", &book.name);
+        write_element(
+            out,
+            indent,
+            "id",
+            &format!("urn:reader:book:{:x}", md5::compute(&book.book_url)),
+        );
+        write_element(out, indent, "updated", &timestamp(self.book_updated(book)));
+        if !book.author.trim().is_empty() {
+            let _ = writeln!(
+                out,
+                "{}<author><name>{}</name></author>",
+                indent,
+                escape_text(book.author.trim())
+            );
+        }
+        if let Some(intro) = book.intro.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            let _ = writeln!(
+                out,
+                r#"{}<summary type="text">{}</summary>"#,
+                indent,
+                escape_text(intro)
+            );
+        }
+        let cover = book.custom_cover_url.as_deref().or(book.cover_url.as_deref());
+        let proxied = |url: &&str| url.starts_with("http://") || url.starts_with("https://") || is_local_cover(url);
+        if let Some(cover) = cover.filter(proxied) {
+            let mut href = format!("{}/cover?path={}", self.base, urlencoding::encode(cover));
+            if let Some(origin) = book.origin.as_deref().filter(|o| !o.is_empty()) {
+                let _ = write!(href, "&bookSourceUrl={}", urlencoding::encode(origin));
+            }
+            for rel in [REL_IMAGE, REL_THUMBNAIL] {
+                Link {
+                    rel,
+                    href: href.clone(),
+                    kind: None,
+                }
+                .write(out, indent);
+            }
+        }
+        let url = urlencoding::encode(&book.book_url);
+        Link::new("alternate", format!("{}/opds/book?url={}", self.base, url), ENTRY_TYPE).write(out, indent);
+        Link::new(
+            REL_ACQUISITION,
+            format!("{}/exportBook?url={}", self.base, url),
+            "application/epub+zip",
+        )
+        .write(out, indent);
+        let _ = writeln!(out, "{}</entry>", close);
+    }
+
+    /// 最新章节时间，其次最近阅读时间
+    fn book_updated(&self, book: &Book) -> DateTime<Utc> {
+        book.latest_chapter_time
+            .or(book.dur_chapter_time)
+            .filter(|&ms| ms > 0)
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+            .unwrap_or(self.now)
+    }
+}
+
+/// 按 ID 查找分组，未保存的“全部书籍”分组也能找到
+pub fn find_group(groups: Vec<BookGroup>, group_id: i64) -> Option<BookGroup> {
+    groups
+        .into_iter()
+        .find(|g| g.group_id == group_id)
+        .or_else(|| (group_id == GROUP_ALL).then(all_books_group))
+}
+
+fn all_books_group() -> BookGroup {
+    BookGroup {
+        group_id: GROUP_ALL,
+        group_name: "全部书籍".to_string(),
+        order: 0,
+        show: true,
+    }
+}
+
+fn with_page(href: String, page: usize) -> String {
+    if page > 1 {
+        format!("{}&page={}", href, page)
+    } else {
+        href
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn write_element(out: &mut String, indent: &str, name: &str, text: &str) {
+    let _ = writeln!(out, "{}<{}>{}</{}>", indent, name, escape_text(text), name);
+}
+
+/// 去掉 XML 1.0 不允许的控制字符
+fn xml_chars(s: &str) -> String {
+    s.chars()
+        .filter(|&c| matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}'))
+        .collect()
+}
+
+fn escape_text(s: &str) -> String {
+    html_escape::encode_text(&xml_chars(s)).to_string()
+}
+
+fn escape_attr(s: &str) -> String {
+    html_escape::encode_double_quoted_attribute(&xml_chars(s)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    /// 元素路径 (如 `feed/entry/title`) 与属性，按文档顺序
+    #[derive(Debug)]
+    struct Element {
+        path: String,
+        attrs: Vec<(String, String)>,
+        text: String,
+    }
+
+    impl Element {
+        fn attr(&self, name: &str) -> Option<&str> {
+            self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// 解析 XML，格式错误或标签不匹配时 panic
+    fn parse(xml: &str) -> Vec<Element> {
+        let mut reader = Reader::from_str(xml);
+        let mut stack: Vec<String> = Vec::new();
+        let mut elements: Vec<Element> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) => {
+                    stack.push(String::from_utf8(e.name().as_ref().to_vec()).unwrap());
+                    open.push(elements.len());
+                    elements.push(element(&stack, &e));
+                }
+                Event::Empty(e) => {
+                    stack.push(String::from_utf8(e.name().as_ref().to_vec()).unwrap());
+                    elements.push(element(&stack, &e));
+                    stack.pop();
+                }
+                Event::Text(t) => {
+                    if let Some(&i) = open.last() {
+                        elements[i].text.push_str(&t.unescape().unwrap());
+                    }
+                }
+                Event::End(_) => {
+                    stack.pop();
+                    open.pop();
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert!(stack.is_empty());
+        elements
+    }
+
+    fn element(stack: &[String], e: &quick_xml::events::BytesStart) -> Element {
+        let attrs = e
+            .attributes()
+            .map(|a| {
+                let a = a.unwrap();
+                (
+                    String::from_utf8(a.key.as_ref().to_vec()).unwrap(),
+                    a.unescape_value().unwrap().to_string(),
+                )
+            })
+            .collect();
+        Element {
+            path: stack.join("/"),
+            attrs,
+            text: String::new(),
+        }
+    }
+
+    fn links<'a>(elements: &'a [Element], path: &str) -> Vec<(&'a str, &'a str)> {
+        elements
+            .iter()
+            .filter(|e| e.path == path)
+            .map(|e| (e.attr("rel").unwrap(), e.attr("href").unwrap()))
+            .collect()
+    }
+
+    fn text<'a>(elements: &'a [Element], path: &str) -> Vec<&'a str> {
+        elements
+            .iter()
+            .filter(|e| e.path == path)
+            .map(|e| e.text.as_str())
+            .collect()
+    }
+
+    fn book(n: usize) -> Book {
+        Book {
+            book_url: format!("https://example.com/book/{}?a=1&b=2", n),
+            name: format!("书 <{}> & 续", n),
+            author: "作者".to_string(),
+            cover_url: Some(format!("https://img.example.com/{}.jpg", n)),
+            origin: Some("https://source.example.com".to_string()),
+            intro: Some("简介\u{0}含控制字符".to_string()),
+            group: Some(if n.is_multiple_of(2) { 1 } else { 2 }),
+            latest_chapter_time: Some(1_700_000_000_000),
+            ..Default::default()
+        }
+    }
+
+    fn catalog() -> OpdsCatalog {
+        OpdsCatalog::at("/reader3/", Utc.timestamp_millis_opt(1_600_000_000_000).unwrap())
+    }
+
+    #[test]
+    fn test_root_navigation_feed() {
+        let groups = vec![
+            BookGroup {
+                group_id: 2,
+                group_name: "完结".to_string(),
+                order: 2,
+                show: true,
+            },
+            BookGroup {
+                group_id: 1,
+                group_name: "追更".to_string(),
+                order: 1,
+                show: true,
+            },
+            BookGroup {
+                group_id: 4,
+                group_name: "隐藏".to_string(),
+                order: 3,
+                show: false,
+            },
+        ];
+        let books: Vec<Book> = (0..3).map(book).collect();
+        let xml = catalog().root(&groups, &books);
+        let elements = parse(&xml);
+
+        assert_eq!(text(&elements, "feed/id"), vec!["urn:reader:opds"]);
+        assert_eq!(text(&elements, "feed/updated"), vec!["2020-09-13T12:26:40Z"]);
+        let feed_links = links(&elements, "feed/link");
+        assert!(feed_links.contains(&("self", "/reader3/opds")));
+        assert!(feed_links.contains(&("start", "/reader3/opds")));
+        assert!(feed_links.contains(&("search", "/reader3/opds/opensearch.xml")));
+
+        assert_eq!(text(&elements, "feed/entry/title"), vec!["全部书籍", "追更", "完结"]);
+        assert_eq!(text(&elements, "feed/entry/content"), vec!["3 本", "2 本", "1 本"]);
+        assert_eq!(
+            links(&elements, "feed/entry/link"),
+            vec![
+                ("subsection", "/reader3/opds/books?group=-1"),
+                ("subsection", "/reader3/opds/books?group=1"),
+                ("subsection", "/reader3/opds/books?group=2"),
+            ]
+        );
+        for entry_link in elements.iter().filter(|e| e.path == "feed/entry/link") {
+            assert_eq!(entry_link.attr("type"), Some(ACQUISITION_FEED_TYPE));
+        }
+    }
+
+    #[test]
+    fn test_acquisition_feed_paging() {
+        let group = find_group(Vec::new(), GROUP_ALL).unwrap();
+        let books: Vec<Book> = (0..OPDS_PAGE_SIZE + 3).map(book).collect();
+
+        let first = parse(&catalog().group(&group, &BookPage::paginate(books.clone(), 1)));
+        assert_eq!(text(&first, "feed/entry/title").len(), OPDS_PAGE_SIZE);
+        assert_eq!(text(&first, "feed/opensearch:totalResults"), vec!["53"]);
+        let feed_links = links(&first, "feed/link");
+        assert!(feed_links.contains(&("next", "/reader3/opds/books?group=-1&page=2")));
+        assert!(feed_links.contains(&("up", "/reader3/opds")));
+        assert!(!feed_links.iter().any(|(rel, _)| *rel == "previous"));
+
+        let last = parse(&catalog().group(&group, &BookPage::paginate(books, 2)));
+        assert_eq!(text(&last, "feed/entry/title").len(), 3);
+        let feed_links = links(&last, "feed/link");
+        assert!(feed_links.contains(&("self", "/reader3/opds/books?group=-1&page=2")));
+        assert!(feed_links.contains(&("previous", "/reader3/opds/books?group=-1")));
+        assert!(!feed_links.iter().any(|(rel, _)| *rel == "next"));
+
+        // 条目：转义后的书名、作者、更新时间、封面代理与 EPUB 导出链接
+        let entry = &book(OPDS_PAGE_SIZE);
+        assert_eq!(text(&last, "feed/entry/title")[0], entry.name);
+        assert_eq!(text(&last, "feed/entry/author/name")[0], "作者");
+        assert_eq!(text(&last, "feed/entry/updated")[0], "2023-11-14T22:13:20Z");
+        assert_eq!(text(&last, "feed/entry/summary")[0], "简介含控制字符");
+        let entry_links: Vec<_> = links(&last, "feed/entry/link").into_iter().take(4).collect();
+        let url = urlencoding::encode(&entry.book_url).into_owned();
+        assert_eq!(
+            entry_links,
+            vec![
+                (
+                    REL_IMAGE,
+                    "/reader3/cover?path=https%3A%2F%2Fimg.example.com%2F50.jpg&bookSourceUrl=https%3A%2F%2Fsource.example.com"
+                ),
+                (
+                    REL_THUMBNAIL,
+                    "/reader3/cover?path=https%3A%2F%2Fimg.example.com%2F50.jpg&bookSourceUrl=https%3A%2F%2Fsource.example.com"
+                ),
+                ("alternate", format!("/reader3/opds/book?url={}", url).as_str()),
+                (REL_ACQUISITION, format!("/reader3/exportBook?url={}", url).as_str()),
+            ]
+        );
+        let acquisition = last
+            .iter()
+            .find(|e| e.path == "feed/entry/link" && e.attr("rel") == Some(REL_ACQUISITION))
+            .unwrap();
+        assert_eq!(acquisition.attr("type"), Some("application/epub+zip"));
+    }
+
+    #[test]
+    fn test_entry_and_opensearch() {
+        let elements = parse(&catalog().entry(&book(1)));
+        assert_eq!(elements[0].path, "entry");
+        assert_eq!(elements[0].attr("xmlns"), Some("http://www.w3.org/2005/Atom"));
+        assert_eq!(text(&elements, "entry/title"), vec!["书 <1> & 续"]);
+        assert!(links(&elements, "entry/link")
+            .iter()
+            .any(|(rel, _)| *rel == REL_ACQUISITION));
+
+        let elements = parse(&catalog().opensearch());
+        let url = elements.iter().find(|e| e.path == "OpenSearchDescription/Url").unwrap();
+        assert_eq!(url.attr("template"), Some("/reader3/opds/search?q={searchTerms}"));
+        assert_eq!(url.attr("type"), Some(ACQUISITION_FEED_TYPE));
+
+        let page = BookPage::paginate(vec![book(1)], 1);
+        let elements = parse(&catalog().search("书 & 作者", &page));
+        assert!(links(&elements, "feed/link")
+            .contains(&("self", "/reader3/opds/search?q=%E4%B9%A6%20%26%20%E4%BD%9C%E8%80%85")));
+        assert_eq!(text(&elements, "feed/entry/title"), vec!["书 <1> & 续"]);
+    }
+}
@@ -0,0 +1,177 @@
+//! 阅读章节后在后台预取后续章节，翻页时直接命中正文缓存
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::{BookService, JobScheduler};
+
+/// 默认预取的章节数
+const DEFAULT_PREFETCH_CHAPTERS: usize = 3;
+
+/// 预取章节数，由环境变量 CONTENT_PREFETCH_CHAPTERS 配置，0 表示关闭
+fn prefetch_chapters() -> usize {
+    std::env::var("CONTENT_PREFETCH_CHAPTERS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PREFETCH_CHAPTERS)
+}
+
+/// 一个后续章节的缓存情况
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrefetchChapter {
+    pub index: i32,
+    pub cached: bool,
+}
+
+/// 预取进度
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchStatus {
+    pub book_url: String,
+    /// 最近阅读章节之后的 N 章
+    pub chapters: Vec<PrefetchChapter>,
+    /// 仍有章节在排队或抓取中
+    pub running: bool,
+}
+
+/// 章节预取队列，同一时间只预取正在阅读的一本书
+pub struct Prefetcher {
+    book_service: BookService,
+    jobs: Arc<JobScheduler>,
+    count: usize,
+    job: Mutex<Option<Job>>,
+}
+
+/// 一本书的预取任务
+struct Job {
+    book_url: String,
+    /// 最近阅读的章节
+    last_index: i32,
+    /// 已排队过的章节，快速翻页时不重复预取
+    seen: HashSet<i32>,
+    queue: Arc<Mutex<JobQueue>>,
+    cancel: CancellationToken,
+}
+
+#[derive(Default)]
+struct JobQueue {
+    pending: VecDeque<i32>,
+    /// 后台线程正在处理队列
+    running: bool,
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Prefetcher {
+    pub fn new(book_service: BookService, jobs: Arc<JobScheduler>) -> Self {
+        Self::with_count(book_service, jobs, prefetch_chapters())
+    }
+
+    pub fn with_count(book_service: BookService, jobs: Arc<JobScheduler>, count: usize) -> Self {
+        Self {
+            book_service,
+            jobs,
+            count,
+            job: Mutex::new(None),
+        }
+    }
+
+    /// 读取第 index 章后预取之后的 N 章，换书时取消之前的预取 (需在 tokio 运行时中调用)
+    ///
+    /// 预取在后台进行，失败只记录日志。
+    pub fn schedule(&self, book_url: &str, index: i32) {
+        if self.count == 0 {
+            return;
+        }
+        let mut guard = self.job.lock();
+        if guard.as_ref().is_none_or(|job| job.book_url != book_url) {
+            *guard = Some(Job {
+                book_url: book_url.to_string(),
+                last_index: index,
+                seen: HashSet::new(),
+                queue: Arc::new(Mutex::new(JobQueue::default())),
+                // 退出时随任务调度器一同取消
+                cancel: self.jobs.child_token(),
+            });
+        }
+        let Some(job) = guard.as_mut() else {
+            return;
+        };
+        job.last_index = index;
+
+        let mut queue = job.queue.lock();
+        for next in index + 1..=index + self.count as i32 {
+            if job.seen.insert(next) {
+                queue.pending.push_back(next);
+            }
+        }
+        if !queue.pending.is_empty() && !queue.running {
+            queue.running = true;
+            self.spawn_worker(job);
+        }
+    }
+
+    /// 取消该书的预取 (换源、删除书籍时调用)
+    pub fn cancel(&self, book_url: &str) {
+        let mut guard = self.job.lock();
+        if guard.as_ref().is_some_and(|job| job.book_url == book_url) {
+            *guard = None;
+        }
+    }
+
+    /// 最近阅读章节之后 N 章的缓存情况
+    pub async fn status(&self, book_url: &str) -> PrefetchStatus {
+        let (last_index, running) = match &*self.job.lock() {
+            Some(job) if job.book_url == book_url => (Some(job.last_index), job.queue.lock().running),
+            _ => (None, false),
+        };
+        PrefetchStatus {
+            book_url: book_url.to_string(),
+            chapters: self
+                .book_service
+                .upcoming_cache_status(book_url, last_index, self.count)
+                .await,
+            running,
+        }
+    }
+
+    /// 在阻塞线程中依次抓取队列中的章节，队列清空后退出
+    fn spawn_worker(&self, job: &Job) {
+        let service = self.book_service.clone();
+        let book_url = job.book_url.clone();
+        let queue = job.queue.clone();
+        let cancel = job.cancel.clone();
+        let rt = tokio::runtime::Handle::current();
+        let worker = tokio::task::spawn_blocking(move || {
+            let mut engine = None;
+            loop {
+                let index = {
+                    let mut queue = queue.lock();
+                    match queue.pending.pop_front() {
+                        Some(index) if !cancel.is_cancelled() => index,
+                        _ => {
+                            queue.running = false;
+                            return;
+                        }
+                    }
+                };
+                match service.prefetch_content(&rt, &mut engine, &book_url, index) {
+                    Ok(true) => tracing::debug!("Prefetched chapter {} of {}", index, book_url),
+                    Ok(false) => {}
+                    Err(e) => tracing::debug!("Failed to prefetch chapter {} of {}: {:#}", index, book_url, e),
+                }
+            }
+        });
+        self.jobs.spawn_named("content prefetch", async move {
+            worker.await?;
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,504 @@
+//! 阅读统计：界面阅读时定期上报心跳，按日期、按书累计阅读时长与读完的章节数
+
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::ServiceError;
+use crate::storage::FileStorage;
+
+/// 阅读统计存储文件名
+pub const READING_STATS_FILE: &str = "readingStats.json";
+
+/// 单次心跳最多计入的阅读时长 (秒)
+pub const MAX_HEARTBEAT_SECS: u64 = 120;
+
+/// 同一章节的心跳间隔小于该值 (毫秒) 时视为重复上报
+const DUPLICATE_WINDOW_MS: i64 = 5_000;
+
+/// 保留的阅读记录条数
+const HISTORY_LIMIT: usize = 500;
+
+/// 一次统计查询最多覆盖的天数
+const MAX_RANGE_DAYS: i64 = 366;
+
+/// 未指定起始日期时统计的天数 (含当天)
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// 阅读心跳
+#[derive(Debug, Clone, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Heartbeat {
+    pub book_url: String,
+    pub chapter_index: i32,
+    /// 距上次心跳的阅读时长 (秒)
+    #[serde(default)]
+    pub duration_sec: u64,
+    #[serde(default)]
+    pub book_name: Option<String>,
+    #[serde(default)]
+    pub chapter_title: Option<String>,
+}
+
+/// 一本书在某一天的统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookDayStat {
+    #[serde(default)]
+    pub seconds: u64,
+    #[serde(default)]
+    pub chapters_completed: u32,
+}
+
+/// 每本书最近一次心跳，用于去重与判断读完章节
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BookState {
+    #[serde(default)]
+    book_name: Option<String>,
+    chapter_index: i32,
+    /// 最近一次被计入的心跳时间 (毫秒时间戳)
+    last_beat: i64,
+}
+
+/// 一条阅读记录：最近读过的章节
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub book_url: String,
+    #[serde(default)]
+    pub book_name: Option<String>,
+    pub chapter_index: i32,
+    #[serde(default)]
+    pub chapter_title: Option<String>,
+    /// 开始阅读时间 (毫秒时间戳)
+    pub started_at: i64,
+    /// 最近阅读时间 (毫秒时间戳)
+    pub last_read_at: i64,
+    /// 本章累计阅读时长 (秒)
+    #[serde(default)]
+    pub seconds: u64,
+}
+
+/// 存储文件内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsData {
+    /// 日期 (YYYY-MM-DD) -> 书籍 URL -> 当日统计
+    #[serde(default)]
+    days: BTreeMap<String, BTreeMap<String, BookDayStat>>,
+    #[serde(default)]
+    books: BTreeMap<String, BookState>,
+    /// 新的在前，同一章节只保留一条
+    #[serde(default)]
+    history: Vec<HistoryEntry>,
+}
+
+/// 一天的合计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyTotal {
+    pub date: String,
+    pub seconds: u64,
+    pub chapters_completed: u32,
+}
+
+/// 一本书在查询范围内的合计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookTotal {
+    pub book_url: String,
+    pub book_name: Option<String>,
+    pub seconds: u64,
+    pub chapters_completed: u32,
+}
+
+/// 阅读统计 (用于接口返回)
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingStats {
+    pub from: String,
+    pub to: String,
+    pub total_seconds: u64,
+    pub chapters_completed: u32,
+    /// 范围内的每一天 (含未阅读的日期)
+    pub days: Vec<DailyTotal>,
+    /// 按阅读时长降序
+    pub books: Vec<BookTotal>,
+    /// 截至今天的连续阅读天数；今天尚未阅读时从昨天算起
+    pub current_streak: u32,
+    /// 历史最长连续阅读天数
+    pub longest_streak: u32,
+}
+
+/// 阅读统计服务
+///
+/// 心跳只更新内存并防抖写盘，退出时由文件存储统一落盘。
+#[derive(Clone)]
+pub struct ReadingStatsService {
+    storage: FileStorage,
+    data: Arc<Mutex<Option<StatsData>>>,
+}
+
+impl ReadingStatsService {
+    pub fn with_storage(storage: FileStorage) -> Self {
+        Self {
+            storage,
+            data: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 丢弃内存中的统计，下次访问时重新从文件加载
+    pub async fn reload(&self) {
+        *self.data.lock().await = None;
+    }
+
+    /// 锁定内存中的统计，首次访问时从文件加载
+    async fn lock_data(&self) -> MutexGuard<'_, Option<StatsData>> {
+        let mut guard = self.data.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.storage.read_json_or_default(READING_STATS_FILE).await);
+        }
+        guard
+    }
+
+    async fn with_data<R>(&self, f: impl FnOnce(&mut StatsData) -> R) -> R {
+        f(self.lock_data().await.as_mut().unwrap())
+    }
+
+    /// 记录一次心跳，返回计入的阅读时长 (秒)；重复的心跳返回 0
+    pub async fn heartbeat(&self, beat: Heartbeat) -> anyhow::Result<u64> {
+        self.heartbeat_at(beat, Local::now().fixed_offset()).await
+    }
+
+    async fn heartbeat_at(&self, beat: Heartbeat, now: DateTime<FixedOffset>) -> anyhow::Result<u64> {
+        if beat.book_url.trim().is_empty() {
+            return Err(ServiceError::invalid_input("bookUrl is required").into());
+        }
+        if beat.chapter_index < 0 {
+            return Err(ServiceError::invalid_input("chapterIndex must not be negative").into());
+        }
+        let mut guard = self.lock_data().await;
+        let data = guard.as_mut().unwrap();
+        let Some(counted) = data.record(beat, now) else {
+            return Ok(0);
+        };
+        // 持锁写入，保证并发心跳按顺序落盘
+        self.storage.write_json_debounced(READING_STATS_FILE, &*data).await?;
+        Ok(counted)
+    }
+
+    /// 统计 from..=to (YYYY-MM-DD) 的阅读情况，默认为截至今天的 30 天
+    pub async fn stats(&self, from: Option<&str>, to: Option<&str>) -> anyhow::Result<ReadingStats> {
+        let today = Local::now().date_naive();
+        let to = match to {
+            Some(to) => parse_date(to)?,
+            None => today,
+        };
+        let from = match from {
+            Some(from) => parse_date(from)?,
+            None => to - ChronoDuration::days(DEFAULT_RANGE_DAYS - 1),
+        };
+        if from > to {
+            return Err(ServiceError::invalid_input("from must not be after to").into());
+        }
+        if (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(ServiceError::invalid_input(format!("range must not exceed {} days", MAX_RANGE_DAYS)).into());
+        }
+        Ok(self.with_data(|data| data.summarize(from, to, today)).await)
+    }
+
+    /// 最近读过的章节，新的在前
+    pub async fn history(&self, limit: usize) -> Vec<HistoryEntry> {
+        self.with_data(|data| data.history.iter().take(limit).cloned().collect())
+            .await
+    }
+}
+
+impl StatsData {
+    /// 记录心跳，返回计入的时长；重复上报返回 None
+    fn record(&mut self, beat: Heartbeat, now: DateTime<FixedOffset>) -> Option<u64> {
+        let now_ms = now.timestamp_millis();
+        let state = self.books.get(&beat.book_url);
+        if state.is_some_and(|s| s.chapter_index == beat.chapter_index && now_ms - s.last_beat < DUPLICATE_WINDOW_MS) {
+            return None;
+        }
+        let completed = state.is_some_and(|s| beat.chapter_index > s.chapter_index);
+        let seconds = beat.duration_sec.min(MAX_HEARTBEAT_SECS);
+
+        for (date, secs) in split_by_date(now, seconds) {
+            let stat = self
+                .days
+                .entry(date)
+                .or_default()
+                .entry(beat.book_url.clone())
+                .or_default();
+            stat.seconds += secs;
+        }
+        if completed {
+            let today = now.format(DATE_FORMAT).to_string();
+            let stat = self
+                .days
+                .entry(today)
+                .or_default()
+                .entry(beat.book_url.clone())
+                .or_default();
+            stat.chapters_completed += 1;
+        }
+
+        let state = self.books.entry(beat.book_url.clone()).or_default();
+        if beat.book_name.is_some() {
+            state.book_name = beat.book_name.clone();
+        }
+        state.chapter_index = beat.chapter_index;
+        state.last_beat = now_ms;
+        let book_name = state.book_name.clone();
+
+        let pos = self
+            .history
+            .iter()
+            .position(|h| h.book_url == beat.book_url && h.chapter_index == beat.chapter_index);
+        let mut entry = match pos {
+            Some(pos) => self.history.remove(pos),
+            None => HistoryEntry {
+                book_url: beat.book_url,
+                chapter_index: beat.chapter_index,
+                started_at: now_ms,
+                ..Default::default()
+            },
+        };
+        entry.book_name = book_name;
+        if beat.chapter_title.is_some() {
+            entry.chapter_title = beat.chapter_title;
+        }
+        entry.last_read_at = now_ms;
+        entry.seconds += seconds;
+        self.history.insert(0, entry);
+        self.history.truncate(HISTORY_LIMIT);
+        Some(seconds)
+    }
+
+    fn summarize(&self, from: NaiveDate, to: NaiveDate, today: NaiveDate) -> ReadingStats {
+        let mut stats = ReadingStats {
+            from: from.format(DATE_FORMAT).to_string(),
+            to: to.format(DATE_FORMAT).to_string(),
+            ..Default::default()
+        };
+        let mut books: BTreeMap<&str, BookTotal> = BTreeMap::new();
+        for date in from.iter_days().take_while(|d| *d <= to) {
+            let date = date.format(DATE_FORMAT).to_string();
+            let mut day = DailyTotal {
+                date,
+                ..Default::default()
+            };
+            for (book_url, stat) in self.days.get(&day.date).into_iter().flatten() {
+                day.seconds += stat.seconds;
+                day.chapters_completed += stat.chapters_completed;
+                let total = books.entry(book_url).or_insert_with(|| BookTotal {
+                    book_url: book_url.clone(),
+                    book_name: self.books.get(book_url).and_then(|s| s.book_name.clone()),
+                    ..Default::default()
+                });
+                total.seconds += stat.seconds;
+                total.chapters_completed += stat.chapters_completed;
+            }
+            stats.total_seconds += day.seconds;
+            stats.chapters_completed += day.chapters_completed;
+            stats.days.push(day);
+        }
+        stats.books = books.into_values().collect();
+        stats.books.sort_by_key(|b| std::cmp::Reverse(b.seconds));
+        (stats.current_streak, stats.longest_streak) = self.streaks(today);
+        stats
+    }
+
+    /// 当前与最长连续阅读天数
+    fn streaks(&self, today: NaiveDate) -> (u32, u32) {
+        let read_days: Vec<NaiveDate> = self
+            .days
+            .iter()
+            .filter(|(_, books)| books.values().any(|s| s.seconds > 0))
+            .filter_map(|(date, _)| NaiveDate::parse_from_str(date, DATE_FORMAT).ok())
+            .collect();
+
+        let mut longest = 0;
+        let mut run = 0;
+        let mut prev: Option<NaiveDate> = None;
+        for &day in &read_days {
+            run = match prev {
+                Some(prev) if prev.succ_opt() == Some(day) => run + 1,
+                _ => 1,
+            };
+            longest = longest.max(run);
+            prev = Some(day);
+        }
+
+        let yesterday = today.pred_opt();
+        let current = match prev {
+            Some(last) if last == today || Some(last) == yesterday => run,
+            _ => 0,
+        };
+        (current, longest)
+    }
+}
+
+/// 把截至 `end` 的一段时长按本地日期拆分，跨零点的心跳分别计入两天
+fn split_by_date(end: DateTime<FixedOffset>, seconds: u64) -> Vec<(String, u64)> {
+    let mut parts = Vec::new();
+    let mut start = end - ChronoDuration::seconds(seconds as i64);
+    while start.date_naive() < end.date_naive() {
+        let next_midnight = (start.date_naive() + ChronoDuration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(*end.offset()).single())
+            .unwrap_or(end);
+        let secs = (next_midnight - start).num_seconds().max(0) as u64;
+        if secs > 0 {
+            parts.push((start.format(DATE_FORMAT).to_string(), secs));
+        }
+        start = next_midnight;
+    }
+    let secs = (end - start).num_seconds().max(0) as u64;
+    if secs > 0 || parts.is_empty() {
+        parts.push((end.format(DATE_FORMAT).to_string(), secs));
+    }
+    parts
+}
+
+fn parse_date(date: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT)
+        .map_err(|_| ServiceError::invalid_input(format!("invalid date, expected YYYY-MM-DD: {}", date)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, h: u32, m: u32, s: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2026, 3, day, h, m, s)
+            .unwrap()
+    }
+
+    fn beat(book_url: &str, chapter_index: i32, duration_sec: u64) -> Heartbeat {
+        Heartbeat {
+            book_url: book_url.to_string(),
+            chapter_index,
+            duration_sec,
+            ..Default::default()
+        }
+    }
+
+    fn service() -> ReadingStatsService {
+        let dir = std::env::temp_dir().join(format!("reader_tests_reading_stats_{}", uuid::Uuid::new_v4()));
+        ReadingStatsService::with_storage(FileStorage::new(dir))
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_split_across_midnight() {
+        let service = service();
+        // 23:59:00 到次日 00:01:00 的两分钟阅读
+        assert_eq!(
+            service.heartbeat_at(beat("b", 0, 120), at(2, 0, 1, 0)).await.unwrap(),
+            120
+        );
+        // 超过上限的时长被截断
+        assert_eq!(
+            service.heartbeat_at(beat("b", 1, 3600), at(2, 0, 10, 0)).await.unwrap(),
+            120
+        );
+
+        let stats = service.with_data(|d| d.summarize(date(1), date(2), date(2))).await;
+        let days: Vec<_> = stats
+            .days
+            .iter()
+            .map(|d| (d.date.as_str(), d.seconds, d.chapters_completed))
+            .collect();
+        assert_eq!(days, vec![("2026-03-01", 60, 0), ("2026-03-02", 180, 1)]);
+        assert_eq!(stats.total_seconds, 240);
+        assert_eq!(stats.books[0].chapters_completed, 1);
+        assert_eq!((stats.current_streak, stats.longest_streak), (2, 2));
+
+        // 今天未读时连续天数从昨天算起，中断后归零
+        assert_eq!(service.with_data(|d| d.streaks(date(3))).await, (2, 2));
+        assert_eq!(service.with_data(|d| d.streaks(date(4))).await, (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_heartbeats_ignored() {
+        let service = service();
+        assert_eq!(
+            service.heartbeat_at(beat("b", 3, 30), at(5, 12, 0, 0)).await.unwrap(),
+            30
+        );
+        assert_eq!(
+            service.heartbeat_at(beat("b", 3, 30), at(5, 12, 0, 4)).await.unwrap(),
+            0
+        );
+        // 其他书籍与换章不受去重影响
+        assert_eq!(
+            service
+                .heartbeat_at(beat("other", 0, 10), at(5, 12, 0, 4))
+                .await
+                .unwrap(),
+            10
+        );
+        assert_eq!(service.heartbeat_at(beat("b", 4, 2), at(5, 12, 0, 4)).await.unwrap(), 2);
+        // 窗口过后再次计入
+        assert_eq!(
+            service.heartbeat_at(beat("b", 4, 30), at(5, 12, 0, 10)).await.unwrap(),
+            30
+        );
+
+        let history = service.history(10).await;
+        let chapters: Vec<_> = history
+            .iter()
+            .map(|h| (h.book_url.as_str(), h.chapter_index, h.seconds))
+            .collect();
+        assert_eq!(chapters, vec![("b", 4, 32), ("other", 0, 10), ("b", 3, 30)]);
+        assert_eq!(service.history(1).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_heartbeats_persist() {
+        let service = service();
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    let book = format!("book{}", i % 4);
+                    service.heartbeat_at(beat(&book, i, 10), at(6, 9, 0, 0)).await.unwrap()
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        service.storage.flush().await.unwrap();
+
+        let reloaded = ReadingStatsService::with_storage(service.storage.clone());
+        let stats = reloaded.with_data(|d| d.summarize(date(6), date(6), date(6))).await;
+        assert_eq!(stats.total_seconds, 200);
+        assert_eq!(stats.books.len(), 4);
+        assert!(stats.books.iter().all(|b| b.seconds == 50));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input() {
+        let service = service();
+        assert!(service.heartbeat(beat("", 0, 10)).await.is_err());
+        assert!(service.stats(Some("2026-03-02"), Some("2026-03-01")).await.is_err());
+        assert!(service.stats(Some("2025-01-01"), Some("2026-03-01")).await.is_err());
+        assert!(service.stats(Some("yesterday"), None).await.is_err());
+        assert_eq!(service.stats(None, None).await.unwrap().days.len(), 30);
+    }
+}
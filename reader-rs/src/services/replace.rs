@@ -7,6 +7,7 @@ use crate::storage::FileStorage;
 /// 替换规则存储文件名
 const RULES_FILE: &str = "replaceRules.json";
 
+#[derive(Clone)]
 pub struct ReplaceService {
     storage: FileStorage,
     rules: Arc<RwLock<Vec<ReplaceRule>>>,
@@ -14,12 +15,22 @@ pub struct ReplaceService {
 
 impl ReplaceService {
     pub fn new() -> Self {
+        Self::with_storage(FileStorage::default())
+    }
+
+    pub fn with_storage(storage: FileStorage) -> Self {
         Self {
-            storage: FileStorage::default(),
+            storage,
             rules: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
+    /// 从磁盘重新加载 (恢复备份后调用)
+    pub async fn reload(&self) {
+        let loaded: Vec<ReplaceRule> = self.storage.read_json_or_default(RULES_FILE).await;
+        *self.rules.write().await = loaded;
+    }
+
     /// 获取所有规则
     pub async fn get_all_rules(&self) -> Result<Vec<ReplaceRule>, anyhow::Error> {
         let rules = self.rules.read().await;
@@ -0,0 +1,78 @@
+//! 接口请求的整体时间预算：超出后不再发起书源请求或执行 JS，接口返回 504
+
+use std::time::Duration;
+
+use crate::engine::deadline::Deadline;
+
+/// 正文接口的默认预算 (含分页与重试)
+const DEFAULT_CONTENT_BUDGET: Duration = Duration::from_secs(30);
+/// 搜索接口的默认预算 (依次尝试多个书源)
+const DEFAULT_SEARCH_BUDGET: Duration = Duration::from_secs(60);
+
+/// 各接口的请求时间预算，由环境变量 READER_CONTENT_BUDGET_SECS / READER_SEARCH_BUDGET_SECS 配置
+///
+/// 设为 0 表示不限制。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestBudgets {
+    /// getBookContent
+    pub content: Option<Duration>,
+    /// searchBook
+    pub search: Option<Duration>,
+}
+
+impl Default for RequestBudgets {
+    fn default() -> Self {
+        Self {
+            content: Some(DEFAULT_CONTENT_BUDGET),
+            search: Some(DEFAULT_SEARCH_BUDGET),
+        }
+    }
+}
+
+impl RequestBudgets {
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let budget = |key: &str, default: Duration| match var(key).and_then(|v| v.trim().parse::<u64>().ok()) {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(default),
+        };
+        Self {
+            content: budget("READER_CONTENT_BUDGET_SECS", DEFAULT_CONTENT_BUDGET),
+            search: budget("READER_SEARCH_BUDGET_SECS", DEFAULT_SEARCH_BUDGET),
+        }
+    }
+
+    /// 本次正文请求的截止时间
+    pub fn content_deadline(&self) -> Option<Deadline> {
+        self.content.map(Deadline::after)
+    }
+
+    /// 本次搜索请求的截止时间
+    pub fn search_deadline(&self) -> Option<Deadline> {
+        self.search.map(Deadline::after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budgets_from_vars() {
+        let budgets = RequestBudgets::from_vars(|_| None);
+        assert_eq!(budgets, RequestBudgets::default());
+
+        let budgets = RequestBudgets::from_vars(|key| match key {
+            "READER_CONTENT_BUDGET_SECS" => Some("12".to_string()),
+            "READER_SEARCH_BUDGET_SECS" => Some("0".to_string()),
+            _ => None,
+        });
+        assert_eq!(budgets.content, Some(Duration::from_secs(12)));
+        assert_eq!(budgets.search, None);
+        assert!(budgets.search_deadline().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::engine::book_source::BookItem;
+use crate::models::{BookSourceFull, SourceHealth};
+use super::search_merge::{normalize_author, normalize_name};
+
+/// 字数中的数字与单位，如 "123.4万字"、"1,024字"、"56k"
+static WORD_COUNT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\d[\d,]*(?:\.\d+)?)\s*(万|千|亿|[wWkK])?").unwrap());
+
+/// 将书源返回的字数文本解析为字数
+///
+/// 支持 "123.4万字"、"3千字"、"12.5w"、"45678" 等写法，无法识别时返回 None。
+pub fn parse_word_count(text: &str) -> Option<u64> {
+    let caps = WORD_COUNT.captures(text)?;
+    let number: f64 = caps[1].replace(',', "").parse().ok()?;
+    let unit = match caps.get(2).map(|m| m.as_str()) {
+        Some("万" | "w" | "W") => 10_000.0,
+        Some("千" | "k" | "K") => 1_000.0,
+        Some("亿") => 100_000_000.0,
+        _ => 1.0,
+    };
+    Some((number * unit).round() as u64)
+}
+
+/// 作者比较前去掉空白、"作者：" 前缀与 "著" 字
+fn author_key(author: &str) -> String {
+    normalize_author(author).trim_end_matches('著').to_string()
+}
+
+/// 搜索结果过滤条件，各条件同时满足才保留
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// 书名与搜索关键字一致 (忽略大小写、空白与书名号)
+    pub exact: bool,
+    /// 作者模糊匹配
+    pub author: Option<String>,
+    pub min_words: Option<u64>,
+    pub max_words: Option<u64>,
+    /// 分类包含该文本
+    pub kind: Option<String>,
+    /// 不搜索已标记为失效 (broken) 的书源；只影响书源的选择，不过滤结果
+    pub skip_broken: bool,
+}
+
+impl SearchFilter {
+    /// 是否没有任何过滤条件
+    pub fn is_empty(&self) -> bool {
+        !self.exact
+            && self.author.is_none()
+            && self.min_words.is_none()
+            && self.max_words.is_none()
+            && self.kind.is_none()
+    }
+
+    /// 是否搜索该书源
+    pub fn includes_source(&self, source: &BookSourceFull) -> bool {
+        !(self.skip_broken && source.health == SourceHealth::Broken)
+    }
+
+    /// 书籍是否满足全部条件
+    ///
+    /// 设置了字数范围时，字数无法解析的书籍被过滤掉。
+    pub fn matches(&self, key: &str, book: &BookItem) -> bool {
+        if self.exact && normalize_name(&book.name) != normalize_name(key) {
+            return false;
+        }
+        if let Some(author) = self.author.as_deref().map(author_key).filter(|a| !a.is_empty()) {
+            let book_author = author_key(&book.author);
+            if book_author.is_empty() || !(book_author.contains(&author) || author.contains(&book_author)) {
+                return false;
+            }
+        }
+        if self.min_words.is_some() || self.max_words.is_some() {
+            let Some(words) = book.word_count.as_deref().and_then(parse_word_count) else {
+                return false;
+            };
+            if self.min_words.is_some_and(|min| words < min) || self.max_words.is_some_and(|max| words > max) {
+                return false;
+            }
+        }
+        if let Some(kind) = self.kind.as_deref().map(str::trim).filter(|k| !k.is_empty()) {
+            if !book.kind.as_deref().is_some_and(|k| k.contains(kind)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 保留满足条件的书籍
+    pub fn apply(&self, key: &str, books: Vec<BookItem>) -> Vec<BookItem> {
+        if self.is_empty() {
+            return books;
+        }
+        books.into_iter().filter(|book| self.matches(key, book)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, author: &str, kind: &str, words: &str) -> BookItem {
+        BookItem {
+            name: name.to_string(),
+            author: author.to_string(),
+            kind: Some(kind.to_string()).filter(|k| !k.is_empty()),
+            word_count: Some(words.to_string()).filter(|w| !w.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_word_count() {
+        assert_eq!(parse_word_count("123.4万字"), Some(1_234_000));
+        assert_eq!(parse_word_count("字数：3千"), Some(3_000));
+        assert_eq!(parse_word_count("12.5w"), Some(125_000));
+        assert_eq!(parse_word_count("45678"), Some(45_678));
+        assert_eq!(parse_word_count("1,024 字"), Some(1_024));
+        assert_eq!(parse_word_count("2亿"), Some(200_000_000));
+        assert_eq!(parse_word_count("未知"), None);
+        assert_eq!(parse_word_count(""), None);
+    }
+
+    #[test]
+    fn test_filters_are_anded() {
+        let books = vec![
+            item("诡秘之主", "爱潜水的乌贼 著", "玄幻奇幻", "446.5万字"),
+            item("诡秘之主同人", "乌贼", "玄幻", "20万字"),
+            item("《诡秘之主》", "别人", "都市", "300万"),
+            item("诡秘之主", "爱潜水的乌贼", "玄幻", ""),
+        ];
+        let names = |filter: &SearchFilter| -> Vec<(String, String)> {
+            filter
+                .apply("诡秘之主", books.clone())
+                .into_iter()
+                .map(|b| (b.name, b.author))
+                .collect()
+        };
+
+        assert_eq!(names(&SearchFilter::default()).len(), 4);
+        let exact = SearchFilter {
+            exact: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&exact).len(), 3);
+
+        let exact_author = SearchFilter {
+            author: Some(" 爱潜水的乌贼著 ".to_string()),
+            ..exact.clone()
+        };
+        assert_eq!(names(&exact_author).len(), 2);
+
+        // 字数未知的书在设置字数范围时被过滤
+        let with_words = SearchFilter {
+            min_words: Some(1_000_000),
+            ..exact_author.clone()
+        };
+        assert_eq!(
+            names(&with_words),
+            vec![("诡秘之主".to_string(), "爱潜水的乌贼 著".to_string())]
+        );
+
+        let kind_and_max = SearchFilter {
+            kind: Some("玄幻".to_string()),
+            max_words: Some(1_000_000),
+            ..Default::default()
+        };
+        assert_eq!(names(&kind_and_max), vec![("诡秘之主同人".to_string(), "乌贼".to_string())]);
+
+        let nothing = SearchFilter {
+            kind: Some("都市".to_string()),
+            author: Some("乌贼".to_string()),
+            ..Default::default()
+        };
+        assert!(names(&nothing).is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::engine::book_source::BookItem;
+
+/// 合并结果中每本书内联返回的来源数，更多来源通过会话按需获取
+pub const MAX_INLINE_ORIGINS: usize = 3;
+
+/// 最多保留的搜索会话数
+const MAX_SEARCH_SESSIONS: usize = 16;
+
+/// 搜索会话有效期
+const SEARCH_SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// 书籍的一个来源
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOrigin {
+    pub source_url: String,
+    pub source_name: String,
+    pub book_url: String,
+    pub last_chapter: Option<String>,
+    /// 书源响应耗时 (毫秒)
+    pub respond_time: u64,
+}
+
+/// 按书名与作者合并后的搜索结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchBookAggregate {
+    pub name: String,
+    pub author: String,
+    pub cover_url: Option<String>,
+    pub intro: Option<String>,
+    pub kind: Option<String>,
+    pub origin_count: usize,
+    pub origins: Vec<SearchOrigin>,
+}
+
+/// 合并搜索的返回结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedSearch {
+    pub search_id: String,
+    pub books: Vec<SearchBookAggregate>,
+}
+
+/// 书名归一化：忽略大小写、空白与书名号
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '《' | '》'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 作者归一化：忽略大小写、空白与 "作者：" 前缀
+pub fn normalize_author(author: &str) -> String {
+    let author = author.trim();
+    let author = author
+        .strip_prefix("作者")
+        .and_then(|rest| rest.trim_start().strip_prefix([':', '：']))
+        .unwrap_or(author);
+    author
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 搜索结果聚合器
+///
+/// 按归一化的 (书名, 作者) 去重合并各书源的结果。
+pub struct SearchAggregator {
+    key: String,
+    books: Vec<SearchBookAggregate>,
+    index: HashMap<(String, String), usize>,
+}
+
+impl SearchAggregator {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: normalize_name(key),
+            books: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// 加入一个书源的搜索结果
+    pub fn add(&mut self, source_url: &str, source_name: &str, items: Vec<BookItem>, respond_time: u64) {
+        for item in items {
+            if item.name.trim().is_empty() {
+                continue;
+            }
+            let key = (normalize_name(&item.name), normalize_author(&item.author));
+            let origin = SearchOrigin {
+                source_url: source_url.to_string(),
+                source_name: source_name.to_string(),
+                book_url: item.book_url,
+                last_chapter: item.last_chapter,
+                respond_time,
+            };
+
+            let book = match self.index.get(&key) {
+                Some(&pos) => &mut self.books[pos],
+                None => {
+                    self.index.insert(key, self.books.len());
+                    self.books.push(SearchBookAggregate {
+                        name: item.name.trim().to_string(),
+                        author: item.author.trim().to_string(),
+                        cover_url: None,
+                        intro: None,
+                        kind: None,
+                        origin_count: 0,
+                        origins: Vec::new(),
+                    });
+                    self.books.last_mut().unwrap()
+                }
+            };
+            // 同一书源重复返回的同一本书只保留一次
+            if book.origins.iter().any(|o| o.source_url == origin.source_url && o.book_url == origin.book_url) {
+                continue;
+            }
+            book.cover_url = book.cover_url.take().or(item.cover_url.filter(|s| !s.is_empty()));
+            book.intro = book.intro.take().or(item.intro.filter(|s| !s.is_empty()));
+            book.kind = book.kind.take().or(item.kind.filter(|s| !s.is_empty()));
+            book.origins.push(origin);
+            book.origin_count = book.origins.len();
+        }
+    }
+
+    /// 排序后的结果
+    ///
+    /// 依次按书名完全匹配、来源数量、最快来源的响应时间排序，
+    /// 最后按书名与作者排序，结果与各书源返回的先后无关。
+    pub fn ranked(&self) -> Vec<SearchBookAggregate> {
+        let mut books = self.books.clone();
+        for book in &mut books {
+            book.origins.sort_by(|a, b| {
+                a.respond_time
+                    .cmp(&b.respond_time)
+                    .then_with(|| a.source_url.cmp(&b.source_url))
+                    .then_with(|| a.book_url.cmp(&b.book_url))
+            });
+        }
+
+        let exact = |book: &SearchBookAggregate| normalize_name(&book.name) == self.key;
+        let fastest = |book: &SearchBookAggregate| book.origins.first().map_or(u64::MAX, |o| o.respond_time);
+        books.sort_by(|a, b| {
+            exact(b)
+                .cmp(&exact(a))
+                .then_with(|| b.origins.len().cmp(&a.origins.len()))
+                .then_with(|| fastest(a).cmp(&fastest(b)))
+                .then_with(|| normalize_name(&a.name).cmp(&normalize_name(&b.name)))
+                .then_with(|| normalize_author(&a.author).cmp(&normalize_author(&b.author)))
+        });
+        books
+    }
+}
+
+struct SearchSession {
+    id: String,
+    created_at: Instant,
+    books: Vec<SearchBookAggregate>,
+}
+
+/// 内存中的搜索会话，用于按需获取某本书的全部来源而无需重新搜索
+#[derive(Clone, Default)]
+pub struct SearchSessions {
+    sessions: Arc<Mutex<VecDeque<SearchSession>>>,
+}
+
+impl SearchSessions {
+    /// 保存一次搜索的完整结果，返回会话 ID
+    pub fn insert(&self, books: Vec<SearchBookAggregate>) -> String {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|s| s.created_at.elapsed() < SEARCH_SESSION_TTL);
+        while sessions.len() >= MAX_SEARCH_SESSIONS {
+            sessions.pop_front();
+        }
+        sessions.push_back(SearchSession {
+            id: id.clone(),
+            created_at: Instant::now(),
+            books,
+        });
+        id
+    }
+
+    /// 查找会话中某本书的全部来源
+    pub fn origins(&self, search_id: &str, name: &str, author: &str) -> Option<Vec<SearchOrigin>> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .iter()
+            .find(|s| s.id == search_id && s.created_at.elapsed() < SEARCH_SESSION_TTL)?;
+        let (name, author) = (normalize_name(name), normalize_author(author));
+        session
+            .books
+            .iter()
+            .find(|b| normalize_name(&b.name) == name && normalize_author(&b.author) == author)
+            .map(|b| b.origins.clone())
+    }
+}
+
+/// 截断内联返回的来源
+pub fn truncate_origins(mut books: Vec<SearchBookAggregate>) -> Vec<SearchBookAggregate> {
+    for book in &mut books {
+        book.origins.truncate(MAX_INLINE_ORIGINS);
+    }
+    books
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, author: &str, url: &str) -> BookItem {
+        BookItem {
+            name: name.to_string(),
+            author: author.to_string(),
+            book_url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dedupe_normalizes_name_and_author() {
+        let mut agg = SearchAggregator::new("斗破苍穹");
+        agg.add("s1", "源一", vec![item("斗破苍穹", "天蚕土豆", "s1/1")], 100);
+        agg.add("s2", "源二", vec![item(" 斗破 苍穹 ", "作者：天蚕土豆 ", "s2/1")], 200);
+        agg.add("s3", "源三", vec![item("《斗破苍穹》", " 天蚕 土豆", "s3/1")], 300);
+        agg.add("s4", "源四", vec![item("Doupo", "Tian Can", "s4/1")], 50);
+        agg.add("s5", "源五", vec![item("doupo ", "tian  can", "s5/1")], 60);
+        // 作者不同视为不同的书
+        agg.add("s6", "源六", vec![item("斗破苍穹", "别人", "s6/1")], 10);
+
+        let books = agg.ranked();
+        assert_eq!(books.len(), 3);
+        assert_eq!(books[0].name, "斗破苍穹");
+        assert_eq!(books[0].author, "天蚕土豆");
+        assert_eq!(books[0].origin_count, 3);
+        let urls: Vec<_> = books[0].origins.iter().map(|o| o.book_url.as_str()).collect();
+        assert_eq!(urls, vec!["s1/1", "s2/1", "s3/1"]);
+        assert_eq!(books[1].author, "别人");
+        assert_eq!(books[2].origin_count, 2);
+    }
+
+    #[test]
+    fn test_ranking_is_stable_across_arrival_order() {
+        let results = [
+            ("a", vec![item("凡人修仙传外传", "忘语", "a/1"), item("凡人修仙传", "忘语", "a/2")], 500),
+            ("b", vec![item("凡人修仙传", "忘语", "b/1")], 100),
+            ("c", vec![item("凡人修仙", "某人", "c/1"), item("凡人修仙传之仙界篇", "忘语", "c/2")], 50),
+            ("d", vec![item("凡人修仙传外传", "忘语", "d/1")], 80),
+            ("e", vec![item("凡人修仙传之仙界篇", "忘语", "e/1")], 90),
+        ];
+
+        let rank = |order: &[usize]| {
+            let mut agg = SearchAggregator::new("凡人修仙传");
+            for &i in order {
+                let (source, items, time) = &results[i];
+                agg.add(source, source, items.clone(), *time);
+            }
+            agg.ranked()
+        };
+
+        let forward = rank(&[0, 1, 2, 3, 4]);
+        let names: Vec<_> = forward.iter().map(|b| b.name.as_str()).collect();
+        // 完全匹配优先，其次来源数量，再按最快响应
+        assert_eq!(names, vec!["凡人修仙传", "凡人修仙传之仙界篇", "凡人修仙传外传", "凡人修仙"]);
+        assert_eq!(forward[0].origins[0].source_url, "b");
+
+        assert_eq!(rank(&[4, 3, 2, 1, 0]), forward);
+        assert_eq!(rank(&[2, 0, 4, 1, 3]), forward);
+    }
+
+    #[test]
+    fn test_search_sessions() {
+        let mut agg = SearchAggregator::new("书");
+        for i in 0..5 {
+            agg.add(&format!("s{}", i), "源", vec![item("书", "作者", &format!("u{}", i))], i * 10);
+        }
+        let sessions = SearchSessions::default();
+        let books = agg.ranked();
+        let id = sessions.insert(books.clone());
+
+        let inline = truncate_origins(books);
+        assert_eq!(inline[0].origins.len(), MAX_INLINE_ORIGINS);
+        assert_eq!(inline[0].origin_count, 5);
+
+        let origins = sessions.origins(&id, " 书", "作者：作者").unwrap();
+        assert_eq!(origins.len(), 5);
+        assert!(sessions.origins(&id, "别的书", "作者").is_none());
+        assert!(sessions.origins("missing", "书", "作者").is_none());
+    }
+}
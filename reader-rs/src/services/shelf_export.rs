@@ -0,0 +1,295 @@
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+
+use crate::models::{Book, BookGroup};
+
+/// 书架报表格式版本，字段含义或类型变化时递增
+pub const SHELF_REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// CSV 的列，与 JSON 报表的字段名一致
+const CSV_HEADERS: [&str; 15] = [
+    "name",
+    "author",
+    "bookUrl",
+    "sourceName",
+    "sourceUrl",
+    "groups",
+    "totalChapters",
+    "durChapterIndex",
+    "durChapterTitle",
+    "percent",
+    "lastReadTime",
+    "latestChapterTitle",
+    "updateFailed",
+    "updateError",
+    "intro",
+];
+
+/// 书架报表 (GET /exportBookshelf?format=json)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShelfReport {
+    /// 报表格式版本，见 SHELF_REPORT_SCHEMA_VERSION
+    pub schema_version: u32,
+    /// 导出时间 (毫秒时间戳)
+    pub exported_at: i64,
+    pub books: Vec<ShelfReportRow>,
+}
+
+/// 报表中的一本书
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShelfReportRow {
+    pub name: String,
+    pub author: String,
+    pub book_url: String,
+    pub source_name: Option<String>,
+    pub source_url: Option<String>,
+    /// 书籍所属的自定义分组名称
+    pub groups: Vec<String>,
+    /// 章节总数，目录未缓存且书籍未记录时为 None
+    pub total_chapters: Option<usize>,
+    /// 当前阅读的章节序号 (从 0 开始)
+    pub dur_chapter_index: Option<i32>,
+    pub dur_chapter_title: Option<String>,
+    /// 已读百分比 (保留一位小数)，未开始阅读或章节总数未知时为 None
+    pub percent: Option<f64>,
+    /// 最后阅读时间 (毫秒时间戳)
+    pub last_read_time: Option<i64>,
+    pub latest_chapter_title: Option<String>,
+    /// 最近一次更新是否失败
+    pub update_failed: bool,
+    /// 失败原因，格式为 `错误类型: 错误信息`
+    pub update_error: Option<String>,
+    pub intro: Option<String>,
+}
+
+impl ShelfReportRow {
+    /// 由书籍生成报表行；`chapter_titles` 为缓存的目录章节标题 (未缓存时为 None)
+    pub fn new(book: &Book, chapter_titles: Option<&[String]>, groups: &[BookGroup]) -> Self {
+        let total_chapters = chapter_titles
+            .map(<[String]>::len)
+            .or_else(|| book.total_chapter_num.and_then(|n| usize::try_from(n).ok()));
+        let dur_chapter_title = book.dur_chapter_title.clone().or_else(|| {
+            let index = usize::try_from(book.dur_chapter_index?).ok()?;
+            chapter_titles?.get(index).cloned()
+        });
+        Self {
+            name: book.name.clone(),
+            author: book.author.clone(),
+            book_url: book.book_url.clone(),
+            source_name: book.origin_name.clone(),
+            source_url: book.origin.clone(),
+            groups: group_names(book.group.unwrap_or(0), groups),
+            total_chapters,
+            dur_chapter_index: book.dur_chapter_index,
+            dur_chapter_title,
+            percent: read_percent(book.dur_chapter_index, total_chapters),
+            last_read_time: book.dur_chapter_time,
+            latest_chapter_title: book.latest_chapter_title.clone(),
+            update_failed: book.last_update_error.is_some(),
+            update_error: book
+                .last_update_error
+                .as_ref()
+                .map(|e| format!("{}: {}", e.kind, e.message)),
+            intro: book.intro.clone(),
+        }
+    }
+
+    /// CSV 中的各列，顺序同 CSV_HEADERS
+    fn csv_record(&self) -> Vec<String> {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        let number = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.name.clone(),
+            self.author.clone(),
+            self.book_url.clone(),
+            text(&self.source_name),
+            text(&self.source_url),
+            self.groups.join(";"),
+            number(self.total_chapters.map(|n| n.to_string())),
+            number(self.dur_chapter_index.map(|i| i.to_string())),
+            text(&self.dur_chapter_title),
+            number(self.percent.map(|p| format!("{:.1}", p))),
+            number(self.last_read_time.and_then(format_time)),
+            text(&self.latest_chapter_title),
+            self.update_failed.to_string(),
+            text(&self.update_error),
+            text(&self.intro),
+        ]
+    }
+}
+
+/// 分组位掩码对应的自定义分组名称 (按分组排序)
+pub fn group_names(mask: i64, groups: &[BookGroup]) -> Vec<String> {
+    groups
+        .iter()
+        .filter(|g| g.group_id > 0 && mask & g.group_id != 0)
+        .map(|g| g.group_name.clone())
+        .collect()
+}
+
+/// 已读百分比：当前章节 (含) 之前的章节数占总数的比例
+fn read_percent(index: Option<i32>, total: Option<usize>) -> Option<f64> {
+    let read = usize::try_from(index?).ok()? + 1;
+    let total = total.filter(|&t| t > 0)?;
+    let percent = read.min(total) as f64 * 100.0 / total as f64;
+    Some((percent * 10.0).round() / 10.0)
+}
+
+/// 毫秒时间戳格式化为本地时间
+fn format_time(millis: i64) -> Option<String> {
+    let time = Local.timestamp_millis_opt(millis).single()?;
+    Some(time.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+impl ShelfReport {
+    /// 生成 JSON (GET /exportBookshelf?format=json)
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// 生成 CSV (GET /exportBookshelf?format=csv)
+    pub fn to_csv(&self) -> String {
+        rows_to_csv(&self.books)
+    }
+}
+
+/// 生成 CSV：UTF-8 BOM 开头 (Excel 据此识别编码)，CRLF 换行
+fn rows_to_csv(rows: &[ShelfReportRow]) -> String {
+    let mut out = String::from("\u{feff}");
+    write_record(&mut out, CSV_HEADERS.iter().map(|h| h.to_string()));
+    for row in rows {
+        write_record(&mut out, row.csv_record());
+    }
+    out
+}
+
+fn write_record(out: &mut String, fields: impl IntoIterator<Item = String>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&csv_field(&field));
+    }
+    out.push_str("\r\n");
+}
+
+/// 含逗号、引号、换行或首尾空白的字段加引号，引号写两次
+fn csv_field(field: &str) -> String {
+    let needs_quotes = field.contains([',', '"', '\r', '\n']) || field.trim() != field;
+    match needs_quotes {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BookUpdateError, UpdateStage};
+
+    /// 按 RFC 4180 解析 CSV (跳过 BOM)
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => record.push(std::mem::take(&mut field)),
+                (false, '\r') => {}
+                (false, '\n') => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+        records
+    }
+
+    fn group(id: i64, name: &str) -> BookGroup {
+        BookGroup {
+            group_id: id,
+            group_name: name.to_string(),
+            order: id as i32,
+            show: true,
+        }
+    }
+
+    fn book(name: &str, group: i64) -> Book {
+        serde_json::from_value(serde_json::json!({
+            "bookUrl": format!("https://example.com/{}", name.len()),
+            "name": name,
+            "author": "作者",
+            "group": group,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let groups = vec![
+            group(-1, "全部"),
+            group(1, "玄幻"),
+            group(2, "完结"),
+            group(4, "在读, 精选"),
+        ];
+
+        let mut first = book("斗破苍穹, 典藏版", 1 | 4);
+        first.intro = Some("第一行, 带逗号\n第二行 \"引号\"".to_string());
+        first.origin_name = Some("示例书源".to_string());
+        first.origin = Some("https://source.example".to_string());
+        first.dur_chapter_index = Some(9);
+        first.last_update_error = Some(BookUpdateError {
+            time: 0,
+            stage: UpdateStage::Toc,
+            kind: "NETWORK".to_string(),
+            message: "timeout".to_string(),
+        });
+        let titles: Vec<String> = (1..=40).map(|i| format!("第{}章", i)).collect();
+        let second = book(" 前后空格 ", 0);
+
+        let rows = vec![
+            ShelfReportRow::new(&first, Some(&titles), &groups),
+            ShelfReportRow::new(&second, None, &groups),
+        ];
+        assert_eq!(rows[0].groups, vec!["玄幻", "在读, 精选"]);
+        assert_eq!(rows[0].dur_chapter_title.as_deref(), Some("第10章"));
+        assert_eq!(rows[0].percent, Some(25.0));
+        assert!(rows[0].update_failed);
+        assert_eq!(rows[0].update_error.as_deref(), Some("NETWORK: timeout"));
+        assert!(rows[1].groups.is_empty());
+        assert_eq!(rows[1].percent, None);
+
+        let csv = rows_to_csv(&rows);
+        assert!(csv.starts_with('\u{feff}'));
+        let records = parse_csv(&csv);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], CSV_HEADERS.map(String::from).to_vec());
+        for (record, row) in records[1..].iter().zip(&rows) {
+            assert_eq!(record, &row.csv_record());
+        }
+        assert_eq!(records[1][0], "斗破苍穹, 典藏版");
+        assert_eq!(records[1][5], "玄幻;在读, 精选");
+        assert_eq!(records[1][14], "第一行, 带逗号\n第二行 \"引号\"");
+        assert_eq!(records[2][0], " 前后空格 ");
+    }
+
+    #[test]
+    fn test_read_percent() {
+        assert_eq!(read_percent(Some(0), Some(3)), Some(33.3));
+        assert_eq!(read_percent(Some(5), Some(3)), Some(100.0));
+        assert_eq!(read_percent(None, Some(3)), None);
+        assert_eq!(read_percent(Some(0), Some(0)), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! 退出流程：先取消后台任务，再在限定时间内写入各服务尚未落盘的状态
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::storage::kv::KvStore;
+use crate::storage::FileStorage;
+
+/// 退出时等待请求结束、写入状态各自的时限
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 退出前需要写入状态的服务
+pub trait Shutdown {
+    /// 写入尚未落盘的状态
+    fn shutdown(&self) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+impl Shutdown for FileStorage {
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.flush().await
+    }
+}
+
+impl Shutdown for KvStore {
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.flush().await?;
+        self.stop_flusher();
+        Ok(())
+    }
+}
+
+/// 依次执行的写盘步骤，共用一个截止时间
+pub struct ShutdownSteps {
+    deadline: Instant,
+    failed: Vec<&'static str>,
+}
+
+impl ShutdownSteps {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + timeout,
+            failed: Vec::new(),
+        }
+    }
+
+    /// 执行一个服务的写盘，失败或超时记入结果
+    pub async fn run(&mut self, name: &'static str, service: &impl Shutdown) {
+        match tokio::time::timeout_at(self.deadline, service.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                tracing::error!("Failed to flush {}: {:#}", name, e);
+                self.failed.push(name);
+            }
+            Err(_) => {
+                tracing::error!("Timed out flushing {}", name);
+                self.failed.push(name);
+            }
+        }
+    }
+
+    /// 未能写入的步骤
+    pub fn finish(self) -> Vec<&'static str> {
+        self.failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::cookie::CookieManager;
+    use crate::services::{AppState, KV_FILE};
+
+    struct Stuck;
+
+    impl Shutdown for Stuck {
+        async fn shutdown(&self) -> anyhow::Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stuck_step_hits_deadline() {
+        let mut steps = ShutdownSteps::new(Duration::from_millis(50));
+        steps.run("stuck", &Stuck).await;
+        // 截止时间已过，后续步骤不再等待
+        steps.run("stuck again", &Stuck).await;
+        assert_eq!(steps.finish(), vec!["stuck", "stuck again"]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_state() {
+        let dir = "/tmp/reader_tests_shutdown";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = AppState::with_storage_dir(dir);
+        state.spawn_kv_maintenance();
+
+        state.kv_store.set_cache("shutdown_key", "kept", 0);
+        CookieManager::shared().set_cookie("shutdown.test", "sid", "42");
+        state
+            .storage
+            .write_json_debounced("pending.json", &vec!["debounced"])
+            .await
+            .unwrap();
+
+        assert!(state.shutdown().await.is_empty());
+
+        let kv = KvStore::new(FileStorage::new(dir), KV_FILE);
+        kv.load().await.unwrap();
+        assert_eq!(kv.get_cache("shutdown_key").as_deref(), Some("kept"));
+        let cookies = std::fs::read_to_string(state.storage.file_path("cookies.json")).unwrap();
+        assert!(cookies.contains("shutdown.test"));
+        // 直接读文件，避开 read_json 对未落盘内容的返回
+        let pending = std::fs::read_to_string(state.storage.file_path("pending.json")).unwrap();
+        assert!(pending.contains("debounced"));
+    }
+}
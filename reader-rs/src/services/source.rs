@@ -1,35 +1,141 @@
 use axum::response::sse::Event;
 use futures::stream::Stream;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+use crate::engine::anti_ban::UaRotation;
+use crate::engine::book_source::{transform_source, BookItem, BookSourceEngine, ExploreKind};
+use crate::engine::cookie::CookieManager;
+use crate::engine::engine_cache::EngineCache;
+use crate::engine::login::{self, LoginResult};
+use crate::engine::rule_cache::RuleCache;
+use crate::engine::rule_analyzer::RuleAnalyzer;
+use crate::engine::rule_context::{BookContext, ChapterContext};
+use crate::engine::rule_validator::{validate_rule, RuleValidation};
+use crate::engine::trace::{TraceCollector, TraceEntry, TraceStage};
+use super::source_health::{self, HealthPolicy, SourceHealthReport};
+use super::source_stats::{sort_by_weight, SearchOutcome, SourceStatInfo, SourceStats};
+use super::source_import::{claim_for_subscription, fetch_remote_sources, merge_sources, parse_sources, ImportReport};
+use super::source_test::{run_source_test, SourceTestEvent, SourceTestOptions};
+use super::shutdown::Shutdown;
+use super::ServiceError;
+use crate::engine::source_inspector::{self, SourceInspection};
 use crate::engine::source_rewriter::SourceRewriter;
-use crate::models::{BookSource, BookSourceFull};
+use crate::models::{BookSourceFull, SourceHealth, SourceScorecard, SourceSubscription};
 use crate::storage::FileStorage;
 
-use crate::storage::kv::KvStore;
+use crate::storage::kv::{KvStore, SOURCE_VARIABLE_KEY};
 
 /// 书源存储文件名
 const SOURCES_FILE: &str = "bookSources.json";
+/// 书源订阅存储文件名
+const SUBSCRIPTIONS_FILE: &str = "sourceSubscriptions.json";
+/// 自动更新的订阅距上次成功更新超过该时长 (毫秒) 后重新下载
+const SUBSCRIPTION_MAX_AGE_MS: i64 = 24 * 60 * 60 * 1000;
+/// 共享 Cookie (登录状态) 存储文件名
+const COOKIES_FILE: &str = "cookies.json";
+/// 登录时单次 JS 执行的超时，登录脚本常需多次请求，比默认值长
+const LOGIN_JS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 书源登录信息
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceLoginInfo {
+    pub book_source_url: String,
+    pub login_url: String,
+    /// loginUi 中的表单字段定义
+    pub login_ui: Vec<serde_json::Value>,
+}
+
+/// 书源变量及其填写说明
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceVariable {
+    pub book_source_url: String,
+    pub variable: String,
+    pub variable_comment: Option<String>,
+}
+
+/// 书源调试参数
+#[derive(Debug, Default, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugSourceRequest {
+    #[serde(default)]
+    pub book_source_url: String,
+    /// 完整书源 JSON，提供时优先于 bookSourceUrl (用于调试未保存的书源)
+    pub book_source: Option<serde_json::Value>,
+    #[serde(default)]
+    pub key: String,
+    /// 跳过搜索，直接从该书籍开始
+    pub book_url: Option<String>,
+    /// 正文阶段使用该章节，而非目录中的第一章
+    pub chapter_url: Option<String>,
+}
+
+/// 规则校验参数
+#[derive(Debug, Default, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateRuleRequest {
+    pub rule: String,
+    /// 样例内容，提供时返回规则在其上的提取结果预览
+    pub sample_content: Option<String>,
+    /// 无样例时用于判断规则类型：html / json / text
+    pub content_type: Option<String>,
+}
 
+#[derive(Clone)]
 pub struct SourceService {
     storage: FileStorage,
     sources: Arc<RwLock<Vec<BookSourceFull>>>,
     kv_store: Arc<KvStore>,
+    /// 按书源复用的书源引擎
+    engines: Arc<EngineCache>,
+    stats: SourceStats,
+    /// 判定书源失效的条件
+    health_policy: HealthPolicy,
+    /// 串行化订阅文件的读改写
+    subscriptions_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl SourceService {
     pub fn new() -> Self {
         let storage = FileStorage::default();
-        let kv_store = Arc::new(KvStore::new(storage.clone(), "kv_store.json"));
+        let kv_store = Arc::new(KvStore::new(storage.clone(), super::KV_FILE));
+        Self::with_storage(storage, kv_store)
+    }
+
+    pub fn with_storage(storage: FileStorage, kv_store: Arc<KvStore>) -> Self {
+        let sources = Arc::new(RwLock::new(Vec::new()));
         Self {
+            stats: SourceStats::new(storage.clone(), sources.clone()),
             storage,
-            sources: Arc::new(RwLock::new(Vec::new())),
+            sources,
+            engines: Arc::new(EngineCache::from_env(kv_store.clone())),
             kv_store,
+            health_policy: HealthPolicy::from_env(),
+            subscriptions_lock: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 
+    /// 使用指定的书源引擎缓存 (与书籍服务共享)
+    pub fn with_engines(mut self, engines: Arc<EngineCache>) -> Self {
+        self.engines = engines;
+        self
+    }
+
+    /// 书源响应统计 (与本服务共享书源缓存)
+    pub fn stats(&self) -> SourceStats {
+        self.stats.clone()
+    }
+
+    /// 书源缓存，供其他服务共享，使启用状态等修改立即生效
+    pub fn shared_sources(&self) -> Arc<RwLock<Vec<BookSourceFull>>> {
+        self.sources.clone()
+    }
+
     /// 初始化加载书源
     pub async fn init(&self) -> anyhow::Result<()> {
         // First, migrate any existing sources that still have java.* calls
@@ -38,6 +144,11 @@ impl SourceService {
         let sources: Vec<BookSourceFull> = self.storage.read_json_or_default(SOURCES_FILE).await;
         let mut cache = self.sources.write().await;
         *cache = sources;
+        drop(cache);
+        self.stats.reload().await;
+
+        let cookies = self.storage.read_json_or_default(COOKIES_FILE).await;
+        CookieManager::shared().restore(cookies);
         Ok(())
     }
 
@@ -118,6 +229,54 @@ impl SourceService {
         Ok(sources.clone())
     }
 
+    /// 获取所有书源，可按启用状态与健康状态过滤、按权重排序 (`sort=weight`)
+    pub async fn get_sources_sorted(
+        &self,
+        sort: Option<&str>,
+        enabled: Option<bool>,
+        health: Option<SourceHealth>,
+    ) -> Result<Vec<BookSourceFull>, anyhow::Error> {
+        let mut sources = self.get_all_sources().await?;
+        if let Some(enabled) = enabled {
+            sources.retain(|s| s.enabled == enabled);
+        }
+        if let Some(health) = health {
+            sources.retain(|s| s.health == health);
+        }
+        match sort.map(str::trim).filter(|s| !s.is_empty()) {
+            None => {}
+            Some("weight") => sort_by_weight(&mut sources),
+            Some(other) => {
+                return Err(ServiceError::invalid_input(format!("Unsupported sort: {}", other)).into())
+            }
+        }
+        Ok(sources)
+    }
+
+    /// 获取各书源的响应时间与成功/失败次数
+    pub async fn get_source_stats(&self) -> Result<Vec<SourceStatInfo>, anyhow::Error> {
+        let sources = self.get_all_sources().await?;
+        Ok(self.stats.summarize(&sources).await)
+    }
+
+    /// 各健康状态的书源数与最近失败的书源
+    pub async fn get_source_health_report(&self) -> Result<SourceHealthReport, anyhow::Error> {
+        let sources = self.get_all_sources().await?;
+        Ok(source_health::report(&sources))
+    }
+
+    /// 健康检查：测试最久未测试的已启用书源，返回其成绩单；没有可检查的书源时为 None
+    pub async fn check_next_source_health(&self) -> Result<Option<SourceScorecard>, anyhow::Error> {
+        let sources = self.get_all_sources().await?;
+        let Some(source) = source_health::next_to_check(&sources).cloned() else {
+            return Ok(None);
+        };
+        drop(sources);
+        let card = run_source_test(source, self.kv_store.clone(), &SourceTestOptions::default()).await;
+        self.record_test_result(&card, true).await?;
+        Ok(Some(card))
+    }
+
     /// 获取完整书源 (用于解析)
     pub async fn get_source_by_url(&self, source_url: &str) -> Option<BookSourceFull> {
         let sources = self.sources.read().await;
@@ -127,27 +286,6 @@ impl SourceService {
             .cloned()
     }
 
-    /// 获取可用书源
-    pub async fn get_available_sources(
-        &self,
-        _book_url: &str,
-        _refresh: bool,
-    ) -> Result<Vec<BookSource>, anyhow::Error> {
-        // TODO: 实现书源搜索匹配
-        Ok(vec![])
-    }
-
-    /// 切换书源
-    pub async fn set_book_source(
-        &self,
-        _book_url: &str,
-        _new_url: &str,
-        _source_url: &str,
-    ) -> Result<(), anyhow::Error> {
-        // TODO: 实现书源切换
-        Ok(())
-    }
-
     /// 搜索书源 (SSE)
     pub fn search_source_sse(
         &self,
@@ -156,7 +294,8 @@ impl SourceService {
         concurrent: usize,
     ) -> impl Stream<Item = Result<Event, Infallible>> {
         let sources_state = self.sources.clone();
-        let kv_store_state = self.kv_store.clone();
+        let engines_state = self.engines.clone();
+        let stats = self.stats.clone();
 
         async_stream::stream! {
             yield Ok(Event::default().data(r#"{"type":"start"}"#));
@@ -171,7 +310,7 @@ impl SourceService {
                          true
                      }
                 })
-                .filter(|s| !s.search_url.is_empty())
+                .filter(|s| s.enabled && !s.search_url.is_empty() && !stats.is_cooling_down(&s.book_source_url))
                 .cloned()
                 .collect();
             drop(sources_guard);
@@ -182,9 +321,11 @@ impl SourceService {
             let mut stream = futures::stream::iter(target_sources)
                 .map(move |source| {
                     let key = key.clone();
+                    let source_url = source.book_source_url.clone();
                     let source_name = source.book_source_name.clone();
-                    let kv_dist = kv_store_state.clone();
+                    let engines = engines_state.clone();
                     async move {
+                        let started = std::time::Instant::now();
                         // Wrap with 10 second timeout per source
                         let search_future = tokio::task::spawn_blocking(move || {
                             // Convert Model to Engine Source
@@ -193,7 +334,7 @@ impl SourceService {
                                 Err(_) => return None,
                             };
 
-                            let engine = match crate::engine::book_source::BookSourceEngine::new(engine_source, kv_dist) {
+                            let engine = match engines.engine(&engine_source) {
                                 Ok(e) => e,
                                 Err(_) => return None,
                             };
@@ -209,25 +350,27 @@ impl SourceService {
                             }
                         });
 
-                        match tokio::time::timeout(std::time::Duration::from_secs(10), search_future).await {
-                            Ok(Ok(result)) => result,
+                        let (result, outcome) = match tokio::time::timeout(std::time::Duration::from_secs(10), search_future).await {
+                            Ok(Ok(Some(result))) => (Some(result), SearchOutcome::Success),
+                            Ok(Ok(None)) => (None, SearchOutcome::Failure),
                             Ok(Err(e)) => {
                                 tracing::warn!("Search task failed for {}: {}", source_name, e);
-                                None
+                                (None, SearchOutcome::Failure)
                             },
                             Err(_) => {
                                 tracing::warn!("Search timeout for source: {}", source_name);
-                                None
+                                (None, SearchOutcome::Timeout)
                             }
-                        }
+                        };
+                        (source_url, started.elapsed().as_millis() as u64, outcome, result)
                     }
                 })
                 .buffer_unordered(concurrent);
 
             use futures::StreamExt;
 
-            while let Some(result) = stream.next().await {
-                // result is now Option directly (not Result from JoinHandle)
+            while let Some((source_url, respond_time, outcome, result)) = stream.next().await {
+                stats.record(&source_url, respond_time, outcome).await;
                 if let Some((origin_url, origin_name, books)) = result {
                     for book in books {
                         // Normalize for deduplication
@@ -255,6 +398,9 @@ impl SourceService {
                 }
             }
 
+            if let Err(e) = stats.persist().await {
+                tracing::warn!("Failed to save source stats: {}", e);
+            }
             yield Ok(Event::default().data(r#"{"type":"end"}"#));
         }
     }
@@ -280,6 +426,11 @@ impl SourceService {
 
         // 3. 反序列化为 BookSourceFull
         let source: BookSourceFull = serde_json::from_value(raw_source)?;
+        if let Some(mode) = source.ua_rotation.as_deref().filter(|m| !m.trim().is_empty()) {
+            if UaRotation::parse(mode).is_none() {
+                return Err(ServiceError::invalid_input(format!("Unsupported uaRotation: {}", mode)).into());
+            }
+        }
         let mut sources = self.sources.write().await;
 
         // 更新或添加
@@ -296,6 +447,23 @@ impl SourceService {
         Ok(())
     }
 
+    /// 批量启用或禁用书源，返回状态实际发生变化的书源数
+    pub async fn set_sources_enabled(&self, source_urls: &[String], enabled: bool) -> Result<usize, anyhow::Error> {
+        self.get_all_sources().await?;
+        let mut sources = self.sources.write().await;
+        let mut changed = 0;
+        for source in sources.iter_mut() {
+            if source.enabled != enabled && source_urls.contains(&source.book_source_url) {
+                source.enabled = enabled;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.storage.write_json(SOURCES_FILE, &*sources).await?;
+        }
+        Ok(changed)
+    }
+
     /// 删除书源
     pub async fn delete_source(&self, source_url: &str) -> Result<(), anyhow::Error> {
         let mut sources = self.sources.write().await;
@@ -306,12 +474,24 @@ impl SourceService {
 
     /// 批量导入书源
     ///
+    /// 按 bookSourceUrl 去重合并，跳过无效书源并返回导入报告；
+    /// dry_run 时只生成报告，不修改已有书源。
     /// 在导入时自动将 java.* 调用转译为 native.* 调用
-    pub async fn import_sources(&self, sources_json: &str) -> Result<i32, anyhow::Error> {
+    pub async fn import_sources(&self, sources_json: &str, dry_run: bool) -> Result<ImportReport, anyhow::Error> {
         // 1. 解析为原始 JSON Value
-        let mut raw_sources: Vec<serde_json::Value> = serde_json::from_str(sources_json)?;
-        let count = raw_sources.len() as i32;
+        let raw_sources = parse_sources(sources_json)?;
+        self.import_raw_sources(raw_sources, dry_run, None).await
+    }
 
+    /// 导入已解析的书源 JSON，见 [`Self::import_sources`]
+    ///
+    /// 指定订阅地址时按 [`claim_for_subscription`] 只导入新增的与已属于该订阅的书源。
+    async fn import_raw_sources(
+        &self,
+        mut raw_sources: Vec<serde_json::Value>,
+        dry_run: bool,
+        subscription: Option<&str>,
+    ) -> Result<ImportReport, anyhow::Error> {
         // 2. 转译 java.* 调用为 native.*
         let rewriter = SourceRewriter::new();
         let mut total_transpiled = 0;
@@ -331,39 +511,145 @@ impl SourceService {
             );
         }
 
-        // 3. 反序列化为 BookSourceFull
-        let new_sources: Vec<BookSourceFull> = raw_sources
-            .into_iter()
-            .filter_map(|v| serde_json::from_value(v).ok())
-            .collect();
+        // 3. 校验并合并 (先确保书源已加载，订阅认领与合并都要对照已有书源)
+        self.get_all_sources().await?;
+        let merge = |sources: &mut Vec<BookSourceFull>| {
+            let skipped = subscription.map_or(0, |url| claim_for_subscription(sources, &mut raw_sources, url));
+            ImportReport { skipped, ..merge_sources(sources, raw_sources) }
+        };
+        if dry_run {
+            let mut preview = self.sources.read().await.clone();
+            return Ok(merge(&mut preview));
+        }
 
         let mut sources = self.sources.write().await;
+        let report = merge(&mut sources);
+        if report.added + report.updated > 0 {
+            self.storage.write_json(SOURCES_FILE, &*sources).await?;
+        }
+        Ok(report)
+    }
 
-        for source in new_sources {
-            if let Some(pos) = sources
-                .iter()
-                .position(|s| s.book_source_url == source.book_source_url)
-            {
-                sources[pos] = source;
-            } else {
-                sources.push(source);
+    /// 从远程 URL 获取并保存书源
+    pub async fn save_from_remote_source(&self, url: &str) -> Result<ImportReport, anyhow::Error> {
+        let text = fetch_remote_sources(url).await?;
+        self.import_sources(&text, false).await
+    }
+
+    /// 获取全部书源订阅
+    pub async fn get_subscriptions(&self) -> Vec<SourceSubscription> {
+        self.storage.read_json_or_default(SUBSCRIPTIONS_FILE).await
+    }
+
+    /// 添加书源订阅，地址已存在时更新名称与自动更新设置
+    pub async fn add_subscription(
+        &self,
+        url: &str,
+        name: &str,
+        auto_update: bool,
+    ) -> Result<SourceSubscription, anyhow::Error> {
+        let url = url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(ServiceError::invalid_input(format!("Invalid subscription url: {}", url)).into());
+        }
+        let _guard = self.subscriptions_lock.lock().await;
+        let mut subscriptions = self.get_subscriptions().await;
+        let subscription = match subscriptions.iter_mut().find(|s| s.url == url) {
+            Some(existing) => existing,
+            None => {
+                subscriptions.push(SourceSubscription {
+                    url: url.to_string(),
+                    ..Default::default()
+                });
+                subscriptions.last_mut().unwrap()
             }
+        };
+        subscription.name = name.trim().to_string();
+        subscription.auto_update = auto_update;
+        let subscription = subscription.clone();
+        self.storage.write_json(SUBSCRIPTIONS_FILE, &subscriptions).await?;
+        Ok(subscription)
+    }
+
+    /// 下载订阅的书源文件并合并导入，新增的与已属于该订阅的书源带有订阅地址
+    ///
+    /// 更新时间与各类书源数记录在订阅上；失败时记录原因并返回错误。
+    pub async fn refresh_subscription(&self, url: &str) -> Result<SourceSubscription, anyhow::Error> {
+        if !self.get_subscriptions().await.iter().any(|s| s.url == url) {
+            return Err(ServiceError::not_found("Subscription", url).into());
         }
 
-        self.storage.write_json(SOURCES_FILE, &*sources).await?;
-        Ok(count)
+        let result = async {
+            let text = fetch_remote_sources(url).await?;
+            let raw_sources = parse_sources(&text)?;
+            self.import_raw_sources(raw_sources, false, Some(url)).await
+        }
+        .await;
+
+        let _guard = self.subscriptions_lock.lock().await;
+        let mut subscriptions = self.get_subscriptions().await;
+        // 刷新期间已被删除
+        let Some(subscription) = subscriptions.iter_mut().find(|s| s.url == url) else {
+            return Err(ServiceError::not_found("Subscription", url).into());
+        };
+        match &result {
+            Ok(report) => {
+                subscription.last_update_time = Some(chrono::Utc::now().timestamp_millis());
+                subscription.added = report.added;
+                subscription.updated = report.updated;
+                subscription.unchanged = report.unchanged;
+                subscription.invalid = report.invalid.len();
+                subscription.skipped = report.skipped;
+                subscription.last_error = None;
+            }
+            Err(e) => subscription.last_error = Some(format!("{:#}", e)),
+        }
+        let subscription = subscription.clone();
+        self.storage.write_json(SUBSCRIPTIONS_FILE, &subscriptions).await?;
+        result.map(|_| subscription)
     }
 
-    /// 从远程 URL 获取并保存书源
-    pub async fn save_from_remote_source(&self, url: &str) -> Result<i32, anyhow::Error> {
-        let client = reqwest::Client::new();
-        let resp = client.get(url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .send()
-            .await?;
+    /// 更新开启了自动更新、且从未成功更新或距上次成功更新已超过一天的订阅，返回更新成功的订阅数
+    pub async fn refresh_auto_subscriptions(&self, now: i64) -> usize {
+        let due = |s: &SourceSubscription| {
+            s.auto_update && s.last_update_time.is_none_or(|t| now - t >= SUBSCRIPTION_MAX_AGE_MS)
+        };
+        let mut refreshed = 0;
+        for subscription in self.get_subscriptions().await.into_iter().filter(due) {
+            match self.refresh_subscription(&subscription.url).await {
+                Ok(_) => refreshed += 1,
+                Err(e) => tracing::warn!("Failed to refresh subscription {}: {:#}", subscription.url, e),
+            }
+        }
+        refreshed
+    }
+
+    /// 删除书源订阅，`delete_sources` 时一并删除从该订阅导入的书源，返回删除的书源数
+    pub async fn delete_subscription(&self, url: &str, delete_sources: bool) -> Result<usize, anyhow::Error> {
+        {
+            let _guard = self.subscriptions_lock.lock().await;
+            let mut subscriptions = self.get_subscriptions().await;
+            let before = subscriptions.len();
+            subscriptions.retain(|s| s.url != url);
+            if subscriptions.len() == before {
+                return Err(ServiceError::not_found("Subscription", url).into());
+            }
+            self.storage.write_json(SUBSCRIPTIONS_FILE, &subscriptions).await?;
+        }
+        if !delete_sources {
+            return Ok(0);
+        }
 
-        let text = resp.text().await?;
-        self.import_sources(&text).await
+        // 确保书源已加载
+        self.get_all_sources().await?;
+        let mut sources = self.sources.write().await;
+        let before = sources.len();
+        sources.retain(|s| s.subscription_url.as_deref() != Some(url));
+        let removed = before - sources.len();
+        if removed > 0 {
+            self.storage.write_json(SOURCES_FILE, &*sources).await?;
+        }
+        Ok(removed)
     }
 
     /// 注入登录 Cookie
@@ -396,10 +682,97 @@ impl SourceService {
             tracing::info!("Injected cookies for source: {}", source_url);
             Ok(())
         } else {
-            Err(anyhow::anyhow!("Source not found: {}", source_url))
+            Err(ServiceError::not_found("Source", source_url).into())
         }
     }
 
+    /// 获取书源登录信息 (登录地址与 loginUi 表单定义)
+    pub async fn get_login_info(&self, source_url: &str) -> Result<SourceLoginInfo, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        let login_url = login_url_of(&source)?;
+        let login_ui = match source.login_ui.as_deref().map(str::trim).filter(|ui| !ui.is_empty()) {
+            Some(ui) => serde_json::from_str(ui)
+                .map_err(|e| ServiceError::invalid_input(format!("invalid loginUi JSON: {}", e)))?,
+            None => Vec::new(),
+        };
+        Ok(SourceLoginInfo {
+            book_source_url: source.book_source_url,
+            login_url,
+            login_ui,
+        })
+    }
+
+    /// 获取书源变量 (规则中 `source.getVariable()` 的值)
+    pub async fn get_source_variable(&self, source_url: &str) -> Result<SourceVariable, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        let variable = self
+            .kv_store
+            .get_source_var(&source.book_source_url, SOURCE_VARIABLE_KEY)
+            .unwrap_or_default();
+        Ok(SourceVariable {
+            book_source_url: source.book_source_url,
+            variable,
+            variable_comment: source.variable_comment,
+        })
+    }
+
+    /// 保存书源变量并立即写盘
+    pub async fn save_source_variable(&self, source_url: &str, variable: &str) -> Result<(), anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        self.kv_store
+            .set_source_var(&source.book_source_url, SOURCE_VARIABLE_KEY, variable);
+        self.kv_store.flush().await
+    }
+
+    /// 执行书源登录，获得的 Cookie 写入共享 Cookie 并持久化
+    pub async fn login_source(
+        &self,
+        source_url: &str,
+        fields: HashMap<String, String>,
+    ) -> Result<LoginResult, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        login_url_of(&source)?;
+
+        let kv_dist = self.kv_store.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let engine_source: crate::engine::book_source::BookSource =
+                serde_json::from_value(serde_json::to_value(&source)?)?;
+            let engine = BookSourceEngine::new(engine_source, kv_dist)?;
+            engine.set_js_timeout(LOGIN_JS_TIMEOUT);
+            engine.login(&fields)
+        })
+        .await??;
+
+        self.save_cookies().await?;
+        tracing::info!("Login {} for source: {}", if result.success { "succeeded" } else { "failed" }, source_url);
+        Ok(result)
+    }
+
+    /// 退出书源登录，清除其域名下的 Cookie
+    pub async fn logout_source(&self, source_url: &str) -> Result<(), anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        login::logout(
+            &source.book_source_url,
+            source.login_url.as_deref(),
+            &CookieManager::shared(),
+        );
+        self.save_cookies().await
+    }
+
+    /// 持久化共享 Cookie
+    async fn save_cookies(&self) -> Result<(), anyhow::Error> {
+        self.storage
+            .write_json(COOKIES_FILE, &CookieManager::shared().snapshot())
+            .await
+    }
+
+    /// 清空书源规则编译缓存，返回删除的缓存条目数
+    pub async fn clear_rule_cache(&self) -> Result<usize, anyhow::Error> {
+        let removed = tokio::task::spawn_blocking(|| RuleCache::open_default().clear()).await??;
+        tracing::info!("Cleared {} rule cache entries", removed);
+        Ok(removed)
+    }
+
     /// 检测书源有效性
     pub async fn check_source(&self, source_url: &str) -> Result<bool, anyhow::Error> {
         let sources = self.sources.read().await;
@@ -408,7 +781,7 @@ impl SourceService {
             .iter()
             .find(|s| s.book_source_url == source_url)
             .cloned()
-            .ok_or_else(|| anyhow::anyhow!("Source not found: {}", source_url))?;
+            .ok_or_else(|| ServiceError::not_found("Source", source_url))?;
 
         drop(sources);
 
@@ -425,6 +798,324 @@ impl SourceService {
 
         Ok(result)
     }
+
+    /// 获取可用于发现的书源 (已启用且开启发现)
+    pub async fn get_explore_sources(&self) -> Result<Vec<BookSourceFull>, anyhow::Error> {
+        let mut sources = self.get_all_sources().await?;
+        sources.retain(|s| can_explore(s) && !s.explore_url.is_empty());
+        Ok(sources)
+    }
+
+    /// 获取发现分类
+    pub async fn get_explore_kinds(
+        &self,
+        source_url: &str,
+    ) -> Result<Vec<ExploreKind>, anyhow::Error> {
+        let source = self.find_explore_source(source_url).await?;
+        let kv_dist = self.kv_store.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let engine_source: crate::engine::book_source::BookSource =
+                serde_json::from_value(serde_json::to_value(&source)?)?;
+
+            let engine = crate::engine::book_source::BookSourceEngine::new(engine_source, kv_dist)?;
+            engine.explore_kinds()
+        })
+        .await?
+    }
+
+    /// 发现书籍
+    pub async fn explore_books(
+        &self,
+        source_url: &str,
+        rule_find_url: &str,
+        page: i32,
+    ) -> Result<Vec<BookItem>, anyhow::Error> {
+        let source = self.find_explore_source(source_url).await?;
+        let kv_dist = self.kv_store.clone();
+        let rule_find_url = rule_find_url.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let engine_source: crate::engine::book_source::BookSource =
+                serde_json::from_value(serde_json::to_value(&source)?)?;
+
+            let engine = crate::engine::book_source::BookSourceEngine::new(engine_source, kv_dist)?;
+            engine.explore(&rule_find_url, page)
+        })
+        .await?
+    }
+
+    /// 调试书源：依次执行搜索、选取首个结果、详情、目录首页、正文，返回逐步跟踪记录
+    pub async fn debug_source(&self, req: DebugSourceRequest) -> Result<Vec<TraceEntry>, anyhow::Error> {
+        let book_url = req.book_url.filter(|u| !u.trim().is_empty());
+        let chapter_url = req.chapter_url.filter(|u| !u.trim().is_empty());
+        if req.key.trim().is_empty() && book_url.is_none() && chapter_url.is_none() {
+            return Err(ServiceError::invalid_input("key, bookUrl or chapterUrl is required").into());
+        }
+        let source = match req.book_source {
+            Some(source) => source,
+            None => serde_json::to_value(self.find_source(&req.book_source_url).await?)?,
+        };
+
+        let kv_dist = self.kv_store.clone();
+        let key = req.key;
+        tokio::task::spawn_blocking(move || {
+            let engine_source: crate::engine::book_source::BookSource = serde_json::from_value(source)
+                .map_err(|e| ServiceError::invalid_input(format!("Invalid book source: {}", e)))?;
+            let mut engine = BookSourceEngine::new(engine_source, kv_dist)?;
+            let trace = TraceCollector::new();
+            engine.set_trace(trace.clone());
+            engine.set_max_toc_pages(1);
+
+            run_debug(&engine, &trace, key.trim(), book_url, chapter_url);
+            Ok(trace.entries())
+        })
+        .await?
+    }
+
+    /// 校验规则语法 (不发起请求、不执行 JS)，有样例内容时附带提取结果预览
+    pub async fn validate_rule(&self, req: ValidateRuleRequest) -> Result<RuleValidation, anyhow::Error> {
+        if req.rule.trim().is_empty() {
+            return Err(ServiceError::invalid_input("rule is required").into());
+        }
+        let kv_dist = self.kv_store.clone();
+        tokio::task::spawn_blocking(move || {
+            let sample = req.sample_content.filter(|s| !s.is_empty());
+            let content = match (&sample, req.content_type.as_deref()) {
+                (Some(sample), _) => sample.as_str(),
+                (None, Some("json")) => "{}",
+                (None, Some("html")) => "<html></html>",
+                _ => "",
+            };
+            let validation = validate_rule(&req.rule, content);
+            match sample {
+                Some(sample) => {
+                    let analyzer = RuleAnalyzer::new(kv_dist)?;
+                    Ok(validation.with_preview(&analyzer, &req.rule, &sample))
+                }
+                None => Ok(validation),
+            }
+        })
+        .await?
+    }
+
+    /// 查看书源规则的编译结果 (读取或生成规则编译缓存，不创建引擎)
+    pub async fn inspect_source(&self, source_url: &str) -> Result<SourceInspection, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        tokio::task::spawn_blocking(move || {
+            let engine_source: crate::engine::book_source::BookSource =
+                serde_json::from_value(serde_json::to_value(&source)?)?;
+            let transformed = transform_source(&engine_source)
+                .ok_or_else(|| ServiceError::invalid_input("Source rules could not be compiled"))?;
+            Ok(source_inspector::inspect(&transformed))
+        })
+        .await?
+    }
+
+    /// 测试书源：搜索 → 详情 → 目录 → 首章正文，返回成绩单并保存到书源上
+    pub async fn test_source(
+        &self,
+        source_url: &str,
+        options: &SourceTestOptions,
+    ) -> Result<SourceScorecard, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        let card = run_source_test(source, self.kv_store.clone(), options).await;
+        self.record_test_result(&card, false).await?;
+        Ok(card)
+    }
+
+    /// 批量测试书源的事件流，最多同时测试 concurrent 个，逐个推送成绩单
+    ///
+    /// 未找到的书源 URL 会被忽略。
+    pub fn test_source_events(
+        &self,
+        source_urls: Vec<String>,
+        concurrent: usize,
+        options: SourceTestOptions,
+    ) -> impl Stream<Item = SourceTestEvent> {
+        let service = self.clone();
+
+        async_stream::stream! {
+            use futures::StreamExt;
+
+            let sources: Vec<BookSourceFull> = match service.get_all_sources().await {
+                Ok(sources) => sources
+                    .into_iter()
+                    .filter(|s| source_urls.contains(&s.book_source_url))
+                    .collect(),
+                Err(e) => {
+                    tracing::warn!("Failed to load sources for testing: {}", e);
+                    Vec::new()
+                }
+            };
+            let total = sources.len();
+            let options = &options;
+            let kv_store = service.kv_store.clone();
+            let mut results = futures::stream::iter(sources)
+                .map(|source| run_source_test(source, kv_store.clone(), options))
+                .buffer_unordered(concurrent.max(1));
+
+            let (mut current, mut passed) = (0, 0);
+            while let Some(card) = results.next().await {
+                current += 1;
+                passed += card.passed as usize;
+                if let Err(e) = service.record_test_result(&card, true).await {
+                    tracing::warn!("Failed to save test result for {}: {}", card.book_source_url, e);
+                }
+                yield SourceTestEvent::Tested { current, total, scorecard: Box::new(card) };
+            }
+            if let Err(e) = service.storage.flush_file(SOURCES_FILE).await {
+                tracing::warn!("Failed to save test results: {}", e);
+            }
+            yield SourceTestEvent::Done { passed, total };
+        }
+    }
+
+    /// 将测试结果保存到书源上并更新健康状态，批量测试时防抖写入
+    async fn record_test_result(&self, card: &SourceScorecard, debounced: bool) -> Result<(), anyhow::Error> {
+        let mut sources = self.sources.write().await;
+        let Some(source) = sources.iter_mut().find(|s| s.book_source_url == card.book_source_url) else {
+            // 测试期间已被删除
+            return Ok(());
+        };
+        let (summary, health) = self.health_policy.record(source.last_test.as_ref(), card);
+        if health != source.health {
+            tracing::info!(
+                "Source {} health changed from {:?} to {:?} after {} consecutive failures",
+                source.book_source_name,
+                source.health,
+                health,
+                summary.consecutive_failures
+            );
+        }
+        source.last_test = Some(summary);
+        source.health = health;
+        if debounced {
+            self.storage.write_json_debounced(SOURCES_FILE, &*sources).await
+        } else {
+            self.storage.write_json(SOURCES_FILE, &*sources).await
+        }
+    }
+
+    /// 按 URL 查找书源 (缓存为空时先从文件加载)
+    async fn find_source(&self, source_url: &str) -> Result<BookSourceFull, anyhow::Error> {
+        self.get_all_sources()
+            .await?
+            .into_iter()
+            .find(|s| s.book_source_url == source_url)
+            .ok_or_else(|| ServiceError::not_found("Source", source_url).into())
+    }
+
+    /// 按 URL 查找可用于发现的书源
+    async fn find_explore_source(&self, source_url: &str) -> Result<BookSourceFull, anyhow::Error> {
+        let source = self.find_source(source_url).await?;
+        if !can_explore(&source) {
+            return Err(ServiceError::source_disabled(source_url).into());
+        }
+        Ok(source)
+    }
+}
+
+impl Shutdown for SourceService {
+    /// 书源统计交给防抖写入 (随后由 FileStorage 落盘)，共享 Cookie 立即写入
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.stats.persist().await?;
+        self.save_cookies().await
+    }
+}
+
+/// 书源的登录地址，未配置时报错
+fn login_url_of(source: &BookSourceFull) -> Result<String, ServiceError> {
+    source
+        .login_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| ServiceError::invalid_input("book source has no loginUrl"))
+}
+
+/// 书源已启用且开启了发现
+fn can_explore(source: &BookSourceFull) -> bool {
+    source.enabled && source.enabled_explore
+}
+
+/// 调试流程，任一阶段失败时记录错误并停止
+fn run_debug(
+    engine: &BookSourceEngine,
+    trace: &TraceCollector,
+    key: &str,
+    book_url: Option<String>,
+    chapter_url: Option<String>,
+) {
+    let fail = |e: anyhow::Error| trace.error(format!("{:#}", e));
+
+    let book_url = match book_url {
+        Some(url) => {
+            trace.set_stage(TraceStage::Search);
+            trace.message("Skipped: bookUrl provided");
+            Some(url)
+        }
+        None if key.is_empty() => None,
+        None => {
+            trace.set_stage(TraceStage::Search);
+            let books = match engine.search(key, 1) {
+                Ok(books) => books,
+                Err(e) => return fail(e),
+            };
+            trace.message(format!("{} results for \"{}\"", books.len(), key));
+
+            trace.set_stage(TraceStage::Pick);
+            let Some(book) = books.into_iter().next() else {
+                return trace.error("No search results");
+            };
+            trace.message(format!("{} / {} -> {}", book.name, book.author, book.book_url));
+            engine.set_book(BookContext::from(&book));
+            Some(book.book_url)
+        }
+    };
+
+    let chapter_url = match (chapter_url, book_url) {
+        (Some(url), _) => Some(url),
+        (None, Some(book_url)) => {
+            trace.set_stage(TraceStage::BookInfo);
+            let info = match engine.get_book_info(&book_url) {
+                Ok(info) => info,
+                Err(e) => return fail(e),
+            };
+            trace.message(format!("{} / {}", info.name, info.author));
+            let toc_url = info
+                .toc_url
+                .filter(|u| !u.trim().is_empty())
+                .unwrap_or(book_url);
+
+            trace.set_stage(TraceStage::Toc);
+            let chapters = match engine.get_chapters(&toc_url) {
+                Ok(chapters) => chapters,
+                Err(e) => return fail(e),
+            };
+            trace.message(format!("{} chapters on the first page", chapters.len()));
+            let Some(first) = chapters.into_iter().next() else {
+                return trace.error("No chapters found");
+            };
+            trace.message(format!("{} -> {}", first.title, first.url));
+            engine.set_chapter(ChapterContext {
+                title: first.title,
+                url: first.url.clone(),
+                index: 0,
+            });
+            Some(first.url)
+        }
+        (None, None) => None,
+    };
+
+    if let Some(chapter_url) = chapter_url {
+        trace.set_stage(TraceStage::Content);
+        match engine.get_content(&chapter_url) {
+            Ok(content) => trace.message(format!("{} characters", content.chars().count())),
+            Err(e) => fail(e),
+        }
+    }
 }
 
 impl Default for SourceService {
@@ -432,3 +1123,233 @@ impl Default for SourceService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_server::{request_path, spawn_concurrent_server, spawn_server};
+    use crate::engine::trace::TraceEvent;
+    use futures::StreamExt;
+
+    /// 按路径 (忽略查询参数) 返回固定页面
+    fn spawn_site(pages: Vec<(&'static str, &'static str)>) -> String {
+        spawn_server(move |head, _| {
+            let target = request_path(head);
+            let path = target.split('?').next().unwrap_or(target);
+            let body = pages.iter().find(|(p, _)| *p == path).map(|(_, b)| *b).unwrap_or("");
+            (200, vec![("Content-Type", "text/html; charset=utf-8".to_string())], body)
+        })
+    }
+
+    /// 使用 CSS 规则的测试书源
+    fn css_source(base: &str, name: &str) -> serde_json::Value {
+        serde_json::json!({
+            "bookSourceUrl": base,
+            "bookSourceName": name,
+            "searchUrl": "/search?q={{key}}",
+            "ruleSearch": {
+                "bookList": "@css:div.book",
+                "name": "@css:a.name@text",
+                "author": "@css:span.author@text",
+                "bookUrl": "@css:a.name@href"
+            },
+            "ruleBookInfo": {
+                "name": "@css:h1@text",
+                "author": "@css:p.author@text",
+                "tocUrl": "@css:#toc@href"
+            },
+            "ruleToc": {
+                "chapterList": "@css:ul li a",
+                "chapterName": "@css:a@text",
+                "chapterUrl": "@css:a@href"
+            },
+            "ruleContent": {
+                "content": "@css:#content@text"
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_debug_source_traces_all_stages() {
+        let base = spawn_site(vec![
+            ("/search", r#"<div class="book"><a class="name" href="/book/1">调试之书</a><span class="author">作者甲</span></div>"#),
+            ("/book/1", r#"<h1>调试之书</h1><p class="author">作者甲</p><a id="toc" href="/toc/1">目录</a>"#),
+            ("/toc/1", r#"<ul><li><a href="/c/1">第一章</a></li><li><a href="/c/2">第二章</a></li></ul>"#),
+            ("/c/1", r#"<div id="content">第一章的正文内容</div>"#),
+        ]);
+        let source = css_source(&base, "Debug Source");
+
+        let storage = FileStorage::new("/tmp/reader_tests_debug_source");
+        let kv_store = Arc::new(KvStore::new(storage.clone(), crate::services::KV_FILE));
+        let service = SourceService::with_storage(storage, kv_store);
+        let trace = service
+            .debug_source(DebugSourceRequest {
+                book_source: Some(source),
+                key: "调试".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for stage in [
+            TraceStage::Search,
+            TraceStage::Pick,
+            TraceStage::BookInfo,
+            TraceStage::Toc,
+            TraceStage::Content,
+        ] {
+            assert!(trace.iter().any(|e| e.stage == stage), "missing stage {:?}", stage);
+        }
+        assert!(!trace.iter().any(|e| matches!(e.event, TraceEvent::Error { .. })));
+
+        let search_request = trace
+            .iter()
+            .find_map(|e| match &e.event {
+                TraceEvent::Request { url, method, response_length, .. } if e.stage == TraceStage::Search => {
+                    Some((url.clone(), method.clone(), *response_length))
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert!(search_request.0.contains("/search?q="));
+        assert_eq!(search_request.1, "GET");
+        assert!(search_request.2 > 0);
+
+        assert!(trace.iter().any(|e| matches!(
+            &e.event,
+            TraceEvent::Rule { rule_type, value_preview, .. }
+                if e.stage == TraceStage::Content && rule_type == "Css" && value_preview.contains("正文内容")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_import_dry_run_leaves_storage_untouched() {
+        let dir = "/tmp/reader_tests_import_dry_run";
+        let _ = std::fs::remove_dir_all(dir);
+        let storage = FileStorage::new(dir);
+        let kv_store = Arc::new(KvStore::new(storage.clone(), crate::services::KV_FILE));
+        let service = SourceService::with_storage(storage.clone(), kv_store);
+
+        let initial = r#"[{"bookSourceUrl":"https://a.com","bookSourceName":"A"}]"#;
+        let report = service.import_sources(initial, false).await.unwrap();
+        assert_eq!(report.added, 1);
+        let before: String = storage.read_file_or_default(SOURCES_FILE).await;
+
+        let overlapping = r#"[
+            {"bookSourceUrl":"https://a.com","bookSourceName":"A2"},
+            {"bookSourceUrl":"https://b.com","bookSourceName":"B"},
+            {"bookSourceName":"no url"}
+        ]"#;
+        let report = service.import_sources(overlapping, true).await.unwrap();
+        assert_eq!((report.added, report.updated, report.unchanged, report.invalid.len()), (1, 1, 0, 1));
+
+        let after: String = storage.read_file_or_default(SOURCES_FILE).await;
+        assert_eq!(before, after);
+        let sources = service.get_all_sources().await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].book_source_name, "A");
+    }
+
+    const SEARCH_PAGE: &str = r#"<div class="book"><a class="name" href="/book/1">测试之书</a><span class="author">作者</span></div>"#;
+    const INFO_PAGE: &str = r#"<h1>测试之书</h1><p class="author">作者</p><a id="toc" href="/toc/1">目录</a>"#;
+    const TOC_PAGE: &str = r#"<ul><li><a href="/c/1">第一章</a></li><li><a href="/c/2">第二章</a></li></ul>"#;
+
+    /// 延迟 2 秒才返回空页面的站点
+    fn spawn_slow_site() -> String {
+        spawn_concurrent_server(|_, _| {
+            std::thread::sleep(std::time::Duration::from_secs(2));
+            (200, vec![], "")
+        })
+    }
+
+    fn stage_statuses(card: &SourceScorecard) -> Vec<crate::models::StageStatus> {
+        card.stages.iter().map(|s| s.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_source_scorecard_stops_at_failed_stage() {
+        use crate::models::{SourceTestStage, StageStatus::*};
+
+        let content = "正文".repeat(150);
+        let content_page: &'static str = Box::leak(format!(r#"<div id="content">{}</div>"#, content).into_boxed_str());
+        let good = spawn_site(vec![
+            ("/search", SEARCH_PAGE),
+            ("/book/1", INFO_PAGE),
+            ("/toc/1", TOC_PAGE),
+            ("/c/1", content_page),
+        ]);
+        let no_results = spawn_site(vec![("/search", "<p>没有结果</p>")]);
+        let empty_toc = spawn_site(vec![("/search", SEARCH_PAGE), ("/book/1", INFO_PAGE), ("/toc/1", "<ul></ul>")]);
+        let empty_content = spawn_site(vec![
+            ("/search", SEARCH_PAGE),
+            ("/book/1", INFO_PAGE),
+            ("/toc/1", TOC_PAGE),
+            ("/c/1", r#"<div id="other">广告</div>"#),
+        ]);
+        let slow = spawn_slow_site();
+
+        let dir = "/tmp/reader_tests_source_scorecard";
+        let _ = std::fs::remove_dir_all(dir);
+        let storage = FileStorage::new(dir);
+        let kv_store = Arc::new(KvStore::new(storage.clone(), crate::services::KV_FILE));
+        let service = SourceService::with_storage(storage.clone(), kv_store);
+        let sources: Vec<_> = [&good, &no_results, &empty_toc, &empty_content, &slow]
+            .iter()
+            .map(|base| css_source(base, base))
+            .collect();
+        service
+            .import_sources(&serde_json::to_string(&sources).unwrap(), false)
+            .await
+            .unwrap();
+
+        let options = SourceTestOptions {
+            key: "测试".to_string(),
+            stage_timeout: Some(1),
+        };
+        let card = service.test_source(&good, &options).await.unwrap();
+        assert!(card.passed);
+        assert_eq!(card.stages.iter().map(|s| s.stage).collect::<Vec<_>>(), SourceTestStage::ALL);
+        assert_eq!(stage_statuses(&card), vec![Passed; 4]);
+        assert_eq!(card.book_name.as_deref(), Some("测试之书"));
+        assert_eq!(card.chapter_count, Some(2));
+        assert_eq!(card.content_sample.as_ref().unwrap().chars().count(), 200);
+
+        // 批量测试：各书源在不同阶段失败，响应过慢的书源按超时处理
+        let started = std::time::Instant::now();
+        let urls = vec![no_results.clone(), empty_toc.clone(), empty_content.clone(), slow.clone()];
+        let events: Vec<SourceTestEvent> = service.test_source_events(urls, 4, options).collect().await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        let cards: HashMap<String, SourceScorecard> = events
+            .iter()
+            .filter_map(|e| match e {
+                SourceTestEvent::Tested { scorecard, total, .. } => {
+                    assert_eq!(*total, 4);
+                    Some((scorecard.book_source_url.clone(), (**scorecard).clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert!(matches!(events.last(), Some(SourceTestEvent::Done { passed: 0, total: 4 })));
+        assert_eq!(stage_statuses(&cards[&no_results]), vec![Failed, Skipped, Skipped, Skipped]);
+        assert!(cards[&no_results].stages[0].error.as_ref().unwrap().contains("no search results"));
+        assert_eq!(stage_statuses(&cards[&empty_toc]), vec![Passed, Passed, Failed, Skipped]);
+        assert_eq!(stage_statuses(&cards[&empty_content]), vec![Passed, Passed, Passed, Failed]);
+        assert_eq!(cards[&empty_content].chapter_count, Some(2));
+        assert_eq!(stage_statuses(&cards[&slow]), vec![Timeout, Skipped, Skipped, Skipped]);
+
+        // 测试结果保存在书源上
+        let reloaded = SourceService::with_storage(storage.clone(), service.kv_store.clone());
+        let sources = reloaded.get_all_sources().await.unwrap();
+        let last_test = |url: &str| {
+            sources
+                .iter()
+                .find(|s| s.book_source_url == url)
+                .and_then(|s| s.last_test.clone())
+                .unwrap()
+        };
+        assert!(last_test(&good).passed);
+        assert_eq!(last_test(&empty_toc).failed_stage, Some(SourceTestStage::Toc));
+        assert_eq!(last_test(&slow).failed_stage, Some(SourceTestStage::Search));
+    }
+}
@@ -0,0 +1,269 @@
+//! 书源健康检查
+//!
+//! 后台任务按固定速率轮流测试已启用的书源 (最久未测试的优先)，与手动测试共用同一成绩单。
+//! 连续失败达到次数且持续足够长的时间后标记为 broken，之后任意一次测试通过即恢复。
+
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::models::{BookSourceFull, SourceHealth, SourceScorecard, SourceTestStage, SourceTestSummary};
+
+/// 判定失效所需的默认连续失败次数
+const DEFAULT_BROKEN_FAILURES: u32 = 3;
+
+/// 判定失效所需的默认连续失败持续天数
+const DEFAULT_BROKEN_DAYS: u64 = 3;
+
+/// 报告中列出的最近失败书源数
+const RECENT_FAILURES_LIMIT: usize = 20;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// 健康检查间隔，由环境变量 SOURCE_HEALTH_CHECKS_PER_HOUR (每小时检查的书源数，如 20) 配置
+///
+/// 未设置或为 0 时不启用。
+pub fn check_interval() -> Option<Duration> {
+    let per_hour = std::env::var("SOURCE_HEALTH_CHECKS_PER_HOUR")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    (per_hour > 0).then(|| Duration::from_secs((3600 / per_hour).max(1)))
+}
+
+/// 判定书源失效的条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthPolicy {
+    /// 连续失败次数
+    pub failures: u32,
+    /// 从第一次失败到最近一次失败至少经过的时间 (毫秒)
+    pub min_span_ms: i64,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failures: DEFAULT_BROKEN_FAILURES,
+            min_span_ms: DEFAULT_BROKEN_DAYS as i64 * DAY_MS,
+        }
+    }
+}
+
+impl HealthPolicy {
+    /// 由环境变量 SOURCE_HEALTH_BROKEN_FAILURES 与 SOURCE_HEALTH_BROKEN_DAYS 配置
+    pub fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        let default = Self::default();
+        Self {
+            failures: env("SOURCE_HEALTH_BROKEN_FAILURES")
+                .map(|n| n.clamp(1, u32::MAX as u64) as u32)
+                .unwrap_or(default.failures),
+            min_span_ms: env("SOURCE_HEALTH_BROKEN_DAYS")
+                .map(|days| days as i64 * DAY_MS)
+                .unwrap_or(default.min_span_ms),
+        }
+    }
+
+    /// 在上一次测试结果的基础上记录本次测试，返回新的测试结果与健康状态
+    ///
+    /// 本功能之前保存的失败结果没有连续失败次数，按一次计。
+    pub fn record(&self, previous: Option<&SourceTestSummary>, card: &SourceScorecard) -> (SourceTestSummary, SourceHealth) {
+        let mut summary = SourceTestSummary::from(card);
+        if !card.passed {
+            let failing = previous.filter(|p| !p.passed);
+            summary.consecutive_failures = failing.map_or(0, |p| p.consecutive_failures.max(1)) + 1;
+            summary.failing_since = Some(
+                failing
+                    .map(|p| p.failing_since.unwrap_or(p.tested_at))
+                    .unwrap_or(card.tested_at),
+            );
+        }
+        let health = self.health(&summary);
+        (summary, health)
+    }
+
+    /// 由测试结果判断健康状态
+    pub fn health(&self, summary: &SourceTestSummary) -> SourceHealth {
+        if summary.passed {
+            return SourceHealth::Healthy;
+        }
+        let span = summary.tested_at - summary.failing_since.unwrap_or(summary.tested_at);
+        if summary.consecutive_failures >= self.failures && span >= self.min_span_ms {
+            SourceHealth::Broken
+        } else {
+            SourceHealth::Degraded
+        }
+    }
+}
+
+/// 下一个要检查的书源：已启用且可搜索的书源中最久未测试的
+pub fn next_to_check(sources: &[BookSourceFull]) -> Option<&BookSourceFull> {
+    sources
+        .iter()
+        .filter(|s| s.enabled && !s.search_url.is_empty())
+        .min_by_key(|s| s.last_test.as_ref().map_or(i64::MIN, |t| t.tested_at))
+}
+
+/// 最近一次测试失败的书源
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceFailure {
+    pub book_source_url: String,
+    pub book_source_name: String,
+    pub health: SourceHealth,
+    pub consecutive_failures: u32,
+    pub failed_stage: Option<SourceTestStage>,
+    pub error: Option<String>,
+    /// 测试时间 (毫秒时间戳)
+    pub tested_at: i64,
+}
+
+/// 书源健康报告
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceHealthReport {
+    pub healthy: usize,
+    pub degraded: usize,
+    pub broken: usize,
+    /// 最近一次测试失败的书源，按测试时间从新到旧
+    pub recent_failures: Vec<SourceFailure>,
+}
+
+/// 汇总各书源的健康状态，未测试过的书源计为 healthy
+pub fn report(sources: &[BookSourceFull]) -> SourceHealthReport {
+    let mut report = SourceHealthReport::default();
+    for source in sources {
+        match source.health {
+            SourceHealth::Healthy => report.healthy += 1,
+            SourceHealth::Degraded => report.degraded += 1,
+            SourceHealth::Broken => report.broken += 1,
+        }
+        if let Some(test) = source.last_test.as_ref().filter(|t| !t.passed) {
+            report.recent_failures.push(SourceFailure {
+                book_source_url: source.book_source_url.clone(),
+                book_source_name: source.book_source_name.clone(),
+                health: source.health,
+                consecutive_failures: test.consecutive_failures.max(1),
+                failed_stage: test.failed_stage,
+                error: test.error.clone(),
+                tested_at: test.tested_at,
+            });
+        }
+    }
+    report.recent_failures.sort_by_key(|f| std::cmp::Reverse(f.tested_at));
+    report.recent_failures.truncate(RECENT_FAILURES_LIMIT);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{StageResult, StageStatus};
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    fn card(passed: bool, tested_at: i64) -> SourceScorecard {
+        let status = if passed { StageStatus::Passed } else { StageStatus::Failed };
+        SourceScorecard {
+            book_source_url: "https://a.example".to_string(),
+            book_source_name: "A".to_string(),
+            passed,
+            stages: vec![StageResult {
+                stage: SourceTestStage::Search,
+                status,
+                elapsed_ms: 10,
+                error: (!passed).then(|| "HTTP 404".to_string()),
+            }],
+            key: "我的".to_string(),
+            book_name: None,
+            chapter_count: None,
+            content_sample: None,
+            elapsed_ms: 10,
+            tested_at,
+        }
+    }
+
+    /// 依次记录测试结果，返回每次之后的健康状态
+    fn run(policy: &HealthPolicy, checks: &[(bool, i64)]) -> Vec<SourceHealth> {
+        let mut last: Option<SourceTestSummary> = None;
+        checks
+            .iter()
+            .map(|&(passed, at)| {
+                let (summary, health) = policy.record(last.as_ref(), &card(passed, at));
+                last = Some(summary);
+                health
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_broken_after_failures_over_days() {
+        use SourceHealth::*;
+        let policy = HealthPolicy::default();
+        // 三次失败但只跨越两天：仍为 degraded，第四次失败满三天后失效
+        let checks = [(false, 0), (false, 24 * HOUR_MS), (false, 48 * HOUR_MS), (false, 72 * HOUR_MS)];
+        assert_eq!(run(&policy, &checks), vec![Degraded, Degraded, Degraded, Broken]);
+
+        // 跨越三天但只失败两次：不足次数
+        assert_eq!(run(&policy, &[(false, 0), (false, 96 * HOUR_MS)]), vec![Degraded, Degraded]);
+    }
+
+    #[test]
+    fn test_success_resets_streak() {
+        use SourceHealth::*;
+        let policy = HealthPolicy::default();
+        let day = 24 * HOUR_MS;
+        let checks = [(false, 0), (false, 2 * day), (false, 4 * day), (true, 5 * day), (false, 6 * day), (false, 9 * day)];
+        assert_eq!(run(&policy, &checks), vec![Degraded, Degraded, Broken, Healthy, Degraded, Degraded]);
+
+        let mut last = None;
+        for &(passed, at) in &checks {
+            last = Some(policy.record(last.as_ref(), &card(passed, at)).0);
+        }
+        let last = last.unwrap();
+        assert_eq!((last.consecutive_failures, last.failing_since), (2, Some(6 * day)));
+    }
+
+    #[test]
+    fn test_legacy_failure_counts_once() {
+        let policy = HealthPolicy { failures: 2, min_span_ms: 0 };
+        let legacy = SourceTestSummary::from(&card(false, 100));
+        assert_eq!(legacy.consecutive_failures, 0);
+        let (summary, health) = policy.record(Some(&legacy), &card(false, 200));
+        assert_eq!((summary.consecutive_failures, summary.failing_since), (2, Some(100)));
+        assert_eq!(health, SourceHealth::Broken);
+    }
+
+    #[test]
+    fn test_report_and_next_to_check() {
+        let policy = HealthPolicy { failures: 1, min_span_ms: 0 };
+        let source = |url: &str, test: Option<(bool, i64)>| {
+            let last_test = test.map(|(passed, at)| policy.record(None, &card(passed, at)));
+            BookSourceFull {
+                book_source_url: url.to_string(),
+                book_source_name: url.to_string(),
+                search_url: "/search?q={{key}}".to_string(),
+                enabled: true,
+                health: last_test.as_ref().map_or(SourceHealth::Healthy, |(_, health)| *health),
+                last_test: last_test.map(|(summary, _)| summary),
+                ..Default::default()
+            }
+        };
+        let mut sources = vec![
+            source("ok", Some((true, 300))),
+            source("old-failure", Some((false, 100))),
+            source("new-failure", Some((false, 200))),
+            source("untested", None),
+        ];
+        sources[2].health = SourceHealth::Degraded;
+
+        let report = report(&sources);
+        assert_eq!((report.healthy, report.degraded, report.broken), (2, 1, 1));
+        let failed: Vec<_> = report.recent_failures.iter().map(|f| f.book_source_url.as_str()).collect();
+        assert_eq!(failed, vec!["new-failure", "old-failure"]);
+        assert_eq!(report.recent_failures[0].failed_stage, Some(SourceTestStage::Search));
+
+        assert_eq!(next_to_check(&sources).unwrap().book_source_url, "untested");
+        sources[3].enabled = false;
+        assert_eq!(next_to_check(&sources).unwrap().book_source_url, "old-failure");
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::ServiceError;
+use crate::models::BookSourceFull;
+
+/// gzip 文件头
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const REMOTE_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// 被跳过的书源及原因
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidSource {
+    /// 在导入数组中的下标
+    pub index: usize,
+    pub reason: String,
+}
+
+/// 书源导入报告
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub added: usize,
+    pub updated: usize,
+    /// 与已有书源相同 (忽略 respondTime 等易变字段)
+    pub unchanged: usize,
+    /// 订阅更新时跳过的书源数：同 URL 的已有书源是手动导入或属于其他订阅的
+    pub skipped: usize,
+    pub invalid: Vec<InvalidSource>,
+}
+
+impl ImportReport {
+    /// 成功导入 (新增、更新或未变化) 的书源数
+    pub fn accepted(&self) -> usize {
+        self.added + self.updated + self.unchanged
+    }
+}
+
+/// 解码书源文件内容，gzip 压缩的内容会先解压
+pub fn decode_payload(bytes: &[u8]) -> anyhow::Result<String> {
+    let text = if bytes.starts_with(&GZIP_MAGIC) {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(bytes)
+            .read_to_string(&mut text)
+            .map_err(|e| ServiceError::invalid_input(format!("invalid gzip payload: {}", e)))?;
+        text
+    } else {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| ServiceError::invalid_input("book source payload is not valid UTF-8"))?
+    };
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+/// 读取远程书源文件
+pub async fn fetch_remote_sources(url: &str) -> anyhow::Result<String> {
+    let bytes = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", REMOTE_USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    decode_payload(&bytes)
+}
+
+/// 解析书源 JSON，支持数组或单个书源对象
+pub fn parse_sources(text: &str) -> anyhow::Result<Vec<Value>> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| ServiceError::invalid_input(format!("invalid book source JSON: {}", e)))?;
+    match value {
+        Value::Array(sources) => Ok(sources),
+        Value::Object(_) => Ok(vec![value]),
+        _ => Err(ServiceError::invalid_input("expected a JSON array of book sources").into()),
+    }
+}
+
+/// 校验单个书源，返回跳过的原因
+fn validate(raw: Value) -> Result<BookSourceFull, String> {
+    if !raw.is_object() {
+        return Err("not a JSON object".to_string());
+    }
+    for field in ["bookSourceUrl", "bookSourceName"] {
+        let present = raw
+            .get(field)
+            .and_then(Value::as_str)
+            .is_some_and(|v| !v.trim().is_empty());
+        if !present {
+            return Err(format!("missing {}", field));
+        }
+    }
+    serde_json::from_value(raw).map_err(|e| format!("malformed rule JSON: {}", e))
+}
+
+/// 除 respondTime、测试结果与健康状态外是否相同 (lastUpdateTime 等未建模的字段反序列化时已丢弃)
+fn same_rules(existing: &BookSourceFull, incoming: &BookSourceFull) -> bool {
+    let incoming = BookSourceFull {
+        respond_time: existing.respond_time,
+        last_test: existing.last_test.clone(),
+        health: existing.health,
+        ..incoming.clone()
+    };
+    serde_json::to_value(existing).ok() == serde_json::to_value(&incoming).ok()
+}
+
+/// 订阅更新时认领要导入的书源，返回跳过的书源数
+///
+/// 新增的书源与已属于该订阅的书源带上订阅地址；同 URL 的已有书源若是手动导入或属于其他订阅，
+/// 则从本次导入中移除，不覆盖用户的书源，删除订阅时也不会被一并删除。
+pub fn claim_for_subscription(sources: &[BookSourceFull], raw_sources: &mut Vec<Value>, subscription_url: &str) -> usize {
+    let owners: HashMap<&str, Option<&str>> = sources
+        .iter()
+        .map(|s| (s.book_source_url.as_str(), s.subscription_url.as_deref()))
+        .collect();
+    let before = raw_sources.len();
+    raw_sources.retain_mut(|raw| {
+        let url = raw.get("bookSourceUrl").and_then(Value::as_str).unwrap_or_default();
+        if owners.get(url).is_some_and(|owner| *owner != Some(subscription_url)) {
+            return false;
+        }
+        if let Some(source) = raw.as_object_mut() {
+            source.insert("subscriptionUrl".to_string(), subscription_url.into());
+        }
+        true
+    });
+    before - raw_sources.len()
+}
+
+/// 将导入的书源按 bookSourceUrl 合并进已有书源
+///
+/// 同一批次内 URL 重复时以最后一个为准；更新已有书源时保留其 respondTime、测试结果与健康状态。
+pub fn merge_sources(sources: &mut Vec<BookSourceFull>, raw_sources: Vec<Value>) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    let mut valid = Vec::new();
+    for (index, raw) in raw_sources.into_iter().enumerate() {
+        match validate(raw) {
+            Ok(source) => valid.push((index, source)),
+            Err(reason) => report.invalid.push(InvalidSource { index, reason }),
+        }
+    }
+
+    let last_index: HashMap<String, usize> = valid
+        .iter()
+        .map(|(index, s)| (s.book_source_url.clone(), *index))
+        .collect();
+
+    for (index, mut source) in valid {
+        let winner = last_index[&source.book_source_url];
+        if winner != index {
+            report.invalid.push(InvalidSource {
+                index,
+                reason: format!("duplicate bookSourceUrl, overridden by index {}", winner),
+            });
+            continue;
+        }
+
+        match sources
+            .iter()
+            .position(|s| s.book_source_url == source.book_source_url)
+        {
+            Some(pos) if same_rules(&sources[pos], &source) => report.unchanged += 1,
+            Some(pos) => {
+                source.respond_time = sources[pos].respond_time;
+                source.last_test = sources[pos].last_test.take();
+                source.health = sources[pos].health;
+                sources[pos] = source;
+                report.updated += 1;
+            }
+            None => {
+                sources.push(source);
+                report.added += 1;
+            }
+        }
+    }
+
+    report.invalid.sort_by_key(|i| i.index);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Write;
+
+    fn source(url: &str, list: &str) -> Value {
+        json!({
+            "bookSourceUrl": url,
+            "bookSourceName": url,
+            "ruleSearch": { "bookList": list },
+        })
+    }
+
+    #[test]
+    fn test_merge_overlapping_import() {
+        let mut sources: Vec<BookSourceFull> = [source("https://a.com", "div"), source("https://b.com", "div")]
+            .into_iter()
+            .map(|v| serde_json::from_value(v).unwrap())
+            .collect();
+        sources[0].respond_time = 120;
+        sources[1].respond_time = 300;
+
+        let mut same = source("https://a.com", "div");
+        same["respondTime"] = json!(5);
+        same["lastUpdateTime"] = json!(1700000000000u64);
+        let report = merge_sources(
+            &mut sources,
+            vec![
+                same,
+                source("https://b.com", "ul"),
+                source("https://c.com", "li"),
+                json!({ "bookSourceName": "no url" }),
+                source("https://c.com", "p"),
+                json!({ "bookSourceUrl": "https://d.com", "bookSourceName": "d", "ruleSearch": "not a rule" }),
+                json!("oops"),
+            ],
+        );
+
+        assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 1));
+        let invalid: Vec<usize> = report.invalid.iter().map(|i| i.index).collect();
+        assert_eq!(invalid, vec![2, 3, 5, 6]);
+        assert!(report.invalid[0].reason.contains("overridden by index 4"));
+        assert_eq!(report.invalid[1].reason, "missing bookSourceUrl");
+        assert!(report.invalid[2].reason.starts_with("malformed rule JSON"));
+
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].respond_time, 120);
+        // 更新时保留已测量的响应时间
+        assert_eq!(sources[1].rule_search.as_ref().unwrap().book_list, "ul");
+        assert_eq!(sources[1].respond_time, 300);
+        assert_eq!(sources[2].rule_search.as_ref().unwrap().book_list, "p");
+    }
+
+    #[test]
+    fn test_decode_gzip_payload() {
+        let json = r#"[{"bookSourceUrl":"https://a.com","bookSourceName":"A"}]"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        assert_eq!(decode_payload(&gz).unwrap(), json);
+        assert_eq!(decode_payload(json.as_bytes()).unwrap(), json);
+        assert_eq!(parse_sources(json).unwrap().len(), 1);
+        assert!(parse_sources("42").is_err());
+    }
+}
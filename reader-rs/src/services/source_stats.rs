@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::engine::anti_ban::Cooldowns;
+use crate::models::BookSourceFull;
+use crate::storage::FileStorage;
+
+/// 书源统计存储文件名
+const STATS_FILE: &str = "sourceStats.json";
+const SOURCES_FILE: &str = "bookSources.json";
+
+/// 响应时间指数移动平均的平滑系数 (新样本所占比重)
+const RESPOND_TIME_ALPHA: f64 = 0.3;
+
+/// 连续超时达到该次数时降低一次书源权重
+pub const TIMEOUT_PENALTY_STREAK: u32 = 3;
+
+/// 一次搜索请求的结果类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOutcome {
+    Success,
+    Failure,
+    Timeout,
+}
+
+/// 单个书源的累计统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStat {
+    /// 响应时间的指数移动平均 (毫秒)，0 表示尚无样本
+    #[serde(default)]
+    pub respond_time: u64,
+    #[serde(default)]
+    pub success_count: u64,
+    #[serde(default)]
+    pub fail_count: u64,
+    /// 当前连续超时次数
+    #[serde(default)]
+    pub timeout_streak: u32,
+}
+
+impl SourceStat {
+    /// 记录一次搜索，返回是否应降低书源权重
+    ///
+    /// 成功与超时计入响应时间 (超时按实际等待时间)；
+    /// 立即失败的请求不代表书源速度，只计入失败次数。
+    pub fn record(&mut self, elapsed_ms: u64, outcome: SearchOutcome) -> bool {
+        match outcome {
+            SearchOutcome::Success => {
+                self.success_count += 1;
+                self.timeout_streak = 0;
+            }
+            SearchOutcome::Failure => self.fail_count += 1,
+            SearchOutcome::Timeout => {
+                self.fail_count += 1;
+                self.timeout_streak += 1;
+            }
+        }
+
+        if outcome != SearchOutcome::Failure {
+            self.respond_time = if self.respond_time == 0 {
+                elapsed_ms
+            } else {
+                (RESPOND_TIME_ALPHA * elapsed_ms as f64 + (1.0 - RESPOND_TIME_ALPHA) * self.respond_time as f64)
+                    .round() as u64
+            };
+        }
+
+        if self.timeout_streak >= TIMEOUT_PENALTY_STREAK {
+            self.timeout_streak = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// 书源统计信息 (用于接口返回)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStatInfo {
+    pub book_source_url: String,
+    pub book_source_name: String,
+    pub weight: i32,
+    pub respond_time: u64,
+    pub success_count: u64,
+    pub fail_count: u64,
+    /// 因请求过于频繁 (429) 暂停使用的截止时间 (毫秒时间戳)，未暂停时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_until: Option<i64>,
+}
+
+/// 书源响应统计
+///
+/// 统计数据保存在单独的文件中，响应时间与权重同时写回书源列表。
+/// `record` 只更新内存，批量搜索结束后调用 `persist` 防抖写入。
+/// 书源的冷却状态由引擎写入进程共享的 `Cooldowns`，不落盘。
+#[derive(Clone)]
+pub struct SourceStats {
+    storage: FileStorage,
+    sources: Arc<RwLock<Vec<BookSourceFull>>>,
+    stats: Arc<Mutex<Option<HashMap<String, SourceStat>>>>,
+    cooldowns: Arc<Cooldowns>,
+}
+
+impl SourceStats {
+    /// `sources` 为书源服务的内存缓存
+    pub fn new(storage: FileStorage, sources: Arc<RwLock<Vec<BookSourceFull>>>) -> Self {
+        Self {
+            storage,
+            sources,
+            stats: Arc::new(Mutex::new(None)),
+            cooldowns: Cooldowns::shared(),
+        }
+    }
+
+    /// 书源冷却状态
+    pub fn cooldowns(&self) -> Arc<Cooldowns> {
+        self.cooldowns.clone()
+    }
+
+    /// 书源是否因请求过于频繁处于冷却中 (多源搜索时跳过)
+    pub fn is_cooling_down(&self, source_url: &str) -> bool {
+        self.cooldowns.is_cooling_down(source_url)
+    }
+
+    /// 丢弃内存中的统计，下次访问时重新从文件加载
+    pub async fn reload(&self) {
+        *self.stats.lock().await = None;
+    }
+
+    async fn with_stats<R>(&self, f: impl FnOnce(&mut HashMap<String, SourceStat>) -> R) -> R {
+        let mut guard = self.stats.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.storage.read_json_or_default(STATS_FILE).await);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    /// 记录一个书源的一次搜索
+    pub async fn record(&self, source_url: &str, elapsed_ms: u64, outcome: SearchOutcome) {
+        let (respond_time, penalize) = self
+            .with_stats(|stats| {
+                let stat = stats.entry(source_url.to_string()).or_default();
+                let penalize = stat.record(elapsed_ms, outcome);
+                (stat.respond_time, penalize)
+            })
+            .await;
+
+        let mut sources = self.sources.write().await;
+        if sources.is_empty() {
+            *sources = self.storage.read_json_or_default(SOURCES_FILE).await;
+        }
+        if let Some(source) = sources.iter_mut().find(|s| s.book_source_url == source_url) {
+            source.respond_time = respond_time;
+            if penalize {
+                source.weight -= 1;
+                tracing::info!(
+                    "Source {} timed out {} times in a row, weight lowered to {}",
+                    source.book_source_name,
+                    TIMEOUT_PENALTY_STREAK,
+                    source.weight
+                );
+            }
+        }
+    }
+
+    /// 防抖写入统计与书源列表
+    pub async fn persist(&self) -> anyhow::Result<()> {
+        {
+            let guard = self.stats.lock().await;
+            if let Some(stats) = guard.as_ref() {
+                self.storage.write_json_debounced(STATS_FILE, stats).await?;
+            }
+        }
+        let sources = self.sources.read().await;
+        if !sources.is_empty() {
+            self.storage.write_json_debounced(SOURCES_FILE, &*sources).await?;
+        }
+        Ok(())
+    }
+
+    /// 获取指定书源的统计
+    pub async fn get(&self, source_url: &str) -> SourceStat {
+        self.with_stats(|stats| stats.get(source_url).cloned().unwrap_or_default())
+            .await
+    }
+
+    /// 按书源列表顺序汇总统计
+    pub async fn summarize(&self, sources: &[BookSourceFull]) -> Vec<SourceStatInfo> {
+        self.with_stats(|stats| {
+            sources
+                .iter()
+                .map(|source| {
+                    let stat = stats.get(&source.book_source_url).cloned().unwrap_or_default();
+                    SourceStatInfo {
+                        book_source_url: source.book_source_url.clone(),
+                        book_source_name: source.book_source_name.clone(),
+                        weight: source.weight,
+                        respond_time: stat.respond_time,
+                        success_count: stat.success_count,
+                        fail_count: stat.fail_count,
+                        cooldown_until: self.cooldowns.until_ms(&source.book_source_url),
+                    }
+                })
+                .collect()
+        })
+        .await
+    }
+}
+
+/// 按权重从高到低排序，权重相同时响应更快的在前，尚无响应时间的排在最后
+pub fn sort_by_weight(sources: &mut [BookSourceFull]) {
+    sources.sort_by(|a, b| {
+        b.weight.cmp(&a.weight).then_with(|| {
+            let key = |s: &BookSourceFull| (s.respond_time == 0, s.respond_time);
+            key(a).cmp(&key(b))
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(url: &str, weight: i32) -> BookSourceFull {
+        BookSourceFull {
+            book_source_url: url.to_string(),
+            book_source_name: url.to_string(),
+            weight,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_respond_time_ema() {
+        let mut fast = SourceStat::default();
+        fast.record(100, SearchOutcome::Success);
+        assert_eq!(fast.respond_time, 100);
+        fast.record(200, SearchOutcome::Success);
+        assert_eq!(fast.respond_time, 130);
+        // 立即失败不影响响应时间
+        fast.record(5, SearchOutcome::Failure);
+        assert_eq!(fast.respond_time, 130);
+        assert_eq!((fast.success_count, fast.fail_count), (2, 1));
+
+        let mut slow = SourceStat::default();
+        slow.record(8000, SearchOutcome::Success);
+        slow.record(15000, SearchOutcome::Timeout);
+        assert_eq!(slow.respond_time, 10100);
+    }
+
+    #[test]
+    fn test_timeout_penalty() {
+        let mut stat = SourceStat::default();
+        assert!(!stat.record(15000, SearchOutcome::Timeout));
+        assert!(!stat.record(15000, SearchOutcome::Timeout));
+        // 成功会打断连续超时
+        assert!(!stat.record(300, SearchOutcome::Success));
+        assert!(!stat.record(15000, SearchOutcome::Timeout));
+        assert!(!stat.record(15000, SearchOutcome::Timeout));
+        assert!(stat.record(15000, SearchOutcome::Timeout));
+        assert_eq!(stat.timeout_streak, 0);
+        assert_eq!((stat.success_count, stat.fail_count), (1, 5));
+    }
+
+    #[tokio::test]
+    async fn test_record_updates_sources_and_persists() {
+        let dir = std::env::temp_dir().join(format!("reader_tests_source_stats_{}", uuid::Uuid::new_v4()));
+        let storage = FileStorage::new(&dir);
+        let sources = Arc::new(RwLock::new(vec![source("fast", 0), source("slow", 0), source("broken", 0)]));
+        let stats = SourceStats::new(storage.clone(), sources.clone());
+
+        for _ in 0..3 {
+            stats.record("fast", 120, SearchOutcome::Success).await;
+            stats.record("slow", 15000, SearchOutcome::Timeout).await;
+            stats.record("broken", 10, SearchOutcome::Failure).await;
+        }
+        stats.persist().await.unwrap();
+        storage.flush().await.unwrap();
+
+        let saved: Vec<BookSourceFull> = storage.read_json(SOURCES_FILE).await.unwrap();
+        let find = |url: &str| saved.iter().find(|s| s.book_source_url == url).unwrap().clone();
+        assert_eq!((find("fast").respond_time, find("fast").weight), (120, 0));
+        assert_eq!((find("slow").respond_time, find("slow").weight), (15000, -1));
+        assert_eq!((find("broken").respond_time, find("broken").weight), (0, 0));
+
+        // 重新加载后统计仍在
+        let reloaded = SourceStats::new(storage.clone(), sources.clone());
+        assert_eq!(reloaded.get("broken").await.fail_count, 3);
+        let summary = reloaded.summarize(&saved).await;
+        assert_eq!(summary[0].success_count, 3);
+        assert_eq!(summary[1].fail_count, 3);
+
+        let mut sorted = saved.clone();
+        sort_by_weight(&mut sorted);
+        let urls: Vec<_> = sorted.iter().map(|s| s.book_source_url.as_str()).collect();
+        assert_eq!(urls, vec!["fast", "broken", "slow"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
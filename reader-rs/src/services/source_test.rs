@@ -0,0 +1,249 @@
+use axum::response::sse::Event;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::engine::book_source::{BookSource, BookSourceEngine};
+use crate::engine::rule_context::{BookContext, ChapterContext};
+use crate::models::{BookSourceFull, SourceScorecard, SourceTestStage, StageResult, StageStatus};
+use crate::storage::kv::KvStore;
+
+/// 默认搜索关键字
+pub const DEFAULT_TEST_KEY: &str = "我的";
+
+/// 每个阶段的默认超时 (秒)
+const DEFAULT_STAGE_TIMEOUT_SECS: u64 = 15;
+
+/// 正文样本的最大字符数
+const CONTENT_SAMPLE_CHARS: usize = 200;
+
+/// 每个阶段的超时，由环境变量 SOURCE_TEST_STAGE_TIMEOUT 配置 (秒)
+fn default_stage_timeout() -> Duration {
+    let secs = std::env::var("SOURCE_TEST_STAGE_TIMEOUT")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_STAGE_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// 书源测试参数
+#[derive(Debug, Clone, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceTestOptions {
+    /// 搜索关键字，为空时使用默认关键字
+    #[serde(default)]
+    pub key: String,
+    /// 每个阶段的超时 (秒)，未指定时使用 SOURCE_TEST_STAGE_TIMEOUT
+    pub stage_timeout: Option<u64>,
+}
+
+impl SourceTestOptions {
+    fn key(&self) -> String {
+        match self.key.trim() {
+            "" => DEFAULT_TEST_KEY.to_string(),
+            key => key.to_string(),
+        }
+    }
+
+    fn stage_timeout(&self) -> Duration {
+        self.stage_timeout
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or_else(default_stage_timeout)
+    }
+}
+
+/// 批量测试过程中的事件
+#[derive(Debug, Clone)]
+pub enum SourceTestEvent {
+    /// 一个书源测试完成
+    Tested {
+        current: usize,
+        total: usize,
+        scorecard: Box<SourceScorecard>,
+    },
+    Done { passed: usize, total: usize },
+}
+
+impl SourceTestEvent {
+    pub fn to_sse(&self) -> Event {
+        let data = match self {
+            Self::Tested { current, total, scorecard } => serde_json::json!({
+                "type": "progress",
+                "current": current,
+                "total": total,
+                "data": scorecard,
+            }),
+            Self::Done { passed, total } => {
+                serde_json::json!({ "type": "end", "passed": passed, "total": total })
+            }
+        };
+        Event::default().data(data.to_string())
+    }
+}
+
+/// 阶段产出的样本
+enum StageSample {
+    BookName(String),
+    ChapterCount(usize),
+    Content(String),
+}
+
+type StageMessage = (u64, Result<StageSample, String>);
+
+/// 依次执行搜索 → 详情 → 目录 → 首章正文，生成成绩单
+///
+/// 各阶段在同一个阻塞线程中执行，逐个回传结果；某阶段失败或超时后，其余阶段记为跳过。
+/// 超时后测试线程在当前请求结束时自行退出，不会阻塞调用方。
+pub async fn run_source_test(
+    source: BookSourceFull,
+    kv_store: Arc<KvStore>,
+    options: &SourceTestOptions,
+) -> SourceScorecard {
+    let started = Instant::now();
+    let key = options.key();
+    let stage_timeout = options.stage_timeout();
+    let mut card = SourceScorecard {
+        book_source_url: source.book_source_url.clone(),
+        book_source_name: source.book_source_name.clone(),
+        passed: false,
+        stages: Vec::new(),
+        key: key.clone(),
+        book_name: None,
+        chapter_count: None,
+        content_sample: None,
+        elapsed_ms: 0,
+        tested_at: chrono::Utc::now().timestamp_millis(),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || run_stages(source, kv_store, &key, tx));
+
+    let mut stopped = false;
+    for stage in SourceTestStage::ALL {
+        if stopped {
+            card.stages.push(StageResult {
+                stage,
+                status: StageStatus::Skipped,
+                elapsed_ms: 0,
+                error: None,
+            });
+            continue;
+        }
+
+        let (status, elapsed_ms, error) = match tokio::time::timeout(stage_timeout, rx.recv()).await {
+            Ok(Some((elapsed_ms, Ok(sample)))) => {
+                match sample {
+                    StageSample::BookName(name) => card.book_name = Some(name),
+                    StageSample::ChapterCount(count) => card.chapter_count = Some(count),
+                    StageSample::Content(content) => {
+                        card.content_sample = Some(content.trim().chars().take(CONTENT_SAMPLE_CHARS).collect())
+                    }
+                }
+                (StageStatus::Passed, elapsed_ms, None)
+            }
+            Ok(Some((elapsed_ms, Err(e)))) => (StageStatus::Failed, elapsed_ms, Some(e)),
+            Ok(None) => (StageStatus::Failed, 0, Some("test task aborted".to_string())),
+            Err(_) => (
+                StageStatus::Timeout,
+                stage_timeout.as_millis() as u64,
+                Some(format!("timed out after {}s", stage_timeout.as_secs())),
+            ),
+        };
+        stopped = status != StageStatus::Passed;
+        card.stages.push(StageResult { stage, status, elapsed_ms, error });
+    }
+
+    card.passed = !stopped;
+    card.elapsed_ms = started.elapsed().as_millis() as u64;
+    card
+}
+
+/// 测试线程：每完成一个阶段发送一条结果，失败或接收方已放弃时停止
+fn run_stages(source: BookSourceFull, kv_store: Arc<KvStore>, key: &str, tx: mpsc::UnboundedSender<StageMessage>) {
+    let send = |started: Instant, result: anyhow::Result<StageSample>| {
+        let ok = result.is_ok();
+        let message = (started.elapsed().as_millis() as u64, result.map_err(|e| format!("{:#}", e)));
+        tx.send(message).is_ok() && ok
+    };
+
+    let started = Instant::now();
+    let engine = serde_json::to_value(&source)
+        .and_then(serde_json::from_value::<BookSource>)
+        .map_err(anyhow::Error::from)
+        .and_then(|s| BookSourceEngine::new(s, kv_store));
+    let engine = match engine {
+        Ok(engine) => engine,
+        Err(e) => {
+            send(started, Err(e));
+            return;
+        }
+    };
+
+    let book = match engine.search(key, 1) {
+        Ok(books) => books.into_iter().next(),
+        Err(e) => {
+            send(started, Err(e));
+            return;
+        }
+    };
+    let Some(book) = book else {
+        send(started, Err(anyhow::anyhow!("no search results for \"{}\"", key)));
+        return;
+    };
+    if !send(started, Ok(StageSample::BookName(book.name.clone()))) {
+        return;
+    }
+    engine.set_book(BookContext::from(&book));
+
+    let started = Instant::now();
+    let info = match engine.get_book_info(&book.book_url) {
+        Ok(info) => info,
+        Err(e) => {
+            send(started, Err(e));
+            return;
+        }
+    };
+    let name = if info.name.trim().is_empty() { book.name } else { info.name };
+    if !send(started, Ok(StageSample::BookName(name))) {
+        return;
+    }
+
+    let started = Instant::now();
+    let toc_url = info
+        .toc_url
+        .filter(|u| !u.trim().is_empty())
+        .unwrap_or(book.book_url);
+    let chapters = match engine.get_chapters(&toc_url) {
+        Ok(chapters) if chapters.is_empty() => Err(anyhow::anyhow!("no chapters found")),
+        result => result,
+    };
+    let first = match chapters {
+        Ok(mut chapters) => {
+            let count = chapters.len();
+            let first = chapters.swap_remove(0);
+            if !send(started, Ok(StageSample::ChapterCount(count))) {
+                return;
+            }
+            first
+        }
+        Err(e) => {
+            send(started, Err(e));
+            return;
+        }
+    };
+
+    let started = Instant::now();
+    engine.set_chapter(ChapterContext {
+        title: first.title,
+        url: first.url.clone(),
+        index: 0,
+    });
+    let content = match engine.get_content(&first.url) {
+        Ok(content) if content.trim().is_empty() => Err(anyhow::anyhow!("empty content")),
+        result => result,
+    };
+    send(started, content.map(StageSample::Content));
+}
@@ -0,0 +1,182 @@
+//! 按行比较文本，输出 unified diff
+//!
+//! 先去掉相同的首尾行，再对中间部分求最长公共子序列 (LCS)。
+
+/// 一行的比较结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// unified diff 中变动行前后保留的上下文行数
+const CONTEXT_LINES: usize = 3;
+
+/// LCS 表的最大单元数，超出时中间部分整体视为删除后插入
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// 按行比较 `old` 与 `new`
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffOp<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops: Vec<DiffOp> = a[..prefix].iter().map(|l| DiffOp::Equal(l)).collect();
+    ops.extend(lcs_ops(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]));
+    ops.extend(a[a.len() - suffix..].iter().map(|l| DiffOp::Equal(l)));
+    ops
+}
+
+fn lcs_ops<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<DiffOp<'a>> {
+    if a.len().saturating_mul(b.len()) > MAX_LCS_CELLS {
+        return a.iter().map(|l| DiffOp::Delete(l)).chain(b.iter().map(|l| DiffOp::Insert(l))).collect();
+    }
+
+    // lcs[i * width + j]: a[i..] 与 b[j..] 的最长公共子序列长度
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            ops.push(DiffOp::Equal(a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push(DiffOp::Delete(a[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[i..].iter().map(|l| DiffOp::Delete(l)));
+    ops.extend(b[j..].iter().map(|l| DiffOp::Insert(l)));
+    ops
+}
+
+/// 将 [`diff_lines`] 的结果格式化为 unified diff，没有差异时为空字符串
+pub fn unified_diff(ops: &[DiffOp], from_label: &str, to_label: &str) -> String {
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(_)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // positions[i]: ops[i] 之前的旧、新文本行数
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in ops {
+        positions.push((old_line, new_line));
+        match op {
+            DiffOp::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            DiffOp::Delete(_) => old_line += 1,
+            DiffOp::Insert(_) => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    // 相邻变动之间的相同行不超过两倍上下文时合并为一个块
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        match hunks.last_mut() {
+            Some((_, last)) if change - *last <= 2 * CONTEXT_LINES + 1 => *last = change,
+            _ => hunks.push((change, change)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", from_label, to_label);
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT_LINES);
+        let end = (last + CONTEXT_LINES + 1).min(ops.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let range = |start: usize, count: usize| match count {
+            0 => format!("{},0", start),
+            _ => format!("{},{}", start + 1, count),
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+        for op in &ops[start..end] {
+            let (prefix, line) = match op {
+                DiffOp::Equal(line) => (' ', line),
+                DiffOp::Delete(line) => ('-', line),
+                DiffOp::Insert(line) => ('+', line),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines_lcs() {
+        let ops = diff_lines("a\nb\nc\nd", "a\nc\nx\nd");
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Equal("a"),
+                DiffOp::Delete("b"),
+                DiffOp::Equal("c"),
+                DiffOp::Insert("x"),
+                DiffOp::Equal("d"),
+            ]
+        );
+        assert!(diff_lines("same\ntext", "same\ntext").iter().all(|op| matches!(op, DiffOp::Equal(_))));
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+        let mut new = old.clone();
+        new.remove(1);
+        new.insert(15, "inserted".to_string());
+        let diff = unified_diff(&diff_lines(&old.join("\n"), &new.join("\n")), "v1", "v2");
+        assert_eq!(
+            diff,
+            "--- v1\n+++ v2\n\
+             @@ -1,5 +1,4 @@\n line 1\n-line 2\n line 3\n line 4\n line 5\n\
+             @@ -14,6 +13,7 @@\n line 14\n line 15\n line 16\n+inserted\n line 17\n line 18\n line 19\n"
+        );
+        assert_eq!(unified_diff(&diff_lines("a", "a"), "v1", "v2"), "");
+    }
+
+    #[test]
+    fn test_unified_diff_from_empty() {
+        assert_eq!(unified_diff(&diff_lines("", "a\nb"), "v1", "v2"), "--- v1\n+++ v2\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(unified_diff(&diff_lines("a", ""), "v1", "v2"), "--- v1\n+++ v2\n@@ -1,1 +0,0 @@\n-a\n");
+    }
+}
@@ -0,0 +1,502 @@
+//! 章节朗读：把净化后的正文交给外部 TTS 后端生成音频
+//!
+//! 后端通过环境变量配置，二选一：
+//! - `TTS_COMMAND`：本地命令 (如 piper / edge-tts)，正文从 stdin 传入，音频从 stdout 读取，
+//!   参数中的 `{voice}` 替换为朗读者
+//! - `TTS_HTTP_URL`：POST `{text, voice}` JSON，响应体即音频
+//!
+//! `TTS_FORMAT` 指定后端输出格式 (mp3 / wav，默认 mp3)，`TTS_VOICE` 为默认朗读者，
+//! `TTS_MAX_CHARS` 为单次合成的最大字符数 (默认 300)。
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::ServiceError;
+use crate::storage::audio_cache::{AudioCache, CachedAudio};
+use crate::storage::FileStorage;
+
+/// 单次合成的默认最大字符数
+const DEFAULT_TTS_MAX_CHARS: usize = 300;
+
+/// HTTP 后端单次请求超时
+const TTS_HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 断句的标点
+const SENTENCE_ENDS: &[char] = &['。', '！', '？', '；', '…', '!', '?', ';', '\n'];
+
+static HTML_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// 后端输出的音频格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Mp3,
+    Wav,
+}
+
+impl AudioFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Wav => "audio/wav",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "mp3" | "mpeg" => Some(Self::Mp3),
+            "wav" | "wave" => Some(Self::Wav),
+            _ => None,
+        }
+    }
+}
+
+/// TTS 后端失败，返回给客户端时带上后端名称
+#[derive(Debug, thiserror::Error)]
+#[error("TTS backend {backend} failed: {message}")]
+pub struct TtsError {
+    pub backend: String,
+    pub message: String,
+}
+
+/// 文本转语音后端
+///
+/// 在阻塞线程中调用，每次合成一段不超过 `TTS_MAX_CHARS` 的文本。
+pub trait TtsBackend: Send + Sync {
+    /// 后端名称，用于错误信息与缓存摘要
+    fn name(&self) -> &str;
+
+    /// 输出音频格式
+    fn format(&self) -> AudioFormat;
+
+    /// 合成一段文本，voice 为空时使用后端默认朗读者
+    fn synthesize(&self, text: &str, voice: Option<&str>) -> anyhow::Result<Vec<u8>>;
+}
+
+/// 调用本地命令合成
+pub struct CommandTts {
+    program: String,
+    args: Vec<String>,
+    default_voice: String,
+    format: AudioFormat,
+}
+
+impl CommandTts {
+    /// 解析命令行 (按空白分隔，不经过 shell)
+    pub fn new(command: &str, default_voice: &str, format: AudioFormat) -> Option<Self> {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        Some(Self {
+            program: parts.next()?,
+            args: parts.collect(),
+            default_voice: default_voice.to_string(),
+            format,
+        })
+    }
+}
+
+impl TtsBackend for CommandTts {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn synthesize(&self, text: &str, voice: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        let voice = voice.unwrap_or(&self.default_voice);
+        let mut child = Command::new(&self.program)
+            .args(self.args.iter().map(|arg| arg.replace("{voice}", voice)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("exited with {}: {}", output.status, stderr.trim());
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// 调用 HTTP 接口合成
+pub struct HttpTts {
+    url: String,
+    default_voice: String,
+    format: AudioFormat,
+    client: reqwest::blocking::Client,
+}
+
+impl HttpTts {
+    pub fn new(url: &str, default_voice: &str, format: AudioFormat) -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(TTS_HTTP_TIMEOUT)
+            .build()
+            .expect("Failed to create TTS client");
+        Self {
+            url: url.to_string(),
+            default_voice: default_voice.to_string(),
+            format,
+            client,
+        }
+    }
+}
+
+impl TtsBackend for HttpTts {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn synthesize(&self, text: &str, voice: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        let voice = voice.unwrap_or(&self.default_voice);
+        let resp = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({ "text": text, "voice": voice }))
+            .send()?;
+        if !resp.status().is_success() {
+            anyhow::bail!("HTTP {}", resp.status());
+        }
+        Ok(resp.bytes()?.to_vec())
+    }
+}
+
+/// 按环境变量创建后端，未配置时返回 None
+fn backend_from_env() -> Option<Arc<dyn TtsBackend>> {
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+    let format = match env("TTS_FORMAT") {
+        Some(value) => AudioFormat::parse(&value).unwrap_or_else(|| {
+            tracing::warn!("Unknown TTS_FORMAT {}, using mp3", value);
+            AudioFormat::Mp3
+        }),
+        None => AudioFormat::Mp3,
+    };
+    let voice = env("TTS_VOICE").unwrap_or_default();
+
+    if let Some(command) = env("TTS_COMMAND") {
+        return CommandTts::new(&command, &voice, format).map(|b| Arc::new(b) as Arc<dyn TtsBackend>);
+    }
+    env("TTS_HTTP_URL").map(|url| Arc::new(HttpTts::new(url.trim(), &voice, format)) as Arc<dyn TtsBackend>)
+}
+
+/// 朗读用的纯文本：去掉图片等标签与空行
+fn speech_text(content: &str) -> String {
+    let text = HTML_TAG.replace_all(content, "");
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 按句切分并合并为不超过 max_chars 个字符的片段，过长的单句按字符截断
+fn split_sentences(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        current.push(c);
+        if SENTENCE_ENDS.contains(&c) {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    sentences.push(current);
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_chars = 0;
+    for sentence in sentences {
+        let sentence = sentence.trim();
+        if sentence.is_empty() {
+            continue;
+        }
+        let chars: Vec<char> = sentence.chars().collect();
+        if chunk_chars + chars.len() > max_chars && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_chars = 0;
+        }
+        for piece in chars.chunks(max_chars) {
+            if chunk_chars + piece.len() > max_chars {
+                chunks.push(std::mem::take(&mut chunk));
+                chunk_chars = 0;
+            }
+            chunk.extend(piece);
+            chunk_chars += piece.len();
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// 拼接各片段的音频：MP3 帧可直接相连，WAV 需合并 data 块并重写头部
+fn concat_audio(format: AudioFormat, parts: Vec<Vec<u8>>) -> anyhow::Result<Vec<u8>> {
+    match format {
+        AudioFormat::Mp3 => Ok(parts.concat()),
+        AudioFormat::Wav => {
+            let mut fmt: Option<&[u8]> = None;
+            let mut data = Vec::new();
+            for part in &parts {
+                let (part_fmt, part_data) = wav_chunks(part)?;
+                match fmt {
+                    Some(fmt) if fmt != part_fmt => anyhow::bail!("WAV segments use different formats"),
+                    _ => fmt = Some(part_fmt),
+                }
+                data.extend_from_slice(part_data);
+            }
+            let fmt = fmt.ok_or_else(|| anyhow::anyhow!("no audio produced"))?;
+
+            let mut wav = Vec::with_capacity(20 + fmt.len() + 8 + data.len());
+            wav.extend_from_slice(b"RIFF");
+            wav.extend_from_slice(&((4 + 8 + fmt.len() + 8 + data.len()) as u32).to_le_bytes());
+            wav.extend_from_slice(b"WAVE");
+            wav.extend_from_slice(b"fmt ");
+            wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+            wav.extend_from_slice(fmt);
+            wav.extend_from_slice(b"data");
+            wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            wav.extend_from_slice(&data);
+            Ok(wav)
+        }
+    }
+}
+
+/// WAV 文件的 fmt 与 data 块内容
+///
+/// 流式输出的 WAV 常把 data 长度写成占位值，因此长度超出文件时截到文件末尾。
+fn wav_chunks(wav: &[u8]) -> anyhow::Result<(&[u8], &[u8])> {
+    if wav.len() < 12 || &wav[..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        anyhow::bail!("output is not a WAV file");
+    }
+    let (mut fmt, mut pos) = (None, 12);
+    while pos + 8 <= wav.len() {
+        let id = &wav[pos..pos + 4];
+        let size = u32::from_le_bytes(wav[pos + 4..pos + 8].try_into()?) as usize;
+        let body = &wav[pos + 8..(pos + 8).saturating_add(size).min(wav.len())];
+        match id {
+            b"fmt " => fmt = Some(body),
+            b"data" => {
+                let fmt = fmt.ok_or_else(|| anyhow::anyhow!("WAV data before fmt chunk"))?;
+                return Ok((fmt, body));
+            }
+            _ => {}
+        }
+        pos += 8 + body.len() + body.len() % 2;
+    }
+    anyhow::bail!("WAV file has no data chunk")
+}
+
+/// 依次合成所有片段并拼接
+fn synthesize_all(backend: &dyn TtsBackend, chunks: &[String], voice: Option<&str>) -> Result<Vec<u8>, TtsError> {
+    let error = |message: String| TtsError {
+        backend: backend.name().to_string(),
+        message,
+    };
+    let mut parts = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let audio = backend.synthesize(chunk, voice).map_err(|e| error(format!("{:#}", e)))?;
+        if audio.is_empty() {
+            return Err(error("empty audio output".to_string()));
+        }
+        parts.push(audio);
+    }
+    concat_audio(backend.format(), parts).map_err(|e| error(e.to_string()))
+}
+
+/// 章节朗读服务
+///
+/// 生成的音频按章节缓存，正文、朗读者或后端变化时重新生成；
+/// 同一章节同时只生成一次，并发请求等待先到者的结果。
+#[derive(Clone)]
+pub struct TtsService {
+    backend: Option<Arc<dyn TtsBackend>>,
+    cache: AudioCache,
+    max_chars: usize,
+    /// 正在生成的章节 (bookUrl#index -> 生成锁)
+    inflight: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl TtsService {
+    /// 使用环境变量配置的后端
+    pub fn with_storage(storage: FileStorage) -> Self {
+        Self::with_backend(storage, backend_from_env())
+    }
+
+    pub fn with_backend(storage: FileStorage, backend: Option<Arc<dyn TtsBackend>>) -> Self {
+        let max_chars = std::env::var("TTS_MAX_CHARS")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_TTS_MAX_CHARS);
+        Self {
+            backend,
+            cache: AudioCache::new(storage),
+            max_chars,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 获取章节音频，缓存未命中时由 content 提供 (已净化的) 正文生成
+    pub async fn chapter_audio<F, Fut>(
+        &self,
+        book_url: &str,
+        index: i32,
+        voice: Option<&str>,
+        content: F,
+    ) -> anyhow::Result<CachedAudio>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<String>>,
+    {
+        let backend = self
+            .backend
+            .clone()
+            .ok_or_else(|| ServiceError::invalid_input("TTS backend not configured: set TTS_COMMAND or TTS_HTTP_URL"))?;
+        let text = speech_text(&content().await?);
+        if text.is_empty() {
+            return Err(ServiceError::invalid_input("Chapter has no text to read").into());
+        }
+        let voice = voice.map(str::trim).filter(|v| !v.is_empty());
+        let key = format!(
+            "{:x}",
+            md5::compute(format!("{}\n{}\n{}", backend.name(), voice.unwrap_or_default(), text))
+        );
+
+        let slot = format!("{}#{}", book_url, index);
+        let lock = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(slot.clone())
+            .or_default()
+            .clone();
+        let result = async {
+            let _guard = lock.lock().await;
+            if let Some(audio) = self.cache.get(book_url, index).await.filter(|a| a.key == key) {
+                return Ok(audio);
+            }
+
+            let chunks = split_sentences(&text, self.max_chars);
+            let voice = voice.map(str::to_string);
+            let format = backend.format();
+            let data =
+                tokio::task::spawn_blocking(move || synthesize_all(backend.as_ref(), &chunks, voice.as_deref()))
+                    .await??;
+            let audio = CachedAudio {
+                content_type: format.content_type().to_string(),
+                key,
+                data,
+            };
+            if let Err(e) = self.cache.put(book_url, index, &audio).await {
+                tracing::warn!("Failed to cache audio of chapter {} of {}: {}", index, book_url, e);
+            }
+            Ok(audio)
+        }
+        .await;
+
+        // 没有其他请求在等待时移除生成锁
+        let mut inflight = self.inflight.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            inflight.remove(&slot);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 每段输出 10 个采样的静音 WAV
+    struct SilenceTts {
+        calls: AtomicUsize,
+    }
+
+    const SILENCE_SAMPLES: usize = 10;
+
+    fn silence_wav(samples: usize) -> Vec<u8> {
+        let fmt: [u8; 16] = [1, 0, 1, 0, 0x40, 0x1f, 0, 0, 0x80, 0x3e, 0, 0, 2, 0, 16, 0];
+        let data = vec![0u8; samples * 2];
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&((36 + data.len()) as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&fmt);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+
+    impl TtsBackend for SilenceTts {
+        fn name(&self) -> &str {
+            "silence"
+        }
+
+        fn format(&self) -> AudioFormat {
+            AudioFormat::Wav
+        }
+
+        fn synthesize(&self, _text: &str, _voice: Option<&str>) -> anyhow::Result<Vec<u8>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            Ok(silence_wav(SILENCE_SAMPLES))
+        }
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(split_sentences("第一句。第二句！\n第三句", 100), vec!["第一句。第二句！第三句"]);
+        assert_eq!(split_sentences("第一句。第二句！第三句", 5), vec!["第一句。", "第二句！", "第三句"]);
+        assert_eq!(split_sentences("一二三四五六七", 3), vec!["一二三", "四五六", "七"]);
+        assert_eq!(speech_text("<img src=\"a.png\">\n  第一段  \n\n第二段"), "第一段\n第二段");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_generate_once() {
+        let dir = "/tmp/reader_tests_tts_dedup";
+        let _ = std::fs::remove_dir_all(dir);
+        let backend = Arc::new(SilenceTts {
+            calls: AtomicUsize::new(0),
+        });
+        let service = TtsService::with_backend(FileStorage::new(dir), Some(backend.clone()));
+        let content = || async { Ok("第一句。第二句！第三句".repeat(30)) };
+
+        let url = "https://example.com/book/1";
+        let (a, b) = tokio::join!(
+            service.chapter_audio(url, 0, None, content),
+            service.chapter_audio(url, 0, None, content)
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        let chunks = split_sentences(&content().await.unwrap(), service.max_chars).len();
+        assert!(chunks > 1);
+        assert_eq!(backend.calls.load(Ordering::SeqCst), chunks);
+        assert_eq!(a.data, b.data);
+        assert_eq!(a.content_type, "audio/wav");
+
+        // 片段合并为一个 WAV
+        let (_, data) = wav_chunks(&a.data).unwrap();
+        assert_eq!(data.len(), chunks * SILENCE_SAMPLES * 2);
+        assert_eq!(a.data.len(), 44 + data.len());
+
+        // 换朗读者后重新生成
+        service.chapter_audio(url, 0, Some("xiaoyi"), content).await.unwrap();
+        assert_eq!(backend.calls.load(Ordering::SeqCst), chunks * 2);
+        assert!(service.inflight.lock().unwrap().is_empty());
+    }
+}
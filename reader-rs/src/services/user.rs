@@ -0,0 +1,336 @@
+//! 多用户模式：账号、登录令牌，以及按用户划分存储目录的服务
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use super::{AppState, ServiceError, SourceService};
+use crate::models::User;
+use crate::storage::FileStorage;
+
+/// 账号存储文件名 (位于全局存储目录)
+const USERS_FILE: &str = "users.json";
+/// 各用户的存储目录 (storage/users/{name})
+const USERS_DIR: &str = "users";
+/// 登录令牌有效期
+pub const TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// PBKDF2 的迭代次数 (测试在 debug 构建下运行，减少迭代)
+const HASH_ROUNDS: u32 = if cfg!(test) { 1_000 } else { 100_000 };
+/// 用户名最大长度
+const MAX_USERNAME_LEN: usize = 32;
+
+/// 多用户配置，由环境变量 READER_MULTI_USER / READER_SECRET / READER_SHARED_SOURCES 配置
+#[derive(Debug, Clone)]
+pub struct UserConfig {
+    /// 开启后所有接口都需要登录，书架等数据按用户存放
+    pub multi_user: bool,
+    /// 登录令牌的签名密钥
+    pub secret: String,
+    /// 书源由全部用户共享，关闭后每个用户维护自己的书源
+    pub shared_sources: bool,
+}
+
+impl UserConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let flag = |key: &str, default: bool| {
+            var(key)
+                .filter(|v| !v.trim().is_empty())
+                .map_or(default, |v| !matches!(v.trim(), "0" | "false"))
+        };
+        let multi_user = flag("READER_MULTI_USER", false);
+        let secret = var("READER_SECRET")
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| {
+                if multi_user {
+                    tracing::warn!("READER_SECRET is not set, login tokens will not survive a restart");
+                }
+                uuid::Uuid::new_v4().simple().to_string()
+            });
+        Self {
+            multi_user,
+            secret,
+            shared_sources: flag("READER_SHARED_SOURCES", true),
+        }
+    }
+}
+
+/// 账号与登录令牌
+pub struct UserService {
+    storage: FileStorage,
+    secret: String,
+    users: RwLock<Option<Vec<User>>>,
+}
+
+impl UserService {
+    pub fn with_storage(storage: FileStorage, secret: &str) -> Self {
+        Self {
+            storage,
+            secret: secret.to_string(),
+            users: RwLock::new(None),
+        }
+    }
+
+    /// 首次调用时加载账号，忽略非法用户名，并把明文密码替换为哈希
+    async fn load(&self) -> anyhow::Result<()> {
+        if self.users.read().await.is_some() {
+            return Ok(());
+        }
+        let mut guard = self.users.write().await;
+        if guard.is_some() {
+            return Ok(());
+        }
+        let mut users: Vec<User> = self.storage.read_json_or_default(USERS_FILE).await;
+        // 用户名会拼接为存储目录，手工写入的非法用户名直接忽略
+        users.retain(|user| {
+            let valid = is_valid_username(&user.username);
+            if !valid {
+                tracing::warn!("Ignoring user with invalid name: {:?}", user.username);
+            }
+            valid
+        });
+        let mut hashed = false;
+        for user in &mut users {
+            if let Some(password) = user.password.take().filter(|p| !p.is_empty()) {
+                user.salt = new_salt();
+                user.password_hash = hash_password(&user.salt, &password);
+                hashed = true;
+            }
+        }
+        if hashed {
+            self.storage.write_json(USERS_FILE, &users).await?;
+        }
+        *guard = Some(users);
+        Ok(())
+    }
+
+    /// 添加账号
+    pub async fn add_user(&self, username: &str, password: &str) -> anyhow::Result<()> {
+        if !is_valid_username(username) {
+            return Err(ServiceError::invalid_input(format!(
+                "Username must be 1-{} letters, digits, '_' or '-': {}",
+                MAX_USERNAME_LEN, username
+            ))
+            .into());
+        }
+        if password.is_empty() {
+            return Err(ServiceError::invalid_input("Password is empty").into());
+        }
+        self.load().await?;
+        let mut guard = self.users.write().await;
+        let users = guard.get_or_insert_with(Vec::new);
+        if users.iter().any(|u| u.username == username) {
+            return Err(ServiceError::invalid_input(format!("User already exists: {}", username)).into());
+        }
+        let salt = new_salt();
+        users.push(User {
+            username: username.to_string(),
+            password_hash: hash_password(&salt, password),
+            salt,
+            password: None,
+        });
+        self.storage.write_json(USERS_FILE, &*users).await
+    }
+
+    /// 校验用户名与密码，返回登录令牌
+    pub async fn login(&self, username: &str, password: &str) -> anyhow::Result<String> {
+        self.load().await?;
+        let guard = self.users.read().await;
+        let valid = guard.iter().flatten().any(|u| {
+            u.username == username
+                && constant_time_eq(hash_password(&u.salt, password).as_bytes(), u.password_hash.as_bytes())
+        });
+        if !valid {
+            return Err(ServiceError::unauthorized("Wrong username or password").into());
+        }
+        let expires_at = chrono::Utc::now().timestamp_millis() + TOKEN_TTL.as_millis() as i64;
+        Ok(self.sign(username, expires_at))
+    }
+
+    /// 校验登录令牌，返回仍然存在的用户名
+    pub async fn authenticate(&self, token: &str) -> Option<String> {
+        let username = self.verify(token, chrono::Utc::now().timestamp_millis())?;
+        self.load().await.ok()?;
+        let guard = self.users.read().await;
+        guard
+            .iter()
+            .flatten()
+            .any(|u| u.username == username)
+            .then_some(username)
+    }
+
+    /// 令牌格式：`{username}.{过期时间毫秒}.{HMAC-SHA256 (hex)}`
+    fn sign(&self, username: &str, expires_at: i64) -> String {
+        let payload = format!("{}.{}", username, expires_at);
+        let mac = self.mac(payload.as_bytes()).finalize().into_bytes();
+        format!("{}.{}", payload, hex::encode(mac))
+    }
+
+    fn verify(&self, token: &str, now: i64) -> Option<String> {
+        let (payload, mac) = token.rsplit_once('.')?;
+        self.mac(payload.as_bytes()).verify_slice(&hex::decode(mac).ok()?).ok()?;
+        let (username, expires_at) = payload.rsplit_once('.')?;
+        let expires_at = expires_at.parse::<i64>().ok()?;
+        (expires_at > now).then(|| username.to_string())
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+}
+
+/// 多用户模式下各用户的服务，首次访问时创建
+pub struct UserServices {
+    pub accounts: UserService,
+    storage_dir: PathBuf,
+    /// 共享书源时全部用户使用的书源服务
+    shared_sources: Option<SourceService>,
+    states: RwLock<HashMap<String, Arc<AppState>>>,
+}
+
+impl UserServices {
+    pub(super) fn new(storage_dir: &str, config: &UserConfig, source_service: &SourceService) -> Self {
+        Self {
+            accounts: UserService::with_storage(FileStorage::new(storage_dir), &config.secret),
+            storage_dir: PathBuf::from(storage_dir).join(USERS_DIR),
+            shared_sources: config.shared_sources.then(|| source_service.clone()),
+            states: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 用户的全部服务 (存储于 storage/users/{name})，首次访问时创建并启动后台任务
+    pub async fn state(&self, username: &str) -> Arc<AppState> {
+        if let Some(state) = self.states.read().await.get(username) {
+            return state.clone();
+        }
+        let mut states = self.states.write().await;
+        states
+            .entry(username.to_string())
+            .or_insert_with(|| {
+                let dir = self.storage_dir.join(username);
+                let state = Arc::new(AppState::build(&dir.to_string_lossy(), self.shared_sources.clone()));
+                state.spawn_kv_maintenance();
+                state.spawn_bookshelf_refresher();
+                // 共享的书源由主服务检查与更新订阅
+                if self.shared_sources.is_none() {
+                    state.spawn_source_health_monitor();
+                    state.spawn_subscription_refresher();
+                }
+                tracing::info!("Loaded storage of user {}", username);
+                state
+            })
+            .clone()
+    }
+
+    /// 写入各用户尚未落盘的状态，返回未能写入的步骤
+    pub(super) async fn shutdown(&self) -> Vec<&'static str> {
+        let mut failed = Vec::new();
+        for state in self.states.read().await.values() {
+            failed.extend(state.shutdown_services().await);
+        }
+        failed
+    }
+}
+
+/// 用户名同时用作目录名，只允许字母、数字、`_` 与 `-`
+fn is_valid_username(username: &str) -> bool {
+    (1..=MAX_USERNAME_LEN).contains(&username.len())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn new_salt() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// PBKDF2-HMAC-SHA256
+fn hash_password(salt: &str, password: &str) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), HASH_ROUNDS, &mut hash);
+    hex::encode(hash)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_mac() {
+        // RFC 4231 test case 2
+        let service = UserService::with_storage(FileStorage::new("/tmp/reader_tests_users_mac"), "Jefe");
+        let mac = service.mac(b"what do ya want for nothing?").finalize().into_bytes();
+        assert_eq!(
+            hex::encode(mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_and_token() {
+        let dir = "/tmp/reader_tests_users";
+        let _ = std::fs::remove_dir_all(dir);
+        let storage = FileStorage::new(dir);
+        // 手工写入的明文密码在加载时被替换为哈希
+        storage
+            .write_json(
+                USERS_FILE,
+                &serde_json::json!([
+                    { "username": "mom", "password": "secret" },
+                    { "username": "../..", "password": "secret" },
+                ]),
+            )
+            .await
+            .unwrap();
+        let service = UserService::with_storage(storage.clone(), "key");
+        service.add_user("kid", "pw").await.unwrap();
+        assert!(service.add_user("kid", "again").await.is_err());
+        assert!(service.add_user("../etc", "pw").await.is_err());
+
+        let saved = std::fs::read_to_string(storage.file_path(USERS_FILE)).unwrap();
+        assert!(!saved.contains("secret"));
+
+        let token = service.login("mom", "secret").await.unwrap();
+        assert_eq!(service.authenticate(&token).await.as_deref(), Some("mom"));
+        assert!(service.login("mom", "wrong").await.is_err());
+        assert!(service.login("nobody", "secret").await.is_err());
+        // 非法用户名在加载时被忽略，无法登录
+        assert!(service.login("../..", "secret").await.is_err());
+
+        // 篡改用户名、换用其他密钥或过期的令牌都无效
+        assert!(service.authenticate(&token.replacen("mom", "kid", 1)).await.is_none());
+        let other = UserService::with_storage(storage, "other key");
+        assert!(other.authenticate(&token).await.is_none());
+        let expired = service.sign("mom", chrono::Utc::now().timestamp_millis() - 1);
+        assert!(service.authenticate(&expired).await.is_none());
+    }
+
+    #[test]
+    fn test_config_from_vars() {
+        let config = UserConfig::from_vars(|_| None);
+        assert!(!config.multi_user && config.shared_sources);
+        assert!(!config.secret.is_empty());
+
+        let config = UserConfig::from_vars(|key| match key {
+            "READER_MULTI_USER" => Some("1".to_string()),
+            "READER_SECRET" => Some("s3cret".to_string()),
+            "READER_SHARED_SOURCES" => Some("false".to_string()),
+            _ => None,
+        });
+        assert!(config.multi_user && !config.shared_sources);
+        assert_eq!(config.secret, "s3cret");
+    }
+}
@@ -0,0 +1,76 @@
+use super::{FileStorage, ReclaimedCache};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 缓存的章节音频
+#[derive(Debug, Clone)]
+pub struct CachedAudio {
+    pub content_type: String,
+    /// 生成音频所用的文本、朗读者与后端的摘要，不一致时需重新生成
+    pub key: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioMeta {
+    content_type: String,
+    key: String,
+}
+
+/// 章节朗读音频缓存
+///
+/// 音频按 `cache/audio/{bookUrlHash}/{chapterIndex}` 存放，每章只保留最新一份，
+/// 类型与摘要记录在同名 `.json` 文件中。
+#[derive(Clone)]
+pub struct AudioCache {
+    storage: FileStorage,
+}
+
+impl AudioCache {
+    pub fn new(storage: FileStorage) -> Self {
+        Self { storage }
+    }
+
+    fn book_dir(book_url: &str) -> String {
+        format!("audio/{:x}", md5::compute(book_url))
+    }
+
+    fn audio_key(book_url: &str, index: i32) -> String {
+        format!("{}/{}", Self::book_dir(book_url), index)
+    }
+
+    /// 读取章节音频缓存
+    pub async fn get(&self, book_url: &str, index: i32) -> Option<CachedAudio> {
+        let key = Self::audio_key(book_url, index);
+        let meta = self.storage.read_cache(&format!("{}.json", key)).await.ok()?;
+        let meta: AudioMeta = serde_json::from_str(&meta).ok()?;
+        let data = self.storage.read_cache_bytes(&key).await.ok()?;
+        Some(CachedAudio {
+            content_type: meta.content_type,
+            key: meta.key,
+            data,
+        })
+    }
+
+    /// 写入章节音频缓存
+    pub async fn put(&self, book_url: &str, index: i32, audio: &CachedAudio) -> Result<()> {
+        let key = Self::audio_key(book_url, index);
+        // 先删除旧的元数据，避免写入中途读到新旧混合的缓存
+        let _ = self.storage.delete_cache(&format!("{}.json", key)).await;
+        self.storage.write_cache_bytes(&key, &audio.data).await?;
+        let meta = AudioMeta {
+            content_type: audio.content_type.clone(),
+            key: audio.key.clone(),
+        };
+        // 元数据最后写入，读取时以它的存在作为缓存完整的标志
+        self.storage
+            .write_cache(&format!("{}.json", key), &serde_json::to_string(&meta)?)
+            .await
+    }
+
+    /// 清除整本书的音频缓存
+    pub async fn clear_book(&self, book_url: &str) -> Result<ReclaimedCache> {
+        self.storage.remove_cache(&Self::book_dir(book_url)).await
+    }
+}
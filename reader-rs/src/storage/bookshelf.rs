@@ -0,0 +1,138 @@
+use super::FileStorage;
+use crate::models::Book;
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::fs;
+
+/// 旧版单文件书架 (备份包中仍使用此格式)
+pub const LEGACY_BOOKSHELF_FILE: &str = "bookshelf.json";
+
+/// 书架目录 (相对数据目录)
+const BOOKSHELF_DIR: &str = "bookshelf";
+
+/// 书架索引文件名
+const INDEX_NAME: &str = "index.json";
+
+/// 加载时同时读取的书籍文件数
+const LOAD_CONCURRENCY: usize = 32;
+
+/// 书架索引条目，按书架顺序排列
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShelfIndexEntry {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<i64>,
+}
+
+/// 按书存放的书架
+///
+/// 索引 `bookshelf/index.json` 记录书籍 ID、书名与分组位，每本书保存在
+/// `bookshelf/{id}.json`，ID 为 bookUrl 的 md5。保存进度等操作只重写对应的书籍文件，
+/// 增删书籍或书名、分组变化时才重写索引。
+#[derive(Clone)]
+pub struct BookshelfStore {
+    storage: FileStorage,
+}
+
+impl BookshelfStore {
+    pub fn new(storage: FileStorage) -> Self {
+        Self { storage }
+    }
+
+    /// 书籍 ID
+    pub fn book_id(book_url: &str) -> String {
+        format!("{:x}", md5::compute(book_url))
+    }
+
+    fn book_file(id: &str) -> String {
+        format!("{}/{}.json", BOOKSHELF_DIR, id)
+    }
+
+    fn index_file() -> String {
+        format!("{}/{}", BOOKSHELF_DIR, INDEX_NAME)
+    }
+
+    /// 是否已使用按书存放的格式
+    pub async fn is_initialized(&self) -> bool {
+        self.storage.exists(&Self::index_file()).await
+    }
+
+    /// 按索引顺序加载全部书籍，缺失或损坏的书籍文件跳过
+    pub async fn load(&self) -> Result<Vec<Book>> {
+        if !self.is_initialized().await {
+            return Ok(Vec::new());
+        }
+        let index: Vec<ShelfIndexEntry> = self.storage.read_json(&Self::index_file()).await?;
+        let books: Vec<Option<Book>> = stream::iter(index)
+            .map(|entry| async move {
+                match self.storage.read_json::<Book>(&Self::book_file(&entry.id)).await {
+                    Ok(book) => Some(book),
+                    Err(e) => {
+                        tracing::warn!("Failed to load book {} ({}): {}", entry.name, entry.id, e);
+                        None
+                    }
+                }
+            })
+            .buffered(LOAD_CONCURRENCY)
+            .collect()
+            .await;
+        Ok(books.into_iter().flatten().collect())
+    }
+
+    /// 写入书籍文件
+    pub async fn write_book(&self, book: &Book) -> Result<()> {
+        self.storage
+            .write_json(&Self::book_file(&Self::book_id(&book.book_url)), book)
+            .await
+    }
+
+    /// 防抖写入书籍文件 (阅读进度)
+    pub async fn write_book_debounced(&self, book: &Book) -> Result<()> {
+        self.storage
+            .write_json_debounced(&Self::book_file(&Self::book_id(&book.book_url)), book)
+            .await
+    }
+
+    /// 删除书籍文件
+    pub async fn delete_book(&self, book_url: &str) -> Result<()> {
+        self.storage.delete(&Self::book_file(&Self::book_id(book_url))).await
+    }
+
+    /// 按书架顺序重写索引
+    pub async fn write_index<'a>(&self, books: impl IntoIterator<Item = &'a Book>) -> Result<()> {
+        let index: Vec<ShelfIndexEntry> = books
+            .into_iter()
+            .map(|book| ShelfIndexEntry {
+                id: Self::book_id(&book.book_url),
+                name: book.name.clone(),
+                group: book.group,
+            })
+            .collect();
+        self.storage.write_json(&Self::index_file(), &index).await
+    }
+
+    /// 用给定书籍整体替换书架，并删除不再属于书架的书籍文件
+    pub async fn replace_all(&self, books: &[Book]) -> Result<()> {
+        for book in books {
+            self.write_book(book).await?;
+        }
+        self.write_index(books).await?;
+
+        let keep: HashSet<String> = books.iter().map(|b| Self::book_id(&b.book_url)).collect();
+        let mut entries = fs::read_dir(self.storage.file_path(BOOKSHELF_DIR)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(id) = name.strip_suffix(".json") else {
+                continue;
+            };
+            if name != INDEX_NAME && !keep.contains(id) {
+                self.storage.delete(&Self::book_file(id)).await?;
+            }
+        }
+        Ok(())
+    }
+}
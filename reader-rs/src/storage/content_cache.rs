@@ -0,0 +1,326 @@
+use super::{FileStorage, ReclaimedCache};
+use anyhow::Result;
+use serde::Serialize;
+use std::future::Future;
+use tokio::fs;
+use utoipa::ToSchema;
+
+/// 每章默认保留的旧版本数
+const DEFAULT_CHAPTER_VERSIONS: usize = 2;
+
+/// 每章保留的旧版本数，由环境变量 CHAPTER_VERSIONS 配置，0 表示不保留
+fn chapter_versions() -> usize {
+    std::env::var("CHAPTER_VERSIONS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_CHAPTER_VERSIONS)
+}
+
+/// 一个章节版本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterVersion {
+    /// 版本号，越新越大；当前缓存的正文是最大的版本
+    pub version: u32,
+    pub current: bool,
+    /// 该版本写入缓存的时间 (毫秒时间戳)
+    pub cached_at: i64,
+    /// 正文字数
+    pub length: usize,
+}
+
+/// 章节正文缓存
+///
+/// 正文按 `cache/books/{bookUrlHash}/{chapterIndex}.txt` 存放，
+/// 书源切换或目录变动时按书籍整体或从某一章起失效。
+/// 缓存的正文被不同内容替换时，旧内容保留为 `{chapterIndex}.v{n}.txt`，每章最多保留 `max_versions` 个。
+#[derive(Clone)]
+pub struct ContentCache {
+    storage: FileStorage,
+    max_versions: usize,
+}
+
+impl ContentCache {
+    pub fn new(storage: FileStorage) -> Self {
+        Self {
+            storage,
+            max_versions: chapter_versions(),
+        }
+    }
+
+    /// 设置每章保留的旧版本数
+    pub fn with_max_versions(mut self, max_versions: usize) -> Self {
+        self.max_versions = max_versions;
+        self
+    }
+
+    /// 书籍缓存目录 (相对 cache 目录)
+    fn book_dir(book_url: &str) -> String {
+        format!("books/{:x}", md5::compute(book_url))
+    }
+
+    fn chapter_key(book_url: &str, index: i32) -> String {
+        format!("{}/{}.txt", Self::book_dir(book_url), index)
+    }
+
+    fn version_key(book_url: &str, index: i32, version: u32) -> String {
+        format!("{}/{}.v{}.txt", Self::book_dir(book_url), index, version)
+    }
+
+    /// 缓存文件名对应的章节序号与旧版本号 (当前正文为 None)
+    fn parse_file_name(name: &str) -> Option<(i32, Option<u32>)> {
+        let stem = name.strip_suffix(".txt")?;
+        match stem.split_once(".v") {
+            Some((index, version)) => Some((index.parse().ok()?, Some(version.parse().ok()?))),
+            None => Some((stem.parse().ok()?, None)),
+        }
+    }
+
+    /// 章节已保存的旧版本号，从旧到新
+    async fn archived_versions(&self, book_url: &str, index: i32) -> Vec<u32> {
+        let dir = self.storage.cache_path(&Self::book_dir(book_url));
+        let mut versions = Vec::new();
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            return versions;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Some((i, Some(version))) = entry.file_name().to_str().and_then(Self::parse_file_name) {
+                if i == index {
+                    versions.push(version);
+                }
+            }
+        }
+        versions.sort_unstable();
+        versions
+    }
+
+    /// 读取章节缓存
+    pub async fn get(&self, book_url: &str, index: i32) -> Option<String> {
+        self.storage
+            .read_cache(&Self::chapter_key(book_url, index))
+            .await
+            .ok()
+    }
+
+    /// 章节是否已缓存 (不读取内容)
+    pub async fn contains(&self, book_url: &str, index: i32) -> bool {
+        let path = self.storage.cache_path(&Self::chapter_key(book_url, index));
+        fs::try_exists(path).await.unwrap_or(false)
+    }
+
+    /// 写入章节缓存，已缓存的内容不同时先保留为旧版本
+    pub async fn put(&self, book_url: &str, index: i32, content: &str) -> Result<()> {
+        if self.max_versions > 0 {
+            if let Some(previous) = self.get(book_url, index).await.filter(|p| p != content) {
+                self.archive(book_url, index, &previous).await?;
+            }
+        }
+        self.storage
+            .write_cache(&Self::chapter_key(book_url, index), content)
+            .await
+    }
+
+    /// 将当前正文保存为新的旧版本 (保留原写入时间)，并删除超出数量的最旧版本
+    async fn archive(&self, book_url: &str, index: i32, previous: &str) -> Result<()> {
+        let mut versions = self.archived_versions(book_url, index).await;
+        let next = versions.last().map_or(1, |v| v + 1);
+        let current = self.storage.cache_path(&Self::chapter_key(book_url, index));
+        let archived = self.storage.cache_path(&Self::version_key(book_url, index, next));
+        if fs::rename(&current, &archived).await.is_err() {
+            self.storage
+                .write_cache(&Self::version_key(book_url, index, next), previous)
+                .await?;
+        }
+        versions.push(next);
+
+        let excess = versions.len().saturating_sub(self.max_versions);
+        for version in &versions[..excess] {
+            self.storage
+                .remove_cache(&Self::version_key(book_url, index, *version))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 章节的全部版本，从旧到新，最后一个为当前缓存的正文；章节未缓存时为空
+    pub async fn versions(&self, book_url: &str, index: i32) -> Vec<ChapterVersion> {
+        let archived = self.archived_versions(book_url, index).await;
+        let current_version = archived.last().map_or(1, |v| v + 1);
+        let keys = archived
+            .iter()
+            .map(|v| (*v, Self::version_key(book_url, index, *v)))
+            .chain(std::iter::once((current_version, Self::chapter_key(book_url, index))));
+
+        let mut versions = Vec::new();
+        for (version, key) in keys {
+            let path = self.storage.cache_path(&key);
+            let (Ok(meta), Ok(content)) = (fs::metadata(&path).await, fs::read_to_string(&path).await) else {
+                continue;
+            };
+            let cached_at = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as i64);
+            versions.push(ChapterVersion {
+                version,
+                current: version == current_version,
+                cached_at,
+                length: content.chars().count(),
+            });
+        }
+        versions
+    }
+
+    /// 读取章节的某个版本 (当前正文或旧版本)
+    pub async fn get_version(&self, book_url: &str, index: i32, version: u32) -> Option<String> {
+        let current_version = self.archived_versions(book_url, index).await.last().map_or(1, |v| v + 1);
+        let key = match version == current_version {
+            true => Self::chapter_key(book_url, index),
+            false => Self::version_key(book_url, index, version),
+        };
+        self.storage.read_cache(&key).await.ok()
+    }
+
+    /// 优先读取缓存，未命中或 refresh 时调用 fetch 并回写非空结果
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        book_url: &str,
+        index: i32,
+        refresh: bool,
+        fetch: F,
+    ) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        if !refresh {
+            if let Some(content) = self.get(book_url, index).await {
+                return Ok(content);
+            }
+        }
+
+        let content = fetch().await?;
+        if !content.is_empty() {
+            if let Err(e) = self.put(book_url, index, &content).await {
+                tracing::warn!("Failed to cache chapter {} of {}: {}", index, book_url, e);
+            }
+        }
+        Ok(content)
+    }
+
+    /// 清除 from_index 及之后的章节缓存及其旧版本 (目录发生偏移时使用)
+    pub async fn invalidate_from(&self, book_url: &str, from_index: i32) -> Result<()> {
+        let dir = self.storage.cache_path(&Self::book_dir(book_url));
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let index = name.to_str().and_then(Self::parse_file_name).map(|(index, _)| index);
+            if matches!(index, Some(i) if i >= from_index) {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 清除整本书的正文缓存
+    pub async fn clear_book(&self, book_url: &str) -> Result<ReclaimedCache> {
+        self.storage.remove_cache(&Self::book_dir(book_url)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn create_test_cache(name: &str) -> ContentCache {
+        let path = format!("/tmp/reader_tests_content_cache_{}", name);
+        let _ = std::fs::remove_dir_all(&path);
+        ContentCache::new(FileStorage::new(path))
+    }
+
+    async fn fetch_counting(cache: &ContentCache, hits: &AtomicUsize, refresh: bool) -> String {
+        cache
+            .get_or_fetch("https://example.com/book/1", 3, refresh, || async {
+                let n = hits.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(format!("content #{}", n))
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_second_fetch_served_from_cache() {
+        let cache = create_test_cache("hit");
+        let hits = AtomicUsize::new(0);
+
+        assert_eq!(fetch_counting(&cache, &hits, false).await, "content #1");
+        assert_eq!(fetch_counting(&cache, &hits, false).await, "content #1");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_bypasses_cache() {
+        let cache = create_test_cache("refresh");
+        let hits = AtomicUsize::new(0);
+
+        fetch_counting(&cache, &hits, false).await;
+        assert_eq!(fetch_counting(&cache, &hits, true).await, "content #2");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        // 刷新结果会回写缓存
+        assert_eq!(fetch_counting(&cache, &hits, false).await, "content #2");
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_from_and_clear() {
+        let cache = create_test_cache("invalidate");
+        let url = "https://example.com/book/2";
+        for i in 0..4 {
+            cache.put(url, i, &format!("c{}", i)).await.unwrap();
+        }
+
+        cache.invalidate_from(url, 2).await.unwrap();
+        assert_eq!(cache.get(url, 1).await.as_deref(), Some("c1"));
+        assert!(cache.get(url, 2).await.is_none());
+        assert!(cache.get(url, 3).await.is_none());
+
+        let reclaimed = cache.clear_book(url).await.unwrap();
+        assert_eq!(reclaimed, ReclaimedCache { files: 2, bytes: 4 });
+        assert!(cache.get(url, 0).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_changed_content_keeps_versions() {
+        let cache = create_test_cache("versions").with_max_versions(2);
+        let url = "https://example.com/book/3";
+        cache.put(url, 0, "first").await.unwrap();
+        cache.put(url, 0, "first").await.unwrap();
+        assert_eq!(cache.versions(url, 0).await.len(), 1);
+
+        cache.put(url, 0, "second").await.unwrap();
+        cache.put(url, 0, "third!").await.unwrap();
+        cache.put(url, 0, "fourth").await.unwrap();
+        let versions = cache.versions(url, 0).await;
+        let numbers: Vec<(u32, bool, usize)> = versions.iter().map(|v| (v.version, v.current, v.length)).collect();
+        // 只保留最近两个旧版本
+        assert_eq!(numbers, vec![(2, false, 6), (3, false, 6), (4, true, 6)]);
+        assert!(versions.windows(2).all(|w| w[0].cached_at <= w[1].cached_at));
+        assert_eq!(cache.get_version(url, 0, 2).await.as_deref(), Some("second"));
+        assert_eq!(cache.get_version(url, 0, 4).await.as_deref(), Some("fourth"));
+        assert!(cache.get_version(url, 0, 1).await.is_none());
+        assert!(cache.versions(url, 1).await.is_empty());
+
+        // 旧版本随章节一同失效，并计入清理的回收量
+        cache.put(url, 1, "other").await.unwrap();
+        cache.put(url, 1, "other2").await.unwrap();
+        cache.invalidate_from(url, 1).await.unwrap();
+        assert!(cache.versions(url, 1).await.is_empty());
+        let reclaimed = cache.clear_book(url).await.unwrap();
+        assert_eq!(reclaimed, ReclaimedCache { files: 3, bytes: 18 });
+    }
+}
@@ -0,0 +1,77 @@
+use super::{FileStorage, ReclaimedCache};
+use anyhow::Result;
+
+/// 缓存的封面图片
+#[derive(Debug, Clone)]
+pub struct CachedCover {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// 上传的封面地址前缀，后接图片内容的哈希
+pub const LOCAL_COVER_PREFIX: &str = "reader3://cover/";
+
+/// 是否为上传到本地的封面地址
+pub fn is_local_cover(url: &str) -> bool {
+    url.strip_prefix(LOCAL_COVER_PREFIX)
+        .is_some_and(|hash| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// 封面图片缓存
+///
+/// 图片按 `cache/covers/{urlHash}` 存放，Content-Type 记录在同名 `.type` 文件中。
+/// 漫画图片代理以 [`CoverCache::with_dir`] 复用同样的结构，存放在另一个目录下。
+/// 上传的封面以 [`LOCAL_COVER_PREFIX`] 开头的地址存放，读取时不访问网络。
+#[derive(Clone)]
+pub struct CoverCache {
+    storage: FileStorage,
+    dir: &'static str,
+}
+
+impl CoverCache {
+    pub fn new(storage: FileStorage) -> Self {
+        Self::with_dir(storage, "covers")
+    }
+
+    /// 图片存放在 `cache/{dir}` 下的缓存
+    pub fn with_dir(storage: FileStorage, dir: &'static str) -> Self {
+        Self { storage, dir }
+    }
+
+    fn cover_key(&self, url: &str) -> String {
+        format!("{}/{:x}", self.dir, md5::compute(url))
+    }
+
+    /// 读取封面缓存
+    pub async fn get(&self, url: &str) -> Option<CachedCover> {
+        let key = self.cover_key(url);
+        let content_type = self.storage.read_cache(&format!("{}.type", key)).await.ok()?;
+        let data = self.storage.read_cache_bytes(&key).await.ok()?;
+        Some(CachedCover { content_type, data })
+    }
+
+    /// 删除封面缓存
+    pub async fn remove(&self, url: &str) -> Result<ReclaimedCache> {
+        let key = self.cover_key(url);
+        let mut reclaimed = self.storage.remove_cache(&format!("{}.type", key)).await?;
+        reclaimed += self.storage.remove_cache(&key).await?;
+        Ok(reclaimed)
+    }
+
+    /// 保存上传的封面，返回其本地地址 (相同图片得到相同地址)
+    pub async fn put_local(&self, cover: &CachedCover) -> Result<String> {
+        let url = format!("{}{:x}", LOCAL_COVER_PREFIX, md5::compute(&cover.data));
+        self.put(&url, cover).await?;
+        Ok(url)
+    }
+
+    /// 写入封面缓存
+    pub async fn put(&self, url: &str, cover: &CachedCover) -> Result<()> {
+        let key = self.cover_key(url);
+        self.storage.write_cache_bytes(&key, &cover.data).await?;
+        // 类型文件最后写入，读取时以它的存在作为缓存完整的标志
+        self.storage
+            .write_cache(&format!("{}.type", key), &cover.content_type)
+            .await
+    }
+}
@@ -0,0 +1,353 @@
+use super::FileStorage;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KvData {
+    // source_url -> key -> value
+    pub source_vars: HashMap<String, HashMap<String, String>>,
+    // key -> (value, expires_at in epoch millis, 0 = never)
+    pub cache: HashMap<String, (String, i64)>,
+}
+
+/// 书源变量 (`source.getVariable()` / `setVariable(v)`，由 variableComment 提示用户填写) 的键
+pub const SOURCE_VARIABLE_KEY: &str = "sourceVariable";
+
+/// 后台写盘前等待的时间，合并这段时间内的全部修改
+const FLUSH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 源变量与缓存存储
+///
+/// 读写都在内存中同步完成 (可在阻塞的 JS 桥接中调用)；修改只标记脏数据，
+/// 由每个存储唯一的后台任务合并后原子写盘。退出前需调用 `flush`。
+#[derive(Clone)]
+pub struct KvStore {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    data: RwLock<KvData>,
+    file_storage: FileStorage,
+    filename: String,
+    /// 有尚未落盘的修改
+    dirty: AtomicBool,
+    /// 唤醒后台写盘任务
+    notify: Arc<Notify>,
+    flusher_started: AtomicBool,
+    flusher: parking_lot::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// 串行化整文件写入
+    save_lock: tokio::sync::Mutex<()>,
+    /// 实际写盘次数
+    disk_writes: AtomicU64,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // 让后台任务醒来并发现存储已释放
+        self.notify.notify_one();
+    }
+}
+
+impl KvStore {
+    pub fn new(file_storage: FileStorage, filename: &str) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                data: RwLock::new(KvData::default()),
+                file_storage,
+                filename: filename.to_string(),
+                dirty: AtomicBool::new(false),
+                notify: Arc::new(Notify::new()),
+                flusher_started: AtomicBool::new(false),
+                flusher: parking_lot::Mutex::new(None),
+                save_lock: tokio::sync::Mutex::new(()),
+                disk_writes: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub async fn load(&self) -> anyhow::Result<()> {
+        let data = self
+            .inner
+            .file_storage
+            .read_json_or_default::<KvData>(&self.inner.filename)
+            .await;
+        *self.inner.data.write() = data;
+        Ok(())
+    }
+
+    /// 立即写盘
+    pub async fn save(&self) -> anyhow::Result<()> {
+        self.inner.dirty.store(true, Ordering::SeqCst);
+        self.flush().await
+    }
+
+    /// 有未落盘的修改时立即写盘 (用于退出前)
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let _guard = self.inner.save_lock.lock().await;
+        if !self.inner.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let data = self.inner.data.read().clone();
+        if let Err(e) = self.inner.file_storage.write_json(&self.inner.filename, &data).await {
+            self.inner.dirty.store(true, Ordering::SeqCst);
+            return Err(e);
+        }
+        self.inner.disk_writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 标记脏数据并唤醒后台写盘任务
+    fn mark_dirty(&self) {
+        self.inner.dirty.store(true, Ordering::SeqCst);
+        self.ensure_flusher();
+        self.inner.notify.notify_one();
+    }
+
+    /// 首次修改时在当前 tokio 运行时中启动后台写盘任务；没有运行时则只保留在内存中
+    fn ensure_flusher(&self) {
+        if self.inner.flusher_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            self.inner.flusher_started.store(false, Ordering::SeqCst);
+            return;
+        };
+        let flusher = handle.spawn(self.flush_loop());
+        *self.inner.flusher.lock() = Some(flusher);
+    }
+
+    /// 交出后台写盘任务，由调用方 (任务调度器) 运行；已在运行时返回 None
+    pub fn take_flusher(&self) -> Option<impl std::future::Future<Output = ()> + Send + 'static> {
+        if self.inner.flusher_started.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(self.flush_loop())
+    }
+
+    /// 被唤醒后等待防抖间隔再写盘，存储释放后结束
+    fn flush_loop(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let store = Arc::downgrade(&self.inner);
+        let notify = self.inner.notify.clone();
+        async move {
+            loop {
+                notify.notified().await;
+                tokio::time::sleep(FLUSH_DEBOUNCE).await;
+                let Some(inner) = store.upgrade() else {
+                    return;
+                };
+                let store = KvStore { inner };
+                if let Err(e) = store.flush().await {
+                    tracing::warn!("Failed to save KV store: {}", e);
+                }
+            }
+        }
+    }
+
+    /// 停止后台写盘任务 (退出时在 `flush` 之后调用)；之后的修改会重新启动它
+    pub fn stop_flusher(&self) {
+        if let Some(flusher) = self.inner.flusher.lock().take() {
+            flusher.abort();
+        }
+        self.inner.flusher_started.store(false, Ordering::SeqCst);
+    }
+
+    /// 与该存储位于同一存储目录下的缓存路径
+    pub fn cache_path(&self, filename: &str) -> std::path::PathBuf {
+        self.inner.file_storage.cache_path(filename)
+    }
+
+    // Source Variable Methods
+    pub fn get_source_var(&self, source_url: &str, key: &str) -> Option<String> {
+        self.inner
+            .data
+            .read()
+            .source_vars
+            .get(source_url)
+            .and_then(|vars| vars.get(key).cloned())
+    }
+
+    pub fn set_source_var(&self, source_url: &str, key: &str, value: &str) {
+        self.inner
+            .data
+            .write()
+            .source_vars
+            .entry(source_url.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+        self.mark_dirty();
+    }
+
+    // Cache Methods
+    pub fn get_cache(&self, key: &str) -> Option<String> {
+        self.get_cache_at(key, now_millis())
+    }
+
+    fn get_cache_at(&self, key: &str, now: i64) -> Option<String> {
+        match self.inner.data.read().cache.get(key) {
+            Some((_, expires_at)) if is_expired(*expires_at, now) => {}
+            Some((value, _)) => return Some(value.clone()),
+            None => return None,
+        }
+        // Lazily drop the expired entry, unless it was refreshed meanwhile
+        let mut guard = self.inner.data.write();
+        if guard.cache.get(key).is_some_and(|(_, expires_at)| is_expired(*expires_at, now)) {
+            guard.cache.remove(key);
+        }
+        None
+    }
+
+    /// Store a value that expires at `expires_at` (epoch millis, 0 = never)
+    pub fn set_cache(&self, key: &str, value: &str, expires_at: i64) {
+        self.inner
+            .data
+            .write()
+            .cache
+            .insert(key.to_string(), (value.to_string(), expires_at));
+        self.mark_dirty();
+    }
+
+    /// Store a value for `save_time` seconds, Legado's `java.put(key, value, saveTime)`;
+    /// `save_time <= 0` keeps it forever
+    pub fn set_cache_ttl(&self, key: &str, value: &str, save_time: i64) {
+        let expires_at = if save_time > 0 {
+            now_millis().saturating_add(save_time.saturating_mul(1000))
+        } else {
+            0
+        };
+        self.set_cache(key, value, expires_at);
+    }
+
+    pub fn remove_cache(&self, key: &str) {
+        if self.inner.data.write().cache.remove(key).is_some() {
+            self.mark_dirty();
+        }
+    }
+
+    /// Drop all expired cache entries, returning how many were removed
+    pub fn purge_expired(&self) -> usize {
+        self.purge_expired_at(now_millis())
+    }
+
+    fn purge_expired_at(&self, now: i64) -> usize {
+        let purged = {
+            let mut guard = self.inner.data.write();
+            let before = guard.cache.len();
+            guard.cache.retain(|_, (_, expires_at)| !is_expired(*expires_at, now));
+            before - guard.cache.len()
+        };
+        if purged > 0 {
+            self.mark_dirty();
+        }
+        purged
+    }
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// An entry is expired from its expiry instant onwards
+fn is_expired(expires_at: i64, now: i64) -> bool {
+    expires_at > 0 && now >= expires_at
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_store(name: &str) -> KvStore {
+        let dir = format!("/tmp/reader_tests_kv_{}_{}", name, std::process::id());
+        let _ = std::fs::remove_dir_all(&dir);
+        KvStore::new(FileStorage::new(dir), "kv_store.json")
+    }
+
+    #[test]
+    fn test_cache_expiry_boundary() {
+        let store = test_store("boundary");
+        store.set_cache("token", "abc", 10_000);
+        store.set_cache("forever", "x", 0);
+
+        assert_eq!(store.get_cache_at("token", 9_999).as_deref(), Some("abc"));
+        assert_eq!(store.get_cache_at("forever", i64::MAX).as_deref(), Some("x"));
+        // Expired exactly at the expiry instant, and purged on read
+        assert_eq!(store.get_cache_at("token", 10_000), None);
+        assert!(!store.inner.data.read().cache.contains_key("token"));
+    }
+
+    #[test]
+    fn test_cache_ttl_and_purge() {
+        let store = test_store("purge");
+        store.set_cache_ttl("short", "1", 1);
+        store.set_cache_ttl("long", "2", 3600);
+        store.set_cache_ttl("none", "3", 0);
+        assert_eq!(store.get_cache("short").as_deref(), Some("1"));
+
+        let in_two_seconds = now_millis() + 2_000;
+        assert_eq!(store.purge_expired_at(in_two_seconds), 1);
+        assert_eq!(store.get_cache("short"), None);
+        assert_eq!(store.get_cache("long").as_deref(), Some("2"));
+        assert_eq!(store.purge_expired_at(i64::MAX), 1);
+        assert_eq!(store.get_cache("none").as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_expiry_survives_save_load() {
+        let store = test_store("persist");
+        store.set_cache_ttl("session", "s1", 3600);
+        store.set_cache("stale", "old", 1);
+        store.save().await.unwrap();
+
+        let reloaded = KvStore::new(store.inner.file_storage.clone(), "kv_store.json");
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.get_cache("session").as_deref(), Some("s1"));
+        assert_eq!(reloaded.get_cache("stale"), None);
+
+        let expires_at = reloaded.inner.data.read().cache["session"].1;
+        let original = store.inner.data.read().cache["session"].1;
+        assert_eq!(expires_at, original);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_writes_are_coalesced() {
+        let store = test_store("concurrent");
+        let writers: Vec<_> = (0..100)
+            .map(|task| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    for i in 0..10 {
+                        store.set_cache(&format!("k{}_{}", task, i), &i.to_string(), 0);
+                        store.set_source_var("https://a.example", &format!("v{}", task), "1");
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        store.flush().await.unwrap();
+
+        let reloaded = KvStore::new(store.inner.file_storage.clone(), "kv_store.json");
+        reloaded.load().await.unwrap();
+        for task in 0..100 {
+            for i in 0..10 {
+                let key = format!("k{}_{}", task, i);
+                assert_eq!(reloaded.get_cache(&key), Some(i.to_string()), "{}", key);
+            }
+            let var = format!("v{}", task);
+            assert_eq!(reloaded.get_source_var("https://a.example", &var).as_deref(), Some("1"));
+        }
+        // 2000 sets, written by a handful of coalesced saves
+        let writes = store.inner.disk_writes.load(Ordering::Relaxed);
+        assert!((1..=10).contains(&writes), "{} disk writes", writes);
+
+        // Nothing left to write
+        store.flush().await.unwrap();
+        assert_eq!(store.inner.disk_writes.load(Ordering::Relaxed), writes);
+    }
+}
@@ -0,0 +1,438 @@
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs;
+pub mod audio_cache;
+pub mod bookshelf;
+pub mod content_cache;
+pub mod cover_cache;
+pub mod kv;
+pub mod sandbox;
+
+/// 防抖写入的默认间隔
+const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// 备份文件后缀，保存上一次成功写入的内容
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// 临时文件序号，避免并发写入同一文件时冲突
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 删除缓存回收的文件数与字节数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ReclaimedCache {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl std::ops::AddAssign for ReclaimedCache {
+    fn add_assign(&mut self, other: Self) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(Clone)]
+pub struct FileStorage {
+    base_path: PathBuf,
+    /// 写入后是否 fsync 文件与所在目录
+    sync_writes: bool,
+    debounce: Duration,
+    /// 尚未落盘的防抖写入 (文件名 -> 序列化内容)，克隆之间共享
+    pending: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl FileStorage {
+    pub fn new(base_path: impl AsRef<Path>) -> Self {
+        Self {
+            base_path: base_path.as_ref().to_path_buf(),
+            sync_writes: true,
+            debounce: DEFAULT_DEBOUNCE,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 设置写入后是否 fsync
+    pub fn with_sync(mut self, sync_writes: bool) -> Self {
+        self.sync_writes = sync_writes;
+        self
+    }
+
+    /// 设置防抖写入的间隔
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// 获取数据目录路径
+    fn data_path(&self, filename: &str) -> PathBuf {
+        self.base_path.join("data").join(filename)
+    }
+
+    /// 获取数据目录下文件的完整路径 (与 read_file/write_file 同一目录)
+    pub fn file_path(&self, filename: &str) -> PathBuf {
+        self.data_path(filename)
+    }
+
+    /// 读取 JSON 文件
+    ///
+    /// 有未落盘的防抖写入时返回其内容；文件损坏时尝试从 `.bak` 恢复。
+    pub async fn read_json<T: DeserializeOwned>(&self, filename: &str) -> Result<T> {
+        if let Some(pending) = self.pending_content(filename) {
+            return Ok(serde_json::from_slice(&pending)?);
+        }
+
+        let path = self.data_path(filename);
+        let content = fs::read(&path).await?;
+        match serde_json::from_slice(&content) {
+            Ok(data) => Ok(data),
+            Err(e) => self.recover_json(filename, e).await,
+        }
+    }
+
+    /// 从备份恢复损坏的 JSON 文件，并用备份覆盖主文件
+    async fn recover_json<T: DeserializeOwned>(&self, filename: &str, error: serde_json::Error) -> Result<T> {
+        let path = self.data_path(filename);
+        let backup = fs::read(backup_path(&path)).await.ok();
+        let Some(data) = backup.as_deref().and_then(|b| serde_json::from_slice(b).ok()) else {
+            tracing::error!("{} is corrupted and no valid backup exists: {}", path.display(), error);
+            return Err(error.into());
+        };
+
+        tracing::error!(
+            "{} is corrupted ({}), recovered from {}{}",
+            path.display(),
+            error,
+            filename,
+            BACKUP_SUFFIX
+        );
+        if let Some(backup) = backup {
+            if let Err(e) = self.write_atomic(&path, &backup).await {
+                tracing::error!("Failed to restore {} from backup: {}", path.display(), e);
+            }
+        }
+        Ok(data)
+    }
+
+    /// 读取 JSON 文件，不存在则返回默认值
+    pub async fn read_json_or_default<T: DeserializeOwned + Default>(&self, filename: &str) -> T {
+        self.read_json(filename).await.unwrap_or_default()
+    }
+
+    /// 写入 JSON 文件 (原子替换，并保留一份 `.bak`)
+    pub async fn write_json<T: Serialize>(&self, filename: &str, data: &T) -> Result<()> {
+        let content = serde_json::to_vec_pretty(data)?;
+        // 立即写入的内容比待写入的防抖内容更新
+        self.take_pending(filename);
+        self.write_json_bytes(filename, &content).await
+    }
+
+    async fn write_json_bytes(&self, filename: &str, content: &[u8]) -> Result<()> {
+        let path = self.data_path(filename);
+        self.write_atomic(&path, content).await?;
+        self.write_atomic(&backup_path(&path), content).await
+    }
+
+    /// 防抖写入 JSON：合并频繁的更新 (如阅读进度)，最多每个间隔落盘一次
+    ///
+    /// 需在 tokio 运行时中调用；退出前应调用 `flush` 写入剩余内容。
+    pub async fn write_json_debounced<T: Serialize>(&self, filename: &str, data: &T) -> Result<()> {
+        let content = serde_json::to_vec_pretty(data)?;
+        let scheduled = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.insert(filename.to_string(), content).is_some()
+        };
+        if !scheduled {
+            let storage = self.clone();
+            let filename = filename.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(storage.debounce).await;
+                if let Err(e) = storage.flush_file(&filename).await {
+                    tracing::error!("Failed to flush {}: {}", filename, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// 立即写入指定文件的防抖内容
+    pub async fn flush_file(&self, filename: &str) -> Result<()> {
+        match self.take_pending(filename) {
+            Some(content) => self.write_json_bytes(filename, &content).await,
+            None => Ok(()),
+        }
+    }
+
+    /// 立即写入全部防抖内容 (用于退出前)
+    pub async fn flush(&self) -> Result<()> {
+        let filenames: Vec<String> = self.pending.lock().unwrap().keys().cloned().collect();
+        for filename in filenames {
+            self.flush_file(&filename).await?;
+        }
+        Ok(())
+    }
+
+    fn pending_content(&self, filename: &str) -> Option<Vec<u8>> {
+        self.pending.lock().unwrap().get(filename).cloned()
+    }
+
+    fn take_pending(&self, filename: &str) -> Option<Vec<u8>> {
+        self.pending.lock().unwrap().remove(filename)
+    }
+
+    /// 先写同目录下的临时文件再 rename，崩溃时不会留下半个文件
+    async fn write_atomic(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(parent).await?;
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("data");
+        let tmp_path = parent.join(format!(
+            ".{}.tmp{}-{}",
+            file_name,
+            std::process::id(),
+            TMP_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let written = async {
+            let mut file = fs::File::create(&tmp_path).await?;
+            tokio::io::AsyncWriteExt::write_all(&mut file, content).await?;
+            if self.sync_writes {
+                file.sync_all().await?;
+            }
+            drop(file);
+            fs::rename(&tmp_path, path).await
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(e.into());
+        }
+
+        if self.sync_writes {
+            // 目录 fsync 确保 rename 本身落盘；部分平台不支持打开目录，忽略失败
+            if let Ok(dir) = fs::File::open(parent).await {
+                let _ = dir.sync_all().await;
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查文件是否存在
+    pub async fn exists(&self, filename: &str) -> bool {
+        let path = self.data_path(filename);
+        fs::try_exists(&path).await.unwrap_or(false)
+    }
+
+    /// 删除文件，连同 `.bak` 与尚未落盘的防抖写入；文件不存在时不报错
+    pub async fn delete(&self, filename: &str) -> Result<()> {
+        self.take_pending(filename);
+        let path = self.data_path(filename);
+        for path in [backup_path(&path), path] {
+            match fs::remove_file(&path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取缓存目录
+    pub fn cache_path(&self, filename: &str) -> PathBuf {
+        self.base_path.join("cache").join(filename)
+    }
+
+    /// 读取缓存
+    pub async fn read_cache(&self, filename: &str) -> Result<String> {
+        let path = self.cache_path(filename);
+        let content = fs::read_to_string(&path).await?;
+        Ok(content)
+    }
+
+    /// 缓存文件距上次写入的时长，文件不存在时返回 None
+    pub async fn cache_age(&self, filename: &str) -> Option<std::time::Duration> {
+        let modified = fs::metadata(self.cache_path(filename)).await.ok()?.modified().ok()?;
+        Some(modified.elapsed().unwrap_or_default())
+    }
+
+    /// 写入缓存
+    pub async fn write_cache(&self, filename: &str, content: &str) -> Result<()> {
+        let path = self.cache_path(filename);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, content).await?;
+        Ok(())
+    }
+
+    /// 读取二进制缓存
+    pub async fn read_cache_bytes(&self, filename: &str) -> Result<Vec<u8>> {
+        let path = self.cache_path(filename);
+        Ok(fs::read(&path).await?)
+    }
+
+    /// 写入二进制缓存
+    pub async fn write_cache_bytes(&self, filename: &str, data: &[u8]) -> Result<()> {
+        let path = self.cache_path(filename);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, data).await?;
+        Ok(())
+    }
+
+    /// 删除缓存
+    pub async fn delete_cache(&self, filename: &str) -> Result<()> {
+        let path = self.cache_path(filename);
+        fs::remove_file(path).await?;
+        Ok(())
+    }
+
+    /// 删除缓存文件或整个缓存目录，返回回收量；不存在时回收量为 0
+    pub async fn remove_cache(&self, filename: &str) -> Result<ReclaimedCache> {
+        let path = self.cache_path(filename);
+        let Ok(meta) = fs::metadata(&path).await else {
+            return Ok(ReclaimedCache::default());
+        };
+        if !meta.is_dir() {
+            fs::remove_file(&path).await?;
+            return Ok(ReclaimedCache {
+                files: 1,
+                bytes: meta.len(),
+            });
+        }
+
+        let mut reclaimed = ReclaimedCache::default();
+        let mut dirs = vec![path.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let meta = entry.metadata().await?;
+                if meta.is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    reclaimed.files += 1;
+                    reclaimed.bytes += meta.len();
+                }
+            }
+        }
+        fs::remove_dir_all(&path).await?;
+        Ok(reclaimed)
+    }
+
+    /// 读取数据目录下的任意文件，路径越出数据目录时返回 [`sandbox::SandboxError`]
+    pub async fn read_file(&self, filename: &str) -> Result<String> {
+        let path = sandbox::resolve(&self.data_path(""), filename)?;
+        let content = fs::read_to_string(path).await?;
+        Ok(content)
+    }
+
+    /// 读取任意文件，不存在则返回空字符串
+    pub async fn read_file_or_default(&self, filename: &str) -> String {
+        self.read_file(filename).await.unwrap_or_default()
+    }
+
+    /// 写入数据目录下的任意文件 (原子替换)，路径限制同 [`Self::read_file`]
+    pub async fn write_file(&self, filename: &str, content: &str) -> Result<()> {
+        let path = sandbox::resolve(&self.data_path(""), filename)?;
+        self.write_atomic(&path, content.as_bytes()).await
+    }
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(BACKUP_SUFFIX);
+    path.with_file_name(name)
+}
+
+impl Default for FileStorage {
+    fn default() -> Self {
+        // 默认使用当前目录下的 storage
+        Self::new("./storage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_storage(name: &str) -> FileStorage {
+        let dir = std::env::temp_dir().join(format!("reader_tests_storage_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        FileStorage::new(dir)
+    }
+
+    fn data_dir_entries(storage: &FileStorage) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(storage.file_path(""))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_atomic_write_leaves_no_temp_files() {
+        let storage = temp_storage("atomic");
+        for i in 0..5 {
+            storage.write_json("bookshelf.json", &vec![i]).await.unwrap();
+        }
+        storage.write_file("notes.txt", "hello").await.unwrap();
+
+        assert_eq!(
+            data_dir_entries(&storage),
+            vec!["bookshelf.json", "bookshelf.json.bak", "notes.txt"]
+        );
+        let shelf: Vec<i32> = storage.read_json("bookshelf.json").await.unwrap();
+        assert_eq!(shelf, vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_recover_corrupt_file_from_backup() {
+        let storage = temp_storage("recover").with_sync(false);
+        storage.write_json("bookshelf.json", &vec!["a", "b"]).await.unwrap();
+
+        // 模拟写入中途断电导致的截断
+        std::fs::write(storage.file_path("bookshelf.json"), "[\"a\", \"b").unwrap();
+        let shelf: Vec<String> = storage.read_json("bookshelf.json").await.unwrap();
+        assert_eq!(shelf, vec!["a", "b"]);
+        // 主文件已用备份修复
+        let repaired = std::fs::read_to_string(storage.file_path("bookshelf.json")).unwrap();
+        assert!(serde_json::from_str::<Vec<String>>(&repaired).is_ok());
+
+        // 备份也损坏时返回错误
+        std::fs::write(storage.file_path("bookshelf.json"), "{").unwrap();
+        std::fs::write(storage.file_path("bookshelf.json.bak"), "{").unwrap();
+        assert!(storage.read_json::<Vec<String>>("bookshelf.json").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_debounced_writes_are_batched() {
+        let storage = temp_storage("debounce")
+            .with_sync(false)
+            .with_debounce(Duration::from_millis(100));
+        for i in 0..10 {
+            storage.write_json_debounced("progress.json", &i).await.unwrap();
+        }
+        // 尚未落盘，但读取能看到最新内容
+        assert!(!storage.file_path("progress.json").exists());
+        assert_eq!(storage.read_json::<i32>("progress.json").await.unwrap(), 9);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let on_disk = std::fs::read_to_string(storage.file_path("progress.json")).unwrap();
+        assert_eq!(on_disk, "9");
+
+        // flush 立即写入
+        storage.write_json_debounced("progress.json", &10).await.unwrap();
+        storage.flush().await.unwrap();
+        let on_disk = std::fs::read_to_string(storage.file_path("progress.json")).unwrap();
+        assert_eq!(on_disk, "10");
+    }
+}
@@ -0,0 +1,176 @@
+//! 文件沙箱：客户端与书源脚本给出的路径都是不可信输入，只允许访问根目录内的文件
+
+use std::path::{Component, Path, PathBuf};
+
+/// 保存文件的默认大小上限
+const DEFAULT_SAVE_MAX_BYTES: u64 = 1024 * 1024;
+/// 默认允许保存的扩展名
+const DEFAULT_SAVE_EXTENSIONS: &str = "json,txt";
+
+/// 沙箱拒绝的文件操作
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("Path escapes the storage root: {0}")]
+    PathEscape(String),
+    #[error("File type not allowed: {0}")]
+    TypeNotAllowed(String),
+    #[error("File too large: {size} bytes exceeds the {max_bytes} byte limit")]
+    TooLarge { size: u64, max_bytes: u64 },
+}
+
+/// 解析 `root` 下的路径
+///
+/// 接受相对路径与位于 `root` 内的绝对路径，拒绝 `..` 与其他绝对路径；
+/// 路径中已存在的部分解析符号链接后仍须位于 `root` 内。
+pub fn resolve(root: &Path, path: &str) -> Result<PathBuf, SandboxError> {
+    let escape = || SandboxError::PathEscape(path.to_string());
+    let requested = Path::new(path);
+    let relative = if requested.is_absolute() {
+        requested.strip_prefix(root).map_err(|_| escape())?
+    } else {
+        requested
+    };
+
+    let mut resolved = root.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return Err(escape()),
+        }
+    }
+
+    // 根目录尚不存在时其中也不会有符号链接
+    let Ok(canonical_root) = root.canonicalize() else {
+        return Ok(resolved);
+    };
+    let existing = resolved
+        .ancestors()
+        .find(|p| p.symlink_metadata().is_ok())
+        .unwrap_or(root);
+    // 悬空的符号链接无法解析，同样拒绝
+    match existing.canonicalize() {
+        Ok(target) if target.starts_with(&canonical_root) => Ok(resolved),
+        _ => Err(escape()),
+    }
+}
+
+/// 通过接口保存文件的限制
+#[derive(Debug, Clone)]
+pub struct FileSaveLimits {
+    /// 内容大小上限 (字节)
+    pub max_bytes: u64,
+    /// 允许的扩展名 (小写，不含点)
+    pub extensions: Vec<String>,
+}
+
+impl Default for FileSaveLimits {
+    fn default() -> Self {
+        Self::from_vars(|_| None)
+    }
+}
+
+impl FileSaveLimits {
+    /// 读取 FILE_SAVE_MAX_BYTES 与 FILE_SAVE_EXTENSIONS (逗号分隔)
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let max_bytes = var("FILE_SAVE_MAX_BYTES")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_SAVE_MAX_BYTES);
+        let extensions = var("FILE_SAVE_EXTENSIONS")
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_SAVE_EXTENSIONS.to_string())
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        Self { max_bytes, extensions }
+    }
+
+    /// 检查文件类型与大小
+    pub fn check(&self, path: &str, size: u64) -> Result<(), SandboxError> {
+        let ext = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !self.extensions.contains(&ext) {
+            return Err(SandboxError::TypeNotAllowed(path.to_string()));
+        }
+        if size > self.max_bytes {
+            return Err(SandboxError::TooLarge {
+                size,
+                max_bytes: self.max_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("reader_tests_sandbox_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_resolve_stays_inside_root() {
+        let root = temp_root();
+        assert_eq!(resolve(&root, "sub/a.txt").unwrap(), root.join("sub/a.txt"));
+        assert_eq!(resolve(&root, "./new/b.txt").unwrap(), root.join("new/b.txt"));
+        let absolute = root.join("sub/a.txt");
+        assert_eq!(resolve(&root, absolute.to_str().unwrap()).unwrap(), absolute);
+
+        for path in ["../etc/passwd", "sub/../../etc/passwd", "/etc/passwd"] {
+            assert!(
+                matches!(resolve(&root, path), Err(SandboxError::PathEscape(_))),
+                "{}",
+                path
+            );
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_escaping_symlink() {
+        let root = temp_root();
+        let outside = temp_root();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(root.join("sub"), root.join("inner")).unwrap();
+
+        assert!(resolve(&root, "link/secret.txt").is_err());
+        assert!(resolve(&root, "link").is_err());
+        assert!(resolve(&root, "inner/a.txt").is_ok());
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn test_save_limits() {
+        let limits = FileSaveLimits::from_vars(|key| match key {
+            "FILE_SAVE_MAX_BYTES" => Some("10".to_string()),
+            "FILE_SAVE_EXTENSIONS" => Some(" JSON, .md ".to_string()),
+            _ => None,
+        });
+        assert_eq!(limits.extensions, vec!["json", "md"]);
+        assert!(limits.check("a/b.JSON", 10).is_ok());
+        assert!(matches!(
+            limits.check("a.json", 11),
+            Err(SandboxError::TooLarge { max_bytes: 10, .. })
+        ));
+        assert!(matches!(limits.check("a.sh", 1), Err(SandboxError::TypeNotAllowed(_))));
+        assert!(limits.check("json", 1).is_err());
+
+        let defaults = FileSaveLimits::default();
+        assert_eq!(defaults.max_bytes, DEFAULT_SAVE_MAX_BYTES);
+        assert!(defaults.check("remoteBookSourceSub.json", 100).is_ok());
+    }
+}